@@ -0,0 +1,264 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use hmac::{Hmac, Mac};
+use sha2::{Digest as _, Sha256};
+
+use crate::categories::Category;
+use crate::encoding::encode_len;
+use crate::leaf::encode_user_leaf;
+use crate::{Digest, Leaf, ParseError, UserLeaf};
+
+type HmacSha256 = Hmac<Sha256>;
+
+const ACCOUNT_HASH_DOMAIN: &[u8] = b"mimi-account-hash-v1";
+const ACCOUNT_HMAC_DOMAIN: &[u8] = b"mimi-account-hmac-v1";
+const EMAIL_HASH_DOMAIN: &[u8] = b"mimi-email-hash-v1";
+
+// Define the AccountKey struct, what identifies an account before it is hashed into a
+// leaf key. Users hold the same values, so they can derive their own key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AccountKey<'a> {
+    pub user_id: &'a str,
+    // `email_hash` of the address on file, when the scheme includes one
+    pub email_hash: Option<[u8; 32]>,
+}
+
+// Trims and lowercases the address first, so "Alice@Example.com " and
+// "alice@example.com" hash alike
+pub fn email_hash(email: &str) -> [u8; 32] {
+    let normalized = email.trim().to_lowercase();
+    let mut hasher = Sha256::new();
+    hasher.update(EMAIL_HASH_DOMAIN);
+    hasher.update(normalized.as_bytes());
+    hasher.finalize().into()
+}
+
+// SHA-256(domain || len || user ID || email flag || [email hash] || epoch || len || pepper).
+// The epoch makes keys unlinkable across epochs; the pepper is the operator's secret,
+// disclosed to each user with their proof.
+pub fn derive_account_hash(key: &AccountKey<'_>, epoch: u64, pepper: &[u8]) -> Digest {
+    let mut hasher = Sha256::new();
+    hasher.update(ACCOUNT_HASH_DOMAIN);
+    hasher.update(encode_len(key.user_id.as_bytes()));
+    hasher.update(key.user_id.as_bytes());
+    match &key.email_hash {
+        Some(email_hash) => {
+            hasher.update([1]);
+            hasher.update(email_hash);
+        }
+        None => hasher.update([0]),
+    }
+    hasher.update(epoch.to_le_bytes());
+    hasher.update(encode_len(pepper));
+    hasher.update(pepper);
+    Digest::from(hasher.finalize())
+}
+
+// Define the HashedUserId struct, a leaf key that only comes out of a `UserIdDerivation`
+// (or parses from one's hex output), so a raw email or account number can't end up as a
+// leaf key by mistake
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HashedUserId(Digest);
+
+impl HashedUserId {
+    pub fn digest(&self) -> &Digest {
+        &self.0
+    }
+}
+
+impl fmt::Display for HashedUserId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+// Only the 64-character hex form is accepted
+impl FromStr for HashedUserId {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(HashedUserId(s.parse()?))
+    }
+}
+
+// Define the UserIdDerivation trait, the approved ways of turning an account key into a
+// leaf key
+pub trait UserIdDerivation {
+    fn derive(&self, key: &AccountKey<'_>, epoch: u64) -> HashedUserId;
+}
+
+// Define the PepperedSha256 struct, `derive_account_hash` with the operator's pepper
+#[derive(Clone)]
+pub struct PepperedSha256 {
+    pepper: Vec<u8>,
+}
+
+impl PepperedSha256 {
+    pub fn new(pepper: impl Into<Vec<u8>>) -> Self {
+        PepperedSha256 {
+            pepper: pepper.into(),
+        }
+    }
+}
+
+impl fmt::Debug for PepperedSha256 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("PepperedSha256(..)")
+    }
+}
+
+impl UserIdDerivation for PepperedSha256 {
+    fn derive(&self, key: &AccountKey<'_>, epoch: u64) -> HashedUserId {
+        HashedUserId(derive_account_hash(key, epoch, &self.pepper))
+    }
+}
+
+// Define the HmacSha256Derivation struct, HMAC-SHA256 keyed with the operator's secret
+// over the same fields as `derive_account_hash`. Unlike a pepper the key never goes to
+// users, so only the operator can derive keys; users are handed theirs with the proof.
+#[derive(Clone)]
+pub struct HmacSha256Derivation {
+    key: Vec<u8>,
+}
+
+impl HmacSha256Derivation {
+    pub fn new(key: impl Into<Vec<u8>>) -> Self {
+        HmacSha256Derivation { key: key.into() }
+    }
+}
+
+impl fmt::Debug for HmacSha256Derivation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("HmacSha256Derivation(..)")
+    }
+}
+
+impl UserIdDerivation for HmacSha256Derivation {
+    fn derive(&self, key: &AccountKey<'_>, epoch: u64) -> HashedUserId {
+        let mut mac = HmacSha256::new_from_slice(&self.key).expect("hmac takes any key length");
+        mac.update(ACCOUNT_HMAC_DOMAIN);
+        mac.update(&encode_len(key.user_id.as_bytes()));
+        mac.update(key.user_id.as_bytes());
+        match &key.email_hash {
+            Some(email_hash) => {
+                mac.update(&[1]);
+                mac.update(email_hash);
+            }
+            None => mac.update(&[0]),
+        }
+        mac.update(&epoch.to_le_bytes());
+        HashedUserId(Digest::from(mac.finalize().into_bytes()))
+    }
+}
+
+// Define the HashedLeaf struct, a record keyed by a derived user ID. It hashes exactly
+// like a `UserLeaf` holding the hex ID, so either can build the same tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HashedLeaf<L: Leaf> {
+    user_id: HashedUserId,
+    // Hex form, kept so `user_id()` can lend it out
+    encoded_id: String,
+    pub record: L,
+}
+
+impl<L: Leaf> HashedLeaf<L> {
+    pub fn new(user_id: HashedUserId, record: L) -> Self {
+        HashedLeaf {
+            encoded_id: user_id.to_string(),
+            user_id,
+            record,
+        }
+    }
+
+    pub fn user_id(&self) -> &HashedUserId {
+        &self.user_id
+    }
+
+    pub fn into_user_leaf(self) -> UserLeaf<L> {
+        UserLeaf {
+            user_id: self.encoded_id,
+            record: self.record,
+        }
+    }
+}
+
+impl<L: Leaf> Leaf for HashedLeaf<L> {
+    fn amount(&self) -> u64 {
+        self.record.amount()
+    }
+
+    fn encode_for_hash(&self) -> Vec<u8> {
+        encode_user_leaf(self.encoded_id.as_bytes(), &self.record.encode_for_hash())
+    }
+
+    fn user_id(&self) -> Option<&[u8]> {
+        Some(self.encoded_id.as_bytes())
+    }
+
+    fn category(&self) -> Category {
+        self.record.category()
+    }
+
+    fn asset(&self) -> Option<&str> {
+        self.record.asset()
+    }
+}
+
+// Tags each record with its derived account hash (hex) as the user ID. Two different
+// account keys hashing alike is an error; the same key twice is left to the builder's
+// `DuplicatePolicy`.
+pub fn hashed_leaves<L: Leaf + Clone>(
+    accounts: &[(AccountKey<'_>, L)],
+    epoch: u64,
+    pepper: &[u8],
+) -> Result<Vec<UserLeaf<L>>, AccountHashError> {
+    let leaves = derived_leaves(accounts, epoch, &PepperedSha256::new(pepper))?;
+    Ok(leaves.into_iter().map(HashedLeaf::into_user_leaf).collect())
+}
+
+// Same as `hashed_leaves` with any approved derivation, keeping the typed key
+pub fn derived_leaves<L: Leaf + Clone>(
+    accounts: &[(AccountKey<'_>, L)],
+    epoch: u64,
+    derivation: &impl UserIdDerivation,
+) -> Result<Vec<HashedLeaf<L>>, AccountHashError> {
+    let mut seen: HashMap<HashedUserId, usize> = HashMap::with_capacity(accounts.len());
+    let mut leaves = Vec::with_capacity(accounts.len());
+    for (index, (key, record)) in accounts.iter().enumerate() {
+        let account_hash = derivation.derive(key, epoch);
+        if let Some(&first) = seen.get(&account_hash) {
+            if accounts[first].0 != *key {
+                return Err(AccountHashError::Collision {
+                    first,
+                    second: index,
+                });
+            }
+        } else {
+            seen.insert(account_hash, index);
+        }
+        leaves.push(HashedLeaf::new(account_hash, record.clone()));
+    }
+    Ok(leaves)
+}
+
+// Define the AccountHashError enum for account keys that can't be told apart once hashed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccountHashError {
+    Collision { first: usize, second: usize },
+}
+
+impl fmt::Display for AccountHashError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AccountHashError::Collision { first, second } => write!(
+                f,
+                "accounts {} and {} derive the same account hash",
+                first, second
+            ),
+        }
+    }
+}
+
+impl std::error::Error for AccountHashError {}
@@ -0,0 +1,456 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use num_bigint::BigUint;
+use sha2::{Digest as _, Sha256};
+
+use crate::ParseError;
+
+const PRIME_DOMAIN: &[u8] = b"mimi-accumulator-prime-v1";
+
+// Moduli shorter than this can be factored by a determined operator
+pub const MIN_MODULUS_BITS: u64 = 2048;
+
+// Miller-Rabin bases; primes are hashed, so an adversary can't aim for a pseudoprime
+const WITNESS_BASES: [u32; 20] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71,
+];
+
+// Define the AccumulatorParams struct, the RSA group an accumulator lives in. Nobody may
+// know the modulus's factors, the operator least of all: with them, witnesses can be
+// forged for any account. Use the output of a setup ceremony that discarded them, or the
+// RSA-2048 challenge number.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccumulatorParams {
+    modulus: BigUint,
+    generator: BigUint,
+}
+
+impl AccumulatorParams {
+    pub fn new(modulus: BigUint) -> Result<Self, AccumulatorError> {
+        if modulus.bits() < MIN_MODULUS_BITS || !modulus.bit(0) {
+            return Err(AccumulatorError::InvalidParams);
+        }
+        let generator = BigUint::from(3u8);
+        // 3 must be a unit, or the non-membership witnesses can't be computed
+        if generator.modinv(&modulus).is_none() {
+            return Err(AccumulatorError::InvalidParams);
+        }
+        Ok(AccumulatorParams { modulus, generator })
+    }
+
+    // Big-endian hex, as setup ceremonies publish it
+    pub fn from_hex(modulus: &str) -> Result<Self, AccumulatorError> {
+        let bytes = hex::decode(modulus.trim()).map_err(|_| ParseError::InvalidHex)?;
+        Self::new(BigUint::from_bytes_be(&bytes))
+    }
+
+    pub fn modulus(&self) -> &BigUint {
+        &self.modulus
+    }
+
+    // Every group element is encoded at this width
+    pub fn element_len(&self) -> usize {
+        self.modulus.bits().div_ceil(8) as usize
+    }
+
+    pub fn encode_element(&self, element: &BigUint) -> Vec<u8> {
+        let bytes = element.to_bytes_be();
+        let mut padded = vec![0u8; self.element_len() - bytes.len()];
+        padded.extend_from_slice(&bytes);
+        padded
+    }
+
+    pub fn decode_element(&self, bytes: &[u8]) -> Result<BigUint, AccumulatorError> {
+        if bytes.len() != self.element_len() {
+            return Err(AccumulatorError::Parse(ParseError::InvalidLength {
+                expected: self.element_len(),
+                found: bytes.len(),
+            }));
+        }
+        let element = BigUint::from_bytes_be(bytes);
+        if element >= self.modulus || element.bits() == 0 {
+            return Err(AccumulatorError::InvalidElement);
+        }
+        Ok(element)
+    }
+}
+
+// Maps an account ID to the 256-bit prime that represents it in the accumulator: the
+// first probable prime among sha256(domain | counter | id) with the top bit set
+pub fn hash_to_prime(element: &[u8]) -> BigUint {
+    (0u32..)
+        .map(|counter| {
+            let digest = Sha256::new()
+                .chain_update(PRIME_DOMAIN)
+                .chain_update(counter.to_le_bytes())
+                .chain_update(element)
+                .finalize();
+            let mut candidate = BigUint::from_bytes_be(&digest);
+            candidate.set_bit(255, true);
+            candidate.set_bit(0, true);
+            candidate
+        })
+        .find(is_probable_prime)
+        .expect("primes are dense enough that the search ends")
+}
+
+fn is_probable_prime(candidate: &BigUint) -> bool {
+    let one = BigUint::from(1u8);
+    let minus_one = candidate - &one;
+    let shift = minus_one.trailing_zeros().unwrap_or(0);
+    let odd = &minus_one >> shift;
+    WITNESS_BASES.iter().all(|&base| {
+        let base = BigUint::from(base);
+        if base >= minus_one {
+            return true;
+        }
+        let mut x = base.modpow(&odd, candidate);
+        if x == one || x == minus_one {
+            return true;
+        }
+        for _ in 1..shift {
+            x = x.modpow(&BigUint::from(2u8), candidate);
+            if x == minus_one {
+                return true;
+            }
+        }
+        false
+    })
+}
+
+// Raises `base` to the product of `primes` one prime at a time, which is as fast as
+// forming the product and never holds it whole
+fn pow_product(base: &BigUint, primes: &[BigUint], modulus: &BigUint) -> BigUint {
+    primes
+        .iter()
+        .fold(base.clone(), |acc, prime| acc.modpow(prime, modulus))
+}
+
+// Product tree, so a large set multiplies in n log n rather than n^2
+fn product(primes: &[BigUint]) -> BigUint {
+    match primes.len() {
+        0 => BigUint::from(1u8),
+        1 => primes[0].clone(),
+        len => {
+            let (left, right) = primes.split_at(len / 2);
+            product(left) * product(right)
+        }
+    }
+}
+
+// Define the RsaAccumulator struct, a universal accumulator over a set of account IDs:
+// the value is g raised to the product of every ID's prime. Membership and
+// non-membership witnesses are one and two group elements whatever the set size, so a
+// verifier on a thin link receives a few hundred bytes instead of a Merkle path.
+#[derive(Debug, Clone)]
+pub struct RsaAccumulator {
+    params: AccumulatorParams,
+    primes: Vec<BigUint>,
+    positions: HashMap<Vec<u8>, usize>,
+    value: BigUint,
+}
+
+impl RsaAccumulator {
+    pub fn new(params: AccumulatorParams) -> Self {
+        let value = params.generator.clone();
+        RsaAccumulator {
+            params,
+            primes: Vec::new(),
+            positions: HashMap::new(),
+            value,
+        }
+    }
+
+    pub fn from_elements<E: AsRef<[u8]>>(
+        params: AccumulatorParams,
+        elements: impl IntoIterator<Item = E>,
+    ) -> Self {
+        let mut accumulator = Self::new(params);
+        for element in elements {
+            accumulator.add(element.as_ref());
+        }
+        accumulator
+    }
+
+    // Returns false when the element was already in the set
+    pub fn add(&mut self, element: &[u8]) -> bool {
+        if self.positions.contains_key(element) {
+            return false;
+        }
+        let prime = hash_to_prime(element);
+        self.value = self.value.modpow(&prime, &self.params.modulus);
+        self.positions.insert(element.to_vec(), self.primes.len());
+        self.primes.push(prime);
+        true
+    }
+
+    pub fn params(&self) -> &AccumulatorParams {
+        &self.params
+    }
+
+    pub fn len(&self) -> usize {
+        self.primes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.primes.is_empty()
+    }
+
+    pub fn value(&self) -> &BigUint {
+        &self.value
+    }
+
+    // The value as published alongside an epoch's root
+    pub fn value_bytes(&self) -> Vec<u8> {
+        self.params.encode_element(&self.value)
+    }
+
+    // g raised to every prime but the element's own
+    pub fn membership_witness(&self, element: &[u8]) -> Option<MembershipWitness> {
+        let position = *self.positions.get(element)?;
+        let witness = pow_product(
+            &self.params.generator,
+            &self.primes[..position],
+            &self.params.modulus,
+        );
+        let witness = pow_product(&witness, &self.primes[position + 1..], &self.params.modulus);
+        Some(MembershipWitness(witness))
+    }
+
+    // Every member's witness, in insertion order. Splits the set in halves and raises
+    // each half's base by the other half's primes, so the whole batch costs n log n
+    // exponentiations instead of n^2.
+    pub fn membership_witnesses(&self) -> Vec<MembershipWitness> {
+        let mut witnesses = Vec::with_capacity(self.primes.len());
+        self.root_factor(self.params.generator.clone(), &self.primes, &mut witnesses);
+        witnesses
+    }
+
+    fn root_factor(&self, base: BigUint, primes: &[BigUint], out: &mut Vec<MembershipWitness>) {
+        match primes.len() {
+            0 => {}
+            1 => out.push(MembershipWitness(base)),
+            len => {
+                let (left, right) = primes.split_at(len / 2);
+                let modulus = &self.params.modulus;
+                self.root_factor(pow_product(&base, right, modulus), left, out);
+                self.root_factor(pow_product(&base, left, modulus), right, out);
+            }
+        }
+    }
+
+    // With u the product of the set's primes and y the element's prime, finds a and b
+    // with a*u + b*y = 1; then value^a * (g^b)^y = g. Only an element outside the set has
+    // such a pair, since y divides u otherwise.
+    pub fn non_membership_witness(&self, element: &[u8]) -> Option<NonMembershipWitness> {
+        if self.positions.contains_key(element) {
+            return None;
+        }
+        let prime = hash_to_prime(element);
+        let set = product(&self.primes);
+        let a = (&set % &prime).modinv(&prime)?;
+        // b = (1 - a*u) / y is negative, so raise g's inverse to -b instead
+        let minus_b = (&a * &set - 1u8) / &prime;
+        let inverse = self.params.generator.modinv(&self.params.modulus)?;
+        Some(NonMembershipWitness {
+            a,
+            d: inverse.modpow(&minus_b, &self.params.modulus),
+        })
+    }
+}
+
+// Define the MembershipWitness struct, the element w with w^y equal to the accumulator
+// value for the account's prime y
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MembershipWitness(BigUint);
+
+impl MembershipWitness {
+    pub fn verify(
+        &self,
+        params: &AccumulatorParams,
+        value: &BigUint,
+        element: &[u8],
+    ) -> Result<(), AccumulatorError> {
+        if self.0 >= params.modulus {
+            return Err(AccumulatorError::InvalidElement);
+        }
+        if self.0.modpow(&hash_to_prime(element), &params.modulus) != *value {
+            return Err(AccumulatorError::Mismatch);
+        }
+        Ok(())
+    }
+
+    pub fn to_bytes(&self, params: &AccumulatorParams) -> Vec<u8> {
+        params.encode_element(&self.0)
+    }
+
+    pub fn from_bytes(params: &AccumulatorParams, bytes: &[u8]) -> Result<Self, AccumulatorError> {
+        params.decode_element(bytes).map(MembershipWitness)
+    }
+}
+
+// Define the NonMembershipWitness struct, the Bezout coefficient a (below the account's
+// prime) and d = g^b, which together show the prime shares no factor with the set
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NonMembershipWitness {
+    a: BigUint,
+    d: BigUint,
+}
+
+impl NonMembershipWitness {
+    pub fn verify(
+        &self,
+        params: &AccumulatorParams,
+        value: &BigUint,
+        element: &[u8],
+    ) -> Result<(), AccumulatorError> {
+        let prime = hash_to_prime(element);
+        if self.a >= prime || self.d >= params.modulus {
+            return Err(AccumulatorError::InvalidElement);
+        }
+        let modulus = &params.modulus;
+        let lhs = value.modpow(&self.a, modulus) * self.d.modpow(&prime, modulus) % modulus;
+        if lhs != params.generator {
+            return Err(AccumulatorError::Mismatch);
+        }
+        Ok(())
+    }
+
+    // a as 32 bytes, then d
+    pub fn to_bytes(&self, params: &AccumulatorParams) -> Vec<u8> {
+        let a = self.a.to_bytes_be();
+        let mut bytes = vec![0u8; 32 - a.len()];
+        bytes.extend_from_slice(&a);
+        bytes.extend_from_slice(&params.encode_element(&self.d));
+        bytes
+    }
+
+    pub fn from_bytes(params: &AccumulatorParams, bytes: &[u8]) -> Result<Self, AccumulatorError> {
+        if bytes.len() != 32 + params.element_len() {
+            return Err(AccumulatorError::Parse(ParseError::InvalidLength {
+                expected: 32 + params.element_len(),
+                found: bytes.len(),
+            }));
+        }
+        let (a, d) = bytes.split_at(32);
+        Ok(NonMembershipWitness {
+            a: BigUint::from_bytes_be(a),
+            d: params.decode_element(d)?,
+        })
+    }
+}
+
+// Define the AccumulatorError enum for unusable parameters and witnesses that don't hold
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AccumulatorError {
+    InvalidParams,
+    // A witness component outside the range it must lie in
+    InvalidElement,
+    Mismatch,
+    Parse(ParseError),
+}
+
+impl fmt::Display for AccumulatorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AccumulatorError::InvalidParams => {
+                write!(f, "modulus must be odd and at least {} bits", MIN_MODULUS_BITS)
+            }
+            AccumulatorError::InvalidElement => write!(f, "witness element out of range"),
+            AccumulatorError::Mismatch => write!(f, "witness does not match the accumulator"),
+            AccumulatorError::Parse(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for AccumulatorError {}
+
+impl From<ParseError> for AccumulatorError {
+    fn from(err: ParseError) -> Self {
+        AccumulatorError::Parse(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Not from a setup ceremony, which only matters for soundness, not for the algebra
+    fn params() -> AccumulatorParams {
+        AccumulatorParams::new((BigUint::from(1u8) << 2047usize) + 3u8).unwrap()
+    }
+
+    #[test]
+    fn moduli_must_be_long_odd_and_coprime_to_the_generator() {
+        let short = (BigUint::from(1u8) << 1023usize) + 3u8;
+        let even = BigUint::from(1u8) << 2047usize;
+        let multiple_of_three = (BigUint::from(1u8) << 2047usize) + 1u8;
+        for modulus in [short, even, multiple_of_three] {
+            assert_eq!(
+                AccumulatorParams::new(modulus),
+                Err(AccumulatorError::InvalidParams)
+            );
+        }
+    }
+
+    #[test]
+    fn witnesses_prove_membership_and_absence() {
+        let params = params();
+        let mut accumulator = RsaAccumulator::from_elements(params.clone(), ["alice", "bob"]);
+        assert!(accumulator.add(b"carol"));
+        assert!(!accumulator.add(b"carol"));
+        let value = accumulator.value().clone();
+
+        let witnesses = accumulator.membership_witnesses();
+        for (witness, member) in witnesses.iter().zip(["alice", "bob", "carol"]) {
+            assert_eq!(
+                accumulator.membership_witness(member.as_bytes()).as_ref(),
+                Some(witness)
+            );
+            assert_eq!(witness.verify(&params, &value, member.as_bytes()), Ok(()));
+        }
+        assert_eq!(
+            witnesses[0].verify(&params, &value, b"mallory"),
+            Err(AccumulatorError::Mismatch)
+        );
+
+        assert!(accumulator.non_membership_witness(b"alice").is_none());
+        let absent = accumulator.non_membership_witness(b"mallory").unwrap();
+        let parsed = NonMembershipWitness::from_bytes(&params, &absent.to_bytes(&params)).unwrap();
+        assert_eq!(parsed.verify(&params, &value, b"mallory"), Ok(()));
+        assert_eq!(
+            parsed.verify(&params, &value, b"alice"),
+            Err(AccumulatorError::Mismatch)
+        );
+        accumulator.add(b"mallory");
+        assert_eq!(
+            parsed.verify(&params, accumulator.value(), b"mallory"),
+            Err(AccumulatorError::Mismatch)
+        );
+    }
+
+    #[test]
+    fn encoded_witnesses_are_range_checked() {
+        let params = params();
+        let accumulator = RsaAccumulator::from_elements(params.clone(), ["alice"]);
+        let bytes = accumulator
+            .membership_witness(b"alice")
+            .unwrap()
+            .to_bytes(&params);
+        assert_eq!(bytes.len(), params.element_len());
+        assert!(MembershipWitness::from_bytes(&params, &bytes).is_ok());
+        assert!(matches!(
+            MembershipWitness::from_bytes(&params, &bytes[1..]),
+            Err(AccumulatorError::Parse(_))
+        ));
+        assert_eq!(
+            MembershipWitness::from_bytes(&params, &vec![0xff; params.element_len()]),
+            Err(AccumulatorError::InvalidElement)
+        );
+        assert_eq!(
+            MembershipWitness::from_bytes(&params, &vec![0; params.element_len()]),
+            Err(AccumulatorError::InvalidElement)
+        );
+    }
+}
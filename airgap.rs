@@ -0,0 +1,238 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use ed25519_dalek::{Signature, VerifyingKey};
+use sha2::{Digest as _, Sha256};
+
+use crate::bundle::{signing_payload, SignedRoot};
+use crate::signer::{Signer, SignerError};
+use crate::{Digest, ParseError, Root, SumCommitment};
+
+const UNSIGNED_HEADER: &str = "mimi-unsigned-root-v1";
+const DETACHED_HEADER: &str = "mimi-root-signature-v1";
+
+// Define the UnsignedRoot struct, the to-be-signed file carried to the offline machine.
+// It repeats the exact payload bytes, so the signer needs nothing but this file.
+#[derive(Debug, Clone)]
+pub struct UnsignedRoot<C: SumCommitment> {
+    pub epoch: u64,
+    pub root: Root<C>,
+}
+
+impl<C: SumCommitment> UnsignedRoot<C> {
+    pub fn new(epoch: u64, root: Root<C>) -> Self {
+        UnsignedRoot { epoch, root }
+    }
+
+    pub fn payload(&self) -> Vec<u8> {
+        signing_payload(self.epoch, &self.root)
+    }
+
+    pub fn to_text(&self) -> String {
+        format!(
+            "{}\nepoch: {}\nroot: {}\npayload: {}\n",
+            UNSIGNED_HEADER,
+            self.epoch,
+            self.root,
+            hex::encode(self.payload())
+        )
+    }
+
+    // The payload line must match the one recomputed from epoch and root, so an edited
+    // file can't make the offline signer sign something other than what it displays
+    pub fn from_text(text: &str) -> Result<Self, AirGapError> {
+        let fields = parse_fields(text, UNSIGNED_HEADER, &["epoch", "root", "payload"])?;
+        let unsigned = UnsignedRoot {
+            epoch: parse_epoch(&fields[0])?,
+            root: fields[1].parse()?,
+        };
+        if hex::encode(unsigned.payload()) != fields[2] {
+            return Err(AirGapError::PayloadMismatch);
+        }
+        Ok(unsigned)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), AirGapError> {
+        fs::write(path, self.to_text()).map_err(AirGapError::Io)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, AirGapError> {
+        Self::from_text(&fs::read_to_string(path).map_err(AirGapError::Io)?)
+    }
+
+    pub fn sign(&self, signer: &dyn Signer) -> Result<DetachedSignature, AirGapError> {
+        let payload = self.payload();
+        Ok(DetachedSignature {
+            epoch: self.epoch,
+            payload_hash: Digest::from(Sha256::digest(&payload)),
+            public_key: signer.verifying_key()?,
+            signature: signer.sign(&payload)?,
+        })
+    }
+
+    // Checks the imported signature against the operator key the online side expects
+    pub fn attach(
+        self,
+        detached: &DetachedSignature,
+        operator_key: &VerifyingKey,
+    ) -> Result<SignedRoot<C>, AirGapError> {
+        if detached.epoch != self.epoch {
+            return Err(AirGapError::EpochMismatch {
+                expected: self.epoch,
+                found: detached.epoch,
+            });
+        }
+        if detached.payload_hash.as_bytes()[..] != Sha256::digest(self.payload())[..] {
+            return Err(AirGapError::PayloadMismatch);
+        }
+        if detached.public_key != *operator_key {
+            return Err(AirGapError::UnexpectedKey);
+        }
+        let signed_root = SignedRoot {
+            epoch: self.epoch,
+            root: self.root,
+            signature: detached.signature,
+        };
+        signed_root
+            .verify(operator_key)
+            .map_err(|_| AirGapError::BadSignature)?;
+        Ok(signed_root)
+    }
+}
+
+// Define the DetachedSignature struct, the file carried back from the offline machine
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DetachedSignature {
+    pub epoch: u64,
+    // SHA-256 of the signed payload, so a signature is never paired with the wrong file
+    pub payload_hash: Digest,
+    pub public_key: VerifyingKey,
+    pub signature: Signature,
+}
+
+impl DetachedSignature {
+    pub fn to_text(&self) -> String {
+        format!(
+            "{}\nepoch: {}\npayload-sha256: {}\npublic-key: {}\nsignature: {}\n",
+            DETACHED_HEADER,
+            self.epoch,
+            self.payload_hash,
+            hex::encode(self.public_key.as_bytes()),
+            hex::encode(self.signature.to_bytes())
+        )
+    }
+
+    pub fn from_text(text: &str) -> Result<Self, AirGapError> {
+        let fields = parse_fields(
+            text,
+            DETACHED_HEADER,
+            &["epoch", "payload-sha256", "public-key", "signature"],
+        )?;
+        let public_key: [u8; 32] = decode_fixed(&fields[2])?;
+        let signature: [u8; 64] = decode_fixed(&fields[3])?;
+        Ok(DetachedSignature {
+            epoch: parse_epoch(&fields[0])?,
+            payload_hash: fields[1].parse()?,
+            public_key: VerifyingKey::from_bytes(&public_key)
+                .map_err(|_| AirGapError::Format("invalid public key".to_string()))?,
+            signature: Signature::from_bytes(&signature),
+        })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), AirGapError> {
+        fs::write(path, self.to_text()).map_err(AirGapError::Io)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, AirGapError> {
+        Self::from_text(&fs::read_to_string(path).map_err(AirGapError::Io)?)
+    }
+}
+
+// Reads `name: value` lines after the header, requiring exactly `names` in that order
+fn parse_fields(text: &str, header: &str, names: &[&str]) -> Result<Vec<String>, AirGapError> {
+    let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty());
+    if lines.next() != Some(header) {
+        return Err(AirGapError::Format(format!("expected `{}` header", header)));
+    }
+    let mut values = Vec::with_capacity(names.len());
+    for name in names {
+        let line = lines
+            .next()
+            .ok_or_else(|| AirGapError::Format(format!("missing `{}`", name)))?;
+        match line.split_once(':') {
+            Some((key, value)) if key.trim() == *name => values.push(value.trim().to_string()),
+            _ => return Err(AirGapError::Format(format!("expected `{}`", name))),
+        }
+    }
+    if lines.next().is_some() {
+        return Err(AirGapError::Format("unexpected trailing lines".to_string()));
+    }
+    Ok(values)
+}
+
+fn parse_epoch(value: &str) -> Result<u64, AirGapError> {
+    value
+        .parse()
+        .map_err(|_| AirGapError::Format("invalid epoch".to_string()))
+}
+
+fn decode_fixed<const N: usize>(value: &str) -> Result<[u8; N], AirGapError> {
+    hex::decode(value)
+        .map_err(|_| ParseError::InvalidHex)?
+        .try_into()
+        .map_err(|bytes: Vec<u8>| {
+            AirGapError::Parse(ParseError::InvalidLength {
+                expected: N,
+                found: bytes.len(),
+            })
+        })
+}
+
+// Define the AirGapError enum for to-be-signed and signature files that don't line up
+#[derive(Debug)]
+pub enum AirGapError {
+    Io(std::io::Error),
+    Format(String),
+    Parse(ParseError),
+    Signer(SignerError),
+    EpochMismatch { expected: u64, found: u64 },
+    PayloadMismatch,
+    UnexpectedKey,
+    BadSignature,
+}
+
+impl fmt::Display for AirGapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AirGapError::Io(err) => write!(f, "i/o error: {}", err),
+            AirGapError::Format(reason) => write!(f, "malformed file: {}", reason),
+            AirGapError::Parse(err) => write!(f, "malformed file: {}", err),
+            AirGapError::Signer(err) => write!(f, "{}", err),
+            AirGapError::EpochMismatch { expected, found } => write!(
+                f,
+                "signature is for epoch {} but the root is for epoch {}",
+                found, expected
+            ),
+            AirGapError::PayloadMismatch => {
+                write!(f, "signature file does not belong to this root payload")
+            }
+            AirGapError::UnexpectedKey => write!(f, "root was signed by an unexpected key"),
+            AirGapError::BadSignature => write!(f, "detached signature does not verify"),
+        }
+    }
+}
+
+impl std::error::Error for AirGapError {}
+
+impl From<ParseError> for AirGapError {
+    fn from(err: ParseError) -> Self {
+        AirGapError::Parse(err)
+    }
+}
+
+impl From<SignerError> for AirGapError {
+    fn from(err: SignerError) -> Self {
+        AirGapError::Signer(err)
+    }
+}
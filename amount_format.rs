@@ -0,0 +1,196 @@
+use std::fmt;
+
+// Exponents past this can't produce a u64 amount from a non-zero mantissa
+const MAX_EXPONENT: i32 = 40;
+
+// Define the DecimalMark enum for the character separating whole units from the fraction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecimalMark {
+    // "1234.56"
+    Point,
+    // "1234,56"
+    Comma,
+}
+
+impl DecimalMark {
+    fn as_char(self) -> char {
+        match self {
+            DecimalMark::Point => '.',
+            DecimalMark::Comma => ',',
+        }
+    }
+}
+
+// Define the AmountFormat struct, how balances are written in an export. Nothing is
+// guessed: a value that doesn't follow the format exactly is rejected, so "1,234" is
+// 1234 only when ',' is the configured grouping separator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AmountFormat {
+    pub decimal_mark: DecimalMark,
+    // Thousands separator allowed in the whole part, in groups of three
+    pub grouping: Option<char>,
+    // Accept "1.5e3"-style values; the result must still be a whole number of units
+    pub scientific: bool,
+    // Fraction digits of the smallest unit; amounts come back in those units
+    pub decimals: u32,
+}
+
+impl Default for AmountFormat {
+    // Plain integers already in the smallest unit
+    fn default() -> Self {
+        AmountFormat {
+            decimal_mark: DecimalMark::Point,
+            grouping: None,
+            scientific: false,
+            decimals: 0,
+        }
+    }
+}
+
+impl AmountFormat {
+    pub fn with_decimals(mut self, decimals: u32) -> Self {
+        self.decimals = decimals;
+        self
+    }
+
+    pub fn with_grouping(mut self, grouping: char) -> Self {
+        self.grouping = Some(grouping);
+        self
+    }
+
+    pub fn with_decimal_mark(mut self, decimal_mark: DecimalMark) -> Self {
+        self.decimal_mark = decimal_mark;
+        self
+    }
+
+    pub fn with_scientific(mut self) -> Self {
+        self.scientific = true;
+        self
+    }
+
+    // Parses `value` into integer units of 10^-decimals
+    pub fn parse(&self, value: &str) -> Result<u64, AmountError> {
+        let mark = self.decimal_mark.as_char();
+        if let Some(grouping) = self.grouping {
+            if grouping == mark || grouping.is_ascii_digit() || matches!(grouping, 'e' | 'E') {
+                return Err(AmountError::ConflictingSeparators);
+            }
+        }
+        let value = value.trim();
+        if value.is_empty() {
+            return Err(AmountError::Empty);
+        }
+        if value.starts_with('-') {
+            return Err(AmountError::Negative);
+        }
+
+        let (mantissa, exponent) = match value.find(['e', 'E']) {
+            Some(index) if self.scientific => {
+                let exponent: i32 = value[index + 1..]
+                    .parse()
+                    .map_err(|_| AmountError::Invalid)?;
+                (&value[..index], exponent)
+            }
+            Some(_) => return Err(AmountError::Invalid),
+            None => (value, 0),
+        };
+        if exponent.abs() > MAX_EXPONENT {
+            return Err(AmountError::Overflow);
+        }
+
+        let (whole, fraction) = match mantissa.split_once(mark) {
+            Some((whole, fraction)) => (whole, fraction),
+            None => (mantissa, ""),
+        };
+        let whole = self.ungroup(whole, exponent != 0)?;
+        if whole.is_empty() || !fraction.bytes().all(|byte| byte.is_ascii_digit()) {
+            return Err(AmountError::Invalid);
+        }
+
+        // Shift the decimal point right by `decimals + exponent`; digits pushed past the
+        // end must be zeros, or the value isn't a whole number of units
+        let digits: Vec<u8> = whole.bytes().chain(fraction.bytes()).map(|b| b - b'0').collect();
+        let shift = self.decimals as i64 + exponent as i64 - fraction.len() as i64;
+        let kept = match shift {
+            shift if shift >= 0 => digits.len(),
+            shift => {
+                let dropped = (-shift) as usize;
+                if digits.len() < dropped {
+                    return Err(AmountError::Precision);
+                }
+                digits.len() - dropped
+            }
+        };
+        if digits[kept..].iter().any(|digit| *digit != 0) {
+            return Err(AmountError::Precision);
+        }
+        let mut amount: u64 = 0;
+        for digit in &digits[..kept] {
+            amount = amount
+                .checked_mul(10)
+                .and_then(|amount| amount.checked_add(*digit as u64))
+                .ok_or(AmountError::Overflow)?;
+        }
+        for _ in 0..shift.max(0) {
+            amount = amount.checked_mul(10).ok_or(AmountError::Overflow)?;
+        }
+        Ok(amount)
+    }
+
+    // Strips grouping separators after checking the groups: 1 to 3 leading digits, then
+    // exactly 3 per group. Grouped mantissas can't carry an exponent.
+    fn ungroup(&self, whole: &str, has_exponent: bool) -> Result<String, AmountError> {
+        let Some(grouping) = self.grouping.filter(|grouping| whole.contains(*grouping)) else {
+            if !whole.bytes().all(|byte| byte.is_ascii_digit()) {
+                return Err(AmountError::Invalid);
+            }
+            return Ok(whole.to_string());
+        };
+        if has_exponent {
+            return Err(AmountError::Invalid);
+        }
+        let groups: Vec<&str> = whole.split(grouping).collect();
+        let well_formed = groups.iter().enumerate().all(|(index, group)| {
+            let len_ok = match index {
+                0 => (1..=3).contains(&group.len()),
+                _ => group.len() == 3,
+            };
+            len_ok && group.bytes().all(|byte| byte.is_ascii_digit())
+        });
+        if !well_formed {
+            return Err(AmountError::Grouping);
+        }
+        Ok(groups.concat())
+    }
+}
+
+// Define the AmountError enum for balances that can't be read unambiguously
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AmountError {
+    Empty,
+    Negative,
+    Invalid,
+    Grouping,
+    // More fraction digits than the unit holds, and they aren't zeros
+    Precision,
+    Overflow,
+    ConflictingSeparators,
+}
+
+impl fmt::Display for AmountError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AmountError::Empty => write!(f, "amount is empty"),
+            AmountError::Negative => write!(f, "amount is negative"),
+            AmountError::Invalid => write!(f, "amount doesn't match the configured format"),
+            AmountError::Grouping => write!(f, "amount has misplaced grouping separators"),
+            AmountError::Precision => write!(f, "amount is finer than the smallest unit"),
+            AmountError::Overflow => write!(f, "amount does not fit in 64 bits"),
+            AmountError::ConflictingSeparators => {
+                write!(f, "grouping separator clashes with the decimal mark or exponent")
+            }
+        }
+    }
+}
+
+impl std::error::Error for AmountError {}
@@ -0,0 +1,451 @@
+use std::fmt;
+
+use sha2::{Digest as _, Sha256};
+
+use crate::encoding::ByteReader;
+use crate::{ParseError, Root, SumCommitment};
+
+// Magic bytes opening every detached OpenTimestamps proof file
+const HEADER_MAGIC: &[u8] = b"\x00OpenTimestamps\x00\x00Proof\x00\xbf\x89\xe2\xe8\x84\xe8\x92\x94";
+const MAJOR_VERSION: u64 = 1;
+
+const TAG_ATTESTATION: u8 = 0x00;
+const TAG_CONTINUE: u8 = 0xff;
+const TAG_SHA256: u8 = 0x08;
+const TAG_APPEND: u8 = 0xf0;
+const TAG_PREPEND: u8 = 0xf1;
+
+const PENDING_TAG: [u8; 8] = [0x83, 0xdf, 0xe3, 0x0d, 0x2e, 0xf9, 0x0c, 0x8e];
+const BITCOIN_TAG: [u8; 8] = [0x05, 0x88, 0x96, 0x0d, 0x73, 0xd7, 0x19, 0x01];
+
+// Same recursion limit as the reference client
+const MAX_DEPTH: usize = 256;
+
+// Define the Op enum, the commitment operations a timestamp is built from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Op {
+    Append(Vec<u8>),
+    Prepend(Vec<u8>),
+    Sha256,
+}
+
+impl Op {
+    fn apply(&self, msg: &[u8]) -> Vec<u8> {
+        match self {
+            Op::Append(suffix) => [msg, suffix].concat(),
+            Op::Prepend(prefix) => [prefix, msg].concat(),
+            Op::Sha256 => Sha256::digest(msg).to_vec(),
+        }
+    }
+}
+
+// Define the Attestation enum; attestations we don't understand are kept verbatim
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Attestation {
+    Pending { uri: String },
+    Bitcoin { height: u64 },
+    Unknown { tag: [u8; 8], payload: Vec<u8> },
+}
+
+// Define the Timestamp struct, a tree of operations over `msg` leading to attestations
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Timestamp {
+    pub msg: Vec<u8>,
+    pub attestations: Vec<Attestation>,
+    pub ops: Vec<(Op, Timestamp)>,
+}
+
+// Define the BitcoinAttestation struct, the block a timestamp was proven against
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BitcoinAttestation {
+    pub height: u64,
+    pub block_time: u32,
+}
+
+// Define the Calendar trait for the OpenTimestamps calendar servers roots are submitted to
+pub trait Calendar {
+    fn uri(&self) -> &str;
+    // POST /digest, returning the serialized timestamp for `digest`
+    fn submit(&self, digest: &[u8; 32]) -> Result<Vec<u8>, AnchorError>;
+    // GET /timestamp/<commitment>, returning None while the calendar hasn't reached Bitcoin yet
+    fn get_timestamp(&self, commitment: &[u8]) -> Result<Option<Vec<u8>>, AnchorError>;
+}
+
+// Define the BlockHeaderSource trait for a trusted view of the Bitcoin chain
+pub trait BlockHeaderSource {
+    fn block_header(&self, height: u64) -> Result<[u8; 80], AnchorError>;
+}
+
+impl Timestamp {
+    pub fn new(msg: Vec<u8>) -> Self {
+        Timestamp {
+            msg,
+            attestations: Vec::new(),
+            ops: Vec::new(),
+        }
+    }
+
+    pub fn merge(&mut self, other: Timestamp) -> Result<(), AnchorError> {
+        if self.msg != other.msg {
+            return Err(AnchorError::DigestMismatch);
+        }
+        for attestation in other.attestations {
+            if !self.attestations.contains(&attestation) {
+                self.attestations.push(attestation);
+            }
+        }
+        for (op, stamp) in other.ops {
+            match self.ops.iter_mut().find(|(existing, _)| *existing == op) {
+                Some((_, existing)) => existing.merge(stamp)?,
+                None => self.ops.push((op, stamp)),
+            }
+        }
+        Ok(())
+    }
+
+    pub fn serialize(&self) -> Result<Vec<u8>, AnchorError> {
+        let mut bytes = Vec::new();
+        self.serialize_into(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    fn serialize_into(&self, bytes: &mut Vec<u8>) -> Result<(), AnchorError> {
+        let count = self.attestations.len() + self.ops.len();
+        if count == 0 {
+            return Err(AnchorError::EmptyTimestamp);
+        }
+
+        let mut written = 0;
+        for attestation in &self.attestations {
+            written += 1;
+            if written < count {
+                bytes.push(TAG_CONTINUE);
+            }
+            bytes.push(TAG_ATTESTATION);
+            let (tag, payload) = match attestation {
+                Attestation::Pending { uri } => {
+                    let mut payload = Vec::new();
+                    write_varbytes(&mut payload, uri.as_bytes());
+                    (PENDING_TAG, payload)
+                }
+                Attestation::Bitcoin { height } => {
+                    let mut payload = Vec::new();
+                    write_varuint(&mut payload, *height);
+                    (BITCOIN_TAG, payload)
+                }
+                Attestation::Unknown { tag, payload } => (*tag, payload.clone()),
+            };
+            bytes.extend_from_slice(&tag);
+            write_varbytes(bytes, &payload);
+        }
+        for (op, stamp) in &self.ops {
+            written += 1;
+            if written < count {
+                bytes.push(TAG_CONTINUE);
+            }
+            match op {
+                Op::Append(arg) => {
+                    bytes.push(TAG_APPEND);
+                    write_varbytes(bytes, arg);
+                }
+                Op::Prepend(arg) => {
+                    bytes.push(TAG_PREPEND);
+                    write_varbytes(bytes, arg);
+                }
+                Op::Sha256 => bytes.push(TAG_SHA256),
+            }
+            stamp.serialize_into(bytes)?;
+        }
+        Ok(())
+    }
+
+    pub fn deserialize(bytes: &[u8], msg: Vec<u8>) -> Result<Self, AnchorError> {
+        let mut reader = ByteReader::new(bytes);
+        let stamp = Self::read(&mut reader, msg, 0)?;
+        reader.finish()?;
+        Ok(stamp)
+    }
+
+    fn read(reader: &mut ByteReader<'_>, msg: Vec<u8>, depth: usize) -> Result<Self, AnchorError> {
+        if depth > MAX_DEPTH {
+            return Err(AnchorError::TooDeep);
+        }
+        let mut stamp = Timestamp::new(msg);
+        loop {
+            let mut tag = read_u8(reader)?;
+            let more = tag == TAG_CONTINUE;
+            if more {
+                tag = read_u8(reader)?;
+            }
+            stamp.read_item(reader, tag, depth)?;
+            if !more {
+                return Ok(stamp);
+            }
+        }
+    }
+
+    fn read_item(
+        &mut self,
+        reader: &mut ByteReader<'_>,
+        tag: u8,
+        depth: usize,
+    ) -> Result<(), AnchorError> {
+        let op = match tag {
+            TAG_ATTESTATION => {
+                let attestation_tag = reader.take_array::<8>()?;
+                let payload = read_varbytes(reader)?;
+                let mut payload_reader = ByteReader::new(&payload);
+                let attestation = match attestation_tag {
+                    PENDING_TAG => {
+                        let uri = read_varbytes(&mut payload_reader)?;
+                        payload_reader.finish()?;
+                        let uri = String::from_utf8(uri).map_err(|_| AnchorError::InvalidUri)?;
+                        Attestation::Pending { uri }
+                    }
+                    BITCOIN_TAG => {
+                        let height = read_varuint(&mut payload_reader)?;
+                        payload_reader.finish()?;
+                        Attestation::Bitcoin { height }
+                    }
+                    tag => Attestation::Unknown { tag, payload },
+                };
+                self.attestations.push(attestation);
+                return Ok(());
+            }
+            TAG_APPEND => Op::Append(read_varbytes(reader)?),
+            TAG_PREPEND => Op::Prepend(read_varbytes(reader)?),
+            TAG_SHA256 => Op::Sha256,
+            other => return Err(AnchorError::UnsupportedOp(other)),
+        };
+        let result = op.apply(&self.msg);
+        let stamp = Self::read(reader, result, depth + 1)?;
+        self.ops.push((op, stamp));
+        Ok(())
+    }
+
+    // Replaces every pending attestation for `calendar` with what the calendar now returns
+    pub fn upgrade(&mut self, calendar: &dyn Calendar) -> Result<bool, AnchorError> {
+        let mut upgraded = false;
+        for (_, stamp) in &mut self.ops {
+            upgraded |= stamp.upgrade(calendar)?;
+        }
+        let pending = self.attestations.iter().any(|attestation| {
+            matches!(attestation, Attestation::Pending { uri } if uri == calendar.uri())
+        });
+        if pending {
+            if let Some(bytes) = calendar.get_timestamp(&self.msg)? {
+                let fetched = Timestamp::deserialize(&bytes, self.msg.clone())?;
+                self.merge(fetched)?;
+                upgraded = true;
+            }
+        }
+        Ok(upgraded)
+    }
+
+    // Checks every Bitcoin attestation and returns the earliest block that proves the timestamp
+    pub fn verify_bitcoin(
+        &self,
+        source: &dyn BlockHeaderSource,
+    ) -> Result<BitcoinAttestation, AnchorError> {
+        let mut earliest: Option<BitcoinAttestation> = None;
+        self.visit_bitcoin(source, &mut earliest)?;
+        earliest.ok_or(AnchorError::NoBitcoinAttestation)
+    }
+
+    fn visit_bitcoin(
+        &self,
+        source: &dyn BlockHeaderSource,
+        earliest: &mut Option<BitcoinAttestation>,
+    ) -> Result<(), AnchorError> {
+        for attestation in &self.attestations {
+            if let Attestation::Bitcoin { height } = attestation {
+                let header = source.block_header(*height)?;
+                // The message must equal the header's merkle root, in internal byte order
+                if self.msg != header[36..68] {
+                    return Err(AnchorError::MerkleRootMismatch { height: *height });
+                }
+                let mut time = [0u8; 4];
+                time.copy_from_slice(&header[68..72]);
+                let found = BitcoinAttestation {
+                    height: *height,
+                    block_time: u32::from_le_bytes(time),
+                };
+                if earliest.is_none_or(|current| found.height < current.height) {
+                    *earliest = Some(found);
+                }
+            }
+        }
+        for (_, stamp) in &self.ops {
+            stamp.visit_bitcoin(source, earliest)?;
+        }
+        Ok(())
+    }
+}
+
+// Define the AnchoredRoot struct, an epoch root together with its OpenTimestamps proof
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnchoredRoot {
+    pub epoch: u64,
+    pub timestamp: Timestamp,
+}
+
+// The digest that gets timestamped binds the epoch number to the root
+pub fn anchor_digest<C: SumCommitment>(epoch: u64, root: &Root<C>) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(b"epoch-root");
+    hasher.update(epoch.to_le_bytes());
+    hasher.update(root.to_bytes());
+    hasher.finalize().into()
+}
+
+impl AnchoredRoot {
+    pub fn anchor<C: SumCommitment>(
+        epoch: u64,
+        root: &Root<C>,
+        calendars: &[&dyn Calendar],
+    ) -> Result<Self, AnchorError> {
+        let digest = anchor_digest(epoch, root);
+        let mut timestamp = Timestamp::new(digest.to_vec());
+        for calendar in calendars {
+            let response = calendar.submit(&digest)?;
+            timestamp.merge(Timestamp::deserialize(&response, digest.to_vec())?)?;
+        }
+        if timestamp.attestations.is_empty() && timestamp.ops.is_empty() {
+            return Err(AnchorError::EmptyTimestamp);
+        }
+        Ok(AnchoredRoot { epoch, timestamp })
+    }
+
+    pub fn verify<C: SumCommitment>(
+        &self,
+        root: &Root<C>,
+        source: &dyn BlockHeaderSource,
+    ) -> Result<BitcoinAttestation, AnchorError> {
+        if self.timestamp.msg != anchor_digest(self.epoch, root) {
+            return Err(AnchorError::DigestMismatch);
+        }
+        self.timestamp.verify_bitcoin(source)
+    }
+
+    // Detached `.ots` file contents for the anchor digest
+    pub fn to_ots_file(&self) -> Result<Vec<u8>, AnchorError> {
+        let mut bytes = HEADER_MAGIC.to_vec();
+        write_varuint(&mut bytes, MAJOR_VERSION);
+        bytes.push(TAG_SHA256);
+        bytes.extend_from_slice(&self.timestamp.msg);
+        self.timestamp.serialize_into(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    pub fn from_ots_file(epoch: u64, bytes: &[u8]) -> Result<Self, AnchorError> {
+        let mut reader = ByteReader::new(bytes);
+        if reader.take(HEADER_MAGIC.len())? != HEADER_MAGIC {
+            return Err(AnchorError::BadHeader);
+        }
+        if read_varuint(&mut reader)? != MAJOR_VERSION {
+            return Err(AnchorError::BadHeader);
+        }
+        if read_u8(&mut reader)? != TAG_SHA256 {
+            return Err(AnchorError::BadHeader);
+        }
+        let digest = reader.take(32)?.to_vec();
+        let timestamp = Timestamp::read(&mut reader, digest, 0)?;
+        reader.finish()?;
+        Ok(AnchoredRoot { epoch, timestamp })
+    }
+}
+
+fn read_u8(reader: &mut ByteReader<'_>) -> Result<u8, AnchorError> {
+    Ok(reader.take_array::<1>()?[0])
+}
+
+fn read_varuint(reader: &mut ByteReader<'_>) -> Result<u64, AnchorError> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = read_u8(reader)?;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(AnchorError::VarintOverflow)
+}
+
+fn read_varbytes(reader: &mut ByteReader<'_>) -> Result<Vec<u8>, AnchorError> {
+    let len = read_varuint(reader)?;
+    let len = usize::try_from(len).map_err(|_| AnchorError::VarintOverflow)?;
+    Ok(reader.take(len)?.to_vec())
+}
+
+fn write_varuint(bytes: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            bytes.push(byte);
+            return;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+fn write_varbytes(bytes: &mut Vec<u8>, data: &[u8]) {
+    write_varuint(bytes, data.len() as u64);
+    bytes.extend_from_slice(data);
+}
+
+// Define the AnchorError enum for timestamps that can't be created, parsed or verified
+#[derive(Debug)]
+pub enum AnchorError {
+    Parse(ParseError),
+    BadHeader,
+    UnsupportedOp(u8),
+    VarintOverflow,
+    InvalidUri,
+    TooDeep,
+    EmptyTimestamp,
+    DigestMismatch,
+    NoBitcoinAttestation,
+    MerkleRootMismatch { height: u64 },
+    Calendar(String),
+    HeaderUnavailable { height: u64 },
+}
+
+impl fmt::Display for AnchorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AnchorError::Parse(err) => write!(f, "malformed timestamp: {}", err),
+            AnchorError::BadHeader => write!(f, "not a detached OpenTimestamps sha256 proof"),
+            AnchorError::UnsupportedOp(tag) => write!(f, "unsupported operation {:#04x}", tag),
+            AnchorError::VarintOverflow => write!(f, "varint does not fit in 64 bits"),
+            AnchorError::InvalidUri => write!(f, "pending attestation uri is not utf-8"),
+            AnchorError::TooDeep => write!(f, "timestamp nesting exceeds {} levels", MAX_DEPTH),
+            AnchorError::EmptyTimestamp => write!(f, "timestamp has no attestations"),
+            AnchorError::DigestMismatch => {
+                write!(f, "timestamp does not commit to this epoch root")
+            }
+            AnchorError::NoBitcoinAttestation => {
+                write!(f, "timestamp has no bitcoin attestation yet")
+            }
+            AnchorError::MerkleRootMismatch { height } => {
+                write!(
+                    f,
+                    "timestamp does not match the merkle root of block {}",
+                    height
+                )
+            }
+            AnchorError::Calendar(message) => write!(f, "calendar error: {}", message),
+            AnchorError::HeaderUnavailable { height } => {
+                write!(f, "no block header available for height {}", height)
+            }
+        }
+    }
+}
+
+impl std::error::Error for AnchorError {}
+
+impl From<ParseError> for AnchorError {
+    fn from(err: ParseError) -> Self {
+        AnchorError::Parse(err)
+    }
+}
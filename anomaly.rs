@@ -0,0 +1,212 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::snapshot::Snapshot;
+use crate::{LeafCommitment, SumCommitment};
+
+// Define the AnomalyThresholds struct, how large a change must be before it is flagged.
+// Each is a fraction of the previous epoch's figure.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AnomalyThresholds {
+    // Funded accounts that are still present but now hold nothing
+    pub zeroed_accounts: f64,
+    // Growth of the amount held by leaves with no account data (padding and tombstones),
+    // relative to the previous total
+    pub unattributed_growth: f64,
+    pub total_drop: f64,
+}
+
+impl Default for AnomalyThresholds {
+    fn default() -> Self {
+        AnomalyThresholds {
+            zeroed_accounts: 0.05,
+            unattributed_growth: 0.01,
+            total_drop: 0.2,
+        }
+    }
+}
+
+// Define the Finding enum, one suspicious change between two epochs
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Finding {
+    MassZeroing {
+        zeroed: usize,
+        funded_before: usize,
+        // What the zeroed accounts held in the previous epoch
+        amount: u64,
+    },
+    UnattributedGrowth {
+        previous_amount: u64,
+        amount: u64,
+        // Leaves without account data that now hold a non-zero amount
+        positions: Vec<usize>,
+    },
+    TotalDrop {
+        previous: u64,
+        current: u64,
+    },
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Finding::MassZeroing {
+                zeroed,
+                funded_before,
+                amount,
+            } => write!(
+                f,
+                "{} of {} funded accounts were zeroed, removing {}",
+                zeroed, funded_before, amount
+            ),
+            Finding::UnattributedGrowth {
+                previous_amount,
+                amount,
+                positions,
+            } => write!(
+                f,
+                "leaves without account data grew from {} to {} across {} positions",
+                previous_amount,
+                amount,
+                positions.len()
+            ),
+            Finding::TotalDrop { previous, current } => {
+                write!(f, "total liabilities fell from {} to {}", previous, current)
+            }
+        }
+    }
+}
+
+// Define the AnomalyReport struct, the machine-readable findings for one pair of epochs
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AnomalyReport {
+    pub previous_epoch: u64,
+    pub current_epoch: u64,
+    pub findings: Vec<Finding>,
+}
+
+impl AnomalyReport {
+    pub fn is_clean(&self) -> bool {
+        self.findings.is_empty()
+    }
+
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+// Compares consecutive snapshots. Accounts are matched by user ID, so only leaves whose
+// account data is still held (not tombstoned) take part in the per-account checks.
+pub fn compare<C: SumCommitment>(
+    previous: &Snapshot<C>,
+    current: &Snapshot<C>,
+    thresholds: &AnomalyThresholds,
+) -> AnomalyReport {
+    let mut findings = Vec::new();
+    let before = EpochSummary::of(previous);
+    let after = EpochSummary::of(current);
+
+    let funded: Vec<(&Vec<u8>, u64)> = before
+        .balances
+        .iter()
+        .filter(|(_, &balance)| balance > 0)
+        .map(|(user_id, &balance)| (user_id, balance))
+        .collect();
+    let zeroed: Vec<u64> = funded
+        .iter()
+        .filter(|(user_id, _)| after.balances.get(*user_id) == Some(&0))
+        .map(|(_, balance)| *balance)
+        .collect();
+    if exceeds(
+        zeroed.len() as u128,
+        funded.len() as u128,
+        thresholds.zeroed_accounts,
+    ) {
+        findings.push(Finding::MassZeroing {
+            zeroed: zeroed.len(),
+            funded_before: funded.len(),
+            amount: zeroed
+                .iter()
+                .fold(0u64, |sum, &balance| sum.saturating_add(balance)),
+        });
+    }
+
+    let growth = after.unattributed.saturating_sub(before.unattributed);
+    if exceeds(
+        growth as u128,
+        before.total as u128,
+        thresholds.unattributed_growth,
+    ) {
+        findings.push(Finding::UnattributedGrowth {
+            previous_amount: before.unattributed,
+            amount: after.unattributed,
+            positions: after.unattributed_positions,
+        });
+    }
+
+    let drop = before.total.saturating_sub(after.total);
+    if exceeds(drop as u128, before.total as u128, thresholds.total_drop) {
+        findings.push(Finding::TotalDrop {
+            previous: before.total,
+            current: after.total,
+        });
+    }
+
+    AnomalyReport {
+        previous_epoch: previous.epoch,
+        current_epoch: current.epoch,
+        findings,
+    }
+}
+
+// Flags `part` when it is a larger share of `whole` than `threshold`; any non-zero
+// change counts when there was nothing before
+fn exceeds(part: u128, whole: u128, threshold: f64) -> bool {
+    if whole == 0 {
+        return part > 0;
+    }
+    part as f64 / whole as f64 > threshold
+}
+
+struct EpochSummary {
+    // Per user ID, across every position the user's data is held at
+    balances: HashMap<Vec<u8>, u64>,
+    unattributed: u64,
+    unattributed_positions: Vec<usize>,
+    total: u64,
+}
+
+impl EpochSummary {
+    // Amounts saturate, since a snapshot's leaves aren't validated here
+    fn of<C: SumCommitment>(snapshot: &Snapshot<C>) -> Self {
+        let mut summary = EpochSummary {
+            balances: HashMap::new(),
+            unattributed: 0,
+            unattributed_positions: Vec::new(),
+            total: 0,
+        };
+        for (position, leaf) in snapshot.leaves().iter().enumerate() {
+            let amount = leaf.commitment.amount();
+            summary.total = summary.total.saturating_add(amount);
+            match leaf
+                .preimage
+                .as_ref()
+                .and_then(|preimage| preimage.user_id.as_ref())
+            {
+                Some(user_id) => {
+                    let balance = summary.balances.entry(user_id.clone()).or_default();
+                    *balance = balance.saturating_add(amount);
+                }
+                None if leaf.preimage.is_none() && amount > 0 => {
+                    summary.unattributed = summary.unattributed.saturating_add(amount);
+                    summary.unattributed_positions.push(position);
+                }
+                None => {}
+            }
+        }
+        summary
+    }
+}
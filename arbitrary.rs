@@ -0,0 +1,59 @@
+use proptest::collection::vec;
+use proptest::prelude::*;
+
+use crate::{
+    ExclusiveAllotmentProof, LeafCommitment, MerkleProof, MimiLeafCommitment, MimiSumCommitment,
+    MimkMerkleTree,
+};
+
+// Balances stay within u32 so that sums of up to MAX_LEAVES leaves never overflow
+const MAX_BALANCE: u64 = u32::MAX as u64;
+const MAX_LEAVES: usize = 64;
+
+impl Arbitrary for MimiLeafCommitment {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        (0..=MAX_BALANCE)
+            .prop_map(|balance| MimiLeafCommitment::from_leaf(&balance))
+            .boxed()
+    }
+}
+
+impl Arbitrary for MimiSumCommitment {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        any::<MimiLeafCommitment>()
+            .prop_map(|leaf| leaf.to_node())
+            .boxed()
+    }
+}
+
+impl<P> Arbitrary for MimkMerkleTree<MimiSumCommitment, P>
+where
+    P: ExclusiveAllotmentProof<MimiSumCommitment> + 'static,
+{
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        vec(0..=MAX_BALANCE, 1..=MAX_LEAVES)
+            .prop_map(|values| MimkMerkleTree::new(values).expect("balances are bounded"))
+            .boxed()
+    }
+}
+
+// Proofs are taken from an arbitrary tree at an arbitrary position
+impl Arbitrary for MerkleProof<MimiSumCommitment> {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        any::<MimkMerkleTree<MimiSumCommitment, MerkleProof<MimiSumCommitment>>>()
+            .prop_flat_map(|tree| (0..tree.len()).prop_map(move |position| tree.prove(position)))
+            .boxed()
+    }
+}
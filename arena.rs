@@ -0,0 +1,131 @@
+use crate::{LeafCommitment, StorageBackend, SumCommitment};
+
+// Define the NodeArena struct, the tree's nodes computed once at build time and kept in
+// one contiguous Vec per level, root first. Halving the leaf range keeps every level
+// complete down to the first one whose ranges hold one or two leaves, so level d holds
+// exactly 2^d nodes and node index i sits at rank i + 1 - 2^d. Pairs of leaves below the
+// last level are hashed from the leaves when needed.
+#[derive(Debug, Clone)]
+pub struct NodeArena<C: SumCommitment> {
+    levels: Vec<Vec<C>>,
+}
+
+impl<C: SumCommitment> NodeArena<C> {
+    pub fn build(leaves: &[C::Leaf]) -> Self {
+        if leaves.is_empty() {
+            return NodeArena { levels: Vec::new() };
+        }
+        let bottom = bottom_level(leaves.len());
+        let mut ranges = vec![(0, leaves.len())];
+        for _ in 0..bottom {
+            ranges = ranges
+                .iter()
+                .flat_map(|&(start, len)| {
+                    let middle = len / 2;
+                    [(start, middle), (start + middle, len - middle)]
+                })
+                .collect();
+        }
+
+        let mut level: Vec<C> = ranges
+            .iter()
+            .map(|&(start, len)| bottom_node::<C>(leaves, start, len))
+            .collect();
+        let mut levels = Vec::with_capacity(bottom + 1);
+        while level.len() > 1 {
+            let parent = level
+                .chunks_exact(2)
+                .map(|pair| C::combine_commitments(&pair[0], &pair[1]))
+                .collect();
+            levels.push(level);
+            level = parent;
+        }
+        levels.push(level);
+        levels.reverse();
+        NodeArena { levels }
+    }
+
+    // None for trees kept in `StorageBackend::Memory`, which recompute nodes on demand
+    pub(crate) fn for_storage(storage: StorageBackend, leaves: &[C::Leaf]) -> Option<Self> {
+        match storage {
+            StorageBackend::Memory => None,
+            StorageBackend::Arena => Some(Self::build(leaves)),
+        }
+    }
+
+    pub fn root(&self) -> Option<&C> {
+        self.levels.first()?.first()
+    }
+
+    pub fn levels(&self) -> &[Vec<C>] {
+        &self.levels
+    }
+
+    // The node at `node_index`, when it lies on a stored level
+    pub fn node(&self, node_index: usize) -> Option<&C> {
+        let position = node_index.checked_add(1)?;
+        let depth = (usize::BITS - 1 - position.leading_zeros()) as usize;
+        self.levels.get(depth)?.get(position - (1 << depth))
+    }
+}
+
+// Computes the root without recursion, streaming the leaves left to right
+pub fn fold_root<C: SumCommitment>(leaves: &[C::Leaf]) -> Option<C> {
+    fold_shape(
+        leaves.len(),
+        |position| leaves[position].to_node(),
+        C::combine_commitments,
+    )
+}
+
+// Folds `leaf_count` leaves in the tree's halving shape. Ranges are split depth-first
+// down to the last complete level, and the nodes found there are folded like a binary
+// counter, keeping at most one pending node per height.
+pub(crate) fn fold_shape<T>(
+    leaf_count: usize,
+    leaf: impl Fn(usize) -> T,
+    combine: impl Fn(&T, &T) -> T,
+) -> Option<T> {
+    if leaf_count == 0 {
+        return None;
+    }
+    let bottom = bottom_level(leaf_count);
+    let mut ranges = vec![(0, leaf_count, 0)];
+    let mut pending: Vec<(T, usize)> = Vec::with_capacity(bottom + 1);
+    while let Some((start, len, depth)) = ranges.pop() {
+        if depth < bottom {
+            let middle = len / 2;
+            ranges.push((start + middle, len - middle, depth + 1));
+            ranges.push((start, middle, depth + 1));
+            continue;
+        }
+        let mut node = match len {
+            1 => leaf(start),
+            _ => combine(&leaf(start), &leaf(start + 1)),
+        };
+        let mut height = 0;
+        while let Some((left, _)) = pending.last().filter(|(_, pending)| *pending == height) {
+            node = combine(left, &node);
+            pending.pop();
+            height += 1;
+        }
+        pending.push((node, height));
+    }
+    pending.pop().map(|(node, _)| node)
+}
+
+// A range on the last complete level is a single leaf or a pair of leaves
+fn bottom_node<C: SumCommitment>(leaves: &[C::Leaf], start: usize, len: usize) -> C {
+    match len {
+        1 => leaves[start].to_node(),
+        _ => C::combine_commitments(&leaves[start].to_node(), &leaves[start + 1].to_node()),
+    }
+}
+
+// Depth of the last complete level: the first whose leaf ranges are at most two long
+fn bottom_level(leaf_count: usize) -> usize {
+    match leaf_count {
+        0..=2 => 0,
+        len => (usize::BITS - (len - 1).leading_zeros()) as usize - 1,
+    }
+}
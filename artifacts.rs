@@ -0,0 +1,213 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
+
+use ed25519_dalek::{Signature, VerifyingKey};
+use sha2::{Digest as _, Sha256};
+
+use crate::encoding::ByteReader;
+use crate::signer::{Signer, SignerError};
+use crate::{Digest, ParseError};
+
+const ARTIFACT_DOMAIN: &[u8] = b"mimi-artifact-v1";
+
+// Encoded size of an ArtifactSignature: file digest, key, signature
+pub const ARTIFACT_SIGNATURE_LEN: usize = 32 + 32 + 64;
+
+// Detached signatures over epoch artifacts (snapshots, proof archives, manifests) as
+// files, so a mirror or CDN copy can be checked before anything in it is parsed. The
+// native form signs the file's SHA-256 with the operator's Ed25519 signer; OpenPGP
+// signatures are available with the "pgp" feature for distributions that already check
+// those. age only encrypts and defines no signatures, so there is no age variant.
+
+// Streams the file through SHA-256, so archives never have to fit in memory
+pub fn file_digest(path: impl AsRef<Path>) -> io::Result<Digest> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(Digest::new(hasher.finalize().into()))
+}
+
+// Define the ArtifactSignature struct, a detached Ed25519 signature over one file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArtifactSignature {
+    pub digest: Digest,
+    pub key: VerifyingKey,
+    pub signature: Signature,
+}
+
+impl ArtifactSignature {
+    pub fn sign_file(path: impl AsRef<Path>, signer: &dyn Signer) -> Result<Self, ArtifactError> {
+        let digest = file_digest(path).map_err(ArtifactError::Io)?;
+        Ok(ArtifactSignature {
+            digest,
+            key: signer.verifying_key()?,
+            signature: signer.sign(&artifact_payload(&digest))?,
+        })
+    }
+
+    // Checks the signature was made by `key` and covers the file as it is now
+    pub fn verify_file(
+        &self,
+        path: impl AsRef<Path>,
+        key: &VerifyingKey,
+    ) -> Result<(), ArtifactError> {
+        if self.key != *key {
+            return Err(ArtifactError::WrongKey);
+        }
+        key.verify_strict(&artifact_payload(&self.digest), &self.signature)
+            .map_err(|_| ArtifactError::BadSignature)?;
+        if file_digest(path).map_err(ArtifactError::Io)? != self.digest {
+            return Err(ArtifactError::FileChanged);
+        }
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> [u8; ARTIFACT_SIGNATURE_LEN] {
+        let mut out = [0u8; ARTIFACT_SIGNATURE_LEN];
+        out[..32].copy_from_slice(self.digest.as_bytes());
+        out[32..64].copy_from_slice(self.key.as_bytes());
+        out[64..].copy_from_slice(&self.signature.to_bytes());
+        out
+    }
+
+    // Written next to the artifact as `<file>.sig` by convention
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ArtifactError> {
+        fs::write(path, self.to_bytes()).map_err(ArtifactError::Io)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ArtifactError> {
+        let bytes = fs::read(path).map_err(ArtifactError::Io)?;
+        Self::try_from(bytes.as_slice())
+    }
+}
+
+impl TryFrom<&[u8]> for ArtifactSignature {
+    type Error = ArtifactError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        let mut reader = ByteReader::new(bytes);
+        let digest = Digest::new(reader.take_array::<32>()?);
+        let key = VerifyingKey::from_bytes(&reader.take_array::<32>()?)
+            .map_err(|_| ArtifactError::InvalidKey)?;
+        let signature = Signature::from_bytes(&reader.take_array::<64>()?);
+        reader.finish()?;
+        Ok(ArtifactSignature {
+            digest,
+            key,
+            signature,
+        })
+    }
+}
+
+fn artifact_payload(digest: &Digest) -> Vec<u8> {
+    let mut payload = ARTIFACT_DOMAIN.to_vec();
+    payload.extend_from_slice(digest.as_bytes());
+    payload
+}
+
+#[cfg(feature = "pgp")]
+pub use openpgp::{sign_file_pgp, verify_file_pgp};
+
+#[cfg(feature = "pgp")]
+mod openpgp {
+    use std::fs::File;
+    use std::io::BufReader;
+    use std::path::Path;
+
+    use chrono::SubsecRound;
+    use pgp::composed::{Deserializable, SignedPublicKey, SignedSecretKey, StandaloneSignature};
+    use pgp::crypto::hash::HashAlgorithm;
+    use pgp::packet::{SignatureConfig, SignatureType, SignatureVersion, Subpacket, SubpacketData};
+    use pgp::types::{KeyTrait, SecretKeyTrait};
+
+    use super::ArtifactError;
+
+    // ASCII-armored detached binary signature, as `gpg --detach-sign --armor` makes
+    pub fn sign_file_pgp(
+        path: impl AsRef<Path>,
+        key: &SignedSecretKey,
+        passphrase: &str,
+    ) -> Result<String, ArtifactError> {
+        let config = SignatureConfig::new_v4(
+            SignatureVersion::V4,
+            SignatureType::Binary,
+            key.algorithm(),
+            HashAlgorithm::SHA2_256,
+            vec![
+                Subpacket::regular(SubpacketData::SignatureCreationTime(
+                    chrono::Utc::now().trunc_subsecs(0),
+                )),
+                Subpacket::regular(SubpacketData::Issuer(key.key_id())),
+            ],
+            Vec::new(),
+        );
+        let file = BufReader::new(File::open(path).map_err(ArtifactError::Io)?);
+        let signature = config
+            .sign(key, || passphrase.to_string(), file)
+            .map_err(pgp_error)?;
+        StandaloneSignature::new(signature)
+            .to_armored_string(None.into())
+            .map_err(pgp_error)
+    }
+
+    // `gpg --verify <signature> <file>` with only `key` trusted
+    pub fn verify_file_pgp(
+        path: impl AsRef<Path>,
+        armored: &str,
+        key: &SignedPublicKey,
+    ) -> Result<(), ArtifactError> {
+        let (signature, _) = StandaloneSignature::from_string(armored).map_err(pgp_error)?;
+        let file = BufReader::new(File::open(path).map_err(ArtifactError::Io)?);
+        signature
+            .verify(key, file)
+            .map_err(|_| ArtifactError::BadSignature)
+    }
+
+    fn pgp_error(err: pgp::errors::Error) -> ArtifactError {
+        ArtifactError::Pgp(err.to_string())
+    }
+}
+
+// Define the ArtifactError enum for artifact signatures that can't be made or don't hold
+#[derive(Debug)]
+pub enum ArtifactError {
+    Io(io::Error),
+    Parse(ParseError),
+    Signer(SignerError),
+    InvalidKey,
+    WrongKey,
+    BadSignature,
+    // The signature is good but the file has changed since it was signed
+    FileChanged,
+    Pgp(String),
+}
+
+impl fmt::Display for ArtifactError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArtifactError::Io(err) => write!(f, "i/o error: {}", err),
+            ArtifactError::Parse(err) => write!(f, "malformed artifact signature: {}", err),
+            ArtifactError::Signer(err) => write!(f, "{}", err),
+            ArtifactError::InvalidKey => write!(f, "invalid Ed25519 public key"),
+            ArtifactError::WrongKey => write!(f, "artifact was signed by another key"),
+            ArtifactError::BadSignature => write!(f, "artifact signature does not verify"),
+            ArtifactError::FileChanged => write!(f, "file differs from the one signed"),
+            ArtifactError::Pgp(reason) => write!(f, "OpenPGP error: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for ArtifactError {}
+
+impl From<ParseError> for ArtifactError {
+    fn from(err: ParseError) -> Self {
+        ArtifactError::Parse(err)
+    }
+}
+
+impl From<SignerError> for ArtifactError {
+    fn from(err: SignerError) -> Self {
+        ArtifactError::Signer(err)
+    }
+}
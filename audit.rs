@@ -0,0 +1,475 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use ed25519_dalek::{Signature, VerifyingKey};
+use serde::{Deserialize, Serialize};
+
+use crate::builder::derive_salt;
+use crate::config::SaltDerivation;
+use crate::entropy::{EntropySource, SeededEntropy};
+use crate::leaf::MergedAccount;
+use crate::report::CheckResult;
+use crate::signer::{Signer, SignerError};
+use crate::snapshot::{LeafPreimage, Snapshot, SnapshotError};
+use crate::{ExclusiveAllotmentProof, Leaf, LeafCommitment, MerkleProof, Root, SumCommitment};
+
+const ATTESTATION_DOMAIN: &[u8] = b"mimi-audit-attestation-v1";
+// Failing positions listed in a check's detail before the rest are only counted
+const REPORTED_POSITIONS: usize = 10;
+
+// Define the AuditOptions struct, what the auditor was given beyond the snapshot itself
+#[derive(Debug, Clone)]
+pub struct AuditOptions {
+    // The salt seed disclosed to the auditor, needed to recompute salted leaf digests
+    pub salt_derivation: SaltDerivation,
+    // Largest balance a single leaf may hold, when the operator publishes one
+    pub max_leaf_amount: Option<u64>,
+}
+
+impl Default for AuditOptions {
+    fn default() -> Self {
+        AuditOptions {
+            salt_derivation: SaltDerivation::None,
+            max_leaf_amount: None,
+        }
+    }
+}
+
+// Define the AuditReport struct, the outcome of a full recomputation of one epoch's tree.
+// Roots are written as `<amount>:<hex digest>`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditReport {
+    pub epoch: u64,
+    pub published_root: String,
+    pub recomputed_root: String,
+    pub leaf_count: usize,
+    // Leaves whose commitment was recomputed from the stored account data
+    pub leaves_checked: usize,
+    pub tombstoned: usize,
+    // None when the leaf amounts overflow
+    pub total: Option<u64>,
+    pub checks: Vec<CheckResult>,
+    pub passed: bool,
+}
+
+pub fn verify_snapshot<C: SumCommitment>(
+    snapshot: &Snapshot<C>,
+    published_root: &Root<C>,
+) -> Result<AuditReport, AuditError> {
+    verify_snapshot_with(snapshot, published_root, &AuditOptions::default())
+}
+
+// Mismatches are recorded in `checks` rather than returned; only a snapshot that can't
+// be rebuilt at all is an error
+pub fn verify_snapshot_with<C: SumCommitment>(
+    snapshot: &Snapshot<C>,
+    published_root: &Root<C>,
+    options: &AuditOptions,
+) -> Result<AuditReport, AuditError> {
+    let recomputed = snapshot.to_tree::<MerkleProof<C>>()?.commit();
+    let mut checks = vec![CheckResult::from_result(
+        "root".to_string(),
+        published_root.check(recomputed.node()),
+    )];
+
+    let total = snapshot
+        .leaves()
+        .iter()
+        .try_fold(0u64, |sum, leaf| sum.checked_add(leaf.commitment.amount()));
+    let total_check = match total {
+        Some(total) if total == published_root.amount() => Ok(()),
+        Some(total) => Err(format!(
+            "leaves sum to {} but the root claims {}",
+            total,
+            published_root.amount()
+        )),
+        None => Err("leaf amounts overflow".to_string()),
+    };
+    checks.push(CheckResult::from_result("total".to_string(), total_check));
+
+    let mut leaves_checked = 0;
+    let mut tombstoned = 0;
+    let mut bad_preimages = Vec::new();
+    let mut out_of_range = Vec::new();
+    for (position, leaf) in snapshot.leaves().iter().enumerate() {
+        let amount = leaf.commitment.amount();
+        if options.max_leaf_amount.is_some_and(|max| amount > max) {
+            out_of_range.push(position);
+        }
+        let Some(preimage) = &leaf.preimage else {
+            tombstoned += 1;
+            continue;
+        };
+        leaves_checked += 1;
+        let expected = recompute_leaf::<C>(position, amount, preimage, options);
+        if expected.is_none_or(|expected| expected.digest() != leaf.commitment.digest()) {
+            bad_preimages.push(position);
+        }
+    }
+    checks.push(CheckResult::from_result(
+        "preimages".to_string(),
+        failing_positions(&bad_preimages, "commitments don't match their account data"),
+    ));
+    if let Some(max) = options.max_leaf_amount {
+        checks.push(CheckResult::from_result(
+            "ranges".to_string(),
+            failing_positions(&out_of_range, &format!("balances above {}", max)),
+        ));
+    }
+
+    Ok(AuditReport {
+        epoch: snapshot.epoch,
+        published_root: published_root.to_string(),
+        recomputed_root: recomputed.to_string(),
+        leaf_count: snapshot.len(),
+        leaves_checked,
+        tombstoned,
+        total,
+        passed: checks.iter().all(|check| check.passed),
+        checks,
+    })
+}
+
+// Rebuilds the commitment the builder would have made for this account data. Several
+// records under one position were merged into a single balance for their user ID.
+fn recompute_leaf<C: SumCommitment>(
+    position: usize,
+    amount: u64,
+    preimage: &LeafPreimage,
+    options: &AuditOptions,
+) -> Option<C::Leaf> {
+    let salt = match &options.salt_derivation {
+        SaltDerivation::None => None,
+        SaltDerivation::FromSeed(seed) => Some(derive_salt(seed, position)),
+    };
+    match preimage.records.as_slice() {
+        [record] => Some(commit_with::<C, _>(
+            &PreimageLeaf {
+                amount,
+                encoding: record,
+            },
+            salt.as_ref(),
+        )),
+        [_, _, ..] => Some(commit_with::<C, _>(
+            &MergedAccount {
+                user_id: preimage.user_id.as_deref()?,
+                balance: amount,
+                // Not part of the hash, so the preimage doesn't need to carry it
+                asset: None,
+            },
+            salt.as_ref(),
+        )),
+        [] => None,
+    }
+}
+
+fn commit_with<C: SumCommitment, L: Leaf>(leaf: &L, salt: Option<&[u8; 32]>) -> C::Leaf {
+    match salt {
+        Some(salt) => C::Leaf::from_salted_leaf(leaf, salt),
+        None => C::Leaf::from_leaf(leaf),
+    }
+}
+
+fn failing_positions(positions: &[usize], what: &str) -> Result<(), String> {
+    if positions.is_empty() {
+        return Ok(());
+    }
+    let listed: Vec<String> = positions
+        .iter()
+        .take(REPORTED_POSITIONS)
+        .map(usize::to_string)
+        .collect();
+    let more = positions.len().saturating_sub(REPORTED_POSITIONS);
+    let mut detail = format!(
+        "{} {} at positions {}",
+        positions.len(),
+        what,
+        listed.join(", ")
+    );
+    if more > 0 {
+        detail.push_str(&format!(" and {} more", more));
+    }
+    Err(detail)
+}
+
+// Define the PreimageLeaf struct, a stored leaf encoding replayed through the commitment.
+// Snapshots don't keep leaf categories, so category trees replay every leaf as Spot.
+struct PreimageLeaf<'a> {
+    amount: u64,
+    encoding: &'a [u8],
+}
+
+impl Leaf for PreimageLeaf<'_> {
+    fn amount(&self) -> u64 {
+        self.amount
+    }
+
+    fn encode_for_hash(&self) -> Vec<u8> {
+        self.encoding.to_vec()
+    }
+}
+
+// Define the SampleParams struct, how many leaves a sampled audit checks and the
+// confidence its bound on invalid leaves is stated at
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SampleParams {
+    pub sample_size: usize,
+    // One-sided, strictly between 0 and 1
+    pub confidence: f64,
+}
+
+impl SampleParams {
+    // The smallest sample that, if it finds no invalid leaf, shows fewer than
+    // `max_invalid_fraction` of the leaves are invalid at `confidence`
+    pub fn for_bound(max_invalid_fraction: f64, confidence: f64) -> Result<Self, AuditError> {
+        check_probability("max_invalid_fraction", max_invalid_fraction)?;
+        check_probability("confidence", confidence)?;
+        let sample_size = ((1.0 - confidence).ln() / (1.0 - max_invalid_fraction).ln()).ceil();
+        Ok(SampleParams {
+            sample_size: sample_size as usize,
+            confidence,
+        })
+    }
+}
+
+// Define the SampledAuditReport struct, the outcome of checking a random sample of leaves
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SampledAuditReport {
+    pub published_root: String,
+    // Hex of the public randomness the sample was drawn from
+    pub randomness: String,
+    pub leaf_count: usize,
+    pub sampled: usize,
+    // Sampled positions whose proof was missing, for another leaf or didn't verify
+    pub invalid_positions: Vec<usize>,
+    pub confidence: f64,
+    // Upper bound on the fraction of invalid leaves in the whole tree at `confidence`
+    pub max_invalid_fraction: f64,
+    pub passed: bool,
+}
+
+// Positions a sampled audit checks, drawn without replacement from `randomness` (a
+// beacon value or block hash fixed after the root was published), so anyone can redraw
+// the same sample
+pub fn sample_positions(leaf_count: usize, randomness: [u8; 32], sample_size: usize) -> Vec<usize> {
+    if sample_size >= leaf_count {
+        return (0..leaf_count).collect();
+    }
+    let mut entropy = SeededEntropy::new(randomness);
+    let mut drawn = HashSet::with_capacity(sample_size);
+    let mut positions = Vec::with_capacity(sample_size);
+    while positions.len() < sample_size {
+        let position = uniform_below(&mut entropy, leaf_count as u64) as usize;
+        if drawn.insert(position) {
+            positions.push(position);
+        }
+    }
+    positions
+}
+
+// Checks a proof for every sampled position against the published root. `fetch_proof`
+// asks the operator for a position's proof; None counts that leaf as invalid.
+pub fn sampled_audit<C, P, F>(
+    published_root: &Root<C>,
+    leaf_count: usize,
+    randomness: [u8; 32],
+    params: &SampleParams,
+    mut fetch_proof: F,
+) -> Result<SampledAuditReport, AuditError>
+where
+    C: SumCommitment,
+    P: ExclusiveAllotmentProof<C>,
+    F: FnMut(usize) -> Option<P>,
+{
+    check_probability("confidence", params.confidence)?;
+    let positions = sample_positions(leaf_count, randomness, params.sample_size);
+    let invalid_positions: Vec<usize> = positions
+        .iter()
+        .copied()
+        .filter(|&position| {
+            !fetch_proof(position).is_some_and(|proof| {
+                proof.position() == position && proof.check(published_root).is_ok()
+            })
+        })
+        .collect();
+    let max_invalid_fraction = if positions.len() == leaf_count {
+        // Every leaf was checked, so the fraction is known exactly
+        invalid_positions.len() as f64 / leaf_count.max(1) as f64
+    } else {
+        clopper_pearson_upper(invalid_positions.len(), positions.len(), params.confidence)
+    };
+    Ok(SampledAuditReport {
+        published_root: published_root.to_string(),
+        randomness: hex::encode(randomness),
+        leaf_count,
+        sampled: positions.len(),
+        passed: invalid_positions.is_empty(),
+        invalid_positions,
+        confidence: params.confidence,
+        max_invalid_fraction,
+    })
+}
+
+// Rejection sampling, so every position is equally likely
+fn uniform_below(entropy: &mut SeededEntropy, bound: u64) -> u64 {
+    let zone = u64::MAX - u64::MAX % bound;
+    loop {
+        let value = entropy.next_u64();
+        if value < zone {
+            return value % bound;
+        }
+    }
+}
+
+// One-sided Clopper-Pearson upper bound on a binomial proportion after `failures` in
+// `trials`. Sampling without replacement only tightens it, so the bound stays valid.
+fn clopper_pearson_upper(failures: usize, trials: usize, confidence: f64) -> f64 {
+    if trials == 0 || failures >= trials {
+        return 1.0;
+    }
+    let alpha = 1.0 - confidence;
+    let (mut low, mut high) = (failures as f64 / trials as f64, 1.0);
+    // The binomial CDF falls as p rises; bisect for CDF(failures; trials, p) = alpha
+    for _ in 0..100 {
+        let middle = (low + high) / 2.0;
+        if binomial_cdf(failures, trials, middle) > alpha {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    high
+}
+
+// Summed in log space so large trials don't underflow
+fn binomial_cdf(k: usize, n: usize, p: f64) -> f64 {
+    if p >= 1.0 {
+        return 0.0;
+    }
+    let (ln_p, ln_q) = (p.ln(), (-p).ln_1p());
+    let mut ln_choose = 0.0;
+    let mut cdf = 0.0;
+    for i in 0..=k {
+        if i > 0 {
+            ln_choose += ((n - i + 1) as f64).ln() - (i as f64).ln();
+        }
+        cdf += (ln_choose + i as f64 * ln_p + (n - i) as f64 * ln_q).exp();
+    }
+    cdf.min(1.0)
+}
+
+fn check_probability(name: &str, value: f64) -> Result<(), AuditError> {
+    if value > 0.0 && value < 1.0 {
+        Ok(())
+    } else {
+        Err(AuditError::InvalidParameter(format!(
+            "{} must be strictly between 0 and 1",
+            name
+        )))
+    }
+}
+
+// Define the AuditAttestation struct, an audit report signed by the auditor's key
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditAttestation {
+    pub report: AuditReport,
+    pub auditor_key: String,
+    pub signature: String,
+}
+
+impl AuditAttestation {
+    pub fn sign(report: AuditReport, signer: &dyn Signer) -> Result<Self, AuditError> {
+        let payload = attestation_payload(&report)?;
+        let auditor_key = signer.verifying_key()?;
+        let signature = signer.sign(&payload)?;
+        auditor_key
+            .verify_strict(&payload, &signature)
+            .map_err(|_| SignerError::InvalidSignature)?;
+        Ok(AuditAttestation {
+            report,
+            auditor_key: hex::encode(auditor_key.as_bytes()),
+            signature: hex::encode(signature.to_bytes()),
+        })
+    }
+
+    pub fn verify(&self, auditor_key: &VerifyingKey) -> Result<(), AuditError> {
+        if hex::encode(auditor_key.as_bytes()) != self.auditor_key {
+            return Err(AuditError::UnexpectedKey);
+        }
+        let signature: [u8; 64] = hex::decode(&self.signature)
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or(AuditError::BadSignature)?;
+        auditor_key
+            .verify_strict(
+                &attestation_payload(&self.report)?,
+                &Signature::from_bytes(&signature),
+            )
+            .map_err(|_| AuditError::BadSignature)
+    }
+
+    pub fn to_json(&self) -> Result<String, AuditError> {
+        serde_json::to_string_pretty(self).map_err(AuditError::Json)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, AuditError> {
+        serde_json::from_str(json).map_err(AuditError::Json)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), AuditError> {
+        fs::write(path, self.to_json()?).map_err(AuditError::Io)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, AuditError> {
+        Self::from_json(&fs::read_to_string(path).map_err(AuditError::Io)?)
+    }
+}
+
+// The domain label followed by the report's compact JSON
+fn attestation_payload(report: &AuditReport) -> Result<Vec<u8>, AuditError> {
+    let mut payload = ATTESTATION_DOMAIN.to_vec();
+    payload.extend_from_slice(&serde_json::to_vec(report).map_err(AuditError::Json)?);
+    Ok(payload)
+}
+
+// Define the AuditError enum for audits that can't be run, signed or checked
+#[derive(Debug)]
+pub enum AuditError {
+    Snapshot(SnapshotError),
+    Signer(SignerError),
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    InvalidParameter(String),
+    UnexpectedKey,
+    BadSignature,
+}
+
+impl fmt::Display for AuditError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuditError::Snapshot(err) => write!(f, "{}", err),
+            AuditError::Signer(err) => write!(f, "{}", err),
+            AuditError::Io(err) => write!(f, "i/o error: {}", err),
+            AuditError::Json(err) => write!(f, "json error: {}", err),
+            AuditError::InvalidParameter(reason) => write!(f, "{}", reason),
+            AuditError::UnexpectedKey => write!(f, "attestation was signed by another auditor"),
+            AuditError::BadSignature => write!(f, "attestation signature does not verify"),
+        }
+    }
+}
+
+impl std::error::Error for AuditError {}
+
+impl From<SnapshotError> for AuditError {
+    fn from(err: SnapshotError) -> Self {
+        AuditError::Snapshot(err)
+    }
+}
+
+impl From<SignerError> for AuditError {
+    fn from(err: SignerError) -> Self {
+        AuditError::Signer(err)
+    }
+}
@@ -0,0 +1,444 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use sha2::Digest as _;
+use sha3::Keccak256;
+
+// keccak256(rlp("")), the root of a trie with no entries
+pub const EMPTY_TRIE_ROOT: [u8; 32] = [
+    0x56, 0xe8, 0x1f, 0x17, 0x1b, 0xcc, 0x55, 0xa6, 0xff, 0x83, 0x45, 0xe6, 0x92, 0xc0, 0xf8,
+    0x6e, 0x5b, 0x48, 0xe0, 0x1b, 0x99, 0x6c, 0xad, 0xc0, 0x01, 0x62, 0x2f, 0xb5, 0xe3, 0x63,
+    0xb4, 0x21,
+];
+
+// A trie entry keyed by its nibble path
+type NibbleEntry<'v> = (Vec<u8>, &'v [u8]);
+
+// Define the AuthenticatedMap trait, a key-value map committed to by a 32-byte root, with
+// proofs that a key holds a value (or is absent) under that root
+pub trait AuthenticatedMap {
+    type Proof;
+
+    fn get(&self, key: &[u8]) -> Option<&[u8]>;
+    fn insert(&mut self, key: &[u8], value: Vec<u8>) -> Option<Vec<u8>>;
+    fn remove(&mut self, key: &[u8]) -> Option<Vec<u8>>;
+    fn root(&self) -> [u8; 32];
+    fn prove(&self, key: &[u8]) -> Self::Proof;
+}
+
+// Define the PatriciaTrie struct, Ethereum's Merkle Patricia Trie. Roots and proofs match
+// what clients compute, so a proof from `prove` checks like an `eth_getProof` one; the
+// secure variant hashes keys with keccak256 as state and storage tries do. Values are
+// stored as given, so storage slots should be RLP-encoded first. Nodes are rebuilt from
+// the sorted entries on each `root` or `prove`, which suits maps built once and proven
+// from, not ones updated per block.
+#[derive(Debug, Clone, Default)]
+pub struct PatriciaTrie {
+    // Keyed by the trie path, i.e. the hashed key for secure tries
+    entries: BTreeMap<Vec<u8>, Vec<u8>>,
+    secure: bool,
+}
+
+impl PatriciaTrie {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn secure() -> Self {
+        PatriciaTrie {
+            entries: BTreeMap::new(),
+            secure: true,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn path(&self, key: &[u8]) -> Vec<u8> {
+        if self.secure {
+            keccak(key).to_vec()
+        } else {
+            key.to_vec()
+        }
+    }
+
+    // Sorted (nibble path, value) pairs; byte order and nibble order agree
+    fn nibble_entries(&self) -> Vec<NibbleEntry<'_>> {
+        self.entries
+            .iter()
+            .map(|(path, value)| (nibbles(path), value.as_slice()))
+            .collect()
+    }
+}
+
+impl AuthenticatedMap for PatriciaTrie {
+    // RLP-encoded nodes from the root down, as `eth_getProof` returns them
+    type Proof = Vec<Vec<u8>>;
+
+    fn get(&self, key: &[u8]) -> Option<&[u8]> {
+        self.entries.get(&self.path(key)).map(Vec::as_slice)
+    }
+
+    // An empty value removes the key, as in Ethereum
+    fn insert(&mut self, key: &[u8], value: Vec<u8>) -> Option<Vec<u8>> {
+        if value.is_empty() {
+            return self.remove(key);
+        }
+        self.entries.insert(self.path(key), value)
+    }
+
+    fn remove(&mut self, key: &[u8]) -> Option<Vec<u8>> {
+        self.entries.remove(&self.path(key))
+    }
+
+    fn root(&self) -> [u8; 32] {
+        let entries = self.nibble_entries();
+        if entries.is_empty() {
+            return EMPTY_TRIE_ROOT;
+        }
+        keccak(&encode_node(&entries, 0))
+    }
+
+    fn prove(&self, key: &[u8]) -> Vec<Vec<u8>> {
+        let entries = self.nibble_entries();
+        let mut proof = Vec::new();
+        if entries.is_empty() {
+            return proof;
+        }
+        let path = nibbles(&self.path(key));
+        let (mut range, mut depth) = (&entries[..], 0);
+        loop {
+            let node = encode_node(range, depth);
+            // Nodes under 32 bytes are embedded in their parent; the root never is
+            if proof.is_empty() || node.len() >= 32 {
+                proof.push(node);
+            }
+            match descend(range, depth, &path) {
+                Some((next, next_depth)) => (range, depth) = (next, next_depth),
+                None => return proof,
+            }
+        }
+    }
+}
+
+// The entries under the child `encode_node(entries, depth)` follows for `path`, and the
+// child's depth; None once the path ends at or leaves this node
+fn descend<'a, 'v>(
+    entries: &'a [NibbleEntry<'v>],
+    depth: usize,
+    path: &[u8],
+) -> Option<(&'a [NibbleEntry<'v>], usize)> {
+    if entries.len() == 1 {
+        return None;
+    }
+    let shared = shared_prefix(entries, depth);
+    if shared > 0 {
+        let matches = path.len() >= depth + shared
+            && path[depth..depth + shared] == entries[0].0[depth..depth + shared];
+        return matches.then_some((entries, depth + shared));
+    }
+    let nibble = *path.get(depth)?;
+    let child: Vec<_> = (0..entries.len())
+        .filter(|&index| entries[index].0.get(depth) == Some(&nibble))
+        .collect();
+    let (first, last) = (*child.first()?, *child.last()?);
+    Some((&entries[first..=last], depth + 1))
+}
+
+// Length of the nibble prefix every entry shares past `depth`. Entries are sorted, so the
+// first and last bound it.
+fn shared_prefix(entries: &[NibbleEntry<'_>], depth: usize) -> usize {
+    let (first, last) = (&entries[0].0[depth..], &entries[entries.len() - 1].0[depth..]);
+    first
+        .iter()
+        .zip(last)
+        .take_while(|(a, b)| a == b)
+        .count()
+}
+
+// RLP of the node holding `entries`, all of which share their first `depth` nibbles
+fn encode_node(entries: &[NibbleEntry<'_>], depth: usize) -> Vec<u8> {
+    if let [(path, value)] = entries {
+        let mut items = rlp_bytes(&hex_prefix(&path[depth..], true));
+        items.extend(rlp_bytes(value));
+        return rlp_list(&items);
+    }
+    let shared = shared_prefix(entries, depth);
+    if shared > 0 {
+        let mut items = rlp_bytes(&hex_prefix(&entries[0].0[depth..depth + shared], false));
+        items.extend(child_reference(&encode_node(entries, depth + shared)));
+        return rlp_list(&items);
+    }
+    let mut items = Vec::new();
+    let mut rest = entries;
+    // A key ending here is the branch's value; it sorts first
+    let value = match rest.first() {
+        Some((path, value)) if path.len() == depth => {
+            rest = &rest[1..];
+            Some(*value)
+        }
+        _ => None,
+    };
+    for nibble in 0..16u8 {
+        let count = rest.iter().take_while(|(path, _)| path[depth] == nibble).count();
+        let (child, tail) = rest.split_at(count);
+        rest = tail;
+        if child.is_empty() {
+            items.push(0x80);
+        } else {
+            items.extend(child_reference(&encode_node(child, depth + 1)));
+        }
+    }
+    items.extend(rlp_bytes(value.unwrap_or_default()));
+    rlp_list(&items)
+}
+
+// Short nodes are inlined, longer ones referenced by hash
+fn child_reference(node: &[u8]) -> Vec<u8> {
+    if node.len() < 32 {
+        node.to_vec()
+    } else {
+        rlp_bytes(&keccak(node))
+    }
+}
+
+// Checks an `eth_getProof`-style proof for `key` against `root`. Returns the value, or
+// None when the proof shows the key is absent. Set `secure` for state and storage tries.
+pub fn verify_trie_proof(
+    root: &[u8; 32],
+    key: &[u8],
+    proof: &[Vec<u8>],
+    secure: bool,
+) -> Result<Option<Vec<u8>>, TrieError> {
+    let path = if secure {
+        nibbles(&keccak(key))
+    } else {
+        nibbles(key)
+    };
+    if proof.is_empty() {
+        if *root == EMPTY_TRIE_ROOT {
+            return Ok(None);
+        }
+        return Err(TrieError::MissingNode);
+    }
+    let mut proof = proof.iter();
+    let mut expected = Reference::Hash(*root);
+    let mut depth = 0;
+    loop {
+        let node = match expected {
+            Reference::Hash(hash) => {
+                let node = proof.next().ok_or(TrieError::MissingNode)?;
+                if keccak(node) != hash {
+                    return Err(TrieError::HashMismatch);
+                }
+                node.as_slice()
+            }
+            Reference::Embedded(node) => node,
+        };
+        let items = match decode(node)? {
+            (Rlp::List(items), []) => items,
+            _ => return Err(TrieError::Malformed),
+        };
+        let next = match items.as_slice() {
+            [(Rlp::Bytes(encoded_path), _), (value, raw)] => {
+                let (segment, leaf) = decode_hex_prefix(encoded_path)?;
+                let remaining = &path[depth..];
+                if leaf {
+                    let Rlp::Bytes(value) = value else {
+                        return Err(TrieError::Malformed);
+                    };
+                    let found = (remaining == segment.as_slice()).then(|| value.to_vec());
+                    return finish(proof, found);
+                }
+                if !remaining.starts_with(&segment) {
+                    return finish(proof, None);
+                }
+                depth += segment.len();
+                reference(value, raw)?
+            }
+            branch if branch.len() == 17 => {
+                let Some(&nibble) = path.get(depth) else {
+                    let Rlp::Bytes(value) = &branch[16].0 else {
+                        return Err(TrieError::Malformed);
+                    };
+                    let found = (!value.is_empty()).then(|| value.to_vec());
+                    return finish(proof, found);
+                };
+                let (child, raw) = &branch[nibble as usize];
+                if matches!(child, Rlp::Bytes(bytes) if bytes.is_empty()) {
+                    return finish(proof, None);
+                }
+                depth += 1;
+                reference(child, raw)?
+            }
+            _ => return Err(TrieError::Malformed),
+        };
+        expected = next;
+    }
+}
+
+// Every proof node has to be used, so nothing unverified rides along
+fn finish<'a>(
+    mut rest: impl Iterator<Item = &'a Vec<u8>>,
+    found: Option<Vec<u8>>,
+) -> Result<Option<Vec<u8>>, TrieError> {
+    match rest.next() {
+        Some(_) => Err(TrieError::UnusedNodes),
+        None => Ok(found),
+    }
+}
+
+enum Reference<'a> {
+    Hash([u8; 32]),
+    Embedded(&'a [u8]),
+}
+
+fn reference<'a>(item: &Rlp<'a>, raw: &'a [u8]) -> Result<Reference<'a>, TrieError> {
+    match item {
+        Rlp::Bytes(hash) => {
+            let hash = <[u8; 32]>::try_from(*hash).map_err(|_| TrieError::Malformed)?;
+            Ok(Reference::Hash(hash))
+        }
+        Rlp::List(_) if raw.len() < 32 => Ok(Reference::Embedded(raw)),
+        Rlp::List(_) => Err(TrieError::Malformed),
+    }
+}
+
+fn keccak(bytes: &[u8]) -> [u8; 32] {
+    Keccak256::digest(bytes).into()
+}
+
+fn nibbles(bytes: &[u8]) -> Vec<u8> {
+    bytes.iter().flat_map(|byte| [byte >> 4, byte & 0x0f]).collect()
+}
+
+// Compact path encoding: a flag nibble (2 for leaves, plus 1 for odd lengths), padded
+// with a zero nibble when even, then the path packed two nibbles to a byte
+fn hex_prefix(path: &[u8], leaf: bool) -> Vec<u8> {
+    let flag = if leaf { 2 } else { 0 } + (path.len() % 2) as u8;
+    let mut padded = vec![flag];
+    if path.len().is_multiple_of(2) {
+        padded.push(0);
+    }
+    padded.extend_from_slice(path);
+    padded.chunks(2).map(|pair| (pair[0] << 4) | pair[1]).collect()
+}
+
+fn decode_hex_prefix(encoded: &[u8]) -> Result<(Vec<u8>, bool), TrieError> {
+    let all = nibbles(encoded);
+    let flag = *all.first().ok_or(TrieError::Malformed)?;
+    if flag > 3 {
+        return Err(TrieError::Malformed);
+    }
+    let skip = if flag % 2 == 1 { 1 } else { 2 };
+    if skip == 2 && all.get(1) != Some(&0) {
+        return Err(TrieError::Malformed);
+    }
+    Ok((all[skip..].to_vec(), flag >= 2))
+}
+
+fn rlp_length(len: usize, short: u8, long: u8) -> Vec<u8> {
+    if len < 56 {
+        return vec![short + len as u8];
+    }
+    let bytes = (len as u64).to_be_bytes();
+    let skip = bytes.iter().take_while(|byte| **byte == 0).count();
+    let mut header = vec![long + (8 - skip) as u8];
+    header.extend_from_slice(&bytes[skip..]);
+    header
+}
+
+fn rlp_bytes(bytes: &[u8]) -> Vec<u8> {
+    if let [byte] = bytes {
+        if *byte < 0x80 {
+            return vec![*byte];
+        }
+    }
+    let mut out = rlp_length(bytes.len(), 0x80, 0xb7);
+    out.extend_from_slice(bytes);
+    out
+}
+
+fn rlp_list(payload: &[u8]) -> Vec<u8> {
+    let mut out = rlp_length(payload.len(), 0xc0, 0xf7);
+    out.extend_from_slice(payload);
+    out
+}
+
+// A decoded RLP item; list items keep their raw encoding for embedded nodes
+enum Rlp<'a> {
+    Bytes(&'a [u8]),
+    List(Vec<(Rlp<'a>, &'a [u8])>),
+}
+
+// Decodes one item and returns it with the bytes after it
+fn decode(bytes: &[u8]) -> Result<(Rlp<'_>, &[u8]), TrieError> {
+    let (&prefix, rest) = bytes.split_first().ok_or(TrieError::Malformed)?;
+    let (is_list, len, rest) = match prefix {
+        0x00..=0x7f => return Ok((Rlp::Bytes(&bytes[..1]), rest)),
+        0x80..=0xb7 => (false, (prefix - 0x80) as usize, rest),
+        0xb8..=0xbf => {
+            let (len, rest) = long_length(rest, prefix - 0xb7)?;
+            (false, len, rest)
+        }
+        0xc0..=0xf7 => (true, (prefix - 0xc0) as usize, rest),
+        0xf8..=0xff => {
+            let (len, rest) = long_length(rest, prefix - 0xf7)?;
+            (true, len, rest)
+        }
+    };
+    if rest.len() < len {
+        return Err(TrieError::Malformed);
+    }
+    let (payload, rest) = rest.split_at(len);
+    if !is_list {
+        return Ok((Rlp::Bytes(payload), rest));
+    }
+    let mut items = Vec::new();
+    let mut remaining = payload;
+    while !remaining.is_empty() {
+        let (item, after) = decode(remaining)?;
+        items.push((item, &remaining[..remaining.len() - after.len()]));
+        remaining = after;
+    }
+    Ok((Rlp::List(items), rest))
+}
+
+fn long_length(bytes: &[u8], count: u8) -> Result<(usize, &[u8]), TrieError> {
+    let count = count as usize;
+    if count > 8 || bytes.len() < count {
+        return Err(TrieError::Malformed);
+    }
+    let len = bytes[..count]
+        .iter()
+        .fold(0u64, |len, byte| (len << 8) | *byte as u64);
+    let len = usize::try_from(len).map_err(|_| TrieError::Malformed)?;
+    Ok((len, &bytes[count..]))
+}
+
+// Define the TrieError enum for trie proofs that don't hold
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrieError {
+    Malformed,
+    MissingNode,
+    HashMismatch,
+    UnusedNodes,
+}
+
+impl fmt::Display for TrieError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrieError::Malformed => write!(f, "malformed trie node"),
+            TrieError::MissingNode => write!(f, "proof is missing a trie node"),
+            TrieError::HashMismatch => write!(f, "trie node does not match its hash"),
+            TrieError::UnusedNodes => write!(f, "proof carries nodes off the key's path"),
+        }
+    }
+}
+
+impl std::error::Error for TrieError {}
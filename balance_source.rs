@@ -0,0 +1,389 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::future::Future;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use futures::executor::block_on;
+use futures::stream::{self, BoxStream, StreamExt};
+use sha2::{Digest as _, Sha256};
+
+use crate::amount_format::{AmountError, AmountFormat};
+use crate::scheduler::DataSource;
+use crate::{AccountRecord, UserLeaf};
+
+pub const CSV_HEADER: &str = "user_id,asset,amount";
+
+// Define the BalanceEntry struct, one (user, asset, amount) row of the ledger
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BalanceEntry {
+    pub user_id: String,
+    pub asset: String,
+    pub amount: u64,
+}
+
+impl BalanceEntry {
+    // The entry as a tree input, stamped with the time its point was taken
+    pub fn into_leaf(self, point: &SnapshotPoint) -> UserLeaf<AccountRecord> {
+        UserLeaf {
+            user_id: self.user_id,
+            record: AccountRecord {
+                balance: self.amount,
+                tier: 0,
+                asset: self.asset,
+                timestamp: point.taken_at,
+            },
+        }
+    }
+}
+
+// Define the SnapshotPoint struct, the logical point every entry of an epoch is read at.
+// `marker` names it in the source's own terms (a content hash, an exported transaction
+// snapshot) so it can be recorded next to the epoch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotPoint {
+    pub epoch: u64,
+    pub taken_at: u64,
+    pub marker: String,
+}
+
+// Define the BalanceSource trait, a live balance system read at a pinned snapshot point:
+// entries streamed for a point all reflect the ledger as of that point, even if it
+// changes while they are read
+pub trait BalanceSource: Send {
+    // Pins a new point, releasing the previous one
+    fn snapshot(
+        &mut self,
+        epoch: u64,
+    ) -> impl Future<Output = Result<SnapshotPoint, SourceError>> + Send;
+
+    // Fails with `Stale` unless `point` is the one pinned last
+    fn balances(
+        &mut self,
+        point: &SnapshotPoint,
+    ) -> BoxStream<'_, Result<BalanceEntry, SourceError>>;
+
+    // Lets go of the pinned point once its entries have been read
+    fn release(&mut self) -> impl Future<Output = Result<(), SourceError>> + Send {
+        async { Ok(()) }
+    }
+}
+
+// Define the CsvSource struct, a `user_id,asset,amount` file (header first, no quoting).
+// The file is read once when the point is pinned, and the point's marker is its SHA-256,
+// so a file rewritten mid-epoch can't mix two ledgers.
+#[derive(Debug, Clone)]
+pub struct CsvSource {
+    path: PathBuf,
+    amount_format: AmountFormat,
+    pinned: Option<(SnapshotPoint, String)>,
+}
+
+impl CsvSource {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        CsvSource {
+            path: path.into(),
+            amount_format: AmountFormat::default(),
+            pinned: None,
+        }
+    }
+
+    // How the amount column is written; plain integer units by default. Fields are
+    // split on ',' first, so a ',' decimal mark or grouping needs another delimiter.
+    pub fn with_amount_format(mut self, amount_format: AmountFormat) -> Self {
+        self.amount_format = amount_format;
+        self
+    }
+}
+
+impl BalanceSource for CsvSource {
+    async fn snapshot(&mut self, epoch: u64) -> Result<SnapshotPoint, SourceError> {
+        let contents = fs::read_to_string(&self.path).map_err(SourceError::Io)?;
+        let point = SnapshotPoint {
+            epoch,
+            taken_at: unix_now(),
+            marker: hex::encode(Sha256::digest(contents.as_bytes())),
+        };
+        self.pinned = Some((point.clone(), contents));
+        Ok(point)
+    }
+
+    fn balances(
+        &mut self,
+        point: &SnapshotPoint,
+    ) -> BoxStream<'_, Result<BalanceEntry, SourceError>> {
+        let contents = match &self.pinned {
+            Some((pinned, contents)) if pinned == point => contents,
+            _ => return stream::once(async { Err(SourceError::Stale) }).boxed(),
+        };
+        let mut lines = contents.lines().enumerate();
+        if let Some((_, header)) = lines.next() {
+            if header.trim() != CSV_HEADER {
+                return stream::once(async { Err(SourceError::Malformed { line: 1 }) }).boxed();
+            }
+        }
+        let amount_format = self.amount_format;
+        let entries = lines
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(move |(index, line)| parse_csv_line(line, index + 1, &amount_format));
+        stream::iter(entries).boxed()
+    }
+
+    fn release(&mut self) -> impl Future<Output = Result<(), SourceError>> + Send {
+        self.pinned = None;
+        async { Ok(()) }
+    }
+}
+
+fn parse_csv_line(
+    line: &str,
+    number: usize,
+    amount_format: &AmountFormat,
+) -> Result<BalanceEntry, SourceError> {
+    let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+    let [user_id, asset, amount] = fields.as_slice() else {
+        return Err(SourceError::Malformed { line: number });
+    };
+    if user_id.is_empty() || asset.is_empty() {
+        return Err(SourceError::Malformed { line: number });
+    }
+    Ok(BalanceEntry {
+        user_id: user_id.to_string(),
+        asset: asset.to_string(),
+        amount: amount_format
+            .parse(amount)
+            .map_err(|error| SourceError::Amount { line: number, error })?,
+    })
+}
+
+// Pins a point, reads every entry at it and releases it. One leaf per entry, with `asset`
+// keeping only that asset's rows; a (user, asset) pair seen twice fails the epoch.
+pub async fn collect_accounts<S: BalanceSource>(
+    source: &mut S,
+    epoch: u64,
+    asset: Option<&str>,
+) -> Result<(SnapshotPoint, Vec<UserLeaf<AccountRecord>>), SourceError> {
+    let (point, entries) = collect_entries(source, epoch, asset).await?;
+    let mut seen = HashSet::new();
+    let mut accounts = Vec::with_capacity(entries.len());
+    for entry in entries {
+        if !seen.insert((entry.user_id.clone(), entry.asset.clone())) {
+            return Err(SourceError::Duplicate {
+                user_id: entry.user_id,
+                asset: entry.asset,
+            });
+        }
+        accounts.push(entry.into_leaf(&point));
+    }
+    Ok((point, accounts))
+}
+
+// Like `collect_accounts` but keeps repeated pairs, for checks that report them instead
+pub async fn collect_entries<S: BalanceSource>(
+    source: &mut S,
+    epoch: u64,
+    asset: Option<&str>,
+) -> Result<(SnapshotPoint, Vec<BalanceEntry>), SourceError> {
+    let point = source.snapshot(epoch).await?;
+    let mut collected = Vec::new();
+    let mut entries = source.balances(&point);
+    while let Some(entry) = entries.next().await {
+        let entry = entry?;
+        if asset.is_none_or(|asset| asset == entry.asset) {
+            collected.push(entry);
+        }
+    }
+    drop(entries);
+    source.release().await?;
+    Ok((point, collected))
+}
+
+// Define the LedgerFeed struct, which hands a BalanceSource to the epoch scheduler. The
+// scheduler runs on its own thread, so each epoch's read is driven to completion there.
+#[derive(Debug)]
+pub struct LedgerFeed<S: BalanceSource> {
+    source: S,
+    asset: Option<String>,
+    last_point: Option<SnapshotPoint>,
+}
+
+impl<S: BalanceSource> LedgerFeed<S> {
+    pub fn new(source: S) -> Self {
+        LedgerFeed {
+            source,
+            asset: None,
+            last_point: None,
+        }
+    }
+
+    // Builds each epoch's tree from this asset's balances only
+    pub fn for_asset(mut self, asset: impl Into<String>) -> Self {
+        self.asset = Some(asset.into());
+        self
+    }
+
+    // Point the last epoch was read at
+    pub fn last_point(&self) -> Option<&SnapshotPoint> {
+        self.last_point.as_ref()
+    }
+}
+
+impl<S: BalanceSource> DataSource<AccountRecord> for LedgerFeed<S> {
+    fn accounts(&mut self, epoch: u64) -> Result<Vec<UserLeaf<AccountRecord>>, String> {
+        let (point, accounts) =
+            block_on(collect_accounts(&mut self.source, epoch, self.asset.as_deref()))
+                .map_err(|err| err.to_string())?;
+        self.last_point = Some(point);
+        Ok(accounts)
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+#[cfg(feature = "postgres")]
+pub use postgres::{PostgresSource, DEFAULT_QUERY};
+
+#[cfg(feature = "postgres")]
+mod postgres {
+    use std::future::Future;
+
+    use futures::stream::{self, BoxStream, StreamExt, TryStreamExt};
+    use tokio_postgres::{Client, Row};
+
+    use super::{unix_now, BalanceEntry, BalanceSource, SnapshotPoint, SourceError};
+
+    pub const DEFAULT_QUERY: &str =
+        "SELECT user_id, asset, amount FROM balances ORDER BY user_id, asset";
+
+    // Define the PostgresSource struct, which reads each epoch inside one REPEATABLE READ
+    // transaction, so every row comes from the same MVCC snapshot however long the read
+    // takes. The marker is the exported snapshot id; the connection task is left to the
+    // caller's runtime.
+    pub struct PostgresSource {
+        client: Client,
+        query: String,
+        pinned: Option<SnapshotPoint>,
+    }
+
+    impl PostgresSource {
+        pub fn new(client: Client) -> Self {
+            PostgresSource {
+                client,
+                query: DEFAULT_QUERY.to_string(),
+                pinned: None,
+            }
+        }
+
+        // The query must return (text, text, bigint) columns: user, asset, amount
+        pub fn with_query(mut self, query: impl Into<String>) -> Self {
+            self.query = query.into();
+            self
+        }
+    }
+
+    impl BalanceSource for PostgresSource {
+        async fn snapshot(&mut self, epoch: u64) -> Result<SnapshotPoint, SourceError> {
+            if self.pinned.take().is_some() {
+                self.client.batch_execute("ROLLBACK").await.map_err(database)?;
+            }
+            self.client
+                .batch_execute("BEGIN ISOLATION LEVEL REPEATABLE READ READ ONLY")
+                .await
+                .map_err(database)?;
+            let row = self
+                .client
+                .query_one("SELECT pg_export_snapshot()", &[])
+                .await
+                .map_err(database)?;
+            let point = SnapshotPoint {
+                epoch,
+                taken_at: unix_now(),
+                marker: row.try_get(0).map_err(database)?,
+            };
+            self.pinned = Some(point.clone());
+            Ok(point)
+        }
+
+        fn balances(
+            &mut self,
+            point: &SnapshotPoint,
+        ) -> BoxStream<'_, Result<BalanceEntry, SourceError>> {
+            if self.pinned.as_ref() != Some(point) {
+                return stream::once(async { Err(SourceError::Stale) }).boxed();
+            }
+            let client = &self.client;
+            let query = self.query.as_str();
+            stream::once(async move {
+                client
+                    .query_raw(query, std::iter::empty::<String>())
+                    .await
+                    .map_err(database)
+            })
+            .map_ok(|rows| rows.map_err(database).and_then(|row| async move { entry(&row) }))
+            .try_flatten()
+            .boxed()
+        }
+
+        fn release(&mut self) -> impl Future<Output = Result<(), SourceError>> + Send {
+            async move {
+                if self.pinned.take().is_some() {
+                    self.client.batch_execute("COMMIT").await.map_err(database)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn entry(row: &Row) -> Result<BalanceEntry, SourceError> {
+        let user_id: String = row.try_get(0).map_err(database)?;
+        let amount: i64 = row.try_get(2).map_err(database)?;
+        Ok(BalanceEntry {
+            amount: u64::try_from(amount).map_err(|_| SourceError::NegativeBalance {
+                user_id: user_id.clone(),
+            })?,
+            asset: row.try_get(1).map_err(database)?,
+            user_id,
+        })
+    }
+
+    fn database(err: tokio_postgres::Error) -> SourceError {
+        SourceError::Database(err.to_string())
+    }
+}
+
+// Define the SourceError enum for ledgers that couldn't be read consistently
+#[derive(Debug)]
+pub enum SourceError {
+    Io(std::io::Error),
+    Database(String),
+    Malformed { line: usize },
+    Amount { line: usize, error: AmountError },
+    NegativeBalance { user_id: String },
+    Duplicate { user_id: String, asset: String },
+    Stale,
+}
+
+impl fmt::Display for SourceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SourceError::Io(err) => write!(f, "cannot read ledger: {}", err),
+            SourceError::Database(err) => write!(f, "ledger query failed: {}", err),
+            SourceError::Malformed { line } => write!(f, "malformed ledger row on line {}", line),
+            SourceError::Amount { line, error } => write!(f, "line {}: {}", line, error),
+            SourceError::NegativeBalance { user_id } => {
+                write!(f, "user {} has a negative balance", user_id)
+            }
+            SourceError::Duplicate { user_id, asset } => {
+                write!(f, "user {} has more than one {} balance", user_id, asset)
+            }
+            SourceError::Stale => write!(f, "snapshot point is no longer pinned"),
+        }
+    }
+}
+
+impl std::error::Error for SourceError {}
@@ -0,0 +1,279 @@
+use std::collections::HashMap;
+use std::marker::PhantomData;
+
+use crate::arena::NodeArena;
+use crate::config::{
+    AmountBinding, BuildError, DuplicatePolicy, HashBackend, PaddingLeaf, PaddingPolicy,
+    SaltDerivation, Shuffling, StorageBackend, TreeConfig,
+};
+use crate::encoding::encode_usize;
+use crate::entropy::EntropySource;
+use crate::leaf::MergedAccount;
+use crate::{
+    hash_bytes, ExclusiveAllotmentProof, Leaf, LeafCommitment, MimkMerkleTree, SumCommitment,
+};
+
+// Define the MerkleSumTreeBuilder struct, the one place where tree options are configured
+#[derive(Debug, Clone)]
+pub struct MerkleSumTreeBuilder<C: SumCommitment, P: ExclusiveAllotmentProof<C>> {
+    config: TreeConfig,
+    _marker: PhantomData<(C, P)>,
+}
+
+impl<C, P> MerkleSumTreeBuilder<C, P>
+where
+    C: SumCommitment,
+    P: ExclusiveAllotmentProof<C>,
+{
+    pub fn new() -> Self {
+        Self::with_config(TreeConfig::default())
+    }
+
+    pub fn with_config(config: TreeConfig) -> Self {
+        MerkleSumTreeBuilder {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn hash_backend(mut self, hash_backend: HashBackend) -> Self {
+        self.config.hash_backend = hash_backend;
+        self
+    }
+
+    pub fn arity(mut self, arity: usize) -> Self {
+        self.config.arity = arity;
+        self
+    }
+
+    pub fn amount_binding(mut self, amount_binding: AmountBinding) -> Self {
+        self.config.amount_binding = amount_binding;
+        self
+    }
+
+    pub fn padding(mut self, padding: PaddingPolicy) -> Self {
+        self.config.padding = padding;
+        self
+    }
+
+    pub fn padding_leaf(mut self, padding_leaf: PaddingLeaf) -> Self {
+        self.config.padding_leaf = padding_leaf;
+        self
+    }
+
+    pub fn salt_derivation(mut self, salt_derivation: SaltDerivation) -> Self {
+        self.config.salt_derivation = salt_derivation;
+        self
+    }
+
+    pub fn shuffling(mut self, shuffling: Shuffling) -> Self {
+        self.config.shuffling = shuffling;
+        self
+    }
+
+    pub fn duplicates(mut self, duplicates: DuplicatePolicy) -> Self {
+        self.config.duplicates = duplicates;
+        self
+    }
+
+    // Draws fresh salt, shuffle and padding seeds from `entropy`
+    pub fn randomized(mut self, entropy: &mut impl EntropySource) -> Self {
+        self.config.salt_derivation = SaltDerivation::random(entropy);
+        self.config.shuffling = Shuffling::random(entropy);
+        self.config.padding_leaf = PaddingLeaf::random(entropy);
+        self
+    }
+
+    pub fn storage(mut self, storage: StorageBackend) -> Self {
+        self.config.storage = storage;
+        self
+    }
+
+    pub fn config(&self) -> &TreeConfig {
+        &self.config
+    }
+
+    pub fn build<L: Leaf>(self, leaves: &[L]) -> Result<MimkMerkleTree<C, P>, BuildError> {
+        self.config.validate(C::HASH_BACKEND, C::AMOUNT_BINDING)?;
+        if leaves.is_empty() {
+            return Err(BuildError::EmptyTree);
+        }
+
+        // Each account is one group of input indices, a single one unless balances were merged
+        let accounts = self.group_accounts(leaves)?;
+
+        // order[position] is the account committed at that position
+        let order: Vec<usize> = match &self.config.shuffling {
+            Shuffling::None => (0..accounts.len()).collect(),
+            Shuffling::Seeded(seed) => shuffled_order(accounts.len(), seed),
+        };
+
+        let mut leaf_nodes: Vec<C::Leaf> = Vec::with_capacity(order.len());
+        for (position, &account) in order.iter().enumerate() {
+            leaf_nodes.push(self.commit_account(position, leaves, &accounts[account])?);
+        }
+
+        if self.config.padding == PaddingPolicy::PowerOfTwo {
+            let padded_len = leaf_nodes.len().next_power_of_two();
+            for position in leaf_nodes.len()..padded_len {
+                leaf_nodes.push(self.commit_padding(position));
+            }
+        }
+
+        // Nodes add amounts unchecked, so make sure the root's total fits before building
+        leaf_nodes
+            .iter()
+            .try_fold(0u64, |sum, leaf| sum.checked_add(leaf.amount()))
+            .ok_or(BuildError::BalanceOverflow)?;
+
+        let identity = self.config.shuffling == Shuffling::None && accounts.len() == leaves.len();
+        let input_positions = if identity {
+            None
+        } else {
+            let mut input_positions = vec![0; leaves.len()];
+            for (position, &account) in order.iter().enumerate() {
+                for &index in &accounts[account] {
+                    input_positions[index] = position;
+                }
+            }
+            Some(input_positions)
+        };
+
+        let mut user_positions: HashMap<Vec<u8>, Vec<usize>> = HashMap::new();
+        for (position, &account) in order.iter().enumerate() {
+            if let Some(user_id) = leaves[accounts[account][0]].user_id() {
+                user_positions
+                    .entry(user_id.to_vec())
+                    .or_default()
+                    .push(position);
+            }
+        }
+
+        Ok(MimkMerkleTree {
+            arena: NodeArena::for_storage(self.config.storage, &leaf_nodes),
+            leaf_nodes,
+            config: self.config,
+            input_positions,
+            user_positions,
+            account_count: order.len(),
+            _proof: PhantomData,
+        })
+    }
+
+    fn group_accounts<L: Leaf>(&self, leaves: &[L]) -> Result<Vec<Vec<usize>>, BuildError> {
+        let mut accounts: Vec<Vec<usize>> = Vec::with_capacity(leaves.len());
+        let mut seen: HashMap<&[u8], usize> = HashMap::new();
+        for (index, leaf) in leaves.iter().enumerate() {
+            let Some(user_id) = leaf.user_id() else {
+                accounts.push(vec![index]);
+                continue;
+            };
+            match seen.get(user_id) {
+                None => {
+                    seen.insert(user_id, accounts.len());
+                    accounts.push(vec![index]);
+                }
+                Some(&account) => match self.config.duplicates {
+                    DuplicatePolicy::Reject => {
+                        return Err(BuildError::DuplicateUserId {
+                            first: accounts[account][0],
+                            second: index,
+                        })
+                    }
+                    DuplicatePolicy::MergeBalances => {
+                        // Amounts in different assets can't be summed into one balance
+                        let first = accounts[account][0];
+                        if leaves[first].asset() != leaf.asset() {
+                            return Err(BuildError::MixedAssets {
+                                first,
+                                second: index,
+                            });
+                        }
+                        accounts[account].push(index)
+                    }
+                    DuplicatePolicy::Allow => accounts.push(vec![index]),
+                },
+            }
+        }
+        Ok(accounts)
+    }
+
+    fn commit_account<L: Leaf>(
+        &self,
+        position: usize,
+        leaves: &[L],
+        account: &[usize],
+    ) -> Result<C::Leaf, BuildError> {
+        let first = &leaves[account[0]];
+        if account.len() == 1 {
+            return Ok(self.commit_leaf(position, first));
+        }
+        let balance = account
+            .iter()
+            .try_fold(0u64, |sum, &index| sum.checked_add(leaves[index].amount()))
+            .ok_or(BuildError::BalanceOverflow)?;
+        let merged = MergedAccount {
+            // Only leaves with a user ID are ever grouped
+            user_id: first.user_id().unwrap_or_default(),
+            balance,
+            // Every merged record holds this asset, see `group_accounts`
+            asset: first.asset(),
+        };
+        Ok(self.commit_leaf(position, &merged))
+    }
+
+    fn commit_padding(&self, position: usize) -> C::Leaf {
+        match &self.config.padding_leaf {
+            PaddingLeaf::Zero => self.commit_leaf(position, &0u64),
+            PaddingLeaf::Blinded(seed) => {
+                C::Leaf::from_parts(0, seeded_digest(b"padding", seed, position).into())
+            }
+        }
+    }
+
+    fn commit_leaf<L: Leaf>(&self, position: usize, leaf: &L) -> C::Leaf {
+        match &self.config.salt_derivation {
+            SaltDerivation::None => C::Leaf::from_leaf(leaf),
+            SaltDerivation::FromSeed(seed) => {
+                C::Leaf::from_salted_leaf(leaf, &derive_salt(seed, position))
+            }
+        }
+    }
+}
+
+impl<C, P> Default for MerkleSumTreeBuilder<C, P>
+where
+    C: SumCommitment,
+    P: ExclusiveAllotmentProof<C>,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Hashes a domain label, the seed and a counter, so salts and shuffles drawn from
+// the same seed never coincide
+pub(crate) fn seeded_digest(label: &[u8], seed: &[u8; 32], counter: usize) -> [u8; 32] {
+    let mut preimage = Vec::with_capacity(label.len() + 40);
+    preimage.extend_from_slice(label);
+    preimage.extend_from_slice(seed);
+    preimage.extend_from_slice(&encode_usize(counter));
+    hash_bytes(&preimage).into()
+}
+
+pub(crate) fn derive_salt(seed: &[u8; 32], position: usize) -> [u8; 32] {
+    seeded_digest(b"salt", seed, position)
+}
+
+// Fisher-Yates shuffle driven by SHA-256 of the seed and the step counter
+fn shuffled_order(len: usize, seed: &[u8; 32]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..len).collect();
+    for i in (1..len).rev() {
+        let digest = seeded_digest(b"shuffle", seed, i);
+        let mut word = [0u8; 8];
+        word.copy_from_slice(&digest[..8]);
+        let j = (u64::from_le_bytes(word) % (i as u64 + 1)) as usize;
+        order.swap(i, j);
+    }
+    order
+}
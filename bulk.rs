@@ -0,0 +1,340 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::num::NonZeroUsize;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Mutex;
+use std::thread;
+
+use ed25519_dalek::VerifyingKey;
+use serde::{Deserialize, Serialize};
+
+use crate::bundle::VerificationPolicy;
+use crate::server::ProofResponse;
+use crate::{ExclusiveAllotmentProof, LeafCommitment, Root, SumCommitment};
+
+// Bundles are a few kilobytes; anything far larger is rejected before it is buffered
+pub const MAX_BUNDLE_BYTES: u64 = 1 << 20;
+
+// Define the BundleFile struct, one proof bundle (a `ProofResponse` in JSON) by file name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BundleFile {
+    pub name: String,
+    pub bytes: Vec<u8>,
+}
+
+// Define the BulkOptions struct for how a bulk verification is run
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BulkOptions {
+    // Worker threads; 0 uses every available core
+    pub threads: usize,
+}
+
+// Define the BulkReport struct, the summary an auditor publishes for one epoch's drop
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BulkReport {
+    pub epoch: u64,
+    // Root as `<amount>:<hex digest>`
+    pub root: String,
+    pub files: usize,
+    pub verified: usize,
+    pub failures: Vec<FileFailure>,
+    // Positions proven by more than one file, i.e. one leaf handed to several users
+    pub duplicate_positions: Vec<DuplicatePosition>,
+    // Sum of the verified leaf balances, None on overflow
+    pub verified_total: Option<u64>,
+    pub passed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileFailure {
+    pub file: String,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DuplicatePosition {
+    pub position: usize,
+    pub files: Vec<String>,
+}
+
+struct Outcome {
+    name: String,
+    result: Result<(usize, u64), String>,
+}
+
+// Checks every bundle against the expected epoch, root and operator key on a pool of
+// worker threads. Files are read on the calling thread and handed over through a
+// bounded queue, so archives of any size run in constant memory apart from the report.
+pub fn verify_bundles<C, I>(
+    files: I,
+    epoch: u64,
+    root: &Root<C>,
+    operator_key: &VerifyingKey,
+    options: BulkOptions,
+) -> Result<BulkReport, BulkError>
+where
+    C: SumCommitment + Sync,
+    I: IntoIterator<Item = Result<BundleFile, BulkError>>,
+{
+    let threads = match options.threads {
+        0 => thread::available_parallelism().map_or(1, NonZeroUsize::get),
+        threads => threads,
+    };
+    let (sender, receiver) = mpsc::sync_channel::<BundleFile>(threads * 16);
+    let receiver = Mutex::new(receiver);
+    let mut rejected = Vec::new();
+
+    let (mut outcomes, source_error) = thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|_| scope.spawn(|| worker(&receiver, epoch, root, operator_key)))
+            .collect();
+        let mut source_error = None;
+        for file in files {
+            match file {
+                // A send only fails once every worker has exited, i.e. panicked
+                Ok(file) => {
+                    if sender.send(file).is_err() {
+                        break;
+                    }
+                }
+                // Oversized files are failures of their own, the rest can still be read
+                Err(BulkError::TooLarge(name)) => rejected.push(name),
+                Err(err) => {
+                    source_error = Some(err);
+                    break;
+                }
+            }
+        }
+        drop(sender);
+        let outcomes: Vec<Outcome> = workers
+            .into_iter()
+            .flat_map(|worker| worker.join().expect("bundle verifier thread panicked"))
+            .collect();
+        (outcomes, source_error)
+    });
+    if let Some(err) = source_error {
+        return Err(err);
+    }
+    outcomes.extend(rejected.into_iter().map(|name| Outcome {
+        result: Err(format!(
+            "larger than the {} byte bundle limit",
+            MAX_BUNDLE_BYTES
+        )),
+        name,
+    }));
+    Ok(summarize(epoch, root, outcomes))
+}
+
+fn worker<C: SumCommitment>(
+    receiver: &Mutex<Receiver<BundleFile>>,
+    epoch: u64,
+    root: &Root<C>,
+    operator_key: &VerifyingKey,
+) -> Vec<Outcome> {
+    let policy = VerificationPolicy::new(*operator_key);
+    let mut outcomes = Vec::new();
+    loop {
+        // The lock is only held while taking the next file
+        let next = receiver.lock().expect("bundle queue lock poisoned").recv();
+        let Ok(file) = next else {
+            return outcomes;
+        };
+        outcomes.push(Outcome {
+            result: verify_file(&file.bytes, epoch, root, &policy),
+            name: file.name,
+        });
+    }
+}
+
+fn verify_file<C: SumCommitment>(
+    bytes: &[u8],
+    epoch: u64,
+    root: &Root<C>,
+    policy: &VerificationPolicy<'_>,
+) -> Result<(usize, u64), String> {
+    let response: ProofResponse =
+        serde_json::from_slice(bytes).map_err(|err| format!("not a proof bundle: {}", err))?;
+    let bundle = response
+        .to_bundle::<C>()
+        .map_err(|err| format!("malformed proof bundle: {}", err))?;
+    if bundle.signed_root.epoch != epoch || !bundle.signed_root.root.matches(root.node()) {
+        return Err(format!(
+            "bundle is for root {} of epoch {}",
+            bundle.signed_root.root, bundle.signed_root.epoch
+        ));
+    }
+    bundle.verify(policy).map_err(|err| err.to_string())?;
+    Ok((bundle.proof.position(), bundle.proof.leaf().amount()))
+}
+
+fn summarize<C: SumCommitment>(epoch: u64, root: &Root<C>, outcomes: Vec<Outcome>) -> BulkReport {
+    let files = outcomes.len();
+    let mut failures = Vec::new();
+    let mut positions: HashMap<usize, Vec<String>> = HashMap::new();
+    let mut verified_total = Some(0u64);
+    for outcome in outcomes {
+        match outcome.result {
+            Ok((position, amount)) => {
+                verified_total = verified_total.and_then(|total| total.checked_add(amount));
+                positions.entry(position).or_default().push(outcome.name);
+            }
+            Err(error) => failures.push(FileFailure {
+                file: outcome.name,
+                error,
+            }),
+        }
+    }
+    failures.sort_by(|a, b| a.file.cmp(&b.file));
+
+    let verified = files - failures.len();
+    let mut duplicate_positions: Vec<DuplicatePosition> = positions
+        .into_iter()
+        .filter(|(_, files)| files.len() > 1)
+        .map(|(position, mut files)| {
+            files.sort();
+            DuplicatePosition { position, files }
+        })
+        .collect();
+    duplicate_positions.sort_by_key(|duplicate| duplicate.position);
+
+    BulkReport {
+        epoch,
+        root: root.to_string(),
+        files,
+        verified,
+        passed: failures.is_empty() && duplicate_positions.is_empty(),
+        failures,
+        duplicate_positions,
+        verified_total,
+    }
+}
+
+#[cfg(feature = "archive")]
+pub use archives::{verify_archive, verify_tar, verify_zip};
+
+#[cfg(feature = "archive")]
+mod archives {
+    use std::fs::File;
+    use std::io::{BufReader, Read, Seek};
+    use std::path::Path;
+
+    use ed25519_dalek::VerifyingKey;
+
+    use super::{verify_bundles, BulkError, BulkOptions, BulkReport, BundleFile, MAX_BUNDLE_BYTES};
+    use crate::porz::{verify_porz, ArchiveError};
+    use crate::{Root, SumCommitment};
+
+    // Every regular file in the archive is taken to be one bundle
+    pub fn verify_tar<C: SumCommitment + Sync, R: Read>(
+        reader: R,
+        epoch: u64,
+        root: &Root<C>,
+        operator_key: &VerifyingKey,
+        options: BulkOptions,
+    ) -> Result<BulkReport, BulkError> {
+        let mut archive = tar::Archive::new(reader);
+        let entries = archive.entries().map_err(BulkError::Io)?;
+        let files = entries.filter_map(|entry| {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(err) => return Some(Err(BulkError::Io(err))),
+            };
+            if !entry.header().entry_type().is_file() {
+                return None;
+            }
+            let name = match entry.path() {
+                Ok(path) => path.display().to_string(),
+                Err(err) => return Some(Err(BulkError::Io(err))),
+            };
+            Some(read_bounded(entry, &name).map(|bytes| BundleFile { name, bytes }))
+        });
+        verify_bundles(files, epoch, root, operator_key, options)
+    }
+
+    pub fn verify_zip<C: SumCommitment + Sync, R: Read + Seek>(
+        reader: R,
+        epoch: u64,
+        root: &Root<C>,
+        operator_key: &VerifyingKey,
+        options: BulkOptions,
+    ) -> Result<BulkReport, BulkError> {
+        let mut archive = zip::ZipArchive::new(reader).map_err(zip_error)?;
+        let files = (0..archive.len()).filter_map(|index| {
+            let entry = match archive.by_index(index) {
+                Ok(entry) => entry,
+                Err(err) => return Some(Err(zip_error(err))),
+            };
+            if !entry.is_file() {
+                return None;
+            }
+            let name = entry.name().to_string();
+            Some(read_bounded(entry, &name).map(|bytes| BundleFile { name, bytes }))
+        });
+        verify_bundles(files, epoch, root, operator_key, options)
+    }
+
+    // Picks the reader from the extension: `.zip`, `.porz`, otherwise tar
+    pub fn verify_archive<C: SumCommitment + Sync>(
+        path: impl AsRef<Path>,
+        epoch: u64,
+        root: &Root<C>,
+        operator_key: &VerifyingKey,
+        options: BulkOptions,
+    ) -> Result<BulkReport, BulkError> {
+        let path = path.as_ref();
+        let file = BufReader::new(File::open(path).map_err(BulkError::Io)?);
+        let extension = path.extension().unwrap_or_default();
+        if extension.eq_ignore_ascii_case("zip") {
+            verify_zip(file, epoch, root, operator_key, options)
+        } else if extension.eq_ignore_ascii_case("porz") {
+            verify_porz(file, epoch, root, operator_key, options).map_err(|err| match err {
+                ArchiveError::Bulk(err) => err,
+                err => BulkError::Archive(err.to_string()),
+            })
+        } else {
+            verify_tar(file, epoch, root, operator_key, options)
+        }
+    }
+
+    fn read_bounded(reader: impl Read, name: &str) -> Result<Vec<u8>, BulkError> {
+        let mut bytes = Vec::new();
+        reader
+            .take(MAX_BUNDLE_BYTES + 1)
+            .read_to_end(&mut bytes)
+            .map_err(BulkError::Io)?;
+        if bytes.len() as u64 > MAX_BUNDLE_BYTES {
+            return Err(BulkError::TooLarge(name.to_string()));
+        }
+        Ok(bytes)
+    }
+
+    fn zip_error(err: zip::result::ZipError) -> BulkError {
+        BulkError::Archive(err.to_string())
+    }
+}
+
+// Define the BulkError enum for archives that can't be read through. Bundles that fail
+// verification are reported, not returned as errors.
+#[derive(Debug)]
+pub enum BulkError {
+    Io(io::Error),
+    Archive(String),
+    TooLarge(String),
+}
+
+impl fmt::Display for BulkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BulkError::Io(err) => write!(f, "i/o error: {}", err),
+            BulkError::Archive(reason) => write!(f, "malformed archive: {}", reason),
+            BulkError::TooLarge(name) => write!(
+                f,
+                "{} is larger than the {} byte bundle limit",
+                name, MAX_BUNDLE_BYTES
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BulkError {}
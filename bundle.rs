@@ -0,0 +1,195 @@
+use std::fmt;
+
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+
+use crate::encoding::{ByteReader, COMMITMENT_LEN};
+use crate::signer::{self, SignerError};
+use crate::tsa::{TimeStampToken, TsaClient, TsaError, TsaVerifier};
+use crate::{ExclusiveAllotmentProof, ParseError, Root, SumCommitment, VerifyError};
+
+// Domain label prefixed to every signed root payload
+const SIGNED_ROOT_DOMAIN: &[u8] = b"mimi-signed-root-v1";
+
+// Encoded size of a SignedRoot: epoch, root, signature
+pub const SIGNED_ROOT_LEN: usize = 8 + COMMITMENT_LEN + 64;
+
+// Define the SignedRoot struct, an epoch root signed by the operator
+#[derive(Debug, Clone)]
+pub struct SignedRoot<C: SumCommitment> {
+    pub epoch: u64,
+    pub root: Root<C>,
+    pub signature: Signature,
+}
+
+impl<C: SumCommitment> SignedRoot<C> {
+    pub fn sign(epoch: u64, root: Root<C>, key: &SigningKey) -> Self {
+        let signature = key.sign(&signing_payload(epoch, &root));
+        SignedRoot {
+            epoch,
+            root,
+            signature,
+        }
+    }
+
+    // Signs through an external backend. The signature is checked against the backend's
+    // key before it is returned, so a faulty token can't publish an unverifiable root.
+    pub fn sign_with(
+        epoch: u64,
+        root: Root<C>,
+        signer: &dyn signer::Signer,
+    ) -> Result<Self, SignerError> {
+        let signature = signer.sign(&signing_payload(epoch, &root))?;
+        let signed_root = SignedRoot {
+            epoch,
+            root,
+            signature,
+        };
+        signed_root
+            .verify(&signer.verifying_key()?)
+            .map_err(|_| SignerError::InvalidSignature)?;
+        Ok(signed_root)
+    }
+
+    pub fn payload(&self) -> Vec<u8> {
+        signing_payload(self.epoch, &self.root)
+    }
+
+    pub fn verify(&self, operator_key: &VerifyingKey) -> Result<(), BundleError> {
+        operator_key
+            .verify_strict(&self.payload(), &self.signature)
+            .map_err(|_| BundleError::BadSignature)
+    }
+
+    // epoch | root | signature
+    pub fn to_bytes(&self) -> [u8; SIGNED_ROOT_LEN] {
+        let mut out = [0u8; SIGNED_ROOT_LEN];
+        out[..8].copy_from_slice(&self.epoch.to_le_bytes());
+        out[8..8 + COMMITMENT_LEN].copy_from_slice(&self.root.to_bytes());
+        out[8 + COMMITMENT_LEN..].copy_from_slice(&self.signature.to_bytes());
+        out
+    }
+}
+
+impl<C: SumCommitment> TryFrom<&[u8]> for SignedRoot<C> {
+    type Error = ParseError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        let mut reader = ByteReader::new(bytes);
+        let epoch = u64::from_le_bytes(reader.take_array::<8>()?);
+        let root = Root::try_from(reader.take(COMMITMENT_LEN)?)?;
+        let signature = Signature::from_bytes(&reader.take_array::<64>()?);
+        reader.finish()?;
+        Ok(SignedRoot {
+            epoch,
+            root,
+            signature,
+        })
+    }
+}
+
+// Canonical bytes the operator signs: domain label, epoch and encoded root
+pub fn signing_payload<C: SumCommitment>(epoch: u64, root: &Root<C>) -> Vec<u8> {
+    let mut payload = SIGNED_ROOT_DOMAIN.to_vec();
+    payload.extend_from_slice(&epoch.to_le_bytes());
+    payload.extend_from_slice(&root.to_bytes());
+    payload
+}
+
+// Define the ProofBundle struct, everything a user needs to check their inclusion
+#[derive(Debug, Clone)]
+pub struct ProofBundle<C: SumCommitment, P: ExclusiveAllotmentProof<C>> {
+    pub proof: P,
+    pub signed_root: SignedRoot<C>,
+    // RFC 3161 token over the root signature, when the operator requested one
+    pub timestamp: Option<TimeStampToken>,
+}
+
+// Define the VerificationPolicy struct, the trust settings a bundle is checked against
+pub struct VerificationPolicy<'a> {
+    pub operator_key: VerifyingKey,
+    pub tsa: Option<&'a dyn TsaVerifier>,
+    pub require_timestamp: bool,
+}
+
+impl<'a> VerificationPolicy<'a> {
+    pub fn new(operator_key: VerifyingKey) -> Self {
+        VerificationPolicy {
+            operator_key,
+            tsa: None,
+            require_timestamp: false,
+        }
+    }
+
+    pub fn with_tsa(mut self, tsa: &'a dyn TsaVerifier, require_timestamp: bool) -> Self {
+        self.tsa = Some(tsa);
+        self.require_timestamp = require_timestamp;
+        self
+    }
+}
+
+impl<C: SumCommitment, P: ExclusiveAllotmentProof<C>> ProofBundle<C, P> {
+    pub fn new(proof: P, signed_root: SignedRoot<C>) -> Self {
+        ProofBundle {
+            proof,
+            signed_root,
+            timestamp: None,
+        }
+    }
+
+    pub fn attach_timestamp(
+        &mut self,
+        client: &dyn TsaClient,
+        nonce: u64,
+    ) -> Result<(), BundleError> {
+        let token = TimeStampToken::request(client, &self.signed_root.signature.to_bytes(), nonce)?;
+        self.timestamp = Some(token);
+        Ok(())
+    }
+
+    pub fn verify(&self, policy: &VerificationPolicy<'_>) -> Result<(), BundleError> {
+        self.signed_root.verify(&policy.operator_key)?;
+        self.proof
+            .check(&self.signed_root.root)
+            .map_err(BundleError::InvalidProof)?;
+
+        match (&self.timestamp, policy.tsa) {
+            (Some(token), Some(tsa)) => {
+                token.verify(&self.signed_root.signature.to_bytes(), tsa)?;
+            }
+            (Some(_), None) if policy.require_timestamp => {
+                return Err(BundleError::Timestamp(TsaError::NoVerifier));
+            }
+            (None, _) if policy.require_timestamp => return Err(BundleError::MissingTimestamp),
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+// Define the BundleError enum for bundles that fail verification
+#[derive(Debug)]
+pub enum BundleError {
+    BadSignature,
+    InvalidProof(VerifyError),
+    MissingTimestamp,
+    Timestamp(TsaError),
+}
+
+impl fmt::Display for BundleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BundleError::BadSignature => write!(f, "root signature does not verify"),
+            BundleError::InvalidProof(err) => write!(f, "inclusion proof is invalid: {}", err),
+            BundleError::MissingTimestamp => write!(f, "bundle carries no timestamp token"),
+            BundleError::Timestamp(err) => write!(f, "timestamp token rejected: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for BundleError {}
+
+impl From<TsaError> for BundleError {
+    fn from(err: TsaError) -> Self {
+        BundleError::Timestamp(err)
+    }
+}
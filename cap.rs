@@ -0,0 +1,200 @@
+use std::fmt;
+
+use crate::{
+    checked_path_sum, ExclusiveAllotmentProof, LeafCommitment, MimkMerkleTree, Root, SumCommitment,
+    VerifyError,
+};
+
+// Define the MerkleCap struct, the 2^height nodes `height` levels below the root, left to
+// right. Verifying against a cap skips the top `height` hashes of every path, as in
+// Plonky2-style recursive provers.
+#[derive(Debug, Clone)]
+pub struct MerkleCap<C: SumCommitment> {
+    height: usize,
+    nodes: Vec<C>,
+}
+
+impl<C: SumCommitment> MerkleCap<C> {
+    // Fails when `nodes` isn't a power of two long
+    pub fn from_nodes(nodes: Vec<C>) -> Result<Self, CapError> {
+        if !nodes.len().is_power_of_two() {
+            return Err(CapError::InvalidCapSize(nodes.len()));
+        }
+        Ok(MerkleCap {
+            height: nodes.len().trailing_zeros() as usize,
+            nodes,
+        })
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn nodes(&self) -> &[C] {
+        &self.nodes
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    // Adjacent cap nodes are siblings, so folding pairs rebuilds the root the cap was
+    // taken from; compare it with the signed root to trust the cap
+    pub fn root(&self) -> Result<Root<C>, VerifyError> {
+        let mut level = self.nodes.clone();
+        while level.len() > 1 {
+            level = level
+                .chunks_exact(2)
+                .map(|pair| {
+                    checked_path_sum(pair[0].amount(), [&pair[1]])?;
+                    Ok(C::combine_commitments(&pair[0], &pair[1]))
+                })
+                .collect::<Result<_, VerifyError>>()?;
+        }
+        Ok(Root::from_node(level.remove(0)))
+    }
+}
+
+// Define the CapProof struct, an inclusion proof that ends at a cap node instead of the root
+#[derive(Debug, Clone)]
+pub struct CapProof<C: SumCommitment> {
+    position: usize,
+    leaf: C::Leaf,
+    // Siblings from the leaf up to the cap, `true` when the sibling is the left child
+    siblings: Vec<(C, bool)>,
+    cap_index: usize,
+}
+
+impl<C: SumCommitment> CapProof<C> {
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn leaf(&self) -> &C::Leaf {
+        &self.leaf
+    }
+
+    pub fn siblings(&self) -> &[(C, bool)] {
+        &self.siblings
+    }
+
+    pub fn cap_index(&self) -> usize {
+        self.cap_index
+    }
+
+    pub fn reconstruct_commitment(&self) -> C {
+        let mut current = self.leaf.to_node();
+        for (sibling, sibling_on_left) in &self.siblings {
+            current = if *sibling_on_left {
+                C::combine_commitments(sibling, &current)
+            } else {
+                C::combine_commitments(&current, sibling)
+            };
+        }
+        current
+    }
+
+    pub fn check(&self, cap: &MerkleCap<C>) -> Result<(), VerifyError> {
+        let node = cap
+            .nodes
+            .get(self.cap_index)
+            .ok_or(VerifyError::DigestMismatch)?;
+        checked_path_sum(self.leaf.amount(), self.siblings.iter().map(|(sibling, _)| sibling))?;
+        Root::from_node(node.clone()).check(&self.reconstruct_commitment())
+    }
+
+    pub fn verify(&self, cap: &MerkleCap<C>) -> bool {
+        self.check(cap).is_ok()
+    }
+}
+
+impl<C, P> MimkMerkleTree<C, P>
+where
+    C: SumCommitment,
+    P: ExclusiveAllotmentProof<C>,
+{
+    // Halving keeps every leaf at least floor(log2(len)) levels deep, so any cap with at
+    // most `len` nodes lies entirely above the leaves
+    pub fn merkle_cap(&self, height: usize) -> Result<MerkleCap<C>, CapError> {
+        self.check_cap_height(height)?;
+        let mut nodes = Vec::with_capacity(1 << height);
+        self.collect_cap(0, &self.leaf_nodes, height, &mut nodes);
+        Ok(MerkleCap { height, nodes })
+    }
+
+    pub fn prove_to_cap(&self, position: usize, height: usize) -> Result<CapProof<C>, CapError> {
+        self.check_cap_height(height)?;
+        if position >= self.leaf_nodes.len() {
+            return Err(CapError::PositionOutOfRange(position));
+        }
+        let proof = self.prove(position);
+        let siblings = proof.siblings();
+        let (below, above) = siblings.split_at(siblings.len() - height);
+        // The top sibling decides the most significant bit of the cap index
+        let cap_index = above
+            .iter()
+            .rev()
+            .fold(0, |index, (_, sibling_on_left)| index * 2 + *sibling_on_left as usize);
+        Ok(CapProof {
+            position,
+            leaf: proof.leaf().clone(),
+            siblings: below.to_vec(),
+            cap_index,
+        })
+    }
+
+    fn check_cap_height(&self, height: usize) -> Result<(), CapError> {
+        let fits = 1usize
+            .checked_shl(height as u32)
+            .is_some_and(|size| size <= self.leaf_nodes.len());
+        if !fits {
+            return Err(CapError::HeightTooLarge {
+                height,
+                leaves: self.leaf_nodes.len(),
+            });
+        }
+        Ok(())
+    }
+
+    fn collect_cap(&self, node_index: usize, nodes: &[C::Leaf], height: usize, out: &mut Vec<C>) {
+        if height == 0 {
+            out.push(self.build_merkle_tree(node_index, nodes));
+            return;
+        }
+        let middle = nodes.len() / 2;
+        self.collect_cap(node_index * 2 + 1, &nodes[..middle], height - 1, out);
+        self.collect_cap(node_index * 2 + 2, &nodes[middle..], height - 1, out);
+    }
+}
+
+// Define the CapError enum for caps that can't be taken from a tree
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CapError {
+    HeightTooLarge { height: usize, leaves: usize },
+    InvalidCapSize(usize),
+    PositionOutOfRange(usize),
+}
+
+impl fmt::Display for CapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CapError::HeightTooLarge { height, leaves } => write!(
+                f,
+                "a cap of height {} needs at least 2^{} leaves, the tree has {}",
+                height, height, leaves
+            ),
+            CapError::InvalidCapSize(len) => {
+                write!(f, "a cap holds a power of two nodes, not {}", len)
+            }
+            CapError::PositionOutOfRange(position) => {
+                write!(f, "leaf {} is outside the tree", position)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CapError {}
@@ -0,0 +1,261 @@
+use std::fmt;
+
+use generic_array::typenum::U32;
+use generic_array::GenericArray;
+use sha2::{Digest as _, Sha256};
+
+use crate::encoding::ByteReader;
+use crate::{
+    AmountBinding, ExclusiveAllotmentProof, HashBackend, Leaf, LeafCommitment, MerkleProof,
+    ParseError, Root, SumCommitment, LEAF_PREFIX, NODE_PREFIX,
+};
+
+pub const CATEGORY_COUNT: usize = 3;
+
+// Encoded category commitments are the little-endian subtotals followed by the digest
+pub const CATEGORY_COMMITMENT_LEN: usize = CATEGORY_COUNT * 8 + 32;
+
+// Define the Category enum, the liability bucket a leaf's balance is counted in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Category {
+    #[default]
+    Spot,
+    Earn,
+    Margin,
+}
+
+impl Category {
+    pub const ALL: [Category; CATEGORY_COUNT] = [Category::Spot, Category::Earn, Category::Margin];
+
+    pub fn index(&self) -> usize {
+        match self {
+            Category::Spot => 0,
+            Category::Earn => 1,
+            Category::Margin => 2,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Category::Spot => "spot",
+            Category::Earn => "earn",
+            Category::Margin => "margin",
+        }
+    }
+
+    pub fn from_index(index: usize) -> Option<Self> {
+        Category::ALL.get(index).copied()
+    }
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+// Define the Categorized struct, a record tagged with the bucket it belongs to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Categorized<L: Leaf> {
+    pub category: Category,
+    pub record: L,
+}
+
+impl<L: Leaf> Leaf for Categorized<L> {
+    fn amount(&self) -> u64 {
+        self.record.amount()
+    }
+
+    fn encode_for_hash(&self) -> Vec<u8> {
+        self.record.encode_for_hash()
+    }
+
+    fn user_id(&self) -> Option<&[u8]> {
+        self.record.user_id()
+    }
+
+    fn category(&self) -> Category {
+        self.category
+    }
+}
+
+// Define CategoryLeafCommitment struct. The digest covers the category, so a user
+// recomputing it from their record learns which bucket the balance was counted in.
+#[derive(Debug, Clone)]
+pub struct CategoryLeafCommitment {
+    category: Category,
+    amount: u64,
+    digest: GenericArray<u8, U32>,
+}
+
+impl CategoryLeafCommitment {
+    pub fn category(&self) -> Category {
+        self.category
+    }
+
+    fn commit<L: Leaf>(leaf: &L, salt: &[u8]) -> Self {
+        let category = leaf.category();
+        let mut hasher = Sha256::new();
+        hasher.update([LEAF_PREFIX]);
+        hasher.update(salt);
+        hasher.update([category.index() as u8]);
+        hasher.update(leaf.encode_for_hash());
+        CategoryLeafCommitment {
+            category,
+            amount: leaf.amount(),
+            digest: hasher.finalize(),
+        }
+    }
+}
+
+impl LeafCommitment for CategoryLeafCommitment {
+    type Node = CategorySumCommitment;
+
+    fn from_leaf<L: Leaf>(leaf: &L) -> Self {
+        Self::commit(leaf, &[])
+    }
+
+    fn from_salted_leaf<L: Leaf>(leaf: &L, salt: &[u8; 32]) -> Self {
+        Self::commit(leaf, salt)
+    }
+
+    // Parts carry no category, so they rebuild a spot leaf
+    fn from_parts(amount: u64, digest: GenericArray<u8, U32>) -> Self {
+        CategoryLeafCommitment {
+            category: Category::Spot,
+            amount,
+            digest,
+        }
+    }
+
+    fn amount(&self) -> u64 {
+        self.amount
+    }
+
+    fn digest(&self) -> GenericArray<u8, U32> {
+        self.digest
+    }
+
+    fn to_node(&self) -> CategorySumCommitment {
+        let mut sums = [0u64; CATEGORY_COUNT];
+        sums[self.category.index()] = self.amount;
+        CategorySumCommitment {
+            sums,
+            digest: self.digest,
+        }
+    }
+}
+
+// Define CategorySumCommitment struct, an internal node carrying one subtotal per
+// category. Both children's subtotals are hashed into the digest, so every subtotal
+// under a root is as binding as the root itself.
+#[derive(Debug, Clone)]
+pub struct CategorySumCommitment {
+    sums: [u64; CATEGORY_COUNT],
+    digest: GenericArray<u8, U32>,
+}
+
+impl CategorySumCommitment {
+    pub fn from_sums(sums: [u64; CATEGORY_COUNT], digest: GenericArray<u8, U32>) -> Self {
+        CategorySumCommitment { sums, digest }
+    }
+
+    pub fn subtotal(&self, category: Category) -> u64 {
+        self.sums[category.index()]
+    }
+
+    pub fn subtotals(&self) -> [u64; CATEGORY_COUNT] {
+        self.sums
+    }
+
+    pub fn to_bytes(&self) -> [u8; CATEGORY_COMMITMENT_LEN] {
+        let mut bytes = [0u8; CATEGORY_COMMITMENT_LEN];
+        for (chunk, sum) in bytes.chunks_exact_mut(8).zip(self.sums) {
+            chunk.copy_from_slice(&sum.to_le_bytes());
+        }
+        bytes[CATEGORY_COUNT * 8..].copy_from_slice(&self.digest);
+        bytes
+    }
+}
+
+impl TryFrom<&[u8]> for CategorySumCommitment {
+    type Error = ParseError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        if bytes.len() != CATEGORY_COMMITMENT_LEN {
+            return Err(ParseError::InvalidLength {
+                expected: CATEGORY_COMMITMENT_LEN,
+                found: bytes.len(),
+            });
+        }
+        let mut reader = ByteReader::new(bytes);
+        let mut sums = [0u64; CATEGORY_COUNT];
+        for sum in &mut sums {
+            *sum = u64::from_le_bytes(reader.take_array::<8>()?);
+        }
+        let digest = GenericArray::clone_from_slice(reader.take(32)?);
+        reader.finish()?;
+        Ok(CategorySumCommitment { sums, digest })
+    }
+}
+
+impl SumCommitment for CategorySumCommitment {
+    type Leaf = CategoryLeafCommitment;
+    const HASH_BACKEND: HashBackend = HashBackend::Sha256;
+    const AMOUNT_BINDING: AmountBinding = AmountBinding::Bound;
+
+    fn amount(&self) -> u64 {
+        self.sums.iter().sum()
+    }
+
+    fn digest(&self) -> GenericArray<u8, U32> {
+        self.digest
+    }
+
+    fn combine_commitments(left: &Self, right: &Self) -> Self {
+        let mut sums = [0u64; CATEGORY_COUNT];
+        let mut hasher = Sha256::new();
+        hasher.update([NODE_PREFIX]);
+        hasher.update(left.digest);
+        hasher.update(right.digest);
+        for (index, sum) in sums.iter_mut().enumerate() {
+            hasher.update(left.sums[index].to_le_bytes());
+            hasher.update(right.sums[index].to_le_bytes());
+            *sum = left.sums[index] + right.sums[index];
+        }
+        CategorySumCommitment {
+            sums,
+            digest: hasher.finalize(),
+        }
+    }
+
+    // Parts carry only the total, so they rebuild a node with everything under spot.
+    // Use `from_sums` (or the 56-byte encoding) wherever subtotals have to survive.
+    fn from_parts(amount: u64, digest: GenericArray<u8, U32>) -> Self {
+        let mut sums = [0u64; CATEGORY_COUNT];
+        sums[Category::Spot.index()] = amount;
+        CategorySumCommitment { sums, digest }
+    }
+}
+
+impl Root<CategorySumCommitment> {
+    pub fn subtotal(&self, category: Category) -> u64 {
+        self.node().subtotal(category)
+    }
+
+    pub fn subtotals(&self) -> Vec<(Category, u64)> {
+        Category::ALL
+            .iter()
+            .map(|category| (*category, self.subtotal(*category)))
+            .collect()
+    }
+}
+
+// Checks the proof against the root and that the leaf was counted in `category`
+pub fn verify_in_category(
+    proof: &MerkleProof<CategorySumCommitment>,
+    root: &Root<CategorySumCommitment>,
+    category: Category,
+) -> bool {
+    proof.leaf().category() == category && proof.verify(root)
+}
@@ -0,0 +1,175 @@
+use std::fmt;
+
+use rustls_pki_types::{CertificateDer, TrustAnchor, UnixTime};
+use webpki::{EndEntityCert, KeyUsage};
+
+use crate::bundle::SignedRoot;
+use crate::encoding::{encode_len, ByteReader, COMMITMENT_LEN};
+use crate::{ParseError, Root, SumCommitment};
+
+// id-kp-codeSigning (1.3.6.1.5.5.7.3.3); a signing certificate that lists extended key
+// usages must include it
+const EKU_CODE_SIGNING: &[u8] = &[0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x03];
+
+// Chains longer than this are refused before any parsing
+const MAX_CHAIN_LEN: usize = 8;
+
+// Define the CaSet struct, the certificate authorities an auditor accepts operator
+// certificates from
+#[derive(Debug, Clone, Default)]
+pub struct CaSet {
+    certificates: Vec<CertificateDer<'static>>,
+}
+
+impl CaSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Adds a DER-encoded CA certificate, checking it can serve as a trust anchor
+    pub fn add_der(&mut self, der: Vec<u8>) -> Result<(), X509Error> {
+        let certificate = CertificateDer::from(der);
+        webpki::anchor_from_trusted_cert(&certificate).map_err(X509Error::InvalidCa)?;
+        self.certificates.push(certificate);
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.certificates.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.certificates.is_empty()
+    }
+
+    fn anchors(&self) -> Result<Vec<TrustAnchor<'_>>, X509Error> {
+        self.certificates
+            .iter()
+            .map(|certificate| {
+                webpki::anchor_from_trusted_cert(certificate).map_err(X509Error::InvalidCa)
+            })
+            .collect()
+    }
+}
+
+// Define the CertifiedRoot struct, a signed root with the X.509 chain of the key that
+// signed it, leaf certificate first. The leaf must hold the operator's Ed25519 key.
+#[derive(Debug, Clone)]
+pub struct CertifiedRoot<C: SumCommitment> {
+    pub signed_root: SignedRoot<C>,
+    // DER certificates from the signing certificate up, CA excluded
+    pub chain: Vec<Vec<u8>>,
+}
+
+impl<C: SumCommitment> CertifiedRoot<C> {
+    pub fn new(signed_root: SignedRoot<C>, chain: Vec<Vec<u8>>) -> Self {
+        CertifiedRoot { signed_root, chain }
+    }
+
+    // Checks the chain leads to a CA in `cas` and is valid at `at` (Unix seconds), then
+    // that the leaf certificate's key made the root signature
+    pub fn verify(&self, cas: &CaSet, at: u64) -> Result<(), X509Error> {
+        let (leaf, intermediates) = self.chain.split_first().ok_or(X509Error::EmptyChain)?;
+        if self.chain.len() > MAX_CHAIN_LEN {
+            return Err(X509Error::ChainTooLong(self.chain.len()));
+        }
+        let leaf = CertificateDer::from(leaf.as_slice());
+        let intermediates: Vec<CertificateDer<'_>> = intermediates
+            .iter()
+            .map(|der| CertificateDer::from(der.as_slice()))
+            .collect();
+        let end_entity = EndEntityCert::try_from(&leaf).map_err(X509Error::InvalidCertificate)?;
+
+        end_entity
+            .verify_for_usage(
+                webpki::ALL_VERIFICATION_ALGS,
+                &cas.anchors()?,
+                &intermediates,
+                UnixTime::since_unix_epoch(std::time::Duration::from_secs(at)),
+                KeyUsage::required_if_present(EKU_CODE_SIGNING),
+                None,
+                None,
+            )
+            .map_err(X509Error::Untrusted)?;
+        end_entity
+            .verify_signature(
+                webpki::ring::ED25519,
+                &self.signed_root.payload(),
+                &self.signed_root.signature.to_bytes(),
+            )
+            .map_err(|_| X509Error::BadSignature)
+    }
+
+    // epoch | root | signature | certificate count (u8) | length-prefixed certificates
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.signed_root.epoch.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&self.signed_root.root.to_bytes());
+        bytes.extend_from_slice(&self.signed_root.signature.to_bytes());
+        bytes.push(self.chain.len() as u8);
+        for certificate in &self.chain {
+            bytes.extend_from_slice(&encode_len(certificate));
+            bytes.extend_from_slice(certificate);
+        }
+        bytes
+    }
+}
+
+impl<C: SumCommitment> TryFrom<&[u8]> for CertifiedRoot<C> {
+    type Error = ParseError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        let mut reader = ByteReader::new(bytes);
+        let epoch = u64::from_le_bytes(reader.take_array::<8>()?);
+        let root = Root::try_from(reader.take(COMMITMENT_LEN)?)?;
+        let signature = ed25519_dalek::Signature::from_bytes(&reader.take_array::<64>()?);
+        let count = reader.take_array::<1>()?[0] as usize;
+        if count > MAX_CHAIN_LEN {
+            return Err(ParseError::TooDeep(count));
+        }
+        let mut chain = Vec::with_capacity(count);
+        for _ in 0..count {
+            let len = u64::from_le_bytes(reader.take_array::<8>()?);
+            let len = usize::try_from(len).map_err(|_| ParseError::PositionOverflow)?;
+            chain.push(reader.take(len)?.to_vec());
+        }
+        reader.finish()?;
+        Ok(CertifiedRoot {
+            signed_root: SignedRoot {
+                epoch,
+                root,
+                signature,
+            },
+            chain,
+        })
+    }
+}
+
+// Define the X509Error enum for certificate chains that don't vouch for a root
+#[derive(Debug)]
+pub enum X509Error {
+    EmptyChain,
+    ChainTooLong(usize),
+    InvalidCa(webpki::Error),
+    InvalidCertificate(webpki::Error),
+    // The chain doesn't lead to a configured CA, or isn't valid at the given time
+    Untrusted(webpki::Error),
+    // The leaf certificate's key didn't sign the root
+    BadSignature,
+}
+
+impl fmt::Display for X509Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            X509Error::EmptyChain => write!(f, "no certificates attached to the root"),
+            X509Error::ChainTooLong(len) => write!(f, "certificate chain of {} is too long", len),
+            X509Error::InvalidCa(err) => write!(f, "unusable CA certificate: {}", err),
+            X509Error::InvalidCertificate(err) => write!(f, "malformed certificate: {}", err),
+            X509Error::Untrusted(err) => write!(f, "certificate chain not trusted: {}", err),
+            X509Error::BadSignature => {
+                write!(f, "root signature was not made by the certified key")
+            }
+        }
+    }
+}
+
+impl std::error::Error for X509Error {}
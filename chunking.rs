@@ -0,0 +1,131 @@
+use sha2::{Digest as _, Sha256};
+
+use crate::encoding::{encode_commitment, encode_usize};
+use crate::{Digest, Root, StreamingVerifier, SumCommitment};
+
+const FRONTIER_DOMAIN: &[u8] = b"mimi-proof-frontier-v1";
+
+// Define the ProofChunk struct, a run of multiproof records together with the verifier
+// frontier it starts from and a commitment to the frontier it ends at. Each chunk checks
+// on its own; chaining the frontier digests ties the chunks back into one proof.
+#[derive(Debug, Clone)]
+pub struct ProofChunk<C: SumCommitment> {
+    // Leaves of the tree the records belong to
+    pub leaf_count: usize,
+    pub input: Vec<(usize, C)>,
+    pub records: Vec<(usize, C)>,
+    pub output: Digest,
+}
+
+impl<C: SumCommitment> ProofChunk<C> {
+    pub fn input_digest(&self) -> Digest {
+        frontier_digest(&self.input)
+    }
+
+    // Folds the records onto the input frontier and checks the result against `output`
+    pub fn verify(&self) -> bool {
+        self.fold()
+            .is_some_and(|verifier| frontier_digest(verifier.frontier()) == self.output)
+    }
+
+    fn fold(&self) -> Option<StreamingVerifier<C>> {
+        let mut verifier = StreamingVerifier::resume(self.leaf_count, self.input.clone());
+        for (node_index, commitment) in &self.records {
+            if !verifier.push(*node_index, commitment.clone()) {
+                return None;
+            }
+        }
+        Some(verifier)
+    }
+}
+
+// Splits a record stream (as from `MimkMerkleTree::multiproof`) of a tree with
+// `leaf_count` leaves into chunks of at most `max_records` records. Returns None when the
+// stream is malformed.
+pub fn split_records<C: SumCommitment>(
+    records: &[(usize, C)],
+    leaf_count: usize,
+    max_records: usize,
+) -> Option<Vec<ProofChunk<C>>> {
+    let mut verifier = StreamingVerifier::new(leaf_count);
+    let mut chunks = Vec::with_capacity(records.len().div_ceil(max_records.max(1)));
+    for run in records.chunks(max_records.max(1)) {
+        let input = verifier.frontier().to_vec();
+        for (node_index, commitment) in run {
+            if !verifier.push(*node_index, commitment.clone()) {
+                return None;
+            }
+        }
+        chunks.push(ProofChunk {
+            leaf_count,
+            input,
+            records: run.to_vec(),
+            output: frontier_digest(verifier.frontier()),
+        });
+    }
+    Some(chunks)
+}
+
+// Checks the chain links and every chunk, then that the last one closes at `root`, and
+// returns the leaf positions the chunks prove together, as `StreamingVerifier::finish`
+// does. Chunks can be verified independently (in parallel or inside a recursive
+// prover); this is the sequential reference.
+pub fn verify_chunks<C: SumCommitment>(
+    chunks: &[ProofChunk<C>],
+    leaf_count: usize,
+    root: &Root<C>,
+) -> Option<Vec<usize>> {
+    if !chunks.first()?.input.is_empty()
+        || chunks.iter().any(|chunk| chunk.leaf_count != leaf_count)
+        || !chunks
+            .windows(2)
+            .all(|pair| pair[0].output == pair[1].input_digest())
+    {
+        return None;
+    }
+    let mut leaves = Vec::new();
+    let mut closed = false;
+    for chunk in chunks {
+        let verifier = chunk.fold()?;
+        if frontier_digest(verifier.frontier()) != chunk.output {
+            return None;
+        }
+        leaves.extend_from_slice(verifier.leaves());
+        closed = verifier.closes_at(root);
+    }
+    (closed && !leaves.is_empty()).then_some(leaves)
+}
+
+// SHA-256(domain | entry count | per entry node index | commitment)
+pub fn frontier_digest<C: SumCommitment>(frontier: &[(usize, C)]) -> Digest {
+    let mut hasher = Sha256::new();
+    hasher.update(FRONTIER_DOMAIN);
+    hasher.update(encode_usize(frontier.len()));
+    for (node_index, commitment) in frontier {
+        hasher.update(encode_usize(*node_index));
+        hasher.update(encode_commitment(commitment.amount(), &commitment.digest()));
+    }
+    Digest::from(hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MerkleProof, MimiSumCommitment, MimkMerkleTree};
+
+    #[test]
+    fn chunks_prove_the_same_leaves_as_the_stream() {
+        let leaves: Vec<u64> = (1..=6).collect();
+        let tree: MimkMerkleTree<MimiSumCommitment, MerkleProof<MimiSumCommitment>> =
+            MimkMerkleTree::builder().build(&leaves).unwrap();
+        let records = tree.multiproof_records(None, 0, usize::MAX).0;
+        let chunks = split_records(&records, 6, 2).unwrap();
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(
+            verify_chunks(&chunks, 6, &tree.commit()),
+            Some((0..6).collect())
+        );
+        assert_eq!(verify_chunks(&chunks, 7, &tree.commit()), None);
+        assert_eq!(verify_chunks(&chunks[..2], 6, &tree.commit()), None);
+    }
+}
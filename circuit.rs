@@ -0,0 +1,186 @@
+use std::fmt;
+
+use generic_array::typenum::U32;
+use generic_array::GenericArray;
+use serde::{Deserialize, Serialize};
+
+use crate::builder::derive_salt;
+use crate::config::{HashBackend, SaltDerivation};
+use crate::rescue::digest_from_bytes;
+use crate::{ExclusiveAllotmentProof, LeafCommitment, MimkMerkleTree, SumCommitment};
+
+// Salt bytes per Goldilocks element, matching how `rescue::hash_bytes` packs preimages
+const BYTES_PER_GOLDILOCKS_ELEMENT: usize = 7;
+
+// Define the CircuitBackend enum for the field a proving circuit works over, which fixes
+// how digests, amounts and salts are cut into field elements
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CircuitBackend {
+    // 64-bit Goldilocks field (Plonky2, Winterfell). Rescue digests are their four
+    // elements; SHA-256 digests are eight 32-bit big-endian words. Amounts are two 32-bit
+    // limbs, low first, as Rescue nodes hash them. Salts are packed 7 bytes per element,
+    // little-endian.
+    Goldilocks,
+    // BN254 scalar field (Circom, Halo2). 32-byte values are two 128-bit limbs, high
+    // first, and an amount is one element.
+    Bn254,
+}
+
+impl CircuitBackend {
+    // Fails only for Rescue digests with a word outside the field
+    pub(crate) fn digest(
+        self,
+        hash_backend: HashBackend,
+        digest: &GenericArray<u8, U32>,
+    ) -> Result<Vec<String>, CircuitError> {
+        Ok(match (self, hash_backend) {
+            (CircuitBackend::Goldilocks, HashBackend::RescuePrime) => digest_from_bytes(digest)
+                .ok_or(CircuitError::NonCanonicalDigest)?
+                .iter()
+                .map(u64::to_string)
+                .collect(),
+            (CircuitBackend::Goldilocks, HashBackend::Sha256) => digest
+                .chunks_exact(4)
+                .map(|word| u32::from_be_bytes(word.try_into().expect("4-byte words")))
+                .map(|word| word.to_string())
+                .collect(),
+            (CircuitBackend::Bn254, _) => u128_limbs(digest),
+        })
+    }
+
+    pub(crate) fn amount(self, amount: u64) -> Vec<String> {
+        match self {
+            CircuitBackend::Goldilocks => {
+                vec![(amount & 0xFFFF_FFFF).to_string(), (amount >> 32).to_string()]
+            }
+            CircuitBackend::Bn254 => vec![amount.to_string()],
+        }
+    }
+
+    pub(crate) fn salt(self, salt: &[u8; 32]) -> Vec<String> {
+        match self {
+            CircuitBackend::Goldilocks => salt
+                .chunks(BYTES_PER_GOLDILOCKS_ELEMENT)
+                .map(|chunk| {
+                    chunk
+                        .iter()
+                        .rev()
+                        .fold(0u64, |acc, byte| (acc << 8) | *byte as u64)
+                        .to_string()
+                })
+                .collect(),
+            CircuitBackend::Bn254 => u128_limbs(salt),
+        }
+    }
+}
+
+fn u128_limbs(bytes: &[u8]) -> Vec<String> {
+    bytes
+        .chunks_exact(16)
+        .map(|limb| u128::from_be_bytes(limb.try_into().expect("16-byte limbs")).to_string())
+        .collect()
+}
+
+// Define the CircuitWitness struct, everything a membership circuit needs for one leaf,
+// already cut into field elements. Elements are decimal strings, as Circom and most
+// prover front ends read them; path entries run from the leaf up to the root.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CircuitWitness {
+    pub backend: CircuitBackend,
+    pub position: usize,
+    pub leaf_digest: Vec<String>,
+    pub leaf_amount: Vec<String>,
+    // The leaf's salt, for trees built with salts
+    pub salt: Option<Vec<String>>,
+    pub sibling_digests: Vec<Vec<String>>,
+    pub sibling_amounts: Vec<Vec<String>>,
+    // "1" when the sibling is the left child
+    pub directions: Vec<String>,
+    // Sum of the node above each level, ending with the root's
+    pub path_sums: Vec<Vec<String>>,
+    pub root_digest: Vec<String>,
+    pub root_amount: Vec<String>,
+}
+
+impl<C, P> MimkMerkleTree<C, P>
+where
+    C: SumCommitment,
+    P: ExclusiveAllotmentProof<C>,
+{
+    pub fn circuit_witness(
+        &self,
+        position: usize,
+        backend: CircuitBackend,
+    ) -> Result<CircuitWitness, CircuitError> {
+        if position >= self.len() {
+            return Err(CircuitError::UnknownPosition(position));
+        }
+        let proof = self.prove(position);
+        let hash_backend = C::HASH_BACKEND;
+        let leaf = proof.leaf();
+
+        let mut sum = leaf.amount();
+        let mut path_sums = Vec::with_capacity(proof.siblings().len());
+        for (sibling, _) in proof.siblings() {
+            sum = sum
+                .checked_add(sibling.amount())
+                .ok_or(CircuitError::SumOverflow)?;
+            path_sums.push(backend.amount(sum));
+        }
+        let root = self.root_node();
+
+        Ok(CircuitWitness {
+            backend,
+            position,
+            leaf_digest: backend.digest(hash_backend, &leaf.digest())?,
+            leaf_amount: backend.amount(leaf.amount()),
+            salt: match &self.config.salt_derivation {
+                SaltDerivation::None => None,
+                SaltDerivation::FromSeed(seed) => {
+                    Some(backend.salt(&derive_salt(seed, position)))
+                }
+            },
+            sibling_digests: proof
+                .siblings()
+                .iter()
+                .map(|(sibling, _)| backend.digest(hash_backend, &sibling.digest()))
+                .collect::<Result<_, _>>()?,
+            sibling_amounts: proof
+                .siblings()
+                .iter()
+                .map(|(sibling, _)| backend.amount(sibling.amount()))
+                .collect(),
+            directions: proof
+                .siblings()
+                .iter()
+                .map(|(_, sibling_on_left)| (*sibling_on_left as u8).to_string())
+                .collect(),
+            path_sums,
+            root_digest: backend.digest(hash_backend, &root.digest())?,
+            root_amount: backend.amount(root.amount()),
+        })
+    }
+}
+
+// Define the CircuitError enum for leaves a witness can't be produced for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CircuitError {
+    UnknownPosition(usize),
+    SumOverflow,
+    // A Rescue digest word is at or above the field modulus
+    NonCanonicalDigest,
+}
+
+impl fmt::Display for CircuitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CircuitError::UnknownPosition(position) => {
+                write!(f, "tree has no leaf at position {}", position)
+            }
+            CircuitError::SumOverflow => write!(f, "path sums overflow 64 bits"),
+            CircuitError::NonCanonicalDigest => write!(f, "digest is not a field element"),
+        }
+    }
+}
+
+impl std::error::Error for CircuitError {}
@@ -0,0 +1,867 @@
+use std::env;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use zeroize::Zeroizing;
+
+use futures::executor::block_on;
+
+use crate::airgap::{AirGapError, UnsignedRoot};
+use crate::amount_format::AmountFormat;
+use crate::balance_source::{collect_accounts, collect_entries, CsvSource};
+use crate::keys::{KeyError, Keystore};
+use crate::manifest::BuildManifest;
+use crate::porz::{apply_delta_porz, create_delta_porz, PorzReader};
+use crate::proof_format::{decode_proof, encode_proof, ProofFormat};
+use crate::root_log::{ConsistencyProof, LogEntry};
+use crate::scheduler::DirectoryStore;
+use crate::server::{ProofResponse, TenantId};
+use crate::trust::{PinnedRoot, TrustError, TrustStore};
+use crate::snapshot::Snapshot;
+use crate::{
+    BundleError, Digest, ExclusiveAllotmentProof, LeafCommitment, MerkleProof,
+    MerkleSumTreeBuilder, MimiSumCommitment, Root, SumCommitment, VerificationPolicy,
+};
+
+const PASSPHRASE_VAR: &str = "MIMI_KEYSTORE_PASSPHRASE";
+
+const USAGE: &str = "usage:
+  mimi build <ledger.csv> --epoch <n> (--dry-run | --out <snapshot> | --tenant <name>)
+             [--asset <asset>] [--decimals <n>] [--report <report.json>]
+  mimi sign-root <unsigned-root> --keystore <keystore.json> [--out <signature>]
+  mimi verify-archive <bundles.tar|.zip|.porz> --epoch <n>
+                      (--root <amount:hex> --key <hex> | --trust-store <trust.json> [--root <amount:hex>])
+                      [--threads <n>] [--report <report.json>]
+  mimi merge-trust <trust.json> <other.json>... [--out <trust.json>]
+  mimi delta-archive --base <old.porz> --new <new.porz> --out <delta.porz>
+  mimi apply-delta --base <old.porz> --delta <delta.porz> --out <new.porz>
+  mimi convert <proof> --to <json|binary|cbor|calldata> [--from <format>] [--out <file>]
+  mimi verify-interactive
+  mimi consistency --old <root.json> --new <root.json> --proof <consistency.bin>
+  mimi inspect header <snapshot>
+  mimi inspect levels <snapshot>
+  mimi inspect node <snapshot> --level <n> --index <n>
+  mimi inspect find <snapshot> <hex leaf digest>
+  mimi openapi [--tenants] [--server <url>] [--out <openapi.json>]";
+
+// Runs one subcommand; `args` excludes the program name
+pub fn run(args: &[String]) -> Result<(), CliError> {
+    match args.split_first() {
+        Some((command, rest)) if command == "build" => build(rest),
+        Some((command, rest)) if command == "sign-root" => sign_root(rest),
+        #[cfg(feature = "archive")]
+        Some((command, rest)) if command == "verify-archive" => verify_archive(rest),
+        Some((command, rest)) if command == "merge-trust" => merge_trust(rest),
+        Some((command, rest)) if command == "delta-archive" => delta_archive(rest),
+        Some((command, rest)) if command == "apply-delta" => apply_delta(rest),
+        Some((command, rest)) if command == "convert" => convert(rest),
+        Some((command, [])) if command == "verify-interactive" => verify_interactive(),
+        Some((command, rest)) if command == "consistency" => consistency(rest),
+        Some((command, rest)) if command == "inspect" => inspect(rest),
+        #[cfg(feature = "openapi")]
+        Some((command, rest)) if command == "openapi" => openapi(rest),
+        Some((command, _)) if command == "help" || command == "--help" => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        Some((command, _)) => Err(CliError::Usage(format!("unknown command `{}`", command))),
+        None => Err(CliError::Usage("missing command".to_string())),
+    }
+}
+
+// Builds an epoch's tree from a ledger export and writes its snapshot, with the build
+// manifest next to it as `<snapshot>.manifest.json`. With --dry-run the export is only
+// checked: the statistics report is printed (or written) and nothing is hashed, failing
+// when the real build would. With --tenant the manifest names the tenant and the
+// snapshot defaults to `<tenant>/epoch-<n>.snapshot`, the layout a tenant's server reads.
+fn build(args: &[String]) -> Result<(), CliError> {
+    let mut ledger = None;
+    let (mut epoch, mut out, mut asset, mut decimals) = (None, None, None, None);
+    let (mut report_path, mut tenant) = (None, None);
+    let mut dry_run = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--epoch" => epoch = Some(flag_value(&mut args, "--epoch")?),
+            "--tenant" => tenant = Some(flag_value(&mut args, "--tenant")?),
+            "--out" => out = Some(flag_value(&mut args, "--out")?),
+            "--asset" => asset = Some(flag_value(&mut args, "--asset")?),
+            "--decimals" => decimals = Some(flag_value(&mut args, "--decimals")?),
+            "--report" => report_path = Some(flag_value(&mut args, "--report")?),
+            "--dry-run" => dry_run = true,
+            _ if ledger.is_none() && !arg.starts_with("--") => ledger = Some(arg.clone()),
+            _ => return Err(CliError::Usage(format!("unexpected argument `{}`", arg))),
+        }
+    }
+    let ledger = ledger.ok_or_else(|| CliError::Usage("missing <ledger.csv>".to_string()))?;
+    let epoch: u64 = required(epoch, "--epoch")?
+        .parse()
+        .map_err(|_| CliError::Usage("--epoch must be a number".to_string()))?;
+    let decimals = match decimals {
+        Some(decimals) => decimals
+            .parse()
+            .map_err(|_| CliError::Usage("--decimals must be a number".to_string()))?,
+        None => 0,
+    };
+    let tenant: Option<TenantId> = tenant
+        .map(|tenant| tenant.parse())
+        .transpose()
+        .map_err(|err| CliError::Usage(format!("--tenant: {}", err)))?;
+    let mut source =
+        CsvSource::new(&ledger).with_amount_format(AmountFormat::default().with_decimals(decimals));
+    let builder = MerkleSumTreeBuilder::<MimiSumCommitment, MerkleProof<MimiSumCommitment>>::new();
+
+    if dry_run {
+        if out.is_some() {
+            return Err(CliError::Usage("--dry-run writes no --out".to_string()));
+        }
+        // Repeated pairs are reported rather than failing the read
+        let (point, entries) = block_on(collect_entries(&mut source, epoch, asset.as_deref()))
+            .map_err(|err| CliError::Failed(err.to_string()))?;
+        let leaves: Vec<_> = entries.into_iter().map(|entry| entry.into_leaf(&point)).collect();
+        let report = builder.dry_run(&leaves);
+        let json = serde_json::to_string_pretty(&report)
+            .map_err(|err| CliError::Failed(err.to_string()))?;
+        match report_path {
+            Some(path) => std::fs::write(&path, json).map_err(CliError::Io)?,
+            None => println!("{}", json),
+        }
+        if !report.valid {
+            return Err(CliError::Failed(report.problems.join("; ")));
+        }
+        return Ok(());
+    }
+
+    if report_path.is_some() {
+        return Err(CliError::Usage("--report needs --dry-run".to_string()));
+    }
+    let out = match (out, &tenant) {
+        (Some(out), _) => out,
+        (None, Some(tenant)) => {
+            let path = DirectoryStore::for_tenant(".", tenant).path(epoch);
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir).map_err(CliError::Io)?;
+            }
+            path.display().to_string()
+        }
+        (None, None) => return Err(CliError::Usage("missing --out".to_string())),
+    };
+    let started_at = unix_now();
+    let (point, leaves) = block_on(collect_accounts(&mut source, epoch, asset.as_deref()))
+        .map_err(|err| CliError::Failed(err.to_string()))?;
+    let tree = builder
+        .clone()
+        .build(&leaves)
+        .map_err(|err| CliError::Failed(err.to_string()))?;
+    let snapshot = Snapshot::capture(epoch, &tree, &leaves).to_bytes();
+    std::fs::write(&out, &snapshot).map_err(CliError::Io)?;
+    let manifest_path = format!("{}.manifest.json", out);
+    BuildManifest::record(
+        epoch,
+        builder.config(),
+        &[&ledger],
+        &tree,
+        &snapshot,
+        started_at,
+        unix_now(),
+    )
+    .map(|manifest| match &tenant {
+        Some(tenant) => manifest.with_tenant(tenant),
+        None => manifest,
+    })
+    .and_then(|manifest| manifest.save(&manifest_path))
+    .map_err(|err| CliError::Failed(err.to_string()))?;
+    eprintln!("ledger: {}", point.marker);
+    eprintln!("root:   {}", tree.commit());
+    eprintln!("snapshot written to {} (manifest {})", out, manifest_path);
+    Ok(())
+}
+
+// Signs an exported to-be-signed root on the offline machine and writes the detached
+// signature next to it (or to --out)
+fn sign_root(args: &[String]) -> Result<(), CliError> {
+    let mut input = None;
+    let mut keystore = None;
+    let mut out = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--keystore" => keystore = Some(flag_value(&mut args, "--keystore")?),
+            "--out" => out = Some(flag_value(&mut args, "--out")?),
+            _ if input.is_none() && !arg.starts_with("--") => input = Some(arg.clone()),
+            _ => return Err(CliError::Usage(format!("unexpected argument `{}`", arg))),
+        }
+    }
+    let input = input.ok_or_else(|| CliError::Usage("missing <unsigned-root>".to_string()))?;
+    let keystore = keystore.ok_or_else(|| CliError::Usage("missing --keystore".to_string()))?;
+    let out = out.unwrap_or_else(|| format!("{}.sig", input));
+
+    let unsigned = UnsignedRoot::<MimiSumCommitment>::load(&input)?;
+    let keystore = Keystore::load(&keystore)?;
+    // Shown before unlocking so the operator can compare it with the online machine
+    eprintln!("epoch: {}", unsigned.epoch);
+    eprintln!("root:  {}", unsigned.root);
+    eprintln!("key:   {}", keystore.public_key);
+
+    let passphrase = read_passphrase()?;
+    let key = keystore.decrypt(passphrase.as_bytes())?;
+    unsigned.sign(&key)?.save(&out)?;
+    eprintln!("signature written to {}", out);
+    Ok(())
+}
+
+// Walks an end user through checking the proof file their exchange gave them: asks for
+// the file, the published root (pasted, or fetched from a URL with the "fetch" feature)
+// and the operator key, then explains the outcome in plain words
+fn verify_interactive() -> Result<(), CliError> {
+    println!("This checks that your balance was included in the exchange's proof of reserves.");
+    println!("You need three things from the exchange: your proof file, the published root");
+    println!("and the exchange's public key. Press Enter after each answer.");
+    println!();
+
+    let path = prompt("1. Path to your proof file (the .json you downloaded): ")?;
+    let proof = match std::fs::read(path.trim()) {
+        Ok(proof) => proof,
+        Err(err) => {
+            return fail(&format!(
+                "The file `{}` could not be opened ({}). Check the path and try again.",
+                path.trim(),
+                err
+            ))
+        }
+    };
+    let bundle = match serde_json::from_slice::<ProofResponse>(&proof)
+        .map_err(|err| err.to_string())
+        .and_then(|response| {
+            response
+                .to_bundle::<MimiSumCommitment>()
+                .map_err(|err| err.to_string())
+        }) {
+        Ok(bundle) => bundle,
+        Err(err) => {
+            return fail(&format!(
+                "That file is not a proof file this tool understands ({}). Download it again \
+                 from the exchange.",
+                err
+            ))
+        }
+    };
+
+    let answer = prompt("2. The published root, or the web address it is published at: ")?;
+    let published = match fetch_root(answer.trim()) {
+        Ok(published) => published,
+        Err(reason) => return fail(&reason),
+    };
+
+    let key = prompt("3. The exchange's public key (64 hexadecimal characters): ")?;
+    let key = match hex::decode(key.trim())
+        .ok()
+        .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+        .and_then(|bytes| ed25519_dalek::VerifyingKey::from_bytes(&bytes).ok())
+    {
+        Some(key) => key,
+        None => return fail("That is not a valid public key. Copy it again from the exchange."),
+    };
+    println!();
+
+    let signed_root = &bundle.signed_root;
+    if signed_root.root.to_bytes() != published.to_bytes() {
+        return fail(&format!(
+            "Your proof was made for the root {} but the exchange published {}. Your proof \
+             does not belong to the published total; ask the exchange why.",
+            signed_root.root, published
+        ));
+    }
+    match bundle.verify(&VerificationPolicy::new(key)) {
+        Ok(()) => {
+            println!("PASS: your balance is included.");
+            println!(
+                "  Your balance of {} is counted in the exchange's total of {} for period {}.",
+                bundle.proof.leaf().amount(),
+                signed_root.root.amount(),
+                signed_root.epoch
+            );
+            println!("  The total was signed with the public key you entered.");
+            Ok(())
+        }
+        Err(BundleError::BadSignature) => fail(
+            "The published root was not signed with the key you entered. Either the key is \
+             wrong or the root did not come from the exchange.",
+        ),
+        Err(err) => fail(&format!(
+            "Your proof does not add up to the published root ({}). Your balance may not \
+             have been counted; contact the exchange and keep the proof file.",
+            err
+        )),
+    }
+}
+
+// A pasted `<amount>:<hex>` root, or one fetched from a URL whose body is that text or
+// JSON with a `root` field (a proof response or `/status`)
+fn fetch_root(answer: &str) -> Result<Root<MimiSumCommitment>, String> {
+    let invalid = |text: &str| {
+        format!(
+            "`{}` is not a root. It looks like a number, a colon and a long hexadecimal code.",
+            text
+        )
+    };
+    if !(answer.starts_with("https://") || answer.starts_with("http://")) {
+        return answer.parse().map_err(|_| invalid(answer));
+    }
+    #[cfg(feature = "fetch")]
+    {
+        let unreachable = |err: &dyn fmt::Display| {
+            format!("The root could not be fetched from {} ({}).", answer, err)
+        };
+        let body = ureq::get(answer)
+            .call()
+            .map_err(|err| unreachable(&err))?
+            .into_string()
+            .map_err(|err| unreachable(&err))?;
+        let text = serde_json::from_str::<serde_json::Value>(&body)
+            .ok()
+            .and_then(|json| json.get("root")?.as_str().map(str::to_string))
+            .unwrap_or_else(|| body.trim().to_string());
+        text.parse().map_err(|_| invalid(&text))
+    }
+    #[cfg(not(feature = "fetch"))]
+    Err(format!(
+        "This build can't fetch web pages. Open {} in your browser and paste the root it shows.",
+        answer
+    ))
+}
+
+fn prompt(question: &str) -> Result<String, CliError> {
+    print!("{}", question);
+    io::stdout().flush().map_err(CliError::Io)?;
+    let mut line = String::new();
+    io::stdin().lock().read_line(&mut line).map_err(CliError::Io)?;
+    Ok(line)
+}
+
+fn fail(explanation: &str) -> Result<(), CliError> {
+    println!("FAIL: {}", explanation);
+    Err(CliError::Failed("inclusion was not verified".to_string()))
+}
+
+// Checks a consistency proof between two published roots, each a pinned-root JSON
+// (`epoch`, `root`, `previous`), and names the property that fails if it doesn't hold
+fn consistency(args: &[String]) -> Result<(), CliError> {
+    let (mut old, mut new, mut proof) = (None, None, None);
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--old" => old = Some(flag_value(&mut args, "--old")?),
+            "--new" => new = Some(flag_value(&mut args, "--new")?),
+            "--proof" => proof = Some(flag_value(&mut args, "--proof")?),
+            _ => return Err(CliError::Usage(format!("unexpected argument `{}`", arg))),
+        }
+    }
+    let old = load_log_entry(&required(old, "--old")?)?;
+    let new = load_log_entry(&required(new, "--new")?)?;
+    let proof = std::fs::read(required(proof, "--proof")?).map_err(CliError::Io)?;
+    let proof = ConsistencyProof::<MimiSumCommitment>::try_from(proof.as_slice())
+        .map_err(|err| CliError::Failed(format!("malformed consistency proof: {}", err)))?;
+
+    eprintln!("old: epoch {} root {}", old.epoch, old.root);
+    eprintln!("new: epoch {} root {}", new.epoch, new.root);
+    proof
+        .verify(&old, &new)
+        .map_err(|err| CliError::Failed(format!("not consistent: {}", err)))?;
+    eprintln!(
+        "consistent: {} log entries extend epoch {} to epoch {} append-only",
+        proof.entries.len(),
+        old.epoch,
+        new.epoch
+    );
+    Ok(())
+}
+
+fn load_log_entry(path: &str) -> Result<LogEntry<MimiSumCommitment>, CliError> {
+    let json = std::fs::read_to_string(path).map_err(CliError::Io)?;
+    let pinned: PinnedRoot = serde_json::from_str(&json)
+        .map_err(|err| CliError::Failed(format!("{}: {}", path, err)))?;
+    Ok(pinned.to_log_entry()?)
+}
+
+// Read-only views of a snapshot file for debugging a root: its summary (and the build
+// manifest next to it, when there is one), node counts per level, one node by level and
+// index (root at level 0), or the leaf with a given digest
+fn inspect(args: &[String]) -> Result<(), CliError> {
+    let (view, rest) = args
+        .split_first()
+        .ok_or_else(|| CliError::Usage("missing inspect view".to_string()))?;
+    let (path, rest) = rest
+        .split_first()
+        .ok_or_else(|| CliError::Usage("missing <snapshot>".to_string()))?;
+    let bytes = std::fs::read(path).map_err(CliError::Io)?;
+    let snapshot = Snapshot::<MimiSumCommitment>::try_from(bytes.as_slice())
+        .map_err(|err| CliError::Failed(format!("{}: {}", path, err)))?;
+    let tree = snapshot
+        .to_tree::<MerkleProof<MimiSumCommitment>>()
+        .map_err(|err| CliError::Failed(format!("{}: {}", path, err)))?;
+
+    match view.as_str() {
+        "header" => {
+            let with_preimage = snapshot
+                .leaves()
+                .iter()
+                .filter(|leaf| leaf.preimage.is_some())
+                .count();
+            println!("epoch:           {}", snapshot.epoch);
+            println!("leaves:          {}", snapshot.len());
+            println!("with preimage:   {}", with_preimage);
+            println!("commitment only: {}", snapshot.len() - with_preimage);
+            println!("depth:           {}", tree.depth());
+            println!("root:            {}", tree.commit());
+            let manifest_path = format!("{}.manifest.json", path);
+            if let Ok(manifest) = BuildManifest::load(&manifest_path) {
+                println!("manifest:        {}", manifest_path);
+                println!("  crate:         {}", manifest.crate_version);
+                println!("  config hash:   {}", manifest.config_hash);
+                if let Some(tenant) = &manifest.tenant {
+                    println!("  tenant:        {}", tenant);
+                }
+                println!("  built:         {} - {}", manifest.started_at, manifest.finished_at);
+            }
+        }
+        "levels" => {
+            for level in 0..=tree.depth() {
+                let first = (1usize << level) - 1;
+                let nodes = (first..first + (1usize << level))
+                    .filter(|index| tree.node_at(*index).is_some())
+                    .count();
+                println!("level {:>2}: {} nodes", level, nodes);
+            }
+        }
+        "node" => {
+            let (mut level, mut index) = (None, None);
+            let mut args = rest.iter();
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    "--level" => level = Some(flag_value(&mut args, "--level")?),
+                    "--index" => index = Some(flag_value(&mut args, "--index")?),
+                    _ => return Err(CliError::Usage(format!("unexpected argument `{}`", arg))),
+                }
+            }
+            let level: u32 = required(level, "--level")?
+                .parse()
+                .map_err(|_| CliError::Usage("--level must be a number".to_string()))?;
+            let index: usize = required(index, "--index")?
+                .parse()
+                .map_err(|_| CliError::Usage("--index must be a number".to_string()))?;
+            let node_index = 1usize
+                .checked_shl(level)
+                .filter(|width| index < *width)
+                .map(|width| width - 1 + index);
+            let node = node_index.and_then(|node_index| tree.node_at(node_index));
+            match node {
+                Some(node) => println!("{}:{}", node.amount(), Digest::from(node.digest())),
+                None => {
+                    return Err(CliError::Failed(format!(
+                        "no node at level {} index {}",
+                        level, index
+                    )))
+                }
+            }
+        }
+        "find" => {
+            let digest: Digest = rest
+                .first()
+                .ok_or_else(|| CliError::Usage("missing <hex leaf digest>".to_string()))?
+                .parse()
+                .map_err(|err| CliError::Usage(format!("leaf digest: {}", err)))?;
+            let position = tree
+                .iter_leaves()
+                .position(|leaf| leaf.digest().as_slice() == digest.as_bytes())
+                .ok_or_else(|| CliError::Failed(format!("no leaf with digest {}", digest)))?;
+            let leaf = &snapshot.leaves()[position];
+            println!("position: {}", position);
+            println!("amount:   {}", leaf.commitment.amount());
+            match &leaf.preimage {
+                Some(preimage) => {
+                    if let Some(user_id) = &preimage.user_id {
+                        println!("user id:  {}", String::from_utf8_lossy(user_id));
+                    }
+                    println!("records:  {}", preimage.records.len());
+                }
+                None => println!("preimage: none (padding or tombstoned)"),
+            }
+        }
+        other => return Err(CliError::Usage(format!("unknown inspect view `{}`", other))),
+    }
+    Ok(())
+}
+
+// Merges other trust stores into the first and writes it back (or to --out). Nothing is
+// written when the stores conflict.
+fn merge_trust(args: &[String]) -> Result<(), CliError> {
+    let mut stores = Vec::new();
+    let mut out = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--out" => out = Some(flag_value(&mut args, "--out")?),
+            _ if !arg.starts_with("--") => stores.push(arg.clone()),
+            _ => return Err(CliError::Usage(format!("unexpected argument `{}`", arg))),
+        }
+    }
+    let Some((first, others)) = stores.split_first() else {
+        return Err(CliError::Usage("missing <trust.json>".to_string()));
+    };
+    if others.is_empty() {
+        return Err(CliError::Usage("nothing to merge into the trust store".to_string()));
+    }
+    let mut store = TrustStore::load(first)?;
+    for other in others {
+        store.merge(&TrustStore::load(other)?)?;
+    }
+    let out = out.unwrap_or_else(|| first.clone());
+    store.save(&out)?;
+    eprintln!(
+        "{} operator keys, {} witnesses, {} pinned roots written to {}",
+        store.operators.len(),
+        store.witnesses.len(),
+        store.pinned_roots.len(),
+        out
+    );
+    Ok(())
+}
+
+// Writes the delta between two full `.porz` archives of consecutive drops, carrying only
+// the bundles whose proofs changed
+fn delta_archive(args: &[String]) -> Result<(), CliError> {
+    let (mut base, mut new, mut out) = (None, None, None);
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--base" => base = Some(flag_value(&mut args, "--base")?),
+            "--new" => new = Some(flag_value(&mut args, "--new")?),
+            "--out" => out = Some(flag_value(&mut args, "--out")?),
+            _ => return Err(CliError::Usage(format!("unexpected argument `{}`", arg))),
+        }
+    }
+    let (base, new) = (required(base, "--base")?, required(new, "--new")?);
+    let out = required(out, "--out")?;
+    let mut archive = PorzReader::<_, MimiSumCommitment>::new(open_buffered(&new)?)
+        .map_err(|err| CliError::Failed(format!("{}: {}", new, err)))?;
+    let bundles = archive
+        .by_ref()
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| CliError::Failed(format!("{}: {}", new, err)))?;
+    let total = bundles.len();
+    let written = write_atomically(&out, |file| {
+        create_delta_porz(
+            open_buffered(&base)?,
+            file,
+            &archive.manifest,
+            &archive.signed_root,
+            archive.signatures.clone(),
+            bundles,
+        )
+        .map_err(|err| CliError::Failed(err.to_string()))
+    })?;
+    eprintln!("delta written to {} ({} bundles in the new drop, {} bytes)", out, total, written);
+    Ok(())
+}
+
+// Rebuilds a full `.porz` archive from the previous one and a delta
+fn apply_delta(args: &[String]) -> Result<(), CliError> {
+    let (mut base, mut delta, mut out) = (None, None, None);
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--base" => base = Some(flag_value(&mut args, "--base")?),
+            "--delta" => delta = Some(flag_value(&mut args, "--delta")?),
+            "--out" => out = Some(flag_value(&mut args, "--out")?),
+            _ => return Err(CliError::Usage(format!("unexpected argument `{}`", arg))),
+        }
+    }
+    let (base, delta) = (required(base, "--base")?, required(delta, "--delta")?);
+    let out = required(out, "--out")?;
+    let written = write_atomically(&out, |file| {
+        let (base, delta) = (open_buffered(&base)?, open_buffered(&delta)?);
+        apply_delta_porz::<MimiSumCommitment, _>(base, delta, file)
+            .map_err(|err| CliError::Failed(err.to_string()))
+    })?;
+    eprintln!("full archive written to {} ({} bytes)", out, written);
+    Ok(())
+}
+
+fn open_buffered(path: &str) -> Result<BufReader<File>, CliError> {
+    Ok(BufReader::new(File::open(path).map_err(CliError::Io)?))
+}
+
+// Writes through `<path>.partial` and only renames it into place on success, so a failed
+// run never leaves a truncated archive behind; returns the bytes written
+fn write_atomically(
+    path: &str,
+    write: impl FnOnce(BufWriter<File>) -> Result<BufWriter<File>, CliError>,
+) -> Result<u64, CliError> {
+    let partial = format!("{}.partial", path);
+    let file = File::create(&partial).map_err(CliError::Io)?;
+    let result = write(BufWriter::new(file)).and_then(|out| {
+        out.into_inner()
+            .map_err(|err| CliError::Io(err.into_error()))?
+            .sync_all()
+            .map_err(CliError::Io)
+    });
+    if let Err(err) = result {
+        let _ = std::fs::remove_file(&partial);
+        return Err(err);
+    }
+    std::fs::rename(&partial, path).map_err(CliError::Io)?;
+    Ok(std::fs::metadata(path).map_err(CliError::Io)?.len())
+}
+
+// Re-encodes an inclusion proof, e.g. a binary proof as calldata for a verifier contract.
+// The input format comes from --from or the file extension (.json, .bin, .cbor,
+// .calldata or .hex); the proof is only translated, not verified.
+fn convert(args: &[String]) -> Result<(), CliError> {
+    let (mut input, mut from, mut to, mut out) = (None, None, None, None);
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--from" => from = Some(flag_value(&mut args, "--from")?),
+            "--to" => to = Some(flag_value(&mut args, "--to")?),
+            "--out" => out = Some(flag_value(&mut args, "--out")?),
+            _ if input.is_none() && !arg.starts_with("--") => input = Some(arg.clone()),
+            _ => return Err(CliError::Usage(format!("unexpected argument `{}`", arg))),
+        }
+    }
+    let input = input.ok_or_else(|| CliError::Usage("missing <proof>".to_string()))?;
+    let to: ProofFormat = required(to, "--to")?
+        .parse()
+        .map_err(|err| CliError::Usage(format!("--to: {}", err)))?;
+    let from = match from {
+        Some(from) => from
+            .parse()
+            .map_err(|err| CliError::Usage(format!("--from: {}", err)))?,
+        None => ProofFormat::from_extension(&input).ok_or_else(|| {
+            CliError::Usage(format!("can't tell the format of `{}`, pass --from", input))
+        })?,
+    };
+
+    let bytes = std::fs::read(&input).map_err(CliError::Io)?;
+    let proof = decode_proof::<MimiSumCommitment>(&bytes, from)
+        .map_err(|err| CliError::Failed(format!("{}: {}", input, err)))?;
+    let converted = encode_proof(&proof, to).map_err(|err| CliError::Failed(err.to_string()))?;
+    match out {
+        Some(path) => std::fs::write(&path, converted).map_err(CliError::Io)?,
+        None => io::stdout().write_all(&converted).map_err(CliError::Io)?,
+    }
+    eprintln!("converted {} proof at position {} to {}", from, proof.position(), to);
+    Ok(())
+}
+
+// Prints (or writes) the OpenAPI document of the proof server's public endpoints
+#[cfg(feature = "openapi")]
+fn openapi(args: &[String]) -> Result<(), CliError> {
+    use crate::server::{openapi_spec, OpenApiOptions};
+
+    let mut options = OpenApiOptions::default();
+    let mut out = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--tenants" => options.tenants = true,
+            "--server" => options.server_url = Some(flag_value(&mut args, "--server")?),
+            "--out" => out = Some(flag_value(&mut args, "--out")?),
+            _ => return Err(CliError::Usage(format!("unexpected argument `{}`", arg))),
+        }
+    }
+    let json = openapi_spec(&options)
+        .to_pretty_json()
+        .map_err(|err| CliError::Failed(err.to_string()))?;
+    match out {
+        Some(path) => std::fs::write(&path, json).map_err(CliError::Io)?,
+        None => println!("{}", json),
+    }
+    Ok(())
+}
+
+// Verifies every bundle of an epoch drop and prints (or writes) the JSON report; fails
+// when any bundle does. With a trust store the key comes from its key ring and a root
+// pinned for the epoch is used (or must match --root).
+#[cfg(feature = "archive")]
+fn verify_archive(args: &[String]) -> Result<(), CliError> {
+    use crate::bulk::{self, BulkOptions};
+
+    let mut archive = None;
+    let (mut epoch, mut root, mut key, mut trust_store) = (None, None, None, None);
+    let mut threads = None;
+    let mut report_path = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--epoch" => epoch = Some(flag_value(&mut args, "--epoch")?),
+            "--root" => root = Some(flag_value(&mut args, "--root")?),
+            "--key" => key = Some(flag_value(&mut args, "--key")?),
+            "--trust-store" => trust_store = Some(flag_value(&mut args, "--trust-store")?),
+            "--threads" => threads = Some(flag_value(&mut args, "--threads")?),
+            "--report" => report_path = Some(flag_value(&mut args, "--report")?),
+            _ if archive.is_none() && !arg.starts_with("--") => archive = Some(arg.clone()),
+            _ => return Err(CliError::Usage(format!("unexpected argument `{}`", arg))),
+        }
+    }
+    let archive = archive.ok_or_else(|| CliError::Usage("missing <bundles>".to_string()))?;
+    let epoch: u64 = required(epoch, "--epoch")?
+        .parse()
+        .map_err(|_| CliError::Usage("--epoch must be a number".to_string()))?;
+    let root: Option<Root<MimiSumCommitment>> = root
+        .map(|root| root.parse())
+        .transpose()
+        .map_err(|err| CliError::Usage(format!("--root: {}", err)))?;
+    let (root, key) = match (trust_store, key) {
+        (Some(_), Some(_)) => {
+            return Err(CliError::Usage(
+                "--key and --trust-store are exclusive".to_string(),
+            ))
+        }
+        (Some(path), None) => {
+            let store = TrustStore::load(&path)?;
+            let key = store.operator_key_for(epoch)?;
+            let pinned = match store.pinned(epoch) {
+                Some(pin) => Some(pin.to_log_entry::<MimiSumCommitment>()?.root),
+                None => None,
+            };
+            let root = match (root, pinned) {
+                (Some(root), Some(pinned)) if root != pinned => {
+                    return Err(CliError::Failed(format!(
+                        "--root {} differs from the root {} pinned for epoch {}",
+                        root, pinned, epoch
+                    )))
+                }
+                (Some(root), _) | (None, Some(root)) => root,
+                (None, None) => {
+                    return Err(CliError::Usage(format!(
+                        "missing --root; the trust store pins no root for epoch {}",
+                        epoch
+                    )))
+                }
+            };
+            (root, key)
+        }
+        (None, key) => (
+            root.ok_or_else(|| CliError::Usage("missing --root".to_string()))?,
+            parse_verifying_key(&required(key, "--key")?)?,
+        ),
+    };
+    let threads = match threads {
+        Some(threads) => threads
+            .parse()
+            .map_err(|_| CliError::Usage("--threads must be a number".to_string()))?,
+        None => 0,
+    };
+
+    let report = bulk::verify_archive(&archive, epoch, &root, &key, BulkOptions { threads })
+        .map_err(|err| CliError::Failed(err.to_string()))?;
+    let json =
+        serde_json::to_string_pretty(&report).map_err(|err| CliError::Failed(err.to_string()))?;
+    match report_path {
+        Some(path) => std::fs::write(&path, json).map_err(CliError::Io)?,
+        None => println!("{}", json),
+    }
+    if !report.passed {
+        return Err(CliError::Failed(format!(
+            "{} of {} bundles failed, {} positions proven more than once",
+            report.failures.len(),
+            report.files,
+            report.duplicate_positions.len()
+        )));
+    }
+    Ok(())
+}
+
+fn required(value: Option<String>, flag: &str) -> Result<String, CliError> {
+    value.ok_or_else(|| CliError::Usage(format!("missing {}", flag)))
+}
+
+#[cfg(feature = "archive")]
+fn parse_verifying_key(encoded: &str) -> Result<ed25519_dalek::VerifyingKey, CliError> {
+    let invalid = || CliError::Usage("--key must be a hex Ed25519 public key".to_string());
+    let bytes: [u8; 32] = hex::decode(encoded)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(invalid)?;
+    ed25519_dalek::VerifyingKey::from_bytes(&bytes).map_err(|_| invalid())
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+fn flag_value<'a>(
+    args: &mut impl Iterator<Item = &'a String>,
+    flag: &str,
+) -> Result<String, CliError> {
+    args.next()
+        .cloned()
+        .ok_or_else(|| CliError::Usage(format!("{} needs a value", flag)))
+}
+
+// Taken from the environment when set, so scripted ceremonies don't need a terminal
+fn read_passphrase() -> Result<Zeroizing<String>, CliError> {
+    if let Ok(passphrase) = env::var(PASSPHRASE_VAR) {
+        return Ok(Zeroizing::new(passphrase));
+    }
+    eprint!("keystore passphrase: ");
+    io::stderr().flush().map_err(CliError::Io)?;
+    let mut line = Zeroizing::new(String::new());
+    io::stdin()
+        .lock()
+        .read_line(&mut line)
+        .map_err(CliError::Io)?;
+    Ok(Zeroizing::new(
+        line.trim_end_matches(['\r', '\n']).to_string(),
+    ))
+}
+
+// Define the CliError enum for subcommands that fail; Usage also prints the usage text
+#[derive(Debug)]
+pub enum CliError {
+    Usage(String),
+    // The command ran but what it checked did not hold
+    Failed(String),
+    Io(io::Error),
+    Key(KeyError),
+    AirGap(AirGapError),
+    Trust(TrustError),
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::Usage(reason) => write!(f, "{}\n{}", reason, USAGE),
+            CliError::Failed(reason) => write!(f, "{}", reason),
+            CliError::Io(err) => write!(f, "i/o error: {}", err),
+            CliError::Key(err) => write!(f, "{}", err),
+            CliError::AirGap(err) => write!(f, "{}", err),
+            CliError::Trust(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for CliError {}
+
+impl From<KeyError> for CliError {
+    fn from(err: KeyError) -> Self {
+        CliError::Key(err)
+    }
+}
+
+impl From<AirGapError> for CliError {
+    fn from(err: AirGapError) -> Self {
+        CliError::AirGap(err)
+    }
+}
+
+impl From<TrustError> for CliError {
+    fn from(err: TrustError) -> Self {
+        CliError::Trust(err)
+    }
+}
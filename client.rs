@@ -0,0 +1,250 @@
+use std::fmt;
+
+use ed25519_dalek::VerifyingKey;
+
+use crate::bundle::{BundleError, SignedRoot, VerificationPolicy};
+use crate::root_log::LogEntry;
+use crate::server::{ProofRequest, ProofResponse, ProofServer, RetrievalToken};
+use crate::view::{ViewChecker, ViewError};
+use crate::{ExclusiveAllotmentProof, Leaf, LeafCommitment, ParseError, SumCommitment};
+
+// Define the ProofSource trait, how the client reaches the operator's proof server
+// (an HTTP client in practice, or the server itself in-process)
+pub trait ProofSource<C: SumCommitment> {
+    fn latest_root(&self) -> Result<SignedRoot<C>, String>;
+    fn log_since(&self, epoch: Option<u64>) -> Result<Vec<LogEntry<C>>, String>;
+    fn proof(&self, request: &ProofRequest) -> Result<ProofResponse, String>;
+}
+
+impl<C: SumCommitment, S: ProofSource<C> + ?Sized> ProofSource<C> for &S {
+    fn latest_root(&self) -> Result<SignedRoot<C>, String> {
+        (**self).latest_root()
+    }
+
+    fn log_since(&self, epoch: Option<u64>) -> Result<Vec<LogEntry<C>>, String> {
+        (**self).log_since(epoch)
+    }
+
+    fn proof(&self, request: &ProofRequest) -> Result<ProofResponse, String> {
+        (**self).proof(request)
+    }
+}
+
+impl<C: SumCommitment> ProofSource<C> for ProofServer<C> {
+    fn latest_root(&self) -> Result<SignedRoot<C>, String> {
+        ProofServer::latest_root(self)
+            .cloned()
+            .ok_or_else(|| "no epoch has been published yet".to_string())
+    }
+
+    fn log_since(&self, epoch: Option<u64>) -> Result<Vec<LogEntry<C>>, String> {
+        Ok(ProofServer::log_since(self, epoch).to_vec())
+    }
+
+    fn proof(&self, request: &ProofRequest) -> Result<ProofResponse, String> {
+        ProofServer::proof(self, request).map_err(|err| err.to_string())
+    }
+}
+
+// Define the Inclusion struct, the single answer a user cares about
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Inclusion {
+    pub epoch: u64,
+    pub balance: u64,
+    pub total: u64,
+}
+
+impl fmt::Display for Inclusion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "your balance {} is included in total {} (epoch {})",
+            self.balance, self.total, self.epoch
+        )
+    }
+}
+
+// Define the Client struct, pinned to the operator's key and the last root it accepted.
+// It holds the commitment of the user's own leaf, recomputed from their record (and the
+// salt the operator gave them, for salted trees), so a proof for any other leaf, even
+// one with the same amount, is refused.
+#[derive(Debug)]
+pub struct Client<C: SumCommitment, S: ProofSource<C>> {
+    source: S,
+    operator_key: VerifyingKey,
+    user_id: String,
+    expected_leaf: C::Leaf,
+    // Leaf position, when the operator told the user where their leaf is
+    position: Option<usize>,
+    view: ViewChecker<C>,
+}
+
+impl<C: SumCommitment, S: ProofSource<C>> Client<C, S> {
+    // `record` is the leaf exactly as the operator commits it, e.g. a `UserLeaf` holding
+    // the user id and balance
+    pub fn new<L: Leaf>(
+        source: S,
+        operator_key: VerifyingKey,
+        user_id: impl Into<String>,
+        record: &L,
+        salt: Option<&[u8; 32]>,
+    ) -> Self {
+        let expected_leaf = match salt {
+            Some(salt) => C::Leaf::from_salted_leaf(record, salt),
+            None => C::Leaf::from_leaf(record),
+        };
+        Client {
+            source,
+            operator_key,
+            user_id: user_id.into(),
+            expected_leaf,
+            position: None,
+            view: ViewChecker::new(operator_key),
+        }
+    }
+
+    pub fn with_position(mut self, position: usize) -> Self {
+        self.position = Some(position);
+        self
+    }
+
+    // Resumes from the log entry pinned in an earlier session, so later roots must extend it
+    pub fn with_pinned(mut self, entry: LogEntry<C>) -> Self {
+        self.view = ViewChecker::new(self.operator_key).with_log_tip(entry);
+        self
+    }
+
+    pub fn pinned(&self) -> Option<&LogEntry<C>> {
+        self.view.log_tip()
+    }
+
+    pub fn view(&self) -> &ViewChecker<C> {
+        &self.view
+    }
+
+    // Fetches the latest root and proof and checks, in order: the operator signature,
+    // the root log extending the pinned entry, and the user's inclusion under that root
+    pub fn check(&mut self, token: &RetrievalToken) -> Result<Inclusion, ClientError> {
+        let latest = self.source.latest_root().map_err(ClientError::Source)?;
+        if let Some(pinned) = self.view.log_tip() {
+            if latest.epoch < pinned.epoch {
+                return Err(ClientError::Rollback {
+                    pinned: pinned.epoch,
+                    served: latest.epoch,
+                });
+            }
+        }
+        self.view.observe(latest.clone())?;
+
+        let since = self.view.log_tip().map(|entry| entry.epoch);
+        if since != Some(latest.epoch) {
+            let entries = self.source.log_since(since).map_err(ClientError::Source)?;
+            self.view.check_log(&entries)?;
+        }
+        match self.view.log_tip() {
+            Some(tip) if tip.epoch == latest.epoch && tip.root.matches(latest.root.node()) => {}
+            _ => return Err(ClientError::NotLogged(latest.epoch)),
+        }
+
+        let request = ProofRequest {
+            epoch: Some(latest.epoch),
+            user_id: self.user_id.clone(),
+            token: token.to_string(),
+        };
+        let bundle = self
+            .source
+            .proof(&request)
+            .map_err(ClientError::Source)?
+            .to_bundle::<C>()?;
+        if bundle.signed_root.epoch != latest.epoch
+            || !bundle.signed_root.root.matches(latest.root.node())
+        {
+            return Err(ClientError::RootMismatch(latest.epoch));
+        }
+        bundle.verify(&VerificationPolicy::new(self.operator_key))?;
+
+        // A valid proof only shows some leaf is in the tree; it must be this user's
+        let proven = bundle.proof.leaf();
+        if proven.amount() != self.expected_leaf.amount()
+            || proven.digest() != self.expected_leaf.digest()
+        {
+            return Err(ClientError::WrongLeaf {
+                position: bundle.proof.position(),
+            });
+        }
+        if let Some(position) = self.position {
+            if bundle.proof.position() != position {
+                return Err(ClientError::WrongLeaf {
+                    position: bundle.proof.position(),
+                });
+            }
+        }
+
+        Ok(Inclusion {
+            epoch: latest.epoch,
+            balance: proven.amount(),
+            total: latest.root.amount(),
+        })
+    }
+}
+
+// Define the ClientError enum for checks that fail on the user's side
+#[derive(Debug)]
+pub enum ClientError {
+    Source(String),
+    Rollback { pinned: u64, served: u64 },
+    NotLogged(u64),
+    RootMismatch(u64),
+    // The proof is for a leaf other than the user's own
+    WrongLeaf { position: usize },
+    View(ViewError),
+    Parse(ParseError),
+    Bundle(BundleError),
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::Source(message) => write!(f, "proof server error: {}", message),
+            ClientError::Rollback { pinned, served } => write!(
+                f,
+                "server returned epoch {} after epoch {} was already accepted",
+                served, pinned
+            ),
+            ClientError::NotLogged(epoch) => {
+                write!(f, "root for epoch {} is missing from the root log", epoch)
+            }
+            ClientError::RootMismatch(epoch) => {
+                write!(f, "proof is not for the latest root of epoch {}", epoch)
+            }
+            ClientError::WrongLeaf { position } => write!(
+                f,
+                "proof is for the leaf at position {}, which is not this account's",
+                position
+            ),
+            ClientError::View(err) => write!(f, "{}", err),
+            ClientError::Parse(err) => write!(f, "malformed server response: {}", err),
+            ClientError::Bundle(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+impl From<ViewError> for ClientError {
+    fn from(err: ViewError) -> Self {
+        ClientError::View(err)
+    }
+}
+
+impl From<ParseError> for ClientError {
+    fn from(err: ParseError) -> Self {
+        ClientError::Parse(err)
+    }
+}
+
+impl From<BundleError> for ClientError {
+    fn from(err: BundleError) -> Self {
+        ClientError::Bundle(err)
+    }
+}
@@ -0,0 +1,239 @@
+use std::fmt;
+use std::io::{self, Read, Write};
+
+// Every encoded artifact starts with the magic and a tag naming its compression, so
+// readers can decode any artifact regardless of the codec they were configured with
+const MAGIC: &[u8; 4] = b"MIMC";
+const TAG_NONE: u8 = 0;
+const TAG_ZSTD: u8 = 1;
+const TAG_LZ4: u8 = 2;
+
+// Decoding into memory stops here unless raised with `with_max_decoded_len`
+pub const DEFAULT_MAX_DECODED_LEN: u64 = 4 << 30;
+
+// Define the Codec trait, how serialized snapshots and proof archives are written to disk
+pub trait Codec {
+    fn encode(&self, data: &[u8]) -> Result<Vec<u8>, CodecError>;
+    fn decode(&self, data: &[u8]) -> Result<Vec<u8>, CodecError>;
+}
+
+// Define the Compression enum. Zstd and Lz4 need the `zstd` and `lz4` features; without
+// them encoding or decoding that format fails with `CodecError::Unsupported`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Zstd { level: i32 },
+    Lz4,
+}
+
+impl Compression {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Compression::None => "none",
+            Compression::Zstd { .. } => "zstd",
+            Compression::Lz4 => "lz4",
+        }
+    }
+
+    fn tag(&self) -> u8 {
+        match self {
+            Compression::None => TAG_NONE,
+            Compression::Zstd { .. } => TAG_ZSTD,
+            Compression::Lz4 => TAG_LZ4,
+        }
+    }
+}
+
+// Define the ArtifactCodec struct, the framed codec epoch artifacts go through
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArtifactCodec {
+    compression: Compression,
+    max_decoded_len: u64,
+}
+
+impl ArtifactCodec {
+    pub fn new(compression: Compression) -> Self {
+        ArtifactCodec {
+            compression,
+            max_decoded_len: DEFAULT_MAX_DECODED_LEN,
+        }
+    }
+
+    // Bounds how much a decoded artifact may expand to, against decompression bombs
+    pub fn with_max_decoded_len(mut self, max_decoded_len: u64) -> Self {
+        self.max_decoded_len = max_decoded_len;
+        self
+    }
+
+    pub fn compression(&self) -> Compression {
+        self.compression
+    }
+
+    // Streams `reader` into `writer` without holding the artifact in memory.
+    // Returns the number of uncompressed bytes read.
+    pub fn encode_stream<R: Read, W: Write>(
+        &self,
+        reader: &mut R,
+        mut writer: W,
+    ) -> Result<u64, CodecError> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[self.compression.tag()])?;
+        let copied = match self.compression {
+            Compression::None => {
+                let copied = io::copy(reader, &mut writer)?;
+                writer.flush()?;
+                copied
+            }
+            Compression::Zstd { level } => zstd_encode(reader, writer, level)?,
+            Compression::Lz4 => lz4_encode(reader, writer)?,
+        };
+        Ok(copied)
+    }
+
+    // Returns the number of decoded bytes written
+    pub fn decode_stream<R: Read, W: Write>(
+        &self,
+        reader: &mut R,
+        mut writer: W,
+    ) -> Result<u64, CodecError> {
+        let mut header = [0u8; 5];
+        reader
+            .read_exact(&mut header)
+            .map_err(|_| CodecError::UnknownFormat)?;
+        if &header[..4] != MAGIC {
+            return Err(CodecError::UnknownFormat);
+        }
+        // One byte past the limit is enough to tell an oversized artifact apart
+        let limit = self.max_decoded_len.saturating_add(1);
+        let copied = match header[4] {
+            TAG_NONE => io::copy(&mut reader.take(limit), &mut writer)?,
+            TAG_ZSTD => zstd_decode(reader, &mut writer, limit)?,
+            TAG_LZ4 => lz4_decode(reader, &mut writer, limit)?,
+            other => return Err(CodecError::UnknownCompression(other)),
+        };
+        if copied > self.max_decoded_len {
+            return Err(CodecError::TooLarge(self.max_decoded_len));
+        }
+        writer.flush()?;
+        Ok(copied)
+    }
+}
+
+impl Default for ArtifactCodec {
+    fn default() -> Self {
+        Self::new(Compression::None)
+    }
+}
+
+impl Codec for ArtifactCodec {
+    fn encode(&self, data: &[u8]) -> Result<Vec<u8>, CodecError> {
+        let mut encoded = Vec::new();
+        self.encode_stream(&mut &data[..], &mut encoded)?;
+        Ok(encoded)
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<Vec<u8>, CodecError> {
+        let mut decoded = Vec::new();
+        self.decode_stream(&mut &data[..], &mut decoded)?;
+        Ok(decoded)
+    }
+}
+
+#[cfg(feature = "zstd")]
+fn zstd_encode<R: Read, W: Write>(
+    reader: &mut R,
+    writer: W,
+    level: i32,
+) -> Result<u64, CodecError> {
+    let mut encoder = zstd::stream::Encoder::new(writer, level)?;
+    let copied = io::copy(reader, &mut encoder)?;
+    encoder.finish()?.flush()?;
+    Ok(copied)
+}
+
+#[cfg(feature = "zstd")]
+fn zstd_decode<R: Read, W: Write>(
+    reader: &mut R,
+    writer: &mut W,
+    limit: u64,
+) -> Result<u64, CodecError> {
+    let decoder = zstd::stream::Decoder::new(reader)?;
+    Ok(io::copy(&mut decoder.take(limit), writer)?)
+}
+
+#[cfg(not(feature = "zstd"))]
+fn zstd_encode<R: Read, W: Write>(_: &mut R, _: W, _: i32) -> Result<u64, CodecError> {
+    Err(CodecError::Unsupported("zstd"))
+}
+
+#[cfg(not(feature = "zstd"))]
+fn zstd_decode<R: Read, W: Write>(_: &mut R, _: &mut W, _: u64) -> Result<u64, CodecError> {
+    Err(CodecError::Unsupported("zstd"))
+}
+
+#[cfg(feature = "lz4")]
+fn lz4_encode<R: Read, W: Write>(reader: &mut R, writer: W) -> Result<u64, CodecError> {
+    let mut encoder = lz4_flex::frame::FrameEncoder::new(writer);
+    let copied = io::copy(reader, &mut encoder)?;
+    encoder
+        .finish()
+        .map_err(|err| CodecError::Io(err.to_string()))?
+        .flush()?;
+    Ok(copied)
+}
+
+#[cfg(feature = "lz4")]
+fn lz4_decode<R: Read, W: Write>(
+    reader: &mut R,
+    writer: &mut W,
+    limit: u64,
+) -> Result<u64, CodecError> {
+    let decoder = lz4_flex::frame::FrameDecoder::new(reader);
+    Ok(io::copy(&mut decoder.take(limit), writer)?)
+}
+
+#[cfg(not(feature = "lz4"))]
+fn lz4_encode<R: Read, W: Write>(_: &mut R, _: W) -> Result<u64, CodecError> {
+    Err(CodecError::Unsupported("lz4"))
+}
+
+#[cfg(not(feature = "lz4"))]
+fn lz4_decode<R: Read, W: Write>(_: &mut R, _: &mut W, _: u64) -> Result<u64, CodecError> {
+    Err(CodecError::Unsupported("lz4"))
+}
+
+// Define the CodecError enum for artifacts that can't be written or read back
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CodecError {
+    Io(String),
+    UnknownFormat,
+    UnknownCompression(u8),
+    Unsupported(&'static str),
+    TooLarge(u64),
+}
+
+impl fmt::Display for CodecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CodecError::Io(reason) => write!(f, "artifact I/O failed: {}", reason),
+            CodecError::UnknownFormat => write!(f, "not an encoded artifact"),
+            CodecError::UnknownCompression(tag) => {
+                write!(f, "unknown compression tag {:#04x}", tag)
+            }
+            CodecError::Unsupported(name) => {
+                write!(f, "{} compression is not enabled in this build", name)
+            }
+            CodecError::TooLarge(max) => {
+                write!(f, "decoded artifact exceeds the limit of {} bytes", max)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CodecError {}
+
+impl From<io::Error> for CodecError {
+    fn from(err: io::Error) -> Self {
+        CodecError::Io(err.to_string())
+    }
+}
@@ -0,0 +1,139 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use generic_array::GenericArray;
+use serde::{Deserialize, Serialize};
+
+use crate::builder::derive_salt;
+use crate::circuit::{CircuitBackend, CircuitError};
+use crate::snapshot::Snapshot;
+use crate::{LeafCommitment, SumCommitment};
+
+// Define the ColumnLayout struct, how a snapshot is laid out as a trace. FRI provers need
+// a power-of-two row count, so by default the leaves are followed by inactive rows up to
+// the next one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnLayout {
+    pub backend: CircuitBackend,
+    // Seed the tree's salts were derived from; without it no salt columns are written
+    pub salt_seed: Option<[u8; 32]>,
+    pub pad_to_power_of_two: bool,
+}
+
+impl ColumnLayout {
+    pub fn new(backend: CircuitBackend) -> Self {
+        ColumnLayout {
+            backend,
+            salt_seed: None,
+            pad_to_power_of_two: true,
+        }
+    }
+}
+
+// Define the LeafColumns struct, a snapshot's leaves as field-element columns, one row per
+// leaf in tree order. Columns are `active` (1 for leaves, 0 for padding), `position`, the
+// digest limbs, the salt limbs, the amount limbs and the running sum up to and including
+// the row, which padding rows carry on unchanged so the last row holds the root's sum.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LeafColumns {
+    pub backend: CircuitBackend,
+    pub epoch: u64,
+    pub rows: usize,
+    pub names: Vec<String>,
+    pub columns: Vec<Vec<String>>,
+}
+
+impl LeafColumns {
+    pub fn from_snapshot<C: SumCommitment>(
+        snapshot: &Snapshot<C>,
+        layout: &ColumnLayout,
+    ) -> Result<Self, CircuitError> {
+        let backend = layout.backend;
+        let hash_backend = C::HASH_BACKEND;
+        let rows = match layout.pad_to_power_of_two {
+            true => snapshot.len().next_power_of_two(),
+            false => snapshot.len(),
+        };
+
+        // Limb counts depend only on the field and hash, so measure them on zeros
+        let digest_width = backend.digest(hash_backend, &GenericArray::default())?.len();
+        let amount_width = backend.amount(0).len();
+        let salt_width = layout.salt_seed.map_or(0, |_| backend.salt(&[0u8; 32]).len());
+
+        let mut names = vec!["active".to_string(), "position".to_string()];
+        for (prefix, width) in [
+            ("digest", digest_width),
+            ("salt", salt_width),
+            ("amount", amount_width),
+            ("running_sum", amount_width),
+        ] {
+            names.extend((0..width).map(|limb| format!("{}_{}", prefix, limb)));
+        }
+        let mut columns = vec![Vec::with_capacity(rows); names.len()];
+
+        let mut sum = 0u64;
+        for row in 0..rows {
+            let mut values = Vec::with_capacity(names.len());
+            match snapshot.leaves().get(row) {
+                Some(leaf) => {
+                    let amount = leaf.commitment.amount();
+                    sum = sum.checked_add(amount).ok_or(CircuitError::SumOverflow)?;
+                    values.push("1".to_string());
+                    values.push(row.to_string());
+                    values.extend(backend.digest(hash_backend, &leaf.commitment.digest())?);
+                    if let Some(seed) = &layout.salt_seed {
+                        values.extend(backend.salt(&derive_salt(seed, row)));
+                    }
+                    values.extend(backend.amount(amount));
+                }
+                None => {
+                    let zeros = 2 + digest_width + salt_width + amount_width;
+                    values.extend((0..zeros).map(|_| "0".to_string()));
+                }
+            }
+            values.extend(backend.amount(sum));
+            for (column, value) in columns.iter_mut().zip(values) {
+                column.push(value);
+            }
+        }
+
+        Ok(LeafColumns {
+            backend,
+            epoch: snapshot.epoch,
+            rows,
+            names,
+            columns,
+        })
+    }
+
+    pub fn column(&self, name: &str) -> Option<&[String]> {
+        let index = self.names.iter().position(|column| column == name)?;
+        Some(&self.columns[index])
+    }
+
+    // Row-major, with the column names as header
+    pub fn to_csv(&self) -> String {
+        let mut csv = self.names.join(",");
+        csv.push('\n');
+        for row in 0..self.rows {
+            for (index, column) in self.columns.iter().enumerate() {
+                let separator = if index == 0 { "" } else { "," };
+                write!(csv, "{}{}", separator, column[row]).expect("writing to a String");
+            }
+            csv.push('\n');
+        }
+        csv
+    }
+
+    pub fn write_csv(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_csv())
+    }
+
+    // Column-major JSON, the shape trace loaders take directly
+    pub fn write_json(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let json = serde_json::to_vec(self).map_err(io::Error::other)?;
+        fs::write(path, json)
+    }
+}
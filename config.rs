@@ -0,0 +1,196 @@
+use std::fmt;
+
+// Define the HashBackend enum, naming the hash function a commitment type is built on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashBackend {
+    Sha256,
+    // Rescue-Prime Rp64_256 over the 64-bit Goldilocks field, for STARK provers
+    RescuePrime,
+}
+
+// Define the AmountBinding enum for whether internal node digests commit to the child sums
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AmountBinding {
+    // Child amounts are hashed with the child digests, so a sum can't be altered without
+    // changing the root digest
+    Bound,
+    // Only the child digests are hashed, for protocols whose nodes carry sums beside the hash
+    Unbound,
+}
+
+// Define the PaddingPolicy enum for filling up the leaf layer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaddingPolicy {
+    None,
+    // Pad with zero-amount leaves up to the next power of two
+    PowerOfTwo,
+}
+
+// Define the PaddingLeaf enum for how padding leaves are committed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PaddingLeaf {
+    // A zero-amount leaf committed like any other (salted when salts are on)
+    Zero,
+    // Zero amount with a digest derived from the seed and position, so padding can't be
+    // told apart from real leaves or claimed by anyone who doesn't hold the seed
+    Blinded([u8; 32]),
+}
+
+// Define the SaltDerivation enum for per-leaf salts mixed into leaf digests
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SaltDerivation {
+    None,
+    FromSeed([u8; 32]),
+}
+
+// Define the Shuffling enum for permuting leaves before they are committed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Shuffling {
+    None,
+    Seeded([u8; 32]),
+}
+
+// Define the DuplicatePolicy enum for inputs that share a user ID
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    // Fail the build, since duplicates inflate liabilities or break exclusivity
+    Reject,
+    // Commit one leaf per user ID holding the summed balance
+    MergeBalances,
+    // Keep every leaf, for users that intentionally own several (multi-asset, split
+    // balances, categories); `prove_user` proves them together
+    Allow,
+}
+
+// Define the StorageBackend enum for where tree nodes are kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageBackend {
+    // Only the leaves are kept; interior nodes are recomputed for every root and proof
+    Memory,
+    // Every complete level is computed once at build time into one contiguous Vec
+    Arena,
+}
+
+// Define the TreeConfig struct, collecting every option a tree is built with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeConfig {
+    pub hash_backend: HashBackend,
+    pub arity: usize,
+    pub amount_binding: AmountBinding,
+    pub padding: PaddingPolicy,
+    pub padding_leaf: PaddingLeaf,
+    pub salt_derivation: SaltDerivation,
+    pub shuffling: Shuffling,
+    pub duplicates: DuplicatePolicy,
+    pub storage: StorageBackend,
+}
+
+impl Default for TreeConfig {
+    fn default() -> Self {
+        TreeConfig {
+            hash_backend: HashBackend::Sha256,
+            arity: 2,
+            amount_binding: AmountBinding::Bound,
+            padding: PaddingPolicy::None,
+            padding_leaf: PaddingLeaf::Zero,
+            salt_derivation: SaltDerivation::None,
+            shuffling: Shuffling::None,
+            duplicates: DuplicatePolicy::Reject,
+            storage: StorageBackend::Arena,
+        }
+    }
+}
+
+impl TreeConfig {
+    pub fn validate(
+        &self,
+        commitment_backend: HashBackend,
+        commitment_binding: AmountBinding,
+    ) -> Result<(), BuildError> {
+        if self.arity != 2 {
+            return Err(BuildError::UnsupportedArity(self.arity));
+        }
+        if self.hash_backend != commitment_backend {
+            return Err(BuildError::HashBackendMismatch {
+                configured: self.hash_backend,
+                commitment: commitment_backend,
+            });
+        }
+        if self.amount_binding != commitment_binding {
+            return Err(BuildError::AmountBindingMismatch {
+                configured: self.amount_binding,
+                commitment: commitment_binding,
+            });
+        }
+        Ok(())
+    }
+}
+
+// Define the BuildError enum for configurations and inputs a tree can't be built from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BuildError {
+    EmptyTree,
+    UnsupportedArity(usize),
+    HashBackendMismatch {
+        configured: HashBackend,
+        commitment: HashBackend,
+    },
+    AmountBindingMismatch {
+        configured: AmountBinding,
+        commitment: AmountBinding,
+    },
+    // Input indices of the first and the repeated occurrence
+    DuplicateUserId {
+        first: usize,
+        second: usize,
+    },
+    // A merged account's balance or the tree's total doesn't fit in a u64
+    BalanceOverflow,
+    // Input indices of two records merged under one user ID that hold different assets
+    MixedAssets {
+        first: usize,
+        second: usize,
+    },
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::EmptyTree => write!(f, "cannot build a tree without leaves"),
+            BuildError::UnsupportedArity(arity) => {
+                write!(
+                    f,
+                    "unsupported arity {}, only binary trees are supported",
+                    arity
+                )
+            }
+            BuildError::HashBackendMismatch {
+                configured,
+                commitment,
+            } => write!(
+                f,
+                "configured hash backend {:?} does not match the commitment type's {:?}",
+                configured, commitment
+            ),
+            BuildError::AmountBindingMismatch {
+                configured,
+                commitment,
+            } => write!(
+                f,
+                "configured amount binding {:?} does not match the commitment type's {:?}",
+                configured, commitment
+            ),
+            BuildError::DuplicateUserId { first, second } => {
+                write!(f, "inputs {} and {} have the same user ID", first, second)
+            }
+            BuildError::BalanceOverflow => write!(f, "balances overflow a u64"),
+            BuildError::MixedAssets { first, second } => write!(
+                f,
+                "inputs {} and {} share a user ID but hold different assets",
+                first, second
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
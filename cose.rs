@@ -0,0 +1,218 @@
+use std::fmt;
+
+use coset::cbor::value::Value;
+use coset::{
+    iana, CborSerializable, CoseSign1, CoseSign1Builder, HeaderBuilder, RegisteredLabelWithPrivate,
+    TaggedCborSerializable,
+};
+use ed25519_dalek::{Signature, VerifyingKey};
+
+use crate::bundle::{BundleError, ProofBundle, SignedRoot, VerificationPolicy};
+use crate::signer::{Signer, SignerError};
+use crate::{ExclusiveAllotmentProof, MerkleProof, ParseError, Root, SumCommitment};
+
+pub const ROOT_CONTENT_TYPE: &str = "application/mimi-root+cbor";
+pub const PROOF_CONTENT_TYPE: &str = "application/mimi-proof+cbor";
+
+// Payload map keys. Small integers keep the map a few bytes long, as CWT claims do.
+const KEY_EPOCH: i64 = 1;
+const KEY_ROOT: i64 = 2;
+const KEY_SIGNATURE: i64 = 3;
+const KEY_PROOF: i64 = 4;
+
+// Tagged COSE_Sign1 of a signed root. The payload is a CBOR map of epoch (uint), root
+// (40-byte encoded commitment) and the operator's root signature (64 bytes).
+pub fn root_to_cose<C: SumCommitment>(
+    signed_root: &SignedRoot<C>,
+    signer: &dyn Signer,
+) -> Result<Vec<u8>, CoseError> {
+    sign(ROOT_CONTENT_TYPE, root_entries(signed_root), signer)
+}
+
+// Tagged COSE_Sign1 of a proof bundle: the root payload plus the proof as
+// `MerkleProof::to_bytes`. Timestamp tokens aren't carried.
+pub fn bundle_to_cose<C, P>(
+    bundle: &ProofBundle<C, P>,
+    signer: &dyn Signer,
+) -> Result<Vec<u8>, CoseError>
+where
+    C: SumCommitment,
+    P: ExclusiveAllotmentProof<C>,
+{
+    let proof = MerkleProof::<C>::new(
+        bundle.proof.position(),
+        bundle.proof.leaf().clone(),
+        bundle.proof.siblings().to_vec(),
+    );
+    let mut entries = root_entries(&bundle.signed_root);
+    entries.push((Value::from(KEY_PROOF), Value::Bytes(proof.to_bytes())));
+    sign(PROOF_CONTENT_TYPE, entries, signer)
+}
+
+// Checks the COSE signature and the root signature inside it
+pub fn root_from_cose<C: SumCommitment>(
+    bytes: &[u8],
+    operator_key: &VerifyingKey,
+) -> Result<SignedRoot<C>, CoseError> {
+    let entries = open(bytes, ROOT_CONTENT_TYPE, operator_key)?;
+    let signed_root = read_root(&entries)?;
+    signed_root
+        .verify(operator_key)
+        .map_err(CoseError::Bundle)?;
+    Ok(signed_root)
+}
+
+// Checks the COSE signature, then the bundle as `ProofBundle::verify` would without a TSA
+pub fn bundle_from_cose<C: SumCommitment>(
+    bytes: &[u8],
+    operator_key: &VerifyingKey,
+) -> Result<ProofBundle<C, MerkleProof<C>>, CoseError> {
+    let entries = open(bytes, PROOF_CONTENT_TYPE, operator_key)?;
+    let proof = MerkleProof::<C>::try_from(bytes_entry(&entries, KEY_PROOF)?)?;
+    let bundle = ProofBundle::new(proof, read_root(&entries)?);
+    bundle
+        .verify(&VerificationPolicy::new(*operator_key))
+        .map_err(CoseError::Bundle)?;
+    Ok(bundle)
+}
+
+fn root_entries<C: SumCommitment>(signed_root: &SignedRoot<C>) -> Vec<(Value, Value)> {
+    vec![
+        (Value::from(KEY_EPOCH), Value::from(signed_root.epoch)),
+        (Value::from(KEY_ROOT), Value::Bytes(signed_root.root.to_bytes().to_vec())),
+        (
+            Value::from(KEY_SIGNATURE),
+            Value::Bytes(signed_root.signature.to_bytes().to_vec()),
+        ),
+    ]
+}
+
+fn read_root<C: SumCommitment>(entries: &[(Value, Value)]) -> Result<SignedRoot<C>, CoseError> {
+    let epoch = entry(entries, KEY_EPOCH)?
+        .as_integer()
+        .and_then(|epoch| u64::try_from(epoch).ok())
+        .ok_or(CoseError::MalformedPayload)?;
+    let root = Root::try_from(bytes_entry(entries, KEY_ROOT)?)?;
+    let signature: [u8; 64] = bytes_entry(entries, KEY_SIGNATURE)?
+        .try_into()
+        .map_err(|_| CoseError::MalformedPayload)?;
+    Ok(SignedRoot {
+        epoch,
+        root,
+        signature: Signature::from_bytes(&signature),
+    })
+}
+
+fn entry(entries: &[(Value, Value)], key: i64) -> Result<&Value, CoseError> {
+    entries
+        .iter()
+        .find(|(label, _)| label.as_integer() == Some(key.into()))
+        .map(|(_, value)| value)
+        .ok_or(CoseError::MalformedPayload)
+}
+
+fn bytes_entry(entries: &[(Value, Value)], key: i64) -> Result<&[u8], CoseError> {
+    entry(entries, key)?
+        .as_bytes()
+        .map(Vec::as_slice)
+        .ok_or(CoseError::MalformedPayload)
+}
+
+fn sign(
+    content_type: &str,
+    entries: Vec<(Value, Value)>,
+    signer: &dyn Signer,
+) -> Result<Vec<u8>, CoseError> {
+    let mut payload = Vec::new();
+    coset::cbor::ser::into_writer(&Value::Map(entries), &mut payload)
+        .map_err(|err| CoseError::Cbor(err.to_string()))?;
+    let protected = HeaderBuilder::new()
+        .algorithm(iana::Algorithm::EdDSA)
+        .key_id(signer.verifying_key()?.to_bytes().to_vec())
+        .content_type(content_type.to_string())
+        .build();
+    CoseSign1Builder::new()
+        .protected(protected)
+        .payload(payload)
+        .try_create_signature(&[], |to_be_signed| {
+            signer
+                .sign(to_be_signed)
+                .map(|signature| signature.to_bytes().to_vec())
+        })?
+        .build()
+        .to_tagged_vec()
+        .map_err(|err| CoseError::Cbor(err.to_string()))
+}
+
+// Untagged COSE_Sign1 is accepted too, since some encoders drop the tag
+fn open(
+    bytes: &[u8],
+    content_type: &str,
+    operator_key: &VerifyingKey,
+) -> Result<Vec<(Value, Value)>, CoseError> {
+    let sign1 = CoseSign1::from_tagged_slice(bytes)
+        .or_else(|_| CoseSign1::from_slice(bytes))
+        .map_err(|err| CoseError::Cbor(err.to_string()))?;
+    let header = &sign1.protected.header;
+    if header.alg != Some(RegisteredLabelWithPrivate::Assigned(iana::Algorithm::EdDSA)) {
+        return Err(CoseError::UnsupportedAlgorithm);
+    }
+    if header.content_type != Some(coset::ContentType::Text(content_type.to_string())) {
+        return Err(CoseError::WrongContentType);
+    }
+    sign1
+        .verify_signature(&[], |signature, to_be_signed| {
+            let signature: [u8; 64] = signature.try_into().map_err(|_| CoseError::BadSignature)?;
+            operator_key
+                .verify_strict(to_be_signed, &Signature::from_bytes(&signature))
+                .map_err(|_| CoseError::BadSignature)
+        })?;
+
+    let payload = sign1.payload.as_deref().ok_or(CoseError::MalformedPayload)?;
+    match coset::cbor::de::from_reader(payload) {
+        Ok(Value::Map(entries)) => Ok(entries),
+        _ => Err(CoseError::MalformedPayload),
+    }
+}
+
+// Define the CoseError enum for COSE envelopes that can't be produced, parsed or trusted
+#[derive(Debug)]
+pub enum CoseError {
+    Cbor(String),
+    UnsupportedAlgorithm,
+    WrongContentType,
+    BadSignature,
+    MalformedPayload,
+    Parse(ParseError),
+    Signer(SignerError),
+    Bundle(BundleError),
+}
+
+impl fmt::Display for CoseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CoseError::Cbor(reason) => write!(f, "cbor error: {}", reason),
+            CoseError::UnsupportedAlgorithm => write!(f, "COSE algorithm is not EdDSA"),
+            CoseError::WrongContentType => write!(f, "unexpected COSE content type"),
+            CoseError::BadSignature => write!(f, "COSE signature does not verify"),
+            CoseError::MalformedPayload => write!(f, "malformed COSE payload"),
+            CoseError::Parse(err) => write!(f, "malformed COSE payload: {}", err),
+            CoseError::Signer(err) => write!(f, "{}", err),
+            CoseError::Bundle(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for CoseError {}
+
+impl From<ParseError> for CoseError {
+    fn from(err: ParseError) -> Self {
+        CoseError::Parse(err)
+    }
+}
+
+impl From<SignerError> for CoseError {
+    fn from(err: SignerError) -> Self {
+        CoseError::Signer(err)
+    }
+}
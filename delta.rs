@@ -0,0 +1,196 @@
+use std::fmt;
+
+use crate::encoding::{
+    decode_commitment, encode_commitment, ByteReader, COMMITMENT_LEN, MAX_PROOF_DEPTH,
+};
+use crate::{ExclusiveAllotmentProof, LeafCommitment, MimkMerkleTree, ParseError, SumCommitment};
+
+// Define the ProofDelta struct, what changed on one user's path between two epochs. A
+// client that re-verifies every epoch applies it to the proof it cached for the previous
+// one; when the tree's shape changed (a different leaf count or position) no delta exists
+// and the full proof has to be fetched again.
+#[derive(Debug, Clone)]
+pub struct ProofDelta<C: SumCommitment> {
+    position: usize,
+    // Root the cached proof reconstructs, so a delta is never applied to a proof from
+    // another epoch
+    base_root: C,
+    // Present when the user's own leaf changed
+    leaf: Option<C::Leaf>,
+    // Replaced siblings as (level from the leaf, new sibling)
+    siblings: Vec<(u8, C)>,
+}
+
+impl<C: SumCommitment> ProofDelta<C> {
+    // None when the two proofs don't follow the same path
+    pub fn between<P: ExclusiveAllotmentProof<C>>(previous: &P, current: &P) -> Option<Self> {
+        if previous.position() != current.position()
+            || previous.siblings().len() != current.siblings().len()
+        {
+            return None;
+        }
+        let mut siblings = Vec::new();
+        for (level, (old, new)) in previous.siblings().iter().zip(current.siblings()).enumerate() {
+            if old.1 != new.1 {
+                return None;
+            }
+            if !same_node(&old.0, &new.0) {
+                siblings.push((level as u8, new.0.clone()));
+            }
+        }
+        let (old_leaf, new_leaf) = (previous.leaf(), current.leaf());
+        let leaf_changed =
+            old_leaf.amount() != new_leaf.amount() || old_leaf.digest() != new_leaf.digest();
+        Some(ProofDelta {
+            position: current.position(),
+            base_root: previous.reconstruct_commitment(),
+            leaf: leaf_changed.then(|| new_leaf.clone()),
+            siblings,
+        })
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn changed_siblings(&self) -> usize {
+        self.siblings.len()
+    }
+
+    pub fn leaf_changed(&self) -> bool {
+        self.leaf.is_some()
+    }
+
+    // The new epoch's proof; verify it against that epoch's root as usual
+    pub fn apply<P: ExclusiveAllotmentProof<C>>(&self, cached: &P) -> Result<P, DeltaError> {
+        if cached.position() != self.position {
+            return Err(DeltaError::PositionMismatch {
+                cached: cached.position(),
+                delta: self.position,
+            });
+        }
+        if !same_node(&cached.reconstruct_commitment(), &self.base_root) {
+            return Err(DeltaError::BaseMismatch);
+        }
+        let mut siblings = cached.siblings().to_vec();
+        for (level, sibling) in &self.siblings {
+            let slot = siblings
+                .get_mut(*level as usize)
+                .ok_or(DeltaError::UnknownLevel(*level))?;
+            slot.0 = sibling.clone();
+        }
+        let leaf = self.leaf.clone().unwrap_or_else(|| cached.leaf().clone());
+        Ok(P::new(self.position, leaf, siblings))
+    }
+
+    // position (u64) | base root | leaf flag (u8) | [leaf] | change count (u8) | per
+    // change the level (u8) followed by the sibling commitment
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(
+            8 + 2 * COMMITMENT_LEN + 2 + self.siblings.len() * (COMMITMENT_LEN + 1),
+        );
+        bytes.extend_from_slice(&(self.position as u64).to_le_bytes());
+        bytes.extend_from_slice(&encode_commitment(
+            self.base_root.amount(),
+            &self.base_root.digest(),
+        ));
+        match &self.leaf {
+            None => bytes.push(0),
+            Some(leaf) => {
+                bytes.push(1);
+                bytes.extend_from_slice(&encode_commitment(leaf.amount(), &leaf.digest()));
+            }
+        }
+        bytes.push(self.siblings.len() as u8);
+        for (level, sibling) in &self.siblings {
+            bytes.push(*level);
+            bytes.extend_from_slice(&encode_commitment(sibling.amount(), &sibling.digest()));
+        }
+        bytes
+    }
+}
+
+impl<C: SumCommitment> TryFrom<&[u8]> for ProofDelta<C> {
+    type Error = ParseError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        let mut reader = ByteReader::new(bytes);
+        let position = u64::from_le_bytes(reader.take_array::<8>()?);
+        let position = usize::try_from(position).map_err(|_| ParseError::PositionOverflow)?;
+        let (amount, digest) = decode_commitment(reader.take(COMMITMENT_LEN)?)?;
+        let base_root = C::from_parts(amount, digest);
+        let leaf = match reader.take_array::<1>()?[0] {
+            0 => None,
+            1 => {
+                let (amount, digest) = decode_commitment(reader.take(COMMITMENT_LEN)?)?;
+                Some(C::Leaf::from_parts(amount, digest))
+            }
+            _ => return Err(ParseError::InvalidShape),
+        };
+
+        let count = reader.take_array::<1>()?[0] as usize;
+        if count > MAX_PROOF_DEPTH {
+            return Err(ParseError::TooDeep(count));
+        }
+        let mut siblings = Vec::with_capacity(count);
+        for _ in 0..count {
+            let level = reader.take_array::<1>()?[0];
+            let (amount, digest) = decode_commitment(reader.take(COMMITMENT_LEN)?)?;
+            siblings.push((level, C::from_parts(amount, digest)));
+        }
+        reader.finish()?;
+
+        Ok(ProofDelta {
+            position,
+            base_root,
+            leaf,
+            siblings,
+        })
+    }
+}
+
+impl<C, P> MimkMerkleTree<C, P>
+where
+    C: SumCommitment,
+    P: ExclusiveAllotmentProof<C>,
+{
+    // The delta taking `previous`, a proof from an earlier epoch, to this tree's proof for
+    // the same position
+    pub fn prove_delta(&self, previous: &P) -> Option<ProofDelta<C>> {
+        if previous.position() >= self.len() {
+            return None;
+        }
+        ProofDelta::between(previous, &self.prove(previous.position()))
+    }
+}
+
+fn same_node<C: SumCommitment>(left: &C, right: &C) -> bool {
+    left.amount() == right.amount() && left.digest() == right.digest()
+}
+
+// Define the DeltaError enum for deltas that don't fit the cached proof
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeltaError {
+    PositionMismatch { cached: usize, delta: usize },
+    // The cached proof isn't the one the delta was computed from
+    BaseMismatch,
+    UnknownLevel(u8),
+}
+
+impl fmt::Display for DeltaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeltaError::PositionMismatch { cached, delta } => write!(
+                f,
+                "cached proof is for position {}, delta for position {}",
+                cached, delta
+            ),
+            DeltaError::BaseMismatch => write!(f, "delta was computed from a different proof"),
+            DeltaError::UnknownLevel(level) => {
+                write!(f, "delta replaces a sibling at level {} the proof lacks", level)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DeltaError {}
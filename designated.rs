@@ -0,0 +1,345 @@
+use std::fmt;
+
+use k256::elliptic_curve::ops::Reduce;
+use k256::{ProjectivePoint, Scalar, U256};
+use sha2::{Digest as _, Sha256};
+
+use crate::bundle::{signing_payload, BundleError, ProofBundle, VerificationPolicy};
+use crate::encoding::{encode_len, ByteReader};
+use crate::entropy::EntropySource;
+use crate::pedersen::{decode_point, decode_scalar, encode_point, random_scalar, POINT_LEN};
+use crate::{MerkleProof, ParseError, SumCommitment};
+
+pub const DESIGNATED_SIGNATURE_LEN: usize = 4 * 32;
+
+const DESIGNATED_DOMAIN: &[u8] = b"mimi-designated-bundle-v1";
+
+// Define the DesignatedSecretKey struct, a secp256k1 secret held by the operator or a user
+#[derive(Clone)]
+pub struct DesignatedSecretKey(Scalar);
+
+impl DesignatedSecretKey {
+    pub fn generate(entropy: &mut impl EntropySource) -> Self {
+        DesignatedSecretKey(random_scalar(entropy))
+    }
+
+    pub fn public_key(&self) -> DesignatedPublicKey {
+        DesignatedPublicKey(ProjectivePoint::GENERATOR * self.0)
+    }
+
+    pub fn to_bytes(&self) -> [u8; 32] {
+        self.0.to_bytes().into()
+    }
+}
+
+// Keeps the secret out of logs
+impl fmt::Debug for DesignatedSecretKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "DesignatedSecretKey({})",
+            hex::encode(self.public_key().to_bytes())
+        )
+    }
+}
+
+impl TryFrom<&[u8]> for DesignatedSecretKey {
+    type Error = ParseError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        decode_scalar(bytes).map(DesignatedSecretKey)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DesignatedPublicKey(ProjectivePoint);
+
+impl DesignatedPublicKey {
+    pub fn to_bytes(&self) -> [u8; POINT_LEN] {
+        encode_point(&self.0)
+    }
+}
+
+impl TryFrom<&[u8]> for DesignatedPublicKey {
+    type Error = ParseError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        decode_point(bytes).map(DesignatedPublicKey)
+    }
+}
+
+// Define the DesignatedSignature struct, a Schnorr OR-proof that the signer knows either the
+// operator's or the user's secret key. The user could have produced it themselves with
+// `forge`, so it convinces them and nobody they show it to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DesignatedSignature {
+    operator_challenge: Scalar,
+    user_challenge: Scalar,
+    operator_response: Scalar,
+    user_response: Scalar,
+}
+
+impl DesignatedSignature {
+    // Real branch for the operator, simulated branch for the user
+    pub fn sign(
+        message: &[u8],
+        operator: &DesignatedSecretKey,
+        user: &DesignatedPublicKey,
+        entropy: &mut impl EntropySource,
+    ) -> Self {
+        let operator_key = operator.public_key();
+        let user_challenge = random_scalar(entropy);
+        let user_response = random_scalar(entropy);
+        let user_nonce = ProjectivePoint::GENERATOR * user_response - user.0 * user_challenge;
+        let nonce = random_scalar(entropy);
+        let operator_nonce = ProjectivePoint::GENERATOR * nonce;
+        let challenge = or_challenge(message, &operator_key, user, &operator_nonce, &user_nonce);
+        let operator_challenge = challenge - user_challenge;
+        DesignatedSignature {
+            operator_challenge,
+            user_challenge,
+            operator_response: nonce + operator_challenge * operator.0,
+            user_response,
+        }
+    }
+
+    // What the user can produce on their own for any message, which is why a designated
+    // signature proves nothing to a third party
+    pub fn forge(
+        message: &[u8],
+        operator: &DesignatedPublicKey,
+        user: &DesignatedSecretKey,
+        entropy: &mut impl EntropySource,
+    ) -> Self {
+        let user_key = user.public_key();
+        let operator_challenge = random_scalar(entropy);
+        let operator_response = random_scalar(entropy);
+        let operator_nonce =
+            ProjectivePoint::GENERATOR * operator_response - operator.0 * operator_challenge;
+        let nonce = random_scalar(entropy);
+        let user_nonce = ProjectivePoint::GENERATOR * nonce;
+        let challenge = or_challenge(message, operator, &user_key, &operator_nonce, &user_nonce);
+        let user_challenge = challenge - operator_challenge;
+        DesignatedSignature {
+            operator_challenge,
+            user_challenge,
+            operator_response,
+            user_response: nonce + user_challenge * user.0,
+        }
+    }
+
+    // Recomputes both nonces and checks the challenges add up to the message hash
+    pub fn verify(
+        &self,
+        message: &[u8],
+        operator: &DesignatedPublicKey,
+        user: &DesignatedPublicKey,
+    ) -> bool {
+        let operator_nonce = ProjectivePoint::GENERATOR * self.operator_response
+            - operator.0 * self.operator_challenge;
+        let user_nonce =
+            ProjectivePoint::GENERATOR * self.user_response - user.0 * self.user_challenge;
+        or_challenge(message, operator, user, &operator_nonce, &user_nonce)
+            == self.operator_challenge + self.user_challenge
+    }
+
+    // c_operator | c_user | s_operator | s_user
+    pub fn to_bytes(&self) -> [u8; DESIGNATED_SIGNATURE_LEN] {
+        let mut bytes = [0u8; DESIGNATED_SIGNATURE_LEN];
+        let scalars = [
+            &self.operator_challenge,
+            &self.user_challenge,
+            &self.operator_response,
+            &self.user_response,
+        ];
+        for (chunk, scalar) in bytes.chunks_exact_mut(32).zip(scalars) {
+            chunk.copy_from_slice(&scalar.to_bytes());
+        }
+        bytes
+    }
+}
+
+impl TryFrom<&[u8]> for DesignatedSignature {
+    type Error = ParseError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        let mut reader = ByteReader::new(bytes);
+        let operator_challenge = decode_scalar(reader.take(32)?)?;
+        let user_challenge = decode_scalar(reader.take(32)?)?;
+        let operator_response = decode_scalar(reader.take(32)?)?;
+        let user_response = decode_scalar(reader.take(32)?)?;
+        reader.finish()?;
+        Ok(DesignatedSignature {
+            operator_challenge,
+            user_challenge,
+            operator_response,
+            user_response,
+        })
+    }
+}
+
+// SHA-256(domain | operator key | user key | R_operator | R_user | len | message) reduced
+// into the scalar field
+fn or_challenge(
+    message: &[u8],
+    operator: &DesignatedPublicKey,
+    user: &DesignatedPublicKey,
+    operator_nonce: &ProjectivePoint,
+    user_nonce: &ProjectivePoint,
+) -> Scalar {
+    let mut hasher = Sha256::new();
+    hasher.update(DESIGNATED_DOMAIN);
+    hasher.update(operator.to_bytes());
+    hasher.update(user.to_bytes());
+    hasher.update(encode_point(operator_nonce));
+    hasher.update(encode_point(user_nonce));
+    hasher.update(encode_len(message));
+    hasher.update(message);
+    <Scalar as Reduce<U256>>::reduce_bytes(&hasher.finalize())
+}
+
+// Define the DesignatedBundle struct, a proof bundle whose claim that the proven leaf belongs
+// to `user_id` is only convincing to that user. The root signature stays public, so anyone
+// can still see that some leaf with this amount is in the tree, just not whose it is.
+#[derive(Debug, Clone)]
+pub struct DesignatedBundle<C: SumCommitment> {
+    pub user_id: Vec<u8>,
+    pub bundle: ProofBundle<C, MerkleProof<C>>,
+    pub signature: DesignatedSignature,
+}
+
+impl<C: SumCommitment> DesignatedBundle<C> {
+    pub fn issue(
+        user_id: impl Into<Vec<u8>>,
+        bundle: ProofBundle<C, MerkleProof<C>>,
+        operator: &DesignatedSecretKey,
+        user: &DesignatedPublicKey,
+        entropy: &mut impl EntropySource,
+    ) -> Self {
+        let user_id = user_id.into();
+        let signature =
+            DesignatedSignature::sign(&designated_payload(&user_id, &bundle), operator, user, entropy);
+        DesignatedBundle {
+            user_id,
+            bundle,
+            signature,
+        }
+    }
+
+    // Checks the bundle as usual, then the designated signature. Only meaningful to the
+    // holder of `user`'s secret key, who knows they didn't forge it.
+    pub fn verify(
+        &self,
+        policy: &VerificationPolicy<'_>,
+        operator: &DesignatedPublicKey,
+        user: &DesignatedPublicKey,
+    ) -> Result<(), DesignatedError> {
+        self.bundle.verify(policy)?;
+        let payload = designated_payload(&self.user_id, &self.bundle);
+        if !self.signature.verify(&payload, operator, user) {
+            return Err(DesignatedError::BadSignature);
+        }
+        Ok(())
+    }
+}
+
+// len | user ID | len | signed-root payload | SHA-256(proof bytes)
+fn designated_payload<C: SumCommitment>(
+    user_id: &[u8],
+    bundle: &ProofBundle<C, MerkleProof<C>>,
+) -> Vec<u8> {
+    let root_payload = signing_payload(bundle.signed_root.epoch, &bundle.signed_root.root);
+    let mut payload = encode_len(user_id).to_vec();
+    payload.extend_from_slice(user_id);
+    payload.extend_from_slice(&encode_len(&root_payload));
+    payload.extend_from_slice(&root_payload);
+    payload.extend_from_slice(&Sha256::digest(bundle.proof.to_bytes()));
+    payload
+}
+
+// Define the DesignatedError enum for designated bundles that fail verification
+#[derive(Debug)]
+pub enum DesignatedError {
+    BadSignature,
+    Bundle(BundleError),
+}
+
+impl fmt::Display for DesignatedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DesignatedError::BadSignature => {
+                write!(f, "designated signature does not verify for this user")
+            }
+            DesignatedError::Bundle(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for DesignatedError {}
+
+impl From<BundleError> for DesignatedError {
+    fn from(err: BundleError) -> Self {
+        DesignatedError::Bundle(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ed25519_dalek::SigningKey;
+
+    use super::*;
+    use crate::bundle::SignedRoot;
+    use crate::{MimiSumCommitment, MimkMerkleTree, SeededEntropy};
+
+    #[test]
+    fn users_can_forge_what_the_operator_signs() {
+        let mut entropy = SeededEntropy::new([8; 32]);
+        let operator = DesignatedSecretKey::generate(&mut entropy);
+        let user = DesignatedSecretKey::generate(&mut entropy);
+        let (operator_key, user_key) = (operator.public_key(), user.public_key());
+
+        let signed = DesignatedSignature::sign(b"alice", &operator, &user_key, &mut entropy);
+        let forged = DesignatedSignature::forge(b"alice", &operator_key, &user, &mut entropy);
+        for signature in [&signed, &forged] {
+            assert!(signature.verify(b"alice", &operator_key, &user_key));
+            assert!(!signature.verify(b"bob", &operator_key, &user_key));
+            assert!(!signature.verify(b"alice", &user_key, &operator_key));
+        }
+        let parsed = DesignatedSignature::try_from(&signed.to_bytes()[..]).unwrap();
+        assert_eq!(parsed, signed);
+        assert!(DesignatedSignature::try_from(&signed.to_bytes()[1..]).is_err());
+    }
+
+    #[test]
+    fn bundles_only_verify_for_their_user_and_root() {
+        let mut entropy = SeededEntropy::new([9; 32]);
+        let operator = DesignatedSecretKey::generate(&mut entropy);
+        let user = DesignatedSecretKey::generate(&mut entropy).public_key();
+        let stranger = DesignatedSecretKey::generate(&mut entropy).public_key();
+        let root_key = SigningKey::from_bytes(&[1; 32]);
+        let tree =
+            MimkMerkleTree::<MimiSumCommitment, MerkleProof<_>>::new(vec![100, 200]).unwrap();
+        let bundle = ProofBundle::new(tree.prove(1), SignedRoot::sign(3, tree.commit(), &root_key));
+        let designated = DesignatedBundle::issue("alice", bundle, &operator, &user, &mut entropy);
+
+        let policy = VerificationPolicy::new(root_key.verifying_key());
+        let operator_key = operator.public_key();
+        assert!(designated.verify(&policy, &operator_key, &user).is_ok());
+        assert!(matches!(
+            designated.verify(&policy, &operator_key, &stranger),
+            Err(DesignatedError::BadSignature)
+        ));
+        let mut renamed = designated.clone();
+        renamed.user_id = b"bob".to_vec();
+        assert!(matches!(
+            renamed.verify(&policy, &operator_key, &user),
+            Err(DesignatedError::BadSignature)
+        ));
+        let other_operator =
+            VerificationPolicy::new(SigningKey::from_bytes(&[2; 32]).verifying_key());
+        assert!(matches!(
+            designated.verify(&other_operator, &operator_key, &user),
+            Err(DesignatedError::Bundle(_))
+        ));
+    }
+}
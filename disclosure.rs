@@ -0,0 +1,315 @@
+use std::fmt;
+
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use sha2::{Digest as _, Sha256};
+
+use crate::encoding::{encode_len, encode_usize};
+use crate::pedersen::{Blinding, HidingProof, HidingRoot, HidingTree, Opening};
+use crate::Digest;
+
+const REQUEST_DOMAIN: &[u8] = b"mimi-disclosure-request-v1";
+const RECORD_DOMAIN: &[u8] = b"mimi-disclosure-record-v1";
+
+// Define the DisclosureRequest struct, a regulator's signed request to open some leaves of
+// one epoch's hiding tree
+#[derive(Debug, Clone)]
+pub struct DisclosureRequest {
+    pub regulator: String,
+    pub epoch: u64,
+    pub positions: Vec<usize>,
+    // Free text kept in the disclosure log, e.g. a case or warrant reference
+    pub reason: String,
+    pub signature: Signature,
+}
+
+impl DisclosureRequest {
+    pub fn sign(
+        regulator: impl Into<String>,
+        epoch: u64,
+        positions: Vec<usize>,
+        reason: impl Into<String>,
+        key: &SigningKey,
+    ) -> Self {
+        let regulator = regulator.into();
+        let reason = reason.into();
+        let signature = key.sign(&request_payload(&regulator, epoch, &positions, &reason));
+        DisclosureRequest {
+            regulator,
+            epoch,
+            positions,
+            reason,
+            signature,
+        }
+    }
+
+    pub fn payload(&self) -> Vec<u8> {
+        request_payload(&self.regulator, self.epoch, &self.positions, &self.reason)
+    }
+}
+
+// domain | len | regulator | epoch | position count | positions | len | reason
+fn request_payload(regulator: &str, epoch: u64, positions: &[usize], reason: &str) -> Vec<u8> {
+    let mut payload = REQUEST_DOMAIN.to_vec();
+    payload.extend_from_slice(&encode_len(regulator.as_bytes()));
+    payload.extend_from_slice(regulator.as_bytes());
+    payload.extend_from_slice(&epoch.to_le_bytes());
+    payload.extend_from_slice(&encode_usize(positions.len()));
+    for position in positions {
+        payload.extend_from_slice(&encode_usize(*position));
+    }
+    payload.extend_from_slice(&encode_len(reason.as_bytes()));
+    payload.extend_from_slice(reason.as_bytes());
+    payload
+}
+
+// Define the RegulatorGrant struct, a regulator allowed to request openings and how many
+// leaves one request may open
+#[derive(Debug, Clone)]
+pub struct RegulatorGrant {
+    pub name: String,
+    pub key: VerifyingKey,
+    pub max_leaves: usize,
+}
+
+// Define the LeafOpening struct, one disclosed leaf: its amount and blinding, and the path
+// showing its commitment is in the published hiding root
+#[derive(Debug, Clone)]
+pub struct LeafOpening {
+    pub amount: u64,
+    pub blinding: Blinding,
+    pub proof: HidingProof,
+}
+
+impl LeafOpening {
+    pub fn position(&self) -> usize {
+        self.proof.position()
+    }
+
+    pub fn verify(&self, root: &HidingRoot) -> bool {
+        let opening = Opening {
+            amount: self.amount,
+            blinding: self.blinding.clone(),
+        };
+        opening.commitment() == *self.proof.leaf() && self.proof.verify(root)
+    }
+}
+
+// Define the BatchOpening struct, the leaves disclosed for one request
+#[derive(Debug, Clone)]
+pub struct BatchOpening {
+    pub epoch: u64,
+    pub openings: Vec<LeafOpening>,
+}
+
+impl BatchOpening {
+    // Checks every opening against the published root and that exactly the requested
+    // positions were opened
+    pub fn verify(
+        &self,
+        request: &DisclosureRequest,
+        root: &HidingRoot,
+    ) -> Result<(), DisclosureError> {
+        if self.epoch != request.epoch {
+            return Err(DisclosureError::EpochMismatch {
+                requested: request.epoch,
+                opened: self.epoch,
+            });
+        }
+        let opened: Vec<usize> = self.openings.iter().map(LeafOpening::position).collect();
+        if opened != request.positions {
+            return Err(DisclosureError::PositionsMismatch);
+        }
+        match self.openings.iter().find(|opening| !opening.verify(root)) {
+            Some(opening) => Err(DisclosureError::InvalidOpening(opening.position())),
+            None => Ok(()),
+        }
+    }
+}
+
+// Define the DisclosureRecord struct, one granted request in the disclosure log, chained to
+// the record before it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DisclosureRecord {
+    pub regulator: String,
+    pub epoch: u64,
+    pub positions: Vec<usize>,
+    pub reason: String,
+    // Hash of the previous record, all zeros for the first one
+    pub previous: Digest,
+}
+
+impl DisclosureRecord {
+    // SHA-256(domain | request payload | previous)
+    pub fn hash(&self) -> Digest {
+        let mut hasher = Sha256::new();
+        hasher.update(RECORD_DOMAIN);
+        hasher.update(request_payload(
+            &self.regulator,
+            self.epoch,
+            &self.positions,
+            &self.reason,
+        ));
+        hasher.update(self.previous.as_bytes());
+        Digest::from(hasher.finalize())
+    }
+}
+
+// Define the DisclosureDesk struct, the operator side of regulator disclosures. Every
+// opening it hands out is first appended to its hash-chained log, so what was disclosed,
+// to whom and why can be audited later.
+#[derive(Debug, Clone, Default)]
+pub struct DisclosureDesk {
+    grants: Vec<RegulatorGrant>,
+    log: Vec<DisclosureRecord>,
+}
+
+impl DisclosureDesk {
+    pub fn new(grants: Vec<RegulatorGrant>) -> Self {
+        DisclosureDesk {
+            grants,
+            log: Vec::new(),
+        }
+    }
+
+    // Resumes from a stored log, rejecting any break in the chain
+    pub fn with_log(
+        grants: Vec<RegulatorGrant>,
+        log: Vec<DisclosureRecord>,
+    ) -> Result<Self, DisclosureError> {
+        verify_log(&log)?;
+        Ok(DisclosureDesk { grants, log })
+    }
+
+    pub fn log(&self) -> &[DisclosureRecord] {
+        &self.log
+    }
+
+    pub fn head(&self) -> Digest {
+        self.log
+            .last()
+            .map_or(Digest::new([0u8; 32]), DisclosureRecord::hash)
+    }
+
+    // `tree` must be the hiding tree published for `request.epoch`
+    pub fn open(
+        &mut self,
+        request: &DisclosureRequest,
+        tree: &HidingTree,
+    ) -> Result<BatchOpening, DisclosureError> {
+        let grant = self
+            .grants
+            .iter()
+            .find(|grant| grant.name == request.regulator)
+            .ok_or_else(|| DisclosureError::UnknownRegulator(request.regulator.clone()))?;
+        grant
+            .key
+            .verify_strict(&request.payload(), &request.signature)
+            .map_err(|_| DisclosureError::BadSignature)?;
+        if request.positions.is_empty() || request.positions.len() > grant.max_leaves {
+            return Err(DisclosureError::TooManyLeaves {
+                requested: request.positions.len(),
+                allowed: grant.max_leaves,
+            });
+        }
+        for (index, position) in request.positions.iter().enumerate() {
+            if *position >= tree.len() {
+                return Err(DisclosureError::OutOfRange(*position));
+            }
+            if request.positions[..index].contains(position) {
+                return Err(DisclosureError::DuplicatePosition(*position));
+            }
+        }
+
+        let previous = self.head();
+        self.log.push(DisclosureRecord {
+            regulator: request.regulator.clone(),
+            epoch: request.epoch,
+            positions: request.positions.clone(),
+            reason: request.reason.clone(),
+            previous,
+        });
+        let openings = request
+            .positions
+            .iter()
+            .map(|position| {
+                let opening = tree.opening(*position).expect("position was range-checked");
+                LeafOpening {
+                    amount: opening.amount,
+                    blinding: opening.blinding.clone(),
+                    proof: tree.prove(*position),
+                }
+            })
+            .collect();
+        Ok(BatchOpening {
+            epoch: request.epoch,
+            openings,
+        })
+    }
+}
+
+// Checks a full disclosure log from its first record; returns the head hash
+pub fn verify_log(records: &[DisclosureRecord]) -> Result<Digest, DisclosureError> {
+    let mut head = Digest::new([0u8; 32]);
+    for (index, record) in records.iter().enumerate() {
+        if record.previous != head {
+            return Err(DisclosureError::BrokenLog(index));
+        }
+        head = record.hash();
+    }
+    Ok(head)
+}
+
+// Define the DisclosureError enum for requests that aren't granted and openings that
+// don't check out
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DisclosureError {
+    UnknownRegulator(String),
+    BadSignature,
+    TooManyLeaves { requested: usize, allowed: usize },
+    OutOfRange(usize),
+    DuplicatePosition(usize),
+    EpochMismatch { requested: u64, opened: u64 },
+    PositionsMismatch,
+    InvalidOpening(usize),
+    BrokenLog(usize),
+}
+
+impl fmt::Display for DisclosureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DisclosureError::UnknownRegulator(name) => {
+                write!(f, "no disclosure grant for regulator {}", name)
+            }
+            DisclosureError::BadSignature => {
+                write!(f, "request is not signed by the regulator's key")
+            }
+            DisclosureError::TooManyLeaves { requested, allowed } => write!(
+                f,
+                "request opens {} leaves, the grant allows 1 to {}",
+                requested, allowed
+            ),
+            DisclosureError::OutOfRange(position) => {
+                write!(f, "leaf {} is outside the tree", position)
+            }
+            DisclosureError::DuplicatePosition(position) => {
+                write!(f, "leaf {} is requested more than once", position)
+            }
+            DisclosureError::EpochMismatch { requested, opened } => write!(
+                f,
+                "openings are for epoch {} but epoch {} was requested",
+                opened, requested
+            ),
+            DisclosureError::PositionsMismatch => {
+                write!(f, "opened leaves differ from the requested ones")
+            }
+            DisclosureError::InvalidOpening(position) => {
+                write!(f, "opening of leaf {} does not match the hiding root", position)
+            }
+            DisclosureError::BrokenLog(index) => {
+                write!(f, "disclosure log breaks at record {}", index)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DisclosureError {}
@@ -0,0 +1,177 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::{DuplicatePolicy, HashBackend, PaddingPolicy};
+use crate::encoding::COMMITMENT_LEN;
+use crate::{ExclusiveAllotmentProof, Leaf, MerkleSumTreeBuilder, SumCommitment};
+
+// Nominal node hashes per second on one core, only used to project build time
+const SHA256_NODES_PER_SEC: u64 = 2_000_000;
+const RESCUE_NODES_PER_SEC: u64 = 100_000;
+
+// Define the DryRunReport struct, what a build would see in its inputs, gathered without
+// hashing anything. Amounts are integers in the tree's base units.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DryRunReport {
+    pub inputs: usize,
+    // Leaves left once duplicates are handled per the configured policy
+    pub accounts: usize,
+    pub padding: usize,
+    pub assets: Vec<AssetTotal>,
+    // None when the sum doesn't fit in 64 bits
+    pub total: Option<u64>,
+    pub max_balance: Option<u64>,
+    pub min_balance: Option<u64>,
+    pub zero_balances: usize,
+    // User IDs seen more than once, lossily decoded as UTF-8
+    pub duplicate_user_ids: Vec<String>,
+    pub projection: Projection,
+    // Reasons the real build would fail
+    pub problems: Vec<String>,
+    pub valid: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AssetTotal {
+    // None for inputs that don't name an asset
+    pub asset: Option<String>,
+    pub inputs: usize,
+    pub total: Option<u64>,
+}
+
+// Define the Projection struct, the size of the tree the inputs would produce
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Projection {
+    pub leaves: usize,
+    pub depth: usize,
+    // Leaf commitments plus internal nodes; each one is a hash
+    pub nodes: usize,
+    // A commitments-only snapshot; preimages add their encoded records on top
+    pub snapshot_bytes: usize,
+    pub estimated_millis: u64,
+}
+
+impl<C, P> MerkleSumTreeBuilder<C, P>
+where
+    C: SumCommitment,
+    P: ExclusiveAllotmentProof<C>,
+{
+    // Checks `leaves` against this builder's configuration and reports what `build` would
+    // produce, so an export can be sanity-checked before the real run
+    pub fn dry_run<L: Leaf>(&self, leaves: &[L]) -> DryRunReport {
+        let config = self.config();
+        let mut problems = Vec::new();
+        if let Err(err) = config.validate(C::HASH_BACKEND, C::AMOUNT_BINDING) {
+            problems.push(err.to_string());
+        }
+        if leaves.is_empty() {
+            problems.push("there are no inputs".to_string());
+        }
+
+        let mut assets: BTreeMap<Option<&str>, (usize, Option<u64>)> = BTreeMap::new();
+        // Per user ID: how often it appears, the asset it first appeared with, and whether
+        // a later record holds a different one
+        let mut seen: HashMap<&[u8], (usize, Option<&str>, bool)> = HashMap::new();
+        let mut duplicate_user_ids = Vec::new();
+        let mut mixed_assets = 0;
+        let mut total = Some(0u64);
+        let (mut max_balance, mut min_balance) = (None, None);
+        let (mut zero_balances, mut identified) = (0, 0);
+        for leaf in leaves {
+            let amount = leaf.amount();
+            let (inputs, asset_total) = assets.entry(leaf.asset()).or_insert((0, Some(0)));
+            *inputs += 1;
+            *asset_total = asset_total.and_then(|sum| sum.checked_add(amount));
+            total = total.and_then(|sum| sum.checked_add(amount));
+            max_balance = max_balance.max(Some(amount));
+            min_balance = Some(min_balance.map_or(amount, |min: u64| min.min(amount)));
+            if amount == 0 {
+                zero_balances += 1;
+            }
+            if let Some(user_id) = leaf.user_id() {
+                identified += 1;
+                let (count, asset, mixed) = seen.entry(user_id).or_insert((0, leaf.asset(), false));
+                *count += 1;
+                if *count == 2 {
+                    duplicate_user_ids.push(String::from_utf8_lossy(user_id).into_owned());
+                }
+                if *asset != leaf.asset() && !*mixed {
+                    *mixed = true;
+                    mixed_assets += 1;
+                }
+            }
+        }
+        if total.is_none() {
+            problems.push("the total balance overflows 64 bits".to_string());
+        }
+
+        let accounts = match config.duplicates {
+            DuplicatePolicy::MergeBalances => {
+                if mixed_assets > 0 {
+                    problems.push(format!(
+                        "{} user IDs hold more than one asset and can't be merged",
+                        mixed_assets
+                    ));
+                }
+                leaves.len() - (identified - seen.len())
+            }
+            DuplicatePolicy::Allow => leaves.len(),
+            DuplicatePolicy::Reject => {
+                if !duplicate_user_ids.is_empty() {
+                    problems.push(format!(
+                        "{} user IDs appear more than once",
+                        duplicate_user_ids.len()
+                    ));
+                }
+                leaves.len()
+            }
+        };
+        let padding = match config.padding {
+            PaddingPolicy::PowerOfTwo if accounts > 0 => accounts.next_power_of_two() - accounts,
+            _ => 0,
+        };
+
+        DryRunReport {
+            inputs: leaves.len(),
+            accounts,
+            padding,
+            assets: assets
+                .into_iter()
+                .map(|(asset, (inputs, total))| AssetTotal {
+                    asset: asset.map(str::to_string),
+                    inputs,
+                    total,
+                })
+                .collect(),
+            total,
+            max_balance,
+            min_balance,
+            zero_balances,
+            duplicate_user_ids,
+            projection: Projection::for_leaves(accounts + padding, config.hash_backend),
+            valid: problems.is_empty(),
+            problems,
+        }
+    }
+}
+
+impl Projection {
+    pub fn for_leaves(leaves: usize, hash_backend: HashBackend) -> Self {
+        let nodes = (2 * leaves).saturating_sub(1);
+        let nodes_per_sec = match hash_backend {
+            HashBackend::Sha256 => SHA256_NODES_PER_SEC,
+            HashBackend::RescuePrime => RESCUE_NODES_PER_SEC,
+        };
+        Projection {
+            leaves,
+            depth: match leaves {
+                0 | 1 => 0,
+                leaves => (usize::BITS - (leaves - 1).leading_zeros()) as usize,
+            },
+            nodes,
+            snapshot_bytes: 24 + leaves * (COMMITMENT_LEN + 1),
+            estimated_millis: (nodes as u64).saturating_mul(1000) / nodes_per_sec,
+        }
+    }
+}
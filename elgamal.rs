@@ -0,0 +1,392 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Add;
+
+use k256::{ProjectivePoint, Scalar};
+use sha2::{Digest as _, Sha256};
+
+use crate::encoding::ByteReader;
+use crate::entropy::EntropySource;
+use crate::pedersen::{decode_point, decode_scalar, encode_point, random_scalar, POINT_LEN};
+use crate::{Digest, ParseError};
+
+pub const CIPHERTEXT_LEN: usize = 2 * POINT_LEN;
+
+const ENCRYPTED_LEAF_DOMAIN: &[u8] = b"mimi-encrypted-leaf-v1";
+const ENCRYPTED_NODE_DOMAIN: &[u8] = b"mimi-encrypted-node-v1";
+
+// Define the AuditorKey struct, the designated auditor's decryption key
+#[derive(Clone)]
+pub struct AuditorKey(Scalar);
+
+impl AuditorKey {
+    pub fn generate(entropy: &mut impl EntropySource) -> Self {
+        AuditorKey(random_scalar(entropy))
+    }
+
+    pub fn public_key(&self) -> AuditorPublicKey {
+        AuditorPublicKey(ProjectivePoint::GENERATOR * self.0)
+    }
+
+    pub fn to_bytes(&self) -> [u8; 32] {
+        self.0.to_bytes().into()
+    }
+
+    // Recovers amount * G and looks the amount up in `table`; amounts above the table's
+    // bound can't be recovered
+    pub fn decrypt(
+        &self,
+        ciphertext: &Ciphertext,
+        table: &DecryptionTable,
+    ) -> Result<u64, ElGamalError> {
+        table
+            .lookup(ciphertext.masked - ciphertext.ephemeral * self.0)
+            .ok_or(ElGamalError::OutOfRange(table.max_amount))
+    }
+}
+
+// Keeps the secret out of logs
+impl fmt::Debug for AuditorKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "AuditorKey({})", hex::encode(self.public_key().to_bytes()))
+    }
+}
+
+impl TryFrom<&[u8]> for AuditorKey {
+    type Error = ParseError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        decode_scalar(bytes).map(AuditorKey)
+    }
+}
+
+// Define the AuditorPublicKey struct, what the operator encrypts leaf amounts to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AuditorPublicKey(ProjectivePoint);
+
+impl AuditorPublicKey {
+    pub fn to_bytes(&self) -> [u8; POINT_LEN] {
+        encode_point(&self.0)
+    }
+}
+
+impl TryFrom<&[u8]> for AuditorPublicKey {
+    type Error = ParseError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        decode_point(bytes).map(AuditorPublicKey)
+    }
+}
+
+// Define the Ciphertext struct, an exponential ElGamal encryption (r * G, amount * G + r * P).
+// Ciphertexts add component-wise into an encryption of the summed amounts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ciphertext {
+    ephemeral: ProjectivePoint,
+    masked: ProjectivePoint,
+}
+
+impl Ciphertext {
+    pub fn encrypt(amount: u64, key: &AuditorPublicKey, entropy: &mut impl EntropySource) -> Self {
+        let randomness = random_scalar(entropy);
+        Ciphertext {
+            ephemeral: ProjectivePoint::GENERATOR * randomness,
+            masked: ProjectivePoint::GENERATOR * Scalar::from(amount) + key.0 * randomness,
+        }
+    }
+
+    // ephemeral | masked, both SEC1 compressed
+    pub fn to_bytes(&self) -> [u8; CIPHERTEXT_LEN] {
+        let mut bytes = [0u8; CIPHERTEXT_LEN];
+        bytes[..POINT_LEN].copy_from_slice(&encode_point(&self.ephemeral));
+        bytes[POINT_LEN..].copy_from_slice(&encode_point(&self.masked));
+        bytes
+    }
+}
+
+impl Add for Ciphertext {
+    type Output = Ciphertext;
+
+    fn add(self, other: Ciphertext) -> Ciphertext {
+        Ciphertext {
+            ephemeral: self.ephemeral + other.ephemeral,
+            masked: self.masked + other.masked,
+        }
+    }
+}
+
+impl TryFrom<&[u8]> for Ciphertext {
+    type Error = ParseError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        let mut reader = ByteReader::new(bytes);
+        let ephemeral = decode_point(reader.take(POINT_LEN)?)?;
+        let masked = decode_point(reader.take(POINT_LEN)?)?;
+        reader.finish()?;
+        Ok(Ciphertext { ephemeral, masked })
+    }
+}
+
+// Largest bound a DecryptionTable accepts. The table holds about sqrt(max_amount) points,
+// so this keeps it near a million entries instead of billions.
+pub const MAX_TABLE_AMOUNT: u64 = 1 << 40;
+
+// Define the DecryptionTable struct, baby-step giant-step lookups for amounts up to
+// `max_amount`. It holds about sqrt(max_amount) points, so bound it by the largest
+// balance the auditor expects rather than by u64::MAX.
+#[derive(Debug, Clone)]
+pub struct DecryptionTable {
+    max_amount: u64,
+    step: u64,
+    baby_steps: HashMap<[u8; POINT_LEN], u64>,
+}
+
+impl DecryptionTable {
+    pub fn new(max_amount: u64) -> Result<Self, ElGamalError> {
+        if max_amount > MAX_TABLE_AMOUNT {
+            return Err(ElGamalError::TableTooLarge(max_amount));
+        }
+        let step = ((max_amount as f64).sqrt() as u64).saturating_add(1);
+        let mut baby_steps = HashMap::with_capacity(step as usize);
+        let mut point = ProjectivePoint::IDENTITY;
+        for index in 0..step {
+            baby_steps.insert(encode_point(&point), index);
+            point += ProjectivePoint::GENERATOR;
+        }
+        Ok(DecryptionTable {
+            max_amount,
+            step,
+            baby_steps,
+        })
+    }
+
+    pub fn max_amount(&self) -> u64 {
+        self.max_amount
+    }
+
+    fn lookup(&self, target: ProjectivePoint) -> Option<u64> {
+        let giant = ProjectivePoint::GENERATOR * Scalar::from(self.step);
+        let mut current = target;
+        for round in 0..=self.max_amount / self.step {
+            if let Some(index) = self.baby_steps.get(&encode_point(&current)) {
+                let amount = round * self.step + index;
+                return (amount <= self.max_amount).then_some(amount);
+            }
+            current -= giant;
+        }
+        None
+    }
+}
+
+// Define the EncryptedRoot struct, the published top of an encrypted tree: the sum of
+// every leaf ciphertext and the digest over the tree's ciphertexts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncryptedRoot {
+    pub ciphertext: Ciphertext,
+    pub digest: Digest,
+}
+
+// Define the EncryptedTree struct, a tree whose leaf amounts are encrypted to an auditor.
+// Anyone can check that internal nodes are the sums of their children; only the auditor
+// can read the amounts.
+//
+// Ciphertexts carry no validity or range proofs and users get no inclusion proofs, so
+// the public check only shows the root is consistent with the published ciphertexts. The
+// auditor is trusted to decrypt every leaf (`decrypt_all` fails on amounts outside the
+// table's range) and to match the amounts against the operator's user list.
+#[derive(Debug, Clone)]
+pub struct EncryptedTree {
+    auditor: AuditorPublicKey,
+    leaves: Vec<Ciphertext>,
+}
+
+impl EncryptedTree {
+    // Leaves in the same order as the transparent tree built from `amounts`
+    pub fn from_amounts(
+        amounts: &[u64],
+        auditor: AuditorPublicKey,
+        entropy: &mut impl EntropySource,
+    ) -> Result<Self, ElGamalError> {
+        if amounts.is_empty() {
+            return Err(ElGamalError::EmptyTree);
+        }
+        amounts
+            .iter()
+            .try_fold(0u64, |total, amount| total.checked_add(*amount))
+            .ok_or(ElGamalError::AmountOverflow)?;
+        let leaves = amounts
+            .iter()
+            .map(|amount| Ciphertext::encrypt(*amount, &auditor, entropy))
+            .collect();
+        Ok(EncryptedTree { auditor, leaves })
+    }
+
+    // For trees rebuilt from published ciphertexts
+    pub fn from_ciphertexts(
+        leaves: Vec<Ciphertext>,
+        auditor: AuditorPublicKey,
+    ) -> Result<Self, ElGamalError> {
+        if leaves.is_empty() {
+            return Err(ElGamalError::EmptyTree);
+        }
+        Ok(EncryptedTree { auditor, leaves })
+    }
+
+    pub fn auditor(&self) -> &AuditorPublicKey {
+        &self.auditor
+    }
+
+    pub fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+
+    pub fn leaf(&self, position: usize) -> Option<&Ciphertext> {
+        self.leaves.get(position)
+    }
+
+    pub fn iter_leaves(&self) -> impl Iterator<Item = &Ciphertext> + '_ {
+        self.leaves.iter()
+    }
+
+    pub fn commit(&self) -> EncryptedRoot {
+        let (ciphertext, digest) = build_encrypted_tree(&self.leaves);
+        EncryptedRoot {
+            ciphertext,
+            digest: Digest::new(digest),
+        }
+    }
+
+    // What a member of the public can check: the published root is recomputed from the
+    // leaf ciphertexts. It says nothing about what the ciphertexts encrypt.
+    pub fn check(&self, root: &EncryptedRoot) -> bool {
+        self.commit() == *root
+    }
+
+    // Decrypts every leaf, for the auditor
+    pub fn decrypt_all(
+        &self,
+        key: &AuditorKey,
+        table: &DecryptionTable,
+    ) -> Result<Vec<u64>, ElGamalError> {
+        if key.public_key() != self.auditor {
+            return Err(ElGamalError::WrongKey);
+        }
+        self.leaves
+            .iter()
+            .map(|ciphertext| key.decrypt(ciphertext, table))
+            .collect()
+    }
+}
+
+// Same halving as MimkMerkleTree, so positions line up with the transparent tree
+fn build_encrypted_tree(leaves: &[Ciphertext]) -> (Ciphertext, [u8; 32]) {
+    if leaves.len() == 1 {
+        let mut hasher = Sha256::new();
+        hasher.update(ENCRYPTED_LEAF_DOMAIN);
+        hasher.update(leaves[0].to_bytes());
+        return (leaves[0], hasher.finalize().into());
+    }
+    let middle = leaves.len() / 2;
+    let (left, left_digest) = build_encrypted_tree(&leaves[..middle]);
+    let (right, right_digest) = build_encrypted_tree(&leaves[middle..]);
+    let ciphertext = left + right;
+    let mut hasher = Sha256::new();
+    hasher.update(ENCRYPTED_NODE_DOMAIN);
+    hasher.update(left_digest);
+    hasher.update(right_digest);
+    hasher.update(ciphertext.to_bytes());
+    (ciphertext, hasher.finalize().into())
+}
+
+// Define the ElGamalError enum for encrypted trees that can't be built or decrypted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ElGamalError {
+    EmptyTree,
+    AmountOverflow,
+    WrongKey,
+    OutOfRange(u64),
+    // A decryption table bound above MAX_TABLE_AMOUNT
+    TableTooLarge(u64),
+}
+
+impl fmt::Display for ElGamalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ElGamalError::EmptyTree => write!(f, "cannot build an encrypted tree without leaves"),
+            ElGamalError::AmountOverflow => write!(f, "encrypted amounts overflow a u64"),
+            ElGamalError::WrongKey => {
+                write!(f, "the tree is encrypted to a different auditor key")
+            }
+            ElGamalError::OutOfRange(max_amount) => write!(
+                f,
+                "ciphertext does not decrypt to an amount of at most {}",
+                max_amount
+            ),
+            ElGamalError::TableTooLarge(max_amount) => write!(
+                f,
+                "decryption tables are limited to {}, not {}",
+                MAX_TABLE_AMOUNT, max_amount
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ElGamalError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SeededEntropy;
+
+    #[test]
+    fn auditors_decrypt_what_the_public_can_only_sum() {
+        let mut entropy = SeededEntropy::new([6; 32]);
+        let key = AuditorKey::generate(&mut entropy);
+        let tree =
+            EncryptedTree::from_amounts(&[0, 200, 300], key.public_key(), &mut entropy).unwrap();
+        let root = tree.commit();
+        let table = DecryptionTable::new(1_000).unwrap();
+        assert_eq!(tree.decrypt_all(&key, &table), Ok(vec![0, 200, 300]));
+        assert_eq!(key.decrypt(&root.ciphertext, &table), Ok(500));
+
+        let published: Vec<_> = tree
+            .iter_leaves()
+            .map(|leaf| Ciphertext::try_from(&leaf.to_bytes()[..]).unwrap())
+            .collect();
+        let mut rebuilt =
+            EncryptedTree::from_ciphertexts(published.clone(), key.public_key()).unwrap();
+        assert!(rebuilt.check(&root));
+        rebuilt.leaves.swap(0, 1);
+        assert!(!rebuilt.check(&root));
+
+        let stranger = AuditorKey::generate(&mut entropy);
+        let rebuilt = EncryptedTree::from_ciphertexts(published, key.public_key()).unwrap();
+        assert_eq!(
+            rebuilt.decrypt_all(&stranger, &table),
+            Err(ElGamalError::WrongKey)
+        );
+    }
+
+    #[test]
+    fn amounts_past_the_table_bound_are_not_recovered() {
+        let mut entropy = SeededEntropy::new([7; 32]);
+        let key = AuditorKey::generate(&mut entropy);
+        let table = DecryptionTable::new(500).unwrap();
+        let at_bound = Ciphertext::encrypt(500, &key.public_key(), &mut entropy);
+        let past_bound = Ciphertext::encrypt(501, &key.public_key(), &mut entropy);
+        assert_eq!(key.decrypt(&at_bound, &table), Ok(500));
+        assert_eq!(
+            key.decrypt(&past_bound, &table),
+            Err(ElGamalError::OutOfRange(500))
+        );
+
+        assert_eq!(
+            DecryptionTable::new(MAX_TABLE_AMOUNT + 1).unwrap_err(),
+            ElGamalError::TableTooLarge(MAX_TABLE_AMOUNT + 1)
+        );
+    }
+}
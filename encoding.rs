@@ -0,0 +1,425 @@
+use std::fmt;
+use std::str::FromStr;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use generic_array::typenum::U32;
+use generic_array::GenericArray;
+
+use crate::{
+    ExclusiveAllotmentProof, LeafCommitment, MerkleProof, MimiLeafCommitment, MimiSumCommitment,
+    Root, SumCommitment,
+};
+
+// Encoded commitments are the little-endian amount followed by the digest
+pub const COMMITMENT_LEN: usize = 40;
+
+// A proof path can't be longer than the number of bits in a position
+pub(crate) const MAX_PROOF_DEPTH: usize = 64;
+
+// Every integer that reaches a hash, signature or wire format is fixed-width little-endian.
+// Positions, counters and lengths are widened to u64 first so 32- and 64-bit builds (and
+// big-endian hosts) produce identical bytes; use these instead of ad hoc casts.
+pub(crate) fn encode_usize(value: usize) -> [u8; 8] {
+    (value as u64).to_le_bytes()
+}
+
+pub(crate) fn encode_len(bytes: &[u8]) -> [u8; 8] {
+    encode_usize(bytes.len())
+}
+
+// Define the Digest newtype, a 32-byte node digest that prints and parses as hex
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Digest([u8; 32]);
+
+impl Digest {
+    pub fn new(bytes: [u8; 32]) -> Self {
+        Digest(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    pub fn to_base64(&self) -> String {
+        BASE64.encode(self.0)
+    }
+
+    pub fn from_base64(encoded: &str) -> Result<Self, ParseError> {
+        let bytes = BASE64
+            .decode(encoded)
+            .map_err(|_| ParseError::InvalidBase64)?;
+        Digest::try_from(bytes.as_slice())
+    }
+}
+
+impl TryFrom<&[u8]> for Digest {
+    type Error = ParseError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        Ok(Digest(to_digest_bytes(bytes)?))
+    }
+}
+
+impl From<GenericArray<u8, U32>> for Digest {
+    fn from(digest: GenericArray<u8, U32>) -> Self {
+        Digest(digest.into())
+    }
+}
+
+impl From<Digest> for GenericArray<u8, U32> {
+    fn from(digest: Digest) -> Self {
+        digest.0.into()
+    }
+}
+
+impl fmt::LowerHex for Digest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&hex::encode(self.0))
+    }
+}
+
+impl fmt::Display for Digest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::LowerHex::fmt(self, f)
+    }
+}
+
+impl FromStr for Digest {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = hex::decode(s).map_err(|_| ParseError::InvalidHex)?;
+        Digest::try_from(bytes.as_slice())
+    }
+}
+
+// Roots print as `<amount>:<hex digest>`
+impl<C: SumCommitment> fmt::Display for Root<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.amount(), Digest::from(self.digest()))
+    }
+}
+
+impl<C: SumCommitment> fmt::LowerHex for Root<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::LowerHex::fmt(&Digest::from(self.digest()), f)
+    }
+}
+
+impl<C: SumCommitment> FromStr for Root<C> {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (amount, digest) = s.split_once(':').ok_or(ParseError::MissingSeparator)?;
+        let amount = amount
+            .parse::<u64>()
+            .map_err(|_| ParseError::InvalidAmount)?;
+        let digest = digest.parse::<Digest>()?;
+        Ok(Root::from_node(C::from_parts(amount, digest.into())))
+    }
+}
+
+impl<C: SumCommitment> Root<C> {
+    pub fn to_bytes(&self) -> [u8; COMMITMENT_LEN] {
+        encode_commitment(self.amount(), &self.digest())
+    }
+
+    pub fn to_base64(&self) -> String {
+        BASE64.encode(self.to_bytes())
+    }
+
+    pub fn from_base64(encoded: &str) -> Result<Self, ParseError> {
+        let bytes = BASE64
+            .decode(encoded)
+            .map_err(|_| ParseError::InvalidBase64)?;
+        Root::try_from(bytes.as_slice())
+    }
+}
+
+impl<C: SumCommitment> TryFrom<&[u8]> for Root<C> {
+    type Error = ParseError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        let (amount, digest) = decode_commitment(bytes)?;
+        Ok(Root::from_node(C::from_parts(amount, digest)))
+    }
+}
+
+impl MimiLeafCommitment {
+    pub fn to_bytes(&self) -> [u8; COMMITMENT_LEN] {
+        encode_commitment(self.amount(), &self.digest())
+    }
+}
+
+impl TryFrom<&[u8]> for MimiLeafCommitment {
+    type Error = ParseError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        let (amount, digest) = decode_commitment(bytes)?;
+        Ok(MimiLeafCommitment::from_parts(amount, digest))
+    }
+}
+
+impl MimiSumCommitment {
+    pub fn to_bytes(&self) -> [u8; COMMITMENT_LEN] {
+        encode_commitment(self.amount(), &self.digest())
+    }
+}
+
+impl TryFrom<&[u8]> for MimiSumCommitment {
+    type Error = ParseError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        let (amount, digest) = decode_commitment(bytes)?;
+        Ok(MimiSumCommitment::from_parts(amount, digest))
+    }
+}
+
+// Proofs encode as: position (u64) | leaf | sibling count (u8) | per sibling a
+// direction byte (0 = right, 1 = left) followed by the sibling commitment
+impl<C: SumCommitment> MerkleProof<C> {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let siblings = self.siblings();
+        let mut bytes = Vec::with_capacity(8 + COMMITMENT_LEN + 1 + siblings.len() * 41);
+        bytes.extend_from_slice(&encode_usize(self.position()));
+        bytes.extend_from_slice(&encode_commitment(
+            self.leaf().amount(),
+            &self.leaf().digest(),
+        ));
+        bytes.push(siblings.len() as u8);
+        for (sibling, sibling_on_left) in siblings {
+            bytes.push(*sibling_on_left as u8);
+            bytes.extend_from_slice(&encode_commitment(sibling.amount(), &sibling.digest()));
+        }
+        bytes
+    }
+}
+
+impl<C: SumCommitment> TryFrom<&[u8]> for MerkleProof<C> {
+    type Error = ParseError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        let mut reader = ByteReader::new(bytes);
+
+        let position = u64::from_le_bytes(reader.take_array::<8>()?);
+        let position = usize::try_from(position).map_err(|_| ParseError::PositionOverflow)?;
+        let (amount, digest) = decode_commitment(reader.take(COMMITMENT_LEN)?)?;
+        let leaf = C::Leaf::from_parts(amount, digest);
+
+        let count = reader.take_array::<1>()?[0] as usize;
+        if count > MAX_PROOF_DEPTH {
+            return Err(ParseError::TooDeep(count));
+        }
+        let mut siblings = Vec::with_capacity(count);
+        for _ in 0..count {
+            let sibling_on_left = match reader.take_array::<1>()?[0] {
+                0 => false,
+                1 => true,
+                other => return Err(ParseError::InvalidDirection(other)),
+            };
+            let (amount, digest) = decode_commitment(reader.take(COMMITMENT_LEN)?)?;
+            siblings.push((C::from_parts(amount, digest), sibling_on_left));
+        }
+        reader.finish()?;
+
+        Ok(MerkleProof::new(position, leaf, siblings))
+    }
+}
+
+pub(crate) fn encode_commitment(
+    amount: u64,
+    digest: &GenericArray<u8, U32>,
+) -> [u8; COMMITMENT_LEN] {
+    let mut bytes = [0u8; COMMITMENT_LEN];
+    bytes[..8].copy_from_slice(&amount.to_le_bytes());
+    bytes[8..].copy_from_slice(digest);
+    bytes
+}
+
+pub(crate) fn decode_commitment(bytes: &[u8]) -> Result<(u64, GenericArray<u8, U32>), ParseError> {
+    if bytes.len() != COMMITMENT_LEN {
+        return Err(ParseError::InvalidLength {
+            expected: COMMITMENT_LEN,
+            found: bytes.len(),
+        });
+    }
+    let mut amount = [0u8; 8];
+    amount.copy_from_slice(&bytes[..8]);
+    let digest = to_digest_bytes(&bytes[8..])?;
+    Ok((u64::from_le_bytes(amount), digest.into()))
+}
+
+fn to_digest_bytes(bytes: &[u8]) -> Result<[u8; 32], ParseError> {
+    bytes.try_into().map_err(|_| ParseError::InvalidLength {
+        expected: 32,
+        found: bytes.len(),
+    })
+}
+
+// Define the ByteReader struct, a cursor that reports truncation instead of panicking
+pub(crate) struct ByteReader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        ByteReader { bytes, offset: 0 }
+    }
+
+    pub(crate) fn take(&mut self, len: usize) -> Result<&'a [u8], ParseError> {
+        let remaining = self.bytes.len() - self.offset;
+        if remaining < len {
+            return Err(ParseError::Truncated {
+                needed: len,
+                remaining,
+            });
+        }
+        let slice = &self.bytes[self.offset..self.offset + len];
+        self.offset += len;
+        Ok(slice)
+    }
+
+    pub(crate) fn take_array<const N: usize>(&mut self) -> Result<[u8; N], ParseError> {
+        let mut array = [0u8; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+
+    pub(crate) fn finish(self) -> Result<(), ParseError> {
+        match self.bytes.len() - self.offset {
+            0 => Ok(()),
+            trailing => Err(ParseError::TrailingBytes(trailing)),
+        }
+    }
+}
+
+// Define the ParseError enum for rejected textual and binary inputs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    InvalidHex,
+    InvalidBase64,
+    InvalidLength { expected: usize, found: usize },
+    InvalidAmount,
+    MissingSeparator,
+    Truncated { needed: usize, remaining: usize },
+    TrailingBytes(usize),
+    InvalidDirection(u8),
+    PositionOverflow,
+    TooDeep(usize),
+    InvalidShape,
+    InvalidPoint,
+    InvalidScalar,
+    UnknownMagic,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::InvalidHex => write!(f, "invalid hex encoding"),
+            ParseError::InvalidBase64 => write!(f, "invalid base64 encoding"),
+            ParseError::InvalidLength { expected, found } => {
+                write!(f, "expected {} bytes, found {}", expected, found)
+            }
+            ParseError::InvalidAmount => write!(f, "invalid amount"),
+            ParseError::MissingSeparator => write!(f, "expected `<amount>:<digest>`"),
+            ParseError::Truncated { needed, remaining } => write!(
+                f,
+                "input truncated, needed {} more bytes but only {} remain",
+                needed, remaining
+            ),
+            ParseError::TrailingBytes(count) => write!(f, "{} unexpected trailing bytes", count),
+            ParseError::InvalidDirection(byte) => {
+                write!(f, "invalid sibling direction byte {:#04x}", byte)
+            }
+            ParseError::PositionOverflow => write!(f, "position does not fit in usize"),
+            ParseError::TooDeep(count) => write!(
+                f,
+                "proof has {} siblings, more than the maximum of {}",
+                count, MAX_PROOF_DEPTH
+            ),
+            ParseError::InvalidShape => {
+                write!(f, "multiproof bitmap does not match the tree shape")
+            }
+            ParseError::InvalidPoint => write!(f, "not a compressed secp256k1 point"),
+            ParseError::InvalidScalar => {
+                write!(f, "scalar is not below the secp256k1 group order")
+            }
+            ParseError::UnknownMagic => write!(f, "unrecognized format magic"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::seeded_digest;
+    use crate::MimkMerkleTree;
+
+    type ReferenceTree = MimkMerkleTree<MimiSumCommitment, MerkleProof<MimiSumCommitment>>;
+
+    // Pins the SHA-256 backend's root and proof bytes, so any change to hashing,
+    // endianness or the proof layout shows up here
+    fn reference_tree() -> ReferenceTree {
+        ReferenceTree::builder()
+            .build(&[100u64, 200, 300, 400])
+            .unwrap()
+    }
+
+    #[test]
+    fn integers_are_little_endian_u64() {
+        assert_eq!(encode_usize(0x0102_0304), [4, 3, 2, 1, 0, 0, 0, 0]);
+        assert_eq!(encode_usize(usize::MAX)[..4], [0xff; 4]);
+        assert_eq!(encode_len(&[0u8; 300]), [0x2c, 0x01, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(encode_len(&[]), [0; 8]);
+    }
+
+    #[test]
+    fn seeded_digest_bytes_are_pinned() {
+        assert_eq!(
+            hex::encode(seeded_digest(b"salt", &[0x42; 32], 7)),
+            "ed5a5d1b8aa83eca3cdca51cd92bd736c2dc7eb1f6d7d0994e274d2ae5c78857"
+        );
+    }
+
+    #[test]
+    fn root_bytes_are_pinned() {
+        assert_eq!(
+            hex::encode(reference_tree().commit().to_bytes()),
+            concat!(
+                "e803000000000000",
+                "5e4c2cb2c6d287bd70661ab5ac1d44ff20bfec02393c241d7cd94f30da5ffc11",
+            )
+        );
+    }
+
+    #[test]
+    fn proof_bytes_are_pinned() {
+        let bytes = reference_tree().prove(2).to_bytes();
+        assert_eq!(
+            hex::encode(&bytes),
+            concat!(
+                // position 2
+                "0200000000000000",
+                // leaf: amount 300 | digest
+                "2c01000000000000",
+                "3b48a2c7abba53bd66759cfef23dfdde0c6c9d9261856ea7f53c775b76c4f091",
+                // two siblings
+                "02",
+                // right sibling: the leaf of 400
+                "00",
+                "9001000000000000",
+                "b7dc49a2a7ad3928eb8a6068e80d1bd431c30e8a4921ff592c34727175fbcd50",
+                // left sibling: the node over 100 and 200
+                "01",
+                "2c01000000000000",
+                "926fd02331e3b6a1477899d115a8e6e5198a3b6219dc2b5baebcc2630937d763",
+            )
+        );
+        let decoded = MerkleProof::<MimiSumCommitment>::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(decoded.to_bytes(), bytes);
+    }
+}
@@ -0,0 +1,96 @@
+use sha2::{Digest as _, Sha256};
+
+use crate::config::{PaddingLeaf, SaltDerivation, Shuffling};
+
+// Define the EntropySource trait, the one place salts, shuffles and blinding seeds come
+// from, so tests and audits can swap the OS generator for a reproducible one
+pub trait EntropySource {
+    fn fill_bytes(&mut self, dest: &mut [u8]);
+
+    fn seed(&mut self) -> [u8; 32] {
+        let mut seed = [0u8; 32];
+        self.fill_bytes(&mut seed);
+        seed
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut bytes = [0u8; 8];
+        self.fill_bytes(&mut bytes);
+        u64::from_le_bytes(bytes)
+    }
+}
+
+impl<E: EntropySource + ?Sized> EntropySource for &mut E {
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        (**self).fill_bytes(dest)
+    }
+}
+
+// Define the OsEntropy struct, the operating system's CSPRNG and the default source
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OsEntropy;
+
+impl EntropySource for OsEntropy {
+    // Panics if the OS generator is unavailable; there is no safe fallback
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        getrandom::getrandom(dest).expect("operating system entropy source failed");
+    }
+}
+
+// Define the SeededEntropy struct, a deterministic stream of SHA-256(seed || counter)
+// blocks. Only for reproducible tests and audits: anyone holding the seed can
+// recompute every salt drawn from it.
+#[derive(Debug, Clone)]
+pub struct SeededEntropy {
+    seed: [u8; 32],
+    counter: u64,
+    block: [u8; 32],
+    used: usize,
+}
+
+impl SeededEntropy {
+    pub fn new(seed: [u8; 32]) -> Self {
+        SeededEntropy {
+            seed,
+            counter: 0,
+            block: [0u8; 32],
+            used: 32,
+        }
+    }
+}
+
+impl EntropySource for SeededEntropy {
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for byte in dest {
+            if self.used == self.block.len() {
+                let mut hasher = Sha256::new();
+                hasher.update(b"mimi-seeded-entropy-v1");
+                hasher.update(self.seed);
+                hasher.update(self.counter.to_le_bytes());
+                self.block = hasher.finalize().into();
+                self.counter += 1;
+                self.used = 0;
+            }
+            *byte = self.block[self.used];
+            self.used += 1;
+        }
+    }
+}
+
+impl SaltDerivation {
+    pub fn random(entropy: &mut impl EntropySource) -> Self {
+        SaltDerivation::FromSeed(entropy.seed())
+    }
+}
+
+impl Shuffling {
+    pub fn random(entropy: &mut impl EntropySource) -> Self {
+        Shuffling::Seeded(entropy.seed())
+    }
+}
+
+impl PaddingLeaf {
+    pub fn random(entropy: &mut impl EntropySource) -> Self {
+        PaddingLeaf::Blinded(entropy.seed())
+    }
+}
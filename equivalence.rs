@@ -0,0 +1,271 @@
+use std::fmt;
+
+use sha2::{Digest as _, Sha256};
+
+use crate::encoding::{encode_usize, ByteReader};
+use crate::header::backend_id;
+use crate::root_signature::{RootSigner, RootVerifier, SignatureError, TaggedSignature};
+use crate::signer::SignerError;
+use crate::{
+    BuildError, ExclusiveAllotmentProof, HashBackend, Leaf, LeafCommitment, MerkleProof,
+    MerkleSumTreeBuilder, MimkMerkleTree, ParseError, Root, SumCommitment, TreeConfig,
+};
+
+const LINKAGE_DOMAIN: &[u8] = b"mimi-root-linkage-v1";
+
+// Define the BackendRoot struct, one tree's root together with the hash it was built on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BackendRoot {
+    pub backend: HashBackend,
+    pub amount: u64,
+    pub digest: [u8; 32],
+}
+
+impl BackendRoot {
+    pub fn of<C: SumCommitment>(root: &Root<C>) -> Self {
+        BackendRoot {
+            backend: C::HASH_BACKEND,
+            amount: root.amount(),
+            digest: root.digest().into(),
+        }
+    }
+
+    // Whether `root` is this one, under the same backend
+    pub fn matches<C: SumCommitment>(&self, root: &Root<C>) -> bool {
+        *self == BackendRoot::of(root)
+    }
+
+    fn read(reader: &mut ByteReader<'_>) -> Result<Self, ParseError> {
+        let backend = match reader.take_array::<1>()?[0] {
+            1 => HashBackend::Sha256,
+            2 => HashBackend::RescuePrime,
+            _ => return Err(ParseError::UnknownMagic),
+        };
+        Ok(BackendRoot {
+            backend,
+            amount: u64::from_le_bytes(reader.take_array()?),
+            digest: reader.take_array()?,
+        })
+    }
+
+    fn write(&self, bytes: &mut Vec<u8>) {
+        bytes.push(backend_id(self.backend));
+        bytes.extend_from_slice(&self.amount.to_le_bytes());
+        bytes.extend_from_slice(&self.digest);
+    }
+}
+
+// Define the LinkageRecord struct, the statement that two roots commit to the same leaf
+// set, e.g. a SHA-256 root for transparent verification and a Rescue root for a STARK
+// solvency proof. `pairing` hashes every position's amount with both leaf digests, so an
+// auditor holding both snapshots can check the correspondence leaf by leaf.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkageRecord {
+    pub epoch: u64,
+    pub leaf_count: usize,
+    pub first: BackendRoot,
+    pub second: BackendRoot,
+    pub pairing: [u8; 32],
+}
+
+impl LinkageRecord {
+    // What the operator signs: domain | epoch | leaf count | first | second | pairing
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = LINKAGE_DOMAIN.to_vec();
+        bytes.extend_from_slice(&self.epoch.to_le_bytes());
+        bytes.extend_from_slice(&encode_usize(self.leaf_count));
+        self.first.write(&mut bytes);
+        self.second.write(&mut bytes);
+        bytes.extend_from_slice(&self.pairing);
+        bytes
+    }
+
+    fn read(reader: &mut ByteReader<'_>) -> Result<Self, ParseError> {
+        if reader.take(LINKAGE_DOMAIN.len())? != LINKAGE_DOMAIN {
+            return Err(ParseError::UnknownMagic);
+        }
+        let epoch = u64::from_le_bytes(reader.take_array()?);
+        let leaf_count = usize::try_from(u64::from_le_bytes(reader.take_array()?))
+            .map_err(|_| ParseError::PositionOverflow)?;
+        Ok(LinkageRecord {
+            epoch,
+            leaf_count,
+            first: BackendRoot::read(reader)?,
+            second: BackendRoot::read(reader)?,
+            pairing: reader.take_array()?,
+        })
+    }
+
+    // One signature over both roots, so neither can be published without the other
+    pub fn sign(self, signer: &dyn RootSigner) -> Result<SignedLinkage, SignerError> {
+        let signature = signer.sign_bytes(&self.to_bytes())?;
+        if signature.scheme() != signer.scheme() {
+            return Err(SignerError::InvalidSignature);
+        }
+        Ok(SignedLinkage {
+            record: self,
+            signature,
+        })
+    }
+}
+
+impl TryFrom<&[u8]> for LinkageRecord {
+    type Error = ParseError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        let mut reader = ByteReader::new(bytes);
+        let record = LinkageRecord::read(&mut reader)?;
+        reader.finish()?;
+        Ok(record)
+    }
+}
+
+// Define the SignedLinkage struct, a linkage record with the operator's signature
+#[derive(Debug, Clone)]
+pub struct SignedLinkage {
+    pub record: LinkageRecord,
+    pub signature: TaggedSignature,
+}
+
+impl SignedLinkage {
+    pub fn verify(&self, verifier: &dyn RootVerifier) -> Result<(), SignatureError> {
+        verifier.verify_bytes(&self.record.to_bytes(), &self.signature)
+    }
+
+    // record | tagged signature
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.record.to_bytes();
+        bytes.extend_from_slice(&self.signature.to_bytes());
+        bytes
+    }
+}
+
+impl TryFrom<&[u8]> for SignedLinkage {
+    type Error = ParseError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        let mut reader = ByteReader::new(bytes);
+        let record = LinkageRecord::read(&mut reader)?;
+        let signature = TaggedSignature::read(&mut reader)?;
+        reader.finish()?;
+        Ok(SignedLinkage { record, signature })
+    }
+}
+
+// Builds the leaves under both commitment types from one configuration (the hash backend
+// and amount binding are taken from each type) and links the two roots
+pub fn check_equivalence<A, B, L>(
+    epoch: u64,
+    config: &TreeConfig,
+    leaves: &[L],
+) -> Result<LinkageRecord, EquivalenceError>
+where
+    A: SumCommitment,
+    B: SumCommitment,
+    L: Leaf,
+{
+    let first = MerkleSumTreeBuilder::<A, MerkleProof<A>>::with_config(TreeConfig {
+        hash_backend: A::HASH_BACKEND,
+        amount_binding: A::AMOUNT_BINDING,
+        ..config.clone()
+    })
+    .build(leaves)?;
+    let second = MerkleSumTreeBuilder::<B, MerkleProof<B>>::with_config(TreeConfig {
+        hash_backend: B::HASH_BACKEND,
+        amount_binding: B::AMOUNT_BINDING,
+        ..config.clone()
+    })
+    .build(leaves)?;
+    link_trees(epoch, &first, &second)
+}
+
+// Links two trees that were already built. Everything that decides where a leaf lands
+// must agree, and every position must hold the same amount in both.
+pub fn link_trees<A, PA, B, PB>(
+    epoch: u64,
+    first: &MimkMerkleTree<A, PA>,
+    second: &MimkMerkleTree<B, PB>,
+) -> Result<LinkageRecord, EquivalenceError>
+where
+    A: SumCommitment,
+    PA: ExclusiveAllotmentProof<A>,
+    B: SumCommitment,
+    PB: ExclusiveAllotmentProof<B>,
+{
+    if A::HASH_BACKEND == B::HASH_BACKEND {
+        return Err(EquivalenceError::SameBackend(A::HASH_BACKEND));
+    }
+    let (a, b) = (first.config(), second.config());
+    if a.padding != b.padding
+        || a.padding_leaf != b.padding_leaf
+        || a.salt_derivation != b.salt_derivation
+        || a.shuffling != b.shuffling
+        || a.duplicates != b.duplicates
+    {
+        return Err(EquivalenceError::ConfigMismatch);
+    }
+    if first.len() != second.len() {
+        return Err(EquivalenceError::LeafCount {
+            first: first.len(),
+            second: second.len(),
+        });
+    }
+
+    let mut pairing = Sha256::new();
+    for (position, (left, right)) in first.iter_leaves().zip(second.iter_leaves()).enumerate() {
+        if left.amount() != right.amount() {
+            return Err(EquivalenceError::AmountMismatch(position));
+        }
+        pairing.update(left.amount().to_le_bytes());
+        pairing.update(left.digest());
+        pairing.update(right.digest());
+    }
+
+    Ok(LinkageRecord {
+        epoch,
+        leaf_count: first.len(),
+        first: BackendRoot::of(&first.commit()),
+        second: BackendRoot::of(&second.commit()),
+        pairing: pairing.finalize().into(),
+    })
+}
+
+// Define the EquivalenceError enum for leaf sets whose trees can't be shown to agree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EquivalenceError {
+    Build(BuildError),
+    // Linking a backend to itself proves nothing
+    SameBackend(HashBackend),
+    // Padding, salts, shuffling or duplicate handling differ, so positions don't line up
+    ConfigMismatch,
+    LeafCount { first: usize, second: usize },
+    AmountMismatch(usize),
+}
+
+impl fmt::Display for EquivalenceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EquivalenceError::Build(err) => write!(f, "{}", err),
+            EquivalenceError::SameBackend(backend) => {
+                write!(f, "both trees use the {:?} backend", backend)
+            }
+            EquivalenceError::ConfigMismatch => {
+                write!(f, "trees were built with different leaf layouts")
+            }
+            EquivalenceError::LeafCount { first, second } => {
+                write!(f, "trees have {} and {} leaves", first, second)
+            }
+            EquivalenceError::AmountMismatch(position) => {
+                write!(f, "trees disagree on the amount at position {}", position)
+            }
+        }
+    }
+}
+
+impl std::error::Error for EquivalenceError {}
+
+impl From<BuildError> for EquivalenceError {
+    fn from(err: BuildError) -> Self {
+        EquivalenceError::Build(err)
+    }
+}
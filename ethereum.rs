@@ -0,0 +1,145 @@
+use std::fmt;
+
+use alloy::eips::BlockId;
+use alloy::primitives::{Address, FixedBytes, TxHash, U256};
+use alloy::providers::Provider;
+use alloy::sol;
+
+use crate::{Root, SumCommitment};
+
+sol! {
+    // Minimal registry contract the helper talks to: one root and total per epoch
+    #[sol(rpc)]
+    contract RootRegistry {
+        function publishRoot(uint64 epoch, bytes32 root, uint256 total) external;
+        function roots(uint64 epoch) external view returns (bytes32 root, uint256 total);
+    }
+}
+
+// Define the Publication struct, where a root landed on chain
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Publication {
+    pub transaction_hash: TxHash,
+    pub block_number: u64,
+}
+
+// Define the RootPublisher struct, bound to one deployed registry contract
+#[derive(Debug, Clone)]
+pub struct RootPublisher<P: Provider> {
+    provider: P,
+    contract: Address,
+}
+
+impl<P: Provider> RootPublisher<P> {
+    pub fn new(provider: P, contract: Address) -> Self {
+        RootPublisher { provider, contract }
+    }
+
+    pub async fn publish<C: SumCommitment>(
+        &self,
+        epoch: u64,
+        root: &Root<C>,
+    ) -> Result<Publication, EthereumError> {
+        let registry = RootRegistry::new(self.contract, &self.provider);
+        let receipt = registry
+            .publishRoot(epoch, root_word(root), U256::from(root.amount()))
+            .send()
+            .await
+            .map_err(|err| EthereumError::Rpc(err.to_string()))?
+            .get_receipt()
+            .await
+            .map_err(|err| EthereumError::Rpc(err.to_string()))?;
+
+        if !receipt.status() {
+            return Err(EthereumError::Reverted(receipt.transaction_hash));
+        }
+        let block_number = receipt
+            .block_number
+            .ok_or(EthereumError::Pending(receipt.transaction_hash))?;
+        Ok(Publication {
+            transaction_hash: receipt.transaction_hash,
+            block_number,
+        })
+    }
+
+    // Confirms the registry held `root` for `epoch` from exactly `block_number` on:
+    // present at that block and absent at the one before
+    pub async fn verify_published<C: SumCommitment>(
+        &self,
+        epoch: u64,
+        root: &Root<C>,
+        block_number: u64,
+    ) -> Result<(), EthereumError> {
+        if !self.holds_root_at(epoch, root, block_number).await? {
+            return Err(EthereumError::NotPublished {
+                epoch,
+                block_number,
+            });
+        }
+        if block_number > 0 && self.holds_root_at(epoch, root, block_number - 1).await? {
+            return Err(EthereumError::PublishedEarlier {
+                epoch,
+                block_number,
+            });
+        }
+        Ok(())
+    }
+
+    async fn holds_root_at<C: SumCommitment>(
+        &self,
+        epoch: u64,
+        root: &Root<C>,
+        block_number: u64,
+    ) -> Result<bool, EthereumError> {
+        let registry = RootRegistry::new(self.contract, &self.provider);
+        let stored = registry
+            .roots(epoch)
+            .block(BlockId::number(block_number))
+            .call()
+            .await
+            .map_err(|err| EthereumError::Rpc(err.to_string()))?;
+        Ok(stored.root == root_word(root) && stored.total == U256::from(root.amount()))
+    }
+}
+
+fn root_word<C: SumCommitment>(root: &Root<C>) -> FixedBytes<32> {
+    FixedBytes::from_slice(&root.digest())
+}
+
+// Define the EthereumError enum for publications that fail or can't be confirmed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EthereumError {
+    Rpc(String),
+    Reverted(TxHash),
+    Pending(TxHash),
+    NotPublished { epoch: u64, block_number: u64 },
+    PublishedEarlier { epoch: u64, block_number: u64 },
+}
+
+impl fmt::Display for EthereumError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EthereumError::Rpc(message) => write!(f, "rpc error: {}", message),
+            EthereumError::Reverted(hash) => write!(f, "transaction {} reverted", hash),
+            EthereumError::Pending(hash) => write!(f, "transaction {} has no block yet", hash),
+            EthereumError::NotPublished {
+                epoch,
+                block_number,
+            } => write!(
+                f,
+                "root for epoch {} is not in the registry at block {}",
+                epoch, block_number
+            ),
+            EthereumError::PublishedEarlier {
+                epoch,
+                block_number,
+            } => write!(
+                f,
+                "root for epoch {} was already published before block {}",
+                epoch, block_number
+            ),
+        }
+    }
+}
+
+impl std::error::Error for EthereumError {}
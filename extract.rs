@@ -0,0 +1,111 @@
+use std::fmt;
+
+use axum::async_trait;
+use axum::extract::rejection::JsonRejection;
+use axum::extract::{FromRef, FromRequest, Request};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use ed25519_dalek::VerifyingKey;
+
+use crate::bundle::{BundleError, ProofBundle, VerificationPolicy};
+use crate::server::ProofResponse;
+use crate::{MerkleProof, ParseError, SumCommitment};
+
+// Define the ProofVerifier struct, the verification settings extractors read from the
+// router state (through `FromRef`, so it can sit inside a larger application state)
+#[derive(Debug, Clone)]
+pub struct ProofVerifier {
+    pub operator_key: VerifyingKey,
+}
+
+impl ProofVerifier {
+    pub fn new(operator_key: VerifyingKey) -> Self {
+        ProofVerifier { operator_key }
+    }
+
+    pub fn verify<C: SumCommitment>(
+        &self,
+        response: &ProofResponse,
+    ) -> Result<ProofBundle<C, MerkleProof<C>>, ProofRejection> {
+        let bundle = response.to_bundle::<C>()?;
+        bundle.verify(&VerificationPolicy::new(self.operator_key))?;
+        Ok(bundle)
+    }
+}
+
+// Define the VerifiedProof struct, an extractor that parses a `ProofResponse` JSON body and
+// only hands the handler bundles that verify. Use it as the last extractor, since it
+// consumes the body.
+#[derive(Debug)]
+pub struct VerifiedProof<C: SumCommitment>(pub ProofBundle<C, MerkleProof<C>>);
+
+#[async_trait]
+impl<S, C> FromRequest<S> for VerifiedProof<C>
+where
+    S: Send + Sync,
+    ProofVerifier: FromRef<S>,
+    C: SumCommitment + Send,
+{
+    type Rejection = ProofRejection;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(response) = Json::<ProofResponse>::from_request(request, state).await?;
+        Ok(VerifiedProof(ProofVerifier::from_ref(state).verify(&response)?))
+    }
+}
+
+// Define the ProofRejection enum, why a request was turned away before reaching the
+// handler. Unreadable bodies are 400s, well-formed proofs that fail are 422s.
+#[derive(Debug)]
+pub enum ProofRejection {
+    Body(JsonRejection),
+    Malformed(ParseError),
+    Invalid(BundleError),
+}
+
+impl ProofRejection {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ProofRejection::Body(rejection) => rejection.status(),
+            ProofRejection::Malformed(_) => StatusCode::BAD_REQUEST,
+            ProofRejection::Invalid(_) => StatusCode::UNPROCESSABLE_ENTITY,
+        }
+    }
+}
+
+impl fmt::Display for ProofRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProofRejection::Body(rejection) => write!(f, "{}", rejection.body_text()),
+            ProofRejection::Malformed(err) => write!(f, "malformed proof: {}", err),
+            ProofRejection::Invalid(err) => write!(f, "proof rejected: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ProofRejection {}
+
+impl IntoResponse for ProofRejection {
+    fn into_response(self) -> Response {
+        (self.status(), self.to_string()).into_response()
+    }
+}
+
+impl From<JsonRejection> for ProofRejection {
+    fn from(rejection: JsonRejection) -> Self {
+        ProofRejection::Body(rejection)
+    }
+}
+
+impl From<ParseError> for ProofRejection {
+    fn from(err: ParseError) -> Self {
+        ProofRejection::Malformed(err)
+    }
+}
+
+impl From<BundleError> for ProofRejection {
+    fn from(err: BundleError) -> Self {
+        ProofRejection::Invalid(err)
+    }
+}
@@ -0,0 +1,100 @@
+use crate::{
+    checked_path_sum, ExclusiveAllotmentProof, LeafCommitment, MimkMerkleTree, Root, SumCommitment,
+    VerifyError,
+};
+
+// Define the FixedProof struct, a sibling path kept in a fixed-size array so that
+// building and verifying a proof never touches the heap
+#[derive(Debug, Clone)]
+pub struct FixedProof<C: SumCommitment, const DEPTH: usize> {
+    position: usize,
+    leaf: C::Leaf,
+    // Siblings ordered from the leaf up to the root, `true` when the sibling is the left child
+    path: [Option<(C, bool)>; DEPTH],
+}
+
+impl<C: SumCommitment, const DEPTH: usize> FixedProof<C, DEPTH> {
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn leaf(&self) -> &C::Leaf {
+        &self.leaf
+    }
+
+    pub fn depth(&self) -> usize {
+        self.path.iter().take_while(|step| step.is_some()).count()
+    }
+
+    pub fn reconstruct_commitment(&self) -> C {
+        let mut current = self.leaf.to_node();
+        for (sibling, sibling_on_left) in self.path.iter().flatten() {
+            current = if *sibling_on_left {
+                C::combine_commitments(sibling, &current)
+            } else {
+                C::combine_commitments(&current, sibling)
+            };
+        }
+        current
+    }
+
+    pub fn check(&self, root: &Root<C>) -> Result<(), VerifyError> {
+        checked_path_sum(
+            self.leaf.amount(),
+            self.path.iter().flatten().map(|(sibling, _)| sibling),
+        )?;
+        root.check(&self.reconstruct_commitment())
+    }
+
+    pub fn verify(&self, root: &Root<C>) -> bool {
+        self.check(root).is_ok()
+    }
+}
+
+impl<C, P> MimkMerkleTree<C, P>
+where
+    C: SumCommitment,
+    P: ExclusiveAllotmentProof<C>,
+{
+    // Returns None when the position is out of range or the leaf sits deeper than DEPTH
+    pub fn prove_fixed<const DEPTH: usize>(&self, position: usize) -> Option<FixedProof<C, DEPTH>> {
+        if position >= self.leaf_nodes.len() {
+            return None;
+        }
+
+        let mut path: [Option<(C, bool)>; DEPTH] = core::array::from_fn(|_| None);
+        let mut depth = 0;
+        let mut node_index = 0;
+        let mut offset = position;
+        let mut nodes = &self.leaf_nodes[..];
+
+        while nodes.len() > 1 {
+            if depth == DEPTH {
+                return None;
+            }
+            let middle = nodes.len() / 2;
+            if offset < middle {
+                // The position is in the left subtree
+                let right_commitment = self.build_merkle_tree(node_index * 2 + 2, &nodes[middle..]);
+                path[depth] = Some((right_commitment, false));
+                node_index = node_index * 2 + 1;
+                nodes = &nodes[0..middle];
+            } else {
+                // The position is in the right subtree
+                let left_commitment = self.build_merkle_tree(node_index * 2 + 1, &nodes[0..middle]);
+                path[depth] = Some((left_commitment, true));
+                node_index = node_index * 2 + 2;
+                offset -= middle;
+                nodes = &nodes[middle..];
+            }
+            depth += 1;
+        }
+
+        path[..depth].reverse();
+        Some(FixedProof {
+            position,
+            leaf: nodes[0].clone(),
+            path,
+        })
+    }
+}
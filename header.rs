@@ -0,0 +1,429 @@
+use std::fmt;
+use std::marker::PhantomData;
+
+use sha2::{Digest as _, Sha256};
+
+use crate::config::{AmountBinding, HashBackend};
+use crate::encoding::{encode_len, encode_usize, ByteReader};
+use crate::{
+    Digest, ExclusiveAllotmentProof, MimkMerkleTree, ParseError, Root, SumCommitment, VerifyError,
+};
+
+pub const HEADER_VERSION: u8 = 2;
+
+const BOUND_ROOT_DOMAIN: &[u8] = b"mimi-bound-root-v1";
+
+// Define the TreeHeader struct, the metadata a published root is bound to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeHeader {
+    pub version: u8,
+    pub epoch: u64,
+    // Unix seconds at which the liabilities were snapshotted
+    pub timestamp: u64,
+    pub assets: Vec<String>,
+    pub hash_backend: HashBackend,
+    pub amount_binding: AmountBinding,
+    pub arity: usize,
+    pub leaf_count: usize,
+    // Leaves holding accounts, without padding; the padding leaves come last
+    pub account_count: usize,
+}
+
+impl TreeHeader {
+    pub fn for_tree<C, P>(
+        tree: &MimkMerkleTree<C, P>,
+        epoch: u64,
+        timestamp: u64,
+        assets: Vec<String>,
+    ) -> Self
+    where
+        C: SumCommitment,
+        P: ExclusiveAllotmentProof<C>,
+    {
+        TreeHeader {
+            version: HEADER_VERSION,
+            epoch,
+            timestamp,
+            assets,
+            hash_backend: tree.config.hash_backend,
+            amount_binding: tree.config.amount_binding,
+            arity: tree.config.arity,
+            leaf_count: tree.leaf_nodes.len(),
+            account_count: tree.account_count,
+        }
+    }
+
+    // version | epoch | timestamp | hash backend | amount binding | arity | leaf count |
+    // account count | asset count | assets. Integers are little-endian u64 apart from the three
+    // single bytes, and each asset is length-prefixed.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(50);
+        bytes.push(self.version);
+        bytes.extend_from_slice(&self.epoch.to_le_bytes());
+        bytes.extend_from_slice(&self.timestamp.to_le_bytes());
+        bytes.push(backend_id(self.hash_backend));
+        bytes.push(self.amount_binding as u8);
+        bytes.extend_from_slice(&encode_usize(self.arity));
+        bytes.extend_from_slice(&encode_usize(self.leaf_count));
+        bytes.extend_from_slice(&encode_usize(self.account_count));
+        bytes.extend_from_slice(&encode_usize(self.assets.len()));
+        for asset in &self.assets {
+            bytes.extend_from_slice(&encode_len(asset.as_bytes()));
+            bytes.extend_from_slice(asset.as_bytes());
+        }
+        bytes
+    }
+}
+
+impl TryFrom<&[u8]> for TreeHeader {
+    type Error = HeaderError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        let mut reader = ByteReader::new(bytes);
+        let version = reader.take_array::<1>()?[0];
+        if version != HEADER_VERSION {
+            return Err(HeaderError::UnsupportedVersion(version));
+        }
+        let epoch = u64::from_le_bytes(reader.take_array::<8>()?);
+        let timestamp = u64::from_le_bytes(reader.take_array::<8>()?);
+        let hash_backend = match reader.take_array::<1>()?[0] {
+            1 => HashBackend::Sha256,
+            2 => HashBackend::RescuePrime,
+            other => return Err(HeaderError::UnknownHashBackend(other)),
+        };
+        let amount_binding = match reader.take_array::<1>()?[0] {
+            0 => AmountBinding::Bound,
+            1 => AmountBinding::Unbound,
+            other => return Err(HeaderError::UnknownAmountBinding(other)),
+        };
+        let arity = read_usize(&mut reader)?;
+        let leaf_count = read_usize(&mut reader)?;
+        let account_count = read_usize(&mut reader)?;
+        let asset_count = read_usize(&mut reader)?;
+        let mut assets = Vec::new();
+        for _ in 0..asset_count {
+            let len = read_usize(&mut reader)?;
+            let asset =
+                std::str::from_utf8(reader.take(len)?).map_err(|_| HeaderError::InvalidAsset)?;
+            assets.push(asset.to_string());
+        }
+        reader.finish()?;
+        Ok(TreeHeader {
+            version,
+            epoch,
+            timestamp,
+            assets,
+            hash_backend,
+            amount_binding,
+            arity,
+            leaf_count,
+            account_count,
+        })
+    }
+}
+
+pub(crate) fn backend_id(backend: HashBackend) -> u8 {
+    match backend {
+        HashBackend::Sha256 => 1,
+        HashBackend::RescuePrime => 2,
+    }
+}
+
+fn read_usize(reader: &mut ByteReader<'_>) -> Result<usize, ParseError> {
+    usize::try_from(u64::from_le_bytes(reader.take_array::<8>()?))
+        .map_err(|_| ParseError::PositionOverflow)
+}
+
+// Define the BoundRoot struct, a tree root committed together with its header. The bound
+// digest is what gets published, so a proof only verifies against a tree with exactly
+// this configuration, size and epoch.
+#[derive(Debug, Clone)]
+pub struct BoundRoot<C: SumCommitment> {
+    pub header: TreeHeader,
+    pub root: Root<C>,
+}
+
+impl<C: SumCommitment> BoundRoot<C> {
+    pub fn new(header: TreeHeader, root: Root<C>) -> Self {
+        BoundRoot { header, root }
+    }
+
+    // SHA-256(domain || header length || header || root bytes)
+    pub fn digest(&self) -> Digest {
+        let header = self.header.to_bytes();
+        let mut hasher = Sha256::new();
+        hasher.update(BOUND_ROOT_DOMAIN);
+        hasher.update(encode_len(&header));
+        hasher.update(&header);
+        hasher.update(self.root.to_bytes());
+        Digest::from(hasher.finalize())
+    }
+
+    // Checks the header against the published bound digest and against what the
+    // verifier's commitment type can produce
+    pub fn check(&self, published: &Digest) -> Result<(), HeaderError> {
+        if self.header.version != HEADER_VERSION {
+            return Err(HeaderError::UnsupportedVersion(self.header.version));
+        }
+        if self.header.hash_backend != C::HASH_BACKEND {
+            return Err(HeaderError::HashBackendMismatch {
+                expected: C::HASH_BACKEND,
+                found: self.header.hash_backend,
+            });
+        }
+        if self.header.amount_binding != C::AMOUNT_BINDING {
+            return Err(HeaderError::AmountBindingMismatch {
+                expected: C::AMOUNT_BINDING,
+                found: self.header.amount_binding,
+            });
+        }
+        if self.header.account_count > self.header.leaf_count {
+            return Err(HeaderError::AccountCount {
+                accounts: self.header.account_count,
+                leaf_count: self.header.leaf_count,
+            });
+        }
+        if self.digest() != *published {
+            return Err(HeaderError::DigestMismatch);
+        }
+        Ok(())
+    }
+
+    pub fn total(&self) -> u64 {
+        self.root.amount()
+    }
+
+    pub fn account_count(&self) -> usize {
+        self.header.account_count
+    }
+
+    // The proof must also sit inside the declared leaf layer at the depth the declared
+    // leaf count implies for its position
+    pub fn verify_proof<P: ExclusiveAllotmentProof<C>>(
+        &self,
+        published: &Digest,
+        proof: &P,
+    ) -> Result<(), HeaderError> {
+        self.check(published)?;
+        self.check_shape(proof)?;
+        proof.check(&self.root).map_err(HeaderError::Proof)
+    }
+
+    pub fn verify_leaf_count<P: ExclusiveAllotmentProof<C>>(
+        &self,
+        published: &Digest,
+        proof: &LeafCountProof<C, P>,
+    ) -> Result<(), HeaderError> {
+        self.check(published)?;
+        let last = &proof.last;
+        if self.header.leaf_count == 0 || last.position() != self.header.leaf_count - 1 {
+            return Err(HeaderError::NotLastLeaf(last.position()));
+        }
+        self.check_shape(last)?;
+        // Every sibling of the last leaf sits to its left
+        if !last.siblings().iter().all(|(_, on_left)| *on_left) {
+            return Err(HeaderError::NotLastLeaf(last.position()));
+        }
+        last.check(&self.root).map_err(HeaderError::Proof)
+    }
+
+    fn check_shape<P: ExclusiveAllotmentProof<C>>(&self, proof: &P) -> Result<(), HeaderError> {
+        let position = proof.position();
+        let expected =
+            path_len(self.header.leaf_count, position).ok_or(HeaderError::PositionOutOfRange {
+                position,
+                leaf_count: self.header.leaf_count,
+            })?;
+        if proof.siblings().len() != expected {
+            return Err(HeaderError::PathLength {
+                expected,
+                found: proof.siblings().len(),
+            });
+        }
+        Ok(())
+    }
+}
+
+// Define the LeafCountProof struct, the inclusion proof of the tree's last leaf. Its path
+// only fits the shape of a tree with the declared leaf count, so no leaves can hide past
+// it; with every user checking their own path against the header, the account set is
+// bounded as well as its total.
+#[derive(Debug, Clone)]
+pub struct LeafCountProof<C: SumCommitment, P: ExclusiveAllotmentProof<C>> {
+    pub last: P,
+    _commitment: PhantomData<C>,
+}
+
+impl<C: SumCommitment, P: ExclusiveAllotmentProof<C>> LeafCountProof<C, P> {
+    pub fn new(last: P) -> Self {
+        LeafCountProof {
+            last,
+            _commitment: PhantomData,
+        }
+    }
+}
+
+// Siblings on the path to `position` in a tree of `leaf_count` leaves, following the
+// split the tree is built with (the left half takes `len / 2` leaves)
+fn path_len(leaf_count: usize, position: usize) -> Option<usize> {
+    if position >= leaf_count {
+        return None;
+    }
+    let (mut len, mut offset, mut depth) = (leaf_count, position, 0);
+    while len > 1 {
+        let middle = len / 2;
+        if offset < middle {
+            len = middle;
+        } else {
+            offset -= middle;
+            len -= middle;
+        }
+        depth += 1;
+    }
+    Some(depth)
+}
+
+impl<C, P> MimkMerkleTree<C, P>
+where
+    C: SumCommitment,
+    P: ExclusiveAllotmentProof<C>,
+{
+    pub fn commit_bound(&self, epoch: u64, timestamp: u64, assets: Vec<String>) -> BoundRoot<C> {
+        BoundRoot::new(
+            TreeHeader::for_tree(self, epoch, timestamp, assets),
+            self.commit(),
+        )
+    }
+
+    pub fn prove_leaf_count(&self) -> LeafCountProof<C, P> {
+        LeafCountProof::new(self.prove(self.leaf_nodes.len() - 1))
+    }
+}
+
+// Define the HeaderError enum for proofs and roots that don't match their header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HeaderError {
+    UnsupportedVersion(u8),
+    UnknownHashBackend(u8),
+    UnknownAmountBinding(u8),
+    InvalidAsset,
+    HashBackendMismatch {
+        expected: HashBackend,
+        found: HashBackend,
+    },
+    AmountBindingMismatch {
+        expected: AmountBinding,
+        found: AmountBinding,
+    },
+    DigestMismatch,
+    AccountCount {
+        accounts: usize,
+        leaf_count: usize,
+    },
+    NotLastLeaf(usize),
+    PositionOutOfRange {
+        position: usize,
+        leaf_count: usize,
+    },
+    PathLength {
+        expected: usize,
+        found: usize,
+    },
+    Proof(VerifyError),
+    Parse(ParseError),
+}
+
+impl fmt::Display for HeaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HeaderError::UnsupportedVersion(version) => {
+                write!(f, "unsupported tree header version {}", version)
+            }
+            HeaderError::UnknownHashBackend(id) => write!(f, "unknown hash backend {:#04x}", id),
+            HeaderError::UnknownAmountBinding(id) => {
+                write!(f, "unknown amount binding {:#04x}", id)
+            }
+            HeaderError::InvalidAsset => write!(f, "asset identifier is not valid UTF-8"),
+            HeaderError::HashBackendMismatch { expected, found } => write!(
+                f,
+                "header names hash backend {:?} but proofs use {:?}",
+                found, expected
+            ),
+            HeaderError::AmountBindingMismatch { expected, found } => write!(
+                f,
+                "header names amount binding {:?} but proofs use {:?}",
+                found, expected
+            ),
+            HeaderError::DigestMismatch => {
+                write!(f, "header and root don't hash to the published root")
+            }
+            HeaderError::AccountCount {
+                accounts,
+                leaf_count,
+            } => write!(
+                f,
+                "header claims {} accounts in a tree of {} leaves",
+                accounts, leaf_count
+            ),
+            HeaderError::NotLastLeaf(position) => {
+                write!(
+                    f,
+                    "proof for position {} is not for the last leaf",
+                    position
+                )
+            }
+            HeaderError::PositionOutOfRange {
+                position,
+                leaf_count,
+            } => write!(
+                f,
+                "position {} is outside a tree of {} leaves",
+                position, leaf_count
+            ),
+            HeaderError::PathLength { expected, found } => write!(
+                f,
+                "proof has {} siblings but the header implies {}",
+                found, expected
+            ),
+            HeaderError::Proof(err) => write!(f, "{}", err),
+            HeaderError::Parse(err) => write!(f, "malformed tree header: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for HeaderError {}
+
+impl From<ParseError> for HeaderError {
+    fn from(err: ParseError) -> Self {
+        HeaderError::Parse(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MerkleProof, MerkleSumTreeBuilder, MimiSumCommitment, TreeConfig};
+
+    #[test]
+    fn amount_binding_is_encoded_and_checked() {
+        let tree: MimkMerkleTree<MimiSumCommitment, MerkleProof<MimiSumCommitment>> =
+            MerkleSumTreeBuilder::with_config(TreeConfig::default())
+                .build(&[100u64, 200, 300])
+                .unwrap();
+        let bound = tree.commit_bound(1, 0, vec!["BTC".to_string()]);
+        let bytes = bound.header.to_bytes();
+        assert_eq!(TreeHeader::try_from(&bytes[..]).unwrap(), bound.header);
+        bound.check(&bound.digest()).unwrap();
+
+        let mut unbound = bound.clone();
+        unbound.header.amount_binding = AmountBinding::Unbound;
+        assert_ne!(unbound.header.to_bytes(), bytes);
+        assert_eq!(
+            unbound.check(&unbound.digest()),
+            Err(HeaderError::AmountBindingMismatch {
+                expected: AmountBinding::Bound,
+                found: AmountBinding::Unbound,
+            })
+        );
+    }
+}
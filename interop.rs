@@ -0,0 +1,300 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::Deserialize;
+use sha2::{Digest as _, Sha256};
+
+use crate::{
+    ExclusiveAllotmentProof, LeafCommitment, MerkleProof, Root, SumCommitment,
+    UnboundLeafCommitment, UnboundSumCommitment,
+};
+
+// Define the ExchangeNode struct, one node of a third-party proof after parsing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExchangeNode {
+    pub hash: [u8; 32],
+    pub balance: u64,
+}
+
+// Define the ExchangeProof struct, the format-independent shape every adapter parses into
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExchangeProof {
+    pub leaf: ExchangeNode,
+    // Siblings ordered from the leaf up to the root, `true` when the sibling is the left child
+    pub path: Vec<(ExchangeNode, bool)>,
+    pub root: ExchangeNode,
+}
+
+// Define the NodeHashRule trait, how a given operator derives a parent hash from its children
+pub trait NodeHashRule {
+    fn combine(&self, left: &ExchangeNode, right: &ExchangeNode) -> [u8; 32];
+}
+
+// SHA-256 over the raw child digests, the rule this crate's UnboundSumCommitment uses
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RawConcat;
+
+impl NodeHashRule for RawConcat {
+    fn combine(&self, left: &ExchangeNode, right: &ExchangeNode) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(left.hash);
+        hasher.update(right.hash);
+        hasher.finalize().into()
+    }
+}
+
+// SHA-256 over each child digest followed by its little-endian balance, the rule this
+// crate's MimiSumCommitment uses
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AmountConcat;
+
+impl NodeHashRule for AmountConcat {
+    fn combine(&self, left: &ExchangeNode, right: &ExchangeNode) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(left.hash);
+        hasher.update(left.balance.to_le_bytes());
+        hasher.update(right.hash);
+        hasher.update(right.balance.to_le_bytes());
+        hasher.finalize().into()
+    }
+}
+
+// SHA-256 over the lowercase hex strings of both children, as used by verifiers that
+// treat hashes as text
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HexConcat;
+
+impl NodeHashRule for HexConcat {
+    fn combine(&self, left: &ExchangeNode, right: &ExchangeNode) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(hex::encode(left.hash).as_bytes());
+        hasher.update(hex::encode(right.hash).as_bytes());
+        hasher.finalize().into()
+    }
+}
+
+impl ExchangeProof {
+    pub fn verify(&self, rule: &dyn NodeHashRule) -> Result<(), InteropError> {
+        let mut current = self.leaf;
+        for (level, (sibling, sibling_on_left)) in self.path.iter().enumerate() {
+            let (left, right) = if *sibling_on_left {
+                (sibling, &current)
+            } else {
+                (&current, sibling)
+            };
+            let balance = left
+                .balance
+                .checked_add(right.balance)
+                .ok_or(InteropError::BalanceOverflow { level })?;
+            current = ExchangeNode {
+                hash: rule.combine(left, right),
+                balance,
+            };
+        }
+
+        if current.hash != self.root.hash {
+            return Err(InteropError::RootHashMismatch);
+        }
+        if current.balance != self.root.balance {
+            return Err(InteropError::RootBalanceMismatch {
+                expected: self.root.balance,
+                computed: current.balance,
+            });
+        }
+        Ok(())
+    }
+
+    // Position implied by the path directions; exact for the balanced trees exchanges publish
+    pub fn position(&self) -> usize {
+        self.path
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, sibling_on_left))| *sibling_on_left)
+            .map(|(level, _)| 1usize << level)
+            .sum()
+    }
+
+    // Maps the proof onto this crate's types; only meaningful for operators using `RawConcat`
+    pub fn to_merkle_proof(
+        &self,
+    ) -> (MerkleProof<UnboundSumCommitment>, Root<UnboundSumCommitment>) {
+        let leaf = UnboundLeafCommitment::from_parts(self.leaf.balance, self.leaf.hash.into());
+        let siblings = self
+            .path
+            .iter()
+            .map(|(node, sibling_on_left)| {
+                (
+                    UnboundSumCommitment::from_parts(node.balance, node.hash.into()),
+                    *sibling_on_left,
+                )
+            })
+            .collect();
+        let root = UnboundSumCommitment::from_parts(self.root.balance, self.root.hash.into());
+        (
+            MerkleProof::new(self.position(), leaf, siblings),
+            Root::from_node(root),
+        )
+    }
+}
+
+#[derive(Deserialize)]
+struct BinanceNode {
+    hash: String,
+    balance: String,
+}
+
+#[derive(Deserialize)]
+struct BinanceStep {
+    hash: String,
+    balance: String,
+    position: String,
+}
+
+#[derive(Deserialize)]
+struct BinanceProof {
+    leaf: BinanceNode,
+    path: Vec<BinanceStep>,
+    root: BinanceNode,
+}
+
+// Parses the Binance-style layout: a leaf, a bottom-up `path` of siblings tagged with
+// `"position": "left" | "right"`, and the root, with decimal balance strings
+pub fn parse_binance(json: &str, decimals: u32) -> Result<ExchangeProof, InteropError> {
+    let proof: BinanceProof = serde_json::from_str(json).map_err(InteropError::Json)?;
+    let node = |hash: &str, balance: &str| -> Result<ExchangeNode, InteropError> {
+        Ok(ExchangeNode {
+            hash: parse_hash(hash)?,
+            balance: parse_units(balance, decimals)?,
+        })
+    };
+
+    let path = proof
+        .path
+        .iter()
+        .map(|step| {
+            let sibling_on_left = match step.position.as_str() {
+                "left" => true,
+                "right" => false,
+                other => return Err(InteropError::InvalidField(other.to_string())),
+            };
+            Ok((node(&step.hash, &step.balance)?, sibling_on_left))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(ExchangeProof {
+        leaf: node(&proof.leaf.hash, &proof.leaf.balance)?,
+        path,
+        root: node(&proof.root.hash, &proof.root.balance)?,
+    })
+}
+
+#[derive(Deserialize)]
+struct OkxNode {
+    hash: String,
+    #[serde(rename = "type")]
+    node_type: u8,
+    height: u32,
+    balances: BTreeMap<String, String>,
+}
+
+// Parses the OKX-style layout: a flat list of nodes with a `type` (1 = the user's leaf,
+// 2 = left sibling, 3 = right sibling, 4 = root), a `height`, and per-asset balances
+pub fn parse_okx(json: &str, asset: &str, decimals: u32) -> Result<ExchangeProof, InteropError> {
+    let mut nodes: Vec<OkxNode> = serde_json::from_str(json).map_err(InteropError::Json)?;
+    nodes.sort_by_key(|node| node.height);
+
+    let mut leaf = None;
+    let mut root = None;
+    let mut path = Vec::new();
+    for node in &nodes {
+        let balance = match node.balances.get(asset) {
+            Some(balance) => parse_units(balance, decimals)?,
+            None => 0,
+        };
+        let parsed = ExchangeNode {
+            hash: parse_hash(&node.hash)?,
+            balance,
+        };
+        match node.node_type {
+            1 if leaf.is_none() => leaf = Some(parsed),
+            2 => path.push((parsed, true)),
+            3 => path.push((parsed, false)),
+            4 if root.is_none() => root = Some(parsed),
+            other => return Err(InteropError::InvalidField(format!("node type {}", other))),
+        }
+    }
+
+    Ok(ExchangeProof {
+        leaf: leaf.ok_or(InteropError::MissingNode("leaf"))?,
+        path,
+        root: root.ok_or(InteropError::MissingNode("root"))?,
+    })
+}
+
+fn parse_hash(hash: &str) -> Result<[u8; 32], InteropError> {
+    let bytes = hex::decode(hash.trim_start_matches("0x"))
+        .map_err(|_| InteropError::InvalidField(hash.to_string()))?;
+    bytes
+        .try_into()
+        .map_err(|_| InteropError::InvalidField(hash.to_string()))
+}
+
+// Converts a decimal string such as "12.5" into integer units with `decimals` fraction digits
+pub fn parse_units(value: &str, decimals: u32) -> Result<u64, InteropError> {
+    let invalid = || InteropError::InvalidAmount(value.to_string());
+    let (whole, fraction) = value.split_once('.').unwrap_or((value, ""));
+    let all_digits = |part: &str| part.bytes().all(|byte| byte.is_ascii_digit());
+    if whole.is_empty() || !all_digits(whole) || !all_digits(fraction) {
+        return Err(invalid());
+    }
+    if fraction.len() > decimals as usize {
+        return Err(invalid());
+    }
+
+    let scale = 10u64.checked_pow(decimals).ok_or_else(invalid)?;
+    let whole: u64 = whole.parse().map_err(|_| invalid())?;
+    let fraction_scale = 10u64.pow(decimals - fraction.len() as u32);
+    let fraction: u64 = if fraction.is_empty() {
+        0
+    } else {
+        fraction.parse().map_err(|_| invalid())?
+    };
+    whole
+        .checked_mul(scale)
+        .and_then(|units| units.checked_add(fraction * fraction_scale))
+        .ok_or_else(invalid)
+}
+
+// Define the InteropError enum for third-party proofs that can't be parsed or don't verify
+#[derive(Debug)]
+pub enum InteropError {
+    Json(serde_json::Error),
+    InvalidField(String),
+    InvalidAmount(String),
+    MissingNode(&'static str),
+    BalanceOverflow { level: usize },
+    RootHashMismatch,
+    RootBalanceMismatch { expected: u64, computed: u64 },
+}
+
+impl fmt::Display for InteropError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InteropError::Json(err) => write!(f, "invalid proof json: {}", err),
+            InteropError::InvalidField(field) => write!(f, "invalid field value `{}`", field),
+            InteropError::InvalidAmount(amount) => write!(f, "invalid amount `{}`", amount),
+            InteropError::MissingNode(node) => write!(f, "proof has no {} node", node),
+            InteropError::BalanceOverflow { level } => {
+                write!(f, "balance overflows at level {}", level)
+            }
+            InteropError::RootHashMismatch => write!(f, "reconstructed root hash does not match"),
+            InteropError::RootBalanceMismatch { expected, computed } => write!(
+                f,
+                "root balance is {} but the path sums to {}",
+                expected, computed
+            ),
+        }
+    }
+}
+
+impl std::error::Error for InteropError {}
@@ -0,0 +1,208 @@
+use std::fmt;
+
+use crate::{
+    AmountBinding, ExclusiveAllotmentProof, LeafCommitment, MimkMerkleTree, SumCommitment,
+};
+
+// Define the InvariantViolation enum, naming which reference property a tree broke
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InvariantViolation {
+    RootSumMismatch {
+        root: u64,
+        leaves: Option<u64>,
+    },
+    ProofRejected {
+        position: usize,
+    },
+    MutatedProofAccepted {
+        position: usize,
+        mutation: &'static str,
+    },
+}
+
+impl fmt::Display for InvariantViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvariantViolation::RootSumMismatch { root, leaves } => match leaves {
+                Some(leaves) => write!(f, "root amount {} differs from leaf sum {}", root, leaves),
+                None => write!(f, "leaf sum overflows, root amount is {}", root),
+            },
+            InvariantViolation::ProofRejected { position } => {
+                write!(f, "proof for position {} does not verify", position)
+            }
+            InvariantViolation::MutatedProofAccepted { position, mutation } => write!(
+                f,
+                "proof for position {} still verifies after {}",
+                position, mutation
+            ),
+        }
+    }
+}
+
+impl std::error::Error for InvariantViolation {}
+
+pub fn root_sum_equals_leaf_sum<C, P>(tree: &MimkMerkleTree<C, P>) -> Result<(), InvariantViolation>
+where
+    C: SumCommitment,
+    P: ExclusiveAllotmentProof<C>,
+{
+    let root = tree.commit().amount();
+    let leaves = tree
+        .iter_leaves()
+        .try_fold(0u64, |sum, leaf| sum.checked_add(leaf.amount()));
+    if leaves == Some(root) {
+        Ok(())
+    } else {
+        Err(InvariantViolation::RootSumMismatch { root, leaves })
+    }
+}
+
+pub fn every_proof_verifies<C, P>(tree: &MimkMerkleTree<C, P>) -> Result<(), InvariantViolation>
+where
+    C: SumCommitment,
+    P: ExclusiveAllotmentProof<C>,
+{
+    let root = tree.commit();
+    for position in 0..tree.len() {
+        if !tree.prove(position).verify(&root) {
+            return Err(InvariantViolation::ProofRejected { position });
+        }
+    }
+    Ok(())
+}
+
+// Tampers with the leaf amount, the leaf digest and every sibling digest in turn. Under
+// bound commitments it also moves one unit between the leaf and each sibling, which keeps
+// the root's total and so must be caught by the digests.
+pub fn mutated_proofs_fail<C, P>(tree: &MimkMerkleTree<C, P>) -> Result<(), InvariantViolation>
+where
+    C: SumCommitment,
+    P: ExclusiveAllotmentProof<C>,
+{
+    let root = tree.commit();
+    for position in 0..tree.len() {
+        let proof = tree.prove(position);
+        let leaf = proof.leaf();
+        let siblings = proof.siblings().to_vec();
+        let rejected = |mutated: P, mutation| {
+            if mutated.verify(&root) {
+                Err(InvariantViolation::MutatedProofAccepted { position, mutation })
+            } else {
+                Ok(())
+            }
+        };
+
+        let inflated = C::Leaf::from_parts(leaf.amount().wrapping_add(1), leaf.digest());
+        rejected(
+            P::new(position, inflated, siblings.clone()),
+            "inflating the leaf amount",
+        )?;
+
+        let forged = C::Leaf::from_parts(leaf.amount(), flip_first_byte(leaf.digest()));
+        rejected(
+            P::new(position, forged, siblings.clone()),
+            "altering the leaf digest",
+        )?;
+
+        for index in 0..siblings.len() {
+            let mut tampered = siblings.clone();
+            let (sibling, sibling_on_left) = &tampered[index];
+            let forged = C::from_parts(sibling.amount(), flip_first_byte(sibling.digest()));
+            tampered[index] = (forged, *sibling_on_left);
+            rejected(
+                P::new(position, leaf.clone(), tampered),
+                "altering a sibling digest",
+            )?;
+
+            if C::AMOUNT_BINDING != AmountBinding::Bound {
+                continue;
+            }
+            let (sibling, sibling_on_left) = &siblings[index];
+            let (leaf_amount, sibling_amount) = if sibling.amount() > 0 {
+                (leaf.amount().checked_add(1), Some(sibling.amount() - 1))
+            } else {
+                (
+                    leaf.amount().checked_sub(1),
+                    sibling.amount().checked_add(1),
+                )
+            };
+            let (Some(leaf_amount), Some(sibling_amount)) = (leaf_amount, sibling_amount) else {
+                continue;
+            };
+            let mut shifted = siblings.clone();
+            shifted[index] = (
+                C::from_parts(sibling_amount, sibling.digest()),
+                *sibling_on_left,
+            );
+            rejected(
+                P::new(
+                    position,
+                    C::Leaf::from_parts(leaf_amount, leaf.digest()),
+                    shifted,
+                ),
+                "moving value between a sibling and the leaf",
+            )?;
+        }
+    }
+    Ok(())
+}
+
+pub fn check_all<C, P>(tree: &MimkMerkleTree<C, P>) -> Result<(), InvariantViolation>
+where
+    C: SumCommitment,
+    P: ExclusiveAllotmentProof<C>,
+{
+    root_sum_equals_leaf_sum(tree)?;
+    every_proof_verifies(tree)?;
+    mutated_proofs_fail(tree)
+}
+
+fn flip_first_byte<D: AsMut<[u8]>>(mut digest: D) -> D {
+    digest.as_mut()[0] ^= 0x01;
+    digest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        MerkleProof, MerkleSumTreeBuilder, MimiSumCommitment, TreeConfig, UnboundSumCommitment,
+    };
+
+    #[test]
+    fn reference_trees_pass_every_invariant() {
+        let leaves = [100u64, 0, 300, 400, 500];
+        let bound: MimkMerkleTree<MimiSumCommitment, MerkleProof<MimiSumCommitment>> =
+            MerkleSumTreeBuilder::new().build(&leaves).unwrap();
+        check_all(&bound).unwrap();
+    }
+
+    #[test]
+    fn unbound_trees_accept_value_moved_between_siblings() {
+        let leaves = [100u64, 200, 300, 400];
+        let tree: MimkMerkleTree<UnboundSumCommitment, MerkleProof<UnboundSumCommitment>> =
+            MerkleSumTreeBuilder::with_config(TreeConfig {
+                amount_binding: AmountBinding::Unbound,
+                ..TreeConfig::default()
+            })
+            .build(&leaves)
+            .unwrap();
+        check_all(&tree).unwrap();
+
+        // The reason the shift is only checked under bound commitments
+        let proof = tree.prove(0);
+        let mut siblings = proof.siblings().to_vec();
+        let (sibling, on_left) = siblings[0].clone();
+        siblings[0] = (
+            UnboundSumCommitment::from_parts(sibling.amount() - 1, sibling.digest()),
+            on_left,
+        );
+        let leaf = proof.leaf();
+        let shifted = <MerkleProof<UnboundSumCommitment>>::new(
+            0,
+            LeafCommitment::from_parts(leaf.amount() + 1, leaf.digest()),
+            siblings,
+        );
+        assert!(shifted.verify(&tree.commit()));
+    }
+}
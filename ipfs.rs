@@ -0,0 +1,309 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+
+use sha2::{Digest as _, Sha256};
+
+use crate::{Root, SumCommitment};
+
+// Multicodec codes of the two block kinds a file DAG is made of
+const RAW_CODEC: u64 = 0x55;
+const DAG_PB_CODEC: u64 = 0x70;
+const SHA2_256: u64 = 0x12;
+
+// `ipfs add --cid-version=1` defaults: 256 KiB chunks, raw leaves, balanced layout with at
+// most 174 links per node. Matching them means a CID computed here is the one a node
+// reports for the same file, so mirrors can be checked without trusting the gateway.
+const CHUNK_SIZE: usize = 256 * 1024;
+const MAX_LINKS: usize = 174;
+
+// Define the Cid struct, a CIDv1 with a SHA-256 multihash. Prints as base32 with the `b`
+// multibase prefix, the form gateways and `ipfs` commands use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Cid {
+    codec: u64,
+    digest: [u8; 32],
+}
+
+impl Cid {
+    // CID of a single raw block, e.g. an encoded root
+    pub fn raw(bytes: &[u8]) -> Self {
+        Cid {
+            codec: RAW_CODEC,
+            digest: Sha256::digest(bytes).into(),
+        }
+    }
+
+    fn dag_pb(block: &[u8]) -> Self {
+        Cid {
+            codec: DAG_PB_CODEC,
+            digest: Sha256::digest(block).into(),
+        }
+    }
+
+    // The root is published as its 40-byte encoding
+    pub fn of_root<C: SumCommitment>(root: &Root<C>) -> Self {
+        Cid::raw(&root.to_bytes())
+    }
+
+    // Same CID as `ipfs add --cid-version=1` gives the file. Reads in chunks, so snapshots
+    // and archives never have to fit in memory.
+    pub fn of_reader(mut reader: impl Read) -> io::Result<Self> {
+        let mut leaves = Vec::new();
+        loop {
+            let chunk = read_chunk(&mut reader)?;
+            if chunk.is_empty() && !leaves.is_empty() {
+                break;
+            }
+            let size = chunk.len() as u64;
+            leaves.push(DagNode {
+                cid: Cid::raw(&chunk),
+                file_size: size,
+                tree_size: size,
+            });
+            if chunk.len() < CHUNK_SIZE {
+                break;
+            }
+        }
+        let mut level = leaves;
+        while level.len() > 1 {
+            level = level.chunks(MAX_LINKS).map(DagNode::parent).collect();
+        }
+        Ok(level[0].cid)
+    }
+
+    pub fn of_bytes(bytes: &[u8]) -> Self {
+        Cid::of_reader(bytes).expect("reading from memory can't fail")
+    }
+
+    pub fn of_file(path: impl AsRef<Path>) -> io::Result<Self> {
+        Cid::of_reader(BufReader::new(File::open(path)?))
+    }
+
+    // version | codec | multihash (hash code, length, digest), all varints
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(36);
+        put_varint(&mut bytes, 1);
+        put_varint(&mut bytes, self.codec);
+        put_varint(&mut bytes, SHA2_256);
+        put_varint(&mut bytes, 32);
+        bytes.extend_from_slice(&self.digest);
+        bytes
+    }
+}
+
+impl fmt::Display for Cid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "b{}", base32(&self.to_bytes()))
+    }
+}
+
+// Fills a whole chunk unless the input ends first
+fn read_chunk(reader: &mut impl Read) -> io::Result<Vec<u8>> {
+    let mut chunk = Vec::with_capacity(CHUNK_SIZE);
+    reader.take(CHUNK_SIZE as u64).read_to_end(&mut chunk)?;
+    Ok(chunk)
+}
+
+// One node of the file DAG: its CID, the file bytes below it and the encoded size of
+// every block below it (the `Tsize` parents record)
+struct DagNode {
+    cid: Cid,
+    file_size: u64,
+    tree_size: u64,
+}
+
+impl DagNode {
+    // dag-pb node with UnixFS file data: links first, then the data field
+    fn parent(children: &[DagNode]) -> DagNode {
+        let mut unixfs = Vec::new();
+        put_field(&mut unixfs, 1, 0);
+        put_varint(&mut unixfs, 2);
+        let file_size = children.iter().map(|child| child.file_size).sum();
+        put_field(&mut unixfs, 3, 0);
+        put_varint(&mut unixfs, file_size);
+        for child in children {
+            put_field(&mut unixfs, 4, 0);
+            put_varint(&mut unixfs, child.file_size);
+        }
+
+        let mut block = Vec::new();
+        for child in children {
+            let mut link = Vec::new();
+            put_bytes(&mut link, 1, &child.cid.to_bytes());
+            put_bytes(&mut link, 2, b"");
+            put_field(&mut link, 3, 0);
+            put_varint(&mut link, child.tree_size);
+            put_bytes(&mut block, 2, &link);
+        }
+        put_bytes(&mut block, 1, &unixfs);
+
+        let below: u64 = children.iter().map(|child| child.tree_size).sum();
+        DagNode {
+            cid: Cid::dag_pb(&block),
+            file_size,
+            tree_size: block.len() as u64 + below,
+        }
+    }
+}
+
+fn put_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+// Protobuf field key
+fn put_field(out: &mut Vec<u8>, number: u64, wire_type: u64) {
+    put_varint(out, (number << 3) | wire_type);
+}
+
+fn put_bytes(out: &mut Vec<u8>, number: u64, bytes: &[u8]) {
+    put_field(out, number, 2);
+    put_varint(out, bytes.len() as u64);
+    out.extend_from_slice(bytes);
+}
+
+// RFC 4648 base32, lowercase and unpadded as multibase `b` requires
+fn base32(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 32] = b"abcdefghijklmnopqrstuvwxyz234567";
+    let mut text = String::with_capacity(bytes.len() * 8 / 5 + 1);
+    let (mut buffer, mut bits) = (0u16, 0);
+    for &byte in bytes {
+        buffer = (buffer << 8) | byte as u16;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            text.push(ALPHABET[(buffer >> bits) as usize & 31] as char);
+        }
+    }
+    if bits > 0 {
+        text.push(ALPHABET[(buffer << (5 - bits)) as usize & 31] as char);
+    }
+    text
+}
+
+#[cfg(feature = "ipfs")]
+pub use publisher::IpfsPublisher;
+
+#[cfg(feature = "ipfs")]
+mod publisher {
+    use std::path::Path;
+
+    use super::{Cid, IpfsError};
+    use crate::{Root, SumCommitment};
+
+    const BOUNDARY: &str = "mimi-ipfs-boundary";
+
+    // Define the IpfsPublisher struct, which pins artifacts to a node through its RPC API
+    // (e.g. `http://127.0.0.1:5001`). Every CID the node reports is checked against the
+    // one computed locally, so a node with different import settings can't publish
+    // something mirrors would fail to match.
+    #[derive(Debug, Clone)]
+    pub struct IpfsPublisher {
+        api: String,
+    }
+
+    impl IpfsPublisher {
+        pub fn new(api: impl Into<String>) -> Self {
+            IpfsPublisher {
+                api: api.into().trim_end_matches('/').to_string(),
+            }
+        }
+
+        // Adds and pins the bytes as a file named `name`
+        pub fn add(&self, name: &str, bytes: &[u8]) -> Result<Cid, IpfsError> {
+            let local = Cid::of_bytes(bytes);
+            let mut body = format!(
+                "--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{}\"\r\n\
+                 Content-Type: application/octet-stream\r\n\r\n",
+                BOUNDARY, name
+            )
+            .into_bytes();
+            body.extend_from_slice(bytes);
+            body.extend_from_slice(format!("\r\n--{}--\r\n", BOUNDARY).as_bytes());
+
+            let url = format!(
+                "{}/api/v0/add?cid-version=1&raw-leaves=true&chunker=size-262144&pin=true",
+                self.api
+            );
+            let response = ureq::post(&url)
+                .set(
+                    "Content-Type",
+                    &format!("multipart/form-data; boundary={}", BOUNDARY),
+                )
+                .send_bytes(&body)
+                .map_err(|err| IpfsError::Http(err.to_string()))?
+                .into_string()
+                .map_err(IpfsError::Io)?;
+            let reported = serde_json::from_str::<serde_json::Value>(&response)
+                .ok()
+                .and_then(|json| json.get("Hash")?.as_str().map(str::to_string))
+                .ok_or_else(|| IpfsError::Response(response.clone()))?;
+            if reported != local.to_string() {
+                return Err(IpfsError::CidMismatch {
+                    local: local.to_string(),
+                    node: reported,
+                });
+            }
+            Ok(local)
+        }
+
+        pub fn add_file(&self, path: impl AsRef<Path>) -> Result<Cid, IpfsError> {
+            let path = path.as_ref();
+            let bytes = std::fs::read(path).map_err(IpfsError::Io)?;
+            let name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            self.add(&name, &bytes)
+        }
+
+        pub fn add_root<C: SumCommitment>(
+            &self,
+            epoch: u64,
+            root: &Root<C>,
+        ) -> Result<Cid, IpfsError> {
+            self.add(&format!("epoch-{}.root", epoch), &root.to_bytes())
+        }
+
+        // Pins content another node already added, e.g. when mirroring an operator
+        pub fn pin(&self, cid: &Cid) -> Result<(), IpfsError> {
+            let url = format!("{}/api/v0/pin/add?arg={}", self.api, cid);
+            ureq::post(&url)
+                .call()
+                .map_err(|err| IpfsError::Http(err.to_string()))?;
+            Ok(())
+        }
+    }
+}
+
+// Define the IpfsError enum for artifacts that can't be published to a node
+#[derive(Debug)]
+pub enum IpfsError {
+    Io(io::Error),
+    Http(String),
+    // The node answered with something that isn't an add result
+    Response(String),
+    CidMismatch { local: String, node: String },
+}
+
+impl fmt::Display for IpfsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IpfsError::Io(err) => write!(f, "i/o error: {}", err),
+            IpfsError::Http(reason) => write!(f, "ipfs node request failed: {}", reason),
+            IpfsError::Response(body) => write!(f, "unexpected ipfs node response: {}", body),
+            IpfsError::CidMismatch { local, node } => write!(
+                f,
+                "ipfs node reported {} but the content hashes to {}",
+                node, local
+            ),
+        }
+    }
+}
+
+impl std::error::Error for IpfsError {}
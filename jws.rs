@@ -0,0 +1,233 @@
+use std::fmt;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL;
+use base64::Engine;
+use ed25519_dalek::{Signature, VerifyingKey};
+use serde::{Deserialize, Serialize};
+
+use crate::bundle::{BundleError, ProofBundle, SignedRoot, VerificationPolicy};
+use crate::signer::{Signer, SignerError};
+use crate::{ExclusiveAllotmentProof, MerkleProof, ParseError, Root, SumCommitment};
+
+// RFC 8037 name for Ed25519 signatures
+const JWS_ALG: &str = "EdDSA";
+pub const ROOT_TYPE: &str = "mimi-root+jws";
+pub const PROOF_TYPE: &str = "mimi-proof+jws";
+
+// Define the JwsHeader struct, the protected header of every token. `kid` is the hex
+// operator key, so a verifier holding a key ring can pick the right one.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JwsHeader {
+    pub alg: String,
+    pub typ: String,
+    pub kid: String,
+}
+
+// Define the RootClaims struct, the payload of a root token. `signature` is the hex
+// operator signature of `SignedRoot`, kept so our own verifier can recheck it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RootClaims {
+    pub epoch: u64,
+    // `<amount>:<hex digest>`
+    pub root: String,
+    pub signature: String,
+}
+
+// Define the ProofClaims struct, a root token's claims plus one user's inclusion proof.
+// `proof` is base64url of `MerkleProof::to_bytes`; timestamp tokens aren't carried.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProofClaims {
+    #[serde(flatten)]
+    pub root: RootClaims,
+    pub position: usize,
+    pub proof: String,
+}
+
+impl RootClaims {
+    fn from_signed_root<C: SumCommitment>(signed_root: &SignedRoot<C>) -> Self {
+        RootClaims {
+            epoch: signed_root.epoch,
+            root: signed_root.root.to_string(),
+            signature: hex::encode(signed_root.signature.to_bytes()),
+        }
+    }
+
+    pub fn to_signed_root<C: SumCommitment>(&self) -> Result<SignedRoot<C>, JwsError> {
+        let root: Root<C> = self.root.parse()?;
+        let signature: [u8; 64] = hex::decode(&self.signature)
+            .map_err(|_| ParseError::InvalidHex)?
+            .try_into()
+            .map_err(|bytes: Vec<u8>| ParseError::InvalidLength {
+                expected: 64,
+                found: bytes.len(),
+            })?;
+        Ok(SignedRoot {
+            epoch: self.epoch,
+            root,
+            signature: Signature::from_bytes(&signature),
+        })
+    }
+}
+
+// Compact JWS of a signed root, signed by the operator key
+pub fn root_to_jws<C: SumCommitment>(
+    signed_root: &SignedRoot<C>,
+    signer: &dyn Signer,
+) -> Result<String, JwsError> {
+    encode(ROOT_TYPE, &RootClaims::from_signed_root(signed_root), signer)
+}
+
+// Compact JWS of a proof bundle, signed by the operator key
+pub fn bundle_to_jws<C, P>(
+    bundle: &ProofBundle<C, P>,
+    signer: &dyn Signer,
+) -> Result<String, JwsError>
+where
+    C: SumCommitment,
+    P: ExclusiveAllotmentProof<C>,
+{
+    let proof = MerkleProof::<C>::new(
+        bundle.proof.position(),
+        bundle.proof.leaf().clone(),
+        bundle.proof.siblings().to_vec(),
+    );
+    let claims = ProofClaims {
+        root: RootClaims::from_signed_root(&bundle.signed_root),
+        position: bundle.proof.position(),
+        proof: BASE64_URL.encode(proof.to_bytes()),
+    };
+    encode(PROOF_TYPE, &claims, signer)
+}
+
+// Checks the JWS signature and the root signature inside it
+pub fn root_from_jws<C: SumCommitment>(
+    token: &str,
+    operator_key: &VerifyingKey,
+) -> Result<SignedRoot<C>, JwsError> {
+    let claims: RootClaims = decode(token, ROOT_TYPE, operator_key)?;
+    let signed_root = claims.to_signed_root()?;
+    signed_root.verify(operator_key).map_err(JwsError::Bundle)?;
+    Ok(signed_root)
+}
+
+// Checks the JWS signature, then the bundle as `ProofBundle::verify` would without a TSA
+pub fn bundle_from_jws<C: SumCommitment>(
+    token: &str,
+    operator_key: &VerifyingKey,
+) -> Result<ProofBundle<C, MerkleProof<C>>, JwsError> {
+    let claims: ProofClaims = decode(token, PROOF_TYPE, operator_key)?;
+    let bytes = BASE64_URL
+        .decode(&claims.proof)
+        .map_err(|_| ParseError::InvalidBase64)?;
+    let proof = MerkleProof::<C>::try_from(bytes.as_slice())?;
+    if proof.position() != claims.position {
+        return Err(JwsError::PositionMismatch);
+    }
+    let bundle = ProofBundle::new(proof, claims.root.to_signed_root()?);
+    bundle
+        .verify(&VerificationPolicy::new(*operator_key))
+        .map_err(JwsError::Bundle)?;
+    Ok(bundle)
+}
+
+fn encode<T: Serialize>(typ: &str, claims: &T, signer: &dyn Signer) -> Result<String, JwsError> {
+    let header = JwsHeader {
+        alg: JWS_ALG.to_string(),
+        typ: typ.to_string(),
+        kid: hex::encode(signer.verifying_key()?.to_bytes()),
+    };
+    let signing_input = format!(
+        "{}.{}",
+        BASE64_URL.encode(serde_json::to_vec(&header).map_err(JwsError::Json)?),
+        BASE64_URL.encode(serde_json::to_vec(claims).map_err(JwsError::Json)?)
+    );
+    let signature = signer.sign(signing_input.as_bytes())?;
+    Ok(format!(
+        "{}.{}",
+        signing_input,
+        BASE64_URL.encode(signature.to_bytes())
+    ))
+}
+
+fn decode<T: for<'de> Deserialize<'de>>(
+    token: &str,
+    typ: &str,
+    operator_key: &VerifyingKey,
+) -> Result<T, JwsError> {
+    let mut parts = token.trim().split('.');
+    let (Some(header), Some(payload), Some(signature), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(JwsError::NotCompact);
+    };
+    let header_json = BASE64_URL
+        .decode(header)
+        .map_err(|_| ParseError::InvalidBase64)?;
+    let parsed: JwsHeader = serde_json::from_slice(&header_json).map_err(JwsError::Json)?;
+    // Only EdDSA is accepted, whatever the header asks for
+    if parsed.alg != JWS_ALG {
+        return Err(JwsError::UnsupportedAlgorithm(parsed.alg));
+    }
+    if parsed.typ != typ {
+        return Err(JwsError::WrongType(parsed.typ));
+    }
+
+    let signature: [u8; 64] = BASE64_URL
+        .decode(signature)
+        .map_err(|_| ParseError::InvalidBase64)?
+        .try_into()
+        .map_err(|_| JwsError::BadSignature)?;
+    let signing_input = &token.trim()[..header.len() + 1 + payload.len()];
+    operator_key
+        .verify_strict(signing_input.as_bytes(), &Signature::from_bytes(&signature))
+        .map_err(|_| JwsError::BadSignature)?;
+
+    let payload = BASE64_URL
+        .decode(payload)
+        .map_err(|_| ParseError::InvalidBase64)?;
+    serde_json::from_slice(&payload).map_err(JwsError::Json)
+}
+
+// Define the JwsError enum for tokens that can't be produced, parsed or trusted
+#[derive(Debug)]
+pub enum JwsError {
+    NotCompact,
+    UnsupportedAlgorithm(String),
+    WrongType(String),
+    BadSignature,
+    PositionMismatch,
+    Json(serde_json::Error),
+    Parse(ParseError),
+    Signer(SignerError),
+    Bundle(BundleError),
+}
+
+impl fmt::Display for JwsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JwsError::NotCompact => write!(f, "not a compact JWS"),
+            JwsError::UnsupportedAlgorithm(alg) => write!(f, "unsupported JWS algorithm {}", alg),
+            JwsError::WrongType(typ) => write!(f, "unexpected JWS type {}", typ),
+            JwsError::BadSignature => write!(f, "JWS signature does not verify"),
+            JwsError::PositionMismatch => write!(f, "proof position differs from the claims"),
+            JwsError::Json(err) => write!(f, "json error: {}", err),
+            JwsError::Parse(err) => write!(f, "malformed JWS payload: {}", err),
+            JwsError::Signer(err) => write!(f, "{}", err),
+            JwsError::Bundle(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for JwsError {}
+
+impl From<ParseError> for JwsError {
+    fn from(err: ParseError) -> Self {
+        JwsError::Parse(err)
+    }
+}
+
+impl From<SignerError> for JwsError {
+    fn from(err: SignerError) -> Self {
+        JwsError::Signer(err)
+    }
+}
@@ -0,0 +1,516 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use ed25519_dalek::{SigningKey, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use crate::bundle::SignedRoot;
+use crate::entropy::EntropySource;
+use crate::SumCommitment;
+
+const KEYSTORE_VERSION: u32 = 1;
+const ALGORITHM_ED25519: &str = "ed25519";
+const KDF_ARGON2ID: &str = "argon2id";
+const CIPHER_XCHACHA: &str = "xchacha20poly1305";
+
+pub fn generate(entropy: &mut impl EntropySource) -> SigningKey {
+    let seed = Zeroizing::new(entropy.seed());
+    SigningKey::from_bytes(&seed)
+}
+
+// Define the KdfParams struct, the Argon2id cost a keystore passphrase is stretched with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KdfParams {
+    // Memory in KiB
+    pub m_cost: u32,
+    pub t_cost: u32,
+    pub p_cost: u32,
+}
+
+impl Default for KdfParams {
+    // 64 MiB and three passes; raise for keys that are rarely unlocked
+    fn default() -> Self {
+        KdfParams {
+            m_cost: 64 * 1024,
+            t_cost: 3,
+            p_cost: 1,
+        }
+    }
+}
+
+// Define the KdfSpec struct, the KDF section of a keystore file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KdfSpec {
+    pub name: String,
+    #[serde(flatten)]
+    pub params: KdfParams,
+    pub salt: String,
+}
+
+// Define the Keystore struct, a signing key encrypted under a passphrase. The public
+// key is stored in the clear and authenticated with the ciphertext, so the file can be
+// identified without the passphrase and can't be paired with a different key. Ed25519
+// keys are always supported, BLS12-381 keys with the `bls` feature.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Keystore {
+    pub version: u32,
+    pub algorithm: String,
+    pub public_key: String,
+    pub kdf: KdfSpec,
+    pub cipher: String,
+    pub nonce: String,
+    pub ciphertext: String,
+}
+
+impl Keystore {
+    pub fn encrypt(
+        key: &SigningKey,
+        passphrase: &[u8],
+        params: KdfParams,
+        entropy: &mut impl EntropySource,
+    ) -> Result<Self, KeyError> {
+        Self::seal(
+            ALGORITHM_ED25519,
+            key.as_bytes(),
+            &key.verifying_key().to_bytes(),
+            passphrase,
+            params,
+            entropy,
+        )
+    }
+
+    pub fn decrypt(&self, passphrase: &[u8]) -> Result<SigningKey, KeyError> {
+        let secret = self.open(ALGORITHM_ED25519, passphrase)?;
+        let public_key = self.verifying_key()?;
+        let secret: &[u8; 32] = secret
+            .as_slice()
+            .try_into()
+            .map_err(|_| KeyError::Format("secret key must be 32 bytes".to_string()))?;
+        let key = SigningKey::from_bytes(secret);
+        if key.verifying_key() != public_key {
+            return Err(KeyError::KeyMismatch);
+        }
+        Ok(key)
+    }
+
+    // Encrypts `secret` with `public_key` as associated data
+    fn seal(
+        algorithm: &str,
+        secret: &[u8],
+        public_key: &[u8],
+        passphrase: &[u8],
+        params: KdfParams,
+        entropy: &mut impl EntropySource,
+    ) -> Result<Self, KeyError> {
+        let mut salt = [0u8; 16];
+        entropy.fill_bytes(&mut salt);
+        let mut nonce = [0u8; 24];
+        entropy.fill_bytes(&mut nonce);
+
+        let cipher = keystore_cipher(passphrase, &salt, params)?;
+        let ciphertext = cipher
+            .encrypt(
+                XNonce::from_slice(&nonce),
+                Payload {
+                    msg: secret,
+                    aad: public_key,
+                },
+            )
+            .map_err(|_| KeyError::Encryption)?;
+
+        Ok(Keystore {
+            version: KEYSTORE_VERSION,
+            algorithm: algorithm.to_string(),
+            public_key: hex::encode(public_key),
+            kdf: KdfSpec {
+                name: KDF_ARGON2ID.to_string(),
+                params,
+                salt: hex::encode(salt),
+            },
+            cipher: CIPHER_XCHACHA.to_string(),
+            nonce: hex::encode(nonce),
+            ciphertext: hex::encode(ciphertext),
+        })
+    }
+
+    // The decrypted secret, still to be checked against the public key by the caller
+    fn open(&self, algorithm: &str, passphrase: &[u8]) -> Result<Zeroizing<Vec<u8>>, KeyError> {
+        self.check_format(algorithm)?;
+        let public_key = decode_hex(&self.public_key)?;
+        let salt = decode_hex(&self.kdf.salt)?;
+        let nonce = decode_hex(&self.nonce)?;
+        if nonce.len() != 24 {
+            return Err(KeyError::Format("nonce must be 24 bytes".to_string()));
+        }
+        let ciphertext = decode_hex(&self.ciphertext)?;
+
+        let cipher = keystore_cipher(passphrase, &salt, self.kdf.params)?;
+        let secret = cipher
+            .decrypt(
+                XNonce::from_slice(&nonce),
+                Payload {
+                    msg: &ciphertext,
+                    aad: &public_key,
+                },
+            )
+            .map_err(|_| KeyError::WrongPassphrase)?;
+        Ok(Zeroizing::new(secret))
+    }
+
+    pub fn verifying_key(&self) -> Result<VerifyingKey, KeyError> {
+        let bytes: [u8; 32] = decode_hex(&self.public_key)?
+            .try_into()
+            .map_err(|_| KeyError::Format("public key must be 32 bytes".to_string()))?;
+        VerifyingKey::from_bytes(&bytes)
+            .map_err(|_| KeyError::Format("invalid Ed25519 public key".to_string()))
+    }
+
+    pub fn to_json(&self) -> Result<String, KeyError> {
+        serde_json::to_string_pretty(self).map_err(KeyError::Json)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, KeyError> {
+        serde_json::from_str(json).map_err(KeyError::Json)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), KeyError> {
+        fs::write(path, self.to_json()?).map_err(KeyError::Io)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, KeyError> {
+        Self::from_json(&fs::read_to_string(path).map_err(KeyError::Io)?)
+    }
+
+    fn check_format(&self, algorithm: &str) -> Result<(), KeyError> {
+        let expected = [
+            (self.version == KEYSTORE_VERSION, "version"),
+            (self.algorithm == algorithm, "algorithm"),
+            (self.kdf.name == KDF_ARGON2ID, "kdf"),
+            (self.cipher == CIPHER_XCHACHA, "cipher"),
+        ];
+        match expected.iter().find(|(supported, _)| !supported) {
+            Some((_, field)) => Err(KeyError::Format(format!("unsupported {}", field))),
+            None => Ok(()),
+        }
+    }
+}
+
+fn keystore_cipher(
+    passphrase: &[u8],
+    salt: &[u8],
+    params: KdfParams,
+) -> Result<XChaCha20Poly1305, KeyError> {
+    let params = Params::new(params.m_cost, params.t_cost, params.p_cost, Some(32))
+        .map_err(|err| KeyError::Kdf(err.to_string()))?;
+    let mut key = Zeroizing::new([0u8; 32]);
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase, salt, key.as_mut())
+        .map_err(|err| KeyError::Kdf(err.to_string()))?;
+    Ok(XChaCha20Poly1305::new(key.as_ref().into()))
+}
+
+fn decode_hex(encoded: &str) -> Result<Vec<u8>, KeyError> {
+    hex::decode(encoded).map_err(|_| KeyError::Format("invalid hex".to_string()))
+}
+
+#[cfg(feature = "bls")]
+pub use bls_keys::generate_bls;
+
+#[cfg(feature = "bls")]
+mod bls_keys {
+    use blst::min_pk::{PublicKey, SecretKey};
+    use zeroize::Zeroizing;
+
+    use super::{decode_hex, KdfParams, KeyError, Keystore};
+    use crate::entropy::EntropySource;
+
+    const ALGORITHM_BLS12_381: &str = "bls12-381";
+
+    // BLS12-381 keys with 48-byte public keys (the min-pk variant), derived with the
+    // IETF KeyGen from a fresh 32-byte seed
+    pub fn generate_bls(entropy: &mut impl EntropySource) -> SecretKey {
+        let seed = Zeroizing::new(entropy.seed());
+        SecretKey::key_gen(seed.as_ref(), &[]).expect("KeyGen accepts a 32-byte seed")
+    }
+
+    impl Keystore {
+        pub fn encrypt_bls(
+            key: &SecretKey,
+            passphrase: &[u8],
+            params: KdfParams,
+            entropy: &mut impl EntropySource,
+        ) -> Result<Self, KeyError> {
+            let secret = Zeroizing::new(key.to_bytes());
+            Self::seal(
+                ALGORITHM_BLS12_381,
+                secret.as_ref(),
+                &key.sk_to_pk().to_bytes(),
+                passphrase,
+                params,
+                entropy,
+            )
+        }
+
+        pub fn decrypt_bls(&self, passphrase: &[u8]) -> Result<SecretKey, KeyError> {
+            let secret = self.open(ALGORITHM_BLS12_381, passphrase)?;
+            let public_key = self.bls_public_key()?;
+            let key = SecretKey::from_bytes(&secret)
+                .map_err(|_| KeyError::Format("invalid BLS12-381 secret key".to_string()))?;
+            if key.sk_to_pk().to_bytes() != public_key.to_bytes() {
+                return Err(KeyError::KeyMismatch);
+            }
+            Ok(key)
+        }
+
+        // Rejects points off the curve, outside the prime-order subgroup and the identity
+        pub fn bls_public_key(&self) -> Result<PublicKey, KeyError> {
+            PublicKey::key_validate(&decode_hex(&self.public_key)?)
+                .map_err(|_| KeyError::Format("invalid BLS12-381 public key".to_string()))
+        }
+    }
+}
+
+// Define the KeyPeriod struct, the epochs a root attestation key is valid for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyPeriod {
+    pub key: VerifyingKey,
+    pub from_epoch: u64,
+    // Inclusive; None while the key is the active one
+    pub until_epoch: Option<u64>,
+}
+
+impl KeyPeriod {
+    pub fn covers(&self, epoch: u64) -> bool {
+        epoch >= self.from_epoch && self.until_epoch.is_none_or(|until| epoch <= until)
+    }
+}
+
+// Define the KeyRing struct, every attestation key the operator has used, so roots
+// from before a rotation keep verifying under the key that signed them
+#[derive(Debug, Clone)]
+pub struct KeyRing {
+    periods: Vec<KeyPeriod>,
+}
+
+impl KeyRing {
+    pub fn new(key: VerifyingKey, from_epoch: u64) -> Self {
+        KeyRing {
+            periods: vec![KeyPeriod {
+                key,
+                from_epoch,
+                until_epoch: None,
+            }],
+        }
+    }
+
+    // Periods must start in increasing epoch order, use distinct keys and leave only the
+    // last one open-ended
+    pub fn from_periods(periods: Vec<KeyPeriod>) -> Result<Self, KeyError> {
+        let Some(last) = periods.last() else {
+            return Err(KeyError::EmptyRing);
+        };
+        let (retired, _) = periods.split_at(periods.len() - 1);
+        if last.until_epoch.is_some() || retired.iter().any(|period| period.until_epoch.is_none()) {
+            return Err(KeyError::OpenEnded);
+        }
+        for pair in periods.windows(2) {
+            if pair[1].from_epoch <= pair[0].from_epoch {
+                return Err(KeyError::RotationOutOfOrder {
+                    active_from: pair[0].from_epoch,
+                    found: pair[1].from_epoch,
+                });
+            }
+        }
+        for (index, period) in periods.iter().enumerate() {
+            if periods[..index].iter().any(|earlier| earlier.key == period.key) {
+                return Err(KeyError::KeyReused);
+            }
+        }
+        Ok(KeyRing { periods })
+    }
+
+    pub fn periods(&self) -> &[KeyPeriod] {
+        &self.periods
+    }
+
+    pub fn active(&self) -> &KeyPeriod {
+        self.periods.last().expect("a key ring always holds a key")
+    }
+
+    // `key` signs from `from_epoch` on. The previous key stays valid for `overlap` more
+    // epochs, so roots signed during the handover verify under either key.
+    pub fn rotate(
+        &mut self,
+        key: VerifyingKey,
+        from_epoch: u64,
+        overlap: u64,
+    ) -> Result<(), KeyError> {
+        let active = self.active();
+        if from_epoch <= active.from_epoch {
+            return Err(KeyError::RotationOutOfOrder {
+                active_from: active.from_epoch,
+                found: from_epoch,
+            });
+        }
+        if self.periods.iter().any(|period| period.key == key) {
+            return Err(KeyError::KeyReused);
+        }
+        let previous = self
+            .periods
+            .last_mut()
+            .expect("a key ring always holds a key");
+        previous.until_epoch = Some((from_epoch - 1).saturating_add(overlap));
+        self.periods.push(KeyPeriod {
+            key,
+            from_epoch,
+            until_epoch: None,
+        });
+        Ok(())
+    }
+
+    pub fn keys_for(&self, epoch: u64) -> impl Iterator<Item = &VerifyingKey> + '_ {
+        self.periods
+            .iter()
+            .filter(move |period| period.covers(epoch))
+            .map(|period| &period.key)
+    }
+
+    // Returns the key that signed the root
+    pub fn verify_root<C: SumCommitment>(
+        &self,
+        signed_root: &SignedRoot<C>,
+    ) -> Result<VerifyingKey, KeyError> {
+        let mut keys = self.keys_for(signed_root.epoch).peekable();
+        if keys.peek().is_none() {
+            return Err(KeyError::NoKeyForEpoch(signed_root.epoch));
+        }
+        keys.find(|key| signed_root.verify(key).is_ok())
+            .copied()
+            .ok_or(KeyError::BadSignature(signed_root.epoch))
+    }
+}
+
+// Define the KeyError enum for keys that can't be stored, loaded or trusted
+#[derive(Debug)]
+pub enum KeyError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    Format(String),
+    Kdf(String),
+    Encryption,
+    WrongPassphrase,
+    KeyMismatch,
+    RotationOutOfOrder { active_from: u64, found: u64 },
+    KeyReused,
+    EmptyRing,
+    OpenEnded,
+    NoKeyForEpoch(u64),
+    BadSignature(u64),
+}
+
+impl fmt::Display for KeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyError::Io(err) => write!(f, "i/o error: {}", err),
+            KeyError::Json(err) => write!(f, "json error: {}", err),
+            KeyError::Format(reason) => write!(f, "malformed keystore: {}", reason),
+            KeyError::Kdf(reason) => write!(f, "key derivation failed: {}", reason),
+            KeyError::Encryption => write!(f, "keystore encryption failed"),
+            KeyError::WrongPassphrase => {
+                write!(f, "wrong passphrase or corrupted keystore")
+            }
+            KeyError::KeyMismatch => {
+                write!(f, "decrypted key does not match the keystore's public key")
+            }
+            KeyError::RotationOutOfOrder { active_from, found } => write!(
+                f,
+                "new key must start after epoch {}, found {}",
+                active_from, found
+            ),
+            KeyError::KeyReused => write!(f, "key is already in the key ring"),
+            KeyError::EmptyRing => write!(f, "key ring needs at least one key"),
+            KeyError::OpenEnded => {
+                write!(f, "exactly the newest key in a key ring must be open-ended")
+            }
+            KeyError::NoKeyForEpoch(epoch) => write!(f, "no key is valid for epoch {}", epoch),
+            KeyError::BadSignature(epoch) => {
+                write!(f, "root for epoch {} is not signed by a valid key", epoch)
+            }
+        }
+    }
+}
+
+impl std::error::Error for KeyError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entropy::SeededEntropy;
+
+    // Cheap enough for tests; real keystores use the defaults
+    const PARAMS: KdfParams = KdfParams {
+        m_cost: 8,
+        t_cost: 1,
+        p_cost: 1,
+    };
+
+    #[test]
+    fn ed25519_keystore_round_trips() {
+        let mut entropy = SeededEntropy::new([7; 32]);
+        let key = generate(&mut entropy);
+        let keystore = Keystore::encrypt(&key, b"passphrase", PARAMS, &mut entropy).unwrap();
+        let keystore = Keystore::from_json(&keystore.to_json().unwrap()).unwrap();
+
+        assert_eq!(keystore.verifying_key().unwrap(), key.verifying_key());
+        assert_eq!(keystore.decrypt(b"passphrase").unwrap(), key);
+        assert!(matches!(
+            keystore.decrypt(b"wrong"),
+            Err(KeyError::WrongPassphrase)
+        ));
+    }
+
+    #[cfg(feature = "bls")]
+    #[test]
+    fn bls_keystore_round_trips() {
+        let mut entropy = SeededEntropy::new([7; 32]);
+        let key = generate_bls(&mut entropy);
+        let keystore = Keystore::encrypt_bls(&key, b"passphrase", PARAMS, &mut entropy).unwrap();
+        let keystore = Keystore::from_json(&keystore.to_json().unwrap()).unwrap();
+
+        assert_eq!(keystore.algorithm, "bls12-381");
+        assert_eq!(
+            keystore.bls_public_key().unwrap().to_bytes(),
+            key.sk_to_pk().to_bytes()
+        );
+        let decrypted = keystore.decrypt_bls(b"passphrase").unwrap();
+        assert_eq!(decrypted.to_bytes(), key.to_bytes());
+        assert!(matches!(
+            keystore.decrypt_bls(b"wrong"),
+            Err(KeyError::WrongPassphrase)
+        ));
+        assert!(matches!(
+            keystore.decrypt(b"passphrase"),
+            Err(KeyError::Format(_))
+        ));
+    }
+
+    #[cfg(feature = "bls")]
+    #[test]
+    fn bls_keystore_is_bound_to_its_public_key() {
+        let mut entropy = SeededEntropy::new([7; 32]);
+        let key = generate_bls(&mut entropy);
+        let other = generate_bls(&mut entropy);
+        let mut keystore =
+            Keystore::encrypt_bls(&key, b"passphrase", PARAMS, &mut entropy).unwrap();
+        keystore.public_key = hex::encode(other.sk_to_pk().to_bytes());
+        assert!(matches!(
+            keystore.decrypt_bls(b"passphrase"),
+            Err(KeyError::WrongPassphrase)
+        ));
+    }
+}
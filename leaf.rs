@@ -0,0 +1,131 @@
+use crate::categories::Category;
+use crate::encoding::encode_len;
+
+// Define the Leaf trait for any record that can be committed to as a tree leaf.
+// Only `amount` takes part in the sums; `encode_for_hash` is what the leaf digest covers.
+pub trait Leaf {
+    fn amount(&self) -> u64;
+    fn encode_for_hash(&self) -> Vec<u8>;
+
+    // The (possibly hashed) ID of the account owning this leaf, checked for duplicates at
+    // build time. Bare amounts have no owner.
+    fn user_id(&self) -> Option<&[u8]> {
+        None
+    }
+
+    // Bucket the balance is counted in by category-aware commitments
+    fn category(&self) -> Category {
+        Category::Spot
+    }
+
+    // Asset the balance is denominated in, when the record names one
+    fn asset(&self) -> Option<&str> {
+        None
+    }
+}
+
+impl Leaf for u64 {
+    fn amount(&self) -> u64 {
+        *self
+    }
+
+    fn encode_for_hash(&self) -> Vec<u8> {
+        self.to_le_bytes().to_vec()
+    }
+}
+
+// Define the AccountRecord struct, a richer leaf carrying account metadata next to the balance
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountRecord {
+    pub balance: u64,
+    pub tier: u8,
+    pub asset: String,
+    pub timestamp: u64,
+}
+
+impl Leaf for AccountRecord {
+    fn amount(&self) -> u64 {
+        self.balance
+    }
+
+    fn encode_for_hash(&self) -> Vec<u8> {
+        let asset = self.asset.as_bytes();
+        let mut encoded = Vec::with_capacity(8 + 1 + 8 + asset.len() + 8);
+        encoded.extend_from_slice(&self.balance.to_le_bytes());
+        encoded.push(self.tier);
+        // Length-prefix the asset so neighbouring fields can't shift into it
+        encoded.extend_from_slice(&encode_len(asset));
+        encoded.extend_from_slice(asset);
+        encoded.extend_from_slice(&self.timestamp.to_le_bytes());
+        encoded
+    }
+
+    fn asset(&self) -> Option<&str> {
+        Some(&self.asset)
+    }
+}
+
+// Define the UserLeaf struct, a record tagged with the ID of the account that owns it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserLeaf<L: Leaf> {
+    pub user_id: String,
+    pub record: L,
+}
+
+impl<L: Leaf> Leaf for UserLeaf<L> {
+    fn amount(&self) -> u64 {
+        self.record.amount()
+    }
+
+    fn encode_for_hash(&self) -> Vec<u8> {
+        encode_user_leaf(self.user_id.as_bytes(), &self.record.encode_for_hash())
+    }
+
+    fn user_id(&self) -> Option<&[u8]> {
+        Some(self.user_id.as_bytes())
+    }
+
+    fn category(&self) -> Category {
+        self.record.category()
+    }
+
+    fn asset(&self) -> Option<&str> {
+        self.record.asset()
+    }
+}
+
+// Define the MergedAccount struct, what the builder commits for a user ID that appeared
+// several times under `DuplicatePolicy::MergeBalances`. It hashes like a
+// `UserLeaf<u64>` holding the summed balance, so the user can recompute it. Only records
+// of one asset are merged, and the asset is kept for reporting but not hashed.
+pub(crate) struct MergedAccount<'a> {
+    pub(crate) user_id: &'a [u8],
+    pub(crate) balance: u64,
+    pub(crate) asset: Option<&'a str>,
+}
+
+impl Leaf for MergedAccount<'_> {
+    fn amount(&self) -> u64 {
+        self.balance
+    }
+
+    fn encode_for_hash(&self) -> Vec<u8> {
+        encode_user_leaf(self.user_id, &self.balance.encode_for_hash())
+    }
+
+    fn user_id(&self) -> Option<&[u8]> {
+        Some(self.user_id)
+    }
+
+    fn asset(&self) -> Option<&str> {
+        self.asset
+    }
+}
+
+pub(crate) fn encode_user_leaf(user_id: &[u8], record: &[u8]) -> Vec<u8> {
+    let mut encoded = Vec::with_capacity(8 + user_id.len() + record.len());
+    encoded.extend_from_slice(&encode_len(user_id));
+    encoded.extend_from_slice(user_id);
+    encoded.extend_from_slice(record);
+    encoded
+}
@@ -1,49 +1,322 @@
 
-use sha2::{Digest, Sha256};
+use sha2::{Digest as _, Sha256};
 use generic_array::typenum::U32;
 use generic_array::GenericArray;
 
-use std::fmt::Debug;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt::{self, Debug};
+use std::marker::PhantomData;
 
-// Define the SumCommitment trait
+#[cfg(feature = "accumulator")]
+pub mod accumulator;
+pub mod account_hash;
+pub mod airgap;
+pub mod amount_format;
+pub mod anchoring;
+pub mod anomaly;
+pub mod arena;
+pub mod artifacts;
+pub mod balance_source;
+#[cfg(feature = "proptest")]
+mod arbitrary;
+pub mod audit;
+pub mod authenticated_map;
+mod builder;
+pub mod bulk;
+mod bundle;
+pub mod cap;
+pub mod categories;
+#[cfg(feature = "x509")]
+pub mod certificate;
+pub mod chunking;
+pub mod circuit;
+pub mod cli;
+pub mod client;
+pub mod codec;
+pub mod columns;
+mod config;
+#[cfg(feature = "cose")]
+pub mod cose;
+pub mod delta;
+pub mod designated;
+pub mod disclosure;
+pub mod dry_run;
+pub mod elgamal;
+mod encoding;
+mod entropy;
+pub mod equivalence;
+#[cfg(feature = "axum")]
+pub mod extract;
+#[cfg(feature = "ethereum")]
+pub mod ethereum;
+mod fixed_proof;
+pub mod header;
+pub mod interop;
+pub mod invariants;
+pub mod ipfs;
+pub mod jws;
+pub mod keys;
+mod leaf;
+pub mod manifest;
+mod multiproof;
+pub mod namespaced;
+pub mod node_store;
+pub mod objects;
+pub mod pedersen;
+pub mod porz;
+pub mod proof_format;
+#[cfg(feature = "qr")]
+pub mod qr;
+pub mod report;
+pub mod reserves;
+pub mod rescue;
+pub mod root_log;
+pub mod root_signature;
+pub mod scheduler;
+pub mod segment;
+pub mod server;
+pub mod signer;
+pub mod sim;
+pub mod snapshot;
+pub mod summa;
+pub mod testvectors;
+pub mod transcript;
+pub mod trust;
+pub mod tsa;
+mod user_proof;
+pub mod versioned;
+pub mod view;
+pub mod wide;
+pub mod witness;
+
+pub use builder::MerkleSumTreeBuilder;
+pub use bundle::{BundleError, ProofBundle, SignedRoot, VerificationPolicy};
+pub use config::{
+    AmountBinding, BuildError, DuplicatePolicy, HashBackend, PaddingLeaf, PaddingPolicy,
+    SaltDerivation, Shuffling, StorageBackend, TreeConfig,
+};
+pub use encoding::{Digest, ParseError};
+pub use entropy::{EntropySource, OsEntropy, SeededEntropy};
+pub use fixed_proof::FixedProof;
+pub use leaf::{AccountRecord, Leaf, UserLeaf};
+pub use multiproof::{verify_stream, CompactMultiproof, StreamingVerifier};
+pub use user_proof::UserProof;
+
+use arena::{fold_root, NodeArena};
+
+// Define the LeafCommitment trait for commitments to a single leaf record
+pub trait LeafCommitment: Debug + Clone {
+    type Node: SumCommitment<Leaf = Self>;
+    fn from_leaf<L: Leaf>(leaf: &L) -> Self;
+    fn from_salted_leaf<L: Leaf>(leaf: &L, salt: &[u8; 32]) -> Self;
+    fn from_parts(amount: u64, digest: GenericArray<u8, U32>) -> Self;
+    fn amount(&self) -> u64;
+    fn digest(&self) -> GenericArray<u8, U32>;
+    fn to_node(&self) -> Self::Node;
+}
+
+// Define the SumCommitment trait for internal nodes
 pub trait SumCommitment: Debug + Clone {
+    type Leaf: LeafCommitment<Node = Self>;
+    const HASH_BACKEND: HashBackend;
+    const AMOUNT_BINDING: AmountBinding;
     fn amount(&self) -> u64;
     fn digest(&self) -> GenericArray<u8, U32>;
     fn combine_commitments(left: &Self, right: &Self) -> Self;
-    fn new(balance: u64) -> Self;
+    fn from_parts(amount: u64, digest: GenericArray<u8, U32>) -> Self;
+}
+
+// Define the Root struct, the published top node of a tree
+#[derive(Debug, Clone)]
+pub struct Root<C: SumCommitment> {
+    node: C,
+}
+
+impl<C: SumCommitment> Root<C> {
+    pub fn from_node(node: C) -> Self {
+        Root { node }
+    }
+
+    pub fn node(&self) -> &C {
+        &self.node
+    }
+
+    pub fn amount(&self) -> u64 {
+        self.node.amount()
+    }
+
+    pub fn digest(&self) -> GenericArray<u8, U32> {
+        self.node.digest()
+    }
+
+    // Compares the digests on their own, then the sums, so a node with the right hashes
+    // but a doctored amount is reported as a sum mismatch rather than a generic failure
+    pub fn check(&self, node: &C) -> Result<(), VerifyError> {
+        if self.digest() != node.digest() {
+            return Err(VerifyError::DigestMismatch);
+        }
+        if self.amount() != node.amount() {
+            return Err(VerifyError::SumMismatch {
+                root: self.amount(),
+                proof: node.amount(),
+            });
+        }
+        Ok(())
+    }
+
+    pub fn matches(&self, node: &C) -> bool {
+        self.check(node).is_ok()
+    }
+}
+
+// Adds the leaf amount and every sibling amount with overflow checks. Run before any
+// hashing, since `combine_commitments` adds unchecked and sibling amounts come from the prover.
+pub fn checked_path_sum<'a, C: SumCommitment + 'a>(
+    leaf_amount: u64,
+    siblings: impl IntoIterator<Item = &'a C>,
+) -> Result<u64, VerifyError> {
+    siblings.into_iter().try_fold(leaf_amount, |sum, sibling| {
+        sum.checked_add(sibling.amount())
+            .ok_or(VerifyError::SumOverflow)
+    })
+}
+
+// Define the VerifyError enum for the ways a proof can fail against a root
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerifyError {
+    DigestMismatch,
+    SumMismatch { root: u64, proof: u64 },
+    SumOverflow,
+    DuplicatePosition(usize),
 }
 
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerifyError::DigestMismatch => write!(f, "recomputed digest does not match the root"),
+            VerifyError::SumMismatch { root, proof } => write!(
+                f,
+                "proof sums to {} but the root commits to {}",
+                proof, root
+            ),
+            VerifyError::SumOverflow => write!(f, "proof amounts overflow a u64"),
+            VerifyError::DuplicatePosition(position) => {
+                write!(f, "leaf {} is proven more than once", position)
+            }
+        }
+    }
+}
+
+impl std::error::Error for VerifyError {}
+
 // Define the ExclusiveAllotmentProof trait
 pub trait ExclusiveAllotmentProof<C: SumCommitment>: Debug {
-    fn new(position: usize, sibling: Option<C>) -> Self;
+    fn new(position: usize, leaf: C::Leaf, siblings: Vec<(C, bool)>) -> Self;
     fn position(&self) -> usize;
-    fn sibling(&self) -> Option<C>;
-    fn verify(&self, root_commitment: &C) -> bool;
-    fn reconstruct_commitment(&self, root_commitment: &C) -> C;
-    fn reconstruct_commitment_recursive(&self, node_index: usize, root_commitment: &C) -> C;
+    fn leaf(&self) -> &C::Leaf;
+    fn siblings(&self) -> &[(C, bool)];
+    fn reconstruct_commitment(&self) -> C;
+
+    fn check(&self, root: &Root<C>) -> Result<(), VerifyError> {
+        checked_path_sum(self.leaf().amount(), self.siblings().iter().map(|(sibling, _)| sibling))?;
+        root.check(&self.reconstruct_commitment())
+    }
+
+    fn verify(&self, root: &Root<C>) -> bool {
+        self.check(root).is_ok()
+    }
 }
 
 // Define the MerkleProof struct
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct MerkleProof<C: SumCommitment> {
-    sibling: Option<C>,
-    sibling_position: usize,
+    position: usize,
+    leaf: C::Leaf,
+    // Siblings ordered from the leaf up to the root, `true` when the sibling is the left child
+    siblings: Vec<(C, bool)>,
 }
 
-impl<C: SumCommitment> MerkleProof<C> {
-    pub fn new(sibling: Option<C>, sibling_position: usize) -> Self {
+impl<C: SumCommitment> ExclusiveAllotmentProof<C> for MerkleProof<C> {
+    fn new(position: usize, leaf: C::Leaf, siblings: Vec<(C, bool)>) -> Self {
         MerkleProof {
-            sibling,
-            sibling_position,
+            position,
+            leaf,
+            siblings,
+        }
+    }
+
+    fn position(&self) -> usize {
+        self.position
+    }
+
+    fn leaf(&self) -> &C::Leaf {
+        &self.leaf
+    }
+
+    fn siblings(&self) -> &[(C, bool)] {
+        &self.siblings
+    }
+
+    fn reconstruct_commitment(&self) -> C {
+        let mut current = self.leaf.to_node();
+        for (sibling, sibling_on_left) in &self.siblings {
+            current = if *sibling_on_left {
+                C::combine_commitments(sibling, &current)
+            } else {
+                C::combine_commitments(&current, sibling)
+            };
         }
+        current
     }
+}
+
+// Define MimiLeafCommitment struct
+#[derive(Debug, Clone)]
+pub struct MimiLeafCommitment {
+    amount: u64,
+    digest: GenericArray<u8, U32>,
+}
 
-    pub fn sibling(&self) -> Option<C> {
-        self.sibling.clone()
+impl LeafCommitment for MimiLeafCommitment {
+    type Node = MimiSumCommitment;
+
+    fn from_leaf<L: Leaf>(leaf: &L) -> Self {
+        let mut preimage = vec![LEAF_PREFIX];
+        preimage.extend_from_slice(&leaf.encode_for_hash());
+        MimiLeafCommitment {
+            amount: leaf.amount(),
+            digest: hash_bytes(&preimage),
+        }
+    }
+
+    fn from_salted_leaf<L: Leaf>(leaf: &L, salt: &[u8; 32]) -> Self {
+        let mut preimage = vec![LEAF_PREFIX];
+        preimage.extend_from_slice(salt);
+        preimage.extend_from_slice(&leaf.encode_for_hash());
+        MimiLeafCommitment {
+            amount: leaf.amount(),
+            digest: hash_bytes(&preimage),
+        }
+    }
+
+    fn from_parts(amount: u64, digest: GenericArray<u8, U32>) -> Self {
+        MimiLeafCommitment { amount, digest }
+    }
+
+    fn amount(&self) -> u64 {
+        self.amount
+    }
+
+    fn digest(&self) -> GenericArray<u8, U32> {
+        self.digest
     }
 
-    pub fn sibling_position(&self) -> usize {
-        self.sibling_position
+    fn to_node(&self) -> MimiSumCommitment {
+        MimiSumCommitment {
+            amount: self.amount,
+            digest: self.digest,
+        }
     }
 }
 
@@ -55,17 +328,29 @@ pub struct MimiSumCommitment {
 }
 
 impl SumCommitment for MimiSumCommitment {
+    type Leaf = MimiLeafCommitment;
+    const HASH_BACKEND: HashBackend = HashBackend::Sha256;
+    const AMOUNT_BINDING: AmountBinding = AmountBinding::Bound;
+
     fn amount(&self) -> u64 {
         self.amount
     }
 
     fn digest(&self) -> GenericArray<u8, U32> {
-        self.digest.clone()
+        self.digest
     }
 
+    // 1 | left digest | left amount | right digest | right amount, amounts little-endian
     fn combine_commitments(left: &Self, right: &Self) -> Self {
         let combined_amount = left.amount() + right.amount();
-        let combined_digest = hash_bytes(&[left.digest(), right.digest()].concat());
+        // Concatenate on the stack so combining stays allocation-free
+        let mut preimage = [0u8; 81];
+        preimage[0] = NODE_PREFIX;
+        preimage[1..33].copy_from_slice(&left.digest());
+        preimage[33..41].copy_from_slice(&left.amount().to_le_bytes());
+        preimage[41..73].copy_from_slice(&right.digest());
+        preimage[73..].copy_from_slice(&right.amount().to_le_bytes());
+        let combined_digest = hash_bytes(&preimage);
 
         MimiSumCommitment {
             amount: combined_amount,
@@ -73,93 +358,467 @@ impl SumCommitment for MimiSumCommitment {
         }
     }
 
-    fn new(balance: u64) -> Self {
-        MimiSumCommitment {
-            amount: balance,
-            digest: hash_bytes(&balance.to_le_bytes()),
+    fn from_parts(amount: u64, digest: GenericArray<u8, U32>) -> Self {
+        MimiSumCommitment { amount, digest }
+    }
+}
+
+// Define UnboundLeafCommitment struct, hashed like `MimiLeafCommitment`
+#[derive(Debug, Clone)]
+pub struct UnboundLeafCommitment {
+    amount: u64,
+    digest: GenericArray<u8, U32>,
+}
+
+impl LeafCommitment for UnboundLeafCommitment {
+    type Node = UnboundSumCommitment;
+
+    fn from_leaf<L: Leaf>(leaf: &L) -> Self {
+        let leaf = MimiLeafCommitment::from_leaf(leaf);
+        UnboundLeafCommitment::from_parts(leaf.amount, leaf.digest)
+    }
+
+    fn from_salted_leaf<L: Leaf>(leaf: &L, salt: &[u8; 32]) -> Self {
+        let leaf = MimiLeafCommitment::from_salted_leaf(leaf, salt);
+        UnboundLeafCommitment::from_parts(leaf.amount, leaf.digest)
+    }
+
+    fn from_parts(amount: u64, digest: GenericArray<u8, U32>) -> Self {
+        UnboundLeafCommitment { amount, digest }
+    }
+
+    fn amount(&self) -> u64 {
+        self.amount
+    }
+
+    fn digest(&self) -> GenericArray<u8, U32> {
+        self.digest
+    }
+
+    fn to_node(&self) -> UnboundSumCommitment {
+        UnboundSumCommitment {
+            amount: self.amount,
+            digest: self.digest,
         }
     }
 }
 
-fn hash_bytes(slice: &[u8]) -> [u8; 32] {
+// Define UnboundSumCommitment struct for protocols that keep sums out of the node hash.
+// Its digests don't commit to any amount, so verifiers must get sums from elsewhere.
+#[derive(Debug, Clone)]
+pub struct UnboundSumCommitment {
+    amount: u64,
+    digest: GenericArray<u8, U32>,
+}
+
+impl SumCommitment for UnboundSumCommitment {
+    type Leaf = UnboundLeafCommitment;
+    const HASH_BACKEND: HashBackend = HashBackend::Sha256;
+    const AMOUNT_BINDING: AmountBinding = AmountBinding::Unbound;
+
+    fn amount(&self) -> u64 {
+        self.amount
+    }
+
+    fn digest(&self) -> GenericArray<u8, U32> {
+        self.digest
+    }
+
+    // 1 | left digest | right digest
+    fn combine_commitments(left: &Self, right: &Self) -> Self {
+        let mut preimage = [0u8; 65];
+        preimage[0] = NODE_PREFIX;
+        preimage[1..33].copy_from_slice(&left.digest);
+        preimage[33..].copy_from_slice(&right.digest);
+        UnboundSumCommitment {
+            amount: left.amount + right.amount,
+            digest: hash_bytes(&preimage),
+        }
+    }
+
+    fn from_parts(amount: u64, digest: GenericArray<u8, U32>) -> Self {
+        UnboundSumCommitment { amount, digest }
+    }
+}
+
+// First byte (or, for Rescue, element) of every leaf and node preimage in every backend,
+// so a leaf digest can never be opened as an internal node or the other way round
+pub(crate) const LEAF_PREFIX: u8 = 0;
+pub(crate) const NODE_PREFIX: u8 = 1;
+
+fn hash_bytes(slice: &[u8]) -> GenericArray<u8, U32> {
     let mut hasher = Sha256::new();
     hasher.update(slice);
-    let result = hasher.finalize();
-    let mut output = [0u8; 32];
-    output.copy_from_slice(result.as_slice());
-    output
+    hasher.finalize()
 }
 
 // Define MimiMerkleTree struct
+#[derive(Debug)]
 pub struct MimkMerkleTree<C: SumCommitment, P: ExclusiveAllotmentProof<C>> {
-    leaf_nodes: Vec<C>,
+    leaf_nodes: Vec<C::Leaf>,
+    config: TreeConfig,
+    // Tree position of each input leaf, only kept when the leaves were shuffled
+    input_positions: Option<Vec<usize>>,
+    // Tree positions of the leaves owned by each user ID, ascending
+    user_positions: HashMap<Vec<u8>, Vec<usize>>,
+    // Leaves holding accounts; the padding leaves come after them
+    account_count: usize,
+    // Precomputed nodes, absent for `StorageBackend::Memory`
+    arena: Option<NodeArena<C>>,
+    _proof: PhantomData<P>,
 }
 
 impl<C, P> MimkMerkleTree<C, P>
 where
-    C: SumCommitment + Clone + PartialEq,
+    C: SumCommitment,
     P: ExclusiveAllotmentProof<C>,
 {
-    pub fn new(values: Vec<u64>) -> Self {
-        let leaf_nodes: Vec<C> = values.into_iter().map(|balance| C::new(balance)).collect();
-        Self { leaf_nodes }
+    // Builds with the default configuration for `C`, so the same duplicate-id and
+    // balance overflow checks apply as through the builder
+    pub fn new(values: Vec<u64>) -> Result<Self, BuildError> {
+        Self::from_leaves(&values)
     }
 
-    pub fn commit(&self) -> C {
-        self.build_merkle_tree(0, &self.leaf_nodes)
+    pub fn from_leaves<L: Leaf>(leaves: &[L]) -> Result<Self, BuildError> {
+        MerkleSumTreeBuilder::with_config(TreeConfig {
+            hash_backend: C::HASH_BACKEND,
+            amount_binding: C::AMOUNT_BINDING,
+            ..TreeConfig::default()
+        })
+        .build(leaves)
     }
 
-    pub fn prove(&self, position: usize) -> P {
-        let proof = self.construct_inclusion_proof(position, 0, &self.leaf_nodes);
-        P::new(position, proof.sibling)
+    // For trees restored from stored leaf commitments
+    pub(crate) fn from_leaf_nodes(leaf_nodes: Vec<C::Leaf>) -> Self {
+        let config = TreeConfig {
+            amount_binding: C::AMOUNT_BINDING,
+            ..TreeConfig::default()
+        };
+        Self {
+            account_count: leaf_nodes.len(),
+            arena: NodeArena::for_storage(config.storage, &leaf_nodes),
+            leaf_nodes,
+            config,
+            input_positions: None,
+            user_positions: HashMap::new(),
+            _proof: PhantomData,
+        }
     }
 
-    fn build_merkle_tree(&self, node_index: usize, nodes: &[C]) -> C {
-        if nodes.len() == 1 {
-            return nodes[0].clone();
+    pub fn builder() -> MerkleSumTreeBuilder<C, P> {
+        MerkleSumTreeBuilder::new()
+    }
+
+    pub fn config(&self) -> &TreeConfig {
+        &self.config
+    }
+
+    pub fn len(&self) -> usize {
+        self.leaf_nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.leaf_nodes.is_empty()
+    }
+
+    pub fn account_count(&self) -> usize {
+        self.account_count
+    }
+
+    // Number of levels below the root; halving puts the longer half on the right,
+    // so the deepest leaf sits at ceil(log2(len))
+    pub fn depth(&self) -> usize {
+        match self.leaf_nodes.len() {
+            0 | 1 => 0,
+            len => (usize::BITS - (len - 1).leading_zeros()) as usize,
         }
-        let middle = nodes.len() / 2;
-        let left = &nodes[0..middle];
-        let right = &nodes[middle..];
+    }
+
+    pub fn leaf(&self, position: usize) -> Option<&C::Leaf> {
+        self.leaf_nodes.get(position)
+    }
+
+    pub fn iter_leaves(&self) -> impl Iterator<Item = &C::Leaf> + '_ {
+        self.leaf_nodes.iter()
+    }
+
+    // Maps the index of an input leaf to its position in the (possibly shuffled) tree
+    pub fn position_of(&self, input_index: usize) -> Option<usize> {
+        match &self.input_positions {
+            Some(input_positions) => input_positions.get(input_index).copied(),
+            None if input_index < self.leaf_nodes.len() => Some(input_index),
+            None => None,
+        }
+    }
+
+    pub fn commit(&self) -> Root<C> {
+        Root::from_node(self.root_node().into_owned())
+    }
+
+    // Borrowed from the arena when the tree keeps one, so reading the root copies nothing
+    pub fn root_node(&self) -> Cow<'_, C> {
+        self.node_commitment(0, &self.leaf_nodes)
+    }
 
-        let left_commitment = self.build_merkle_tree(node_index * 2 + 1, left);
-        let right_commitment = self.build_merkle_tree(node_index * 2 + 2, right);
+    // The node at `node_index` (root 0, children of i at 2i + 1 and 2i + 2), or None when
+    // the tree has no such node
+    pub fn node_at(&self, node_index: usize) -> Option<Cow<'_, C>> {
+        let mut ancestors = Vec::new();
+        let mut index = node_index;
+        while index > 0 {
+            ancestors.push(index);
+            index = (index - 1) / 2;
+        }
+        let mut nodes = &self.leaf_nodes[..];
+        for index in ancestors.into_iter().rev() {
+            if nodes.len() < 2 {
+                return None;
+            }
+            let middle = nodes.len() / 2;
+            nodes = if index % 2 == 1 {
+                &nodes[..middle]
+            } else {
+                &nodes[middle..]
+            };
+        }
+        if nodes.is_empty() {
+            return None;
+        }
+        Some(self.node_commitment(node_index, nodes))
+    }
 
-        C::combine_commitments(&left_commitment, &right_commitment)
+    // Panics when the position is out of range, like slice indexing
+    pub fn prove(&self, position: usize) -> P {
+        let mut siblings = Vec::new();
+        let leaf = self.construct_inclusion_proof(position, 0, &self.leaf_nodes, &mut siblings);
+        P::new(position, leaf, siblings)
     }
 
+    fn build_merkle_tree(&self, node_index: usize, nodes: &[C::Leaf]) -> C {
+        self.node_commitment(node_index, nodes).into_owned()
+    }
+
+    // Borrowed when the arena holds the node, folded from its leaves otherwise; a node is
+    // copied once, when the caller keeps it. Panics on an empty range.
+    fn node_commitment(&self, node_index: usize, nodes: &[C::Leaf]) -> Cow<'_, C> {
+        if let Some(node) = self.arena.as_ref().and_then(|arena| arena.node(node_index)) {
+            return Cow::Borrowed(node);
+        }
+        Cow::Owned(fold_root(nodes).expect("a tree node covers at least one leaf"))
+    }
+
+    // Returns the leaf at `position` and pushes its siblings from the bottom up
     fn construct_inclusion_proof(
         &self,
         position: usize,
         node_index: usize,
-        nodes: &[C],
-    ) -> MerkleProof<C> {
+        nodes: &[C::Leaf],
+        siblings: &mut Vec<(C, bool)>,
+    ) -> C::Leaf {
         if nodes.len() == 1 {
-            return MerkleProof::new(None, node_index);
+            return nodes[0].clone();
         }
 
         let middle = nodes.len() / 2;
         if position < middle {
             // The position is in the left subtree
+            let leaf =
+                self.construct_inclusion_proof(position, node_index * 2 + 1, &nodes[0..middle], siblings);
             let right_commitment = self.build_merkle_tree(node_index * 2 + 2, &nodes[middle..]);
-            let right_sibling_position = node_index * 2 + 1;
-            MerkleProof::new(Some(right_commitment), right_sibling_position)
+            siblings.push((right_commitment, false));
+            leaf
         } else {
             // The position is in the right subtree
+            let leaf = self.construct_inclusion_proof(
+                position - middle,
+                node_index * 2 + 2,
+                &nodes[middle..],
+                siblings,
+            );
             let left_commitment = self.build_merkle_tree(node_index * 2 + 1, &nodes[0..middle]);
-            let left_sibling_position = node_index * 2 + 2;
-            MerkleProof::new(Some(left_commitment), left_sibling_position)
+            siblings.push((left_commitment, true));
+            leaf
         }
     }
 }
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        if let Err(err) = cli::run(&args) {
+            eprintln!("error: {}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     let values = vec![100, 200, 300, 400, 500];
-    let merkle_tree = MimkMerkleTree::<MimiSumCommitment, MerkleProof<MimiSumCommitment>>::new(values);
+    let merkle_tree = MimkMerkleTree::<MimiSumCommitment, MerkleProof<MimiSumCommitment>>::new(values)
+        .expect("the demo balances fit in a u64");
     let commitment = merkle_tree.commit();
     let proof = merkle_tree.prove(2);
 
-    println!("Root Commitment: {:?}", commitment);
+    println!("Root Commitment: {}", commitment);
     println!("Proof: {:?}", proof);
+    println!("Proof verifies: {}", proof.verify(&commitment));
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree<C: SumCommitment>(binding: AmountBinding) -> MimkMerkleTree<C, MerkleProof<C>> {
+        MimkMerkleTree::builder()
+            .amount_binding(binding)
+            .build(&[100u64, 200, 300, 400])
+            .unwrap()
+    }
+
+    // Moves `delta` from the second sibling of leaf 0's path to the first, so the path
+    // still sums to the root's total
+    fn shift_between_siblings<C: SumCommitment>(
+        proof: &MerkleProof<C>,
+        delta: u64,
+    ) -> MerkleProof<C> {
+        let mut siblings = proof.siblings().to_vec();
+        let (first, second) = (siblings[0].0.clone(), siblings[1].0.clone());
+        siblings[0].0 = C::from_parts(first.amount() + delta, first.digest());
+        siblings[1].0 = C::from_parts(second.amount() - delta, second.digest());
+        MerkleProof::new(proof.position(), proof.leaf().clone(), siblings)
+    }
+
+    #[test]
+    fn bound_digests_catch_amounts_shifted_between_siblings() {
+        let tree = tree::<MimiSumCommitment>(AmountBinding::Bound);
+        let root = tree.commit();
+        let forged = shift_between_siblings(&tree.prove(0), 50);
+        assert_eq!(forged.check(&root), Err(VerifyError::DigestMismatch));
+    }
+
+    #[test]
+    fn unbound_digests_only_check_the_total() {
+        let tree = tree::<UnboundSumCommitment>(AmountBinding::Unbound);
+        let root = tree.commit();
+        // The shifted amounts aren't hashed, so only the unchanged total is checked
+        let forged = shift_between_siblings(&tree.prove(0), 50);
+        assert_eq!(forged.check(&root), Ok(()));
+
+        let mut siblings = tree.prove(0).siblings().to_vec();
+        let sibling = siblings[0].0.clone();
+        siblings[0].0 = UnboundSumCommitment::from_parts(sibling.amount() + 1, sibling.digest());
+        let inflated = MerkleProof::new(0, tree.prove(0).leaf().clone(), siblings);
+        assert_eq!(
+            inflated.check(&root),
+            Err(VerifyError::SumMismatch {
+                root: 1000,
+                proof: 1001
+            })
+        );
+    }
+
+    #[test]
+    fn changed_root_amount_is_a_sum_mismatch() {
+        let tree = tree::<MimiSumCommitment>(AmountBinding::Bound);
+        let root = tree.commit();
+        let forged = Root::from_node(MimiSumCommitment::from_parts(
+            root.amount() + 1,
+            root.digest(),
+        ));
+        assert_eq!(
+            tree.prove(2).check(&forged),
+            Err(VerifyError::SumMismatch {
+                root: 1001,
+                proof: 1000
+            })
+        );
+    }
+
+    #[test]
+    fn path_sum_overflow_is_reported() {
+        let tree = tree::<MimiSumCommitment>(AmountBinding::Bound);
+        let proof = tree.prove(0);
+        let mut siblings = proof.siblings().to_vec();
+        siblings[1].0 = MimiSumCommitment::from_parts(u64::MAX, siblings[1].0.digest());
+        let forged = MerkleProof::new(0, proof.leaf().clone(), siblings);
+        assert_eq!(forged.check(&tree.commit()), Err(VerifyError::SumOverflow));
+
+        let huge = MimiSumCommitment::from_parts(u64::MAX, GenericArray::default());
+        assert_eq!(checked_path_sum(1, [&huge]), Err(VerifyError::SumOverflow));
+        assert_eq!(checked_path_sum::<MimiSumCommitment>(1, []), Ok(1));
+    }
+
+    #[test]
+    fn streaming_verifier_rejects_overflowing_siblings() {
+        let mut verifier = StreamingVerifier::new(4);
+        assert!(verifier.push(
+            1,
+            MimiSumCommitment::from_parts(u64::MAX, GenericArray::default())
+        ));
+        assert!(!verifier.push(2, MimiSumCommitment::from_parts(1, GenericArray::default())));
+        // Once malformed, nothing more is taken and the stream can't verify
+        assert!(!verifier.push(2, MimiSumCommitment::from_parts(0, GenericArray::default())));
+        let tree = tree::<MimiSumCommitment>(AmountBinding::Bound);
+        assert_eq!(verifier.finish(&tree.commit()), None);
+    }
+
+    #[test]
+    fn constructors_reject_what_the_builder_rejects() {
+        type Tree = MimkMerkleTree<MimiSumCommitment, MerkleProof<MimiSumCommitment>>;
+        assert_eq!(
+            Tree::new(vec![u64::MAX, 1]).unwrap_err(),
+            BuildError::BalanceOverflow
+        );
+        let twice = [
+            UserLeaf {
+                user_id: "alice".to_string(),
+                record: 1u64,
+            },
+            UserLeaf {
+                user_id: "alice".to_string(),
+                record: 2u64,
+            },
+        ];
+        assert_eq!(
+            Tree::from_leaves(&twice).unwrap_err(),
+            BuildError::DuplicateUserId {
+                first: 0,
+                second: 1
+            }
+        );
+        assert_eq!(Tree::new(vec![]).unwrap_err(), BuildError::EmptyTree);
+
+        let tree = Tree::new(vec![100, 200, 300]).unwrap();
+        assert_eq!(tree.commit().amount(), 600);
+        let rescue = MimkMerkleTree::<rescue::RescueSumCommitment, MerkleProof<_>>::new(vec![1, 2]);
+        assert_eq!(rescue.unwrap().commit().amount(), 3);
+    }
+
+    #[test]
+    fn merging_balances_keeps_assets_apart() {
+        let holding = |asset: &str, balance| UserLeaf {
+            user_id: "alice".to_string(),
+            record: AccountRecord {
+                balance,
+                tier: 0,
+                asset: asset.to_string(),
+                timestamp: 0,
+            },
+        };
+        let builder = MimkMerkleTree::<MimiSumCommitment, MerkleProof<_>>::builder()
+            .duplicates(DuplicatePolicy::MergeBalances);
+
+        let mixed = [holding("BTC", 1), holding("ETH", 1_000)];
+        assert_eq!(
+            builder.clone().build(&mixed).unwrap_err(),
+            BuildError::MixedAssets {
+                first: 0,
+                second: 1
+            }
+        );
+        assert!(!builder.dry_run(&mixed).valid);
+
+        let same = [holding("BTC", 1), holding("BTC", 2)];
+        let tree = builder.build(&same).unwrap();
+        assert_eq!(tree.commit().amount(), 3);
+    }
+}
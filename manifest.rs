@@ -0,0 +1,268 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::{PaddingLeaf, SaltDerivation, Shuffling, TreeConfig};
+use crate::encoding::encode_usize;
+use crate::header::backend_id;
+use crate::server::TenantId;
+use crate::snapshot::{Snapshot, SnapshotError};
+use crate::{
+    hash_bytes, Digest, ExclusiveAllotmentProof, Leaf, MerkleSumTreeBuilder, MimkMerkleTree, Root,
+    SumCommitment,
+};
+
+pub const MANIFEST_VERSION: u8 = 1;
+
+const CONFIG_DOMAIN: &[u8] = b"mimi-config-v1";
+
+// Define the BuildManifest struct, written next to each snapshot so anyone holding the
+// inputs can rerun the build and land on the same root. Digests are SHA-256 hex and
+// times are Unix seconds.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BuildManifest {
+    pub version: u8,
+    pub crate_version: String,
+    // Set when the tree is one of several hosted side by side
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+    pub epoch: u64,
+    pub config_hash: String,
+    pub inputs: Vec<InputDigest>,
+    pub snapshot_digest: String,
+    // Root as `<amount>:<hex digest>`
+    pub root: String,
+    pub total: u64,
+    pub leaf_count: usize,
+    pub started_at: u64,
+    pub finished_at: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InputDigest {
+    pub path: PathBuf,
+    pub digest: String,
+    pub bytes: u64,
+}
+
+impl InputDigest {
+    pub fn of_file(path: impl AsRef<Path>) -> Result<Self, ManifestError> {
+        let path = path.as_ref();
+        let contents = fs::read(path).map_err(ManifestError::Io)?;
+        Ok(InputDigest {
+            path: path.to_path_buf(),
+            digest: Digest::new(hash_bytes(&contents).into()).to_string(),
+            bytes: contents.len() as u64,
+        })
+    }
+}
+
+impl BuildManifest {
+    // Digests the input files as they are now, so record the manifest right after the build
+    pub fn record<C, P>(
+        epoch: u64,
+        config: &TreeConfig,
+        inputs: &[impl AsRef<Path>],
+        tree: &MimkMerkleTree<C, P>,
+        snapshot: &[u8],
+        started_at: u64,
+        finished_at: u64,
+    ) -> Result<Self, ManifestError>
+    where
+        C: SumCommitment,
+        P: ExclusiveAllotmentProof<C>,
+    {
+        let root = tree.commit();
+        Ok(BuildManifest {
+            version: MANIFEST_VERSION,
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            tenant: None,
+            epoch,
+            config_hash: config_hash(config).to_string(),
+            inputs: inputs
+                .iter()
+                .map(InputDigest::of_file)
+                .collect::<Result<_, _>>()?,
+            snapshot_digest: Digest::new(hash_bytes(snapshot).into()).to_string(),
+            root: root.to_string(),
+            total: root.amount(),
+            leaf_count: tree.len(),
+            started_at,
+            finished_at,
+        })
+    }
+
+    pub fn with_tenant(mut self, tenant: &TenantId) -> Self {
+        self.tenant = Some(tenant.to_string());
+        self
+    }
+
+    pub fn to_json(&self) -> Result<String, ManifestError> {
+        serde_json::to_string_pretty(self).map_err(ManifestError::Json)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, ManifestError> {
+        serde_json::from_str(json).map_err(ManifestError::Json)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ManifestError> {
+        fs::write(path, self.to_json()?).map_err(ManifestError::Io)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ManifestError> {
+        Self::from_json(&fs::read_to_string(path).map_err(ManifestError::Io)?)
+    }
+}
+
+// Hash of every option that changes the committed tree. Storage is left out since
+// both backends produce the same nodes; the seeds are in, so a manifest pins them.
+pub fn config_hash(config: &TreeConfig) -> Digest {
+    let mut bytes = CONFIG_DOMAIN.to_vec();
+    bytes.push(backend_id(config.hash_backend));
+    bytes.extend_from_slice(&encode_usize(config.arity));
+    bytes.push(config.amount_binding as u8);
+    bytes.push(config.padding as u8);
+    let padding_seed = match &config.padding_leaf {
+        PaddingLeaf::Zero => None,
+        PaddingLeaf::Blinded(seed) => Some(seed),
+    };
+    let salt_seed = match &config.salt_derivation {
+        SaltDerivation::None => None,
+        SaltDerivation::FromSeed(seed) => Some(seed),
+    };
+    let shuffle_seed = match &config.shuffling {
+        Shuffling::None => None,
+        Shuffling::Seeded(seed) => Some(seed),
+    };
+    for seed in [padding_seed, salt_seed, shuffle_seed] {
+        push_seed(&mut bytes, seed);
+    }
+    bytes.push(config.duplicates as u8);
+    Digest::new(hash_bytes(&bytes).into())
+}
+
+fn push_seed(bytes: &mut Vec<u8>, seed: Option<&[u8; 32]>) {
+    match seed {
+        None => bytes.push(0),
+        Some(seed) => {
+            bytes.push(1);
+            bytes.extend_from_slice(seed);
+        }
+    }
+}
+
+// Confirms `snapshot` came from the claimed inputs under `config`: the crate version and
+// configuration match the manifest, the input files still hash to their recorded digests,
+// and rebuilding from `leaves` (read back from those files) reproduces the manifest's root,
+// which must also be the snapshot's.
+pub fn verify_manifest<C, P, L>(
+    manifest: &BuildManifest,
+    config: &TreeConfig,
+    snapshot: &[u8],
+    leaves: &[L],
+) -> Result<(), ManifestError>
+where
+    C: SumCommitment,
+    P: ExclusiveAllotmentProof<C>,
+    L: Leaf,
+{
+    let crate_version = env!("CARGO_PKG_VERSION");
+    if manifest.crate_version != crate_version {
+        return Err(ManifestError::CrateVersion {
+            manifest: manifest.crate_version.clone(),
+            running: crate_version.to_string(),
+        });
+    }
+    if manifest.config_hash != config_hash(config).to_string() {
+        return Err(ManifestError::ConfigMismatch);
+    }
+    for input in &manifest.inputs {
+        if InputDigest::of_file(&input.path)? != *input {
+            return Err(ManifestError::InputChanged(input.path.clone()));
+        }
+    }
+    if manifest.snapshot_digest != Digest::new(hash_bytes(snapshot).into()).to_string() {
+        return Err(ManifestError::SnapshotChanged);
+    }
+
+    let snapshot = Snapshot::<C>::try_from(snapshot)?;
+    if snapshot.epoch != manifest.epoch {
+        return Err(ManifestError::EpochMismatch {
+            manifest: manifest.epoch,
+            snapshot: snapshot.epoch,
+        });
+    }
+    let root: Root<C> = manifest
+        .root
+        .parse()
+        .map_err(|_| ManifestError::RootMismatch)?;
+    if root.amount() != manifest.total || root.check(snapshot.root()?.node()).is_err() {
+        return Err(ManifestError::RootMismatch);
+    }
+    let rebuilt = MerkleSumTreeBuilder::<C, P>::with_config(config.clone())
+        .build(leaves)
+        .map_err(|err| ManifestError::Rebuild(err.to_string()))?;
+    if rebuilt.len() != manifest.leaf_count || root.check(&rebuilt.root_node()).is_err() {
+        return Err(ManifestError::NotReproduced);
+    }
+    Ok(())
+}
+
+// Define the ManifestError enum for manifests that can't be written, read or reproduced
+#[derive(Debug)]
+pub enum ManifestError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    CrateVersion { manifest: String, running: String },
+    ConfigMismatch,
+    InputChanged(PathBuf),
+    SnapshotChanged,
+    EpochMismatch { manifest: u64, snapshot: u64 },
+    // The manifest's root and total don't describe the snapshot
+    RootMismatch,
+    Snapshot(SnapshotError),
+    Rebuild(String),
+    // The inputs rebuild to a different tree than the one recorded
+    NotReproduced,
+}
+
+impl fmt::Display for ManifestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ManifestError::Io(err) => write!(f, "i/o error: {}", err),
+            ManifestError::Json(err) => write!(f, "json error: {}", err),
+            ManifestError::CrateVersion { manifest, running } => write!(
+                f,
+                "manifest was produced by version {}, this is {}",
+                manifest, running
+            ),
+            ManifestError::ConfigMismatch => write!(f, "configuration differs from the manifest"),
+            ManifestError::InputChanged(path) => {
+                write!(f, "input {} differs from the manifest", path.display())
+            }
+            ManifestError::SnapshotChanged => write!(f, "snapshot differs from the manifest"),
+            ManifestError::EpochMismatch { manifest, snapshot } => write!(
+                f,
+                "manifest is for epoch {}, snapshot for epoch {}",
+                manifest, snapshot
+            ),
+            ManifestError::RootMismatch => write!(f, "snapshot root differs from the manifest"),
+            ManifestError::Snapshot(err) => write!(f, "{}", err),
+            ManifestError::Rebuild(reason) => write!(f, "inputs can't be rebuilt: {}", reason),
+            ManifestError::NotReproduced => {
+                write!(f, "inputs don't rebuild to the manifest's root")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ManifestError {}
+
+impl From<SnapshotError> for ManifestError {
+    fn from(err: SnapshotError) -> Self {
+        ManifestError::Snapshot(err)
+    }
+}
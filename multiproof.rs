@@ -0,0 +1,589 @@
+use crate::encoding::{
+    decode_commitment, encode_commitment, encode_usize, ByteReader, COMMITMENT_LEN,
+    MAX_PROOF_DEPTH,
+};
+use crate::{
+    ExclusiveAllotmentProof, LeafCommitment, MimkMerkleTree, ParseError, Root, SumCommitment,
+};
+
+// Define the StreamingVerifier struct, which folds (node index, commitment) records
+// as they arrive and only keeps the unmatched left siblings (the frontier) in memory.
+// It is bound to the tree's leaf count, so every record must be a node of that tree's
+// shape, and it remembers the positions of the leaf records it took.
+#[derive(Debug)]
+pub struct StreamingVerifier<C: SumCommitment> {
+    leaf_count: usize,
+    frontier: Vec<(usize, C)>,
+    // Positions of the single-leaf records pushed so far, ascending
+    leaves: Vec<usize>,
+    malformed: bool,
+}
+
+impl<C: SumCommitment> StreamingVerifier<C> {
+    pub fn new(leaf_count: usize) -> Self {
+        StreamingVerifier {
+            leaf_count,
+            frontier: Vec::new(),
+            leaves: Vec::new(),
+            malformed: false,
+        }
+    }
+
+    // Records must arrive left to right, as produced by `MimkMerkleTree::multiproof`: each
+    // one strictly right of the frontier's top, never inside or above it, and a node of a
+    // tree with `leaf_count` leaves. A supplied root (node 0) proves nothing and is
+    // rejected, so a one-leaf tree can't be streamed.
+    pub fn push(&mut self, node_index: usize, commitment: C) -> bool {
+        let in_order = match self.frontier.last() {
+            Some((top, _)) => is_right_of(node_index, *top),
+            None => node_index != 0,
+        };
+        let span = node_span(self.leaf_count, node_index);
+        if self.malformed || !in_order || span.is_none() {
+            self.malformed = true;
+            return false;
+        }
+        if let Some((start, 1)) = span {
+            self.leaves.push(start);
+        }
+
+        let mut index = node_index;
+        let mut current = commitment;
+        loop {
+            // A right child (even index) closes its parent when the left sibling is on top
+            let closes_parent = index != 0
+                && index.is_multiple_of(2)
+                && matches!(self.frontier.last(), Some((top, _)) if *top == index - 1);
+            if !closes_parent {
+                // Each frontier entry waits at a different level of one path
+                if self.frontier.len() >= MAX_PROOF_DEPTH {
+                    self.malformed = true;
+                    return false;
+                }
+                self.frontier.push((index, current));
+                return true;
+            }
+            let (_, left) = self.frontier.pop().unwrap();
+            // Records come from the prover, so their amounts may not fit once summed
+            if left.amount().checked_add(current.amount()).is_none() {
+                self.malformed = true;
+                return false;
+            }
+            current = C::combine_commitments(&left, &current);
+            index = (index - 1) / 2;
+        }
+    }
+
+    // Continues from a frontier another verifier stopped at, so a record stream can be
+    // checked piecewise. A frontier no verifier of this tree could have reached is
+    // malformed. Only leaves pushed after resuming are remembered.
+    pub fn resume(leaf_count: usize, frontier: Vec<(usize, C)>) -> Self {
+        let malformed = frontier.len() > MAX_PROOF_DEPTH
+            || frontier
+                .iter()
+                .any(|(index, _)| node_span(leaf_count, *index).is_none())
+            || frontier
+                .windows(2)
+                .any(|pair| !is_right_of(pair[1].0, pair[0].0));
+        StreamingVerifier {
+            leaf_count,
+            frontier,
+            leaves: Vec::new(),
+            malformed,
+        }
+    }
+
+    pub fn leaf_count(&self) -> usize {
+        self.leaf_count
+    }
+
+    pub fn frontier(&self) -> &[(usize, C)] {
+        &self.frontier
+    }
+
+    pub fn frontier_len(&self) -> usize {
+        self.frontier.len()
+    }
+
+    // Leaf positions taken so far; a full-tree audit of n leaves keeps n of them
+    pub fn leaves(&self) -> &[usize] {
+        &self.leaves
+    }
+
+    // The positions of the leaves the records proved, ascending, once they fold to
+    // `root`. Records that prove no leaf at all (e.g. just the root's two children)
+    // are rejected.
+    pub fn finish(self, root: &Root<C>) -> Option<Vec<usize>> {
+        (self.closes_at(root) && !self.leaves.is_empty()).then_some(self.leaves)
+    }
+
+    // Whether the frontier has folded into exactly `root`
+    pub(crate) fn closes_at(&self, root: &Root<C>) -> bool {
+        match self.frontier.as_slice() {
+            [(0, reconstructed)] => !self.malformed && root.matches(reconstructed),
+            _ => false,
+        }
+    }
+}
+
+// Whether node `index` lies wholly right of node `other`: lift the deeper of the two to
+// the other's depth, where heap indices increase left to right. Equal there means one
+// contains the other.
+fn is_right_of(index: usize, other: usize) -> bool {
+    let (index, other) = (index as u128 + 1, other as u128 + 1);
+    let (depth, other_depth) = (index.ilog2(), other.ilog2());
+    let common = depth.min(other_depth);
+    index >> (depth - common) > other >> (other_depth - common)
+}
+
+// The first leaf and leaf count under node `index` in a tree of `leaf_count` leaves,
+// following the split the tree is built with; None when the tree has no such node
+fn node_span(leaf_count: usize, index: usize) -> Option<(usize, usize)> {
+    if leaf_count == 0 {
+        return None;
+    }
+    // Below the leading one, the bits of index + 1 spell the path from the root
+    let path = index as u128 + 1;
+    let (mut start, mut len) = (0, leaf_count);
+    for level in (0..path.ilog2()).rev() {
+        if len == 1 {
+            return None;
+        }
+        let middle = len / 2;
+        if path >> level & 1 == 0 {
+            len = middle;
+        } else {
+            start += middle;
+            len -= middle;
+        }
+    }
+    Some((start, len))
+}
+
+// The leaf positions the records prove, or None when they don't fold to `root` in a
+// tree of `leaf_count` leaves
+pub fn verify_stream<C, I>(records: I, leaf_count: usize, root: &Root<C>) -> Option<Vec<usize>>
+where
+    C: SumCommitment,
+    I: IntoIterator<Item = (usize, C)>,
+{
+    let mut verifier = StreamingVerifier::new(leaf_count);
+    for (node_index, commitment) in records {
+        if !verifier.push(node_index, commitment) {
+            return None;
+        }
+    }
+    verifier.finish(root)
+}
+
+// Define the CompactMultiproof struct, a multiproof without node indices: a pre-order
+// bitmap says which nodes are recomputed and which are supplied, so shared path prefixes
+// are written once and each supplied commitment costs only its 40 bytes
+#[derive(Debug, Clone)]
+pub struct CompactMultiproof<C: SumCommitment> {
+    leaf_count: usize,
+    positions: Vec<usize>,
+    records: Vec<(usize, C)>,
+}
+
+impl<C: SumCommitment> CompactMultiproof<C> {
+    pub fn leaf_count(&self) -> usize {
+        self.leaf_count
+    }
+
+    // Proven leaf positions, ascending
+    pub fn positions(&self) -> &[usize] {
+        &self.positions
+    }
+
+    // The (node index, commitment) records in the order StreamingVerifier consumes them
+    pub fn records(&self) -> &[(usize, C)] {
+        &self.records
+    }
+
+    // Proven leaves with their positions, ascending. Leaf and node preimages are hashed
+    // under different prefixes, so a record recomputed from account data can't be a subtree.
+    pub fn leaves(&self) -> Vec<(usize, C)> {
+        let (_, proven) = self.layout();
+        self.records
+            .iter()
+            .zip(proven)
+            .filter_map(|((_, commitment), start)| start.map(|start| (start, commitment.clone())))
+            .collect()
+    }
+
+    // Every claimed position must be among the leaves the stream proves
+    pub fn verify(&self, root: &Root<C>) -> bool {
+        verify_stream(self.records.iter().cloned(), self.leaf_count, root).is_some_and(|proven| {
+            self.positions
+                .iter()
+                .all(|position| proven.binary_search(position).is_ok())
+        })
+    }
+
+    // leaf count | record count | shape bit count | shape bitmap | proven bitmap | commitments.
+    // Shape bits are 1 for a recomputed node and 0 for a supplied one; proven bits mark the
+    // supplied records that are proven leaves rather than sibling subtrees.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let (shape, proven) = self.layout();
+        let mut proven_bits = Bitmap::default();
+        for start in &proven {
+            proven_bits.push(start.is_some());
+        }
+
+        let mut bytes = Vec::with_capacity(
+            24 + shape.bytes.len() + proven_bits.bytes.len() + self.records.len() * COMMITMENT_LEN,
+        );
+        bytes.extend_from_slice(&encode_usize(self.leaf_count));
+        bytes.extend_from_slice(&encode_usize(self.records.len()));
+        bytes.extend_from_slice(&encode_usize(shape.len));
+        bytes.extend_from_slice(&shape.bytes);
+        bytes.extend_from_slice(&proven_bits.bytes);
+        for (_, commitment) in &self.records {
+            bytes.extend_from_slice(&encode_commitment(
+                commitment.amount(),
+                &commitment.digest(),
+            ));
+        }
+        bytes
+    }
+
+    // Replays the split the tree uses to recover the shape bits, and for each record
+    // the leaf position it proves (None for sibling subtrees)
+    fn layout(&self) -> (Bitmap, Vec<Option<usize>>) {
+        let mut shape = Bitmap::default();
+        let mut proven = Vec::with_capacity(self.records.len());
+        if self.leaf_count > 0 {
+            self.walk(0, 0, self.leaf_count, &mut shape, &mut proven);
+        }
+        (shape, proven)
+    }
+
+    fn walk(
+        &self,
+        node_index: usize,
+        start: usize,
+        len: usize,
+        shape: &mut Bitmap,
+        proven: &mut Vec<Option<usize>>,
+    ) {
+        let supplied =
+            matches!(self.records.get(proven.len()), Some((index, _)) if *index == node_index);
+        if supplied || len == 1 {
+            shape.push(false);
+            let is_proven = len == 1 && self.positions.binary_search(&start).is_ok();
+            proven.push(is_proven.then_some(start));
+            return;
+        }
+        shape.push(true);
+        let middle = len / 2;
+        self.walk(node_index * 2 + 1, start, middle, shape, proven);
+        self.walk(
+            node_index * 2 + 2,
+            start + middle,
+            len - middle,
+            shape,
+            proven,
+        );
+    }
+}
+
+impl<C: SumCommitment> TryFrom<&[u8]> for CompactMultiproof<C> {
+    type Error = ParseError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        let mut reader = ByteReader::new(bytes);
+        let leaf_count = read_usize(&mut reader)?;
+        let record_count = read_usize(&mut reader)?;
+        let shape_len = read_usize(&mut reader)?;
+        // Every supplied record costs one shape bit, so a valid proof never has more records
+        if leaf_count == 0 || record_count == 0 || record_count > shape_len {
+            return Err(ParseError::InvalidShape);
+        }
+        let shape = Bitmap::read(&mut reader, shape_len)?;
+        let proven = Bitmap::read(&mut reader, record_count)?;
+        // Checked up front so a forged count can't drive a huge allocation
+        let needed = record_count
+            .checked_mul(COMMITMENT_LEN)
+            .ok_or(ParseError::PositionOverflow)?;
+        let commitments = reader.take(needed)?;
+        reader.finish()?;
+
+        let mut decoder = ShapeDecoder {
+            shape: &shape,
+            proven: &proven,
+            shape_bit: 0,
+            records: Vec::with_capacity(record_count),
+            positions: Vec::new(),
+        };
+        decoder.walk(0, 0, leaf_count)?;
+        if decoder.shape_bit != shape_len || decoder.records.len() != record_count {
+            return Err(ParseError::InvalidShape);
+        }
+
+        let records = decoder
+            .records
+            .into_iter()
+            .zip(commitments.chunks_exact(COMMITMENT_LEN))
+            .map(|(node_index, bytes)| {
+                let (amount, digest) = decode_commitment(bytes)?;
+                Ok((node_index, C::from_parts(amount, digest)))
+            })
+            .collect::<Result<Vec<_>, ParseError>>()?;
+        Ok(CompactMultiproof {
+            leaf_count,
+            positions: decoder.positions,
+            records,
+        })
+    }
+}
+
+fn read_usize(reader: &mut ByteReader<'_>) -> Result<usize, ParseError> {
+    usize::try_from(u64::from_le_bytes(reader.take_array::<8>()?))
+        .map_err(|_| ParseError::PositionOverflow)
+}
+
+// Define the Bitmap struct, bits packed least significant first
+#[derive(Debug, Default)]
+struct Bitmap {
+    bytes: Vec<u8>,
+    len: usize,
+}
+
+impl Bitmap {
+    fn push(&mut self, bit: bool) {
+        if self.len.is_multiple_of(8) {
+            self.bytes.push(0);
+        }
+        if bit {
+            *self.bytes.last_mut().unwrap() |= 1 << (self.len % 8);
+        }
+        self.len += 1;
+    }
+
+    fn get(&self, index: usize) -> Option<bool> {
+        (index < self.len).then(|| self.bytes[index / 8] & (1 << (index % 8)) != 0)
+    }
+
+    fn read(reader: &mut ByteReader<'_>, len: usize) -> Result<Self, ParseError> {
+        let bytes = reader.take(len.div_ceil(8))?.to_vec();
+        // Padding bits must be zero so every proof has exactly one encoding
+        if !len.is_multiple_of(8) && bytes[len / 8] >> (len % 8) != 0 {
+            return Err(ParseError::InvalidShape);
+        }
+        Ok(Bitmap { bytes, len })
+    }
+}
+
+// Define the ShapeDecoder struct, which replays the shape bitmap over the tree split
+// to recover each record's node index and the proven positions
+struct ShapeDecoder<'a> {
+    shape: &'a Bitmap,
+    proven: &'a Bitmap,
+    shape_bit: usize,
+    records: Vec<usize>,
+    positions: Vec<usize>,
+}
+
+impl ShapeDecoder<'_> {
+    fn walk(&mut self, node_index: usize, start: usize, len: usize) -> Result<(), ParseError> {
+        let recomputed = self
+            .shape
+            .get(self.shape_bit)
+            .ok_or(ParseError::InvalidShape)?;
+        self.shape_bit += 1;
+        if !recomputed {
+            let is_proven = self
+                .proven
+                .get(self.records.len())
+                .ok_or(ParseError::InvalidShape)?;
+            if is_proven {
+                // Only single leaves can be proven; a subtree root is always a sibling
+                if len != 1 {
+                    return Err(ParseError::InvalidShape);
+                }
+                self.positions.push(start);
+            }
+            self.records.push(node_index);
+            return Ok(());
+        }
+        if len == 1 {
+            return Err(ParseError::InvalidShape);
+        }
+        let middle = len / 2;
+        self.walk(node_index * 2 + 1, start, middle)?;
+        self.walk(node_index * 2 + 2, start + middle, len - middle)
+    }
+}
+
+impl<C, P> MimkMerkleTree<C, P>
+where
+    C: SumCommitment,
+    P: ExclusiveAllotmentProof<C>,
+{
+    // Emits the proven leaves and the roots of all untouched subtrees, left to right
+    pub fn multiproof(&self, positions: &[usize]) -> Vec<(usize, C)> {
+        let mut positions: Vec<usize> = positions
+            .iter()
+            .copied()
+            .filter(|&position| position < self.leaf_nodes.len())
+            .collect();
+        positions.sort_unstable();
+        positions.dedup();
+        self.multiproof_records(Some(&positions), 0, usize::MAX).0
+    }
+
+    // At most `limit` multiproof records covering the leaves from `start` on, and the leaf
+    // the next batch starts from (None after the last record). Subtrees left of `start`
+    // are skipped without being visited, so paging through a stream costs each page only
+    // its own records plus one root-to-leaf descent. `positions` must be sorted and
+    // deduplicated; None proves every leaf, as a full-tree audit does.
+    pub fn multiproof_records(
+        &self,
+        positions: Option<&[usize]>,
+        start: usize,
+        limit: usize,
+    ) -> (Vec<(usize, C)>, Option<usize>) {
+        let mut page = RecordPage {
+            start,
+            limit,
+            records: Vec::new(),
+            resume: None,
+        };
+        if !self.leaf_nodes.is_empty() {
+            self.collect_multiproof(positions, 0, 0, &self.leaf_nodes, &mut page);
+        }
+        (page.records, page.resume)
+    }
+
+    pub fn compact_multiproof(&self, positions: &[usize]) -> CompactMultiproof<C> {
+        let mut positions: Vec<usize> = positions
+            .iter()
+            .copied()
+            .filter(|&position| position < self.leaf_nodes.len())
+            .collect();
+        positions.sort_unstable();
+        positions.dedup();
+        CompactMultiproof {
+            leaf_count: self.leaf_nodes.len(),
+            records: self.multiproof(&positions),
+            positions,
+        }
+    }
+
+    fn collect_multiproof(
+        &self,
+        positions: Option<&[usize]>,
+        offset: usize,
+        node_index: usize,
+        nodes: &[C::Leaf],
+        page: &mut RecordPage<C>,
+    ) {
+        if offset + nodes.len() <= page.start || page.resume.is_some() {
+            return;
+        }
+        let touched = positions.is_none_or(|positions| !positions.is_empty());
+        if !touched || nodes.len() == 1 {
+            if page.records.len() == page.limit {
+                page.resume = Some(offset);
+                return;
+            }
+            let node = match touched {
+                true => nodes[0].to_node(),
+                false => self.build_merkle_tree(node_index, nodes),
+            };
+            page.records.push((node_index, node));
+            return;
+        }
+
+        let middle = nodes.len() / 2;
+        // Positions are sorted, so each half's are one contiguous run
+        let (left, right) = match positions {
+            Some(positions) => {
+                let split = positions.partition_point(|&position| position < offset + middle);
+                (Some(&positions[..split]), Some(&positions[split..]))
+            }
+            None => (None, None),
+        };
+        self.collect_multiproof(left, offset, node_index * 2 + 1, &nodes[..middle], page);
+        self.collect_multiproof(
+            right,
+            offset + middle,
+            node_index * 2 + 2,
+            &nodes[middle..],
+            page,
+        );
+    }
+}
+
+// Where a batch of multiproof records starts, how many it may hold and where the next
+// one resumes
+struct RecordPage<C> {
+    start: usize,
+    limit: usize,
+    records: Vec<(usize, C)>,
+    resume: Option<usize>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MerkleProof, MimiSumCommitment};
+
+    type Tree = MimkMerkleTree<MimiSumCommitment, MerkleProof<MimiSumCommitment>>;
+
+    fn tree(leaf_count: u64) -> Tree {
+        let leaves: Vec<u64> = (1..=leaf_count).map(|leaf| leaf * 100).collect();
+        Tree::builder().build(&leaves).unwrap()
+    }
+
+    #[test]
+    fn streams_return_the_leaves_they_prove() {
+        let tree = tree(8);
+        let records = tree.multiproof(&[5]);
+        let indices: Vec<usize> = records.iter().map(|(index, _)| *index).collect();
+        assert_eq!(indices, [1, 11, 12, 6]);
+        assert_eq!(
+            verify_stream(records.clone(), 8, &tree.commit()),
+            Some(vec![4, 5])
+        );
+        // Node 11 isn't a leaf of a five-leaf tree
+        assert_eq!(verify_stream(records, 5, &tree.commit()), None);
+
+        let all = tree.multiproof_records(None, 0, usize::MAX).0;
+        assert_eq!(
+            verify_stream(all, 8, &tree.commit()),
+            Some((0..8).collect())
+        );
+        assert!(tree.compact_multiproof(&[2, 7]).verify(&tree.commit()));
+    }
+
+    #[test]
+    fn root_children_alone_are_rejected() {
+        let tree = tree(4);
+        let proof = tree.prove(0);
+        let siblings = proof.siblings();
+        let left = MimiSumCommitment::combine_commitments(&proof.leaf().to_node(), &siblings[0].0);
+        let records = vec![(1, left), (2, siblings[1].0.clone())];
+
+        let mut verifier = StreamingVerifier::new(4);
+        for (index, commitment) in records.clone() {
+            assert!(verifier.push(index, commitment));
+        }
+        assert!(verifier.closes_at(&tree.commit()));
+        assert!(verifier.leaves().is_empty());
+        assert_eq!(verifier.finish(&tree.commit()), None);
+        assert_eq!(verify_stream(records, 4, &tree.commit()), None);
+    }
+
+    #[test]
+    fn nodes_outside_the_shape_are_rejected() {
+        let tree = tree(3);
+        let leaf = tree.prove(0).leaf().to_node();
+        // Node 1 is the single leaf left of the split, so it has no children
+        let mut verifier = StreamingVerifier::new(3);
+        assert!(!verifier.push(3, leaf.clone()));
+        let resumed = StreamingVerifier::resume(3, vec![(3, leaf)]);
+        assert_eq!(resumed.finish(&tree.commit()), None);
+    }
+}
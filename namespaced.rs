@@ -0,0 +1,369 @@
+use std::fmt;
+use std::str::FromStr;
+
+use sha2::{Digest as _, Sha256};
+
+use crate::arena::fold_shape;
+use crate::encoding::{encode_usize, ByteReader, MAX_PROOF_DEPTH};
+use crate::{Leaf, ParseError, VerifyError, LEAF_PREFIX, NODE_PREFIX};
+
+pub const NAMESPACE_LEN: usize = 8;
+
+// Domain bytes keep a leaf from ever hashing like an inner node
+
+// Bytes in one encoded node: min and max namespace, amount, digest
+pub const NAMESPACED_NODE_LEN: usize = 2 * NAMESPACE_LEN + 8 + 32;
+
+// Define the NamespaceId struct, the fixed-width namespace a leaf belongs to, e.g. a
+// rollup or an asset. Namespaces order bytewise and print as hex.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NamespaceId(pub [u8; NAMESPACE_LEN]);
+
+impl fmt::Display for NamespaceId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&hex::encode(self.0))
+    }
+}
+
+impl FromStr for NamespaceId {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = hex::decode(s).map_err(|_| ParseError::InvalidHex)?;
+        let found = bytes.len();
+        bytes
+            .try_into()
+            .map(NamespaceId)
+            .map_err(|_| ParseError::InvalidLength {
+                expected: NAMESPACE_LEN,
+                found,
+            })
+    }
+}
+
+// Define the NamespacedNode struct, a sum node that also records the smallest and largest
+// namespace below it, as in Celestia's namespaced Merkle trees. Both bounds are hashed,
+// so a proof can't hide a namespace's leaves in a sibling that claims not to hold any.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NamespacedNode {
+    pub min: NamespaceId,
+    pub max: NamespaceId,
+    pub amount: u64,
+    pub digest: [u8; 32],
+}
+
+impl NamespacedNode {
+    // Hashes 0 | namespace | amount | leaf encoding
+    pub fn from_leaf<L: Leaf>(namespace: NamespaceId, leaf: &L) -> Self {
+        let digest = Sha256::new()
+            .chain_update([LEAF_PREFIX])
+            .chain_update(namespace.0)
+            .chain_update(leaf.amount().to_le_bytes())
+            .chain_update(leaf.encode_for_hash())
+            .finalize();
+        NamespacedNode {
+            min: namespace,
+            max: namespace,
+            amount: leaf.amount(),
+            digest: digest.into(),
+        }
+    }
+
+    // Hashes 1 | left encoding | right encoding. Siblings are in namespace order, so the
+    // parent spans the left's min to the right's max.
+    pub fn combine(left: &Self, right: &Self) -> Option<Self> {
+        let digest = Sha256::new()
+            .chain_update([NODE_PREFIX])
+            .chain_update(left.to_bytes())
+            .chain_update(right.to_bytes())
+            .finalize();
+        Some(NamespacedNode {
+            min: left.min,
+            max: right.max,
+            amount: left.amount.checked_add(right.amount)?,
+            digest: digest.into(),
+        })
+    }
+
+    pub fn to_bytes(&self) -> [u8; NAMESPACED_NODE_LEN] {
+        let mut bytes = [0u8; NAMESPACED_NODE_LEN];
+        bytes[..NAMESPACE_LEN].copy_from_slice(&self.min.0);
+        bytes[NAMESPACE_LEN..2 * NAMESPACE_LEN].copy_from_slice(&self.max.0);
+        bytes[2 * NAMESPACE_LEN..2 * NAMESPACE_LEN + 8].copy_from_slice(&self.amount.to_le_bytes());
+        bytes[2 * NAMESPACE_LEN + 8..].copy_from_slice(&self.digest);
+        bytes
+    }
+
+    fn read(reader: &mut ByteReader<'_>) -> Result<Self, ParseError> {
+        Ok(NamespacedNode {
+            min: NamespaceId(reader.take_array()?),
+            max: NamespaceId(reader.take_array()?),
+            amount: u64::from_le_bytes(reader.take_array()?),
+            digest: reader.take_array()?,
+        })
+    }
+}
+
+// Define the NamespacedTree struct, a sum tree over leaves sorted by namespace, with the
+// same halving shape as `MimkMerkleTree`
+#[derive(Debug, Clone)]
+pub struct NamespacedTree {
+    leaves: Vec<NamespacedNode>,
+}
+
+impl NamespacedTree {
+    // Leaves must already be in namespace order; within a namespace any order is kept
+    pub fn from_leaves<L: Leaf>(leaves: &[(NamespaceId, L)]) -> Result<Self, NamespaceError> {
+        Self::from_nodes(
+            leaves
+                .iter()
+                .map(|(namespace, leaf)| NamespacedNode::from_leaf(*namespace, leaf))
+                .collect(),
+        )
+    }
+
+    pub fn from_nodes(leaves: Vec<NamespacedNode>) -> Result<Self, NamespaceError> {
+        if let Some(position) = leaves.windows(2).position(|pair| pair[0].max > pair[1].min) {
+            return Err(NamespaceError::Unsorted(position + 1));
+        }
+        let tree = NamespacedTree { leaves };
+        if !tree.leaves.is_empty() && tree.root().is_none() {
+            return Err(NamespaceError::Verify(VerifyError::SumOverflow));
+        }
+        Ok(tree)
+    }
+
+    pub fn leaves(&self) -> &[NamespacedNode] {
+        &self.leaves
+    }
+
+    pub fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+
+    pub fn root(&self) -> Option<NamespacedNode> {
+        fold_range(&self.leaves)
+    }
+
+    // Every leaf of `namespace`, or proof that there is none. Leaves are sorted, so the
+    // namespace is one contiguous range.
+    pub fn prove_namespace(&self, namespace: NamespaceId) -> NamespaceProof {
+        let start = self.leaves.partition_point(|leaf| leaf.min < namespace);
+        let end = start + self.leaves[start..].partition_point(|leaf| leaf.min == namespace);
+        let mut nodes = Vec::new();
+        collect_outside(&self.leaves, 0, start, end, &mut nodes);
+        NamespaceProof {
+            namespace,
+            start,
+            end,
+            leaf_count: self.leaves.len(),
+            nodes,
+        }
+    }
+}
+
+fn fold_range(nodes: &[NamespacedNode]) -> Option<NamespacedNode> {
+    // An overflowing sum poisons every node above it; `from_nodes` rejects such trees
+    fold_shape(
+        nodes.len(),
+        |position| Some(nodes[position].clone()),
+        |left: &Option<NamespacedNode>, right: &Option<NamespacedNode>| {
+            NamespacedNode::combine(left.as_ref()?, right.as_ref()?)
+        },
+    )
+    .flatten()
+}
+
+// Subtrees that don't reach into [start, end) are taken whole; for an empty range that
+// is every subtree not straddling `start`
+fn outside(offset: usize, len: usize, start: usize, end: usize) -> bool {
+    offset + len <= start || offset >= end
+}
+
+// Pushes, left to right, the largest subtrees outside the range
+fn collect_outside(
+    nodes: &[NamespacedNode],
+    offset: usize,
+    start: usize,
+    end: usize,
+    out: &mut Vec<NamespacedNode>,
+) {
+    if outside(offset, nodes.len(), start, end) {
+        out.extend(fold_range(nodes));
+    } else if nodes.len() > 1 {
+        let (left, right) = nodes.split_at(nodes.len() / 2);
+        collect_outside(left, offset, start, end, out);
+        collect_outside(right, offset + left.len(), start, end, out);
+    }
+}
+
+// Define the NamespaceProof struct, a range proof that leaves [start, end) are exactly
+// the leaves of one namespace. Every subtree left of the range must end below the
+// namespace and every one right of it start above, which makes the range complete; an
+// empty range proves the namespace absent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NamespaceProof {
+    pub namespace: NamespaceId,
+    pub start: usize,
+    pub end: usize,
+    pub leaf_count: usize,
+    // Subtrees outside the range, left to right
+    pub nodes: Vec<NamespacedNode>,
+}
+
+impl NamespaceProof {
+    // Checks the namespace's leaves against the root and returns their total
+    pub fn verify(
+        &self,
+        root: &NamespacedNode,
+        leaves: &[NamespacedNode],
+    ) -> Result<u64, NamespaceError> {
+        if self.start > self.end
+            || self.end > self.leaf_count
+            || leaves.len() != self.end - self.start
+        {
+            return Err(NamespaceError::LeafCount);
+        }
+        if let Some(position) = leaves.iter().position(|leaf| {
+            leaf.min != self.namespace || leaf.max != self.namespace
+        }) {
+            return Err(NamespaceError::WrongNamespace(self.start + position));
+        }
+        let mut nodes = self.nodes.iter();
+        let rebuilt = self.rebuild(0, self.leaf_count, leaves, &mut nodes)?;
+        if nodes.next().is_some() {
+            return Err(NamespaceError::Incomplete);
+        }
+        if rebuilt.digest != root.digest || rebuilt.min != root.min || rebuilt.max != root.max {
+            return Err(NamespaceError::Verify(VerifyError::DigestMismatch));
+        }
+        if rebuilt.amount != root.amount {
+            return Err(NamespaceError::Verify(VerifyError::SumMismatch {
+                root: root.amount,
+                proof: rebuilt.amount,
+            }));
+        }
+        leaves
+            .iter()
+            .try_fold(0u64, |total, leaf| total.checked_add(leaf.amount))
+            .ok_or(NamespaceError::Verify(VerifyError::SumOverflow))
+    }
+
+    // Recomputes the subtree [offset, offset + len), checking that proof nodes left of the
+    // range end below the namespace and those right of it start above
+    fn rebuild<'a>(
+        &self,
+        offset: usize,
+        len: usize,
+        leaves: &[NamespacedNode],
+        nodes: &mut impl Iterator<Item = &'a NamespacedNode>,
+    ) -> Result<NamespacedNode, NamespaceError> {
+        if outside(offset, len, self.start, self.end) {
+            let node = nodes.next().ok_or(NamespaceError::Incomplete)?;
+            let complete = if offset + len <= self.start {
+                node.max < self.namespace
+            } else {
+                node.min > self.namespace
+            };
+            if !complete || node.min > node.max {
+                return Err(NamespaceError::Incomplete);
+            }
+            return Ok(node.clone());
+        }
+        if len == 1 {
+            return Ok(leaves[offset - self.start].clone());
+        }
+        let middle = len / 2;
+        let left = self.rebuild(offset, middle, leaves, nodes)?;
+        let right = self.rebuild(offset + middle, len - middle, leaves, nodes)?;
+        if left.max > right.min {
+            return Err(NamespaceError::Incomplete);
+        }
+        NamespacedNode::combine(&left, &right)
+            .ok_or(NamespaceError::Verify(VerifyError::SumOverflow))
+    }
+
+    // namespace | start | end | leaf count | node count (u8) | nodes
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(
+            NAMESPACE_LEN + 25 + self.nodes.len() * NAMESPACED_NODE_LEN,
+        );
+        bytes.extend_from_slice(&self.namespace.0);
+        bytes.extend_from_slice(&encode_usize(self.start));
+        bytes.extend_from_slice(&encode_usize(self.end));
+        bytes.extend_from_slice(&encode_usize(self.leaf_count));
+        bytes.push(self.nodes.len() as u8);
+        for node in &self.nodes {
+            bytes.extend_from_slice(&node.to_bytes());
+        }
+        bytes
+    }
+}
+
+impl TryFrom<&[u8]> for NamespaceProof {
+    type Error = ParseError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        let mut reader = ByteReader::new(bytes);
+        let namespace = NamespaceId(reader.take_array()?);
+        let mut position = || {
+            usize::try_from(u64::from_le_bytes(reader.take_array()?))
+                .map_err(|_| ParseError::PositionOverflow)
+        };
+        let (start, end, leaf_count) = (position()?, position()?, position()?);
+        // A range proof has at most two subtrees per level, one on each side
+        let count = reader.take_array::<1>()?[0] as usize;
+        if count > 2 * MAX_PROOF_DEPTH {
+            return Err(ParseError::TooDeep(count));
+        }
+        let mut nodes = Vec::with_capacity(count);
+        for _ in 0..count {
+            nodes.push(NamespacedNode::read(&mut reader)?);
+        }
+        reader.finish()?;
+        Ok(NamespaceProof {
+            namespace,
+            start,
+            end,
+            leaf_count,
+            nodes,
+        })
+    }
+}
+
+// Define the NamespaceError enum for namespaced trees that can't be built and namespace
+// proofs that don't hold
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NamespaceError {
+    // The leaf at this position sorts before the one ahead of it
+    Unsorted(usize),
+    LeafCount,
+    WrongNamespace(usize),
+    // Proof nodes are missing, left over, or could hide leaves of the namespace
+    Incomplete,
+    Verify(VerifyError),
+}
+
+impl fmt::Display for NamespaceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NamespaceError::Unsorted(position) => {
+                write!(f, "leaf {} is out of namespace order", position)
+            }
+            NamespaceError::LeafCount => write!(f, "leaf count does not match the proof range"),
+            NamespaceError::WrongNamespace(position) => {
+                write!(f, "leaf {} belongs to another namespace", position)
+            }
+            NamespaceError::Incomplete => {
+                write!(f, "proof does not show the namespace range is complete")
+            }
+            NamespaceError::Verify(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for NamespaceError {}
@@ -0,0 +1,386 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::{ExclusiveAllotmentProof, LeafCommitment, MimkMerkleTree, SumCommitment};
+
+// Define the NodeStore trait, where an epoch's nodes are persisted by node index (root 0,
+// children of i at 2i + 1 and 2i + 2) so proofs can be served without rebuilding the tree
+pub trait NodeStore<C: SumCommitment> {
+    // Records the epoch's shape; called once before its levels are written
+    fn begin_epoch(&mut self, epoch: u64, leaf_count: usize) -> Result<(), NodeStoreError>;
+
+    fn leaf_count(&mut self, epoch: u64) -> Result<usize, NodeStoreError>;
+
+    // Writes every node `level` edges below the root
+    fn write_level(
+        &mut self,
+        epoch: u64,
+        level: usize,
+        nodes: &[(usize, C)],
+    ) -> Result<(), NodeStoreError>;
+
+    // Looks up several nodes at once, in the order of `node_indexes`
+    fn nodes(&mut self, epoch: u64, node_indexes: &[usize]) -> Result<Vec<C>, NodeStoreError>;
+}
+
+// Define the MemoryNodeStore struct, the in-process reference store
+#[derive(Debug, Clone)]
+pub struct MemoryNodeStore<C: SumCommitment> {
+    leaf_counts: HashMap<u64, usize>,
+    nodes: HashMap<(u64, usize), C>,
+}
+
+impl<C: SumCommitment> MemoryNodeStore<C> {
+    pub fn new() -> Self {
+        MemoryNodeStore {
+            leaf_counts: HashMap::new(),
+            nodes: HashMap::new(),
+        }
+    }
+}
+
+impl<C: SumCommitment> Default for MemoryNodeStore<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C: SumCommitment> NodeStore<C> for MemoryNodeStore<C> {
+    fn begin_epoch(&mut self, epoch: u64, leaf_count: usize) -> Result<(), NodeStoreError> {
+        if self.leaf_counts.insert(epoch, leaf_count).is_some() {
+            return Err(NodeStoreError::EpochExists(epoch));
+        }
+        Ok(())
+    }
+
+    fn leaf_count(&mut self, epoch: u64) -> Result<usize, NodeStoreError> {
+        self.leaf_counts
+            .get(&epoch)
+            .copied()
+            .ok_or(NodeStoreError::UnknownEpoch(epoch))
+    }
+
+    fn write_level(
+        &mut self,
+        epoch: u64,
+        _level: usize,
+        nodes: &[(usize, C)],
+    ) -> Result<(), NodeStoreError> {
+        for (node_index, node) in nodes {
+            self.nodes.insert((epoch, *node_index), node.clone());
+        }
+        Ok(())
+    }
+
+    fn nodes(&mut self, epoch: u64, node_indexes: &[usize]) -> Result<Vec<C>, NodeStoreError> {
+        node_indexes
+            .iter()
+            .map(|node_index| {
+                self.nodes
+                    .get(&(epoch, *node_index))
+                    .cloned()
+                    .ok_or(NodeStoreError::Missing {
+                        epoch,
+                        node_index: *node_index,
+                    })
+            })
+            .collect()
+    }
+}
+
+impl<C, P> MimkMerkleTree<C, P>
+where
+    C: SumCommitment,
+    P: ExclusiveAllotmentProof<C>,
+{
+    // Every node (leaves included) grouped by depth, each level ordered by node index
+    pub fn levels(&self) -> Vec<Vec<(usize, C)>> {
+        let mut levels = Vec::new();
+        self.collect_levels(0, 0, &self.leaf_nodes, &mut levels);
+        for level in &mut levels {
+            level.sort_unstable_by_key(|(node_index, _)| *node_index);
+        }
+        levels
+    }
+
+    // Writes the tree level by level, one `write_level` call per depth
+    pub fn write_nodes<S: NodeStore<C>>(
+        &self,
+        store: &mut S,
+        epoch: u64,
+    ) -> Result<(), NodeStoreError> {
+        store.begin_epoch(epoch, self.leaf_nodes.len())?;
+        for (level, nodes) in self.levels().iter().enumerate() {
+            store.write_level(epoch, level, nodes)?;
+        }
+        Ok(())
+    }
+
+    // Pushes each node after its subtree, so a parent's children are the last two nodes
+    // one level down and are hashed in place rather than handed back up by value
+    fn collect_levels(
+        &self,
+        node_index: usize,
+        depth: usize,
+        nodes: &[C::Leaf],
+        levels: &mut Vec<Vec<(usize, C)>>,
+    ) {
+        if levels.len() <= depth {
+            levels.resize_with(depth + 1, Vec::new);
+        }
+        let node = if nodes.len() == 1 {
+            nodes[0].to_node()
+        } else {
+            let middle = nodes.len() / 2;
+            let (left, right) = nodes.split_at(middle);
+            self.collect_levels(node_index * 2 + 1, depth + 1, left, levels);
+            self.collect_levels(node_index * 2 + 2, depth + 1, right, levels);
+            let children = &levels[depth + 1][levels[depth + 1].len() - 2..];
+            C::combine_commitments(&children[0].1, &children[1].1)
+        };
+        levels[depth].push((node_index, node));
+    }
+}
+
+// Node index of the leaf at `position` and of every sibling on its path, siblings from
+// the leaf up. The tree splits each range at half its length, so the path only depends
+// on the leaf count.
+pub fn proof_path(leaf_count: usize, position: usize) -> Option<(usize, Vec<(usize, bool)>)> {
+    if position >= leaf_count {
+        return None;
+    }
+    let (mut node_index, mut len, mut position) = (0, leaf_count, position);
+    let mut siblings = Vec::new();
+    while len > 1 {
+        let middle = len / 2;
+        if position < middle {
+            siblings.push((node_index * 2 + 2, false));
+            node_index = node_index * 2 + 1;
+            len = middle;
+        } else {
+            siblings.push((node_index * 2 + 1, true));
+            node_index = node_index * 2 + 2;
+            position -= middle;
+            len -= middle;
+        }
+    }
+    siblings.reverse();
+    Some((node_index, siblings))
+}
+
+// Reads one proof back with a single batched lookup
+pub fn prove_from_store<C, P, S>(
+    store: &mut S,
+    epoch: u64,
+    position: usize,
+) -> Result<P, NodeStoreError>
+where
+    C: SumCommitment,
+    P: ExclusiveAllotmentProof<C>,
+    S: NodeStore<C>,
+{
+    let leaf_count = store.leaf_count(epoch)?;
+    let (leaf_index, path) =
+        proof_path(leaf_count, position).ok_or(NodeStoreError::PositionOutOfRange(position))?;
+    let mut node_indexes = vec![leaf_index];
+    node_indexes.extend(path.iter().map(|(node_index, _)| *node_index));
+    let mut nodes = store.nodes(epoch, &node_indexes)?.into_iter();
+    let leaf = nodes
+        .next()
+        .map(|node| C::Leaf::from_parts(node.amount(), node.digest()))
+        .ok_or(NodeStoreError::Missing {
+            epoch,
+            node_index: leaf_index,
+        })?;
+    let siblings = nodes
+        .zip(path)
+        .map(|(node, (_, sibling_on_left))| (node, sibling_on_left))
+        .collect();
+    Ok(P::new(position, leaf, siblings))
+}
+
+#[cfg(feature = "postgres")]
+pub use postgres_store::PostgresNodeStore;
+
+#[cfg(feature = "postgres")]
+mod postgres_store {
+    use std::collections::HashMap;
+
+    use generic_array::GenericArray;
+    use postgres::binary_copy::BinaryCopyInWriter;
+    use postgres::types::Type;
+    use postgres::Client;
+
+    use super::{NodeStore, NodeStoreError};
+    use crate::SumCommitment;
+
+    // The primary keys double as the lookup indexes
+    const SCHEMA: &str = "
+        CREATE TABLE IF NOT EXISTS mimi_epochs (
+            epoch BIGINT PRIMARY KEY,
+            leaf_count BIGINT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS mimi_nodes (
+            epoch BIGINT NOT NULL,
+            node_index BIGINT NOT NULL,
+            level INTEGER NOT NULL,
+            amount BIGINT NOT NULL,
+            digest BYTEA NOT NULL,
+            PRIMARY KEY (epoch, node_index)
+        );
+    ";
+
+    const COPY_NODES: &str =
+        "COPY mimi_nodes (epoch, node_index, level, amount, digest) FROM STDIN BINARY";
+
+    // Define the PostgresNodeStore struct, for operators keeping trees in their managed
+    // database. Levels are bulk-loaded with binary COPY; amounts are stored as the
+    // two's-complement BIGINT with the same bits, since Postgres has no unsigned type.
+    pub struct PostgresNodeStore {
+        client: Client,
+    }
+
+    impl PostgresNodeStore {
+        // Creates the tables when they don't exist yet
+        pub fn new(mut client: Client) -> Result<Self, NodeStoreError> {
+            client.batch_execute(SCHEMA).map_err(database)?;
+            Ok(PostgresNodeStore { client })
+        }
+
+        pub fn into_client(self) -> Client {
+            self.client
+        }
+    }
+
+    impl<C: SumCommitment> NodeStore<C> for PostgresNodeStore {
+        fn begin_epoch(&mut self, epoch: u64, leaf_count: usize) -> Result<(), NodeStoreError> {
+            let inserted = self
+                .client
+                .execute(
+                    "INSERT INTO mimi_epochs (epoch, leaf_count) VALUES ($1, $2) \
+                     ON CONFLICT DO NOTHING",
+                    &[&(epoch as i64), &(leaf_count as i64)],
+                )
+                .map_err(database)?;
+            if inserted == 0 {
+                return Err(NodeStoreError::EpochExists(epoch));
+            }
+            Ok(())
+        }
+
+        fn leaf_count(&mut self, epoch: u64) -> Result<usize, NodeStoreError> {
+            let row = self
+                .client
+                .query_opt(
+                    "SELECT leaf_count FROM mimi_epochs WHERE epoch = $1",
+                    &[&(epoch as i64)],
+                )
+                .map_err(database)?
+                .ok_or(NodeStoreError::UnknownEpoch(epoch))?;
+            let leaf_count: i64 = row.try_get(0).map_err(database)?;
+            Ok(leaf_count as usize)
+        }
+
+        fn write_level(
+            &mut self,
+            epoch: u64,
+            level: usize,
+            nodes: &[(usize, C)],
+        ) -> Result<(), NodeStoreError> {
+            let sink = self.client.copy_in(COPY_NODES).map_err(database)?;
+            let mut writer = BinaryCopyInWriter::new(
+                sink,
+                &[Type::INT8, Type::INT8, Type::INT4, Type::INT8, Type::BYTEA],
+            );
+            for (node_index, node) in nodes {
+                let digest = node.digest();
+                writer
+                    .write(&[
+                        &(epoch as i64),
+                        &(*node_index as i64),
+                        &(level as i32),
+                        &(node.amount() as i64),
+                        &digest.as_slice(),
+                    ])
+                    .map_err(database)?;
+            }
+            writer.finish().map_err(database)?;
+            Ok(())
+        }
+
+        fn nodes(
+            &mut self,
+            epoch: u64,
+            node_indexes: &[usize],
+        ) -> Result<Vec<C>, NodeStoreError> {
+            let keys: Vec<i64> = node_indexes.iter().map(|index| *index as i64).collect();
+            let rows = self
+                .client
+                .query(
+                    "SELECT node_index, amount, digest FROM mimi_nodes \
+                     WHERE epoch = $1 AND node_index = ANY($2)",
+                    &[&(epoch as i64), &keys],
+                )
+                .map_err(database)?;
+            let mut found = HashMap::with_capacity(rows.len());
+            for row in rows {
+                let node_index: i64 = row.try_get(0).map_err(database)?;
+                let amount: i64 = row.try_get(1).map_err(database)?;
+                let digest: Vec<u8> = row.try_get(2).map_err(database)?;
+                if digest.len() != 32 {
+                    return Err(NodeStoreError::Corrupt {
+                        epoch,
+                        node_index: node_index as usize,
+                    });
+                }
+                let node = C::from_parts(amount as u64, GenericArray::clone_from_slice(&digest));
+                found.insert(node_index as usize, node);
+            }
+            node_indexes
+                .iter()
+                .map(|node_index| {
+                    found.get(node_index).cloned().ok_or(NodeStoreError::Missing {
+                        epoch,
+                        node_index: *node_index,
+                    })
+                })
+                .collect()
+        }
+    }
+
+    fn database(err: postgres::Error) -> NodeStoreError {
+        NodeStoreError::Database(err.to_string())
+    }
+}
+
+// Define the NodeStoreError enum for nodes that couldn't be written or read back
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NodeStoreError {
+    Database(String),
+    EpochExists(u64),
+    UnknownEpoch(u64),
+    PositionOutOfRange(usize),
+    Missing { epoch: u64, node_index: usize },
+    Corrupt { epoch: u64, node_index: usize },
+}
+
+impl fmt::Display for NodeStoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NodeStoreError::Database(err) => write!(f, "node store query failed: {}", err),
+            NodeStoreError::EpochExists(epoch) => write!(f, "epoch {} is already stored", epoch),
+            NodeStoreError::UnknownEpoch(epoch) => write!(f, "epoch {} isn't stored", epoch),
+            NodeStoreError::PositionOutOfRange(position) => {
+                write!(f, "leaf {} is outside the tree", position)
+            }
+            NodeStoreError::Missing { epoch, node_index } => {
+                write!(f, "node {} of epoch {} is missing", node_index, epoch)
+            }
+            NodeStoreError::Corrupt { epoch, node_index } => {
+                write!(f, "node {} of epoch {} has a malformed digest", node_index, epoch)
+            }
+        }
+    }
+}
+
+impl std::error::Error for NodeStoreError {}
@@ -0,0 +1,349 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::Range;
+use std::sync::Mutex;
+
+use crate::encoding::{
+    decode_commitment, encode_commitment, encode_usize, ByteReader, COMMITMENT_LEN,
+};
+use crate::node_store::proof_path;
+use crate::scheduler::SnapshotStore;
+use crate::snapshot::{Snapshot, SnapshotError};
+use crate::{
+    ExclusiveAllotmentProof, LeafCommitment, MerkleProof, MimkMerkleTree, ParseError, SumCommitment,
+};
+
+const NODES_MAGIC: &[u8; 8] = b"MIMINOD1";
+const NODES_HEADER_LEN: usize = 32;
+// Presence flag followed by the commitment
+const SLOT_LEN: usize = 1 + COMMITMENT_LEN;
+
+// Define the ObjectStore trait, the operations the proof server needs from S3-compatible
+// storage: whole-object writes and reads, and byte-range reads (`range.end` exclusive)
+pub trait ObjectStore {
+    fn put(&self, key: &str, bytes: Vec<u8>) -> Result<(), ObjectError>;
+    fn get(&self, key: &str) -> Result<Vec<u8>, ObjectError>;
+    fn get_range(&self, key: &str, range: Range<u64>) -> Result<Vec<u8>, ObjectError>;
+}
+
+// Define the MemoryObjects struct, an in-process bucket for local runs
+#[derive(Debug, Default)]
+pub struct MemoryObjects {
+    objects: Mutex<HashMap<String, Vec<u8>>>,
+}
+
+impl MemoryObjects {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl ObjectStore for MemoryObjects {
+    fn put(&self, key: &str, bytes: Vec<u8>) -> Result<(), ObjectError> {
+        let mut objects = self.objects.lock().map_err(|_| poisoned())?;
+        objects.insert(key.to_string(), bytes);
+        Ok(())
+    }
+
+    fn get(&self, key: &str) -> Result<Vec<u8>, ObjectError> {
+        let objects = self.objects.lock().map_err(|_| poisoned())?;
+        objects
+            .get(key)
+            .cloned()
+            .ok_or_else(|| ObjectError::NotFound(key.to_string()))
+    }
+
+    fn get_range(&self, key: &str, range: Range<u64>) -> Result<Vec<u8>, ObjectError> {
+        let objects = self.objects.lock().map_err(|_| poisoned())?;
+        let object = objects
+            .get(key)
+            .ok_or_else(|| ObjectError::NotFound(key.to_string()))?;
+        let start = (range.start as usize).min(object.len());
+        let end = (range.end as usize).clamp(start, object.len());
+        Ok(object[start..end].to_vec())
+    }
+}
+
+fn poisoned() -> ObjectError {
+    ObjectError::Backend("object map lock is poisoned".to_string())
+}
+
+// Node file: magic | epoch | leaf count | slot count, then one slot per node index (root
+// 0, children of i at 2i + 1 and 2i + 2). Slots are fixed-size so any node is one range
+// read away; indexes the tree doesn't use hold an empty slot, at most four slots per leaf.
+pub fn encode_nodes<C, P>(epoch: u64, tree: &MimkMerkleTree<C, P>) -> Vec<u8>
+where
+    C: SumCommitment,
+    P: ExclusiveAllotmentProof<C>,
+{
+    let levels = tree.levels();
+    let slot_count = levels
+        .iter()
+        .flatten()
+        .map(|(node_index, _)| node_index + 1)
+        .max()
+        .unwrap_or(0);
+    let mut bytes = vec![0u8; NODES_HEADER_LEN + slot_count * SLOT_LEN];
+    bytes[..8].copy_from_slice(NODES_MAGIC);
+    bytes[8..16].copy_from_slice(&epoch.to_le_bytes());
+    bytes[16..24].copy_from_slice(&encode_usize(tree.len()));
+    bytes[24..32].copy_from_slice(&encode_usize(slot_count));
+    for (node_index, node) in levels.iter().flatten() {
+        let offset = NODES_HEADER_LEN + node_index * SLOT_LEN;
+        bytes[offset] = 1;
+        bytes[offset + 1..offset + SLOT_LEN]
+            .copy_from_slice(&encode_commitment(node.amount(), &node.digest()));
+    }
+    bytes
+}
+
+// Define the ObjectSnapshotStore struct, which keeps each epoch under `prefix` as
+// `epoch-<n>.snapshot` (full snapshot), `epoch-<n>.nodes` (range-readable nodes) and
+// any proof archives beside them, so proof servers in front of it need no local state
+#[derive(Debug)]
+pub struct ObjectSnapshotStore<S: ObjectStore> {
+    store: S,
+    prefix: String,
+}
+
+impl<S: ObjectStore> ObjectSnapshotStore<S> {
+    pub fn new(store: S, prefix: impl Into<String>) -> Self {
+        ObjectSnapshotStore {
+            store,
+            prefix: prefix.into().trim_end_matches('/').to_string(),
+        }
+    }
+
+    pub fn key(&self, epoch: u64, suffix: &str) -> String {
+        if self.prefix.is_empty() {
+            format!("epoch-{}.{}", epoch, suffix)
+        } else {
+            format!("{}/epoch-{}.{}", self.prefix, epoch, suffix)
+        }
+    }
+
+    pub fn write_snapshot<C: SumCommitment>(
+        &self,
+        snapshot: &Snapshot<C>,
+    ) -> Result<(), ObjectError> {
+        let tree = snapshot.to_tree::<MerkleProof<C>>()?;
+        self.store
+            .put(&self.key(snapshot.epoch, "snapshot"), snapshot.to_bytes())?;
+        self.store
+            .put(&self.key(snapshot.epoch, "nodes"), encode_nodes(snapshot.epoch, &tree))
+    }
+
+    pub fn read_snapshot<C: SumCommitment>(&self, epoch: u64) -> Result<Snapshot<C>, ObjectError> {
+        let bytes = self.store.get(&self.key(epoch, "snapshot"))?;
+        Ok(Snapshot::try_from(bytes.as_slice())?)
+    }
+
+    // Reads only the node file header; nodes are fetched as proofs need them
+    pub fn open_nodes<C: SumCommitment>(
+        &self,
+        epoch: u64,
+    ) -> Result<RemoteNodes<'_, C, S>, ObjectError> {
+        let key = self.key(epoch, "nodes");
+        let header = self.store.get_range(&key, 0..NODES_HEADER_LEN as u64)?;
+        let mut reader = ByteReader::new(&header);
+        if reader.take(NODES_MAGIC.len())? != NODES_MAGIC {
+            return Err(ObjectError::UnknownFormat);
+        }
+        let stored_epoch = u64::from_le_bytes(reader.take_array::<8>()?);
+        let leaf_count = read_len(&mut reader)?;
+        let slot_count = read_len(&mut reader)?;
+        reader.finish()?;
+        if stored_epoch != epoch {
+            return Err(ObjectError::EpochMismatch {
+                expected: epoch,
+                found: stored_epoch,
+            });
+        }
+        Ok(RemoteNodes {
+            store: &self.store,
+            key,
+            leaf_count,
+            slot_count,
+            _commitment: PhantomData,
+        })
+    }
+
+    // Proof archives (as read by `bulk::verify_archive`) stored next to the epoch
+    pub fn write_archive(&self, epoch: u64, name: &str, bytes: Vec<u8>) -> Result<(), ObjectError> {
+        self.store.put(&self.key(epoch, name), bytes)
+    }
+
+    pub fn read_archive(&self, epoch: u64, name: &str) -> Result<Vec<u8>, ObjectError> {
+        self.store.get(&self.key(epoch, name))
+    }
+}
+
+impl<C: SumCommitment, S: ObjectStore> SnapshotStore<C> for ObjectSnapshotStore<S> {
+    fn persist(&mut self, snapshot: &Snapshot<C>) -> Result<(), String> {
+        self.write_snapshot(snapshot).map_err(|err| err.to_string())
+    }
+}
+
+// Define the RemoteNodes struct, an epoch's node file read lazily with one range read per
+// node on a proof path
+#[derive(Debug)]
+pub struct RemoteNodes<'a, C: SumCommitment, S: ObjectStore> {
+    store: &'a S,
+    key: String,
+    leaf_count: usize,
+    slot_count: usize,
+    _commitment: PhantomData<C>,
+}
+
+impl<C: SumCommitment, S: ObjectStore> RemoteNodes<'_, C, S> {
+    pub fn leaf_count(&self) -> usize {
+        self.leaf_count
+    }
+
+    pub fn node(&self, node_index: usize) -> Result<C, ObjectError> {
+        if node_index >= self.slot_count {
+            return Err(ObjectError::EmptySlot(node_index));
+        }
+        let offset = (NODES_HEADER_LEN + node_index * SLOT_LEN) as u64;
+        let slot = self
+            .store
+            .get_range(&self.key, offset..offset + SLOT_LEN as u64)?;
+        if slot.len() != SLOT_LEN || slot[0] != 1 {
+            return Err(ObjectError::EmptySlot(node_index));
+        }
+        let (amount, digest) = decode_commitment(&slot[1..])?;
+        Ok(C::from_parts(amount, digest))
+    }
+
+    pub fn prove<P: ExclusiveAllotmentProof<C>>(&self, position: usize) -> Result<P, ObjectError> {
+        let (leaf_index, path) = proof_path(self.leaf_count, position)
+            .ok_or(ObjectError::PositionOutOfRange(position))?;
+        let leaf = self.node(leaf_index)?;
+        let siblings = path
+            .into_iter()
+            .map(|(node_index, sibling_on_left)| Ok((self.node(node_index)?, sibling_on_left)))
+            .collect::<Result<_, ObjectError>>()?;
+        Ok(P::new(
+            position,
+            C::Leaf::from_parts(leaf.amount(), leaf.digest()),
+            siblings,
+        ))
+    }
+}
+
+fn read_len(reader: &mut ByteReader<'_>) -> Result<usize, ParseError> {
+    usize::try_from(u64::from_le_bytes(reader.take_array::<8>()?))
+        .map_err(|_| ParseError::PositionOverflow)
+}
+
+#[cfg(feature = "s3")]
+pub use s3_objects::S3Objects;
+
+#[cfg(feature = "s3")]
+mod s3_objects {
+    use std::ops::Range;
+
+    use s3::Bucket;
+
+    use super::{ObjectError, ObjectStore};
+
+    // Define the S3Objects struct, any S3-compatible bucket (AWS, MinIO, R2, GCS interop)
+    #[derive(Debug)]
+    pub struct S3Objects {
+        bucket: Box<Bucket>,
+    }
+
+    impl S3Objects {
+        pub fn new(bucket: Box<Bucket>) -> Self {
+            S3Objects { bucket }
+        }
+    }
+
+    impl ObjectStore for S3Objects {
+        fn put(&self, key: &str, bytes: Vec<u8>) -> Result<(), ObjectError> {
+            let response = self
+                .bucket
+                .put_object_blocking(key, &bytes)
+                .map_err(backend)?;
+            check_status(response.status_code(), key)
+        }
+
+        fn get(&self, key: &str) -> Result<Vec<u8>, ObjectError> {
+            let response = self.bucket.get_object_blocking(key).map_err(backend)?;
+            check_status(response.status_code(), key)?;
+            Ok(response.to_vec())
+        }
+
+        fn get_range(&self, key: &str, range: Range<u64>) -> Result<Vec<u8>, ObjectError> {
+            if range.is_empty() {
+                return Ok(Vec::new());
+            }
+            // S3 ranges are inclusive
+            let response = self
+                .bucket
+                .get_object_range_blocking(key, range.start, Some(range.end - 1))
+                .map_err(backend)?;
+            check_status(response.status_code(), key)?;
+            Ok(response.to_vec())
+        }
+    }
+
+    fn check_status(status: u16, key: &str) -> Result<(), ObjectError> {
+        match status {
+            200..=299 => Ok(()),
+            404 => Err(ObjectError::NotFound(key.to_string())),
+            other => Err(ObjectError::Backend(format!("status {} for {}", other, key))),
+        }
+    }
+
+    fn backend(err: s3::error::S3Error) -> ObjectError {
+        ObjectError::Backend(err.to_string())
+    }
+}
+
+// Define the ObjectError enum for snapshots and nodes that couldn't be stored or read back
+#[derive(Debug)]
+pub enum ObjectError {
+    Backend(String),
+    NotFound(String),
+    UnknownFormat,
+    EpochMismatch { expected: u64, found: u64 },
+    PositionOutOfRange(usize),
+    EmptySlot(usize),
+    Snapshot(SnapshotError),
+    Parse(ParseError),
+}
+
+impl fmt::Display for ObjectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ObjectError::Backend(err) => write!(f, "object store failed: {}", err),
+            ObjectError::NotFound(key) => write!(f, "no object at {}", key),
+            ObjectError::UnknownFormat => write!(f, "not a node file"),
+            ObjectError::EpochMismatch { expected, found } => {
+                write!(f, "expected nodes for epoch {}, found epoch {}", expected, found)
+            }
+            ObjectError::PositionOutOfRange(position) => {
+                write!(f, "leaf {} is outside the tree", position)
+            }
+            ObjectError::EmptySlot(node_index) => write!(f, "node {} isn't stored", node_index),
+            ObjectError::Snapshot(err) => write!(f, "{}", err),
+            ObjectError::Parse(err) => write!(f, "malformed node file: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ObjectError {}
+
+impl From<SnapshotError> for ObjectError {
+    fn from(err: SnapshotError) -> Self {
+        ObjectError::Snapshot(err)
+    }
+}
+
+impl From<ParseError> for ObjectError {
+    fn from(err: ParseError) -> Self {
+        ObjectError::Parse(err)
+    }
+}
@@ -0,0 +1,624 @@
+use std::fmt;
+use std::ops::Add;
+use std::sync::OnceLock;
+
+use k256::elliptic_curve::group::GroupEncoding;
+use k256::elliptic_curve::ops::Reduce;
+use k256::elliptic_curve::PrimeField;
+use k256::{FieldBytes, ProjectivePoint, Scalar, U256};
+use sha2::{Digest as _, Sha256};
+
+use crate::encoding::ByteReader;
+use crate::entropy::EntropySource;
+use crate::{Digest, ParseError, Root, SumCommitment};
+
+pub const POINT_LEN: usize = 33;
+pub const SCALAR_LEN: usize = 32;
+
+const GENERATOR_H_DOMAIN: &[u8] = b"mimi-pedersen-h-v1";
+const HIDING_LEAF_DOMAIN: &[u8] = b"mimi-hiding-leaf-v1";
+const HIDING_NODE_DOMAIN: &[u8] = b"mimi-hiding-node-v1";
+const TOTAL_EQUALITY_DOMAIN: &[u8] = b"mimi-total-equality-v1";
+const OPENING_PROOF_DOMAIN: &[u8] = b"mimi-opening-proof-v1";
+
+// Second generator for the blinding factor. It is hashed onto the curve by try-and-increment
+// so nobody knows its discrete log relative to G.
+pub fn generator_h() -> ProjectivePoint {
+    static H: OnceLock<ProjectivePoint> = OnceLock::new();
+    *H.get_or_init(|| {
+        (0u32..)
+            .find_map(|counter| {
+                let mut encoded = [0u8; POINT_LEN];
+                encoded[0] = 0x02;
+                let mut hasher = Sha256::new();
+                hasher.update(GENERATOR_H_DOMAIN);
+                hasher.update(counter.to_le_bytes());
+                encoded[1..].copy_from_slice(&hasher.finalize());
+                decode_point(&encoded).ok()
+            })
+            .expect("half of all x-coordinates are on the curve")
+    })
+}
+
+// Define the Blinding struct, the secret scalar that hides a committed amount
+#[derive(Clone, PartialEq, Eq)]
+pub struct Blinding(Scalar);
+
+impl Blinding {
+    pub fn random(entropy: &mut impl EntropySource) -> Self {
+        Blinding(random_scalar(entropy))
+    }
+
+    pub fn to_bytes(&self) -> [u8; SCALAR_LEN] {
+        self.0.to_bytes().into()
+    }
+}
+
+// Keeps the scalar out of logs
+impl fmt::Debug for Blinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Blinding(..)")
+    }
+}
+
+impl TryFrom<&[u8]> for Blinding {
+    type Error = ParseError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        decode_scalar(bytes).map(Blinding)
+    }
+}
+
+// Define the PedersenCommitment struct, amount * G + blinding * H
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PedersenCommitment(ProjectivePoint);
+
+impl PedersenCommitment {
+    pub fn commit(amount: u64, blinding: &Blinding) -> Self {
+        PedersenCommitment(
+            ProjectivePoint::GENERATOR * Scalar::from(amount) + generator_h() * blinding.0,
+        )
+    }
+
+    pub fn point(&self) -> &ProjectivePoint {
+        &self.0
+    }
+
+    // SEC1 compressed point
+    pub fn to_bytes(&self) -> [u8; POINT_LEN] {
+        encode_point(&self.0)
+    }
+}
+
+// Commitments add like the amounts and blindings they hide
+impl Add for PedersenCommitment {
+    type Output = PedersenCommitment;
+
+    fn add(self, other: PedersenCommitment) -> PedersenCommitment {
+        PedersenCommitment(self.0 + other.0)
+    }
+}
+
+impl TryFrom<&[u8]> for PedersenCommitment {
+    type Error = ParseError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        decode_point(bytes).map(PedersenCommitment)
+    }
+}
+
+// Define the Opening struct, what the operator keeps to open a commitment
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Opening {
+    pub amount: u64,
+    pub blinding: Blinding,
+}
+
+impl Opening {
+    pub fn random(amount: u64, entropy: &mut impl EntropySource) -> Self {
+        Opening {
+            amount,
+            blinding: Blinding::random(entropy),
+        }
+    }
+
+    pub fn commitment(&self) -> PedersenCommitment {
+        PedersenCommitment::commit(self.amount, &self.blinding)
+    }
+
+    // Opening of the sum of both commitments; None when the amounts overflow a u64
+    pub fn combine(&self, other: &Opening) -> Option<Opening> {
+        Some(Opening {
+            amount: self.amount.checked_add(other.amount)?,
+            blinding: Blinding(self.blinding.0 + other.blinding.0),
+        })
+    }
+}
+
+// Define the HidingRoot struct, the published top of a hiding tree: the Pedersen sum
+// of every leaf and the digest over the tree's commitments
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HidingRoot {
+    pub commitment: PedersenCommitment,
+    pub digest: Digest,
+}
+
+// Define the HidingTree struct, the Pedersen counterpart of MimkMerkleTree. Leaves commit
+// to their amounts under fresh blindings and internal nodes add the children's
+// commitments, so the root commits to the total without revealing it.
+//
+// Leaves carry no range proofs. Pedersen amounts live in the scalar field, so a leaf may
+// commit to a "negative" amount (group order minus x) and cancel out other users'
+// balances, and the root's total then proves nothing about solvency. Pair the tree with
+// per-leaf range proofs (e.g. Bulletproofs over [0, 2^64)) before relying on the total.
+#[derive(Debug, Clone)]
+pub struct HidingTree {
+    openings: Vec<Opening>,
+}
+
+impl HidingTree {
+    // Leaves in the same order as the transparent tree built from `amounts`
+    pub fn from_amounts(
+        amounts: &[u64],
+        entropy: &mut impl EntropySource,
+    ) -> Result<Self, PedersenError> {
+        if amounts.is_empty() {
+            return Err(PedersenError::EmptyTree);
+        }
+        let openings = amounts
+            .iter()
+            .map(|amount| Opening::random(*amount, entropy))
+            .collect();
+        Self::from_openings(openings)
+    }
+
+    pub fn from_openings(openings: Vec<Opening>) -> Result<Self, PedersenError> {
+        if openings.is_empty() {
+            return Err(PedersenError::EmptyTree);
+        }
+        let tree = HidingTree { openings };
+        tree.total_opening()?;
+        Ok(tree)
+    }
+
+    pub fn len(&self) -> usize {
+        self.openings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.openings.is_empty()
+    }
+
+    pub fn opening(&self, position: usize) -> Option<&Opening> {
+        self.openings.get(position)
+    }
+
+    pub fn commit(&self) -> HidingRoot {
+        let node = build_hiding_tree(&self.openings);
+        HidingRoot {
+            commitment: node.commitment,
+            digest: Digest::new(node.digest),
+        }
+    }
+
+    // Proves the operator can open the root commitment without revealing the total
+    pub fn prove_root_opening(
+        &self,
+        entropy: &mut impl EntropySource,
+    ) -> Result<OpeningProof, PedersenError> {
+        Ok(OpeningProof::prove(&self.total_opening()?, entropy))
+    }
+
+    // Opening of the root commitment
+    pub fn total_opening(&self) -> Result<Opening, PedersenError> {
+        let (first, rest) = self.openings.split_first().ok_or(PedersenError::EmptyTree)?;
+        rest.iter().try_fold(first.clone(), |total, opening| {
+            total.combine(opening).ok_or(PedersenError::AmountOverflow)
+        })
+    }
+}
+
+// Define the HidingProof struct, the path from one hiding leaf to the root. Each sibling
+// carries its commitment and digest, since parents hash both.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HidingProof {
+    position: usize,
+    leaf: PedersenCommitment,
+    // Ordered from the leaf up to the root, `true` when the sibling is the left child
+    siblings: Vec<(HidingNode, bool)>,
+}
+
+impl HidingProof {
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn leaf(&self) -> &PedersenCommitment {
+        &self.leaf
+    }
+
+    pub fn verify(&self, root: &HidingRoot) -> bool {
+        let mut current = hiding_leaf(self.leaf);
+        for (sibling, sibling_on_left) in &self.siblings {
+            current = if *sibling_on_left {
+                hiding_node(sibling, &current)
+            } else {
+                hiding_node(&current, sibling)
+            };
+        }
+        current.commitment == root.commitment && current.digest == *root.digest.as_bytes()
+    }
+}
+
+// Define the HidingNode struct, a subtree's summed commitment and digest
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HidingNode {
+    pub commitment: PedersenCommitment,
+    pub digest: [u8; 32],
+}
+
+impl HidingTree {
+    // Panics when the position is out of range, like slice indexing
+    pub fn prove(&self, position: usize) -> HidingProof {
+        let mut siblings = Vec::new();
+        let leaf = prove_hiding_leaf(position, &self.openings, &mut siblings);
+        HidingProof {
+            position,
+            leaf,
+            siblings,
+        }
+    }
+}
+
+fn prove_hiding_leaf(
+    position: usize,
+    openings: &[Opening],
+    siblings: &mut Vec<(HidingNode, bool)>,
+) -> PedersenCommitment {
+    if openings.len() == 1 {
+        return openings[0].commitment();
+    }
+    let middle = openings.len() / 2;
+    if position < middle {
+        let leaf = prove_hiding_leaf(position, &openings[..middle], siblings);
+        siblings.push((build_hiding_tree(&openings[middle..]), false));
+        leaf
+    } else {
+        let leaf = prove_hiding_leaf(position - middle, &openings[middle..], siblings);
+        siblings.push((build_hiding_tree(&openings[..middle]), true));
+        leaf
+    }
+}
+
+// Same halving as MimkMerkleTree, so positions line up with the transparent tree
+fn build_hiding_tree(openings: &[Opening]) -> HidingNode {
+    if openings.len() == 1 {
+        return hiding_leaf(openings[0].commitment());
+    }
+    let middle = openings.len() / 2;
+    hiding_node(
+        &build_hiding_tree(&openings[..middle]),
+        &build_hiding_tree(&openings[middle..]),
+    )
+}
+
+fn hiding_leaf(commitment: PedersenCommitment) -> HidingNode {
+    let mut hasher = Sha256::new();
+    hasher.update(HIDING_LEAF_DOMAIN);
+    hasher.update(commitment.to_bytes());
+    HidingNode {
+        commitment,
+        digest: hasher.finalize().into(),
+    }
+}
+
+fn hiding_node(left: &HidingNode, right: &HidingNode) -> HidingNode {
+    let commitment = left.commitment + right.commitment;
+    let mut hasher = Sha256::new();
+    hasher.update(HIDING_NODE_DOMAIN);
+    hasher.update(left.digest);
+    hasher.update(right.digest);
+    hasher.update(commitment.to_bytes());
+    HidingNode {
+        commitment,
+        digest: hasher.finalize().into(),
+    }
+}
+
+// Define the TotalEqualityProof struct, a Schnorr proof that the transparent root's total is
+// the amount inside a Pedersen commitment. Subtracting total * G leaves blinding * H, and the
+// proof shows knowledge of that blinding without revealing it.
+//
+// It only ties the two totals together. Without range proofs on the hiding tree's leaves
+// (see `HidingTree`) the Pedersen total may include wrapped-around negative amounts, so
+// this proof alone doesn't show the leaves sum to the published total.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TotalEqualityProof {
+    nonce_commitment: ProjectivePoint,
+    response: Scalar,
+}
+
+impl TotalEqualityProof {
+    pub fn prove(
+        total: u64,
+        opening: &Opening,
+        entropy: &mut impl EntropySource,
+    ) -> Result<Self, PedersenError> {
+        if opening.amount != total {
+            return Err(PedersenError::TotalMismatch {
+                transparent: total,
+                hidden: opening.amount,
+            });
+        }
+        let commitment = opening.commitment();
+        let nonce = random_scalar(entropy);
+        let nonce_commitment = generator_h() * nonce;
+        let challenge = equality_challenge(total, &commitment, &nonce_commitment);
+        Ok(TotalEqualityProof {
+            nonce_commitment,
+            response: nonce + challenge * opening.blinding.0,
+        })
+    }
+
+    // Checks response * H == R + challenge * (C - total * G)
+    pub fn verify(&self, total: u64, commitment: &PedersenCommitment) -> bool {
+        let challenge = equality_challenge(total, commitment, &self.nonce_commitment);
+        let blinding_part = commitment.0 - ProjectivePoint::GENERATOR * Scalar::from(total);
+        generator_h() * self.response == self.nonce_commitment + blinding_part * challenge
+    }
+
+    // R | s
+    pub fn to_bytes(&self) -> [u8; POINT_LEN + SCALAR_LEN] {
+        let mut bytes = [0u8; POINT_LEN + SCALAR_LEN];
+        bytes[..POINT_LEN].copy_from_slice(&encode_point(&self.nonce_commitment));
+        bytes[POINT_LEN..].copy_from_slice(&self.response.to_bytes());
+        bytes
+    }
+}
+
+impl TryFrom<&[u8]> for TotalEqualityProof {
+    type Error = ParseError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        let mut reader = ByteReader::new(bytes);
+        let nonce_commitment = decode_point(reader.take(POINT_LEN)?)?;
+        let response = decode_scalar(reader.take(SCALAR_LEN)?)?;
+        reader.finish()?;
+        Ok(TotalEqualityProof {
+            nonce_commitment,
+            response,
+        })
+    }
+}
+
+// Define the OpeningProof struct, a Schnorr proof of knowledge of both the amount and the
+// blinding inside a Pedersen commitment, revealing neither
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpeningProof {
+    nonce_commitment: ProjectivePoint,
+    amount_response: Scalar,
+    blinding_response: Scalar,
+}
+
+impl OpeningProof {
+    pub fn prove(opening: &Opening, entropy: &mut impl EntropySource) -> Self {
+        let commitment = opening.commitment();
+        let amount_nonce = random_scalar(entropy);
+        let blinding_nonce = random_scalar(entropy);
+        let nonce_commitment =
+            ProjectivePoint::GENERATOR * amount_nonce + generator_h() * blinding_nonce;
+        let challenge = opening_challenge(&commitment, &nonce_commitment);
+        OpeningProof {
+            nonce_commitment,
+            amount_response: amount_nonce + challenge * Scalar::from(opening.amount),
+            blinding_response: blinding_nonce + challenge * opening.blinding.0,
+        }
+    }
+
+    // Checks s_amount * G + s_blinding * H == R + challenge * C
+    pub fn verify(&self, commitment: &PedersenCommitment) -> bool {
+        let challenge = opening_challenge(commitment, &self.nonce_commitment);
+        ProjectivePoint::GENERATOR * self.amount_response + generator_h() * self.blinding_response
+            == self.nonce_commitment + commitment.0 * challenge
+    }
+
+    // R | s_amount | s_blinding
+    pub fn to_bytes(&self) -> [u8; POINT_LEN + 2 * SCALAR_LEN] {
+        let mut bytes = [0u8; POINT_LEN + 2 * SCALAR_LEN];
+        bytes[..POINT_LEN].copy_from_slice(&encode_point(&self.nonce_commitment));
+        bytes[POINT_LEN..POINT_LEN + SCALAR_LEN].copy_from_slice(&self.amount_response.to_bytes());
+        bytes[POINT_LEN + SCALAR_LEN..].copy_from_slice(&self.blinding_response.to_bytes());
+        bytes
+    }
+}
+
+impl TryFrom<&[u8]> for OpeningProof {
+    type Error = ParseError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        let mut reader = ByteReader::new(bytes);
+        let nonce_commitment = decode_point(reader.take(POINT_LEN)?)?;
+        let amount_response = decode_scalar(reader.take(SCALAR_LEN)?)?;
+        let blinding_response = decode_scalar(reader.take(SCALAR_LEN)?)?;
+        reader.finish()?;
+        Ok(OpeningProof {
+            nonce_commitment,
+            amount_response,
+            blinding_response,
+        })
+    }
+}
+
+impl HidingRoot {
+    pub fn verify_opening_proof(&self, proof: &OpeningProof) -> bool {
+        proof.verify(&self.commitment)
+    }
+}
+
+// Proves that both trees built from the same dataset commit to the same total. Equal
+// totals are only meaningful once every hiding leaf is range-proven, see `HidingTree`.
+pub fn prove_equal_totals<C: SumCommitment>(
+    transparent: &Root<C>,
+    hiding: &HidingTree,
+    entropy: &mut impl EntropySource,
+) -> Result<TotalEqualityProof, PedersenError> {
+    TotalEqualityProof::prove(transparent.amount(), &hiding.total_opening()?, entropy)
+}
+
+pub fn verify_equal_totals<C: SumCommitment>(
+    transparent: &Root<C>,
+    hiding: &HidingRoot,
+    proof: &TotalEqualityProof,
+) -> bool {
+    proof.verify(transparent.amount(), &hiding.commitment)
+}
+
+// SHA-256(domain | total | C | R) reduced into the scalar field
+fn equality_challenge(
+    total: u64,
+    commitment: &PedersenCommitment,
+    nonce_commitment: &ProjectivePoint,
+) -> Scalar {
+    let mut hasher = Sha256::new();
+    hasher.update(TOTAL_EQUALITY_DOMAIN);
+    hasher.update(total.to_le_bytes());
+    hasher.update(commitment.to_bytes());
+    hasher.update(encode_point(nonce_commitment));
+    <Scalar as Reduce<U256>>::reduce_bytes(&hasher.finalize())
+}
+
+// SHA-256(domain | C | R) reduced into the scalar field
+fn opening_challenge(commitment: &PedersenCommitment, nonce_commitment: &ProjectivePoint) -> Scalar {
+    let mut hasher = Sha256::new();
+    hasher.update(OPENING_PROOF_DOMAIN);
+    hasher.update(commitment.to_bytes());
+    hasher.update(encode_point(nonce_commitment));
+    <Scalar as Reduce<U256>>::reduce_bytes(&hasher.finalize())
+}
+
+// The bias from reducing 256 uniform bits mod the group order is below 2^-127
+pub(crate) fn random_scalar(entropy: &mut impl EntropySource) -> Scalar {
+    <Scalar as Reduce<U256>>::reduce_bytes(&FieldBytes::from(entropy.seed()))
+}
+
+pub(crate) fn encode_point(point: &ProjectivePoint) -> [u8; POINT_LEN] {
+    let mut bytes = [0u8; POINT_LEN];
+    bytes.copy_from_slice(&point.to_affine().to_bytes());
+    bytes
+}
+
+pub(crate) fn decode_point(bytes: &[u8]) -> Result<ProjectivePoint, ParseError> {
+    if bytes.len() != POINT_LEN {
+        return Err(ParseError::InvalidLength {
+            expected: POINT_LEN,
+            found: bytes.len(),
+        });
+    }
+    let encoded = k256::CompressedPoint::clone_from_slice(bytes);
+    Option::from(ProjectivePoint::from_bytes(&encoded)).ok_or(ParseError::InvalidPoint)
+}
+
+// Rejects scalars at or above the group order instead of reducing them
+pub(crate) fn decode_scalar(bytes: &[u8]) -> Result<Scalar, ParseError> {
+    if bytes.len() != SCALAR_LEN {
+        return Err(ParseError::InvalidLength {
+            expected: SCALAR_LEN,
+            found: bytes.len(),
+        });
+    }
+    Option::from(Scalar::from_repr(FieldBytes::clone_from_slice(bytes)))
+        .ok_or(ParseError::InvalidScalar)
+}
+
+// Define the PedersenError enum for hiding trees and proofs that can't be built
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PedersenError {
+    EmptyTree,
+    AmountOverflow,
+    TotalMismatch { transparent: u64, hidden: u64 },
+}
+
+impl fmt::Display for PedersenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PedersenError::EmptyTree => write!(f, "cannot build a hiding tree without leaves"),
+            PedersenError::AmountOverflow => write!(f, "hidden amounts overflow a u64"),
+            PedersenError::TotalMismatch {
+                transparent,
+                hidden,
+            } => write!(
+                f,
+                "transparent total {} differs from the hidden total {}",
+                transparent, hidden
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PedersenError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MerkleProof, MimiSumCommitment, MimkMerkleTree, SeededEntropy};
+
+    #[test]
+    fn opening_proofs_round_trip_and_bind_the_commitment() {
+        let mut entropy = SeededEntropy::new([4; 32]);
+        let tree = HidingTree::from_amounts(&[100, 200, 300], &mut entropy).unwrap();
+        let root = tree.commit();
+        assert_eq!(tree.total_opening().unwrap().commitment(), root.commitment);
+
+        let proof = tree.prove_root_opening(&mut entropy).unwrap();
+        let parsed = OpeningProof::try_from(&proof.to_bytes()[..]).unwrap();
+        assert_eq!(parsed, proof);
+        assert!(root.verify_opening_proof(&parsed));
+
+        let other = HidingTree::from_amounts(&[100, 200, 300], &mut entropy).unwrap();
+        assert!(!other.commit().verify_opening_proof(&proof));
+        let mut bytes = proof.to_bytes();
+        bytes[POINT_LEN] ^= 1;
+        let tampered = OpeningProof::try_from(&bytes[..]).unwrap();
+        assert!(!root.verify_opening_proof(&tampered));
+        assert!(OpeningProof::try_from(&bytes[1..]).is_err());
+    }
+
+    #[test]
+    fn hidden_totals_match_only_the_same_transparent_total() {
+        let mut entropy = SeededEntropy::new([5; 32]);
+        let amounts = [100, 200, 300];
+        let hiding = HidingTree::from_amounts(&amounts, &mut entropy).unwrap();
+        let root = hiding.commit();
+        for position in 0..amounts.len() {
+            assert!(hiding.prove(position).verify(&root));
+        }
+
+        let transparent =
+            MimkMerkleTree::<MimiSumCommitment, MerkleProof<_>>::new(amounts.to_vec())
+                .unwrap()
+                .commit();
+        let proof = prove_equal_totals(&transparent, &hiding, &mut entropy).unwrap();
+        let parsed = TotalEqualityProof::try_from(&proof.to_bytes()[..]).unwrap();
+        assert!(verify_equal_totals(&transparent, &root, &parsed));
+        assert!(!parsed.verify(601, &root.commitment));
+
+        let smaller = MimkMerkleTree::<MimiSumCommitment, MerkleProof<_>>::new(vec![100, 200])
+            .unwrap()
+            .commit();
+        assert_eq!(
+            prove_equal_totals(&smaller, &hiding, &mut entropy),
+            Err(PedersenError::TotalMismatch {
+                transparent: 300,
+                hidden: 600
+            })
+        );
+        assert_eq!(
+            HidingTree::from_amounts(&[u64::MAX, 1], &mut entropy).unwrap_err(),
+            PedersenError::AmountOverflow
+        );
+        assert_eq!(
+            HidingTree::from_amounts(&[], &mut entropy).unwrap_err(),
+            PedersenError::EmptyTree
+        );
+    }
+}
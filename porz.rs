@@ -0,0 +1,731 @@
+use std::fmt;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use ed25519_dalek::VerifyingKey;
+use sha2::{Digest as _, Sha256};
+
+use crate::bulk::{
+    verify_bundles, BulkError, BulkOptions, BulkReport, BundleFile, MAX_BUNDLE_BYTES,
+};
+use crate::encoding::ByteReader;
+use crate::manifest::{BuildManifest, ManifestError};
+use crate::server::ProofResponse;
+use crate::{BundleError, Digest, ParseError, Root, SignedRoot, SumCommitment};
+
+const PORZ_MAGIC: &[u8; 8] = b"MIMIPRZ1";
+
+// Names are single path components, so extraction can't escape its directory
+const MAX_NAME_LEN: usize = 255;
+
+// Names the fixed entries get, and the layout `extract_porz` writes
+pub const MANIFEST_ENTRY: &str = "manifest.json";
+pub const ROOT_ENTRY: &str = "signed-root.bin";
+pub const SIGNATURES_DIR: &str = "signatures";
+pub const BUNDLES_DIR: &str = "bundles";
+
+const BASE_ENTRY: &str = "base";
+
+// Entries are: kind | name length (u16) | name | data length (u64) | data | SHA-256 of
+// everything before it in the entry. An archive is the magic, the manifest, the signed
+// root, any detached signatures and the proof bundles (each group sorted by name), then a
+// trailer holding the entry count and the SHA-256 of all entry checksums in order. The
+// same inputs always give the same bytes, so mirrors can compare archives by digest.
+// Delta archives add a base entry after the root and the names of removed bundles
+// before the bundles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EntryKind {
+    Manifest = 0,
+    Root = 1,
+    // Detached signatures over the drop, e.g. witness cosignatures or timestamps
+    Signature = 2,
+    Bundle = 3,
+    Trailer = 4,
+    Base = 5,
+    Removed = 6,
+}
+
+impl EntryKind {
+    fn from_byte(byte: u8) -> Result<Self, ArchiveError> {
+        match byte {
+            0 => Ok(EntryKind::Manifest),
+            1 => Ok(EntryKind::Root),
+            2 => Ok(EntryKind::Signature),
+            3 => Ok(EntryKind::Bundle),
+            4 => Ok(EntryKind::Trailer),
+            5 => Ok(EntryKind::Base),
+            6 => Ok(EntryKind::Removed),
+            other => Err(ArchiveError::UnknownEntry(other)),
+        }
+    }
+
+    // Place in the canonical order, which isn't the order kinds were numbered in
+    fn rank(self) -> u8 {
+        match self {
+            EntryKind::Manifest => 0,
+            EntryKind::Root => 1,
+            EntryKind::Base => 2,
+            EntryKind::Signature => 3,
+            EntryKind::Removed => 4,
+            EntryKind::Bundle => 5,
+            EntryKind::Trailer => 6,
+        }
+    }
+}
+
+// Define the DeltaBase struct, the full archive a delta applies to: its epoch and the
+// SHA-256 of the whole file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeltaBase {
+    pub epoch: u64,
+    pub digest: Digest,
+}
+
+impl DeltaBase {
+    fn to_bytes(self) -> [u8; 40] {
+        let mut bytes = [0u8; 40];
+        bytes[..8].copy_from_slice(&self.epoch.to_le_bytes());
+        bytes[8..].copy_from_slice(self.digest.as_bytes());
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self, ArchiveError> {
+        let mut reader = ByteReader::new(bytes);
+        let epoch = u64::from_le_bytes(reader.take_array::<8>().map_err(ArchiveError::Parse)?);
+        let digest = Digest::new(reader.take_array::<32>().map_err(ArchiveError::Parse)?);
+        reader.finish().map_err(ArchiveError::Parse)?;
+        Ok(DeltaBase { epoch, digest })
+    }
+}
+
+fn check_name(name: &str) -> Result<(), ArchiveError> {
+    let valid = !name.is_empty()
+        && name.len() <= MAX_NAME_LEN
+        && name != "."
+        && name != ".."
+        && !name.contains(['/', '\\', '\0']);
+    if !valid {
+        return Err(ArchiveError::InvalidName(name.to_string()));
+    }
+    Ok(())
+}
+
+// Define the PorzWriter struct, which streams an archive out. Signatures must be added
+// before bundles and names must increase within each group; anything else is refused
+// rather than silently producing a second byte layout for the same drop.
+pub struct PorzWriter<W: Write> {
+    out: W,
+    stage: EntryKind,
+    last_name: Option<String>,
+    entries: u64,
+    checksums: Sha256,
+}
+
+impl<W: Write> PorzWriter<W> {
+    pub fn new<C: SumCommitment>(
+        mut out: W,
+        manifest: &BuildManifest,
+        signed_root: &SignedRoot<C>,
+    ) -> Result<Self, ArchiveError> {
+        if manifest.epoch != signed_root.epoch || manifest.root != signed_root.root.to_string() {
+            return Err(ArchiveError::ManifestMismatch);
+        }
+        out.write_all(PORZ_MAGIC).map_err(ArchiveError::Io)?;
+        let mut writer = PorzWriter {
+            out,
+            stage: EntryKind::Manifest,
+            last_name: None,
+            entries: 0,
+            checksums: Sha256::new(),
+        };
+        let manifest = manifest.to_json().map_err(ArchiveError::Manifest)?;
+        writer.write_entry(EntryKind::Manifest, MANIFEST_ENTRY, manifest.as_bytes())?;
+        writer.write_entry(EntryKind::Root, ROOT_ENTRY, &signed_root.to_bytes())?;
+        Ok(writer)
+    }
+
+    // Starts a delta archive; add removed bundle names with `add_removed` and only the
+    // bundles that changed since `base`
+    pub fn delta<C: SumCommitment>(
+        out: W,
+        manifest: &BuildManifest,
+        signed_root: &SignedRoot<C>,
+        base: DeltaBase,
+    ) -> Result<Self, ArchiveError> {
+        if base.epoch >= signed_root.epoch {
+            return Err(ArchiveError::NotNewer {
+                base: base.epoch,
+                epoch: signed_root.epoch,
+            });
+        }
+        let mut writer = PorzWriter::new(out, manifest, signed_root)?;
+        writer.add(EntryKind::Base, BASE_ENTRY, &base.to_bytes())?;
+        Ok(writer)
+    }
+
+    pub fn add_removed(&mut self, name: &str) -> Result<(), ArchiveError> {
+        self.add(EntryKind::Removed, name, &[])
+    }
+
+    pub fn add_signature(&mut self, name: &str, bytes: &[u8]) -> Result<(), ArchiveError> {
+        self.add(EntryKind::Signature, name, bytes)
+    }
+
+    pub fn add_bundle(&mut self, name: &str, bytes: &[u8]) -> Result<(), ArchiveError> {
+        self.add(EntryKind::Bundle, name, bytes)
+    }
+
+    // Writes the trailer and hands back the output
+    pub fn finish(mut self) -> Result<W, ArchiveError> {
+        let mut trailer = self.entries.to_le_bytes().to_vec();
+        trailer.extend_from_slice(&self.checksums.clone().finalize());
+        self.write_entry(EntryKind::Trailer, "", &trailer)?;
+        self.out.flush().map_err(ArchiveError::Io)?;
+        Ok(self.out)
+    }
+
+    fn add(&mut self, kind: EntryKind, name: &str, bytes: &[u8]) -> Result<(), ArchiveError> {
+        check_name(name)?;
+        if bytes.len() as u64 > MAX_BUNDLE_BYTES {
+            return Err(ArchiveError::TooLarge(name.to_string()));
+        }
+        if kind.rank() < self.stage.rank() {
+            return Err(ArchiveError::OutOfOrder(name.to_string()));
+        }
+        if kind.rank() > self.stage.rank() {
+            self.stage = kind;
+            self.last_name = None;
+        }
+        if self.last_name.as_deref().is_some_and(|last| last >= name) {
+            return Err(ArchiveError::OutOfOrder(name.to_string()));
+        }
+        self.last_name = Some(name.to_string());
+        self.write_entry(kind, name, bytes)
+    }
+
+    fn write_entry(
+        &mut self,
+        kind: EntryKind,
+        name: &str,
+        data: &[u8],
+    ) -> Result<(), ArchiveError> {
+        let mut entry = vec![kind as u8];
+        entry.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        entry.extend_from_slice(name.as_bytes());
+        entry.extend_from_slice(&(data.len() as u64).to_le_bytes());
+        entry.extend_from_slice(data);
+        let checksum = Sha256::digest(&entry);
+        self.out.write_all(&entry).map_err(ArchiveError::Io)?;
+        self.out.write_all(&checksum).map_err(ArchiveError::Io)?;
+        if kind != EntryKind::Trailer {
+            self.checksums.update(checksum);
+            self.entries += 1;
+        }
+        Ok(())
+    }
+}
+
+// Writes a whole drop, sorting the signatures and bundles first
+pub fn create_porz<C: SumCommitment, W: Write>(
+    out: W,
+    manifest: &BuildManifest,
+    signed_root: &SignedRoot<C>,
+    mut signatures: Vec<BundleFile>,
+    mut bundles: Vec<BundleFile>,
+) -> Result<W, ArchiveError> {
+    signatures.sort_by(|a, b| a.name.cmp(&b.name));
+    bundles.sort_by(|a, b| a.name.cmp(&b.name));
+    let mut writer = PorzWriter::new(out, manifest, signed_root)?;
+    for signature in &signatures {
+        writer.add_signature(&signature.name, &signature.bytes)?;
+    }
+    for bundle in &bundles {
+        writer.add_bundle(&bundle.name, &bundle.bytes)?;
+    }
+    writer.finish()
+}
+
+struct Entry {
+    kind: EntryKind,
+    name: String,
+    data: Vec<u8>,
+}
+
+// Define the PorzReader struct, which streams an archive in. The manifest, signed root and
+// signatures are read up front; bundles are then yielded one at a time, each checked
+// against its checksum, and the trailer is checked after the last one. Until the
+// iterator has returned None the trailer hasn't been seen, so bundles are provisional.
+pub struct PorzReader<R: Read, C: SumCommitment> {
+    input: R,
+    pub manifest: BuildManifest,
+    pub signed_root: SignedRoot<C>,
+    pub signatures: Vec<BundleFile>,
+    // Set for delta archives, with the bundles dropped since the base
+    pub base: Option<DeltaBase>,
+    pub removed: Vec<String>,
+    // First entry after the header, read while looking for its end
+    pending: Option<Entry>,
+    last: Option<(EntryKind, String)>,
+    entries: u64,
+    checksums: Sha256,
+    done: bool,
+}
+
+impl<R: Read, C: SumCommitment> PorzReader<R, C> {
+    pub fn new(mut input: R) -> Result<Self, ArchiveError> {
+        let mut magic = [0u8; 8];
+        input.read_exact(&mut magic).map_err(ArchiveError::Io)?;
+        if &magic != PORZ_MAGIC {
+            return Err(ArchiveError::Parse(ParseError::UnknownMagic));
+        }
+        let mut checksums = Sha256::new();
+        let (manifest, checksum) = read_entry(&mut input)?;
+        checksums.update(checksum);
+        let (root, checksum) = read_entry(&mut input)?;
+        checksums.update(checksum);
+        if manifest.kind != EntryKind::Manifest || root.kind != EntryKind::Root {
+            return Err(ArchiveError::OutOfOrder(root.name));
+        }
+        let manifest = std::str::from_utf8(&manifest.data)
+            .map_err(|_| ArchiveError::InvalidName(manifest.name.clone()))
+            .and_then(|json| BuildManifest::from_json(json).map_err(ArchiveError::Manifest))?;
+        let signed_root =
+            SignedRoot::try_from(root.data.as_slice()).map_err(ArchiveError::Parse)?;
+        if manifest.epoch != signed_root.epoch || manifest.root != signed_root.root.to_string() {
+            return Err(ArchiveError::ManifestMismatch);
+        }
+
+        let mut reader = PorzReader {
+            input,
+            manifest,
+            signed_root,
+            signatures: Vec::new(),
+            base: None,
+            removed: Vec::new(),
+            pending: None,
+            last: None,
+            entries: 2,
+            checksums,
+            done: false,
+        };
+        loop {
+            let entry = reader.next_entry()?;
+            match entry.kind {
+                EntryKind::Base => reader.base = Some(DeltaBase::from_bytes(&entry.data)?),
+                EntryKind::Signature => reader.signatures.push(BundleFile {
+                    name: entry.name,
+                    bytes: entry.data,
+                }),
+                EntryKind::Removed => reader.removed.push(entry.name),
+                _ => {
+                    reader.pending = Some(entry);
+                    return Ok(reader);
+                }
+            }
+        }
+    }
+
+    pub fn epoch(&self) -> u64 {
+        self.signed_root.epoch
+    }
+
+    // Reads the next entry, checking its checksum and its place in the order
+    fn next_entry(&mut self) -> Result<Entry, ArchiveError> {
+        let (entry, checksum) = read_entry(&mut self.input)?;
+        if entry.kind == EntryKind::Trailer {
+            return Ok(entry);
+        }
+        let rank = entry.kind.rank();
+        let in_order = rank > EntryKind::Root.rank()
+            && self.last.as_ref().is_none_or(|(kind, name)| {
+                kind.rank() < rank || (*kind == entry.kind && *name < entry.name)
+            });
+        if !in_order {
+            return Err(ArchiveError::OutOfOrder(entry.name));
+        }
+        self.last = Some((entry.kind, entry.name.clone()));
+        self.entries += 1;
+        self.checksums.update(checksum);
+        Ok(entry)
+    }
+
+    fn check_trailer(&mut self, trailer: Entry) -> Result<(), ArchiveError> {
+        let mut expected = self.entries.to_le_bytes().to_vec();
+        expected.extend_from_slice(&self.checksums.clone().finalize());
+        if trailer.data != expected {
+            return Err(ArchiveError::Trailer);
+        }
+        // Nothing may follow the trailer
+        let mut rest = [0u8; 1];
+        match self.input.read(&mut rest).map_err(ArchiveError::Io)? {
+            0 => Ok(()),
+            _ => Err(ArchiveError::Trailer),
+        }
+    }
+}
+
+impl<R: Read, C: SumCommitment> Iterator for PorzReader<R, C> {
+    type Item = Result<BundleFile, ArchiveError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let entry = match self.pending.take().map_or_else(|| self.next_entry(), Ok) {
+            Ok(entry) => entry,
+            Err(err) => {
+                self.done = true;
+                return Some(Err(err));
+            }
+        };
+        match entry.kind {
+            EntryKind::Bundle => Some(Ok(BundleFile {
+                name: entry.name,
+                bytes: entry.data,
+            })),
+            EntryKind::Trailer => {
+                self.done = true;
+                self.check_trailer(entry).err().map(Err)
+            }
+            _ => {
+                self.done = true;
+                Some(Err(ArchiveError::OutOfOrder(entry.name)))
+            }
+        }
+    }
+}
+
+// Returns the entry with its checksum, once it has been checked
+fn read_entry(input: &mut impl Read) -> Result<(Entry, [u8; 32]), ArchiveError> {
+    let mut head = [0u8; 3];
+    input.read_exact(&mut head).map_err(ArchiveError::Io)?;
+    let kind = EntryKind::from_byte(head[0])?;
+    let mut name = vec![0u8; u16::from_le_bytes([head[1], head[2]]) as usize];
+    input.read_exact(&mut name).map_err(ArchiveError::Io)?;
+    let name = String::from_utf8(name).map_err(|err| {
+        ArchiveError::InvalidName(String::from_utf8_lossy(err.as_bytes()).into_owned())
+    })?;
+    if kind != EntryKind::Trailer {
+        check_name(&name)?;
+    }
+    let mut len = [0u8; 8];
+    input.read_exact(&mut len).map_err(ArchiveError::Io)?;
+    let len = u64::from_le_bytes(len);
+    if len > MAX_BUNDLE_BYTES {
+        return Err(ArchiveError::TooLarge(name));
+    }
+    let mut data = vec![0u8; len as usize];
+    input.read_exact(&mut data).map_err(ArchiveError::Io)?;
+    let mut checksum = [0u8; 32];
+    input.read_exact(&mut checksum).map_err(ArchiveError::Io)?;
+
+    let entry = Entry { kind, name, data };
+    let computed = entry_checksum(&entry);
+    if computed != checksum {
+        return Err(ArchiveError::Checksum(entry.name));
+    }
+    Ok((entry, computed))
+}
+
+fn entry_checksum(entry: &Entry) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update([entry.kind as u8]);
+    hasher.update((entry.name.len() as u16).to_le_bytes());
+    hasher.update(entry.name.as_bytes());
+    hasher.update((entry.data.len() as u64).to_le_bytes());
+    hasher.update(&entry.data);
+    hasher.finalize().into()
+}
+
+// Checks a drop end to end: the signed root against the operator key and the expected
+// epoch and root, then every bundle as `verify_tar` would. Checksum, order and trailer
+// errors stop the run, since they mean the archive itself was damaged or tampered with.
+pub fn verify_porz<C: SumCommitment + Sync, R: Read>(
+    reader: R,
+    epoch: u64,
+    root: &Root<C>,
+    operator_key: &VerifyingKey,
+    options: BulkOptions,
+) -> Result<BulkReport, ArchiveError> {
+    let archive = PorzReader::<R, C>::new(reader)?;
+    archive.signed_root.verify(operator_key).map_err(ArchiveError::Root)?;
+    if archive.signed_root.epoch != epoch || !archive.signed_root.root.matches(root.node()) {
+        return Err(ArchiveError::UnexpectedRoot {
+            epoch: archive.signed_root.epoch,
+            root: archive.signed_root.root.to_string(),
+        });
+    }
+    let files = archive.map(|file| {
+        file.map_err(|err| match err {
+            ArchiveError::TooLarge(name) => BulkError::TooLarge(name),
+            ArchiveError::Io(err) => BulkError::Io(err),
+            err => BulkError::Archive(err.to_string()),
+        })
+    });
+    verify_bundles(files, epoch, root, operator_key, options).map_err(ArchiveError::Bulk)
+}
+
+// Define the PorzContents struct, what `extract_porz` wrote
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PorzContents {
+    pub epoch: u64,
+    pub signatures: usize,
+    pub bundles: usize,
+}
+
+// Unpacks an archive into `dir` as `manifest.json`, `signed-root.bin`, `signatures/` and
+// `bundles/`. Files go to `<dir>.partial` first and the directory is only renamed into
+// place once the trailer has checked out, so `dir` never holds a damaged drop.
+pub fn extract_porz<C: SumCommitment>(
+    reader: impl Read,
+    dir: impl AsRef<Path>,
+) -> Result<PorzContents, ArchiveError> {
+    let dir = dir.as_ref();
+    let mut partial = dir.as_os_str().to_owned();
+    partial.push(".partial");
+    let partial = PathBuf::from(partial);
+    if partial.exists() {
+        fs::remove_dir_all(&partial).map_err(ArchiveError::Io)?;
+    }
+    fs::create_dir_all(partial.join(SIGNATURES_DIR)).map_err(ArchiveError::Io)?;
+    fs::create_dir_all(partial.join(BUNDLES_DIR)).map_err(ArchiveError::Io)?;
+
+    let mut archive = PorzReader::<_, C>::new(reader)?;
+    if archive.base.is_some() {
+        return Err(ArchiveError::IsDelta);
+    }
+    let manifest = archive.manifest.to_json().map_err(ArchiveError::Manifest)?;
+    fs::write(partial.join(MANIFEST_ENTRY), manifest).map_err(ArchiveError::Io)?;
+    fs::write(partial.join(ROOT_ENTRY), archive.signed_root.to_bytes())
+        .map_err(ArchiveError::Io)?;
+    for signature in &archive.signatures {
+        fs::write(partial.join(SIGNATURES_DIR).join(&signature.name), &signature.bytes)
+            .map_err(ArchiveError::Io)?;
+    }
+    let (epoch, signatures) = (archive.epoch(), archive.signatures.len());
+    let mut bundles = 0;
+    for bundle in archive.by_ref() {
+        let bundle = bundle?;
+        fs::write(partial.join(BUNDLES_DIR).join(&bundle.name), &bundle.bytes)
+            .map_err(ArchiveError::Io)?;
+        bundles += 1;
+    }
+    fs::rename(&partial, dir).map_err(ArchiveError::Io)?;
+    Ok(PorzContents {
+        epoch,
+        signatures,
+        bundles,
+    })
+}
+
+// Define the HashingReader struct, which digests a base archive as it is read, so its
+// SHA-256 can be checked against the delta without a second pass
+struct HashingReader<R: Read> {
+    inner: R,
+    hasher: Sha256,
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.hasher.update(&buf[..read]);
+        Ok(read)
+    }
+}
+
+// The part of a bundle that survives an epoch change. Bundles that aren't proof bundles
+// count as changed, so they always travel in full.
+fn bundle_proof(bytes: &[u8]) -> Option<String> {
+    serde_json::from_slice::<ProofResponse>(bytes)
+        .ok()
+        .map(|response| response.proof)
+}
+
+// Writes a delta from the full archive `base` to the new epoch: only bundles that are new
+// or whose proof changed, and the names of those no longer present. Any changed leaf
+// also changes the siblings on its path up to the root, so deltas pay off when few
+// balances move between epochs.
+pub fn create_delta_porz<C: SumCommitment, W: Write>(
+    base: impl Read,
+    out: W,
+    manifest: &BuildManifest,
+    signed_root: &SignedRoot<C>,
+    mut signatures: Vec<BundleFile>,
+    mut bundles: Vec<BundleFile>,
+) -> Result<W, ArchiveError> {
+    let mut base = HashingReader {
+        inner: base,
+        hasher: Sha256::new(),
+    };
+    let previous = PorzReader::<_, C>::new(&mut base)?;
+    if previous.base.is_some() {
+        return Err(ArchiveError::IsDelta);
+    }
+    let base_epoch = previous.epoch();
+    let mut proofs = std::collections::BTreeMap::new();
+    for bundle in previous {
+        let bundle = bundle?;
+        proofs.insert(bundle.name, bundle_proof(&bundle.bytes));
+    }
+    let base = DeltaBase {
+        epoch: base_epoch,
+        digest: Digest::new(base.hasher.finalize().into()),
+    };
+
+    signatures.sort_by(|a, b| a.name.cmp(&b.name));
+    bundles.sort_by(|a, b| a.name.cmp(&b.name));
+    let mut writer = PorzWriter::delta(out, manifest, signed_root, base)?;
+    for signature in &signatures {
+        writer.add_signature(&signature.name, &signature.bytes)?;
+    }
+    let mut current = bundles.iter().map(|bundle| bundle.name.as_str()).peekable();
+    for name in proofs.keys() {
+        while current.next_if(|current| *current < name.as_str()).is_some() {}
+        if current.peek() != Some(&name.as_str()) {
+            writer.add_removed(name)?;
+        }
+    }
+    for bundle in &bundles {
+        let unchanged = proofs
+            .get(&bundle.name)
+            .and_then(Option::as_ref)
+            .is_some_and(|proof| bundle_proof(&bundle.bytes).as_ref() == Some(proof));
+        if !unchanged {
+            writer.add_bundle(&bundle.name, &bundle.bytes)?;
+        }
+    }
+    writer.finish()
+}
+
+// Rebuilds the new epoch's full archive from the previous full archive and a delta.
+// Unchanged bundles get the delta's signed root, re-serialized the way the proof server
+// writes them; output is streamed, so if the base turns out not to be the one the delta
+// names (checked once it has been read to the end) discard what was written.
+pub fn apply_delta_porz<C: SumCommitment, W: Write>(
+    base: impl Read,
+    delta: impl Read,
+    out: W,
+) -> Result<W, ArchiveError> {
+    let mut delta = PorzReader::<_, C>::new(delta)?;
+    let expected = delta.base.ok_or(ArchiveError::NotDelta)?;
+    let mut base = HashingReader {
+        inner: base,
+        hasher: Sha256::new(),
+    };
+    let previous = PorzReader::<_, C>::new(&mut base)?;
+    if previous.epoch() != expected.epoch || previous.base.is_some() {
+        return Err(ArchiveError::BaseMismatch);
+    }
+
+    let signed_root = delta.signed_root.clone();
+    let mut writer = PorzWriter::new(out, &delta.manifest, &signed_root)?;
+    for signature in &delta.signatures {
+        writer.add_signature(&signature.name, &signature.bytes)?;
+    }
+    let removed: std::collections::BTreeSet<String> = delta.removed.drain(..).collect();
+    // Both sides are sorted by name, so the merge streams
+    let mut changed = delta.peekable();
+    for bundle in previous {
+        let bundle = bundle?;
+        let mut replaced = false;
+        while let Some(next) =
+            changed.next_if(|next| !matches!(next, Ok(next) if next.name > bundle.name))
+        {
+            let next = next?;
+            replaced |= next.name == bundle.name;
+            writer.add_bundle(&next.name, &next.bytes)?;
+        }
+        if replaced || removed.contains(&bundle.name) {
+            continue;
+        }
+        let mut response: ProofResponse = serde_json::from_slice(&bundle.bytes)
+            .map_err(|_| ArchiveError::NotProofBundle(bundle.name.clone()))?;
+        response.epoch = signed_root.epoch;
+        response.root = signed_root.root.to_string();
+        response.signature = hex::encode(signed_root.signature.to_bytes());
+        let bytes = serde_json::to_vec(&response)
+            .map_err(|_| ArchiveError::NotProofBundle(bundle.name.clone()))?;
+        writer.add_bundle(&bundle.name, &bytes)?;
+    }
+    for next in changed {
+        let next = next?;
+        writer.add_bundle(&next.name, &next.bytes)?;
+    }
+    let digest = Digest::new(base.hasher.finalize().into());
+    if digest != expected.digest {
+        return Err(ArchiveError::BaseMismatch);
+    }
+    writer.finish()
+}
+
+// Define the ArchiveError enum for `.porz` archives that can't be written, read or
+// trusted. Bundles that fail verification are reported by `verify_porz`, not returned as errors.
+#[derive(Debug)]
+pub enum ArchiveError {
+    Io(io::Error),
+    Parse(ParseError),
+    Manifest(ManifestError),
+    UnknownEntry(u8),
+    InvalidName(String),
+    // Entries out of the canonical order, or a name repeated
+    OutOfOrder(String),
+    TooLarge(String),
+    Checksum(String),
+    // The entry count or checksum digest disagrees, or bytes follow the trailer
+    Trailer,
+    // The manifest describes a different epoch or root than the signed root
+    ManifestMismatch,
+    Root(BundleError),
+    // The archive is for another epoch or root than the one being checked
+    UnexpectedRoot { epoch: u64, root: String },
+    // A delta was given where a full archive is needed, or the other way around
+    IsDelta,
+    NotDelta,
+    NotNewer { base: u64, epoch: u64 },
+    // The base archive isn't the one the delta was made against
+    BaseMismatch,
+    NotProofBundle(String),
+    Bulk(BulkError),
+}
+
+impl fmt::Display for ArchiveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArchiveError::Io(err) => write!(f, "i/o error: {}", err),
+            ArchiveError::Parse(err) => write!(f, "malformed archive: {}", err),
+            ArchiveError::Manifest(err) => write!(f, "archive manifest: {}", err),
+            ArchiveError::UnknownEntry(kind) => write!(f, "unknown archive entry kind {}", kind),
+            ArchiveError::InvalidName(name) => write!(f, "invalid archive entry name `{}`", name),
+            ArchiveError::OutOfOrder(name) => {
+                write!(f, "archive entry `{}` is out of order", name)
+            }
+            ArchiveError::TooLarge(name) => write!(
+                f,
+                "{} is larger than the {} byte entry limit",
+                name, MAX_BUNDLE_BYTES
+            ),
+            ArchiveError::Checksum(name) => write!(f, "checksum mismatch in entry `{}`", name),
+            ArchiveError::Trailer => write!(f, "archive trailer does not match its entries"),
+            ArchiveError::ManifestMismatch => {
+                write!(f, "manifest and signed root describe different epochs or roots")
+            }
+            ArchiveError::Root(err) => write!(f, "signed root: {}", err),
+            ArchiveError::UnexpectedRoot { epoch, root } => {
+                write!(f, "archive is for root {} of epoch {}", root, epoch)
+            }
+            ArchiveError::IsDelta => write!(f, "archive is a delta; apply it to its base first"),
+            ArchiveError::NotDelta => write!(f, "archive is a full archive, not a delta"),
+            ArchiveError::NotNewer { base, epoch } => {
+                write!(f, "delta for epoch {} must be newer than base epoch {}", epoch, base)
+            }
+            ArchiveError::BaseMismatch => {
+                write!(f, "base archive is not the one the delta was made against")
+            }
+            ArchiveError::NotProofBundle(name) => write!(f, "`{}` is not a proof bundle", name),
+            ArchiveError::Bulk(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for ArchiveError {}
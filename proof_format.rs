@@ -0,0 +1,347 @@
+use std::fmt;
+use std::str::FromStr;
+
+use generic_array::typenum::U32;
+use generic_array::GenericArray;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Digest, ExclusiveAllotmentProof, LeafCommitment, MerkleProof, ParseError, SumCommitment,
+};
+
+// Define the ProofFormat enum, the encodings one inclusion proof can be handed out in.
+// CBOR needs the "cbor" feature and calldata the "ethereum" feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProofFormat {
+    // ProofJson, for web apps
+    Json,
+    // `MerkleProof::to_bytes`
+    Binary,
+    // Integer-keyed map, as in the COSE payloads
+    Cbor,
+    // `0x`-hex ABI calldata of `verifyProof`, for verifier contracts
+    Calldata,
+}
+
+impl ProofFormat {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ProofFormat::Json => "json",
+            ProofFormat::Binary => "binary",
+            ProofFormat::Cbor => "cbor",
+            ProofFormat::Calldata => "calldata",
+        }
+    }
+
+    // Guess from a file extension: .json, .bin, .cbor, .calldata or .hex
+    pub fn from_extension(path: &str) -> Option<Self> {
+        match path.rsplit_once('.')?.1 {
+            "json" => Some(ProofFormat::Json),
+            "bin" => Some(ProofFormat::Binary),
+            "cbor" => Some(ProofFormat::Cbor),
+            "calldata" | "hex" => Some(ProofFormat::Calldata),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for ProofFormat {
+    type Err = ConvertError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(ProofFormat::Json),
+            "binary" | "bin" => Ok(ProofFormat::Binary),
+            "cbor" => Ok(ProofFormat::Cbor),
+            "calldata" => Ok(ProofFormat::Calldata),
+            other => Err(ConvertError::UnknownFormat(other.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for ProofFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+// Define the ProofJson struct, the JSON form of a MerkleProof. Nodes are written as
+// `<amount>:<hex digest>`, like roots.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProofJson {
+    pub position: usize,
+    pub leaf: String,
+    // From the leaf up to the root
+    pub siblings: Vec<SiblingJson>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SiblingJson {
+    // The sibling is the left child
+    pub left: bool,
+    pub node: String,
+}
+
+impl ProofJson {
+    pub fn from_proof<C: SumCommitment>(proof: &MerkleProof<C>) -> Self {
+        ProofJson {
+            position: proof.position(),
+            leaf: node_text(proof.leaf().amount(), proof.leaf().digest()),
+            siblings: proof
+                .siblings()
+                .iter()
+                .map(|(node, left)| SiblingJson {
+                    left: *left,
+                    node: node_text(node.amount(), node.digest()),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn to_proof<C: SumCommitment>(&self) -> Result<MerkleProof<C>, ParseError> {
+        let (amount, digest) = parse_node(&self.leaf)?;
+        let siblings = self
+            .siblings
+            .iter()
+            .map(|sibling| {
+                let (amount, digest) = parse_node(&sibling.node)?;
+                Ok((C::from_parts(amount, digest), sibling.left))
+            })
+            .collect::<Result<_, ParseError>>()?;
+        Ok(MerkleProof::new(
+            self.position,
+            C::Leaf::from_parts(amount, digest),
+            siblings,
+        ))
+    }
+}
+
+fn node_text(amount: u64, digest: GenericArray<u8, U32>) -> String {
+    format!("{}:{}", amount, Digest::from(digest))
+}
+
+fn parse_node(text: &str) -> Result<(u64, GenericArray<u8, U32>), ParseError> {
+    let (amount, digest) = text.split_once(':').ok_or(ParseError::MissingSeparator)?;
+    let amount = amount.parse::<u64>().map_err(|_| ParseError::InvalidAmount)?;
+    let digest = digest.parse::<Digest>()?;
+    Ok((amount, digest.into()))
+}
+
+pub fn encode_proof<C: SumCommitment>(
+    proof: &MerkleProof<C>,
+    format: ProofFormat,
+) -> Result<Vec<u8>, ConvertError> {
+    match format {
+        ProofFormat::Json => serde_json::to_vec_pretty(&ProofJson::from_proof(proof))
+            .map_err(ConvertError::Json),
+        ProofFormat::Binary => Ok(proof.to_bytes()),
+        #[cfg(feature = "cbor")]
+        ProofFormat::Cbor => cbor::encode(proof),
+        #[cfg(feature = "ethereum")]
+        ProofFormat::Calldata => {
+            Ok(format!("0x{}", hex::encode(calldata::encode(proof))).into_bytes())
+        }
+        #[allow(unreachable_patterns)]
+        other => Err(ConvertError::Unsupported(other)),
+    }
+}
+
+pub fn decode_proof<C: SumCommitment>(
+    bytes: &[u8],
+    format: ProofFormat,
+) -> Result<MerkleProof<C>, ConvertError> {
+    match format {
+        ProofFormat::Json => {
+            let json: ProofJson = serde_json::from_slice(bytes).map_err(ConvertError::Json)?;
+            Ok(json.to_proof()?)
+        }
+        ProofFormat::Binary => Ok(MerkleProof::try_from(bytes)?),
+        #[cfg(feature = "cbor")]
+        ProofFormat::Cbor => cbor::decode(bytes),
+        #[cfg(feature = "ethereum")]
+        ProofFormat::Calldata => {
+            let text = std::str::from_utf8(bytes).map_err(|_| ParseError::InvalidHex)?.trim();
+            let text = text.strip_prefix("0x").unwrap_or(text);
+            calldata::decode(&hex::decode(text).map_err(|_| ParseError::InvalidHex)?)
+        }
+        #[allow(unreachable_patterns)]
+        other => Err(ConvertError::Unsupported(other)),
+    }
+}
+
+#[cfg(feature = "cbor")]
+mod cbor {
+    use ciborium::value::Value;
+
+    use super::ConvertError;
+    use crate::encoding::{decode_commitment, encode_commitment};
+    use crate::{ExclusiveAllotmentProof, LeafCommitment, MerkleProof, SumCommitment};
+
+    const KEY_POSITION: i64 = 1;
+    const KEY_LEAF: i64 = 2;
+    // Array of [left (bool), node (40 bytes)] pairs, leaf to root
+    const KEY_SIBLINGS: i64 = 3;
+
+    pub(super) fn encode<C: SumCommitment>(
+        proof: &MerkleProof<C>,
+    ) -> Result<Vec<u8>, ConvertError> {
+        let leaf = encode_commitment(proof.leaf().amount(), &proof.leaf().digest());
+        let siblings = proof
+            .siblings()
+            .iter()
+            .map(|(node, left)| {
+                let node = encode_commitment(node.amount(), &node.digest());
+                Value::Array(vec![Value::Bool(*left), Value::Bytes(node.to_vec())])
+            })
+            .collect();
+        let map = Value::Map(vec![
+            (Value::from(KEY_POSITION), Value::from(proof.position() as u64)),
+            (Value::from(KEY_LEAF), Value::Bytes(leaf.to_vec())),
+            (Value::from(KEY_SIBLINGS), Value::Array(siblings)),
+        ]);
+        let mut bytes = Vec::new();
+        ciborium::into_writer(&map, &mut bytes)
+            .map_err(|err| ConvertError::Cbor(err.to_string()))?;
+        Ok(bytes)
+    }
+
+    pub(super) fn decode<C: SumCommitment>(bytes: &[u8]) -> Result<MerkleProof<C>, ConvertError> {
+        let malformed = || ConvertError::Cbor("malformed proof map".to_string());
+        let Ok(Value::Map(entries)) = ciborium::from_reader::<Value, _>(bytes) else {
+            return Err(malformed());
+        };
+        let entry = |key: i64| {
+            entries
+                .iter()
+                .find(|(label, _)| label.as_integer() == Some(key.into()))
+                .map(|(_, value)| value)
+                .ok_or_else(malformed)
+        };
+        let position = entry(KEY_POSITION)?
+            .as_integer()
+            .and_then(|position| usize::try_from(position).ok())
+            .ok_or_else(malformed)?;
+        let leaf = entry(KEY_LEAF)?.as_bytes().ok_or_else(malformed)?;
+        let (amount, digest) = decode_commitment(leaf)?;
+        let mut siblings = Vec::new();
+        for sibling in entry(KEY_SIBLINGS)?.as_array().ok_or_else(malformed)? {
+            let Some([Value::Bool(left), Value::Bytes(node)]) =
+                sibling.as_array().map(Vec::as_slice)
+            else {
+                return Err(malformed());
+            };
+            let (amount, digest) = decode_commitment(node)?;
+            siblings.push((C::from_parts(amount, digest), *left));
+        }
+        Ok(MerkleProof::new(
+            position,
+            C::Leaf::from_parts(amount, digest),
+            siblings,
+        ))
+    }
+}
+
+#[cfg(feature = "ethereum")]
+mod calldata {
+    use alloy::primitives::{FixedBytes, U256};
+    use alloy::sol;
+    use alloy::sol_types::SolCall;
+
+    use super::ConvertError;
+    use crate::{ExclusiveAllotmentProof, LeafCommitment, MerkleProof, SumCommitment};
+
+    sol! {
+        // Verifier contract entry point; the contract checks the path against the root it
+        // stores for the epoch
+        struct ProofNode {
+            bytes32 digest;
+            uint256 amount;
+            bool left;
+        }
+
+        function verifyProof(
+            uint256 position,
+            bytes32 leafDigest,
+            uint256 leafAmount,
+            ProofNode[] siblings
+        ) external view returns (bool);
+    }
+
+    pub(super) fn encode<C: SumCommitment>(proof: &MerkleProof<C>) -> Vec<u8> {
+        verifyProofCall {
+            position: U256::from(proof.position()),
+            leafDigest: FixedBytes::from_slice(&proof.leaf().digest()),
+            leafAmount: U256::from(proof.leaf().amount()),
+            siblings: proof
+                .siblings()
+                .iter()
+                .map(|(node, left)| ProofNode {
+                    digest: FixedBytes::from_slice(&node.digest()),
+                    amount: U256::from(node.amount()),
+                    left: *left,
+                })
+                .collect(),
+        }
+        .abi_encode()
+    }
+
+    pub(super) fn decode<C: SumCommitment>(bytes: &[u8]) -> Result<MerkleProof<C>, ConvertError> {
+        let call = verifyProofCall::abi_decode(bytes, true)
+            .map_err(|err| ConvertError::Abi(err.to_string()))?;
+        let amount = |value: U256| {
+            u64::try_from(value).map_err(|_| ConvertError::Abi("amount exceeds u64".to_string()))
+        };
+        let position = usize::try_from(call.position)
+            .map_err(|_| ConvertError::Abi("position exceeds usize".to_string()))?;
+        let siblings = call
+            .siblings
+            .iter()
+            .map(|node| {
+                Ok((
+                    C::from_parts(amount(node.amount)?, node.digest.0.into()),
+                    node.left,
+                ))
+            })
+            .collect::<Result<_, ConvertError>>()?;
+        Ok(MerkleProof::new(
+            position,
+            C::Leaf::from_parts(amount(call.leafAmount)?, call.leafDigest.0.into()),
+            siblings,
+        ))
+    }
+}
+
+// Define the ConvertError enum for proofs that can't be read or written in a format
+#[derive(Debug)]
+pub enum ConvertError {
+    UnknownFormat(String),
+    // The format's feature isn't enabled in this build
+    Unsupported(ProofFormat),
+    Json(serde_json::Error),
+    Cbor(String),
+    Abi(String),
+    Parse(ParseError),
+}
+
+impl fmt::Display for ConvertError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConvertError::UnknownFormat(name) => write!(f, "unknown proof format `{}`", name),
+            ConvertError::Unsupported(format) => {
+                write!(f, "{} proofs are not supported by this build", format)
+            }
+            ConvertError::Json(err) => write!(f, "json error: {}", err),
+            ConvertError::Cbor(reason) => write!(f, "cbor error: {}", reason),
+            ConvertError::Abi(reason) => write!(f, "calldata error: {}", reason),
+            ConvertError::Parse(err) => write!(f, "malformed proof: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ConvertError {}
+
+impl From<ParseError> for ConvertError {
+    fn from(err: ParseError) -> Self {
+        ConvertError::Parse(err)
+    }
+}
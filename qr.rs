@@ -0,0 +1,150 @@
+use std::fmt;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL;
+use base64::Engine;
+use ed25519_dalek::Signature;
+use qrcode::render::{svg, unicode};
+use qrcode::QrCode;
+use sha2::{Digest as _, Sha256};
+
+use crate::bundle::{ProofBundle, SignedRoot};
+use crate::encoding::{ByteReader, COMMITMENT_LEN};
+use crate::{Digest, MerkleProof, ParseError, Root, SumCommitment};
+
+// Scanned text starts with one of these, so apps can tell proof codes from other QR codes
+const BUNDLE_PREFIX: &str = "MIMI1:";
+const LINK_PREFIX: &str = "MIMI1L:";
+
+// Define the QrPayload enum, what a proof QR code carries: the whole bundle when it fits,
+// or a URL to fetch it from plus the hash the fetched bundle must match
+#[derive(Debug, Clone)]
+pub enum QrPayload<C: SumCommitment> {
+    Bundle(ProofBundle<C, MerkleProof<C>>),
+    Link { url: String, digest: Digest },
+}
+
+impl<C: SumCommitment> QrPayload<C> {
+    pub fn link(url: impl Into<String>, bundle: &ProofBundle<C, MerkleProof<C>>) -> Self {
+        QrPayload::Link {
+            url: url.into(),
+            digest: Digest::from(Sha256::digest(bundle_to_bytes(bundle))),
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        match self {
+            QrPayload::Bundle(bundle) => {
+                format!(
+                    "{}{}",
+                    BUNDLE_PREFIX,
+                    BASE64_URL.encode(bundle_to_bytes(bundle))
+                )
+            }
+            QrPayload::Link { url, digest } => format!("{}{}@{}", LINK_PREFIX, digest, url),
+        }
+    }
+
+    pub fn from_text(text: &str) -> Result<Self, QrError> {
+        let text = text.trim();
+        if let Some(link) = text.strip_prefix(LINK_PREFIX) {
+            let (digest, url) = link.split_once('@').ok_or(ParseError::MissingSeparator)?;
+            return Ok(QrPayload::Link {
+                url: url.to_string(),
+                digest: digest.parse()?,
+            });
+        }
+        let encoded = text
+            .strip_prefix(BUNDLE_PREFIX)
+            .ok_or(QrError::UnknownPrefix)?;
+        let bytes = BASE64_URL
+            .decode(encoded)
+            .map_err(|_| ParseError::InvalidBase64)?;
+        Ok(QrPayload::Bundle(bundle_from_bytes(&bytes)?))
+    }
+
+    // For link payloads: whether a bundle fetched from the URL is the one the code vouches for
+    pub fn matches(&self, bundle: &ProofBundle<C, MerkleProof<C>>) -> bool {
+        match self {
+            QrPayload::Bundle(embedded) => bundle_to_bytes(embedded) == bundle_to_bytes(bundle),
+            QrPayload::Link { digest, .. } => {
+                digest.as_bytes()[..] == Sha256::digest(bundle_to_bytes(bundle))[..]
+            }
+        }
+    }
+
+    pub fn to_qr(&self) -> Result<QrCode, QrError> {
+        QrCode::new(self.to_text()).map_err(|err| QrError::Encode(err.to_string()))
+    }
+
+    pub fn render_svg(&self) -> Result<String, QrError> {
+        Ok(self
+            .to_qr()?
+            .render::<svg::Color>()
+            .min_dimensions(256, 256)
+            .build())
+    }
+
+    // Two modules per character, for terminals and plain-text statements
+    pub fn render_text(&self) -> Result<String, QrError> {
+        Ok(self
+            .to_qr()?
+            .render::<unicode::Dense1x2>()
+            .quiet_zone(true)
+            .build())
+    }
+}
+
+// Compact bundle form: epoch | root | signature | proof. The RFC 3161 timestamp is
+// left out to keep codes scannable.
+pub fn bundle_to_bytes<C: SumCommitment>(bundle: &ProofBundle<C, MerkleProof<C>>) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(&bundle.signed_root.epoch.to_le_bytes());
+    bytes.extend_from_slice(&bundle.signed_root.root.to_bytes());
+    bytes.extend_from_slice(&bundle.signed_root.signature.to_bytes());
+    bytes.extend_from_slice(&bundle.proof.to_bytes());
+    bytes
+}
+
+pub fn bundle_from_bytes<C: SumCommitment>(
+    bytes: &[u8],
+) -> Result<ProofBundle<C, MerkleProof<C>>, ParseError> {
+    let mut reader = ByteReader::new(bytes);
+    let epoch = u64::from_le_bytes(reader.take_array::<8>()?);
+    let root = Root::try_from(reader.take(COMMITMENT_LEN)?)?;
+    let signature = Signature::from_bytes(&reader.take_array::<64>()?);
+    let proof = MerkleProof::try_from(&bytes[8 + COMMITMENT_LEN + 64..])?;
+    Ok(ProofBundle::new(
+        proof,
+        SignedRoot {
+            epoch,
+            root,
+            signature,
+        },
+    ))
+}
+
+// Define the QrError enum for payloads that can't be rendered or scanned back
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QrError {
+    UnknownPrefix,
+    Encode(String),
+    Parse(ParseError),
+}
+
+impl fmt::Display for QrError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QrError::UnknownPrefix => write!(f, "not a proof QR payload"),
+            QrError::Encode(reason) => write!(f, "payload does not fit a QR code: {}", reason),
+            QrError::Parse(err) => write!(f, "malformed QR payload: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for QrError {}
+
+impl From<ParseError> for QrError {
+    fn from(err: ParseError) -> Self {
+        QrError::Parse(err)
+    }
+}
@@ -0,0 +1,153 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::reserves::ReservesAttestation;
+use crate::{Root, SumCommitment};
+
+// Define the SolvencyReport struct, the machine-readable outcome of one epoch's checks.
+// Amounts are integers in the tree's base units; ratios are reserves / liabilities.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SolvencyReport {
+    pub epoch: u64,
+    pub challenge: String,
+    // Hex of `ReservesAttestation::commitment`
+    pub reserves_commitment: String,
+    pub assets: Vec<AssetCoverage>,
+    pub checks: Vec<CheckResult>,
+    // True only if every check passed and every asset is fully covered
+    pub solvent: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AssetCoverage {
+    pub asset: String,
+    // Root as `<amount>:<hex digest>`, absent for assets with reserves but no liabilities
+    pub liability_root: Option<String>,
+    pub liabilities: u64,
+    pub reserves: u64,
+    // None when there are no liabilities to cover
+    pub coverage_ratio: Option<f64>,
+    pub covered: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CheckResult {
+    pub check: String,
+    pub passed: bool,
+    pub detail: Option<String>,
+}
+
+impl CheckResult {
+    pub(crate) fn from_result<E: fmt::Display>(check: String, result: Result<(), E>) -> Self {
+        CheckResult {
+            check,
+            passed: result.is_ok(),
+            detail: result.err().map(|err| err.to_string()),
+        }
+    }
+}
+
+impl SolvencyReport {
+    // Failures are recorded in `checks` rather than returned, so a report is always produced
+    pub fn generate<C: SumCommitment>(
+        epoch: u64,
+        liabilities: &[(&str, &Root<C>)],
+        reserves: &ReservesAttestation,
+    ) -> Self {
+        let mut checks: Vec<CheckResult> = reserves
+            .proofs
+            .iter()
+            .map(|proof| {
+                let check = format!(
+                    "ownership:{}:{}",
+                    proof.address.chain.name(),
+                    proof.address.address
+                );
+                CheckResult::from_result(check, proof.verify(&reserves.challenge))
+            })
+            .collect();
+
+        let mut asset_names: Vec<&str> = liabilities.iter().map(|(asset, _)| *asset).collect();
+        for proof in &reserves.proofs {
+            if !asset_names.contains(&proof.address.asset.as_str()) {
+                asset_names.push(&proof.address.asset);
+            }
+        }
+
+        let mut assets = Vec::with_capacity(asset_names.len());
+        for asset in asset_names {
+            let root = liabilities
+                .iter()
+                .find(|(name, _)| *name == asset)
+                .map(|(_, root)| *root);
+            let liability_amount = root.map_or(0, |root| root.amount());
+            let reserve_amount = match reserves.total(asset) {
+                Ok(total) => total,
+                Err(err) => {
+                    checks.push(CheckResult::from_result(
+                        format!("reserve-total:{}", asset),
+                        Err(err),
+                    ));
+                    0
+                }
+            };
+            let coverage_ratio = if liability_amount == 0 {
+                None
+            } else {
+                Some(reserve_amount as f64 / liability_amount as f64)
+            };
+            assets.push(AssetCoverage {
+                asset: asset.to_string(),
+                liability_root: root.map(|root| root.to_string()),
+                liabilities: liability_amount,
+                reserves: reserve_amount,
+                coverage_ratio,
+                covered: reserve_amount >= liability_amount,
+            });
+        }
+
+        let solvent =
+            checks.iter().all(|check| check.passed) && assets.iter().all(|asset| asset.covered);
+        SolvencyReport {
+            epoch,
+            challenge: reserves.challenge.clone(),
+            reserves_commitment: reserves.commitment().to_string(),
+            assets,
+            checks,
+            solvent,
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, ReportError> {
+        serde_json::to_string_pretty(self).map_err(ReportError::Json)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, ReportError> {
+        serde_json::from_str(json).map_err(ReportError::Json)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ReportError> {
+        fs::write(path, self.to_json()?).map_err(ReportError::Io)
+    }
+}
+
+// Define the ReportError enum for reports that can't be serialized or written
+#[derive(Debug)]
+pub enum ReportError {
+    Json(serde_json::Error),
+    Io(std::io::Error),
+}
+
+impl fmt::Display for ReportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReportError::Json(err) => write!(f, "json error: {}", err),
+            ReportError::Io(err) => write!(f, "i/o error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ReportError {}
@@ -0,0 +1,554 @@
+use generic_array::typenum::U32;
+use generic_array::GenericArray;
+
+use crate::{
+    AmountBinding, HashBackend, Leaf, LeafCommitment, SumCommitment, LEAF_PREFIX, NODE_PREFIX,
+};
+
+// The 64-bit "Goldilocks" prime 2^64 - 2^32 + 1 used by Winterfell and Miden
+pub const MODULUS: u64 = 0xFFFF_FFFF_0000_0001;
+
+// Rp64_256 parameters: 12 field elements of state, 4 of them capacity, 7 rounds
+pub const STATE_WIDTH: usize = 12;
+pub const CAPACITY: usize = 4;
+pub const RATE: usize = STATE_WIDTH - CAPACITY;
+pub const DIGEST_ELEMENTS: usize = 4;
+pub const ROUNDS: usize = 7;
+
+const ALPHA: u64 = 7;
+// ALPHA^-1 mod (MODULUS - 1)
+const ALPHA_INV: u64 = 10540996611094048183;
+
+// Bytes packed into one element; 7 bytes always stay below the modulus
+const BYTES_PER_ELEMENT: usize = 7;
+
+// The MDS matrix and round constants of Rp64_256 as Winterfell and Miden instantiate it
+// (winter-crypto's `MDS`, `ARK1` and `ARK2`), so digests match theirs
+const MDS: [[u64; STATE_WIDTH]; STATE_WIDTH] = [
+    [7, 23, 8, 26, 13, 10, 9, 7, 6, 22, 21, 8],
+    [8, 7, 23, 8, 26, 13, 10, 9, 7, 6, 22, 21],
+    [21, 8, 7, 23, 8, 26, 13, 10, 9, 7, 6, 22],
+    [22, 21, 8, 7, 23, 8, 26, 13, 10, 9, 7, 6],
+    [6, 22, 21, 8, 7, 23, 8, 26, 13, 10, 9, 7],
+    [7, 6, 22, 21, 8, 7, 23, 8, 26, 13, 10, 9],
+    [9, 7, 6, 22, 21, 8, 7, 23, 8, 26, 13, 10],
+    [10, 9, 7, 6, 22, 21, 8, 7, 23, 8, 26, 13],
+    [13, 10, 9, 7, 6, 22, 21, 8, 7, 23, 8, 26],
+    [26, 13, 10, 9, 7, 6, 22, 21, 8, 7, 23, 8],
+    [8, 26, 13, 10, 9, 7, 6, 22, 21, 8, 7, 23],
+    [23, 8, 26, 13, 10, 9, 7, 6, 22, 21, 8, 7],
+];
+
+const ARK1: [[u64; STATE_WIDTH]; ROUNDS] = [
+    [
+        13917550007135091859,
+        16002276252647722320,
+        4729924423368391595,
+        10059693067827680263,
+        9804807372516189948,
+        15666751576116384237,
+        10150587679474953119,
+        13627942357577414247,
+        2323786301545403792,
+        615170742765998613,
+        8870655212817778103,
+        10534167191270683080,
+    ],
+    [
+        14572151513649018290,
+        9445470642301863087,
+        6565801926598404534,
+        12667566692985038975,
+        7193782419267459720,
+        11874811971940314298,
+        17906868010477466257,
+        1237247437760523561,
+        6829882458376718831,
+        2140011966759485221,
+        1624379354686052121,
+        50954653459374206,
+    ],
+    [
+        16288075653722020941,
+        13294924199301620952,
+        13370596140726871456,
+        611533288599636281,
+        12865221627554828747,
+        12269498015480242943,
+        8230863118714645896,
+        13466591048726906480,
+        10176988631229240256,
+        14951460136371189405,
+        5882405912332577353,
+        18125144098115032453,
+    ],
+    [
+        6076976409066920174,
+        7466617867456719866,
+        5509452692963105675,
+        14692460717212261752,
+        12980373618703329746,
+        1361187191725412610,
+        6093955025012408881,
+        5110883082899748359,
+        8578179704817414083,
+        9311749071195681469,
+        16965242536774914613,
+        5747454353875601040,
+    ],
+    [
+        13684212076160345083,
+        19445754899749561,
+        16618768069125744845,
+        278225951958825090,
+        4997246680116830377,
+        782614868534172852,
+        16423767594935000044,
+        9990984633405879434,
+        16757120847103156641,
+        2103861168279461168,
+        16018697163142305052,
+        6479823382130993799,
+    ],
+    [
+        13957683526597936825,
+        9702819874074407511,
+        18357323897135139931,
+        3029452444431245019,
+        1809322684009991117,
+        12459356450895788575,
+        11985094908667810946,
+        12868806590346066108,
+        7872185587893926881,
+        10694372443883124306,
+        8644995046789277522,
+        1422920069067375692,
+    ],
+    [
+        17619517835351328008,
+        6173683530634627901,
+        15061027706054897896,
+        4503753322633415655,
+        11538516425871008333,
+        12777459872202073891,
+        17842814708228807409,
+        13441695826912633916,
+        5950710620243434509,
+        17040450522225825296,
+        8787650312632423701,
+        7431110942091427450,
+    ],
+];
+
+const ARK2: [[u64; STATE_WIDTH]; ROUNDS] = [
+    [
+        7989257206380839449,
+        8639509123020237648,
+        6488561830509603695,
+        5519169995467998761,
+        2972173318556248829,
+        14899875358187389787,
+        14160104549881494022,
+        5969738169680657501,
+        5116050734813646528,
+        12120002089437618419,
+        17404470791907152876,
+        2718166276419445724,
+    ],
+    [
+        2485377440770793394,
+        14358936485713564605,
+        3327012975585973824,
+        6001912612374303716,
+        17419159457659073951,
+        11810720562576658327,
+        14802512641816370470,
+        751963320628219432,
+        9410455736958787393,
+        16405548341306967018,
+        6867376949398252373,
+        13982182448213113532,
+    ],
+    [
+        10436926105997283389,
+        13237521312283579132,
+        668335841375552722,
+        2385521647573044240,
+        3874694023045931809,
+        12952434030222726182,
+        1972984540857058687,
+        14000313505684510403,
+        976377933822676506,
+        8407002393718726702,
+        338785660775650958,
+        4208211193539481671,
+    ],
+    [
+        2284392243703840734,
+        4500504737691218932,
+        3976085877224857941,
+        2603294837319327956,
+        5760259105023371034,
+        2911579958858769248,
+        18415938932239013434,
+        7063156700464743997,
+        16626114991069403630,
+        163485390956217960,
+        11596043559919659130,
+        2976841507452846995,
+    ],
+    [
+        15090073748392700862,
+        3496786927732034743,
+        8646735362535504000,
+        2460088694130347125,
+        3944675034557577794,
+        14781700518249159275,
+        2857749437648203959,
+        8505429584078195973,
+        18008150643764164736,
+        720176627102578275,
+        7038653538629322181,
+        8849746187975356582,
+    ],
+    [
+        17427790390280348710,
+        1159544160012040055,
+        17946663256456930598,
+        6338793524502945410,
+        17715539080731926288,
+        4208940652334891422,
+        12386490721239135719,
+        10010817080957769535,
+        5566101162185411405,
+        12520146553271266365,
+        4972547404153988943,
+        5597076522138709717,
+    ],
+    [
+        18338863478027005376,
+        115128380230345639,
+        4427489889653730058,
+        10890727269603281956,
+        7094492770210294530,
+        7345573238864544283,
+        6834103517673002336,
+        14002814950696095900,
+        15939230865809555943,
+        12717309295554119359,
+        4130723396860574906,
+        7706153020203677238,
+    ],
+];
+
+fn add(a: u64, b: u64) -> u64 {
+    ((a as u128 + b as u128) % MODULUS as u128) as u64
+}
+
+fn mul(a: u64, b: u64) -> u64 {
+    ((a as u128 * b as u128) % MODULUS as u128) as u64
+}
+
+fn pow(mut base: u64, mut exponent: u64) -> u64 {
+    let mut result = 1;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = mul(result, base);
+        }
+        base = mul(base, base);
+        exponent >>= 1;
+    }
+    result
+}
+
+fn apply_mds(state: &mut [u64; STATE_WIDTH]) {
+    let mut result = [0u64; STATE_WIDTH];
+    for (out, row) in result.iter_mut().zip(&MDS) {
+        *out = row
+            .iter()
+            .zip(state.iter())
+            .fold(0, |acc, (coefficient, value)| {
+                add(acc, mul(*coefficient, *value))
+            });
+    }
+    *state = result;
+}
+
+fn add_constants(state: &mut [u64; STATE_WIDTH], constants: &[u64; STATE_WIDTH]) {
+    for (value, constant) in state.iter_mut().zip(constants) {
+        *value = add(*value, *constant);
+    }
+}
+
+// Each round is x^alpha, MDS, constants, then x^(1/alpha), MDS, constants
+pub fn permute(state: &mut [u64; STATE_WIDTH]) {
+    for round in 0..ROUNDS {
+        for value in state.iter_mut() {
+            *value = pow(*value, ALPHA);
+        }
+        apply_mds(state);
+        add_constants(state, &ARK1[round]);
+        for value in state.iter_mut() {
+            *value = pow(*value, ALPHA_INV);
+        }
+        apply_mds(state);
+        add_constants(state, &ARK2[round]);
+    }
+}
+
+// Sponge over field elements, as Rp64_256's `hash_elements`. The first capacity element
+// holds the input length so inputs that differ only in trailing zeros don't collide;
+// elements are reduced mod p.
+pub fn hash_elements(elements: &[u64]) -> [u64; DIGEST_ELEMENTS] {
+    let mut state = [0u64; STATE_WIDTH];
+    state[0] = elements.len() as u64 % MODULUS;
+    for chunk in elements.chunks(RATE) {
+        for (value, element) in state[CAPACITY..].iter_mut().zip(chunk) {
+            *value = add(*value, *element % MODULUS);
+        }
+        permute(&mut state);
+    }
+    let mut digest = [0u64; DIGEST_ELEMENTS];
+    digest.copy_from_slice(&state[CAPACITY..CAPACITY + DIGEST_ELEMENTS]);
+    digest
+}
+
+// As Rp64_256's `hash`: bytes packed 7 at a time (little-endian) so every element is
+// canonical, with a 1 byte after the last chunk so trailing zeros change the digest
+pub fn hash_bytes(bytes: &[u8]) -> [u64; DIGEST_ELEMENTS] {
+    let count = bytes.len().div_ceil(BYTES_PER_ELEMENT);
+    let elements: Vec<u64> = bytes
+        .chunks(BYTES_PER_ELEMENT)
+        .enumerate()
+        .map(|(index, chunk)| {
+            let mut word = [0u8; 8];
+            word[..chunk.len()].copy_from_slice(chunk);
+            if index + 1 == count {
+                word[chunk.len()] = 1;
+            }
+            u64::from_le_bytes(word)
+        })
+        .collect();
+    hash_elements(&elements)
+}
+
+// Digest elements as 8-byte little-endian words
+pub fn digest_to_bytes(digest: &[u64; DIGEST_ELEMENTS]) -> GenericArray<u8, U32> {
+    let mut bytes = GenericArray::default();
+    for (chunk, element) in bytes.chunks_exact_mut(8).zip(digest) {
+        chunk.copy_from_slice(&element.to_le_bytes());
+    }
+    bytes
+}
+
+// None when a word is at or above the modulus: each digest has exactly one encoding, so
+// a word and its reduction can't stand for the same node
+pub fn digest_from_bytes(bytes: &GenericArray<u8, U32>) -> Option<[u64; DIGEST_ELEMENTS]> {
+    let mut digest = [0u64; DIGEST_ELEMENTS];
+    for (element, chunk) in digest.iter_mut().zip(bytes.chunks_exact(8)) {
+        let word = u64::from_le_bytes(chunk.try_into().expect("chunks are 8 bytes"));
+        if word >= MODULUS {
+            return None;
+        }
+        *element = word;
+    }
+    Some(digest)
+}
+
+// Define RescueLeafCommitment struct, a leaf hashed with Rescue-Prime
+#[derive(Debug, Clone)]
+pub struct RescueLeafCommitment {
+    amount: u64,
+    digest: GenericArray<u8, U32>,
+}
+
+impl LeafCommitment for RescueLeafCommitment {
+    type Node = RescueSumCommitment;
+
+    fn from_leaf<L: Leaf>(leaf: &L) -> Self {
+        let mut preimage = vec![LEAF_PREFIX];
+        preimage.extend_from_slice(&leaf.encode_for_hash());
+        RescueLeafCommitment {
+            amount: leaf.amount(),
+            digest: digest_to_bytes(&hash_bytes(&preimage)),
+        }
+    }
+
+    fn from_salted_leaf<L: Leaf>(leaf: &L, salt: &[u8; 32]) -> Self {
+        let mut preimage = vec![LEAF_PREFIX];
+        preimage.extend_from_slice(salt);
+        preimage.extend_from_slice(&leaf.encode_for_hash());
+        RescueLeafCommitment {
+            amount: leaf.amount(),
+            digest: digest_to_bytes(&hash_bytes(&preimage)),
+        }
+    }
+
+    fn from_parts(amount: u64, digest: GenericArray<u8, U32>) -> Self {
+        RescueLeafCommitment { amount, digest }
+    }
+
+    fn amount(&self) -> u64 {
+        self.amount
+    }
+
+    fn digest(&self) -> GenericArray<u8, U32> {
+        self.digest
+    }
+
+    fn to_node(&self) -> RescueSumCommitment {
+        RescueSumCommitment {
+            amount: self.amount,
+            digest: self.digest,
+        }
+    }
+}
+
+// Define RescueSumCommitment struct, for trees whose hashes are re-checked inside a STARK
+#[derive(Debug, Clone)]
+pub struct RescueSumCommitment {
+    amount: u64,
+    digest: GenericArray<u8, U32>,
+}
+
+impl SumCommitment for RescueSumCommitment {
+    type Leaf = RescueLeafCommitment;
+    const HASH_BACKEND: HashBackend = HashBackend::RescuePrime;
+    const AMOUNT_BINDING: AmountBinding = AmountBinding::Bound;
+
+    fn amount(&self) -> u64 {
+        self.amount
+    }
+
+    fn digest(&self) -> GenericArray<u8, U32> {
+        self.digest
+    }
+
+    // 1 | left digest | left amount | right digest | right amount, amounts split into
+    // 32-bit limbs (low first) since a u64 may exceed the modulus. A child digest with a
+    // non-canonical word gives the parent the (equally non-canonical) all-0xFF digest, so
+    // such a path never reaches a root built from field elements.
+    fn combine_commitments(left: &Self, right: &Self) -> Self {
+        let amount = left.amount + right.amount;
+        let (Some(left_digest), Some(right_digest)) = (
+            digest_from_bytes(&left.digest),
+            digest_from_bytes(&right.digest),
+        ) else {
+            return RescueSumCommitment {
+                amount,
+                digest: [0xFF; 32].into(),
+            };
+        };
+        let mut elements = [0u64; 1 + 2 * (DIGEST_ELEMENTS + 2)];
+        elements[0] = NODE_PREFIX as u64;
+        let children = [(left_digest, left.amount), (right_digest, right.amount)];
+        for (index, (digest, child_amount)) in children.iter().enumerate() {
+            let offset = 1 + index * (DIGEST_ELEMENTS + 2);
+            elements[offset..offset + DIGEST_ELEMENTS].copy_from_slice(digest);
+            elements[offset + DIGEST_ELEMENTS] = child_amount & 0xFFFF_FFFF;
+            elements[offset + DIGEST_ELEMENTS + 1] = child_amount >> 32;
+        }
+        RescueSumCommitment {
+            amount,
+            digest: digest_to_bytes(&hash_elements(&elements)),
+        }
+    }
+
+    fn from_parts(amount: u64, digest: GenericArray<u8, U32>) -> Self {
+        RescueSumCommitment { amount, digest }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Known answers from winter-crypto's Rp64_256; the permutation one is its test
+    // vector from the Sage reference implementation
+    #[test]
+    fn permutation_matches_rp64_256() {
+        let mut state: [u64; STATE_WIDTH] = std::array::from_fn(|index| index as u64);
+        permute(&mut state);
+        assert_eq!(
+            state,
+            [
+                11084501481526603421,
+                6291559951628160880,
+                13626645864671311919,
+                18397438323058963117,
+                7443014167353970324,
+                17930833023906771425,
+                4275355080008025761,
+                7676681476902901785,
+                3460534574143792217,
+                11912731278641497187,
+                8104899243369883110,
+                674509706691634438,
+            ]
+        );
+    }
+
+    #[test]
+    fn hashes_match_rp64_256() {
+        assert_eq!(
+            hash_bytes(b"abc"),
+            [
+                10044651378672137912,
+                10790900633927995114,
+                945783814243028441,
+                15905618125373294627,
+            ]
+        );
+        // A full last chunk still takes the padding byte
+        assert_eq!(
+            hash_bytes(&[1, 2, 3, 4, 5, 6, 7]),
+            [
+                15921221105204917321,
+                7694163014451104367,
+                1726831751270061975,
+                15031791537473506653,
+            ]
+        );
+        let bytes: Vec<u8> = (0..20).collect();
+        assert_eq!(
+            hash_bytes(&bytes),
+            [
+                5682940177529352797,
+                14967351399797162795,
+                13661766400941525220,
+                737608359140949052,
+            ]
+        );
+        let elements: Vec<u64> = (0..13).collect();
+        assert_eq!(
+            hash_elements(&elements),
+            [
+                136686448124051290,
+                7832224329739448631,
+                6095827458827761357,
+                7624886842213099278,
+            ]
+        );
+    }
+
+    #[test]
+    fn non_canonical_digests_are_rejected() {
+        let digest = digest_to_bytes(&[1, 2, 3, MODULUS - 1]);
+        assert_eq!(digest_from_bytes(&digest), Some([1, 2, 3, MODULUS - 1]));
+
+        let mut aliased = digest;
+        aliased[24..].copy_from_slice(&MODULUS.to_le_bytes());
+        assert_eq!(digest_from_bytes(&aliased), None);
+
+        // The reduced word hashes differently from its unreduced alias
+        let honest = RescueSumCommitment::from_parts(5, digest_to_bytes(&[1, 2, 3, 0]));
+        let forged = RescueSumCommitment::from_parts(5, aliased);
+        let sibling = RescueSumCommitment::from_parts(7, digest_to_bytes(&[4, 5, 6, 7]));
+        let parent = RescueSumCommitment::combine_commitments(&forged, &sibling);
+        assert_ne!(
+            parent.digest(),
+            RescueSumCommitment::combine_commitments(&honest, &sibling).digest()
+        );
+        assert_eq!(digest_from_bytes(&parent.digest()), None);
+    }
+}
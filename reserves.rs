@@ -0,0 +1,329 @@
+use std::fmt;
+
+use k256::ecdsa::{RecoveryId, Signature, SigningKey, VerifyingKey};
+use ripemd::Ripemd160;
+use sha2::{Digest as _, Sha256};
+use sha3::Keccak256;
+
+use crate::encoding::encode_len;
+use crate::{Digest, Root, SumCommitment};
+
+const BITCOIN_MESSAGE_PREFIX: &[u8] = b"\x18Bitcoin Signed Message:\n";
+const ETHEREUM_MESSAGE_PREFIX: &[u8] = b"\x19Ethereum Signed Message:\n";
+
+// Define the Chain enum, the address formats ownership proofs are checked for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Chain {
+    // Mainnet P2PKH addresses, compressed keys
+    Bitcoin,
+    // Lowercase 0x-prefixed hex addresses
+    Ethereum,
+}
+
+impl Chain {
+    pub fn name(self) -> &'static str {
+        match self {
+            Chain::Bitcoin => "bitcoin",
+            Chain::Ethereum => "ethereum",
+        }
+    }
+
+    fn tag(self) -> u8 {
+        match self {
+            Chain::Bitcoin => 0,
+            Chain::Ethereum => 1,
+        }
+    }
+}
+
+// Define the ReserveAddress struct, an on-chain address and the balance claimed for it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReserveAddress {
+    pub chain: Chain,
+    pub address: String,
+    pub asset: String,
+    pub balance: u64,
+}
+
+// Define the OwnershipProof struct, a recoverable signature over the challenge by the address key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnershipProof {
+    pub address: ReserveAddress,
+    // 65 bytes: Bitcoin header || r || s, or Ethereum r || s || v
+    pub signature: Vec<u8>,
+}
+
+impl OwnershipProof {
+    pub fn sign(address: ReserveAddress, challenge: &str, key: &SigningKey) -> Self {
+        let signature = match address.chain {
+            Chain::Bitcoin => sign_bitcoin(key, challenge.as_bytes()),
+            Chain::Ethereum => sign_ethereum(key, challenge.as_bytes()),
+        };
+        OwnershipProof {
+            address,
+            signature: signature.to_vec(),
+        }
+    }
+
+    pub fn verify(&self, challenge: &str) -> Result<(), ReservesError> {
+        let recovered = match self.address.chain {
+            Chain::Bitcoin => recover_bitcoin(challenge.as_bytes(), &self.signature)?,
+            Chain::Ethereum => recover_ethereum(challenge.as_bytes(), &self.signature)?,
+        };
+        // Ethereum addresses may arrive EIP-55 checksummed
+        let matches = match self.address.chain {
+            Chain::Bitcoin => recovered == self.address.address,
+            Chain::Ethereum => recovered.eq_ignore_ascii_case(&self.address.address),
+        };
+        if !matches {
+            return Err(ReservesError::WrongSigner {
+                address: self.address.address.clone(),
+                recovered,
+            });
+        }
+        Ok(())
+    }
+}
+
+// Define the ReservesAttestation struct, every address the operator controls, signed over one challenge
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReservesAttestation {
+    pub challenge: String,
+    pub proofs: Vec<OwnershipProof>,
+}
+
+impl ReservesAttestation {
+    pub fn new(challenge: impl Into<String>) -> Self {
+        ReservesAttestation {
+            challenge: challenge.into(),
+            proofs: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, address: ReserveAddress, key: &SigningKey) {
+        let proof = OwnershipProof::sign(address, &self.challenge, key);
+        self.proofs.push(proof);
+    }
+
+    // Binds the challenge and every claimed address and balance in one digest
+    pub fn commitment(&self) -> Digest {
+        let mut hasher = Sha256::new();
+        hasher.update(b"mimi-reserves-v1");
+        write_field(&mut hasher, self.challenge.as_bytes());
+        for proof in &self.proofs {
+            let address = &proof.address;
+            hasher.update([address.chain.tag()]);
+            write_field(&mut hasher, address.address.as_bytes());
+            write_field(&mut hasher, address.asset.as_bytes());
+            hasher.update(address.balance.to_le_bytes());
+        }
+        Digest::from(hasher.finalize())
+    }
+
+    // Sum of the claimed balances for one asset; each address counts once
+    pub fn total(&self, asset: &str) -> Result<u64, ReservesError> {
+        let mut seen = Vec::new();
+        let mut total = 0u64;
+        for proof in self
+            .proofs
+            .iter()
+            .filter(|proof| proof.address.asset == asset)
+        {
+            let key = (proof.address.chain, proof.address.address.as_str());
+            if seen.contains(&key) {
+                return Err(ReservesError::DuplicateAddress(
+                    proof.address.address.clone(),
+                ));
+            }
+            seen.push(key);
+            total = total
+                .checked_add(proof.address.balance)
+                .ok_or(ReservesError::Overflow)?;
+        }
+        Ok(total)
+    }
+
+    pub fn verify(&self) -> Result<(), ReservesError> {
+        for proof in &self.proofs {
+            proof.verify(&self.challenge)?;
+        }
+        Ok(())
+    }
+
+    // Checks every signature, then compares the asset's reserves with the liability root
+    pub fn compare<C: SumCommitment>(
+        &self,
+        asset: &str,
+        liabilities: &Root<C>,
+    ) -> Result<Solvency, ReservesError> {
+        self.verify()?;
+        Ok(Solvency {
+            reserves: self.total(asset)?,
+            liabilities: liabilities.amount(),
+        })
+    }
+}
+
+// Define the Solvency struct, reserves against liabilities for one asset
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Solvency {
+    pub reserves: u64,
+    pub liabilities: u64,
+}
+
+impl Solvency {
+    pub fn is_solvent(&self) -> bool {
+        self.reserves >= self.liabilities
+    }
+
+    pub fn surplus(&self) -> i128 {
+        self.reserves as i128 - self.liabilities as i128
+    }
+}
+
+fn write_field(hasher: &mut Sha256, bytes: &[u8]) {
+    hasher.update(encode_len(bytes));
+    hasher.update(bytes);
+}
+
+// Double SHA-256 of the prefixed message, as `signmessage` computes it
+pub fn bitcoin_message_hash(message: &[u8]) -> [u8; 32] {
+    let mut preimage = BITCOIN_MESSAGE_PREFIX.to_vec();
+    write_compact_size(&mut preimage, message.len() as u64);
+    preimage.extend_from_slice(message);
+    Sha256::digest(Sha256::digest(&preimage)).into()
+}
+
+// EIP-191 version 0x45 (`personal_sign`) hash
+pub fn ethereum_message_hash(message: &[u8]) -> [u8; 32] {
+    let mut hasher = Keccak256::new();
+    hasher.update(ETHEREUM_MESSAGE_PREFIX);
+    hasher.update(message.len().to_string().as_bytes());
+    hasher.update(message);
+    hasher.finalize().into()
+}
+
+fn write_compact_size(out: &mut Vec<u8>, len: u64) {
+    match len {
+        0..=0xfc => out.push(len as u8),
+        0xfd..=0xffff => {
+            out.push(0xfd);
+            out.extend_from_slice(&(len as u16).to_le_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            out.push(0xfe);
+            out.extend_from_slice(&(len as u32).to_le_bytes());
+        }
+        _ => {
+            out.push(0xff);
+            out.extend_from_slice(&len.to_le_bytes());
+        }
+    }
+}
+
+pub fn bitcoin_address(key: &VerifyingKey) -> String {
+    let point = key.to_encoded_point(true);
+    let hash = Ripemd160::digest(Sha256::digest(point.as_bytes()));
+    let mut payload = vec![0x00];
+    payload.extend_from_slice(&hash);
+    let checksum = Sha256::digest(Sha256::digest(&payload));
+    payload.extend_from_slice(&checksum[..4]);
+    bs58::encode(payload).into_string()
+}
+
+pub fn ethereum_address(key: &VerifyingKey) -> String {
+    let point = key.to_encoded_point(false);
+    let hash = Keccak256::digest(&point.as_bytes()[1..]);
+    format!("0x{}", hex::encode(&hash[12..]))
+}
+
+pub fn sign_bitcoin(key: &SigningKey, message: &[u8]) -> [u8; 65] {
+    let (signature, recovery_id) = sign_prehash(key, &bitcoin_message_hash(message));
+    let mut out = [0u8; 65];
+    // 27 + recovery id, plus 4 to mark a compressed key
+    out[0] = 31 + recovery_id.to_byte();
+    out[1..].copy_from_slice(&signature.to_bytes());
+    out
+}
+
+pub fn sign_ethereum(key: &SigningKey, message: &[u8]) -> [u8; 65] {
+    let (signature, recovery_id) = sign_prehash(key, &ethereum_message_hash(message));
+    let mut out = [0u8; 65];
+    out[..64].copy_from_slice(&signature.to_bytes());
+    out[64] = 27 + recovery_id.to_byte();
+    out
+}
+
+fn sign_prehash(key: &SigningKey, hash: &[u8; 32]) -> (Signature, RecoveryId) {
+    // RFC 6979 signing only fails on an invalid prehash length
+    key.sign_prehash_recoverable(hash)
+        .expect("32-byte prehash is always signable")
+}
+
+pub fn recover_bitcoin(message: &[u8], signature: &[u8]) -> Result<String, ReservesError> {
+    let (&header, rs) = signature
+        .split_first()
+        .filter(|(_, rs)| rs.len() == 64)
+        .ok_or(ReservesError::InvalidSignature)?;
+    // Only compressed P2PKH headers (31..=34) map to the address format checked here
+    if !(31..=34).contains(&header) {
+        return Err(ReservesError::UnsupportedHeader(header));
+    }
+    let key = recover(&bitcoin_message_hash(message), rs, header - 31)?;
+    Ok(bitcoin_address(&key))
+}
+
+pub fn recover_ethereum(message: &[u8], signature: &[u8]) -> Result<String, ReservesError> {
+    if signature.len() != 65 {
+        return Err(ReservesError::InvalidSignature);
+    }
+    let v = signature[64];
+    let recovery_id = match v {
+        27 | 28 => v - 27,
+        0 | 1 => v,
+        _ => return Err(ReservesError::InvalidSignature),
+    };
+    let key = recover(
+        &ethereum_message_hash(message),
+        &signature[..64],
+        recovery_id,
+    )?;
+    Ok(ethereum_address(&key))
+}
+
+fn recover(hash: &[u8; 32], rs: &[u8], recovery_id: u8) -> Result<VerifyingKey, ReservesError> {
+    let signature = Signature::from_slice(rs).map_err(|_| ReservesError::InvalidSignature)?;
+    let recovery_id = RecoveryId::from_byte(recovery_id).ok_or(ReservesError::InvalidSignature)?;
+    VerifyingKey::recover_from_prehash(hash, &signature, recovery_id)
+        .map_err(|_| ReservesError::InvalidSignature)
+}
+
+// Define the ReservesError enum for attestations that fail verification
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReservesError {
+    InvalidSignature,
+    UnsupportedHeader(u8),
+    WrongSigner { address: String, recovered: String },
+    DuplicateAddress(String),
+    Overflow,
+}
+
+impl fmt::Display for ReservesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReservesError::InvalidSignature => write!(f, "malformed ownership signature"),
+            ReservesError::UnsupportedHeader(header) => {
+                write!(f, "unsupported bitcoin signature header {}", header)
+            }
+            ReservesError::WrongSigner { address, recovered } => {
+                write!(f, "signature for {} was made by {}", address, recovered)
+            }
+            ReservesError::DuplicateAddress(address) => {
+                write!(f, "address {} is listed more than once", address)
+            }
+            ReservesError::Overflow => write!(f, "reserve total overflows u64"),
+        }
+    }
+}
+
+impl std::error::Error for ReservesError {}
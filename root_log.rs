@@ -0,0 +1,350 @@
+use std::fmt;
+
+use sha2::{Digest as _, Sha256};
+
+use crate::encoding::{encode_usize, ByteReader, COMMITMENT_LEN};
+use crate::{Digest, ParseError, Root, SumCommitment};
+
+const LOG_ENTRY_DOMAIN: &[u8] = b"mimi-root-log-v1";
+
+// Encoded size of one log entry: epoch, root, previous hash
+const LOG_ENTRY_LEN: usize = 8 + COMMITMENT_LEN + 32;
+
+// Define the LogEntry struct, one published root chained to the entry before it
+#[derive(Debug, Clone)]
+pub struct LogEntry<C: SumCommitment> {
+    pub epoch: u64,
+    pub root: Root<C>,
+    // Hash of the previous entry, all zeros for the first one
+    pub previous: Digest,
+}
+
+impl<C: SumCommitment> LogEntry<C> {
+    pub fn hash(&self) -> Digest {
+        let mut hasher = Sha256::new();
+        hasher.update(LOG_ENTRY_DOMAIN);
+        hasher.update(self.epoch.to_le_bytes());
+        hasher.update(self.root.to_bytes());
+        hasher.update(self.previous.as_bytes());
+        Digest::from(hasher.finalize())
+    }
+}
+
+// Define the RootLog struct, an append-only hash chain of epoch roots
+#[derive(Debug, Clone)]
+pub struct RootLog<C: SumCommitment> {
+    entries: Vec<LogEntry<C>>,
+}
+
+impl<C: SumCommitment> Default for RootLog<C> {
+    fn default() -> Self {
+        RootLog::new()
+    }
+}
+
+impl<C: SumCommitment> RootLog<C> {
+    pub fn new() -> Self {
+        RootLog {
+            entries: Vec::new(),
+        }
+    }
+
+    // Rebuilds a log from stored entries, rejecting any break in the chain
+    pub fn from_entries(entries: Vec<LogEntry<C>>) -> Result<Self, LogError> {
+        verify_chain(&entries)?;
+        Ok(RootLog { entries })
+    }
+
+    pub fn append(&mut self, epoch: u64, root: Root<C>) -> Result<&LogEntry<C>, LogError> {
+        if let Some(last) = self.entries.last() {
+            if epoch <= last.epoch {
+                return Err(LogError::EpochNotIncreasing {
+                    previous: last.epoch,
+                    found: epoch,
+                });
+            }
+        }
+        let previous = self.head();
+        self.entries.push(LogEntry {
+            epoch,
+            root,
+            previous,
+        });
+        Ok(self.entries.last().expect("entry was just pushed"))
+    }
+
+    pub fn head(&self) -> Digest {
+        self.entries
+            .last()
+            .map_or(Digest::new([0u8; 32]), LogEntry::hash)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn entries(&self) -> &[LogEntry<C>] {
+        &self.entries
+    }
+
+    pub fn entry(&self, epoch: u64) -> Option<&LogEntry<C>> {
+        self.entries.iter().find(|entry| entry.epoch == epoch)
+    }
+
+    // Entries after `old_epoch` up to and including `new_epoch`; None unless both are
+    // logged and `new_epoch` is the later one
+    pub fn consistency_proof(&self, old_epoch: u64, new_epoch: u64) -> Option<ConsistencyProof<C>> {
+        let old = self.entries.iter().position(|entry| entry.epoch == old_epoch)?;
+        let new = self.entries.iter().position(|entry| entry.epoch == new_epoch)?;
+        (old < new).then(|| ConsistencyProof {
+            entries: self.entries[old + 1..=new].to_vec(),
+        })
+    }
+}
+
+// Define the ConsistencyProof struct, the log entries linking an older published root
+// to a newer one. It shows the newer root's log extends the older one, so nothing
+// published in between was dropped or rewritten.
+#[derive(Debug, Clone)]
+pub struct ConsistencyProof<C: SumCommitment> {
+    pub entries: Vec<LogEntry<C>>,
+}
+
+impl<C: SumCommitment> ConsistencyProof<C> {
+    // Checks each property in turn and reports the first that fails
+    pub fn verify(&self, old: &LogEntry<C>, new: &LogEntry<C>) -> Result<(), ConsistencyError> {
+        if new.epoch <= old.epoch {
+            return Err(ConsistencyError::NotNewer {
+                old: old.epoch,
+                new: new.epoch,
+            });
+        }
+        let last = self.entries.last().ok_or(ConsistencyError::Empty)?;
+        verify_continuation(old, &self.entries).map_err(ConsistencyError::Chain)?;
+        if last.epoch != new.epoch {
+            return Err(ConsistencyError::EndsAtEpoch {
+                expected: new.epoch,
+                found: last.epoch,
+            });
+        }
+        if last.root.to_bytes() != new.root.to_bytes() {
+            return Err(ConsistencyError::RootMismatch(new.epoch));
+        }
+        if last.previous != new.previous {
+            return Err(ConsistencyError::PreviousMismatch(new.epoch));
+        }
+        Ok(())
+    }
+
+    // entry count | per entry: epoch | root | previous hash
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(8 + self.entries.len() * LOG_ENTRY_LEN);
+        bytes.extend_from_slice(&encode_usize(self.entries.len()));
+        for entry in &self.entries {
+            bytes.extend_from_slice(&entry.epoch.to_le_bytes());
+            bytes.extend_from_slice(&entry.root.to_bytes());
+            bytes.extend_from_slice(entry.previous.as_bytes());
+        }
+        bytes
+    }
+}
+
+impl<C: SumCommitment> TryFrom<&[u8]> for ConsistencyProof<C> {
+    type Error = ParseError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        let mut reader = ByteReader::new(bytes);
+        let count = usize::try_from(u64::from_le_bytes(reader.take_array::<8>()?))
+            .map_err(|_| ParseError::PositionOverflow)?;
+        let mut entries = Vec::with_capacity(count.min(bytes.len() / LOG_ENTRY_LEN));
+        for _ in 0..count {
+            entries.push(LogEntry {
+                epoch: u64::from_le_bytes(reader.take_array::<8>()?),
+                root: Root::try_from(reader.take(COMMITMENT_LEN)?)?,
+                previous: Digest::new(reader.take_array::<32>()?),
+            });
+        }
+        reader.finish()?;
+        Ok(ConsistencyProof { entries })
+    }
+}
+
+// Checks a full log from its first entry; returns the head hash
+pub fn verify_chain<C: SumCommitment>(entries: &[LogEntry<C>]) -> Result<Digest, LogError> {
+    verify_links(Digest::new([0u8; 32]), None, entries)
+}
+
+// Checks `entries` extend a log whose last entry is `known`, so a verifier who saw
+// `known` earlier can tell nothing before it was retracted or reordered
+pub fn verify_continuation<C: SumCommitment>(
+    known: &LogEntry<C>,
+    entries: &[LogEntry<C>],
+) -> Result<Digest, LogError> {
+    verify_links(known.hash(), Some(known.epoch), entries)
+}
+
+fn verify_links<C: SumCommitment>(
+    mut head: Digest,
+    mut last_epoch: Option<u64>,
+    entries: &[LogEntry<C>],
+) -> Result<Digest, LogError> {
+    for (index, entry) in entries.iter().enumerate() {
+        if entry.previous != head {
+            return Err(LogError::BrokenLink {
+                index,
+                epoch: entry.epoch,
+            });
+        }
+        if let Some(previous) = last_epoch {
+            if entry.epoch <= previous {
+                return Err(LogError::EpochNotIncreasing {
+                    previous,
+                    found: entry.epoch,
+                });
+            }
+        }
+        head = entry.hash();
+        last_epoch = Some(entry.epoch);
+    }
+    Ok(head)
+}
+
+// Define the LogError enum for appends and chains that break the log's ordering
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LogError {
+    EpochNotIncreasing { previous: u64, found: u64 },
+    BrokenLink { index: usize, epoch: u64 },
+}
+
+impl fmt::Display for LogError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LogError::EpochNotIncreasing { previous, found } => {
+                write!(f, "epoch {} does not follow epoch {}", found, previous)
+            }
+            LogError::BrokenLink { index, epoch } => write!(
+                f,
+                "entry {} (epoch {}) does not chain to the entry before it",
+                index, epoch
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LogError {}
+
+// Define the ConsistencyError enum, the property a consistency proof failed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConsistencyError {
+    // The "new" root isn't from a later epoch than the "old" one
+    NotNewer { old: u64, new: u64 },
+    Empty,
+    // The entries don't extend the old root's log entry
+    Chain(LogError),
+    EndsAtEpoch { expected: u64, found: u64 },
+    // The chain reaches the new epoch with a different root
+    RootMismatch(u64),
+    // The new entry claims a different history than the chain
+    PreviousMismatch(u64),
+}
+
+impl fmt::Display for ConsistencyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConsistencyError::NotNewer { old, new } => {
+                write!(f, "new root (epoch {}) is not newer than old root (epoch {})", new, old)
+            }
+            ConsistencyError::Empty => write!(f, "consistency proof has no entries"),
+            ConsistencyError::Chain(err) => write!(f, "log is not append-only: {}", err),
+            ConsistencyError::EndsAtEpoch { expected, found } => write!(
+                f,
+                "proof ends at epoch {} instead of epoch {}",
+                found, expected
+            ),
+            ConsistencyError::RootMismatch(epoch) => {
+                write!(f, "logged root for epoch {} differs from the new root", epoch)
+            }
+            ConsistencyError::PreviousMismatch(epoch) => write!(
+                f,
+                "new root's previous hash differs from the log's entry before epoch {}",
+                epoch
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ConsistencyError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MimiSumCommitment;
+
+    fn root(epoch: u64) -> Root<MimiSumCommitment> {
+        Root::from_node(MimiSumCommitment::from_parts(
+            epoch * 100,
+            [epoch as u8; 32].into(),
+        ))
+    }
+
+    fn log(epochs: &[u64]) -> RootLog<MimiSumCommitment> {
+        let mut log = RootLog::new();
+        for &epoch in epochs {
+            log.append(epoch, root(epoch)).unwrap();
+        }
+        log
+    }
+
+    #[test]
+    fn rewritten_or_stale_entries_break_the_chain() {
+        let mut log = log(&[1, 2, 3]);
+        assert_eq!(verify_chain(log.entries()), Ok(log.head()));
+        assert_eq!(
+            log.append(3, root(4)).unwrap_err(),
+            LogError::EpochNotIncreasing {
+                previous: 3,
+                found: 3
+            }
+        );
+
+        let mut entries = log.entries().to_vec();
+        entries[1].root = root(7);
+        assert_eq!(
+            RootLog::from_entries(entries).unwrap_err(),
+            LogError::BrokenLink { index: 2, epoch: 3 }
+        );
+    }
+
+    #[test]
+    fn consistency_proofs_only_link_the_logged_history() {
+        let log = log(&[1, 2, 3]);
+        let (old, new) = (log.entry(1).unwrap(), log.entry(3).unwrap());
+        let proof = log.consistency_proof(1, 3).unwrap();
+        assert_eq!(proof.verify(old, new), Ok(()));
+        let parsed = ConsistencyProof::<MimiSumCommitment>::try_from(&proof.to_bytes()[..]);
+        assert_eq!(parsed.unwrap().verify(old, new), Ok(()));
+        assert!(log.consistency_proof(3, 1).is_none());
+        assert!(log.consistency_proof(1, 4).is_none());
+
+        let mut forked = new.clone();
+        forked.root = root(9);
+        assert_eq!(
+            proof.verify(old, &forked),
+            Err(ConsistencyError::RootMismatch(3))
+        );
+
+        let mut skipping = proof.clone();
+        skipping.entries.remove(0);
+        assert_eq!(
+            skipping.verify(old, new),
+            Err(ConsistencyError::Chain(LogError::BrokenLink {
+                index: 0,
+                epoch: 3
+            }))
+        );
+    }
+}
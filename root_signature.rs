@@ -0,0 +1,442 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::bundle::signing_payload;
+use crate::encoding::{ByteReader, COMMITMENT_LEN};
+use crate::reserves::{ethereum_address, recover_ethereum, sign_ethereum};
+use crate::signer::{Signer, SignerError};
+use crate::{ParseError, Root, SumCommitment};
+
+// Define the SignatureScheme enum for the algorithms a root can be signed with. Every
+// signature carries its scheme, so a verifier never guesses how to read the bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SignatureScheme {
+    Ed25519,
+    // ECDSA over SHA-256 of the payload, signatures as r || s with a low s
+    EcdsaSecp256k1,
+    // secp256k1 over the EIP-191 `personal_sign` hash of the payload, signatures as
+    // r || s || v, checked by recovering the signer's Ethereum address
+    EthereumPersonalSign,
+}
+
+impl SignatureScheme {
+    pub fn name(self) -> &'static str {
+        match self {
+            SignatureScheme::Ed25519 => "ed25519",
+            SignatureScheme::EcdsaSecp256k1 => "ecdsa-secp256k1",
+            SignatureScheme::EthereumPersonalSign => "eth-personal-sign",
+        }
+    }
+
+    pub fn tag(self) -> u8 {
+        match self {
+            SignatureScheme::Ed25519 => 1,
+            SignatureScheme::EcdsaSecp256k1 => 2,
+            SignatureScheme::EthereumPersonalSign => 3,
+        }
+    }
+
+    pub fn from_tag(tag: u8) -> Option<Self> {
+        match tag {
+            1 => Some(SignatureScheme::Ed25519),
+            2 => Some(SignatureScheme::EcdsaSecp256k1),
+            3 => Some(SignatureScheme::EthereumPersonalSign),
+            _ => None,
+        }
+    }
+
+    pub fn signature_len(self) -> usize {
+        match self {
+            SignatureScheme::Ed25519 | SignatureScheme::EcdsaSecp256k1 => 64,
+            SignatureScheme::EthereumPersonalSign => 65,
+        }
+    }
+}
+
+impl fmt::Display for SignatureScheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+// Define the TaggedSignature struct, signature bytes and the scheme that made them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaggedSignature {
+    scheme: SignatureScheme,
+    bytes: Vec<u8>,
+}
+
+impl TaggedSignature {
+    pub fn new(scheme: SignatureScheme, bytes: Vec<u8>) -> Result<Self, SignatureError> {
+        if bytes.len() != scheme.signature_len() {
+            return Err(SignatureError::Malformed(scheme));
+        }
+        Ok(TaggedSignature { scheme, bytes })
+    }
+
+    pub fn scheme(&self) -> SignatureScheme {
+        self.scheme
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    // scheme tag (u8) | signature, whose length the scheme fixes
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(1 + self.bytes.len());
+        bytes.push(self.scheme.tag());
+        bytes.extend_from_slice(&self.bytes);
+        bytes
+    }
+
+    pub(crate) fn read(reader: &mut ByteReader<'_>) -> Result<Self, ParseError> {
+        let tag = reader.take_array::<1>()?[0];
+        let scheme = SignatureScheme::from_tag(tag).ok_or(ParseError::UnknownMagic)?;
+        Ok(TaggedSignature {
+            scheme,
+            bytes: reader.take(scheme.signature_len())?.to_vec(),
+        })
+    }
+}
+
+impl TryFrom<&[u8]> for TaggedSignature {
+    type Error = ParseError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        let mut reader = ByteReader::new(bytes);
+        let signature = Self::read(&mut reader)?;
+        reader.finish()?;
+        Ok(signature)
+    }
+}
+
+// Displays as `<scheme>:<hex>`
+impl fmt::Display for TaggedSignature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.scheme, hex::encode(&self.bytes))
+    }
+}
+
+impl FromStr for TaggedSignature {
+    type Err = ParseError;
+
+    fn from_str(encoded: &str) -> Result<Self, Self::Err> {
+        let (name, signature) = encoded
+            .split_once(':')
+            .ok_or(ParseError::MissingSeparator)?;
+        let scheme = [
+            SignatureScheme::Ed25519,
+            SignatureScheme::EcdsaSecp256k1,
+            SignatureScheme::EthereumPersonalSign,
+        ]
+        .into_iter()
+        .find(|scheme| scheme.name() == name)
+        .ok_or(ParseError::UnknownMagic)?;
+        let bytes = hex::decode(signature).map_err(|_| ParseError::InvalidHex)?;
+        TaggedSignature::new(scheme, bytes).map_err(|_| ParseError::InvalidLength {
+            expected: scheme.signature_len(),
+            found: signature.len() / 2,
+        })
+    }
+}
+
+// Define the RootSigner trait, any key that can sign root payloads. Teams with keys in
+// an existing PKI implement it for their own backend.
+pub trait RootSigner {
+    fn scheme(&self) -> SignatureScheme;
+    // Public key in the scheme's usual encoding
+    fn public_key(&self) -> Result<Vec<u8>, SignerError>;
+    fn sign_bytes(&self, payload: &[u8]) -> Result<TaggedSignature, SignerError>;
+}
+
+// Define the RootVerifier trait, the public half matching a RootSigner
+pub trait RootVerifier {
+    fn scheme(&self) -> SignatureScheme;
+    fn verify_bytes(
+        &self,
+        payload: &[u8],
+        signature: &TaggedSignature,
+    ) -> Result<(), SignatureError>;
+}
+
+// Ed25519 public keys are 32 raw bytes
+impl RootSigner for ed25519_dalek::SigningKey {
+    fn scheme(&self) -> SignatureScheme {
+        SignatureScheme::Ed25519
+    }
+
+    fn public_key(&self) -> Result<Vec<u8>, SignerError> {
+        Ok(self.verifying_key().to_bytes().to_vec())
+    }
+
+    fn sign_bytes(&self, payload: &[u8]) -> Result<TaggedSignature, SignerError> {
+        let signature = ed25519_dalek::Signer::sign(self, payload);
+        Ok(TaggedSignature {
+            scheme: SignatureScheme::Ed25519,
+            bytes: signature.to_bytes().to_vec(),
+        })
+    }
+}
+
+impl RootVerifier for ed25519_dalek::VerifyingKey {
+    fn scheme(&self) -> SignatureScheme {
+        SignatureScheme::Ed25519
+    }
+
+    fn verify_bytes(
+        &self,
+        payload: &[u8],
+        signature: &TaggedSignature,
+    ) -> Result<(), SignatureError> {
+        expect_scheme(SignatureScheme::Ed25519, signature)?;
+        let bytes: [u8; 64] = signature.bytes[..]
+            .try_into()
+            .map_err(|_| SignatureError::Malformed(SignatureScheme::Ed25519))?;
+        self.verify_strict(payload, &ed25519_dalek::Signature::from_bytes(&bytes))
+            .map_err(|_| SignatureError::BadSignature)
+    }
+}
+
+// Define the Ed25519Backend struct, which lets an existing `Signer` (HSM, KMS, PKCS#11)
+// sign through the scheme-tagged interface
+#[derive(Debug)]
+pub struct Ed25519Backend<S: Signer>(pub S);
+
+impl<S: Signer> RootSigner for Ed25519Backend<S> {
+    fn scheme(&self) -> SignatureScheme {
+        SignatureScheme::Ed25519
+    }
+
+    fn public_key(&self) -> Result<Vec<u8>, SignerError> {
+        Ok(self.0.verifying_key()?.to_bytes().to_vec())
+    }
+
+    fn sign_bytes(&self, payload: &[u8]) -> Result<TaggedSignature, SignerError> {
+        Ok(TaggedSignature {
+            scheme: SignatureScheme::Ed25519,
+            bytes: self.0.sign(payload)?.to_bytes().to_vec(),
+        })
+    }
+}
+
+// secp256k1 public keys are 33-byte compressed SEC1 points
+impl RootSigner for k256::ecdsa::SigningKey {
+    fn scheme(&self) -> SignatureScheme {
+        SignatureScheme::EcdsaSecp256k1
+    }
+
+    fn public_key(&self) -> Result<Vec<u8>, SignerError> {
+        Ok(self
+            .verifying_key()
+            .to_encoded_point(true)
+            .as_bytes()
+            .to_vec())
+    }
+
+    fn sign_bytes(&self, payload: &[u8]) -> Result<TaggedSignature, SignerError> {
+        let signature: k256::ecdsa::Signature =
+            k256::ecdsa::signature::Signer::try_sign(self, payload)
+                .map_err(|err| SignerError::Backend(err.to_string()))?;
+        Ok(TaggedSignature {
+            scheme: SignatureScheme::EcdsaSecp256k1,
+            bytes: signature.to_bytes().to_vec(),
+        })
+    }
+}
+
+impl RootVerifier for k256::ecdsa::VerifyingKey {
+    fn scheme(&self) -> SignatureScheme {
+        SignatureScheme::EcdsaSecp256k1
+    }
+
+    fn verify_bytes(
+        &self,
+        payload: &[u8],
+        signature: &TaggedSignature,
+    ) -> Result<(), SignatureError> {
+        expect_scheme(SignatureScheme::EcdsaSecp256k1, signature)?;
+        let parsed = k256::ecdsa::Signature::from_slice(&signature.bytes)
+            .map_err(|_| SignatureError::Malformed(SignatureScheme::EcdsaSecp256k1))?;
+        // Malleable high-s twins of a valid signature are refused
+        if parsed.normalize_s().is_some() {
+            return Err(SignatureError::BadSignature);
+        }
+        k256::ecdsa::signature::Verifier::verify(self, payload, &parsed)
+            .map_err(|_| SignatureError::BadSignature)
+    }
+}
+
+// Define the EthereumSigner struct, a secp256k1 key signing roots the way wallets sign
+// messages, so a root can be attested by the key behind a well-known on-chain address
+#[derive(Debug, Clone)]
+pub struct EthereumSigner(pub k256::ecdsa::SigningKey);
+
+impl EthereumSigner {
+    // Lowercase 0x-prefixed address of the key
+    pub fn address(&self) -> String {
+        ethereum_address(self.0.verifying_key())
+    }
+}
+
+// The public key is the 20-byte address
+impl RootSigner for EthereumSigner {
+    fn scheme(&self) -> SignatureScheme {
+        SignatureScheme::EthereumPersonalSign
+    }
+
+    fn public_key(&self) -> Result<Vec<u8>, SignerError> {
+        hex::decode(&self.address()[2..]).map_err(|_| SignerError::InvalidKey)
+    }
+
+    fn sign_bytes(&self, payload: &[u8]) -> Result<TaggedSignature, SignerError> {
+        Ok(TaggedSignature {
+            scheme: SignatureScheme::EthereumPersonalSign,
+            bytes: sign_ethereum(&self.0, payload).to_vec(),
+        })
+    }
+}
+
+// Define the EthereumAddress struct, a verifier that knows only the signer's address.
+// Checksummed and lowercase spellings are the same address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EthereumAddress(String);
+
+impl EthereumAddress {
+    pub fn parse(address: &str) -> Result<Self, ParseError> {
+        let hex_part = address
+            .strip_prefix("0x")
+            .ok_or(ParseError::MissingSeparator)?;
+        let bytes = hex::decode(hex_part).map_err(|_| ParseError::InvalidHex)?;
+        if bytes.len() != 20 {
+            return Err(ParseError::InvalidLength {
+                expected: 20,
+                found: bytes.len(),
+            });
+        }
+        Ok(EthereumAddress(format!("0x{}", hex::encode(bytes))))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl RootVerifier for EthereumAddress {
+    fn scheme(&self) -> SignatureScheme {
+        SignatureScheme::EthereumPersonalSign
+    }
+
+    fn verify_bytes(
+        &self,
+        payload: &[u8],
+        signature: &TaggedSignature,
+    ) -> Result<(), SignatureError> {
+        expect_scheme(SignatureScheme::EthereumPersonalSign, signature)?;
+        let recovered = recover_ethereum(payload, &signature.bytes)
+            .map_err(|_| SignatureError::Malformed(SignatureScheme::EthereumPersonalSign))?;
+        if recovered != self.0 {
+            return Err(SignatureError::WrongSigner {
+                expected: self.0.clone(),
+                recovered,
+            });
+        }
+        Ok(())
+    }
+}
+
+fn expect_scheme(
+    expected: SignatureScheme,
+    signature: &TaggedSignature,
+) -> Result<(), SignatureError> {
+    if signature.scheme != expected {
+        return Err(SignatureError::SchemeMismatch {
+            expected,
+            found: signature.scheme,
+        });
+    }
+    Ok(())
+}
+
+// Define the TaggedSignedRoot struct, an epoch root signed under any supported scheme.
+// The payload is the same one `SignedRoot` signs.
+#[derive(Debug, Clone)]
+pub struct TaggedSignedRoot<C: SumCommitment> {
+    pub epoch: u64,
+    pub root: Root<C>,
+    pub signature: TaggedSignature,
+}
+
+impl<C: SumCommitment> TaggedSignedRoot<C> {
+    pub fn sign(epoch: u64, root: Root<C>, signer: &dyn RootSigner) -> Result<Self, SignerError> {
+        let signature = signer.sign_bytes(&signing_payload(epoch, &root))?;
+        if signature.scheme != signer.scheme() {
+            return Err(SignerError::InvalidSignature);
+        }
+        Ok(TaggedSignedRoot {
+            epoch,
+            root,
+            signature,
+        })
+    }
+
+    pub fn verify(&self, verifier: &dyn RootVerifier) -> Result<(), SignatureError> {
+        verifier.verify_bytes(&signing_payload(self.epoch, &self.root), &self.signature)
+    }
+
+    // epoch | root | tagged signature
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.epoch.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&self.root.to_bytes());
+        bytes.extend_from_slice(&self.signature.to_bytes());
+        bytes
+    }
+}
+
+impl<C: SumCommitment> TryFrom<&[u8]> for TaggedSignedRoot<C> {
+    type Error = ParseError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        let mut reader = ByteReader::new(bytes);
+        let epoch = u64::from_le_bytes(reader.take_array::<8>()?);
+        let root = Root::try_from(reader.take(COMMITMENT_LEN)?)?;
+        let signature = TaggedSignature::read(&mut reader)?;
+        reader.finish()?;
+        Ok(TaggedSignedRoot {
+            epoch,
+            root,
+            signature,
+        })
+    }
+}
+
+// Define the SignatureError enum for tagged signatures that don't verify
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignatureError {
+    SchemeMismatch {
+        expected: SignatureScheme,
+        found: SignatureScheme,
+    },
+    Malformed(SignatureScheme),
+    BadSignature,
+    // A recovered signer other than the expected address
+    WrongSigner { expected: String, recovered: String },
+}
+
+impl fmt::Display for SignatureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignatureError::SchemeMismatch { expected, found } => {
+                write!(f, "expected a {} signature, found {}", expected, found)
+            }
+            SignatureError::Malformed(scheme) => write!(f, "malformed {} signature", scheme),
+            SignatureError::BadSignature => write!(f, "signature does not verify"),
+            SignatureError::WrongSigner {
+                expected,
+                recovered,
+            } => write!(f, "signature for {} was made by {}", expected, recovered),
+        }
+    }
+}
+
+impl std::error::Error for SignatureError {}
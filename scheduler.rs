@@ -0,0 +1,291 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::bundle::SignedRoot;
+use crate::server::{ProofServer, ServedEpoch, ServerError, TenantId};
+use crate::signer::{Signer, SignerError};
+use crate::snapshot::Snapshot;
+use crate::{
+    BuildError, Leaf, MerkleProof, MerkleSumTreeBuilder, SumCommitment, TreeConfig, UserLeaf,
+};
+
+// Define the Schedule struct, when epochs are cut: every `interval`, aligned to the Unix
+// epoch and shifted by `offset`. An hourly schedule with a 5-minute offset runs at :05.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Schedule {
+    pub interval: Duration,
+    pub offset: Duration,
+}
+
+impl Schedule {
+    pub fn every(interval: Duration) -> Self {
+        Schedule {
+            interval,
+            offset: Duration::ZERO,
+        }
+    }
+
+    pub fn with_offset(mut self, offset: Duration) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    // First slot strictly after `now`
+    pub fn next_after(&self, now: SystemTime) -> SystemTime {
+        let interval = self.interval.as_secs().max(1);
+        let offset = self.offset.as_secs() % interval;
+        let now = now
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        let slot = (now.saturating_sub(offset) / interval + 1) * interval + offset;
+        UNIX_EPOCH + Duration::from_secs(slot)
+    }
+}
+
+// Define the SnapshotStore trait, where each epoch's snapshot is persisted before the
+// epoch is served
+pub trait SnapshotStore<C: SumCommitment> {
+    fn persist(&mut self, snapshot: &Snapshot<C>) -> Result<(), String>;
+
+    // Called once the snapshot is persisted; stores that feed an EpochReloader keep it
+    fn persist_root(&mut self, _signed_root: &SignedRoot<C>) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+// Define the DirectoryStore struct, one `epoch-<n>.snapshot` file per epoch and the
+// signed root next to it as `epoch-<n>.root`
+#[derive(Debug, Clone)]
+pub struct DirectoryStore {
+    dir: PathBuf,
+}
+
+impl DirectoryStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        DirectoryStore { dir: dir.into() }
+    }
+
+    // `<dir>/<tenant>`, so tenants sharing a store never see each other's epochs
+    pub fn for_tenant(dir: impl Into<PathBuf>, tenant: &TenantId) -> Self {
+        DirectoryStore::new(dir.into().join(tenant.as_str()))
+    }
+
+    pub fn path(&self, epoch: u64) -> PathBuf {
+        self.dir.join(format!("epoch-{}.snapshot", epoch))
+    }
+
+    pub fn root_path(&self, epoch: u64) -> PathBuf {
+        self.dir.join(format!("epoch-{}.root", epoch))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+impl<C: SumCommitment> SnapshotStore<C> for DirectoryStore {
+    fn persist(&mut self, snapshot: &Snapshot<C>) -> Result<(), String> {
+        fs::write(self.path(snapshot.epoch), snapshot.to_bytes()).map_err(|err| err.to_string())
+    }
+
+    // Written last and renamed into place, so a reader that sees the root file also
+    // sees the whole snapshot
+    fn persist_root(&mut self, signed_root: &SignedRoot<C>) -> Result<(), String> {
+        let path = self.root_path(signed_root.epoch);
+        let partial = path.with_extension("root.partial");
+        fs::write(&partial, signed_root.to_bytes()).map_err(|err| err.to_string())?;
+        fs::rename(&partial, &path).map_err(|err| err.to_string())
+    }
+}
+
+// Define the DataSource trait, the callback that returns every account's balance at the
+// moment an epoch is cut
+pub trait DataSource<L: Leaf> {
+    fn accounts(&mut self, epoch: u64) -> Result<Vec<UserLeaf<L>>, String>;
+}
+
+impl<L: Leaf, F: FnMut(u64) -> Result<Vec<UserLeaf<L>>, String>> DataSource<L> for F {
+    fn accounts(&mut self, epoch: u64) -> Result<Vec<UserLeaf<L>>, String> {
+        self(epoch)
+    }
+}
+
+// Define the EpochScheduler struct, which cuts epochs end to end: pull balances, build the
+// tree, sign the root, persist the snapshot and publish the epoch. A failing step leaves
+// the served epoch unchanged.
+pub struct EpochScheduler<C: SumCommitment, L: Leaf> {
+    schedule: Schedule,
+    config: TreeConfig,
+    source: Box<dyn DataSource<L> + Send>,
+    signer: Box<dyn Signer + Send>,
+    store: Box<dyn SnapshotStore<C> + Send>,
+    first_epoch: u64,
+}
+
+impl<C, L> EpochScheduler<C, L>
+where
+    C: SumCommitment,
+    L: Leaf,
+{
+    pub fn new(
+        schedule: Schedule,
+        source: impl DataSource<L> + Send + 'static,
+        signer: impl Signer + Send + 'static,
+        store: impl SnapshotStore<C> + Send + 'static,
+    ) -> Self {
+        EpochScheduler {
+            schedule,
+            config: TreeConfig::default(),
+            source: Box::new(source),
+            signer: Box::new(signer),
+            store: Box::new(store),
+            first_epoch: 1,
+        }
+    }
+
+    pub fn with_config(mut self, config: TreeConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    // Epoch number used when the server hasn't served any yet
+    pub fn with_first_epoch(mut self, first_epoch: u64) -> Self {
+        self.first_epoch = first_epoch;
+        self
+    }
+
+    pub fn schedule(&self) -> &Schedule {
+        &self.schedule
+    }
+
+    // Swaps the signing backend, e.g. after a key rotation; the next run signs with it
+    pub fn set_signer(&mut self, signer: Box<dyn Signer + Send>) {
+        self.signer = signer;
+    }
+
+    // Epoch number the next run will cut
+    pub fn next_epoch(&self, server: &ProofServer<C>) -> u64 {
+        server
+            .current()
+            .map_or(self.first_epoch, |current| current.epoch() + 1)
+    }
+
+    // Cuts the epoch after the server's current one; returns its number
+    pub fn run_once(&mut self, server: &Mutex<ProofServer<C>>) -> Result<u64, SchedulerError> {
+        let epoch = self.next_epoch(&*server.lock().map_err(|_| SchedulerError::Poisoned)?);
+        let accounts = self.source.accounts(epoch).map_err(SchedulerError::Source)?;
+        let tree = MerkleSumTreeBuilder::<C, MerkleProof<C>>::with_config(self.config.clone())
+            .build(&accounts)?;
+        let signed_root = SignedRoot::sign_with(epoch, tree.commit(), self.signer.as_ref())?;
+        self.store
+            .persist(&Snapshot::capture(epoch, &tree, &accounts))
+            .map_err(SchedulerError::Store)?;
+        self.store
+            .persist_root(&signed_root)
+            .map_err(SchedulerError::Store)?;
+
+        let mut served = ServedEpoch::new(tree, signed_root)?;
+        for (index, account) in accounts.iter().enumerate() {
+            served = served.with_account(account.user_id.clone(), index);
+        }
+        server
+            .lock()
+            .map_err(|_| SchedulerError::Poisoned)?
+            .publish(served)?;
+        Ok(epoch)
+    }
+
+    // Runs on the schedule until `stop` is called on the handle; every run's outcome is
+    // passed to `on_run`
+    pub fn spawn(
+        mut self,
+        server: Arc<Mutex<ProofServer<C>>>,
+        mut on_run: impl FnMut(Result<u64, SchedulerError>) + Send + 'static,
+    ) -> SchedulerHandle
+    where
+        C: Send + 'static,
+        C::Leaf: Send,
+        L: 'static,
+    {
+        let (stop, stopped) = mpsc::channel();
+        let thread = thread::spawn(move || loop {
+            let next = self.schedule.next_after(SystemTime::now());
+            let wait = next
+                .duration_since(SystemTime::now())
+                .unwrap_or(Duration::ZERO);
+            match stopped.recv_timeout(wait) {
+                Err(RecvTimeoutError::Timeout) => on_run(self.run_once(&server)),
+                Ok(()) | Err(RecvTimeoutError::Disconnected) => return,
+            }
+        });
+        SchedulerHandle { stop, thread }
+    }
+}
+
+// Define the SchedulerHandle struct, the running scheduler (or reloader) thread
+#[derive(Debug)]
+pub struct SchedulerHandle {
+    stop: Sender<()>,
+    thread: JoinHandle<()>,
+}
+
+impl SchedulerHandle {
+    pub(crate) fn new(stop: Sender<()>, thread: JoinHandle<()>) -> Self {
+        SchedulerHandle { stop, thread }
+    }
+
+    // Waits for a run in progress to finish
+    pub fn stop(self) {
+        let _ = self.stop.send(());
+        let _ = self.thread.join();
+    }
+}
+
+// Define the SchedulerError enum for epochs that couldn't be cut
+#[derive(Debug)]
+pub enum SchedulerError {
+    Source(String),
+    Build(BuildError),
+    Signing(SignerError),
+    Store(String),
+    Server(ServerError),
+    Poisoned,
+}
+
+impl fmt::Display for SchedulerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchedulerError::Source(err) => write!(f, "data source failed: {}", err),
+            SchedulerError::Build(err) => write!(f, "cannot build tree: {}", err),
+            SchedulerError::Signing(err) => write!(f, "cannot sign root: {}", err),
+            SchedulerError::Store(err) => write!(f, "cannot persist snapshot: {}", err),
+            SchedulerError::Server(err) => write!(f, "cannot publish epoch: {}", err),
+            SchedulerError::Poisoned => write!(f, "proof server lock is poisoned"),
+        }
+    }
+}
+
+impl std::error::Error for SchedulerError {}
+
+impl From<BuildError> for SchedulerError {
+    fn from(err: BuildError) -> Self {
+        SchedulerError::Build(err)
+    }
+}
+
+impl From<SignerError> for SchedulerError {
+    fn from(err: SignerError) -> Self {
+        SchedulerError::Signing(err)
+    }
+}
+
+impl From<ServerError> for SchedulerError {
+    fn from(err: ServerError) -> Self {
+        SchedulerError::Server(err)
+    }
+}
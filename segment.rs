@@ -0,0 +1,355 @@
+use std::fmt;
+
+use sha2::{Digest as _, Sha256};
+
+use crate::arena::fold_shape;
+use crate::encoding::{encode_usize, ByteReader, MAX_PROOF_DEPTH};
+use crate::{Leaf, ParseError, VerifyError, LEAF_PREFIX, NODE_PREFIX};
+
+
+// Bytes in one encoded node: low and high key, sum, count, digest
+pub const SEGMENT_NODE_LEN: usize = 8 + 8 + 8 + 8 + 32;
+
+// Define the SegmentNode struct, a node of a segment tree: the key interval its leaves
+// span, e.g. account opening times, with their total and how many there are. All four
+// are hashed into the parent, so a proof node can't misstate what it covers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SegmentNode {
+    pub low: u64,
+    pub high: u64,
+    pub sum: u64,
+    pub count: u64,
+    pub digest: [u8; 32],
+}
+
+impl SegmentNode {
+    // Hashes 0 | key | amount | leaf encoding
+    pub fn from_leaf<L: Leaf>(key: u64, leaf: &L) -> Self {
+        let digest = Sha256::new()
+            .chain_update([LEAF_PREFIX])
+            .chain_update(key.to_le_bytes())
+            .chain_update(leaf.amount().to_le_bytes())
+            .chain_update(leaf.encode_for_hash())
+            .finalize();
+        SegmentNode {
+            low: key,
+            high: key,
+            sum: leaf.amount(),
+            count: 1,
+            digest: digest.into(),
+        }
+    }
+
+    // Hashes 1 | left encoding | right encoding
+    pub fn combine(left: &Self, right: &Self) -> Option<Self> {
+        let digest = Sha256::new()
+            .chain_update([NODE_PREFIX])
+            .chain_update(left.to_bytes())
+            .chain_update(right.to_bytes())
+            .finalize();
+        Some(SegmentNode {
+            low: left.low,
+            high: right.high,
+            sum: left.sum.checked_add(right.sum)?,
+            count: left.count + right.count,
+            digest: digest.into(),
+        })
+    }
+
+    pub fn to_bytes(&self) -> [u8; SEGMENT_NODE_LEN] {
+        let mut bytes = [0u8; SEGMENT_NODE_LEN];
+        bytes[..8].copy_from_slice(&self.low.to_le_bytes());
+        bytes[8..16].copy_from_slice(&self.high.to_le_bytes());
+        bytes[16..24].copy_from_slice(&self.sum.to_le_bytes());
+        bytes[24..32].copy_from_slice(&self.count.to_le_bytes());
+        bytes[32..].copy_from_slice(&self.digest);
+        bytes
+    }
+
+    fn read(reader: &mut ByteReader<'_>) -> Result<Self, ParseError> {
+        Ok(SegmentNode {
+            low: u64::from_le_bytes(reader.take_array()?),
+            high: u64::from_le_bytes(reader.take_array()?),
+            sum: u64::from_le_bytes(reader.take_array()?),
+            count: u64::from_le_bytes(reader.take_array()?),
+            digest: reader.take_array()?,
+        })
+    }
+}
+
+// Define the RangeAggregate struct, what a range proof establishes about the leaves whose
+// keys fall in the queried interval
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RangeAggregate {
+    pub sum: u64,
+    pub count: u64,
+}
+
+// Define the SegmentTree struct, a sum tree over leaves sorted by key, with the same
+// halving shape as `MimkMerkleTree`. A key range of leaves is covered by at most two
+// subtrees per level, so "total liabilities of accounts opened in Q3" is proven from
+// those aggregates alone, without revealing any account in it.
+#[derive(Debug, Clone)]
+pub struct SegmentTree {
+    leaves: Vec<SegmentNode>,
+}
+
+impl SegmentTree {
+    // Leaves must already be in key order; equal keys may come in any order
+    pub fn from_leaves<L: Leaf>(leaves: &[(u64, L)]) -> Result<Self, SegmentError> {
+        Self::from_nodes(
+            leaves
+                .iter()
+                .map(|(key, leaf)| SegmentNode::from_leaf(*key, leaf))
+                .collect(),
+        )
+    }
+
+    pub fn from_nodes(leaves: Vec<SegmentNode>) -> Result<Self, SegmentError> {
+        if let Some(position) = leaves.windows(2).position(|pair| pair[0].high > pair[1].low) {
+            return Err(SegmentError::Unsorted(position + 1));
+        }
+        let tree = SegmentTree { leaves };
+        if !tree.leaves.is_empty() && tree.root().is_none() {
+            return Err(SegmentError::Verify(VerifyError::SumOverflow));
+        }
+        Ok(tree)
+    }
+
+    pub fn leaves(&self) -> &[SegmentNode] {
+        &self.leaves
+    }
+
+    pub fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+
+    pub fn root(&self) -> Option<SegmentNode> {
+        fold_range(&self.leaves)
+    }
+
+    // Aggregate of every leaf with a key in [low, high], both ends inclusive
+    pub fn prove_range(&self, low: u64, high: u64) -> RangeProof {
+        let start = self.leaves.partition_point(|leaf| leaf.low < low);
+        let end = if low > high {
+            start
+        } else {
+            start + self.leaves[start..].partition_point(|leaf| leaf.low <= high)
+        };
+        let mut nodes = Vec::new();
+        collect_cover(&self.leaves, 0, start, end, &mut nodes);
+        RangeProof {
+            low,
+            high,
+            start,
+            end,
+            leaf_count: self.leaves.len(),
+            nodes,
+        }
+    }
+}
+
+fn fold_range(nodes: &[SegmentNode]) -> Option<SegmentNode> {
+    // An overflowing sum poisons every node above it; `from_nodes` rejects such trees
+    fold_shape(
+        nodes.len(),
+        |position| Some(nodes[position].clone()),
+        |left: &Option<SegmentNode>, right: &Option<SegmentNode>| {
+            SegmentNode::combine(left.as_ref()?, right.as_ref()?)
+        },
+    )
+    .flatten()
+}
+
+fn outside(offset: usize, len: usize, start: usize, end: usize) -> bool {
+    offset + len <= start || offset >= end
+}
+
+fn inside(offset: usize, len: usize, start: usize, end: usize) -> bool {
+    offset >= start && offset + len <= end
+}
+
+// Pushes, left to right, the largest subtrees lying wholly inside or wholly outside the
+// range; together they cover every leaf once
+fn collect_cover(
+    nodes: &[SegmentNode],
+    offset: usize,
+    start: usize,
+    end: usize,
+    out: &mut Vec<SegmentNode>,
+) {
+    if outside(offset, nodes.len(), start, end) || inside(offset, nodes.len(), start, end) {
+        out.extend(fold_range(nodes));
+    } else {
+        let (left, right) = nodes.split_at(nodes.len() / 2);
+        collect_cover(left, offset, start, end, out);
+        collect_cover(right, offset + left.len(), start, end, out);
+    }
+}
+
+// Define the RangeProof struct, a proof that leaves [start, end) are exactly those with a
+// key in [low, high]. Subtrees inside the range must have keys within it, those left of
+// it must end below `low` and those right of it start above `high`, so no leaf of the
+// range can be left out and none from outside counted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RangeProof {
+    pub low: u64,
+    pub high: u64,
+    pub start: usize,
+    pub end: usize,
+    pub leaf_count: usize,
+    // Covering subtrees, left to right
+    pub nodes: Vec<SegmentNode>,
+}
+
+impl RangeProof {
+    // Checks the proof against the root and returns the range's aggregate
+    pub fn verify(&self, root: &SegmentNode) -> Result<RangeAggregate, SegmentError> {
+        if self.leaf_count == 0 || self.start > self.end || self.end > self.leaf_count {
+            return Err(SegmentError::LeafCount);
+        }
+        let mut nodes = self.nodes.iter();
+        let mut aggregate = RangeAggregate::default();
+        let rebuilt = self.rebuild(0, self.leaf_count, &mut nodes, &mut aggregate)?;
+        if nodes.next().is_some() {
+            return Err(SegmentError::Incomplete);
+        }
+        if rebuilt.digest != root.digest
+            || rebuilt.low != root.low
+            || rebuilt.high != root.high
+            || rebuilt.count != root.count
+        {
+            return Err(SegmentError::Verify(VerifyError::DigestMismatch));
+        }
+        if rebuilt.sum != root.sum {
+            return Err(SegmentError::Verify(VerifyError::SumMismatch {
+                root: root.sum,
+                proof: rebuilt.sum,
+            }));
+        }
+        Ok(aggregate)
+    }
+
+    // Recomputes the subtree [offset, offset + len), adding covering nodes inside the
+    // range to `aggregate`
+    fn rebuild<'a>(
+        &self,
+        offset: usize,
+        len: usize,
+        nodes: &mut impl Iterator<Item = &'a SegmentNode>,
+        aggregate: &mut RangeAggregate,
+    ) -> Result<SegmentNode, SegmentError> {
+        let covering = inside(offset, len, self.start, self.end);
+        if covering || outside(offset, len, self.start, self.end) {
+            let node = nodes.next().ok_or(SegmentError::Incomplete)?;
+            // The shape fixes how many leaves a subtree holds
+            if node.count != len as u64 || node.low > node.high {
+                return Err(SegmentError::Incomplete);
+            }
+            let bounded = if covering {
+                node.low >= self.low && node.high <= self.high
+            } else if offset + len <= self.start {
+                node.high < self.low
+            } else {
+                node.low > self.high
+            };
+            if !bounded {
+                return Err(SegmentError::Incomplete);
+            }
+            if covering {
+                aggregate.sum = aggregate
+                    .sum
+                    .checked_add(node.sum)
+                    .ok_or(SegmentError::Verify(VerifyError::SumOverflow))?;
+                aggregate.count += node.count;
+            }
+            return Ok(node.clone());
+        }
+        let middle = len / 2;
+        let left = self.rebuild(offset, middle, nodes, aggregate)?;
+        let right = self.rebuild(offset + middle, len - middle, nodes, aggregate)?;
+        if left.high > right.low {
+            return Err(SegmentError::Incomplete);
+        }
+        SegmentNode::combine(&left, &right).ok_or(SegmentError::Verify(VerifyError::SumOverflow))
+    }
+
+    // low | high | start | end | leaf count | node count (u8) | nodes
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(41 + self.nodes.len() * SEGMENT_NODE_LEN);
+        bytes.extend_from_slice(&self.low.to_le_bytes());
+        bytes.extend_from_slice(&self.high.to_le_bytes());
+        bytes.extend_from_slice(&encode_usize(self.start));
+        bytes.extend_from_slice(&encode_usize(self.end));
+        bytes.extend_from_slice(&encode_usize(self.leaf_count));
+        bytes.push(self.nodes.len() as u8);
+        for node in &self.nodes {
+            bytes.extend_from_slice(&node.to_bytes());
+        }
+        bytes
+    }
+}
+
+impl TryFrom<&[u8]> for RangeProof {
+    type Error = ParseError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        let mut reader = ByteReader::new(bytes);
+        let low = u64::from_le_bytes(reader.take_array()?);
+        let high = u64::from_le_bytes(reader.take_array()?);
+        let mut position = || {
+            usize::try_from(u64::from_le_bytes(reader.take_array()?))
+                .map_err(|_| ParseError::PositionOverflow)
+        };
+        let (start, end, leaf_count) = (position()?, position()?, position()?);
+        // Inside and outside subtrees together: at most two per level
+        let count = reader.take_array::<1>()?[0] as usize;
+        if count > 2 * MAX_PROOF_DEPTH {
+            return Err(ParseError::TooDeep(count));
+        }
+        let mut nodes = Vec::with_capacity(count);
+        for _ in 0..count {
+            nodes.push(SegmentNode::read(&mut reader)?);
+        }
+        reader.finish()?;
+        Ok(RangeProof {
+            low,
+            high,
+            start,
+            end,
+            leaf_count,
+            nodes,
+        })
+    }
+}
+
+// Define the SegmentError enum for segment trees that can't be built and range proofs
+// that don't hold
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SegmentError {
+    // The leaf at this position sorts before the one ahead of it
+    Unsorted(usize),
+    LeafCount,
+    // Proof nodes are missing, left over, or straddle the range bounds
+    Incomplete,
+    Verify(VerifyError),
+}
+
+impl fmt::Display for SegmentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SegmentError::Unsorted(position) => write!(f, "leaf {} is out of key order", position),
+            SegmentError::LeafCount => write!(f, "leaf count does not match the proof range"),
+            SegmentError::Incomplete => {
+                write!(f, "proof does not show the range is covered exactly")
+            }
+            SegmentError::Verify(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for SegmentError {}
@@ -0,0 +1,821 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use ed25519_dalek::Signature;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
+
+use crate::bundle::{ProofBundle, SignedRoot};
+use crate::root_log::{ConsistencyProof, LogEntry, RootLog};
+use crate::{MerkleProof, MimkMerkleTree, ParseError, Root, SumCommitment};
+
+mod admin;
+mod health;
+mod limits;
+#[cfg(feature = "openapi")]
+mod openapi;
+mod pages;
+mod reload;
+mod tenants;
+#[cfg(feature = "tls")]
+mod tls;
+mod tokens;
+mod webhooks;
+
+pub use admin::{
+    AdminApi, AdminAuth, AdminCommand, AdminCredentials, AdminError, AdminResponse, KeyRotation,
+    ADMIN_PATH,
+};
+pub use health::{
+    BuildProgress, HealthResponse, ReadinessResponse, StatusResponse, HEALTHZ_PATH, READYZ_PATH,
+    STATUS_PATH,
+};
+pub use limits::{Limits, Rate, RateLimiter};
+#[cfg(feature = "openapi")]
+pub use openapi::{openapi_spec, OpenApiOptions};
+pub use pages::{MultiproofPage, MultiproofPageRequest, PageRecord, MAX_PAGE_SIZE};
+pub use reload::{EpochReloader, ReloadError};
+pub use tenants::{split_tenant_path, TenantAdmin, TenantId, TenantServer, TENANT_PREFIX};
+#[cfg(feature = "tls")]
+pub use tls::{ClientAuth, TlsConnection, TlsError, TlsListener, TlsSettings};
+pub use tokens::{RetrievalToken, TokenKey};
+pub use webhooks::{
+    signature_header, verify_signature, Delivery, EventKind, Subscription, WebhookDispatcher,
+    WebhookEvent, WebhookTransport, SIGNATURE_HEADER,
+};
+
+use limits::RequestGuard;
+use pages::{selection_tag, Cursor};
+
+// The proof server is transport-agnostic: HTTP or gRPC bindings deserialize a request,
+// call into ProofServer and map ServerError::status onto their own status codes.
+pub type ServedTree<C> = MimkMerkleTree<C, MerkleProof<C>>;
+
+// Paths HTTP bindings serve the JSON endpoints on, all POST
+pub const PROOF_PATH: &str = "/proof";
+pub const MULTIPROOF_PATH: &str = "/multiproof";
+
+// Define the ServedEpoch struct, one built tree with its signed root and account index
+#[derive(Debug)]
+pub struct ServedEpoch<C: SumCommitment> {
+    tree: ServedTree<C>,
+    signed_root: SignedRoot<C>,
+    // User id -> input index the account's leaf was built from
+    accounts: HashMap<String, usize>,
+}
+
+impl<C: SumCommitment> ServedEpoch<C> {
+    pub fn new(tree: ServedTree<C>, signed_root: SignedRoot<C>) -> Result<Self, ServerError> {
+        if !signed_root.root.matches(tree.commit().node()) {
+            return Err(ServerError::RootMismatch(signed_root.epoch));
+        }
+        Ok(ServedEpoch {
+            tree,
+            signed_root,
+            accounts: HashMap::new(),
+        })
+    }
+
+    pub fn with_account(mut self, user_id: impl Into<String>, input_index: usize) -> Self {
+        self.accounts.insert(user_id.into(), input_index);
+        self
+    }
+
+    pub fn epoch(&self) -> u64 {
+        self.signed_root.epoch
+    }
+
+    pub fn signed_root(&self) -> &SignedRoot<C> {
+        &self.signed_root
+    }
+
+    pub fn tree(&self) -> &ServedTree<C> {
+        &self.tree
+    }
+}
+
+// Define the ProofRequest struct, what a user sends to fetch their proof
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ProofRequest {
+    // Latest epoch when absent
+    pub epoch: Option<u64>,
+    pub user_id: String,
+    // Hex retrieval token issued to this user for this epoch
+    pub token: String,
+}
+
+// Define the ProofResponse struct, a proof bundle in wire form
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ProofResponse {
+    pub epoch: u64,
+    // Root as `<amount>:<hex digest>`
+    pub root: String,
+    // Hex Ed25519 signature over the signed-root payload
+    pub signature: String,
+    // Hex of `MerkleProof::to_bytes`
+    pub proof: String,
+}
+
+impl ProofResponse {
+    pub fn from_bundle<C: SumCommitment>(bundle: &ProofBundle<C, MerkleProof<C>>) -> Self {
+        ProofResponse {
+            epoch: bundle.signed_root.epoch,
+            root: bundle.signed_root.root.to_string(),
+            signature: hex::encode(bundle.signed_root.signature.to_bytes()),
+            proof: hex::encode(bundle.proof.to_bytes()),
+        }
+    }
+
+    pub fn to_bundle<C: SumCommitment>(
+        &self,
+    ) -> Result<ProofBundle<C, MerkleProof<C>>, ParseError> {
+        let root: Root<C> = self.root.parse()?;
+        let signature = hex::decode(&self.signature).map_err(|_| ParseError::InvalidHex)?;
+        let signature =
+            Signature::from_slice(&signature).map_err(|_| ParseError::InvalidLength {
+                expected: 64,
+                found: signature.len(),
+            })?;
+        let proof = hex::decode(&self.proof).map_err(|_| ParseError::InvalidHex)?;
+        let proof = MerkleProof::try_from(proof.as_slice())?;
+        Ok(ProofBundle::new(
+            proof,
+            SignedRoot {
+                epoch: self.epoch,
+                root,
+                signature,
+            },
+        ))
+    }
+}
+
+// Define the ProofServer struct, the epochs being served and the token secret
+#[derive(Debug)]
+pub struct ProofServer<C: SumCommitment> {
+    tokens: TokenKey,
+    epochs: Vec<ServedEpoch<C>>,
+    // Every published root, hash-chained for client consistency checks
+    log: RootLog<C>,
+    guard: RequestGuard,
+    // SHA-256 fingerprints of the client certificates allowed to page through multiproofs
+    auditors: Vec<[u8; 32]>,
+    webhooks: WebhookDispatcher,
+    // Epoch build the pipeline reports as running, shown by `/status`
+    build: Option<BuildProgress>,
+}
+
+impl<C: SumCommitment> ProofServer<C> {
+    pub fn new(tokens: TokenKey) -> Self {
+        ProofServer {
+            tokens,
+            epochs: Vec::new(),
+            log: RootLog::new(),
+            guard: RequestGuard::new(Limits::default()),
+            auditors: Vec::new(),
+            webhooks: WebhookDispatcher::new(),
+            build: None,
+        }
+    }
+
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.guard = RequestGuard::new(limits);
+        self
+    }
+
+    // Without auditors, multiproof pages are refused to every caller
+    pub fn with_auditors(mut self, fingerprints: Vec<[u8; 32]>) -> Self {
+        self.auditors = fingerprints;
+        self
+    }
+
+    pub fn limits(&self) -> &Limits {
+        &self.guard.limits
+    }
+
+    pub fn webhooks(&self) -> &WebhookDispatcher {
+        &self.webhooks
+    }
+
+    pub fn webhooks_mut(&mut self) -> &mut WebhookDispatcher {
+        &mut self.webhooks
+    }
+
+    // Publishes the epoch and announces it to subscribers of `RootPublished`, then to
+    // those of `ConsistencyProofAvailable` with the log heads before and after
+    pub fn publish_and_notify(
+        &mut self,
+        epoch: ServedEpoch<C>,
+        transport: &dyn WebhookTransport,
+    ) -> Result<Vec<Delivery>, ServerError> {
+        let previous = self.log.entries().last().map(|entry| (entry.epoch, entry.hash()));
+        let (number, root) = (epoch.epoch(), epoch.signed_root.root.clone());
+        self.publish(epoch)?;
+
+        let published = WebhookEvent::root_published(number, &root);
+
+        // The new entry extends the log, so a consistency proof from the previous epoch
+        // can now be fetched
+        let consistency = WebhookEvent::consistency_available(
+            number,
+            &root,
+            &self.log.head(),
+            previous.as_ref().map(|(epoch, head)| (*epoch, head)),
+        );
+        let mut deliveries = self.notify(&published, transport);
+        deliveries.extend(self.notify(&consistency, transport));
+        Ok(deliveries)
+    }
+
+    pub fn notify(&self, event: &WebhookEvent, transport: &dyn WebhookTransport) -> Vec<Delivery> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        self.webhooks.dispatch(event, transport, timestamp)
+    }
+
+    pub fn publish(&mut self, epoch: ServedEpoch<C>) -> Result<(), ServerError> {
+        if let Some(current) = self.current() {
+            if epoch.epoch() <= current.epoch() {
+                return Err(ServerError::StaleEpoch {
+                    current: current.epoch(),
+                    found: epoch.epoch(),
+                });
+            }
+        }
+        self.log
+            .append(epoch.epoch(), epoch.signed_root.root.clone())
+            .expect("epoch order was checked above");
+        self.epochs.push(epoch);
+        Ok(())
+    }
+
+    pub fn begin_build(&mut self, progress: BuildProgress) {
+        self.build = Some(progress);
+    }
+
+    pub fn build_progress_mut(&mut self) -> Option<&mut BuildProgress> {
+        self.build.as_mut()
+    }
+
+    // Called when the build is published or abandoned
+    pub fn finish_build(&mut self) -> Option<BuildProgress> {
+        self.build.take()
+    }
+
+    pub fn healthz(&self) -> HealthResponse {
+        HealthResponse {
+            status: "ok".to_string(),
+        }
+    }
+
+    pub fn readyz(&self) -> ReadinessResponse {
+        match self.current() {
+            Some(_) => ReadinessResponse {
+                ready: true,
+                reason: None,
+            },
+            None => ReadinessResponse {
+                ready: false,
+                reason: Some(ServerError::NoEpoch.to_string()),
+            },
+        }
+    }
+
+    pub fn status(&self) -> StatusResponse {
+        let current = self.current();
+        StatusResponse {
+            epoch: current.map(ServedEpoch::epoch),
+            root: current.map(|served| served.signed_root.root.to_string()),
+            leaf_count: current.map(|served| served.tree.len()),
+            served_epochs: self.epochs.iter().map(ServedEpoch::epoch).collect(),
+            log_len: self.log.len(),
+            build: self.build.clone(),
+        }
+    }
+
+    // Answers a GET on one of the probe paths with its status code and JSON body, or
+    // None for any other path. Probes skip the rate limits so they never fail under load.
+    pub fn probe(&self, path: &str) -> Option<(u16, String)> {
+        let (status, body) = match path {
+            HEALTHZ_PATH => (200, serde_json::to_string(&self.healthz())),
+            READYZ_PATH => {
+                let readiness = self.readyz();
+                (readiness.status(), serde_json::to_string(&readiness))
+            }
+            STATUS_PATH => (200, serde_json::to_string(&self.status())),
+            _ => return None,
+        };
+        Some((status, body.expect("probe responses always serialize")))
+    }
+
+    // Drops all but the `keep` newest epochs (at least one is kept) and returns the
+    // dropped epoch numbers. Their roots stay in the log.
+    pub fn prune(&mut self, keep: usize) -> Vec<u64> {
+        let excess = self.epochs.len().saturating_sub(keep.max(1));
+        self.epochs
+            .drain(..excess)
+            .map(|served| served.epoch())
+            .collect()
+    }
+
+    // Every retrieval token issued under the old key stops validating
+    pub fn rotate_token_key(&mut self, tokens: TokenKey) {
+        self.tokens = tokens;
+    }
+
+    pub fn latest_root(&self) -> Option<&SignedRoot<C>> {
+        self.current().map(ServedEpoch::signed_root)
+    }
+
+    // Log entries after `epoch`, or the whole log; a client checks them against its last entry
+    pub fn log_since(&self, epoch: Option<u64>) -> &[LogEntry<C>] {
+        let entries = self.log.entries();
+        match epoch {
+            Some(epoch) => {
+                let start = entries.partition_point(|entry| entry.epoch <= epoch);
+                &entries[start..]
+            }
+            None => entries,
+        }
+    }
+
+    // Links the root a client saw at `old_epoch` to the one at `new_epoch`
+    pub fn consistency_proof(&self, old_epoch: u64, new_epoch: u64) -> Option<ConsistencyProof<C>> {
+        self.log.consistency_proof(old_epoch, new_epoch)
+    }
+
+    pub fn current(&self) -> Option<&ServedEpoch<C>> {
+        self.epochs.last()
+    }
+
+    pub fn epoch(&self, epoch: u64) -> Option<&ServedEpoch<C>> {
+        self.epochs.iter().find(|served| served.epoch() == epoch)
+    }
+
+    // Tokens are handed to users out of band, e.g. on the exchange's account page
+    pub fn issue_token(&self, epoch: u64, user_id: &str) -> RetrievalToken {
+        self.tokens.derive(epoch, user_id)
+    }
+
+    // Entry point for transports: enforces the size and per-IP limits before any parsing
+    // or proof work, the per-user limit once the token is known to be genuine, then
+    // answers the JSON-encoded ProofRequest in `body`
+    pub fn handle_proof(&self, client: IpAddr, body: &[u8]) -> Result<ProofResponse, ServerError> {
+        let now = Instant::now();
+        self.admit(client, body, now)?;
+        let request: ProofRequest = parse_body(body)?;
+        let served = self.authorize(&request)?;
+        self.guard
+            .check_user(served.epoch(), &request.user_id, now)
+            .map_err(|retry_after| ServerError::RateLimited { retry_after })?;
+        Ok(ProofResponse::from_bundle(&Self::bundle_for(served, &request)?))
+    }
+
+    // Pages reveal every leaf of an epoch, so they are only served to a listed auditor:
+    // `auditor` is the DER client certificate the transport verified, e.g.
+    // `TlsConnection::auditor`. Each auditor gets the per-token rate limit.
+    pub fn handle_multiproof_page(
+        &self,
+        client: IpAddr,
+        auditor: Option<&[u8]>,
+        body: &[u8],
+    ) -> Result<MultiproofPage, ServerError> {
+        let now = Instant::now();
+        self.admit(client, body, now)?;
+        let fingerprint: [u8; 32] =
+            Sha256::digest(auditor.ok_or(ServerError::ClientCertificateRequired)?).into();
+        if !self.auditors.contains(&fingerprint) {
+            return Err(ServerError::ClientCertificateRequired);
+        }
+        self.guard
+            .check_auditor(fingerprint, now)
+            .map_err(|retry_after| ServerError::RateLimited { retry_after })?;
+        self.multiproof_page(&parse_body(body)?)
+    }
+
+    pub fn proof(&self, request: &ProofRequest) -> Result<ProofResponse, ServerError> {
+        Ok(ProofResponse::from_bundle(&self.bundle(request)?))
+    }
+
+    pub(crate) fn bundle(
+        &self,
+        request: &ProofRequest,
+    ) -> Result<ProofBundle<C, MerkleProof<C>>, ServerError> {
+        Self::bundle_for(self.authorize(request)?, request)
+    }
+
+    // The epoch the request is for, once its token validates for that epoch and user
+    fn authorize(&self, request: &ProofRequest) -> Result<&ServedEpoch<C>, ServerError> {
+        let served = self.resolve(request.epoch)?;
+
+        // Token first, so unauthenticated callers can't probe which user ids exist
+        let token: RetrievalToken = request
+            .token
+            .parse()
+            .map_err(|_| ServerError::InvalidToken)?;
+        if !self
+            .tokens
+            .validate(served.epoch(), &request.user_id, &token)
+        {
+            return Err(ServerError::InvalidToken);
+        }
+        Ok(served)
+    }
+
+    fn bundle_for(
+        served: &ServedEpoch<C>,
+        request: &ProofRequest,
+    ) -> Result<ProofBundle<C, MerkleProof<C>>, ServerError> {
+        let position = served
+            .accounts
+            .get(&request.user_id)
+            .and_then(|input_index| served.tree.position_of(*input_index))
+            .ok_or(ServerError::UnknownAccount)?;
+        Ok(ProofBundle::new(
+            served.tree.prove(position),
+            served.signed_root.clone(),
+        ))
+    }
+
+    // Pages through the multiproof records in DFS order; feeding every page's records
+    // to one StreamingVerifier of the tree's leaf count checks the whole stream
+    pub fn multiproof_page(
+        &self,
+        request: &MultiproofPageRequest,
+    ) -> Result<MultiproofPage, ServerError> {
+        let selection = selection_tag(request.positions.as_deref());
+        let (served, offset) = match &request.cursor {
+            Some(cursor) => {
+                let cursor = Cursor::decode(cursor)
+                    .filter(|cursor| cursor.selection == selection)
+                    .ok_or(ServerError::InvalidCursor)?;
+                let served = self
+                    .epoch(cursor.epoch)
+                    .ok_or(ServerError::UnknownEpoch(cursor.epoch))?;
+                (served, cursor.offset)
+            }
+            None => (self.resolve(request.epoch)?, 0),
+        };
+
+        let len = served.tree.len();
+        let positions = match &request.positions {
+            Some(positions) => {
+                if let Some(position) = positions.iter().find(|position| **position >= len) {
+                    return Err(ServerError::BadRequest(format!(
+                        "position {} is outside a tree of {} leaves",
+                        position, len
+                    )));
+                }
+                let mut positions = positions.clone();
+                positions.sort_unstable();
+                positions.dedup();
+                Some(positions)
+            }
+            None => None,
+        };
+        if offset > len {
+            return Err(ServerError::InvalidCursor);
+        }
+
+        // The cursor holds the first leaf the page covers, so each page is generated on
+        // its own without rebuilding the records before it
+        let (records, resume) = served.tree.multiproof_records(
+            positions.as_deref(),
+            offset,
+            request.limit.clamp(1, MAX_PAGE_SIZE),
+        );
+        let next_cursor = resume.map(|offset| {
+            Cursor {
+                epoch: served.epoch(),
+                offset,
+                selection,
+            }
+            .encode()
+        });
+        Ok(MultiproofPage {
+            epoch: served.epoch(),
+            root: served.signed_root.root.to_string(),
+            leaf_count: len,
+            records: records
+                .iter()
+                .map(|(index, node)| PageRecord::new(*index, node))
+                .collect(),
+            next_cursor,
+        })
+    }
+
+    fn resolve(&self, epoch: Option<u64>) -> Result<&ServedEpoch<C>, ServerError> {
+        match epoch {
+            Some(epoch) => self.epoch(epoch).ok_or(ServerError::UnknownEpoch(epoch)),
+            None => self.current().ok_or(ServerError::NoEpoch),
+        }
+    }
+
+    // Size and per-IP limits, checked before a request body is parsed
+    fn admit(&self, client: IpAddr, body: &[u8], now: Instant) -> Result<(), ServerError> {
+        if body.len() > self.guard.limits.max_request_bytes {
+            return Err(ServerError::RequestTooLarge {
+                limit: self.guard.limits.max_request_bytes,
+                found: body.len(),
+            });
+        }
+        self.guard
+            .check_ip(client, now)
+            .map_err(|retry_after| ServerError::RateLimited { retry_after })
+    }
+}
+
+fn parse_body<T: serde::de::DeserializeOwned>(body: &[u8]) -> Result<T, ServerError> {
+    serde_json::from_slice(body).map_err(|err| ServerError::BadRequest(err.to_string()))
+}
+
+// Define the ServerError enum for requests the server refuses
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ServerError {
+    NoEpoch,
+    UnknownEpoch(u64),
+    StaleEpoch { current: u64, found: u64 },
+    RootMismatch(u64),
+    InvalidToken,
+    UnknownAccount,
+    BadRequest(String),
+    InvalidCursor,
+    RequestTooLarge { limit: usize, found: usize },
+    RateLimited { retry_after: Duration },
+    Signing(String),
+    InvalidTenant(String),
+    UnknownTenant(String),
+    DuplicateTenant(String),
+    // An auditor-only endpoint was called without a listed client certificate
+    ClientCertificateRequired,
+    // A tenant's server lock was poisoned by a panicking holder
+    Poisoned,
+}
+
+impl ServerError {
+    // HTTP status a transport binding should answer with
+    pub fn status(&self) -> u16 {
+        match self {
+            ServerError::NoEpoch => 503,
+            ServerError::UnknownEpoch(_)
+            | ServerError::UnknownAccount
+            | ServerError::UnknownTenant(_) => 404,
+            ServerError::StaleEpoch { .. }
+            | ServerError::RootMismatch(_)
+            | ServerError::DuplicateTenant(_) => 409,
+            ServerError::InvalidToken => 401,
+            ServerError::ClientCertificateRequired => 403,
+            ServerError::BadRequest(_)
+            | ServerError::InvalidCursor
+            | ServerError::InvalidTenant(_) => 400,
+            ServerError::RequestTooLarge { .. } => 413,
+            ServerError::RateLimited { .. } => 429,
+            ServerError::Signing(_) | ServerError::Poisoned => 500,
+        }
+    }
+}
+
+impl fmt::Display for ServerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ServerError::NoEpoch => write!(f, "no epoch has been published yet"),
+            ServerError::UnknownEpoch(epoch) => write!(f, "epoch {} is not served", epoch),
+            ServerError::StaleEpoch { current, found } => write!(
+                f,
+                "epoch {} is not newer than the served epoch {}",
+                found, current
+            ),
+            ServerError::RootMismatch(epoch) => {
+                write!(f, "signed root for epoch {} does not match its tree", epoch)
+            }
+            ServerError::InvalidToken => write!(f, "retrieval token is missing or invalid"),
+            ServerError::UnknownAccount => write!(f, "no account with that id in this epoch"),
+            ServerError::BadRequest(reason) => write!(f, "malformed request: {}", reason),
+            ServerError::InvalidCursor => {
+                write!(f, "continuation cursor does not match this stream")
+            }
+            ServerError::RequestTooLarge { limit, found } => write!(
+                f,
+                "request of {} bytes exceeds the {} byte limit",
+                found, limit
+            ),
+            ServerError::RateLimited { retry_after } => write!(
+                f,
+                "rate limit exceeded, retry in {:.1}s",
+                retry_after.as_secs_f64()
+            ),
+            ServerError::Signing(reason) => write!(f, "signing failed: {}", reason),
+            ServerError::InvalidTenant(name) => write!(f, "invalid tenant name `{}`", name),
+            ServerError::UnknownTenant(name) => write!(f, "no tenant named `{}`", name),
+            ServerError::DuplicateTenant(name) => write!(f, "tenant `{}` already exists", name),
+            ServerError::ClientCertificateRequired => {
+                write!(f, "this endpoint needs an auditor client certificate")
+            }
+            ServerError::Poisoned => write!(f, "tenant server lock is poisoned"),
+        }
+    }
+}
+
+impl std::error::Error for ServerError {}
+
+#[cfg(test)]
+mod tests {
+    use ed25519_dalek::SigningKey;
+
+    use super::*;
+    use crate::{MerkleSumTreeBuilder, MimiSumCommitment, StreamingVerifier, UserLeaf};
+
+    const CLIENT: IpAddr = IpAddr::V4(std::net::Ipv4Addr::LOCALHOST);
+    const AUDITOR: &[u8] = b"auditor certificate";
+
+    pub(super) fn served(epoch: u64) -> ServedEpoch<MimiSumCommitment> {
+        let leaves: Vec<UserLeaf<u64>> = ["alice", "bob", "carol"]
+            .iter()
+            .zip([100, 200, 300])
+            .map(|(user_id, record)| UserLeaf {
+                user_id: user_id.to_string(),
+                record,
+            })
+            .collect();
+        let tree: ServedTree<MimiSumCommitment> =
+            MerkleSumTreeBuilder::new().build(&leaves).unwrap();
+        let signed_root = SignedRoot::sign(epoch, tree.commit(), &SigningKey::from_bytes(&[9; 32]));
+        ServedEpoch::new(tree, signed_root)
+            .unwrap()
+            .with_account("alice", 0)
+            .with_account("bob", 1)
+            .with_account("carol", 2)
+    }
+
+    pub(super) fn server() -> ProofServer<MimiSumCommitment> {
+        let mut server = ProofServer::new(TokenKey::new([1; 32]))
+            .with_limits(Limits::unlimited())
+            .with_auditors(vec![Sha256::digest(AUDITOR).into()]);
+        server.publish(served(1)).unwrap();
+        server
+    }
+
+    fn page_body(cursor: Option<String>) -> Vec<u8> {
+        let request = MultiproofPageRequest {
+            epoch: None,
+            positions: None,
+            cursor,
+            limit: 1,
+        };
+        serde_json::to_vec(&request).unwrap()
+    }
+
+    fn proof_body(epoch: Option<u64>, user_id: &str, token: &str) -> Vec<u8> {
+        let request = ProofRequest {
+            epoch,
+            user_id: user_id.to_string(),
+            token: token.to_string(),
+        };
+        serde_json::to_vec(&request).unwrap()
+    }
+
+    #[test]
+    fn proofs_need_the_users_token_for_that_epoch() {
+        let mut server = server();
+        let alice = server.issue_token(1, "alice").to_string();
+        let response = server
+            .handle_proof(CLIENT, &proof_body(None, "alice", &alice))
+            .unwrap();
+        assert_eq!(response.epoch, 1);
+
+        let bob = server.issue_token(1, "bob").to_string();
+        for body in [
+            proof_body(None, "alice", "not a token"),
+            proof_body(None, "alice", &bob),
+            proof_body(Some(1), "bob", &alice),
+        ] {
+            assert_eq!(
+                server.handle_proof(CLIENT, &body),
+                Err(ServerError::InvalidToken)
+            );
+        }
+        let dave = server.issue_token(1, "dave").to_string();
+        assert_eq!(
+            server.handle_proof(CLIENT, &proof_body(None, "dave", &dave)),
+            Err(ServerError::UnknownAccount)
+        );
+
+        // Once epoch 2 is served, last epoch's token only works when asked for by epoch
+        server.publish(served(2)).unwrap();
+        assert_eq!(
+            server.handle_proof(CLIENT, &proof_body(None, "alice", &alice)),
+            Err(ServerError::InvalidToken)
+        );
+        assert!(server
+            .handle_proof(CLIENT, &proof_body(Some(1), "alice", &alice))
+            .is_ok());
+    }
+
+    #[test]
+    fn oversized_and_rapid_requests_are_refused_before_parsing() {
+        let mut server = ProofServer::new(TokenKey::new([1; 32])).with_limits(Limits {
+            max_request_bytes: 128,
+            per_ip: Some(Rate {
+                burst: 1,
+                per_second: 0.0,
+            }),
+            per_token: None,
+        });
+        server.publish(served(1)).unwrap();
+        assert_eq!(
+            server.handle_proof(CLIENT, &[b' '; 129]),
+            Err(ServerError::RequestTooLarge {
+                limit: 128,
+                found: 129
+            })
+        );
+        assert!(matches!(
+            server.handle_proof(CLIENT, b"{"),
+            Err(ServerError::BadRequest(_))
+        ));
+        assert_eq!(
+            server.handle_proof(CLIENT, b"{"),
+            Err(ServerError::RateLimited {
+                retry_after: Duration::MAX
+            })
+        );
+    }
+
+    #[test]
+    fn pages_stream_every_leaf_and_refuse_tampered_cursors() {
+        let server = server();
+        let page = |body: &[u8]| server.handle_multiproof_page(CLIENT, Some(AUDITOR), body);
+        let root = server.latest_root().unwrap().root.clone();
+        let mut verifier = StreamingVerifier::new(3);
+        let mut cursors = Vec::new();
+        let mut next = page(&page_body(None)).unwrap();
+        loop {
+            for record in &next.records {
+                let (index, node) = record.decode().unwrap();
+                assert!(verifier.push(index, node));
+            }
+            let Some(cursor) = next.next_cursor.clone() else {
+                break;
+            };
+            cursors.push(cursor.clone());
+            next = page(&page_body(Some(cursor))).unwrap();
+        }
+        assert_eq!(verifier.finish(&root), Some(vec![0, 1, 2]));
+
+        let mut cursor = Cursor::decode(&cursors[0]).unwrap();
+        let other_selection = MultiproofPageRequest {
+            epoch: None,
+            positions: Some(vec![0]),
+            cursor: Some(cursors[0].clone()),
+            limit: 1,
+        };
+        assert_eq!(
+            page(&serde_json::to_vec(&other_selection).unwrap()),
+            Err(ServerError::InvalidCursor)
+        );
+        assert_eq!(
+            page(&page_body(Some("not a cursor".to_string()))),
+            Err(ServerError::InvalidCursor)
+        );
+        cursor.offset = 4;
+        assert_eq!(
+            page(&page_body(Some(cursor.encode()))),
+            Err(ServerError::InvalidCursor)
+        );
+        cursor.offset = 1;
+        cursor.epoch = 9;
+        assert_eq!(
+            page(&page_body(Some(cursor.encode()))),
+            Err(ServerError::UnknownEpoch(9))
+        );
+    }
+
+    #[test]
+    fn multiproof_pages_need_a_listed_auditor() {
+        let server = server();
+        let body = page_body(None);
+        assert_eq!(
+            server.handle_multiproof_page(CLIENT, None, &body),
+            Err(ServerError::ClientCertificateRequired)
+        );
+        assert_eq!(
+            server.handle_multiproof_page(CLIENT, Some(b"someone else"), &body),
+            Err(ServerError::ClientCertificateRequired)
+        );
+        let page = server
+            .handle_multiproof_page(CLIENT, Some(AUDITOR), &body)
+            .unwrap();
+        assert_eq!(page.epoch, 1);
+        assert!(!page.records.is_empty());
+
+        let closed = ProofServer::<MimiSumCommitment>::new(TokenKey::new([1; 32]));
+        assert_eq!(
+            closed.handle_multiproof_page(CLIENT, Some(AUDITOR), &body),
+            Err(ServerError::ClientCertificateRequired)
+        );
+    }
+}
@@ -0,0 +1,340 @@
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use sha2::digest::CtOutput;
+use sha2::{Digest as _, Sha256};
+
+use super::{BuildProgress, EpochReloader, ProofServer, ReloadError, TokenKey};
+use crate::keys::{KeyError, KeyRing};
+use crate::scheduler::{EpochScheduler, SchedulerError};
+use crate::signer::{Signer, SignerError};
+use crate::{EntropySource, Leaf, OsEntropy, SumCommitment};
+
+// Admin requests are POSTed here. Bindings should serve this path on its own listener
+// (an internal port or a unix socket), never next to the public proof endpoints.
+pub const ADMIN_PATH: &str = "/admin";
+
+// Define the AdminAuth enum, who may call the admin API. mTLS is terminated by the
+// transport, which passes the verified peer certificate on; it is accepted when its
+// SHA-256 fingerprint is listed.
+#[derive(Clone)]
+pub enum AdminAuth {
+    // SHA-256 of the bearer token, so the token itself isn't kept in memory
+    Token([u8; 32]),
+    ClientCertificates(Vec<[u8; 32]>),
+}
+
+impl AdminAuth {
+    pub fn token(token: &str) -> Self {
+        AdminAuth::Token(Sha256::digest(token.as_bytes()).into())
+    }
+
+    pub fn client_certificates(fingerprints: Vec<[u8; 32]>) -> Self {
+        AdminAuth::ClientCertificates(fingerprints)
+    }
+
+    // Token hashes are compared in constant time, like retrieval token MACs, so response
+    // timing doesn't leak how much of a guess matched
+    fn admits(&self, credentials: &AdminCredentials<'_>) -> bool {
+        match self {
+            AdminAuth::Token(expected) => credentials.bearer.is_some_and(|token| {
+                CtOutput::<Sha256>::new(Sha256::digest(token.as_bytes()))
+                    == CtOutput::new((*expected).into())
+            }),
+            AdminAuth::ClientCertificates(fingerprints) => {
+                credentials.client_certificate.is_some_and(|der| {
+                    let fingerprint: [u8; 32] = Sha256::digest(der).into();
+                    fingerprints.contains(&fingerprint)
+                })
+            }
+        }
+    }
+}
+
+impl fmt::Debug for AdminAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AdminAuth::Token(_) => f.write_str("AdminAuth::Token(..)"),
+            AdminAuth::ClientCertificates(fingerprints) => {
+                write!(f, "AdminAuth::ClientCertificates({})", fingerprints.len())
+            }
+        }
+    }
+}
+
+// Define the AdminCredentials struct, what the transport saw of the caller
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AdminCredentials<'a> {
+    // `Authorization: Bearer` value
+    pub bearer: Option<&'a str>,
+    // DER of the client certificate the TLS layer verified
+    pub client_certificate: Option<&'a [u8]>,
+}
+
+// Define the AdminCommand enum, the JSON body of an admin request
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum AdminCommand {
+    // Cuts the next epoch now instead of waiting for the schedule
+    TriggerBuild,
+    // Verifies an epoch another process built and starts serving it
+    SealEpoch { epoch: u64 },
+    // Moves root signing to the next key from the rotation backend, from the next epoch
+    // on; the old key stays valid for `overlap` more epochs
+    RotateKey { overlap: u64 },
+    // Invalidates every retrieval token issued so far
+    RotateTokenKey,
+    // Keeps only the `keep` newest epochs in memory
+    Prune { keep: usize },
+}
+
+// Define the AdminResponse enum, what a successful command did
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum AdminResponse {
+    Built { epoch: u64 },
+    Sealed { epoch: u64 },
+    // Hex of the new verifying key
+    KeyRotated { key: String, from_epoch: u64 },
+    TokenKeyRotated,
+    Pruned { removed: Vec<u64> },
+}
+
+// Define the KeyRotation trait, where the next root signing key comes from, e.g. a new
+// KMS key version or HSM label. Private keys never travel through the admin API.
+pub trait KeyRotation {
+    fn next_signer(&mut self) -> Result<Box<dyn Signer + Send>, SignerError>;
+}
+
+impl<F: FnMut() -> Result<Box<dyn Signer + Send>, SignerError>> KeyRotation for F {
+    fn next_signer(&mut self) -> Result<Box<dyn Signer + Send>, SignerError> {
+        self()
+    }
+}
+
+// Define the AdminApi struct, the authenticated epoch management surface over a proof
+// server. Each command needs the component it drives; missing ones answer 501.
+pub struct AdminApi<C: SumCommitment, L: Leaf> {
+    auth: AdminAuth,
+    server: Arc<Mutex<ProofServer<C>>>,
+    keys: Mutex<KeyRing>,
+    scheduler: Option<Mutex<EpochScheduler<C, L>>>,
+    reloader: Option<Mutex<EpochReloader>>,
+    rotation: Option<Mutex<Box<dyn KeyRotation + Send>>>,
+}
+
+impl<C: SumCommitment, L: Leaf> AdminApi<C, L> {
+    pub fn new(auth: AdminAuth, server: Arc<Mutex<ProofServer<C>>>, keys: KeyRing) -> Self {
+        AdminApi {
+            auth,
+            server,
+            keys: Mutex::new(keys),
+            scheduler: None,
+            reloader: None,
+            rotation: None,
+        }
+    }
+
+    pub fn with_scheduler(mut self, scheduler: EpochScheduler<C, L>) -> Self {
+        self.scheduler = Some(Mutex::new(scheduler));
+        self
+    }
+
+    pub fn with_reloader(mut self, reloader: EpochReloader) -> Self {
+        self.reloader = Some(Mutex::new(reloader));
+        self
+    }
+
+    pub fn with_rotation(mut self, rotation: impl KeyRotation + Send + 'static) -> Self {
+        self.rotation = Some(Mutex::new(Box::new(rotation)));
+        self
+    }
+
+    pub fn keys(&self) -> Result<KeyRing, AdminError> {
+        Ok(lock(&self.keys)?.clone())
+    }
+
+    // Entry point for transports: authenticates before the body is even parsed
+    pub fn handle(
+        &self,
+        credentials: &AdminCredentials<'_>,
+        body: &[u8],
+    ) -> Result<AdminResponse, AdminError> {
+        if !self.auth.admits(credentials) {
+            return Err(AdminError::Unauthorized);
+        }
+        let command: AdminCommand = serde_json::from_slice(body)
+            .map_err(|err| AdminError::BadRequest(err.to_string()))?;
+        self.execute(command)
+    }
+
+    fn execute(&self, command: AdminCommand) -> Result<AdminResponse, AdminError> {
+        match command {
+            AdminCommand::TriggerBuild => self.trigger_build(),
+            AdminCommand::SealEpoch { epoch } => {
+                let reloader = self
+                    .reloader
+                    .as_ref()
+                    .ok_or(AdminError::NotConfigured("reloader"))?;
+                lock(reloader)?.reload(epoch, &self.server)?;
+                Ok(AdminResponse::Sealed { epoch })
+            }
+            AdminCommand::RotateKey { overlap } => self.rotate_key(overlap),
+            AdminCommand::RotateTokenKey => {
+                lock(&self.server)?.rotate_token_key(TokenKey::new(OsEntropy.seed()));
+                Ok(AdminResponse::TokenKeyRotated)
+            }
+            AdminCommand::Prune { keep } => Ok(AdminResponse::Pruned {
+                removed: lock(&self.server)?.prune(keep),
+            }),
+        }
+    }
+
+    // The build shows up in `/status` while it runs
+    fn trigger_build(&self) -> Result<AdminResponse, AdminError> {
+        let scheduler = self
+            .scheduler
+            .as_ref()
+            .ok_or(AdminError::NotConfigured("scheduler"))?;
+        let mut scheduler = lock(scheduler)?;
+        {
+            let mut server = lock(&self.server)?;
+            let epoch = scheduler.next_epoch(&server);
+            server.begin_build(BuildProgress::new(epoch, unix_now()));
+        }
+        let built = scheduler.run_once(&self.server);
+        lock(&self.server)?.finish_build();
+        Ok(AdminResponse::Built { epoch: built? })
+    }
+
+    fn rotate_key(&self, overlap: u64) -> Result<AdminResponse, AdminError> {
+        let scheduler = self
+            .scheduler
+            .as_ref()
+            .ok_or(AdminError::NotConfigured("scheduler"))?;
+        let rotation = self
+            .rotation
+            .as_ref()
+            .ok_or(AdminError::NotConfigured("key rotation"))?;
+        let signer = lock(rotation)?.next_signer()?;
+        let key = signer.verifying_key()?;
+
+        let mut scheduler = lock(scheduler)?;
+        let from_epoch = scheduler.next_epoch(&*lock(&self.server)?);
+        let mut keys = lock(&self.keys)?;
+        keys.rotate(key, from_epoch, overlap)?;
+        scheduler.set_signer(signer);
+        if let Some(reloader) = &self.reloader {
+            lock(reloader)?.set_keys(keys.clone());
+        }
+        Ok(AdminResponse::KeyRotated {
+            key: hex::encode(key.as_bytes()),
+            from_epoch,
+        })
+    }
+}
+
+fn lock<T: ?Sized>(mutex: &Mutex<T>) -> Result<std::sync::MutexGuard<'_, T>, AdminError> {
+    mutex.lock().map_err(|_| AdminError::Poisoned)
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+// Define the AdminError enum for admin requests that are refused or fail
+#[derive(Debug)]
+pub enum AdminError {
+    Unauthorized,
+    BadRequest(String),
+    // The command needs a component this AdminApi wasn't given
+    NotConfigured(&'static str),
+    Scheduler(SchedulerError),
+    Reload(ReloadError),
+    Key(KeyError),
+    Signer(SignerError),
+    Poisoned,
+}
+
+impl AdminError {
+    // HTTP status a transport binding should answer with
+    pub fn status(&self) -> u16 {
+        match self {
+            AdminError::Unauthorized => 401,
+            AdminError::BadRequest(_) => 400,
+            AdminError::NotConfigured(_) => 501,
+            AdminError::Reload(ReloadError::Server(err))
+            | AdminError::Scheduler(SchedulerError::Server(err)) => err.status(),
+            AdminError::Key(_) => 409,
+            AdminError::Scheduler(_)
+            | AdminError::Reload(_)
+            | AdminError::Signer(_)
+            | AdminError::Poisoned => 500,
+        }
+    }
+}
+
+impl fmt::Display for AdminError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AdminError::Unauthorized => write!(f, "admin credentials are missing or invalid"),
+            AdminError::BadRequest(reason) => write!(f, "malformed admin request: {}", reason),
+            AdminError::NotConfigured(component) => {
+                write!(f, "no {} is configured for this server", component)
+            }
+            AdminError::Scheduler(err) => write!(f, "{}", err),
+            AdminError::Reload(err) => write!(f, "{}", err),
+            AdminError::Key(err) => write!(f, "{}", err),
+            AdminError::Signer(err) => write!(f, "{}", err),
+            AdminError::Poisoned => write!(f, "proof server lock is poisoned"),
+        }
+    }
+}
+
+impl std::error::Error for AdminError {}
+
+impl From<SchedulerError> for AdminError {
+    fn from(err: SchedulerError) -> Self {
+        AdminError::Scheduler(err)
+    }
+}
+
+impl From<ReloadError> for AdminError {
+    fn from(err: ReloadError) -> Self {
+        AdminError::Reload(err)
+    }
+}
+
+impl From<KeyError> for AdminError {
+    fn from(err: KeyError) -> Self {
+        AdminError::Key(err)
+    }
+}
+
+impl From<SignerError> for AdminError {
+    fn from(err: SignerError) -> Self {
+        AdminError::Signer(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens_are_checked_against_their_hash() {
+        let auth = AdminAuth::token("operator secret");
+        let bearer = |token| AdminCredentials {
+            bearer: Some(token),
+            client_certificate: None,
+        };
+        assert!(auth.admits(&bearer("operator secret")));
+        assert!(!auth.admits(&bearer("operator secreT")));
+        assert!(!auth.admits(&bearer("")));
+        assert!(!auth.admits(&AdminCredentials::default()));
+    }
+}
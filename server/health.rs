@@ -0,0 +1,76 @@
+use serde::{Deserialize, Serialize};
+
+// Probe paths, as orchestrators expect them. Bindings route GETs on these to
+// ProofServer::probe and should leave them out of the rate limits.
+pub const HEALTHZ_PATH: &str = "/healthz";
+pub const READYZ_PATH: &str = "/readyz";
+pub const STATUS_PATH: &str = "/status";
+
+// Define the BuildProgress struct, the epoch build the operator's pipeline reports as
+// running, so `/status` can show it next to the epoch being served
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct BuildProgress {
+    pub epoch: u64,
+    // Unix seconds
+    pub started_at: u64,
+    // Free-form pipeline stage, e.g. "snapshot", "tree", "signing"
+    pub stage: String,
+    pub leaves_done: usize,
+    // Unknown until the snapshot has been read
+    pub leaves_total: Option<usize>,
+}
+
+impl BuildProgress {
+    pub fn new(epoch: u64, started_at: u64) -> Self {
+        BuildProgress {
+            epoch,
+            started_at,
+            stage: "snapshot".to_string(),
+            leaves_done: 0,
+            leaves_total: None,
+        }
+    }
+}
+
+// Define the HealthResponse struct, the `/healthz` liveness body. Answering at all is
+// the signal, so it is always "ok" with status 200.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct HealthResponse {
+    pub status: String,
+}
+
+// Define the ReadinessResponse struct, the `/readyz` body. A server is ready once it
+// serves an epoch; until then probes get 503 so no traffic is routed to it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ReadinessResponse {
+    pub ready: bool,
+    // Why the server isn't ready, absent when it is
+    pub reason: Option<String>,
+}
+
+impl ReadinessResponse {
+    pub fn status(&self) -> u16 {
+        if self.ready {
+            200
+        } else {
+            503
+        }
+    }
+}
+
+// Define the StatusResponse struct, the `/status` body for dashboards and operators
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct StatusResponse {
+    // Latest served epoch and its root as `<amount>:<hex digest>`, absent before the first
+    pub epoch: Option<u64>,
+    pub root: Option<String>,
+    pub leaf_count: Option<usize>,
+    // Every epoch still answerable, oldest first
+    pub served_epochs: Vec<u64>,
+    pub log_len: usize,
+    pub build: Option<BuildProgress>,
+}
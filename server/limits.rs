@@ -0,0 +1,263 @@
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// Buckets tracked per limiter; past this the least recently used key is forgotten
+const MAX_TRACKED_KEYS: usize = 100_000;
+
+// Define the Rate struct, a token bucket: `burst` requests at once, refilled at `per_second`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rate {
+    pub burst: u32,
+    pub per_second: f64,
+}
+
+impl Rate {
+    pub fn per_minute(requests: u32, burst: u32) -> Self {
+        Rate {
+            burst,
+            per_second: requests as f64 / 60.0,
+        }
+    }
+}
+
+// Define the Limits struct, the abuse protection settings of a public proof server
+#[derive(Debug, Clone, PartialEq)]
+pub struct Limits {
+    pub max_request_bytes: usize,
+    // None disables the limit
+    pub per_ip: Option<Rate>,
+    pub per_token: Option<Rate>,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_request_bytes: 16 * 1024,
+            per_ip: Some(Rate::per_minute(60, 20)),
+            per_token: Some(Rate::per_minute(10, 5)),
+        }
+    }
+}
+
+impl Limits {
+    pub fn unlimited() -> Self {
+        Limits {
+            max_request_bytes: usize::MAX,
+            per_ip: None,
+            per_token: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+// Define the RateLimiter struct, one token bucket per key behind a lock so a shared
+// server can be called from many request handlers at once. At most MAX_TRACKED_KEYS
+// buckets are kept: each use stamps its key with a sequence number, and a new key past
+// the cap evicts the oldest stamp, so every check stays O(log n) whatever keys arrive.
+#[derive(Debug)]
+pub struct RateLimiter<K: Eq + Hash> {
+    rate: Rate,
+    capacity: usize,
+    state: Mutex<LimiterState<K>>,
+}
+
+#[derive(Debug)]
+struct LimiterState<K> {
+    buckets: HashMap<K, (Bucket, u64)>,
+    // Sequence number of each key's last use -> key, oldest first
+    recency: BTreeMap<u64, K>,
+    next: u64,
+}
+
+impl<K: Eq + Hash + Clone> RateLimiter<K> {
+    pub fn new(rate: Rate) -> Self {
+        Self::with_capacity(rate, MAX_TRACKED_KEYS)
+    }
+
+    pub fn with_capacity(rate: Rate, capacity: usize) -> Self {
+        RateLimiter {
+            rate,
+            capacity: capacity.max(1),
+            state: Mutex::new(LimiterState {
+                buckets: HashMap::new(),
+                recency: BTreeMap::new(),
+                next: 0,
+            }),
+        }
+    }
+
+    pub fn tracked(&self) -> usize {
+        self.state
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .buckets
+            .len()
+    }
+
+    // Takes one token for `key`, or returns how long until one is available
+    pub fn check(&self, key: &K, now: Instant) -> Result<(), Duration> {
+        let mut state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+        let state = &mut *state;
+        let sequence = state.next;
+        state.next += 1;
+
+        match state.buckets.get_mut(key) {
+            Some((_, used)) => {
+                state.recency.remove(&*used);
+                *used = sequence;
+            }
+            None => {
+                if state.buckets.len() >= self.capacity {
+                    if let Some((_, oldest)) = state.recency.pop_first() {
+                        state.buckets.remove(&oldest);
+                    }
+                }
+                let fresh = Bucket {
+                    tokens: self.rate.burst as f64,
+                    updated: now,
+                };
+                state.buckets.insert(key.clone(), (fresh, sequence));
+            }
+        }
+        state.recency.insert(sequence, key.clone());
+        let (bucket, _) = state
+            .buckets
+            .get_mut(key)
+            .expect("the key was inserted above");
+
+        let tokens = refill(bucket, self.rate, now);
+        bucket.updated = now;
+        if tokens >= 1.0 {
+            bucket.tokens = tokens - 1.0;
+            return Ok(());
+        }
+        bucket.tokens = tokens;
+        if self.rate.per_second <= 0.0 {
+            return Err(Duration::MAX);
+        }
+        Err(Duration::from_secs_f64(
+            (1.0 - tokens) / self.rate.per_second,
+        ))
+    }
+}
+
+fn refill(bucket: &Bucket, rate: Rate, now: Instant) -> f64 {
+    let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+    (bucket.tokens + elapsed * rate.per_second).min(rate.burst as f64)
+}
+
+// Define the RequestGuard struct, the limiters built from a server's Limits
+#[derive(Debug)]
+pub(crate) struct RequestGuard {
+    pub(crate) limits: Limits,
+    per_ip: Option<RateLimiter<IpAddr>>,
+    // Keyed on the (epoch, user id) a token was validated for, so made-up tokens and
+    // other spellings of a real one don't each get a fresh bucket
+    per_token: Option<RateLimiter<(u64, String)>>,
+    // Keyed on the auditor's certificate fingerprint, at the per-token rate
+    per_auditor: Option<RateLimiter<[u8; 32]>>,
+}
+
+impl RequestGuard {
+    pub(crate) fn new(limits: Limits) -> Self {
+        RequestGuard {
+            per_ip: limits.per_ip.map(RateLimiter::new),
+            per_token: limits.per_token.map(RateLimiter::new),
+            per_auditor: limits.per_token.map(RateLimiter::new),
+            limits,
+        }
+    }
+
+    pub(crate) fn check_ip(&self, ip: IpAddr, now: Instant) -> Result<(), Duration> {
+        match &self.per_ip {
+            Some(limiter) => limiter.check(&ip, now),
+            None => Ok(()),
+        }
+    }
+
+    // Only call once the request's token has been validated
+    pub(crate) fn check_user(
+        &self,
+        epoch: u64,
+        user_id: &str,
+        now: Instant,
+    ) -> Result<(), Duration> {
+        match &self.per_token {
+            Some(limiter) => limiter.check(&(epoch, user_id.to_string()), now),
+            None => Ok(()),
+        }
+    }
+
+    pub(crate) fn check_auditor(
+        &self,
+        fingerprint: [u8; 32],
+        now: Instant,
+    ) -> Result<(), Duration> {
+        match &self.per_auditor {
+            Some(limiter) => limiter.check(&fingerprint, now),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exhausted_buckets_refill_at_the_rate() {
+        let limiter = RateLimiter::new(Rate {
+            burst: 2,
+            per_second: 1.0,
+        });
+        let start = Instant::now();
+        assert_eq!(limiter.check(&"alice", start), Ok(()));
+        assert_eq!(limiter.check(&"alice", start), Ok(()));
+        assert_eq!(limiter.check(&"alice", start), Err(Duration::from_secs(1)));
+        assert_eq!(limiter.check(&"bob", start), Ok(()));
+
+        let later = start + Duration::from_millis(500);
+        assert_eq!(
+            limiter.check(&"alice", later),
+            Err(Duration::from_millis(500))
+        );
+        assert_eq!(
+            limiter.check(&"alice", later + Duration::from_millis(500)),
+            Ok(())
+        );
+
+        let never = RateLimiter::new(Rate {
+            burst: 0,
+            per_second: 0.0,
+        });
+        assert_eq!(never.check(&"alice", start), Err(Duration::MAX));
+    }
+
+    #[test]
+    fn the_least_recently_used_key_is_forgotten() {
+        let once = Rate {
+            burst: 1,
+            per_second: 0.0,
+        };
+        let limiter = RateLimiter::with_capacity(once, 2);
+        let now = Instant::now();
+        assert_eq!(limiter.check(&"a", now), Ok(()));
+        assert_eq!(limiter.check(&"b", now), Ok(()));
+        assert_eq!(limiter.check(&"a", now), Err(Duration::MAX));
+
+        // "b" is the oldest now, so "c" takes its place and "a" stays exhausted
+        assert_eq!(limiter.check(&"c", now), Ok(()));
+        assert_eq!(limiter.tracked(), 2);
+        assert_eq!(limiter.check(&"a", now), Err(Duration::MAX));
+        assert_eq!(limiter.check(&"b", now), Ok(()));
+        assert_eq!(limiter.tracked(), 2);
+    }
+}
@@ -0,0 +1,171 @@
+use utoipa::openapi::path::{OperationBuilder, ParameterBuilder, ParameterIn};
+use utoipa::openapi::request_body::RequestBodyBuilder;
+use utoipa::openapi::{
+    ComponentsBuilder, ContentBuilder, InfoBuilder, ObjectBuilder, OpenApi, OpenApiBuilder,
+    PathItem, PathItemType, PathsBuilder, Ref, Required, ResponseBuilder, SchemaType,
+};
+
+use super::{
+    BuildProgress, HealthResponse, MultiproofPage, MultiproofPageRequest, PageRecord,
+    ProofRequest, ProofResponse, ReadinessResponse, StatusResponse, HEALTHZ_PATH,
+    MULTIPROOF_PATH, PROOF_PATH, READYZ_PATH, STATUS_PATH, TENANT_PREFIX,
+};
+
+const JSON: &str = "application/json";
+
+// Define the OpenApiOptions struct, what the generated document describes
+#[derive(Debug, Clone, Default)]
+pub struct OpenApiOptions {
+    // Public base URL, listed as the only server when set
+    pub server_url: Option<String>,
+    // Documents the endpoints under `/tenants/{tenant}`, as a TenantServer serves them
+    pub tenants: bool,
+}
+
+// OpenAPI 3 document of the public endpoints, for client generators. The admin API is
+// left out on purpose, since it is served on a separate listener. Errors are plain text
+// with the status of ServerError::status.
+pub fn openapi_spec(options: &OpenApiOptions) -> OpenApi {
+    let prefix = if options.tenants {
+        format!("{}{{tenant}}", TENANT_PREFIX)
+    } else {
+        String::new()
+    };
+    let route = |path: &str| format!("{}{}", prefix, path);
+    let operation = |id: &str, summary: &str, tag: &str| {
+        let operation = OperationBuilder::new()
+            .operation_id(Some(id))
+            .summary(Some(summary))
+            .tag(tag);
+        if options.tenants {
+            operation.parameter(
+                ParameterBuilder::new()
+                    .name("tenant")
+                    .parameter_in(ParameterIn::Path)
+                    .required(Required::True)
+                    .schema(Some(
+                        ObjectBuilder::new()
+                            .schema_type(SchemaType::String)
+                            .pattern(Some("^[a-z0-9][a-z0-9-]{0,63}$")),
+                    )),
+            )
+        } else {
+            operation
+        }
+    };
+
+    let paths = PathsBuilder::new()
+        .path(
+            route(PROOF_PATH),
+            PathItem::new(
+                PathItemType::Post,
+                operation("getProof", "Fetch one user's signed inclusion proof", "proofs")
+                    .request_body(Some(json_body("ProofRequest")))
+                    .response("200", json_response("Proof bundle in wire form", "ProofResponse"))
+                    .response("401", text_response("Retrieval token is missing or invalid"))
+                    .response("404", text_response("Unknown epoch or account"))
+                    .response("429", text_response("Rate limit exceeded"))
+                    .response("503", text_response("No epoch has been published yet"))
+                    .build(),
+            ),
+        )
+        .path(
+            route(MULTIPROOF_PATH),
+            PathItem::new(
+                PathItemType::Post,
+                operation(
+                    "getMultiproofPage",
+                    "Page through multiproof or full-tree audit data",
+                    "proofs",
+                )
+                .request_body(Some(json_body("MultiproofPageRequest")))
+                .response("200", json_response("One page of records", "MultiproofPage"))
+                .response("400", text_response("Malformed request or cursor"))
+                .response("403", text_response("Auditor client certificate required"))
+                .response("404", text_response("Unknown epoch"))
+                .response("429", text_response("Rate limit exceeded"))
+                .build(),
+            ),
+        )
+        .path(
+            HEALTHZ_PATH,
+            PathItem::new(
+                PathItemType::Get,
+                OperationBuilder::new()
+                    .operation_id(Some("healthz"))
+                    .summary(Some("Liveness probe"))
+                    .tag("probes")
+                    .response("200", json_response("Process is up", "HealthResponse"))
+                    .build(),
+            ),
+        )
+        .path(
+            route(READYZ_PATH),
+            PathItem::new(
+                PathItemType::Get,
+                operation("readyz", "Readiness probe", "probes")
+                    .response("200", json_response("Serving an epoch", "ReadinessResponse"))
+                    .response("503", json_response("Not serving yet", "ReadinessResponse"))
+                    .build(),
+            ),
+        )
+        .path(
+            route(STATUS_PATH),
+            PathItem::new(
+                PathItemType::Get,
+                operation("status", "Current epoch, root and build in progress", "probes")
+                    .response("200", json_response("Server status", "StatusResponse"))
+                    .build(),
+            ),
+        )
+        .build();
+
+    let components = ComponentsBuilder::new()
+        .schema_from::<ProofRequest>()
+        .schema_from::<ProofResponse>()
+        .schema_from::<MultiproofPageRequest>()
+        .schema_from::<MultiproofPage>()
+        .schema_from::<PageRecord>()
+        .schema_from::<HealthResponse>()
+        .schema_from::<ReadinessResponse>()
+        .schema_from::<StatusResponse>()
+        .schema_from::<BuildProgress>()
+        .build();
+
+    let mut spec = OpenApiBuilder::new()
+        .info(
+            InfoBuilder::new()
+                .title("mimi proof server")
+                .version(env!("CARGO_PKG_VERSION"))
+                .description(Some(
+                    "Self-verification API: users fetch inclusion proofs for their balance \
+                     and check them against the operator's signed root.",
+                ))
+                .build(),
+        )
+        .paths(paths)
+        .components(Some(components))
+        .build();
+    if let Some(url) = &options.server_url {
+        spec.servers = Some(vec![utoipa::openapi::Server::new(url)]);
+    }
+    spec
+}
+
+fn json_body(schema: &str) -> utoipa::openapi::request_body::RequestBody {
+    RequestBodyBuilder::new()
+        .content(JSON, ContentBuilder::new().schema(Ref::from_schema_name(schema)).build())
+        .required(Some(Required::True))
+        .build()
+}
+
+fn json_response(description: &str, schema: &str) -> utoipa::openapi::Response {
+    ResponseBuilder::new()
+        .description(description)
+        .content(JSON, ContentBuilder::new().schema(Ref::from_schema_name(schema)).build())
+        .build()
+}
+
+fn text_response(description: &str) -> utoipa::openapi::Response {
+    ResponseBuilder::new().description(description).build()
+}
@@ -0,0 +1,142 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
+
+use crate::encoding::{decode_commitment, encode_commitment, encode_usize};
+use crate::{ParseError, SumCommitment};
+
+// Largest page a client may ask for; bigger limits are clamped
+pub const MAX_PAGE_SIZE: usize = 10_000;
+
+// Define the MultiproofPageRequest struct, one page of a multiproof or full-tree audit stream
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct MultiproofPageRequest {
+    // Latest epoch when absent; ignored once a cursor pins the epoch
+    pub epoch: Option<u64>,
+    // Leaf positions to prove; absent streams every leaf, i.e. the full audit data
+    pub positions: Option<Vec<usize>>,
+    // `next_cursor` from the previous page, absent for the first page
+    pub cursor: Option<String>,
+    pub limit: usize,
+}
+
+// Define the MultiproofPage struct, a slice of the DFS-ordered records StreamingVerifier consumes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct MultiproofPage {
+    pub epoch: u64,
+    // Root as `<amount>:<hex digest>`
+    pub root: String,
+    // Leaves of the epoch's tree, which StreamingVerifier is built with; clients should
+    // take it from the signed tree header rather than trust it here
+    pub leaf_count: usize,
+    pub records: Vec<PageRecord>,
+    // Absent on the last page
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct PageRecord {
+    pub index: usize,
+    // Hex of the 40-byte amount || digest encoding
+    pub commitment: String,
+}
+
+impl PageRecord {
+    pub(crate) fn new<C: SumCommitment>(index: usize, node: &C) -> Self {
+        PageRecord {
+            index,
+            commitment: hex::encode(encode_commitment(node.amount(), &node.digest())),
+        }
+    }
+
+    pub fn decode<C: SumCommitment>(&self) -> Result<(usize, C), ParseError> {
+        let bytes = hex::decode(&self.commitment).map_err(|_| ParseError::InvalidHex)?;
+        let (amount, digest) = decode_commitment(&bytes)?;
+        Ok((self.index, C::from_parts(amount, digest)))
+    }
+}
+
+// Define the Cursor struct, where the next page starts. It pins the epoch and a tag of
+// the requested positions so pages of one stream can't mix trees or selections.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Cursor {
+    pub(crate) epoch: u64,
+    // First leaf the page covers; records partition the leaves, so a page resumes there
+    pub(crate) offset: usize,
+    pub(crate) selection: [u8; 8],
+}
+
+impl Cursor {
+    pub(crate) fn encode(&self) -> String {
+        let mut bytes = Vec::with_capacity(24);
+        bytes.extend_from_slice(&self.epoch.to_le_bytes());
+        bytes.extend_from_slice(&encode_usize(self.offset));
+        bytes.extend_from_slice(&self.selection);
+        hex::encode(bytes)
+    }
+
+    pub(crate) fn decode(cursor: &str) -> Option<Self> {
+        let bytes = hex::decode(cursor).ok()?;
+        if bytes.len() != 24 {
+            return None;
+        }
+        let epoch = u64::from_le_bytes(bytes[..8].try_into().ok()?);
+        let offset = u64::from_le_bytes(bytes[8..16].try_into().ok()?);
+        Some(Cursor {
+            epoch,
+            offset: usize::try_from(offset).ok()?,
+            selection: bytes[16..].try_into().ok()?,
+        })
+    }
+}
+
+pub(crate) fn selection_tag(positions: Option<&[usize]>) -> [u8; 8] {
+    let mut hasher = Sha256::new();
+    match positions {
+        None => hasher.update([0u8]),
+        Some(positions) => {
+            hasher.update([1u8]);
+            for position in positions {
+                hasher.update(encode_usize(*position));
+            }
+        }
+    }
+    let digest = hasher.finalize();
+    let mut tag = [0u8; 8];
+    tag.copy_from_slice(&digest[..8]);
+    tag
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MimiSumCommitment;
+
+    #[test]
+    fn cursors_and_records_round_trip_and_reject_garbage() {
+        let cursor = Cursor {
+            epoch: 7,
+            offset: 3,
+            selection: selection_tag(Some(&[1, 2])),
+        };
+        assert_eq!(Cursor::decode(&cursor.encode()), Some(cursor));
+        assert_ne!(cursor.selection, selection_tag(None));
+        assert_eq!(Cursor::decode(&cursor.encode()[2..]), None);
+        assert_eq!(Cursor::decode(&"zz".repeat(24)), None);
+
+        let node = MimiSumCommitment::from_parts(42, [5; 32].into());
+        let record = PageRecord::new(6, &node);
+        let (index, decoded) = record.decode::<MimiSumCommitment>().unwrap();
+        assert_eq!(
+            (index, decoded.amount(), decoded.digest()),
+            (6, 42, node.digest())
+        );
+        let truncated = PageRecord {
+            index: 6,
+            commitment: record.commitment[2..].to_string(),
+        };
+        assert!(truncated.decode::<MimiSumCommitment>().is_err());
+    }
+}
@@ -0,0 +1,274 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use super::{ProofServer, ServedEpoch, ServerError};
+use crate::bundle::SignedRoot;
+use crate::keys::{KeyError, KeyRing};
+use crate::scheduler::{DirectoryStore, SchedulerHandle};
+use crate::snapshot::{Snapshot, SnapshotError};
+use crate::{ParseError, SumCommitment};
+
+// Define the EpochReloader struct, which picks up epochs another process built into a
+// DirectoryStore and publishes them to a running server. The snapshot is read, checked
+// and turned into a tree without holding the server lock; only the publish itself takes
+// it, so requests in flight finish on the old epoch and the next ones see the new one.
+#[derive(Debug, Clone)]
+pub struct EpochReloader {
+    store: DirectoryStore,
+    // Roots must verify under the key the ring holds for their epoch
+    keys: KeyRing,
+}
+
+impl EpochReloader {
+    pub fn new(store: DirectoryStore, keys: KeyRing) -> Self {
+        EpochReloader { store, keys }
+    }
+
+    // Replaces the key ring after a rotation
+    pub fn set_keys(&mut self, keys: KeyRing) {
+        self.keys = keys;
+    }
+
+    // Newest epoch whose root file has been written; the root lands after the snapshot,
+    // so a half-written epoch is never picked
+    pub fn latest_epoch(&self) -> Result<Option<u64>, ReloadError> {
+        let mut latest = None;
+        for entry in fs::read_dir(self.store.dir()).map_err(ReloadError::Io)? {
+            let name = entry.map_err(ReloadError::Io)?.file_name();
+            let epoch = name
+                .to_str()
+                .and_then(|name| name.strip_prefix("epoch-")?.strip_suffix(".root"))
+                .and_then(|epoch| epoch.parse::<u64>().ok());
+            latest = latest.max(epoch);
+        }
+        Ok(latest)
+    }
+
+    // Reads one epoch, checking the operator signed its root and the snapshot rebuilds
+    // to that root. Accounts are indexed by the user ids the snapshot kept.
+    pub fn load<C: SumCommitment>(&self, epoch: u64) -> Result<ServedEpoch<C>, ReloadError> {
+        let signed_root = fs::read(self.store.root_path(epoch)).map_err(ReloadError::Io)?;
+        let signed_root = SignedRoot::<C>::try_from(signed_root.as_slice())?;
+        if signed_root.epoch != epoch {
+            return Err(ReloadError::EpochMismatch {
+                expected: epoch,
+                found: signed_root.epoch,
+            });
+        }
+        self.keys
+            .verify_root(&signed_root)
+            .map_err(ReloadError::Key)?;
+
+        let snapshot = fs::read(self.store.path(epoch)).map_err(ReloadError::Io)?;
+        let snapshot = Snapshot::<C>::try_from(snapshot.as_slice())?;
+        if snapshot.epoch != epoch {
+            return Err(ReloadError::EpochMismatch {
+                expected: epoch,
+                found: snapshot.epoch,
+            });
+        }
+        let mut served = ServedEpoch::new(snapshot.to_tree()?, signed_root)?;
+        for (position, leaf) in snapshot.leaves().iter().enumerate() {
+            let user_id = leaf
+                .preimage
+                .as_ref()
+                .and_then(|preimage| preimage.user_id.as_deref())
+                .and_then(|user_id| std::str::from_utf8(user_id).ok());
+            if let Some(user_id) = user_id {
+                served = served.with_account(user_id, position);
+            }
+        }
+        Ok(served)
+    }
+
+    // Loads and publishes `epoch`, e.g. from an admin endpoint after a build finished
+    pub fn reload<C: SumCommitment>(
+        &self,
+        epoch: u64,
+        server: &Mutex<ProofServer<C>>,
+    ) -> Result<(), ReloadError> {
+        let served = self.load(epoch)?;
+        server
+            .lock()
+            .map_err(|_| ReloadError::Poisoned)?
+            .publish(served)?;
+        Ok(())
+    }
+
+    // Publishes the newest epoch on disk if it is newer than the one served; returns it
+    pub fn poll<C: SumCommitment>(
+        &self,
+        server: &Mutex<ProofServer<C>>,
+    ) -> Result<Option<u64>, ReloadError> {
+        let current = server
+            .lock()
+            .map_err(|_| ReloadError::Poisoned)?
+            .current()
+            .map(ServedEpoch::epoch);
+        match self.latest_epoch()? {
+            Some(latest) if current.is_none_or(|current| latest > current) => {
+                self.reload(latest, server)?;
+                Ok(Some(latest))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    // Polls the directory every `interval` until `stop` is called on the handle. Only
+    // polls that published an epoch or failed are passed to `on_reload`.
+    pub fn spawn<C>(
+        self,
+        server: Arc<Mutex<ProofServer<C>>>,
+        interval: Duration,
+        mut on_reload: impl FnMut(Result<u64, ReloadError>) + Send + 'static,
+    ) -> SchedulerHandle
+    where
+        C: SumCommitment + Send + 'static,
+        C::Leaf: Send,
+    {
+        let (stop, stopped) = mpsc::channel();
+        let thread = thread::spawn(move || loop {
+            match stopped.recv_timeout(interval) {
+                Err(RecvTimeoutError::Timeout) => match self.poll(&server) {
+                    Ok(Some(epoch)) => on_reload(Ok(epoch)),
+                    Ok(None) => {}
+                    Err(err) => on_reload(Err(err)),
+                },
+                Ok(()) | Err(RecvTimeoutError::Disconnected) => return,
+            }
+        });
+        SchedulerHandle::new(stop, thread)
+    }
+}
+
+// Define the ReloadError enum for epochs on disk that can't be served
+#[derive(Debug)]
+pub enum ReloadError {
+    Io(io::Error),
+    Parse(ParseError),
+    Snapshot(SnapshotError),
+    Key(KeyError),
+    EpochMismatch { expected: u64, found: u64 },
+    Server(ServerError),
+    Poisoned,
+}
+
+impl fmt::Display for ReloadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReloadError::Io(err) => write!(f, "i/o error: {}", err),
+            ReloadError::Parse(err) => write!(f, "malformed signed root: {}", err),
+            ReloadError::Snapshot(err) => write!(f, "{}", err),
+            ReloadError::Key(err) => write!(f, "{}", err),
+            ReloadError::EpochMismatch { expected, found } => write!(
+                f,
+                "file for epoch {} holds epoch {}",
+                expected, found
+            ),
+            ReloadError::Server(err) => write!(f, "cannot publish epoch: {}", err),
+            ReloadError::Poisoned => write!(f, "proof server lock is poisoned"),
+        }
+    }
+}
+
+impl std::error::Error for ReloadError {}
+
+impl From<ParseError> for ReloadError {
+    fn from(err: ParseError) -> Self {
+        ReloadError::Parse(err)
+    }
+}
+
+impl From<SnapshotError> for ReloadError {
+    fn from(err: SnapshotError) -> Self {
+        ReloadError::Snapshot(err)
+    }
+}
+
+impl From<ServerError> for ReloadError {
+    fn from(err: ServerError) -> Self {
+        ReloadError::Server(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ed25519_dalek::SigningKey;
+
+    use super::*;
+    use crate::scheduler::SnapshotStore;
+    use crate::server::{ServedTree, TokenKey};
+    use crate::{MerkleSumTreeBuilder, MimiSumCommitment, UserLeaf};
+
+    type C = MimiSumCommitment;
+
+    // Writes epoch `epoch` of a two-account tree the way an EpochScheduler would
+    fn persist(store: &mut DirectoryStore, epoch: u64, key: &SigningKey) -> SignedRoot<C> {
+        let leaves: Vec<UserLeaf<u64>> = [("alice", 100), ("bob", 200 + epoch)]
+            .into_iter()
+            .map(|(user_id, record)| UserLeaf {
+                user_id: user_id.to_string(),
+                record,
+            })
+            .collect();
+        let tree: ServedTree<C> = MerkleSumTreeBuilder::new().build(&leaves).unwrap();
+        let signed_root = SignedRoot::sign(epoch, tree.commit(), key);
+        store
+            .persist(&Snapshot::capture(epoch, &tree, &leaves))
+            .unwrap();
+        SnapshotStore::<C>::persist_root(store, &signed_root).unwrap();
+        signed_root
+    }
+
+    #[test]
+    fn only_signed_epochs_stored_under_their_own_number_are_served() {
+        let dir = std::env::temp_dir().join(format!("mimi-reload-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut store = DirectoryStore::new(&dir);
+        let key = SigningKey::from_bytes(&[9; 32]);
+        let reloader = EpochReloader::new(store.clone(), KeyRing::new(key.verifying_key(), 0));
+        let server = Mutex::new(ProofServer::<C>::new(TokenKey::new([1; 32])));
+
+        let first = persist(&mut store, 1, &key);
+        assert_eq!(reloader.poll(&server).unwrap(), Some(1));
+        assert_eq!(reloader.poll(&server).unwrap(), None);
+        assert_eq!(server.lock().unwrap().epoch(1).unwrap().tree().len(), 2);
+
+        // A root file holding another epoch's root
+        fs::write(store.root_path(2), first.to_bytes()).unwrap();
+        assert!(matches!(
+            reloader.reload(2, &server),
+            Err(ReloadError::EpochMismatch {
+                expected: 2,
+                found: 1
+            })
+        ));
+        // A correctly signed root next to another epoch's snapshot
+        persist(&mut store, 3, &key);
+        fs::copy(store.path(1), store.path(3)).unwrap();
+        assert!(matches!(
+            reloader.reload(3, &server),
+            Err(ReloadError::EpochMismatch {
+                expected: 3,
+                found: 1
+            })
+        ));
+        persist(&mut store, 4, &SigningKey::from_bytes(&[8; 32]));
+        assert!(matches!(
+            reloader.reload(4, &server),
+            Err(ReloadError::Key(_))
+        ));
+        assert!(matches!(
+            reloader.reload(1, &server),
+            Err(ReloadError::Server(ServerError::StaleEpoch { .. }))
+        ));
+        assert_eq!(server.lock().unwrap().current().unwrap().epoch(), 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,289 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use super::{
+    AdminApi, AdminCredentials, AdminError, AdminResponse, HealthResponse, MultiproofPage,
+    ProofResponse, ProofServer, ServerError, ADMIN_PATH, HEALTHZ_PATH,
+};
+use crate::{Leaf, SumCommitment};
+
+// Every tenant endpoint lives under `/tenants/<tenant>`, e.g. `/tenants/spot/readyz`
+pub const TENANT_PREFIX: &str = "/tenants/";
+
+const MAX_TENANT_LEN: usize = 64;
+
+// Define the TenantId struct, the name a tree is hosted under. Lowercase ASCII letters,
+// digits and `-` only, so it is safe in URL paths, directory names and object keys.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TenantId(String);
+
+impl TenantId {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromStr for TenantId {
+    type Err = ServerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let valid = !s.is_empty()
+            && s.len() <= MAX_TENANT_LEN
+            && !s.starts_with('-')
+            && s.bytes()
+                .all(|byte| byte.is_ascii_lowercase() || byte.is_ascii_digit() || byte == b'-');
+        if !valid {
+            return Err(ServerError::InvalidTenant(s.to_string()));
+        }
+        Ok(TenantId(s.to_string()))
+    }
+}
+
+impl fmt::Display for TenantId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+// Splits `/tenants/<tenant>/<rest>` into the tenant and `/<rest>`; None for paths
+// outside the tenant prefix
+pub fn split_tenant_path(path: &str) -> Option<Result<(TenantId, &str), ServerError>> {
+    let tail = path.strip_prefix(TENANT_PREFIX)?;
+    let (tenant, rest) = match tail.find('/') {
+        Some(index) => tail.split_at(index),
+        None => (tail, "/"),
+    };
+    Some(tenant.parse().map(|tenant| (tenant, rest)))
+}
+
+// Define the TenantServer struct, several independent trees behind one server. Each
+// tenant is a full ProofServer with its own retrieval token key, limits, epochs and
+// root log, and should be published under its own operator key, so nothing one
+// tenant serves vouches for another. Servers are shared, so a tenant's scheduler,
+// reloader and AdminApi drive the same server its routes answer from.
+#[derive(Debug)]
+pub struct TenantServer<C: SumCommitment> {
+    tenants: BTreeMap<TenantId, Arc<Mutex<ProofServer<C>>>>,
+}
+
+impl<C: SumCommitment> Default for TenantServer<C> {
+    fn default() -> Self {
+        TenantServer {
+            tenants: BTreeMap::new(),
+        }
+    }
+}
+
+impl<C: SumCommitment> TenantServer<C> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, tenant: TenantId, server: ProofServer<C>) -> Result<(), ServerError> {
+        if self.tenants.contains_key(&tenant) {
+            return Err(ServerError::DuplicateTenant(tenant.to_string()));
+        }
+        self.tenants.insert(tenant, Arc::new(Mutex::new(server)));
+        Ok(())
+    }
+
+    pub fn remove(&mut self, tenant: &TenantId) -> Option<Arc<Mutex<ProofServer<C>>>> {
+        self.tenants.remove(tenant)
+    }
+
+    // The tenant's shared server, e.g. to hand to its AdminApi or scheduler
+    pub fn tenant(&self, tenant: &TenantId) -> Result<Arc<Mutex<ProofServer<C>>>, ServerError> {
+        self.tenants
+            .get(tenant)
+            .cloned()
+            .ok_or_else(|| ServerError::UnknownTenant(tenant.to_string()))
+    }
+
+    fn lock(&self, tenant: &TenantId) -> Result<MutexGuard<'_, ProofServer<C>>, ServerError> {
+        self.tenants
+            .get(tenant)
+            .ok_or_else(|| ServerError::UnknownTenant(tenant.to_string()))?
+            .lock()
+            .map_err(|_| ServerError::Poisoned)
+    }
+
+    pub fn tenants(&self) -> impl Iterator<Item = &TenantId> + '_ {
+        self.tenants.keys()
+    }
+
+    pub fn handle_proof(
+        &self,
+        tenant: &TenantId,
+        client: IpAddr,
+        body: &[u8],
+    ) -> Result<ProofResponse, ServerError> {
+        self.lock(tenant)?.handle_proof(client, body)
+    }
+
+    pub fn handle_multiproof_page(
+        &self,
+        tenant: &TenantId,
+        client: IpAddr,
+        auditor: Option<&[u8]>,
+        body: &[u8],
+    ) -> Result<MultiproofPage, ServerError> {
+        self.lock(tenant)?
+            .handle_multiproof_page(client, auditor, body)
+    }
+
+    // `/healthz` answers for the process; readiness and status are per tenant, under
+    // the tenant prefix. Unknown or invalid tenants get the error's status and message.
+    pub fn probe(&self, path: &str) -> Option<(u16, String)> {
+        if path == HEALTHZ_PATH {
+            let health = HealthResponse {
+                status: "ok".to_string(),
+            };
+            let body = serde_json::to_string(&health).expect("probe responses always serialize");
+            return Some((200, body));
+        }
+        let routed =
+            split_tenant_path(path)?.and_then(|(tenant, rest)| Ok((self.lock(&tenant)?, rest)));
+        match routed {
+            Ok((server, rest)) => server.probe(rest),
+            Err(err) => Some((err.status(), err.to_string())),
+        }
+    }
+}
+
+// Define the TenantAdmin struct, the admin API of a TenantServer. Each tenant gets its own
+// AdminApi over its shared server, with its own AdminAuth, so a tenant's admin
+// credential drives that tenant and no other.
+pub struct TenantAdmin<C: SumCommitment, L: Leaf> {
+    tenants: BTreeMap<TenantId, AdminApi<C, L>>,
+}
+
+impl<C: SumCommitment, L: Leaf> Default for TenantAdmin<C, L> {
+    fn default() -> Self {
+        TenantAdmin {
+            tenants: BTreeMap::new(),
+        }
+    }
+}
+
+impl<C: SumCommitment, L: Leaf> TenantAdmin<C, L> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, tenant: TenantId, admin: AdminApi<C, L>) -> Result<(), ServerError> {
+        if self.tenants.contains_key(&tenant) {
+            return Err(ServerError::DuplicateTenant(tenant.to_string()));
+        }
+        self.tenants.insert(tenant, admin);
+        Ok(())
+    }
+
+    pub fn tenant(&self, tenant: &TenantId) -> Option<&AdminApi<C, L>> {
+        self.tenants.get(tenant)
+    }
+
+    // Unknown tenants are refused like bad credentials, so callers without any can't
+    // probe which tenants exist
+    pub fn handle(
+        &self,
+        tenant: &TenantId,
+        credentials: &AdminCredentials<'_>,
+        body: &[u8],
+    ) -> Result<AdminResponse, AdminError> {
+        self.tenants
+            .get(tenant)
+            .ok_or(AdminError::Unauthorized)?
+            .handle(credentials, body)
+    }
+
+    // Admin requests for a tenant are POSTed to `/tenants/<tenant>/admin`; None for
+    // other paths
+    pub fn route(
+        &self,
+        path: &str,
+        credentials: &AdminCredentials<'_>,
+        body: &[u8],
+    ) -> Option<Result<AdminResponse, AdminError>> {
+        match split_tenant_path(path)? {
+            Ok((tenant, ADMIN_PATH)) => Some(self.handle(&tenant, credentials, body)),
+            Ok(_) => None,
+            Err(_) => Some(Err(AdminError::Unauthorized)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ed25519_dalek::SigningKey;
+
+    use super::*;
+    use crate::keys::KeyRing;
+    use crate::server::tests::{served, server};
+    use crate::server::AdminAuth;
+    use crate::MimiSumCommitment;
+
+    fn admin(
+        tenants: &TenantServer<MimiSumCommitment>,
+        tenant: &TenantId,
+        token: &str,
+    ) -> AdminApi<MimiSumCommitment, u64> {
+        let keys = KeyRing::new(SigningKey::from_bytes(&[9; 32]).verifying_key(), 0);
+        AdminApi::new(
+            AdminAuth::token(token),
+            tenants.tenant(tenant).unwrap(),
+            keys,
+        )
+    }
+
+    fn bearer(token: &str) -> AdminCredentials<'_> {
+        AdminCredentials {
+            bearer: Some(token),
+            client_certificate: None,
+        }
+    }
+
+    #[test]
+    fn admin_credentials_only_drive_their_own_tenant() {
+        let (spot, margin): (TenantId, TenantId) =
+            ("spot".parse().unwrap(), "margin".parse().unwrap());
+        let mut tenants = TenantServer::new();
+        tenants.add(spot.clone(), server()).unwrap();
+        tenants.add(margin.clone(), server()).unwrap();
+        for tenant in [&spot, &margin] {
+            let shared = tenants.tenant(tenant).unwrap();
+            shared.lock().unwrap().publish(served(2)).unwrap();
+        }
+        let mut admins = TenantAdmin::new();
+        admins
+            .add(spot.clone(), admin(&tenants, &spot, "spot-token"))
+            .unwrap();
+        admins
+            .add(margin.clone(), admin(&tenants, &margin, "margin-token"))
+            .unwrap();
+
+        let prune = br#"{"command":"prune","keep":1}"#;
+        assert!(matches!(
+            admins.handle(&spot, &bearer("margin-token"), prune),
+            Err(AdminError::Unauthorized)
+        ));
+        assert!(matches!(
+            admins.route("/tenants/futures/admin", &bearer("spot-token"), prune),
+            Some(Err(AdminError::Unauthorized))
+        ));
+        assert!(admins
+            .route("/tenants/spot/status", &bearer("spot-token"), prune)
+            .is_none());
+
+        let pruned = admins.route("/tenants/spot/admin", &bearer("spot-token"), prune);
+        assert_eq!(
+            pruned.unwrap().unwrap(),
+            AdminResponse::Pruned { removed: vec![1] }
+        );
+        let serves_first = |tenant| tenants.lock(tenant).unwrap().epoch(1).is_some();
+        assert!(!serves_first(&spot));
+        assert!(serves_first(&margin));
+    }
+}
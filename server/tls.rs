@@ -0,0 +1,241 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{RootCertStore, ServerConfig, ServerConnection, StreamOwned};
+
+use super::{AdminCredentials, ServerError};
+
+// How long a client gets to finish the handshake before its connection is dropped
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Define the ClientAuth enum, whether clients present certificates. With `Optional`
+// anyone can fetch their own proof, while auditor endpoints (full-tree multiproof
+// pages, the admin API) check `TlsConnection::auditor` first. CA certificates are DER.
+#[derive(Debug, Clone, Default)]
+pub enum ClientAuth {
+    #[default]
+    None,
+    Optional(Vec<Vec<u8>>),
+    Required(Vec<Vec<u8>>),
+}
+
+// Define the TlsSettings struct, the server certificate and client authentication of
+// the built-in TLS listener
+#[derive(Debug, Clone)]
+pub struct TlsSettings {
+    // PEM chain, server certificate first
+    pub cert_chain: PathBuf,
+    // PEM PKCS#8, PKCS#1 or SEC1 key
+    pub private_key: PathBuf,
+    pub client_auth: ClientAuth,
+    // Read and write timeout on the socket while the handshake runs on the accept thread
+    pub handshake_timeout: Duration,
+}
+
+impl TlsSettings {
+    pub fn from_pem_files(cert_chain: impl Into<PathBuf>, private_key: impl Into<PathBuf>) -> Self {
+        TlsSettings {
+            cert_chain: cert_chain.into(),
+            private_key: private_key.into(),
+            client_auth: ClientAuth::None,
+            handshake_timeout: HANDSHAKE_TIMEOUT,
+        }
+    }
+
+    pub fn with_client_auth(mut self, client_auth: ClientAuth) -> Self {
+        self.client_auth = client_auth;
+        self
+    }
+
+    pub fn with_handshake_timeout(mut self, timeout: Duration) -> Self {
+        self.handshake_timeout = timeout;
+        self
+    }
+
+    pub fn server_config(&self) -> Result<Arc<ServerConfig>, TlsError> {
+        let chain = rustls_pemfile::certs(&mut open_pem(&self.cert_chain)?)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(TlsError::Io)?;
+        if chain.is_empty() {
+            return Err(TlsError::NoCertificate);
+        }
+        let key: PrivateKeyDer<'static> =
+            rustls_pemfile::private_key(&mut open_pem(&self.private_key)?)
+                .map_err(TlsError::Io)?
+                .ok_or(TlsError::NoPrivateKey)?;
+
+        let builder = ServerConfig::builder();
+        let builder = match &self.client_auth {
+            ClientAuth::None => builder.with_no_client_auth(),
+            ClientAuth::Optional(cas) => builder.with_client_cert_verifier(
+                WebPkiClientVerifier::builder(root_store(cas)?)
+                    .allow_unauthenticated()
+                    .build()
+                    .map_err(|err| TlsError::Config(err.to_string()))?,
+            ),
+            ClientAuth::Required(cas) => builder.with_client_cert_verifier(
+                WebPkiClientVerifier::builder(root_store(cas)?)
+                    .build()
+                    .map_err(|err| TlsError::Config(err.to_string()))?,
+            ),
+        };
+        let mut config = builder
+            .with_single_cert(chain, key)
+            .map_err(|err| TlsError::Config(err.to_string()))?;
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+        Ok(Arc::new(config))
+    }
+}
+
+fn open_pem(path: &Path) -> Result<BufReader<File>, TlsError> {
+    Ok(BufReader::new(File::open(path).map_err(TlsError::Io)?))
+}
+
+fn root_store(cas: &[Vec<u8>]) -> Result<Arc<RootCertStore>, TlsError> {
+    if cas.is_empty() {
+        return Err(TlsError::Config("client authentication needs a CA".to_string()));
+    }
+    let mut roots = RootCertStore::empty();
+    for ca in cas {
+        roots
+            .add(CertificateDer::from(ca.clone()))
+            .map_err(|err| TlsError::Config(err.to_string()))?;
+    }
+    Ok(Arc::new(roots))
+}
+
+// Define the TlsListener struct, a blocking TCP listener that completes the TLS
+// handshake before handing a connection to the HTTP layer
+#[derive(Debug)]
+pub struct TlsListener {
+    listener: TcpListener,
+    config: Arc<ServerConfig>,
+    handshake_timeout: Duration,
+}
+
+impl TlsListener {
+    pub fn bind(addr: impl ToSocketAddrs, settings: &TlsSettings) -> Result<Self, TlsError> {
+        Ok(TlsListener {
+            listener: TcpListener::bind(addr).map_err(TlsError::Io)?,
+            config: settings.server_config()?,
+            handshake_timeout: settings.handshake_timeout,
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    // A failed handshake only fails this connection; keep accepting after an error. The
+    // handshake is bounded by the handshake timeout, so a client that never sends its
+    // ClientHello can't hold up the connections behind it. The timeouts are cleared once
+    // the handshake is done, leaving the HTTP layer to set its own.
+    pub fn accept(&self) -> Result<TlsConnection, TlsError> {
+        let (mut socket, peer) = self.listener.accept().map_err(TlsError::Io)?;
+        set_timeouts(&socket, Some(self.handshake_timeout))?;
+        let mut connection = ServerConnection::new(self.config.clone())
+            .map_err(|err| TlsError::Handshake(err.to_string()))?;
+        while connection.is_handshaking() {
+            connection
+                .complete_io(&mut socket)
+                .map_err(|err| TlsError::Handshake(err.to_string()))?;
+        }
+        set_timeouts(&socket, None)?;
+        Ok(TlsConnection {
+            stream: StreamOwned::new(connection, socket),
+            peer,
+        })
+    }
+}
+
+// A zero timeout is an error for the socket, so it is treated as the smallest one
+fn set_timeouts(socket: &TcpStream, timeout: Option<Duration>) -> Result<(), TlsError> {
+    let timeout = timeout.map(|timeout| timeout.max(Duration::from_millis(1)));
+    socket.set_read_timeout(timeout).map_err(TlsError::Io)?;
+    socket.set_write_timeout(timeout).map_err(TlsError::Io)
+}
+
+// Define the TlsConnection struct, one accepted connection with what the handshake
+// established about the client
+#[derive(Debug)]
+pub struct TlsConnection {
+    stream: StreamOwned<ServerConnection, TcpStream>,
+    peer: SocketAddr,
+}
+
+impl TlsConnection {
+    pub fn peer_addr(&self) -> SocketAddr {
+        self.peer
+    }
+
+    // DER of the client certificate, already verified against the configured CAs
+    pub fn client_certificate(&self) -> Option<&[u8]> {
+        self.stream
+            .conn
+            .peer_certificates()
+            .and_then(|chain| chain.first())
+            .map(|certificate| certificate.as_ref())
+    }
+
+    // Gate for auditor-only endpoints
+    pub fn auditor(&self) -> Result<&[u8], ServerError> {
+        self.client_certificate()
+            .ok_or(ServerError::ClientCertificateRequired)
+    }
+
+    // Admin credentials for AdminApi::handle, with the bearer token from the request
+    pub fn admin_credentials<'a>(&'a self, bearer: Option<&'a str>) -> AdminCredentials<'a> {
+        AdminCredentials {
+            bearer,
+            client_certificate: self.client_certificate(),
+        }
+    }
+}
+
+impl Read for TlsConnection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stream.read(buf)
+    }
+}
+
+impl Write for TlsConnection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+// Define the TlsError enum for TLS settings that can't be loaded or connections that
+// fail their handshake
+#[derive(Debug)]
+pub enum TlsError {
+    Io(io::Error),
+    NoCertificate,
+    NoPrivateKey,
+    Config(String),
+    Handshake(String),
+}
+
+impl fmt::Display for TlsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TlsError::Io(err) => write!(f, "i/o error: {}", err),
+            TlsError::NoCertificate => write!(f, "no certificate in the PEM chain"),
+            TlsError::NoPrivateKey => write!(f, "no private key in the PEM file"),
+            TlsError::Config(reason) => write!(f, "invalid TLS configuration: {}", reason),
+            TlsError::Handshake(reason) => write!(f, "TLS handshake failed: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for TlsError {}
@@ -0,0 +1,109 @@
+use std::fmt;
+use std::str::FromStr;
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::encoding::encode_len;
+use crate::ParseError;
+
+type HmacSha256 = Hmac<Sha256>;
+
+const TOKEN_DOMAIN: &[u8] = b"mimi-retrieval-token-v1";
+
+// Define the RetrievalToken struct, the per-user, per-epoch secret a proof request must carry
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct RetrievalToken([u8; 32]);
+
+impl RetrievalToken {
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+impl fmt::Debug for RetrievalToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("RetrievalToken(..)")
+    }
+}
+
+impl fmt::Display for RetrievalToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&hex::encode(self.0))
+    }
+}
+
+impl FromStr for RetrievalToken {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = hex::decode(s).map_err(|_| ParseError::InvalidHex)?;
+        let bytes: [u8; 32] =
+            bytes
+                .try_into()
+                .map_err(|bytes: Vec<u8>| ParseError::InvalidLength {
+                    expected: 32,
+                    found: bytes.len(),
+                })?;
+        Ok(RetrievalToken(bytes))
+    }
+}
+
+// Define the TokenKey struct, the server secret retrieval tokens are derived from
+#[derive(Clone)]
+pub struct TokenKey([u8; 32]);
+
+impl TokenKey {
+    pub fn new(key: [u8; 32]) -> Self {
+        TokenKey(key)
+    }
+
+    pub fn derive(&self, epoch: u64, user_id: &str) -> RetrievalToken {
+        RetrievalToken(self.mac(epoch, user_id).finalize().into_bytes().into())
+    }
+
+    // Constant-time comparison, so response timing doesn't leak how much of a guess matched
+    pub fn validate(&self, epoch: u64, user_id: &str, token: &RetrievalToken) -> bool {
+        self.mac(epoch, user_id).verify_slice(&token.0).is_ok()
+    }
+
+    fn mac(&self, epoch: u64, user_id: &str) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(&self.0).expect("hmac takes any key length");
+        mac.update(TOKEN_DOMAIN);
+        mac.update(&epoch.to_le_bytes());
+        mac.update(&encode_len(user_id.as_bytes()));
+        mac.update(user_id.as_bytes());
+        mac
+    }
+}
+
+impl fmt::Debug for TokenKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("TokenKey(..)")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens_only_validate_for_their_epoch_and_user() {
+        let key = TokenKey::new([1; 32]);
+        let token = key.derive(3, "alice");
+        assert!(key.validate(3, "alice", &token));
+        assert!(!key.validate(4, "alice", &token));
+        assert!(!key.validate(3, "alicE", &token));
+        assert!(!TokenKey::new([2; 32]).validate(3, "alice", &token));
+
+        assert_eq!(token.to_string().parse::<RetrievalToken>(), Ok(token));
+        assert_eq!("zz".parse::<RetrievalToken>(), Err(ParseError::InvalidHex));
+        assert_eq!(
+            "00".repeat(31).parse::<RetrievalToken>(),
+            Err(ParseError::InvalidLength {
+                expected: 32,
+                found: 31
+            })
+        );
+    }
+}
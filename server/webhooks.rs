@@ -0,0 +1,204 @@
+use std::fmt;
+
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::{Digest, Root, SumCommitment};
+
+type HmacSha256 = Hmac<Sha256>;
+
+// Header carrying `t=<unix seconds>,v1=<hex hmac>` over `<t>.<body>`
+pub const SIGNATURE_HEADER: &str = "X-Mimi-Signature";
+
+// Define the EventKind enum, what a subscriber can be notified about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum EventKind {
+    #[serde(rename = "root.published")]
+    RootPublished,
+    #[serde(rename = "consistency.available")]
+    ConsistencyProofAvailable,
+}
+
+// Define the WebhookEvent struct, the JSON body posted to subscribers
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WebhookEvent {
+    pub kind: EventKind,
+    pub epoch: u64,
+    // Root as `<amount>:<hex digest>`
+    pub root: String,
+    // Hex RootLog head the consistency proof ends at, for consistency events
+    pub log_head: Option<String>,
+    // For consistency events after the first epoch: the epoch and hex log head the proof
+    // starts from. Absent in events sent before they were added.
+    #[serde(default)]
+    pub previous_epoch: Option<u64>,
+    #[serde(default)]
+    pub previous_head: Option<String>,
+}
+
+impl WebhookEvent {
+    pub fn root_published<C: SumCommitment>(epoch: u64, root: &Root<C>) -> Self {
+        WebhookEvent {
+            kind: EventKind::RootPublished,
+            epoch,
+            root: root.to_string(),
+            log_head: None,
+            previous_epoch: None,
+            previous_head: None,
+        }
+    }
+
+    // `previous` is the last epoch and log head before this one, None for the first epoch
+    pub fn consistency_available<C: SumCommitment>(
+        epoch: u64,
+        root: &Root<C>,
+        log_head: &Digest,
+        previous: Option<(u64, &Digest)>,
+    ) -> Self {
+        WebhookEvent {
+            kind: EventKind::ConsistencyProofAvailable,
+            epoch,
+            root: root.to_string(),
+            log_head: Some(log_head.to_string()),
+            previous_epoch: previous.map(|(epoch, _)| epoch),
+            previous_head: previous.map(|(_, head)| head.to_string()),
+        }
+    }
+}
+
+// Define the WebhookTransport trait, the HTTP client events are delivered through
+pub trait WebhookTransport {
+    fn post(&self, url: &str, headers: &[(&str, String)], body: &[u8]) -> Result<(), String>;
+}
+
+// Define the Subscription struct, a registered URL, its shared secret and the events it wants
+#[derive(Clone)]
+pub struct Subscription {
+    pub url: String,
+    secret: Vec<u8>,
+    pub kinds: Vec<EventKind>,
+}
+
+impl Subscription {
+    pub fn new(url: impl Into<String>, secret: &[u8], kinds: Vec<EventKind>) -> Self {
+        Subscription {
+            url: url.into(),
+            secret: secret.to_vec(),
+            kinds,
+        }
+    }
+}
+
+impl fmt::Debug for Subscription {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Subscription")
+            .field("url", &self.url)
+            .field("kinds", &self.kinds)
+            .finish_non_exhaustive()
+    }
+}
+
+// Define the Delivery struct, the outcome of posting one event to one subscriber
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Delivery {
+    pub url: String,
+    pub result: Result<(), String>,
+}
+
+// Define the WebhookDispatcher struct, the registered subscriptions
+#[derive(Debug, Default, Clone)]
+pub struct WebhookDispatcher {
+    subscriptions: Vec<Subscription>,
+}
+
+impl WebhookDispatcher {
+    pub fn new() -> Self {
+        WebhookDispatcher::default()
+    }
+
+    // Replaces any existing subscription for the same URL
+    pub fn register(&mut self, subscription: Subscription) {
+        self.unregister(&subscription.url);
+        self.subscriptions.push(subscription);
+    }
+
+    pub fn unregister(&mut self, url: &str) -> bool {
+        let before = self.subscriptions.len();
+        self.subscriptions
+            .retain(|subscription| subscription.url != url);
+        self.subscriptions.len() != before
+    }
+
+    pub fn subscriptions(&self) -> &[Subscription] {
+        &self.subscriptions
+    }
+
+    // Posts to every interested subscriber; one failing URL doesn't stop the rest
+    pub fn dispatch(
+        &self,
+        event: &WebhookEvent,
+        transport: &dyn WebhookTransport,
+        timestamp: u64,
+    ) -> Vec<Delivery> {
+        let body = serde_json::to_vec(event).expect("webhook events always serialize");
+        self.subscriptions
+            .iter()
+            .filter(|subscription| subscription.kinds.contains(&event.kind))
+            .map(|subscription| {
+                let headers = [
+                    ("Content-Type", "application/json".to_string()),
+                    (
+                        SIGNATURE_HEADER,
+                        signature_header(&subscription.secret, timestamp, &body),
+                    ),
+                ];
+                Delivery {
+                    url: subscription.url.clone(),
+                    result: transport.post(&subscription.url, &headers, &body),
+                }
+            })
+            .collect()
+    }
+}
+
+pub fn signature_header(secret: &[u8], timestamp: u64, body: &[u8]) -> String {
+    let tag = signing_mac(secret, timestamp, body).finalize().into_bytes();
+    format!("t={},v1={}", timestamp, hex::encode(tag))
+}
+
+// Receiver-side check: constant-time tag comparison and a freshness window against replays
+pub fn verify_signature(
+    secret: &[u8],
+    header: &str,
+    body: &[u8],
+    now: u64,
+    tolerance_secs: u64,
+) -> bool {
+    let mut timestamp = None;
+    let mut tag = None;
+    for part in header.split(',') {
+        match part.split_once('=') {
+            Some(("t", value)) => timestamp = value.parse::<u64>().ok(),
+            Some(("v1", value)) => tag = hex::decode(value).ok(),
+            _ => {}
+        }
+    }
+    let (Some(timestamp), Some(tag)) = (timestamp, tag) else {
+        return false;
+    };
+    if now.abs_diff(timestamp) > tolerance_secs {
+        return false;
+    }
+    signing_mac(secret, timestamp, body)
+        .verify_slice(&tag)
+        .is_ok()
+}
+
+fn signing_mac(secret: &[u8], timestamp: u64, body: &[u8]) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(secret).expect("hmac takes any key length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    mac
+}
@@ -0,0 +1,220 @@
+use std::fmt;
+
+use ed25519_dalek::{Signature, SigningKey, VerifyingKey};
+
+// Define the Signer trait, where root signatures come from. Keys held by an HSM or a
+// cloud KMS implement it, so the attestation key never enters this process.
+pub trait Signer {
+    fn verifying_key(&self) -> Result<VerifyingKey, SignerError>;
+    fn sign(&self, payload: &[u8]) -> Result<Signature, SignerError>;
+}
+
+impl Signer for SigningKey {
+    fn verifying_key(&self) -> Result<VerifyingKey, SignerError> {
+        Ok(SigningKey::verifying_key(self))
+    }
+
+    fn sign(&self, payload: &[u8]) -> Result<Signature, SignerError> {
+        Ok(ed25519_dalek::Signer::sign(self, payload))
+    }
+}
+
+impl<S: Signer + ?Sized> Signer for &S {
+    fn verifying_key(&self) -> Result<VerifyingKey, SignerError> {
+        (**self).verifying_key()
+    }
+
+    fn sign(&self, payload: &[u8]) -> Result<Signature, SignerError> {
+        (**self).sign(payload)
+    }
+}
+
+// Define the KmsClient trait, a cloud KMS's public-key and sign calls (the HTTP client
+// and credentials live with the caller). Keys must be Ed25519; raw 32-byte public keys
+// and 64-byte signatures are expected.
+pub trait KmsClient {
+    fn public_key(&self, key_id: &str) -> Result<Vec<u8>, String>;
+    fn sign(&self, key_id: &str, message: &[u8]) -> Result<Vec<u8>, String>;
+}
+
+// Define the KmsSigner struct, a Signer backed by one KMS key
+#[derive(Debug)]
+pub struct KmsSigner<K: KmsClient> {
+    client: K,
+    key_id: String,
+}
+
+impl<K: KmsClient> KmsSigner<K> {
+    pub fn new(client: K, key_id: impl Into<String>) -> Self {
+        KmsSigner {
+            client,
+            key_id: key_id.into(),
+        }
+    }
+
+    pub fn key_id(&self) -> &str {
+        &self.key_id
+    }
+}
+
+impl<K: KmsClient> Signer for KmsSigner<K> {
+    fn verifying_key(&self) -> Result<VerifyingKey, SignerError> {
+        let bytes = self
+            .client
+            .public_key(&self.key_id)
+            .map_err(SignerError::Backend)?;
+        parse_verifying_key(&bytes)
+    }
+
+    fn sign(&self, payload: &[u8]) -> Result<Signature, SignerError> {
+        let bytes = self
+            .client
+            .sign(&self.key_id, payload)
+            .map_err(SignerError::Backend)?;
+        parse_signature(&bytes)
+    }
+}
+
+fn parse_verifying_key(bytes: &[u8]) -> Result<VerifyingKey, SignerError> {
+    let bytes: [u8; 32] = bytes.try_into().map_err(|_| SignerError::InvalidKey)?;
+    VerifyingKey::from_bytes(&bytes).map_err(|_| SignerError::InvalidKey)
+}
+
+fn parse_signature(bytes: &[u8]) -> Result<Signature, SignerError> {
+    let bytes: [u8; 64] = bytes
+        .try_into()
+        .map_err(|_| SignerError::InvalidSignature)?;
+    Ok(Signature::from_bytes(&bytes))
+}
+
+#[cfg(feature = "pkcs11")]
+pub use pkcs11::Pkcs11Signer;
+
+#[cfg(feature = "pkcs11")]
+mod pkcs11 {
+    use std::path::Path;
+    use std::sync::Mutex;
+
+    use cryptoki::context::{CInitializeArgs, Pkcs11};
+    use cryptoki::mechanism::Mechanism;
+    use cryptoki::object::{Attribute, AttributeType, ObjectClass, ObjectHandle};
+    use cryptoki::session::{Session, UserType};
+    use cryptoki::types::AuthPin;
+    use ed25519_dalek::{Signature, VerifyingKey};
+
+    use super::{parse_signature, parse_verifying_key, Signer, SignerError};
+
+    // Define the Pkcs11Signer struct, an Ed25519 key pair on a PKCS#11 token (CKM_EDDSA)
+    #[derive(Debug)]
+    pub struct Pkcs11Signer {
+        session: Mutex<Session>,
+        key: ObjectHandle,
+        verifying_key: VerifyingKey,
+    }
+
+    impl Pkcs11Signer {
+        // Loads the vendor module, logs into the first slot holding a token and finds
+        // the key pair whose objects carry `label`
+        pub fn open(module: impl AsRef<Path>, pin: &str, label: &str) -> Result<Self, SignerError> {
+            let context = Pkcs11::new(module.as_ref()).map_err(backend)?;
+            context
+                .initialize(CInitializeArgs::OsThreads)
+                .map_err(backend)?;
+            let slot = *context
+                .get_slots_with_token()
+                .map_err(backend)?
+                .first()
+                .ok_or_else(|| SignerError::Backend("no PKCS#11 token present".to_string()))?;
+            let session = context.open_ro_session(slot).map_err(backend)?;
+            session
+                .login(UserType::User, Some(&AuthPin::new(pin.to_string())))
+                .map_err(backend)?;
+
+            let key = find_object(&session, ObjectClass::PRIVATE_KEY, label)?;
+            let public = find_object(&session, ObjectClass::PUBLIC_KEY, label)?;
+            let verifying_key = match session
+                .get_attributes(public, &[AttributeType::EcPoint])
+                .map_err(backend)?
+                .first()
+            {
+                Some(Attribute::EcPoint(point)) => parse_verifying_key(strip_octet_string(point))?,
+                _ => return Err(SignerError::InvalidKey),
+            };
+
+            Ok(Pkcs11Signer {
+                session: Mutex::new(session),
+                key,
+                verifying_key,
+            })
+        }
+    }
+
+    impl Signer for Pkcs11Signer {
+        fn verifying_key(&self) -> Result<VerifyingKey, SignerError> {
+            Ok(self.verifying_key)
+        }
+
+        fn sign(&self, payload: &[u8]) -> Result<Signature, SignerError> {
+            let session = self
+                .session
+                .lock()
+                .map_err(|_| SignerError::Backend("PKCS#11 session poisoned".to_string()))?;
+            let signature = session
+                .sign(&Mechanism::Eddsa, self.key, payload)
+                .map_err(backend)?;
+            parse_signature(&signature)
+        }
+    }
+
+    fn find_object(
+        session: &Session,
+        class: ObjectClass,
+        label: &str,
+    ) -> Result<ObjectHandle, SignerError> {
+        session
+            .find_objects(&[
+                Attribute::Class(class),
+                Attribute::Label(label.as_bytes().to_vec()),
+            ])
+            .map_err(backend)?
+            .first()
+            .copied()
+            .ok_or_else(|| SignerError::KeyNotFound(label.to_string()))
+    }
+
+    // Tokens return CKA_EC_POINT either raw or DER-wrapped in an OCTET STRING
+    fn strip_octet_string(point: &[u8]) -> &[u8] {
+        match point {
+            [0x04, 0x20, rest @ ..] if rest.len() == 32 => rest,
+            _ => point,
+        }
+    }
+
+    fn backend(err: cryptoki::error::Error) -> SignerError {
+        SignerError::Backend(err.to_string())
+    }
+}
+
+// Define the SignerError enum for signing backends that fail or misbehave
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignerError {
+    Backend(String),
+    KeyNotFound(String),
+    InvalidKey,
+    InvalidSignature,
+}
+
+impl fmt::Display for SignerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignerError::Backend(reason) => write!(f, "signing backend error: {}", reason),
+            SignerError::KeyNotFound(label) => write!(f, "no signing key labelled {}", label),
+            SignerError::InvalidKey => write!(f, "backend returned an invalid Ed25519 public key"),
+            SignerError::InvalidSignature => {
+                write!(f, "backend returned a signature that does not verify")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SignerError {}
@@ -0,0 +1,359 @@
+use std::fmt;
+
+use ed25519_dalek::{SigningKey, VerifyingKey};
+
+use crate::bundle::{ProofBundle, SignedRoot, VerificationPolicy};
+use crate::entropy::{EntropySource, SeededEntropy};
+use crate::view::{ViewChecker, ViewError};
+use crate::{
+    BuildError, ExclusiveAllotmentProof, LeafCommitment, MerkleProof, MimiLeafCommitment,
+    MimiSumCommitment, MimkMerkleTree, Root, UserLeaf,
+};
+
+type SimTree = MimkMerkleTree<MimiSumCommitment, MerkleProof<MimiSumCommitment>>;
+type SimBundle = ProofBundle<MimiSumCommitment, MerkleProof<MimiSumCommitment>>;
+
+// Define the BalanceDistribution enum for how the simulated users' balances are drawn
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BalanceDistribution {
+    Constant(u64),
+    // Inclusive on both ends
+    Uniform { min: u64, max: u64 },
+    // Many small accounts and a few large ones, as real exchanges have
+    Exponential { mean: u64 },
+}
+
+impl BalanceDistribution {
+    fn sample(&self, entropy: &mut impl EntropySource) -> u64 {
+        match *self {
+            BalanceDistribution::Constant(balance) => balance,
+            BalanceDistribution::Uniform { min, max } => {
+                match max.saturating_sub(min).checked_add(1) {
+                    Some(span) => min + entropy.next_u64() % span,
+                    None => entropy.next_u64(),
+                }
+            }
+            BalanceDistribution::Exponential { mean } => {
+                // Uniform in (0, 1], so the logarithm is finite
+                let uniform = (entropy.next_u64() >> 11) as f64 / (1u64 << 53) as f64;
+                let uniform = 1.0 - uniform;
+                (-(mean as f64) * uniform.ln()) as u64
+            }
+        }
+    }
+}
+
+// Define the Behavior enum for what the simulated operator does to its victims, a
+// deterministic selection of `victims` users
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Behavior {
+    Honest,
+    // Victims are left out of the tree and get no proof
+    Omission { victims: usize },
+    // Victims' balances are committed reduced by `percent`
+    Understatement { victims: usize, percent: u8 },
+    // The published root leaves the victims out; each victim is shown a second signed
+    // root for the same epoch in which they are included. Every bundle verifies on its
+    // own, so only clients comparing roots catch it.
+    Equivocation { victims: usize },
+}
+
+impl Behavior {
+    fn victims(&self) -> usize {
+        match *self {
+            Behavior::Honest => 0,
+            Behavior::Omission { victims }
+            | Behavior::Understatement { victims, .. }
+            | Behavior::Equivocation { victims } => victims,
+        }
+    }
+}
+
+// Define the SimConfig struct, everything a simulation run is derived from. The same
+// config always produces the same users, victims, keys and roots.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimConfig {
+    pub users: usize,
+    pub distribution: BalanceDistribution,
+    pub behavior: Behavior,
+    pub seed: [u8; 32],
+    // Clients share the roots they were served, which is what exposes equivocation
+    pub gossip: bool,
+}
+
+impl SimConfig {
+    pub fn new(users: usize, seed: [u8; 32]) -> Self {
+        SimConfig {
+            users,
+            distribution: BalanceDistribution::Uniform { min: 0, max: 1_000_000 },
+            behavior: Behavior::Honest,
+            seed,
+            gossip: true,
+        }
+    }
+}
+
+// Define the SimUser struct, one simulated account and the balance its client expects
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimUser {
+    pub user_id: String,
+    pub balance: u64,
+}
+
+impl SimUser {
+    fn leaf(&self, balance: u64) -> UserLeaf<u64> {
+        UserLeaf {
+            user_id: self.user_id.clone(),
+            record: balance,
+        }
+    }
+}
+
+// Define the Detection enum for what the simulated clients noticed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Detection {
+    // The operator had no proof for this user
+    MissingProof { user: usize },
+    // The bundle failed verification or proves some other account's leaf
+    InvalidBundle { user: usize, reason: String },
+    WrongBalance { user: usize, expected: u64, proven: u64 },
+    // Gossiping clients saw two signed roots for the epoch
+    Equivocation { epoch: u64 },
+}
+
+// Define the EpochReport struct, the outcome of one build-sign-distribute-verify round
+#[derive(Debug, Clone)]
+pub struct EpochReport {
+    pub epoch: u64,
+    pub published_root: Root<MimiSumCommitment>,
+    // Sum of every user's real balance, against the published root's sum
+    pub true_liabilities: u64,
+    pub committed_liabilities: u64,
+    // Indices into `Simulation::users`
+    pub victims: Vec<usize>,
+    pub detections: Vec<Detection>,
+}
+
+impl EpochReport {
+    pub fn detected(&self) -> bool {
+        !self.detections.is_empty()
+    }
+}
+
+// Define the Simulation struct, an in-process operator and its users' clients. Each
+// `run_epoch` builds the tree (as the configured behavior dictates), signs and hands out
+// bundles, and has every client verify its own, so integrators can check their client
+// logic against the attacks it must catch.
+#[derive(Debug)]
+pub struct Simulation {
+    config: SimConfig,
+    operator_key: SigningKey,
+    users: Vec<SimUser>,
+    victims: Vec<usize>,
+    view: ViewChecker<MimiSumCommitment>,
+    epoch: u64,
+}
+
+impl Simulation {
+    pub fn new(config: SimConfig) -> Self {
+        let mut entropy = SeededEntropy::new(config.seed);
+        let operator_key = SigningKey::from_bytes(&entropy.seed());
+        let users = (0..config.users)
+            .map(|index| SimUser {
+                user_id: format!("user-{:06}", index),
+                balance: config.distribution.sample(&mut entropy),
+            })
+            .collect();
+
+        // Partial Fisher-Yates over the user indices
+        let mut order: Vec<usize> = (0..config.users).collect();
+        let victims = config.behavior.victims().min(config.users);
+        for i in 0..victims {
+            let j = i + (entropy.next_u64() % (config.users - i) as u64) as usize;
+            order.swap(i, j);
+        }
+        let mut victims = order[..victims].to_vec();
+        victims.sort_unstable();
+
+        Simulation {
+            view: ViewChecker::new(operator_key.verifying_key()),
+            config,
+            operator_key,
+            users,
+            victims,
+            epoch: 0,
+        }
+    }
+
+    pub fn users(&self) -> &[SimUser] {
+        &self.users
+    }
+
+    pub fn victims(&self) -> &[usize] {
+        &self.victims
+    }
+
+    pub fn operator_key(&self) -> VerifyingKey {
+        self.operator_key.verifying_key()
+    }
+
+    // Roots the gossiping clients have pooled so far
+    pub fn view(&self) -> &ViewChecker<MimiSumCommitment> {
+        &self.view
+    }
+
+    pub fn run_epoch(&mut self) -> Result<EpochReport, SimError> {
+        self.epoch += 1;
+        let epoch = self.epoch;
+        let (published_root, bundles) = self.distribute(epoch)?;
+
+        let policy = VerificationPolicy::new(self.operator_key.verifying_key());
+        let mut detections = Vec::new();
+        for (user, bundle) in bundles.into_iter().enumerate() {
+            let Some(bundle) = bundle else {
+                detections.push(Detection::MissingProof { user });
+                continue;
+            };
+            if let Some(detection) = self.check_bundle(user, &bundle, &policy) {
+                detections.push(detection);
+            }
+            if self.config.gossip {
+                if let Err(ViewError::Equivocation { epoch }) =
+                    self.view.observe(bundle.signed_root.clone())
+                {
+                    if !detections.contains(&Detection::Equivocation { epoch }) {
+                        detections.push(Detection::Equivocation { epoch });
+                    }
+                }
+            }
+        }
+
+        Ok(EpochReport {
+            epoch,
+            committed_liabilities: published_root.amount(),
+            published_root,
+            true_liabilities: self
+                .users
+                .iter()
+                .fold(0u64, |total, user| total.saturating_add(user.balance)),
+            victims: self.victims.clone(),
+            detections,
+        })
+    }
+
+    // The operator's side: the published root and each user's bundle, if it gives one
+    fn distribute(
+        &self,
+        epoch: u64,
+    ) -> Result<(Root<MimiSumCommitment>, Vec<Option<SimBundle>>), SimError> {
+        let is_victim = |user: usize| self.victims.binary_search(&user).is_ok();
+        let everyone: Vec<usize> = (0..self.users.len()).collect();
+        let spared: Vec<usize> = everyone
+            .iter()
+            .copied()
+            .filter(|&user| !is_victim(user))
+            .collect();
+
+        let (published, shadow) = match self.config.behavior {
+            Behavior::Honest => (self.build(&everyone, 0)?, None),
+            Behavior::Omission { .. } => (self.build(&spared, 0)?, None),
+            Behavior::Understatement { percent, .. } => (self.build(&everyone, percent)?, None),
+            Behavior::Equivocation { .. } => {
+                (self.build(&spared, 0)?, Some(self.build(&everyone, 0)?))
+            }
+        };
+        let published_root = published.0.commit();
+        let published_signed = SignedRoot::sign(epoch, published_root.clone(), &self.operator_key);
+        let shadow_signed = shadow.as_ref().map(|(tree, _)| {
+            SignedRoot::sign(epoch, tree.commit(), &self.operator_key)
+        });
+
+        let bundles = everyone
+            .iter()
+            .map(|&user| {
+                let ((tree, included), signed) = match (&shadow, &shadow_signed) {
+                    (Some(shadow), Some(signed)) if is_victim(user) => (shadow, signed),
+                    _ => (&published, &published_signed),
+                };
+                let index = included.binary_search(&user).ok()?;
+                let position = tree.position_of(index)?;
+                Some(ProofBundle::new(tree.prove(position), signed.clone()))
+            })
+            .collect();
+        Ok((published_root, bundles))
+    }
+
+    // Builds a tree over `included`, victims' balances cut by `percent`
+    fn build(&self, included: &[usize], percent: u8) -> Result<(SimTree, Vec<usize>), SimError> {
+        let leaves: Vec<UserLeaf<u64>> = included
+            .iter()
+            .map(|&user| {
+                let balance = self.users[user].balance;
+                let balance = match self.victims.binary_search(&user) {
+                    Ok(_) => {
+                        let cut = balance as u128 * percent.min(100) as u128 / 100;
+                        balance - cut as u64
+                    }
+                    Err(_) => balance,
+                };
+                self.users[user].leaf(balance)
+            })
+            .collect();
+        let tree = SimTree::builder().build(&leaves)?;
+        Ok((tree, included.to_vec()))
+    }
+
+    // The client's side: the bundle must verify and prove exactly the user's own leaf
+    fn check_bundle(
+        &self,
+        user: usize,
+        bundle: &SimBundle,
+        policy: &VerificationPolicy<'_>,
+    ) -> Option<Detection> {
+        if let Err(err) = bundle.verify(policy) {
+            return Some(Detection::InvalidBundle {
+                user,
+                reason: err.to_string(),
+            });
+        }
+        let account = &self.users[user];
+        let proven = bundle.proof.leaf();
+        if proven.amount() != account.balance {
+            return Some(Detection::WrongBalance {
+                user,
+                expected: account.balance,
+                proven: proven.amount(),
+            });
+        }
+        let expected = MimiLeafCommitment::from_leaf(&account.leaf(account.balance));
+        if proven.digest() != expected.digest() {
+            return Some(Detection::InvalidBundle {
+                user,
+                reason: "proof is for another account's leaf".to_string(),
+            });
+        }
+        None
+    }
+}
+
+// Define the SimError enum for simulations the operator can't carry out
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SimError {
+    Build(BuildError),
+}
+
+impl fmt::Display for SimError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SimError::Build(err) => write!(f, "operator could not build the tree: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for SimError {}
+
+impl From<BuildError> for SimError {
+    fn from(err: BuildError) -> Self {
+        SimError::Build(err)
+    }
+}
@@ -0,0 +1,267 @@
+use std::fmt;
+
+use crate::encoding::{
+    decode_commitment, encode_commitment, encode_len, encode_usize, ByteReader, COMMITMENT_LEN,
+};
+use crate::{
+    ExclusiveAllotmentProof, Leaf, LeafCommitment, MerkleProof, MimkMerkleTree, ParseError, Root,
+    SumCommitment,
+};
+
+const SNAPSHOT_MAGIC: &[u8; 8] = b"MIMISNP1";
+
+// Define the LeafPreimage struct, the account data a leaf commitment was computed from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LeafPreimage {
+    pub user_id: Option<Vec<u8>>,
+    // `encode_for_hash` of every input committed at this position (several when merged)
+    pub records: Vec<Vec<u8>>,
+}
+
+// Define the SnapshotLeaf struct. A tombstoned leaf keeps its commitment and drops the
+// preimage, so the tree (and every root published from it) stays reproducible after
+// the account data has been erased.
+#[derive(Debug, Clone)]
+pub struct SnapshotLeaf<C: SumCommitment> {
+    pub commitment: C::Leaf,
+    pub preimage: Option<LeafPreimage>,
+}
+
+// Define the Snapshot struct, the stored form of one epoch's tree
+#[derive(Debug, Clone)]
+pub struct Snapshot<C: SumCommitment> {
+    pub epoch: u64,
+    leaves: Vec<SnapshotLeaf<C>>,
+}
+
+impl<C: SumCommitment> Snapshot<C> {
+    // `leaves` are the inputs the tree was built from, so their preimages can be kept
+    pub fn capture<P, L>(epoch: u64, tree: &MimkMerkleTree<C, P>, leaves: &[L]) -> Self
+    where
+        P: ExclusiveAllotmentProof<C>,
+        L: Leaf,
+    {
+        let mut snapshot = Snapshot::from_tree(epoch, tree);
+        for (index, leaf) in leaves.iter().enumerate() {
+            let Some(position) = tree.position_of(index) else {
+                continue;
+            };
+            let preimage = snapshot.leaves[position]
+                .preimage
+                .get_or_insert_with(|| LeafPreimage {
+                    user_id: leaf.user_id().map(<[u8]>::to_vec),
+                    records: Vec::new(),
+                });
+            preimage.records.push(leaf.encode_for_hash());
+        }
+        snapshot
+    }
+
+    // Commitments only, as if every leaf were already tombstoned
+    pub fn from_tree<P: ExclusiveAllotmentProof<C>>(
+        epoch: u64,
+        tree: &MimkMerkleTree<C, P>,
+    ) -> Self {
+        Snapshot {
+            epoch,
+            leaves: tree
+                .iter_leaves()
+                .map(|commitment| SnapshotLeaf {
+                    commitment: commitment.clone(),
+                    preimage: None,
+                })
+                .collect(),
+        }
+    }
+
+    pub fn leaves(&self) -> &[SnapshotLeaf<C>] {
+        &self.leaves
+    }
+
+    pub fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+
+    pub fn preimage(&self, position: usize) -> Option<&LeafPreimage> {
+        self.leaves.get(position)?.preimage.as_ref()
+    }
+
+    pub fn is_tombstoned(&self, position: usize) -> bool {
+        matches!(self.leaves.get(position), Some(leaf) if leaf.preimage.is_none())
+    }
+
+    pub fn tombstone(&mut self, position: usize) -> Result<(), SnapshotError> {
+        let leaf = self
+            .leaves
+            .get_mut(position)
+            .ok_or(SnapshotError::UnknownPosition(position))?;
+        leaf.preimage = None;
+        Ok(())
+    }
+
+    // Erases every leaf owned by `user_id` and returns how many there were
+    pub fn tombstone_user(&mut self, user_id: impl AsRef<[u8]>) -> usize {
+        let user_id = user_id.as_ref();
+        let mut erased = 0;
+        for leaf in &mut self.leaves {
+            let owner = leaf
+                .preimage
+                .as_ref()
+                .and_then(|preimage| preimage.user_id.as_deref());
+            if owner == Some(user_id) {
+                leaf.preimage = None;
+                erased += 1;
+            }
+        }
+        erased
+    }
+
+    // Rebuilds the tree from the stored commitments, so proofs for old epochs can still
+    // be served, tombstoned leaves included
+    pub fn to_tree<P: ExclusiveAllotmentProof<C>>(
+        &self,
+    ) -> Result<MimkMerkleTree<C, P>, SnapshotError> {
+        if self.leaves.is_empty() {
+            return Err(SnapshotError::Empty);
+        }
+        self.leaves
+            .iter()
+            .try_fold(0u64, |sum, leaf| sum.checked_add(leaf.commitment.amount()))
+            .ok_or(SnapshotError::BalanceOverflow)?;
+        Ok(MimkMerkleTree::from_leaf_nodes(
+            self.leaves
+                .iter()
+                .map(|leaf| leaf.commitment.clone())
+                .collect(),
+        ))
+    }
+
+    pub fn root(&self) -> Result<Root<C>, SnapshotError> {
+        Ok(self.to_tree::<MerkleProof<C>>()?.commit())
+    }
+
+    // magic | epoch | leaf count | per leaf: commitment | flag | [user ID] | records.
+    // Byte strings are length-prefixed; the flag is 0 for a tombstone, 1 for a preimage
+    // without a user ID and 2 for one with a user ID.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(24 + self.leaves.len() * (COMMITMENT_LEN + 1));
+        bytes.extend_from_slice(SNAPSHOT_MAGIC);
+        bytes.extend_from_slice(&self.epoch.to_le_bytes());
+        bytes.extend_from_slice(&encode_usize(self.leaves.len()));
+        for leaf in &self.leaves {
+            bytes.extend_from_slice(&encode_commitment(
+                leaf.commitment.amount(),
+                &leaf.commitment.digest(),
+            ));
+            let Some(preimage) = &leaf.preimage else {
+                bytes.push(0);
+                continue;
+            };
+            match &preimage.user_id {
+                None => bytes.push(1),
+                Some(user_id) => {
+                    bytes.push(2);
+                    bytes.extend_from_slice(&encode_len(user_id));
+                    bytes.extend_from_slice(user_id);
+                }
+            }
+            bytes.extend_from_slice(&encode_usize(preimage.records.len()));
+            for record in &preimage.records {
+                bytes.extend_from_slice(&encode_len(record));
+                bytes.extend_from_slice(record);
+            }
+        }
+        bytes
+    }
+}
+
+impl<C: SumCommitment> TryFrom<&[u8]> for Snapshot<C> {
+    type Error = SnapshotError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        let mut reader = ByteReader::new(bytes);
+        if reader.take(SNAPSHOT_MAGIC.len())? != SNAPSHOT_MAGIC {
+            return Err(SnapshotError::UnknownFormat);
+        }
+        let epoch = u64::from_le_bytes(reader.take_array::<8>()?);
+        let count = read_len(&mut reader)?;
+        // Each leaf takes at least its commitment and flag, so a forged count can't
+        // reserve more than the input could hold
+        let mut leaves = Vec::with_capacity(count.min(bytes.len() / (COMMITMENT_LEN + 1)));
+        for _ in 0..count {
+            let (amount, digest) = decode_commitment(reader.take(COMMITMENT_LEN)?)?;
+            let commitment = C::Leaf::from_parts(amount, digest);
+            let preimage = match reader.take_array::<1>()?[0] {
+                0 => None,
+                flag @ (1 | 2) => {
+                    let user_id = match flag {
+                        2 => Some(read_bytes(&mut reader)?),
+                        _ => None,
+                    };
+                    let record_count = read_len(&mut reader)?;
+                    let mut records = Vec::new();
+                    for _ in 0..record_count {
+                        records.push(read_bytes(&mut reader)?);
+                    }
+                    Some(LeafPreimage { user_id, records })
+                }
+                other => return Err(SnapshotError::InvalidFlag(other)),
+            };
+            leaves.push(SnapshotLeaf {
+                commitment,
+                preimage,
+            });
+        }
+        reader.finish()?;
+        Ok(Snapshot { epoch, leaves })
+    }
+}
+
+fn read_len(reader: &mut ByteReader<'_>) -> Result<usize, ParseError> {
+    usize::try_from(u64::from_le_bytes(reader.take_array::<8>()?))
+        .map_err(|_| ParseError::PositionOverflow)
+}
+
+fn read_bytes(reader: &mut ByteReader<'_>) -> Result<Vec<u8>, ParseError> {
+    let len = read_len(reader)?;
+    Ok(reader.take(len)?.to_vec())
+}
+
+// Define the SnapshotError enum for snapshots that can't be edited, stored or loaded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnapshotError {
+    Empty,
+    UnknownPosition(usize),
+    UnknownFormat,
+    InvalidFlag(u8),
+    // The stored amounts add up past a u64
+    BalanceOverflow,
+    Parse(ParseError),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Empty => write!(f, "snapshot has no leaves"),
+            SnapshotError::UnknownPosition(position) => {
+                write!(f, "snapshot has no leaf at position {}", position)
+            }
+            SnapshotError::UnknownFormat => write!(f, "not a tree snapshot"),
+            SnapshotError::InvalidFlag(flag) => write!(f, "invalid leaf flag {:#04x}", flag),
+            SnapshotError::BalanceOverflow => write!(f, "snapshot amounts overflow a u64"),
+            SnapshotError::Parse(err) => write!(f, "malformed snapshot: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl From<ParseError> for SnapshotError {
+    fn from(err: ParseError) -> Self {
+        SnapshotError::Parse(err)
+    }
+}
@@ -0,0 +1,119 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::{Digest, ExclusiveAllotmentProof, LeafCommitment, MimkMerkleTree, SumCommitment};
+
+// Define the SummaCurrency struct, one balance column of a Summa entry file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SummaCurrency {
+    pub name: String,
+    pub chain: String,
+}
+
+// Define the SummaEntry struct, one row of the entry file Summa builds its tree from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SummaEntry {
+    pub username: String,
+    pub balances: Vec<u64>,
+}
+
+// Exports one entry per leaf, using the hex leaf digest as the username so that the
+// Summa tree links back to ours without revealing account identifiers
+pub fn entries_from_tree<C, P>(tree: &MimkMerkleTree<C, P>) -> Vec<SummaEntry>
+where
+    C: SumCommitment,
+    P: ExclusiveAllotmentProof<C>,
+{
+    tree.iter_leaves()
+        .map(|leaf| SummaEntry {
+            username: Digest::from(leaf.digest()).to_string(),
+            balances: vec![leaf.amount()],
+        })
+        .collect()
+}
+
+// Renders entries as Summa's CSV layout: `username,balance_<NAME>_<CHAIN>,...`
+pub fn to_csv(currencies: &[SummaCurrency], entries: &[SummaEntry]) -> Result<String, SummaError> {
+    if currencies.is_empty() {
+        return Err(SummaError::NoCurrencies);
+    }
+    for currency in currencies {
+        check_field(&currency.name)?;
+        check_field(&currency.chain)?;
+    }
+
+    let mut csv = String::from("username");
+    for currency in currencies {
+        csv.push_str(&format!(",balance_{}_{}", currency.name, currency.chain));
+    }
+    csv.push('\n');
+
+    for (row, entry) in entries.iter().enumerate() {
+        check_field(&entry.username)?;
+        if entry.balances.len() != currencies.len() {
+            return Err(SummaError::BalanceCount {
+                row,
+                expected: currencies.len(),
+                found: entry.balances.len(),
+            });
+        }
+        csv.push_str(&entry.username);
+        for balance in &entry.balances {
+            csv.push_str(&format!(",{}", balance));
+        }
+        csv.push('\n');
+    }
+    Ok(csv)
+}
+
+pub fn write_csv(
+    path: impl AsRef<Path>,
+    currencies: &[SummaCurrency],
+    entries: &[SummaEntry],
+) -> Result<(), SummaError> {
+    fs::write(path, to_csv(currencies, entries)?).map_err(SummaError::Io)
+}
+
+fn check_field(field: &str) -> Result<(), SummaError> {
+    if field.is_empty() || field.contains([',', '\n', '\r']) {
+        return Err(SummaError::InvalidField(field.to_string()));
+    }
+    Ok(())
+}
+
+// Define the SummaError enum for entries that can't be rendered in Summa's format
+#[derive(Debug)]
+pub enum SummaError {
+    NoCurrencies,
+    InvalidField(String),
+    BalanceCount {
+        row: usize,
+        expected: usize,
+        found: usize,
+    },
+    Io(std::io::Error),
+}
+
+impl fmt::Display for SummaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SummaError::NoCurrencies => write!(f, "at least one currency column is required"),
+            SummaError::InvalidField(field) => {
+                write!(f, "field `{}` is empty or contains a separator", field)
+            }
+            SummaError::BalanceCount {
+                row,
+                expected,
+                found,
+            } => write!(
+                f,
+                "entry {} has {} balances, expected {}",
+                row, found, expected
+            ),
+            SummaError::Io(err) => write!(f, "i/o error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for SummaError {}
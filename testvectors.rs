@@ -0,0 +1,390 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::rescue::RescueSumCommitment;
+use crate::{
+    AmountBinding, BuildError, Digest, DuplicatePolicy, HashBackend, MerkleProof, MerkleSumTreeBuilder,
+    MimiSumCommitment, MimkMerkleTree, PaddingLeaf, PaddingPolicy, ParseError, Root,
+    SaltDerivation, Shuffling, StorageBackend, SumCommitment, TreeConfig, UnboundSumCommitment,
+};
+
+type ReferenceTree<C> = MimkMerkleTree<C, MerkleProof<C>>;
+
+// Define the TestVector struct, one known-answer case: inputs, configuration and expected outputs.
+// Everything is plain JSON (hex strings for bytes) so other languages can load it without this crate.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TestVector {
+    pub name: String,
+    pub leaves: Vec<u64>,
+    pub config: VectorConfig,
+    // Root as `<amount>:<hex digest>`
+    pub root: String,
+    pub proofs: Vec<VectorProof>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VectorConfig {
+    pub hash_backend: String,
+    pub arity: usize,
+    // Absent in vectors written before unbound trees had vectors; those are all bound
+    #[serde(default = "default_amount_binding")]
+    pub amount_binding: String,
+    pub padding: String,
+    // Absent in vectors written before blinded padding existed
+    #[serde(default)]
+    pub padding_seed: Option<String>,
+    pub salt_seed: Option<String>,
+    pub shuffle_seed: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VectorProof {
+    pub position: usize,
+    // Hex of `MerkleProof::to_bytes`
+    pub proof: String,
+}
+
+impl TestVector {
+    // The tree is built with the commitment type of the configured hash backend and amount
+    // binding; Rescue only comes bound, so an unbound Rescue config fails to build
+    pub fn generate(name: &str, leaves: &[u64], config: &TreeConfig) -> Result<Self, VectorError> {
+        match (config.hash_backend, config.amount_binding) {
+            (HashBackend::Sha256, AmountBinding::Bound) => {
+                Self::generate_with::<MimiSumCommitment>(name, leaves, config)
+            }
+            (HashBackend::Sha256, AmountBinding::Unbound) => {
+                Self::generate_with::<UnboundSumCommitment>(name, leaves, config)
+            }
+            (HashBackend::RescuePrime, _) => {
+                Self::generate_with::<RescueSumCommitment>(name, leaves, config)
+            }
+        }
+    }
+
+    fn generate_with<C: SumCommitment>(
+        name: &str,
+        leaves: &[u64],
+        config: &TreeConfig,
+    ) -> Result<Self, VectorError> {
+        let tree: ReferenceTree<C> =
+            MerkleSumTreeBuilder::with_config(config.clone()).build(leaves)?;
+
+        let proofs = (0..tree.len())
+            .map(|position| VectorProof {
+                position,
+                proof: hex::encode(tree.prove(position).to_bytes()),
+            })
+            .collect();
+
+        Ok(TestVector {
+            name: name.to_string(),
+            leaves: leaves.to_vec(),
+            config: VectorConfig::from_config(config),
+            root: tree.commit().to_string(),
+            proofs,
+        })
+    }
+
+    // Rebuilds the tree from the vector's inputs and checks every output byte-for-byte
+    pub fn check(&self) -> Result<(), VectorError> {
+        let config = self.config.to_config()?;
+        match (config.hash_backend, config.amount_binding) {
+            (HashBackend::Sha256, AmountBinding::Bound) => {
+                self.check_with::<MimiSumCommitment>(config)
+            }
+            (HashBackend::Sha256, AmountBinding::Unbound) => {
+                self.check_with::<UnboundSumCommitment>(config)
+            }
+            (HashBackend::RescuePrime, _) => self.check_with::<RescueSumCommitment>(config),
+        }
+    }
+
+    fn check_with<C: SumCommitment>(&self, config: TreeConfig) -> Result<(), VectorError> {
+        let tree: ReferenceTree<C> = MerkleSumTreeBuilder::with_config(config).build(&self.leaves)?;
+
+        let expected_root: Root<C> = self.root.parse()?;
+        let root = tree.commit();
+        if !expected_root.matches(root.node()) {
+            return Err(VectorError::RootMismatch {
+                expected: self.root.clone(),
+                actual: root.to_string(),
+            });
+        }
+
+        if self.proofs.len() != tree.len() {
+            return Err(VectorError::ProofCountMismatch {
+                expected: self.proofs.len(),
+                actual: tree.len(),
+            });
+        }
+        for vector_proof in &self.proofs {
+            if vector_proof.position >= tree.len() {
+                return Err(VectorError::ProofMismatch {
+                    position: vector_proof.position,
+                });
+            }
+            let expected = hex::decode(&vector_proof.proof).map_err(|_| ParseError::InvalidHex)?;
+            if tree.prove(vector_proof.position).to_bytes() != expected {
+                return Err(VectorError::ProofMismatch {
+                    position: vector_proof.position,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+impl VectorConfig {
+    pub fn from_config(config: &TreeConfig) -> Self {
+        VectorConfig {
+            hash_backend: match config.hash_backend {
+                HashBackend::Sha256 => "sha256".to_string(),
+                HashBackend::RescuePrime => "rescue-prime".to_string(),
+            },
+            arity: config.arity,
+            amount_binding: match config.amount_binding {
+                AmountBinding::Bound => "bound".to_string(),
+                AmountBinding::Unbound => "unbound".to_string(),
+            },
+            padding: match config.padding {
+                PaddingPolicy::None => "none".to_string(),
+                PaddingPolicy::PowerOfTwo => "power-of-two".to_string(),
+            },
+            padding_seed: match &config.padding_leaf {
+                PaddingLeaf::Zero => None,
+                PaddingLeaf::Blinded(seed) => Some(hex::encode(seed)),
+            },
+            salt_seed: match &config.salt_derivation {
+                SaltDerivation::None => None,
+                SaltDerivation::FromSeed(seed) => Some(hex::encode(seed)),
+            },
+            shuffle_seed: match &config.shuffling {
+                Shuffling::None => None,
+                Shuffling::Seeded(seed) => Some(hex::encode(seed)),
+            },
+        }
+    }
+
+    pub fn to_config(&self) -> Result<TreeConfig, VectorError> {
+        let hash_backend = match self.hash_backend.as_str() {
+            "sha256" => HashBackend::Sha256,
+            "rescue-prime" => HashBackend::RescuePrime,
+            other => return Err(VectorError::UnknownOption(other.to_string())),
+        };
+        let amount_binding = match self.amount_binding.as_str() {
+            "bound" => AmountBinding::Bound,
+            "unbound" => AmountBinding::Unbound,
+            other => return Err(VectorError::UnknownOption(other.to_string())),
+        };
+        let padding = match self.padding.as_str() {
+            "none" => PaddingPolicy::None,
+            "power-of-two" => PaddingPolicy::PowerOfTwo,
+            other => return Err(VectorError::UnknownOption(other.to_string())),
+        };
+        let padding_leaf = match &self.padding_seed {
+            None => PaddingLeaf::Zero,
+            Some(seed) => PaddingLeaf::Blinded(parse_seed(seed)?),
+        };
+        let salt_derivation = match &self.salt_seed {
+            None => SaltDerivation::None,
+            Some(seed) => SaltDerivation::FromSeed(parse_seed(seed)?),
+        };
+        let shuffling = match &self.shuffle_seed {
+            None => Shuffling::None,
+            Some(seed) => Shuffling::Seeded(parse_seed(seed)?),
+        };
+        Ok(TreeConfig {
+            hash_backend,
+            arity: self.arity,
+            amount_binding,
+            padding,
+            padding_leaf,
+            salt_derivation,
+            shuffling,
+            duplicates: DuplicatePolicy::Reject,
+            storage: StorageBackend::Memory,
+        })
+    }
+}
+
+fn default_amount_binding() -> String {
+    "bound".to_string()
+}
+
+fn parse_seed(seed: &str) -> Result<[u8; 32], VectorError> {
+    Ok(*seed.parse::<Digest>()?.as_bytes())
+}
+
+pub fn to_json(vectors: &[TestVector]) -> Result<String, VectorError> {
+    Ok(serde_json::to_string_pretty(vectors)?)
+}
+
+pub fn from_json(json: &str) -> Result<Vec<TestVector>, VectorError> {
+    Ok(serde_json::from_str(json)?)
+}
+
+pub fn save(path: impl AsRef<Path>, vectors: &[TestVector]) -> Result<(), VectorError> {
+    fs::write(path, to_json(vectors)?)?;
+    Ok(())
+}
+
+pub fn load(path: impl AsRef<Path>) -> Result<Vec<TestVector>, VectorError> {
+    from_json(&fs::read_to_string(path)?)
+}
+
+// A small fixed set of cases covering each configuration option
+pub fn reference_vectors() -> Result<Vec<TestVector>, VectorError> {
+    let leaves = [100, 200, 300, 400, 500];
+    let seed = [0x42; 32];
+    let configs = [
+        ("default", TreeConfig::default()),
+        (
+            "padded",
+            TreeConfig {
+                padding: PaddingPolicy::PowerOfTwo,
+                ..TreeConfig::default()
+            },
+        ),
+        (
+            "blinded-padding",
+            TreeConfig {
+                padding: PaddingPolicy::PowerOfTwo,
+                padding_leaf: PaddingLeaf::Blinded(seed),
+                ..TreeConfig::default()
+            },
+        ),
+        (
+            "salted-shuffled",
+            TreeConfig {
+                salt_derivation: SaltDerivation::FromSeed(seed),
+                shuffling: Shuffling::Seeded(seed),
+                ..TreeConfig::default()
+            },
+        ),
+        (
+            "unbound",
+            TreeConfig {
+                amount_binding: AmountBinding::Unbound,
+                ..TreeConfig::default()
+            },
+        ),
+        (
+            "rescue-prime",
+            TreeConfig {
+                hash_backend: HashBackend::RescuePrime,
+                ..TreeConfig::default()
+            },
+        ),
+    ];
+    configs
+        .iter()
+        .map(|(name, config)| TestVector::generate(name, &leaves, config))
+        .collect()
+}
+
+// Define the VectorError enum for vectors that can't be read or don't match this implementation
+#[derive(Debug)]
+pub enum VectorError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    Build(BuildError),
+    Parse(ParseError),
+    UnknownOption(String),
+    RootMismatch { expected: String, actual: String },
+    ProofCountMismatch { expected: usize, actual: usize },
+    ProofMismatch { position: usize },
+}
+
+impl fmt::Display for VectorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VectorError::Io(err) => write!(f, "i/o error: {}", err),
+            VectorError::Json(err) => write!(f, "invalid vector json: {}", err),
+            VectorError::Build(err) => write!(f, "cannot build tree: {}", err),
+            VectorError::Parse(err) => write!(f, "invalid vector field: {}", err),
+            VectorError::UnknownOption(option) => write!(f, "unknown config option `{}`", option),
+            VectorError::RootMismatch { expected, actual } => {
+                write!(f, "expected root {}, computed {}", expected, actual)
+            }
+            VectorError::ProofCountMismatch { expected, actual } => {
+                write!(
+                    f,
+                    "expected {} proofs, tree has {} leaves",
+                    expected, actual
+                )
+            }
+            VectorError::ProofMismatch { position } => {
+                write!(f, "proof for position {} does not match", position)
+            }
+        }
+    }
+}
+
+impl std::error::Error for VectorError {}
+
+impl From<std::io::Error> for VectorError {
+    fn from(err: std::io::Error) -> Self {
+        VectorError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for VectorError {
+    fn from(err: serde_json::Error) -> Self {
+        VectorError::Json(err)
+    }
+}
+
+impl From<BuildError> for VectorError {
+    fn from(err: BuildError) -> Self {
+        VectorError::Build(err)
+    }
+}
+
+impl From<ParseError> for VectorError {
+    fn from(err: ParseError) -> Self {
+        VectorError::Parse(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reference_vectors_check_under_their_own_backend() {
+        let vectors = from_json(&to_json(&reference_vectors().unwrap()).unwrap()).unwrap();
+        for vector in &vectors {
+            vector.check().unwrap();
+        }
+        let rescue = vectors
+            .iter()
+            .find(|vector| vector.name == "rescue-prime")
+            .unwrap();
+        assert_eq!(rescue.config.hash_backend, "rescue-prime");
+        assert_ne!(rescue.root, vectors[0].root);
+    }
+
+    #[test]
+    fn amount_binding_is_carried_and_defaults_to_bound() {
+        let vectors = reference_vectors().unwrap();
+        let unbound = vectors
+            .iter()
+            .find(|vector| vector.name == "unbound")
+            .unwrap();
+        assert_eq!(unbound.config.amount_binding, "unbound");
+        assert_eq!(
+            unbound.config.to_config().unwrap().amount_binding,
+            AmountBinding::Unbound
+        );
+        assert_ne!(unbound.root, vectors[0].root);
+
+        let mut json = serde_json::to_value(&vectors[0]).unwrap();
+        json["config"].as_object_mut().unwrap().remove("amount_binding");
+        let legacy: TestVector = serde_json::from_value(json).unwrap();
+        assert_eq!(legacy.config.amount_binding, "bound");
+        legacy.check().unwrap();
+    }
+}
@@ -0,0 +1,226 @@
+use std::fmt;
+use std::str::FromStr;
+
+use ed25519_dalek::{Signature, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
+
+use crate::bundle::{signing_payload, BundleError, ProofBundle, VerificationPolicy};
+use crate::encoding::encode_len;
+use crate::entropy::EntropySource;
+use crate::server::{ProofRequest, ProofResponse, ProofServer, ServerError};
+use crate::signer::{Signer, SignerError};
+use crate::{MerkleProof, ParseError, SumCommitment};
+
+const TRANSCRIPT_DOMAIN: &[u8] = b"mimi-challenge-transcript-v1";
+
+// Define the Challenge struct, the fresh nonce a user sends with a proof request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Challenge {
+    pub nonce: [u8; 32],
+}
+
+impl Challenge {
+    pub fn new(entropy: &mut impl EntropySource) -> Self {
+        Challenge {
+            nonce: entropy.seed(),
+        }
+    }
+}
+
+impl fmt::Display for Challenge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", hex::encode(self.nonce))
+    }
+}
+
+impl FromStr for Challenge {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = hex::decode(s).map_err(|_| ParseError::InvalidHex)?;
+        let found = bytes.len();
+        let nonce = bytes.try_into().map_err(|_| ParseError::InvalidLength {
+            expected: 32,
+            found,
+        })?;
+        Ok(Challenge { nonce })
+    }
+}
+
+// Define the ChallengeRequest struct, a proof request carrying the user's nonce
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChallengeRequest {
+    #[serde(flatten)]
+    pub request: ProofRequest,
+    // Hex of the 32-byte nonce
+    pub nonce: String,
+}
+
+// Define the ChallengeResponse struct, a proof bundle bound to the user's nonce and to
+// the epoch the server says is current, all under the operator's signature. A replayed
+// bundle from an earlier epoch then either fails the nonce or is a signed false claim
+// about the current epoch.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChallengeResponse {
+    pub nonce: String,
+    pub current_epoch: u64,
+    pub bundle: ProofResponse,
+    // Hex Ed25519 signature over the transcript payload
+    pub signature: String,
+}
+
+impl ChallengeResponse {
+    pub fn sign<C: SumCommitment>(
+        challenge: &Challenge,
+        current_epoch: u64,
+        bundle: &ProofBundle<C, MerkleProof<C>>,
+        signer: &dyn Signer,
+    ) -> Result<Self, TranscriptError> {
+        let signature = signer.sign(&transcript_payload(challenge, current_epoch, bundle))?;
+        Ok(ChallengeResponse {
+            nonce: challenge.to_string(),
+            current_epoch,
+            bundle: ProofResponse::from_bundle(bundle),
+            signature: hex::encode(signature.to_bytes()),
+        })
+    }
+
+    // `known_epoch` is the newest epoch the user has already seen, if any. Returns the
+    // verified bundle.
+    pub fn verify<C: SumCommitment>(
+        &self,
+        challenge: &Challenge,
+        policy: &VerificationPolicy<'_>,
+        known_epoch: Option<u64>,
+    ) -> Result<ProofBundle<C, MerkleProof<C>>, TranscriptError> {
+        if self.nonce.parse::<Challenge>()? != *challenge {
+            return Err(TranscriptError::NonceMismatch);
+        }
+        let bundle = self.bundle.to_bundle::<C>()?;
+        if bundle.signed_root.epoch != self.current_epoch {
+            return Err(TranscriptError::Stale {
+                current: self.current_epoch,
+                bundle: bundle.signed_root.epoch,
+            });
+        }
+        if let Some(known) = known_epoch.filter(|known| self.current_epoch < *known) {
+            return Err(TranscriptError::Rollback {
+                known,
+                current: self.current_epoch,
+            });
+        }
+        verify_signature(
+            &policy.operator_key,
+            &transcript_payload(challenge, self.current_epoch, &bundle),
+            &self.signature,
+        )?;
+        bundle.verify(policy)?;
+        Ok(bundle)
+    }
+}
+
+// domain | nonce | current epoch | len | signed-root payload | SHA-256(proof bytes)
+fn transcript_payload<C: SumCommitment>(
+    challenge: &Challenge,
+    current_epoch: u64,
+    bundle: &ProofBundle<C, MerkleProof<C>>,
+) -> Vec<u8> {
+    let root_payload = signing_payload(bundle.signed_root.epoch, &bundle.signed_root.root);
+    let mut payload = TRANSCRIPT_DOMAIN.to_vec();
+    payload.extend_from_slice(&challenge.nonce);
+    payload.extend_from_slice(&current_epoch.to_le_bytes());
+    payload.extend_from_slice(&encode_len(&root_payload));
+    payload.extend_from_slice(&root_payload);
+    payload.extend_from_slice(&Sha256::digest(bundle.proof.to_bytes()));
+    payload
+}
+
+fn verify_signature(
+    operator_key: &VerifyingKey,
+    payload: &[u8],
+    signature: &str,
+) -> Result<(), TranscriptError> {
+    let signature: [u8; 64] = hex::decode(signature)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or(TranscriptError::BadSignature)?;
+    operator_key
+        .verify_strict(payload, &Signature::from_bytes(&signature))
+        .map_err(|_| TranscriptError::BadSignature)
+}
+
+impl<C: SumCommitment> ProofServer<C> {
+    // Answers like `proof` and binds the bundle to the request's nonce and the latest
+    // served epoch, signed with the operator key
+    pub fn challenge_proof(
+        &self,
+        request: &ChallengeRequest,
+        signer: &dyn Signer,
+    ) -> Result<ChallengeResponse, ServerError> {
+        let challenge: Challenge = request
+            .nonce
+            .parse()
+            .map_err(|err: ParseError| ServerError::BadRequest(err.to_string()))?;
+        let bundle = self.bundle(&request.request)?;
+        let current_epoch = self.current().ok_or(ServerError::NoEpoch)?.epoch();
+        ChallengeResponse::sign(&challenge, current_epoch, &bundle, signer)
+            .map_err(|err| ServerError::Signing(err.to_string()))
+    }
+}
+
+// Define the TranscriptError enum for challenge responses that aren't fresh or authentic
+#[derive(Debug)]
+pub enum TranscriptError {
+    NonceMismatch,
+    Stale { current: u64, bundle: u64 },
+    Rollback { known: u64, current: u64 },
+    BadSignature,
+    Signer(SignerError),
+    Parse(ParseError),
+    Bundle(BundleError),
+}
+
+impl fmt::Display for TranscriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TranscriptError::NonceMismatch => write!(f, "response is not for this challenge"),
+            TranscriptError::Stale { current, bundle } => write!(
+                f,
+                "bundle is for epoch {} but the current epoch is {}",
+                bundle, current
+            ),
+            TranscriptError::Rollback { known, current } => write!(
+                f,
+                "server claims epoch {} is current after epoch {} was seen",
+                current, known
+            ),
+            TranscriptError::BadSignature => {
+                write!(f, "transcript is not signed by the operator key")
+            }
+            TranscriptError::Signer(err) => write!(f, "{}", err),
+            TranscriptError::Parse(err) => write!(f, "malformed challenge response: {}", err),
+            TranscriptError::Bundle(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for TranscriptError {}
+
+impl From<ParseError> for TranscriptError {
+    fn from(err: ParseError) -> Self {
+        TranscriptError::Parse(err)
+    }
+}
+
+impl From<BundleError> for TranscriptError {
+    fn from(err: BundleError) -> Self {
+        TranscriptError::Bundle(err)
+    }
+}
+
+impl From<SignerError> for TranscriptError {
+    fn from(err: SignerError) -> Self {
+        TranscriptError::Signer(err)
+    }
+}
@@ -0,0 +1,334 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use ed25519_dalek::VerifyingKey;
+use serde::{Deserialize, Serialize};
+
+use crate::client::{Client, ProofSource};
+use crate::keys::{KeyError, KeyPeriod, KeyRing};
+use crate::root_log::LogEntry;
+use crate::witness::WitnessPolicy;
+use crate::{Leaf, ParseError, SumCommitment};
+
+const TRUST_STORE_VERSION: u32 = 1;
+
+// Define the TrustedOperator struct, one operator attestation key and the epochs it signs
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrustedOperator {
+    pub name: String,
+    // Hex Ed25519 public key
+    pub key: String,
+    pub from_epoch: u64,
+    // Inclusive; absent for the active key
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until_epoch: Option<u64>,
+}
+
+// Define the TrustedWitness struct, a third party whose countersignatures count
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrustedWitness {
+    pub name: String,
+    pub key: String,
+}
+
+// Define the PinnedRoot struct, a root-log entry the verifier has accepted, so later
+// roots must extend it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PinnedRoot {
+    pub epoch: u64,
+    // Root as `<amount>:<hex digest>`
+    pub root: String,
+    // Hex hash of the log entry before it
+    pub previous: String,
+}
+
+impl PinnedRoot {
+    pub fn to_log_entry<C: SumCommitment>(&self) -> Result<LogEntry<C>, TrustError> {
+        Ok(LogEntry {
+            epoch: self.epoch,
+            root: self.root.parse()?,
+            previous: self.previous.parse()?,
+        })
+    }
+}
+
+// Define the TrustStore struct, everything a verifier is configured to trust, kept in one
+// JSON file so the configuration can be copied between machines and reviewed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrustStore {
+    pub version: u32,
+    pub operators: Vec<TrustedOperator>,
+    #[serde(default)]
+    pub witnesses: Vec<TrustedWitness>,
+    // Witness countersignatures required per root; 0 turns witnessing off
+    #[serde(default)]
+    pub witness_threshold: usize,
+    // Ascending by epoch
+    #[serde(default)]
+    pub pinned_roots: Vec<PinnedRoot>,
+}
+
+impl TrustStore {
+    pub fn new(name: impl Into<String>, key: &VerifyingKey, from_epoch: u64) -> Self {
+        TrustStore {
+            version: TRUST_STORE_VERSION,
+            operators: vec![TrustedOperator {
+                name: name.into(),
+                key: hex::encode(key.as_bytes()),
+                from_epoch,
+                until_epoch: None,
+            }],
+            witnesses: Vec::new(),
+            witness_threshold: 0,
+            pinned_roots: Vec::new(),
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, TrustError> {
+        serde_json::to_string_pretty(self).map_err(TrustError::Json)
+    }
+
+    // Rejects stores that don't describe a usable key ring or witness policy
+    pub fn from_json(json: &str) -> Result<Self, TrustError> {
+        let store: TrustStore = serde_json::from_str(json).map_err(TrustError::Json)?;
+        if store.version != TRUST_STORE_VERSION {
+            return Err(TrustError::UnsupportedVersion(store.version));
+        }
+        store.key_ring()?;
+        store.witness_policy()?;
+        Ok(store)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), TrustError> {
+        fs::write(path, self.to_json()?).map_err(TrustError::Io)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, TrustError> {
+        Self::from_json(&fs::read_to_string(path).map_err(TrustError::Io)?)
+    }
+
+    pub fn key_ring(&self) -> Result<KeyRing, TrustError> {
+        let mut operators: Vec<&TrustedOperator> = self.operators.iter().collect();
+        operators.sort_by_key(|operator| operator.from_epoch);
+        let periods = operators
+            .into_iter()
+            .map(|operator| {
+                Ok(KeyPeriod {
+                    key: parse_key(&operator.key)?,
+                    from_epoch: operator.from_epoch,
+                    until_epoch: operator.until_epoch,
+                })
+            })
+            .collect::<Result<Vec<_>, TrustError>>()?;
+        Ok(KeyRing::from_periods(periods)?)
+    }
+
+    pub fn active_key(&self) -> Result<VerifyingKey, TrustError> {
+        Ok(self.key_ring()?.active().key)
+    }
+
+    // The newest key valid for `epoch`, which during a handover overlap is the incoming one
+    pub fn operator_key_for(&self, epoch: u64) -> Result<VerifyingKey, TrustError> {
+        let ring = self.key_ring()?;
+        let key = ring.keys_for(epoch).last().copied();
+        key.ok_or(TrustError::Keys(KeyError::NoKeyForEpoch(epoch)))
+    }
+
+    pub fn witness_policy(&self) -> Result<WitnessPolicy, TrustError> {
+        let keys = self
+            .witnesses
+            .iter()
+            .map(|witness| parse_key(&witness.key))
+            .collect::<Result<Vec<_>, _>>()?;
+        if self.witness_threshold > keys.len() {
+            return Err(TrustError::WitnessThreshold {
+                threshold: self.witness_threshold,
+                witnesses: keys.len(),
+            });
+        }
+        Ok(WitnessPolicy::new(keys, self.witness_threshold))
+    }
+
+    pub fn latest_pin(&self) -> Option<&PinnedRoot> {
+        self.pinned_roots.last()
+    }
+
+    pub fn pinned(&self, epoch: u64) -> Option<&PinnedRoot> {
+        self.pinned_roots
+            .binary_search_by_key(&epoch, |pin| pin.epoch)
+            .ok()
+            .map(|index| &self.pinned_roots[index])
+    }
+
+    // Rotates to a new operator key; see `KeyRing::rotate` for `overlap`
+    pub fn rotate_operator(
+        &mut self,
+        name: impl Into<String>,
+        key: &VerifyingKey,
+        from_epoch: u64,
+        overlap: u64,
+    ) -> Result<(), TrustError> {
+        let mut ring = self.key_ring()?;
+        ring.rotate(*key, from_epoch, overlap)?;
+        let until = ring.periods()[ring.periods().len() - 2].until_epoch;
+        let active = self
+            .operators
+            .iter_mut()
+            .find(|operator| operator.until_epoch.is_none())
+            .expect("a valid key ring has an active key");
+        active.until_epoch = until;
+        self.operators.push(TrustedOperator {
+            name: name.into(),
+            key: hex::encode(key.as_bytes()),
+            from_epoch,
+            until_epoch: None,
+        });
+        Ok(())
+    }
+
+    pub fn add_witness(&mut self, name: impl Into<String>, key: &VerifyingKey) {
+        let key = hex::encode(key.as_bytes());
+        if !self.witnesses.iter().any(|witness| witness.key == key) {
+            self.witnesses.push(TrustedWitness {
+                name: name.into(),
+                key,
+            });
+        }
+    }
+
+    // Pins an accepted log entry. Pinning another root for an already pinned epoch is a
+    // conflict, not an update.
+    pub fn pin<C: SumCommitment>(&mut self, entry: &LogEntry<C>) -> Result<(), TrustError> {
+        self.insert_pin(PinnedRoot {
+            epoch: entry.epoch,
+            root: entry.root.to_string(),
+            previous: entry.previous.to_string(),
+        })
+    }
+
+    // Takes the union of both stores; the same epoch pinned to different roots, or the
+    // same key trusted for different periods, is a conflict
+    pub fn merge(&mut self, other: &TrustStore) -> Result<(), TrustError> {
+        let mut merged = self.clone();
+        for operator in &other.operators {
+            match merged.operators.iter().find(|known| known.key == operator.key) {
+                Some(known) if known.from_epoch != operator.from_epoch
+                    || known.until_epoch != operator.until_epoch =>
+                {
+                    return Err(TrustError::Conflict(format!(
+                        "operator key {} has two validity periods",
+                        operator.key
+                    )));
+                }
+                Some(_) => {}
+                None => merged.operators.push(operator.clone()),
+            }
+        }
+        merged.key_ring()?;
+        for witness in &other.witnesses {
+            if !merged.witnesses.iter().any(|known| known.key == witness.key) {
+                merged.witnesses.push(witness.clone());
+            }
+        }
+        merged.witness_threshold = merged.witness_threshold.max(other.witness_threshold);
+        for pin in &other.pinned_roots {
+            merged.insert_pin(pin.clone())?;
+        }
+        *self = merged;
+        Ok(())
+    }
+
+    fn insert_pin(&mut self, pin: PinnedRoot) -> Result<(), TrustError> {
+        match self
+            .pinned_roots
+            .binary_search_by_key(&pin.epoch, |known| known.epoch)
+        {
+            Ok(index) if self.pinned_roots[index] == pin => Ok(()),
+            Ok(_) => Err(TrustError::Conflict(format!(
+                "epoch {} is already pinned to another root",
+                pin.epoch
+            ))),
+            Err(index) => {
+                self.pinned_roots.insert(index, pin);
+                Ok(())
+            }
+        }
+    }
+}
+
+impl<C: SumCommitment, S: ProofSource<C>> Client<C, S> {
+    // Trusts the store's active operator key and resumes from its latest pin
+    pub fn from_trust_store<L: Leaf>(
+        source: S,
+        store: &TrustStore,
+        user_id: impl Into<String>,
+        record: &L,
+        salt: Option<&[u8; 32]>,
+    ) -> Result<Self, TrustError> {
+        let client = Client::new(source, store.active_key()?, user_id, record, salt);
+        Ok(match store.latest_pin() {
+            Some(pin) => client.with_pinned(pin.to_log_entry()?),
+            None => client,
+        })
+    }
+}
+
+fn parse_key(encoded: &str) -> Result<VerifyingKey, TrustError> {
+    let bytes: [u8; 32] = hex::decode(encoded)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| TrustError::InvalidKey(encoded.to_string()))?;
+    VerifyingKey::from_bytes(&bytes).map_err(|_| TrustError::InvalidKey(encoded.to_string()))
+}
+
+// Define the TrustError enum for trust stores that can't be loaded, used or combined
+#[derive(Debug)]
+pub enum TrustError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    UnsupportedVersion(u32),
+    InvalidKey(String),
+    WitnessThreshold { threshold: usize, witnesses: usize },
+    Conflict(String),
+    Keys(KeyError),
+    Parse(ParseError),
+}
+
+impl fmt::Display for TrustError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrustError::Io(err) => write!(f, "i/o error: {}", err),
+            TrustError::Json(err) => write!(f, "json error: {}", err),
+            TrustError::UnsupportedVersion(version) => {
+                write!(f, "unsupported trust store version {}", version)
+            }
+            TrustError::InvalidKey(key) => write!(f, "invalid Ed25519 public key {}", key),
+            TrustError::WitnessThreshold {
+                threshold,
+                witnesses,
+            } => write!(
+                f,
+                "witness threshold {} exceeds the {} trusted witnesses",
+                threshold, witnesses
+            ),
+            TrustError::Conflict(reason) => write!(f, "conflicting trust stores: {}", reason),
+            TrustError::Keys(err) => write!(f, "{}", err),
+            TrustError::Parse(err) => write!(f, "malformed pinned root: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for TrustError {}
+
+impl From<KeyError> for TrustError {
+    fn from(err: KeyError) -> Self {
+        TrustError::Keys(err)
+    }
+}
+
+impl From<ParseError> for TrustError {
+    fn from(err: ParseError) -> Self {
+        TrustError::Parse(err)
+    }
+}
@@ -0,0 +1,278 @@
+use std::fmt;
+
+use sha2::{Digest as _, Sha256};
+
+// DER object identifiers, content bytes only
+const OID_SHA256: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01];
+const OID_SIGNED_DATA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x07, 0x02];
+const OID_TST_INFO: &[u8] = &[
+    0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x10, 0x01, 0x04,
+];
+
+const TAG_BOOLEAN: u8 = 0x01;
+const TAG_INTEGER: u8 = 0x02;
+const TAG_OCTET_STRING: u8 = 0x04;
+const TAG_NULL: u8 = 0x05;
+const TAG_OID: u8 = 0x06;
+const TAG_GENERALIZED_TIME: u8 = 0x18;
+const TAG_SEQUENCE: u8 = 0x30;
+const TAG_SET: u8 = 0x31;
+const TAG_CONTEXT_0: u8 = 0xa0;
+
+// Define the TsaClient trait, the transport to a timestamp authority
+// (an HTTP POST of `application/timestamp-query` in practice)
+pub trait TsaClient {
+    fn request(&self, timestamp_query: &[u8]) -> Result<Vec<u8>, TsaError>;
+}
+
+// Define the TsaVerifier trait, which checks the CMS signature and certificate chain of
+// a token against the TSA certificates the deployment trusts
+pub trait TsaVerifier {
+    fn verify_token(&self, token: &[u8]) -> Result<(), TsaError>;
+}
+
+// Define the TstInfo struct, the fields of a token this crate checks itself
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TstInfo {
+    pub hashed_message: Vec<u8>,
+    pub serial_number: Vec<u8>,
+    // GeneralizedTime as sent by the TSA, e.g. `20240101120000Z`
+    pub gen_time: String,
+    pub nonce: Option<Vec<u8>>,
+}
+
+// Define the TimeStampToken struct, a DER-encoded RFC 3161 token and its parsed TSTInfo
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimeStampToken {
+    der: Vec<u8>,
+    info: TstInfo,
+}
+
+impl TimeStampToken {
+    pub fn request(client: &dyn TsaClient, data: &[u8], nonce: u64) -> Result<Self, TsaError> {
+        let response = client.request(&timestamp_query(data, nonce))?;
+        let token = parse_response(&response)?;
+        if token.info.nonce.as_deref() != Some(encode_unsigned(nonce).as_slice()) {
+            return Err(TsaError::NonceMismatch);
+        }
+        Ok(token)
+    }
+
+    pub fn from_der(der: &[u8]) -> Result<Self, TsaError> {
+        let info = parse_token(der)?;
+        Ok(TimeStampToken {
+            der: der.to_vec(),
+            info,
+        })
+    }
+
+    pub fn der(&self) -> &[u8] {
+        &self.der
+    }
+
+    pub fn info(&self) -> &TstInfo {
+        &self.info
+    }
+
+    // Checks the token covers `data`, then hands the CMS signature to the TSA verifier
+    pub fn verify(&self, data: &[u8], tsa: &dyn TsaVerifier) -> Result<(), TsaError> {
+        if self.info.hashed_message != Sha256::digest(data).as_slice() {
+            return Err(TsaError::ImprintMismatch);
+        }
+        tsa.verify_token(&self.der)
+    }
+}
+
+// TimeStampReq for the SHA-256 of `data`, asking the TSA to include its certificate
+pub fn timestamp_query(data: &[u8], nonce: u64) -> Vec<u8> {
+    let mut request = encode(TAG_INTEGER, &[0x01]);
+    request.extend(message_imprint(&Sha256::digest(data)));
+    request.extend(encode(TAG_INTEGER, &encode_unsigned(nonce)));
+    request.extend(encode(TAG_BOOLEAN, &[0xff]));
+    encode(TAG_SEQUENCE, &request)
+}
+
+fn message_imprint(hashed_message: &[u8]) -> Vec<u8> {
+    let mut algorithm = encode(TAG_OID, OID_SHA256);
+    algorithm.extend(encode(TAG_NULL, &[]));
+    let mut imprint = encode(TAG_SEQUENCE, &algorithm);
+    imprint.extend(encode(TAG_OCTET_STRING, hashed_message));
+    encode(TAG_SEQUENCE, &imprint)
+}
+
+// TimeStampResp: a granted status (0) or granted with modifications (1) plus the token
+pub fn parse_response(response: &[u8]) -> Result<TimeStampToken, TsaError> {
+    let (body, rest) = expect(response, TAG_SEQUENCE)?;
+    ensure_empty(rest)?;
+    let (status_info, token) = expect(body, TAG_SEQUENCE)?;
+    let (status, _) = expect(status_info, TAG_INTEGER)?;
+    match status {
+        [0] | [1] => {}
+        _ => return Err(TsaError::Rejected(status.to_vec())),
+    }
+    let (_, _, after) = read_tlv(token)?;
+    ensure_empty(after)?;
+    TimeStampToken::from_der(token)
+}
+
+// ContentInfo -> SignedData -> encapContentInfo -> TSTInfo
+fn parse_token(der: &[u8]) -> Result<TstInfo, TsaError> {
+    let (content_info, rest) = expect(der, TAG_SEQUENCE)?;
+    ensure_empty(rest)?;
+    let (content_type, rest) = expect(content_info, TAG_OID)?;
+    if content_type != OID_SIGNED_DATA {
+        return Err(TsaError::Malformed("token is not CMS signed data"));
+    }
+    let (explicit, _) = expect(rest, TAG_CONTEXT_0)?;
+    let (signed_data, _) = expect(explicit, TAG_SEQUENCE)?;
+    let (_, rest) = expect(signed_data, TAG_INTEGER)?;
+    let (_, rest) = expect(rest, TAG_SET)?;
+    let (encap, _) = expect(rest, TAG_SEQUENCE)?;
+    let (econtent_type, rest) = expect(encap, TAG_OID)?;
+    if econtent_type != OID_TST_INFO {
+        return Err(TsaError::Malformed("signed content is not TSTInfo"));
+    }
+    let (explicit, _) = expect(rest, TAG_CONTEXT_0)?;
+    let (tst_info, _) = expect(explicit, TAG_OCTET_STRING)?;
+    parse_tst_info(tst_info)
+}
+
+fn parse_tst_info(der: &[u8]) -> Result<TstInfo, TsaError> {
+    let (body, rest) = expect(der, TAG_SEQUENCE)?;
+    ensure_empty(rest)?;
+    let (_, rest) = expect(body, TAG_INTEGER)?;
+    let (_, rest) = expect(rest, TAG_OID)?;
+    let (imprint, rest) = expect(rest, TAG_SEQUENCE)?;
+    let (algorithm, hashed) = expect(imprint, TAG_SEQUENCE)?;
+    let (algorithm_oid, _) = expect(algorithm, TAG_OID)?;
+    if algorithm_oid != OID_SHA256 {
+        return Err(TsaError::Malformed("message imprint is not sha256"));
+    }
+    let (hashed_message, _) = expect(hashed, TAG_OCTET_STRING)?;
+    let (serial_number, rest) = expect(rest, TAG_INTEGER)?;
+    let (gen_time, mut rest) = expect(rest, TAG_GENERALIZED_TIME)?;
+    let gen_time = std::str::from_utf8(gen_time)
+        .map_err(|_| TsaError::Malformed("genTime is not ascii"))?
+        .to_string();
+
+    // Skip accuracy and ordering to reach the optional nonce
+    let mut nonce = None;
+    while !rest.is_empty() {
+        let (tag, value, after) = read_tlv(rest)?;
+        if tag == TAG_INTEGER {
+            nonce = Some(value.to_vec());
+            break;
+        }
+        rest = after;
+    }
+
+    Ok(TstInfo {
+        hashed_message: hashed_message.to_vec(),
+        serial_number: serial_number.to_vec(),
+        gen_time,
+        nonce,
+    })
+}
+
+fn encode(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag];
+    let len = content.len();
+    if len < 0x80 {
+        out.push(len as u8);
+    } else {
+        let bytes = (len as u32).to_be_bytes();
+        let skip = bytes.iter().take_while(|byte| **byte == 0).count();
+        out.push(0x80 | (4 - skip) as u8);
+        out.extend_from_slice(&bytes[skip..]);
+    }
+    out.extend_from_slice(content);
+    out
+}
+
+// Minimal two's-complement encoding of a non-negative integer
+fn encode_unsigned(value: u64) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    let skip = bytes.iter().take_while(|byte| **byte == 0).count().min(7);
+    let mut out = bytes[skip..].to_vec();
+    if out[0] & 0x80 != 0 {
+        out.insert(0, 0);
+    }
+    out
+}
+
+fn read_tlv(bytes: &[u8]) -> Result<(u8, &[u8], &[u8]), TsaError> {
+    let truncated = TsaError::Malformed("truncated DER");
+    let (&tag, rest) = bytes.split_first().ok_or(truncated.clone())?;
+    let (&first, rest) = rest.split_first().ok_or(truncated.clone())?;
+    let (len, rest) = if first < 0x80 {
+        (first as usize, rest)
+    } else {
+        let count = (first & 0x7f) as usize;
+        if count == 0 || count > 4 || rest.len() < count {
+            return Err(TsaError::Malformed("unsupported DER length"));
+        }
+        let len = rest[..count]
+            .iter()
+            .fold(0usize, |len, byte| (len << 8) | *byte as usize);
+        (len, &rest[count..])
+    };
+    if rest.len() < len {
+        return Err(truncated);
+    }
+    Ok((tag, &rest[..len], &rest[len..]))
+}
+
+fn expect(bytes: &[u8], tag: u8) -> Result<(&[u8], &[u8]), TsaError> {
+    let (found, value, rest) = read_tlv(bytes)?;
+    if found != tag {
+        return Err(TsaError::UnexpectedTag {
+            expected: tag,
+            found,
+        });
+    }
+    Ok((value, rest))
+}
+
+fn ensure_empty(rest: &[u8]) -> Result<(), TsaError> {
+    if rest.is_empty() {
+        Ok(())
+    } else {
+        Err(TsaError::Malformed("trailing bytes after DER structure"))
+    }
+}
+
+// Define the TsaError enum for timestamp requests and tokens that fail
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TsaError {
+    Transport(String),
+    Rejected(Vec<u8>),
+    Malformed(&'static str),
+    UnexpectedTag { expected: u8, found: u8 },
+    NonceMismatch,
+    ImprintMismatch,
+    NoVerifier,
+    Untrusted(String),
+}
+
+impl fmt::Display for TsaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TsaError::Transport(message) => write!(f, "tsa request failed: {}", message),
+            TsaError::Rejected(status) => {
+                write!(f, "tsa rejected the request with status {:?}", status)
+            }
+            TsaError::Malformed(reason) => write!(f, "malformed timestamp: {}", reason),
+            TsaError::UnexpectedTag { expected, found } => write!(
+                f,
+                "expected DER tag {:#04x}, found {:#04x}",
+                expected, found
+            ),
+            TsaError::NonceMismatch => write!(f, "token nonce does not match the request"),
+            TsaError::ImprintMismatch => write!(f, "token does not cover the root signature"),
+            TsaError::NoVerifier => write!(f, "no tsa verifier configured"),
+            TsaError::Untrusted(reason) => write!(f, "tsa signature not trusted: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for TsaError {}
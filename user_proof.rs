@@ -0,0 +1,72 @@
+use std::marker::PhantomData;
+
+use crate::{
+    ExclusiveAllotmentProof, LeafCommitment, MimkMerkleTree, Root, SumCommitment, VerifyError,
+};
+
+// Define the UserProof struct, inclusion proofs for every leaf one user owns
+#[derive(Debug)]
+pub struct UserProof<C: SumCommitment, P: ExclusiveAllotmentProof<C>> {
+    proofs: Vec<P>,
+    _commitment: PhantomData<C>,
+}
+
+impl<C, P> UserProof<C, P>
+where
+    C: SumCommitment,
+    P: ExclusiveAllotmentProof<C>,
+{
+    pub fn new(proofs: Vec<P>) -> Self {
+        UserProof {
+            proofs,
+            _commitment: PhantomData,
+        }
+    }
+
+    pub fn proofs(&self) -> &[P] {
+        &self.proofs
+    }
+
+    pub fn len(&self) -> usize {
+        self.proofs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.proofs.is_empty()
+    }
+
+    // Checks every leaf against the root and returns the user's total. A position may
+    // only appear once, so no leaf is counted twice towards the total.
+    pub fn verify(&self, root: &Root<C>) -> Result<u64, VerifyError> {
+        let mut positions: Vec<usize> = Vec::with_capacity(self.proofs.len());
+        let mut total = 0u64;
+        for proof in &self.proofs {
+            if positions.contains(&proof.position()) {
+                return Err(VerifyError::DuplicatePosition(proof.position()));
+            }
+            positions.push(proof.position());
+            proof.check(root)?;
+            total = total
+                .checked_add(proof.leaf().amount())
+                .ok_or(VerifyError::SumOverflow)?;
+        }
+        Ok(total)
+    }
+}
+
+impl<C, P> MimkMerkleTree<C, P>
+where
+    C: SumCommitment,
+    P: ExclusiveAllotmentProof<C>,
+{
+    // None when no leaf carries this user ID
+    pub fn prove_user(&self, user_id: impl AsRef<[u8]>) -> Option<UserProof<C, P>> {
+        let positions = self.user_positions.get(user_id.as_ref())?;
+        Some(UserProof::new(
+            positions
+                .iter()
+                .map(|&position| self.prove(position))
+                .collect(),
+        ))
+    }
+}
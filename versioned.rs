@@ -0,0 +1,340 @@
+use std::fmt;
+use std::sync::Arc;
+
+use sha2::{Digest as _, Sha256};
+
+const LEAF_DOMAIN: &[u8] = b"mimi-jmt-leaf-v1";
+const INTERNAL_DOMAIN: &[u8] = b"mimi-jmt-internal-v1";
+
+// Hash of an empty subtree
+pub const PLACEHOLDER_HASH: [u8; 32] = [0u8; 32];
+
+// Keys are hashed to 256-bit paths; a proof can't be deeper than that
+const KEY_BITS: usize = 256;
+
+// A key hash and the value stored under it
+pub type KeyedValue<'a> = ([u8; 32], &'a [u8]);
+
+pub fn key_hash(key: &[u8]) -> [u8; 32] {
+    Sha256::digest(key).into()
+}
+
+fn value_hash(value: &[u8]) -> [u8; 32] {
+    Sha256::digest(value).into()
+}
+
+fn leaf_hash(key: &[u8; 32], value: &[u8; 32]) -> [u8; 32] {
+    Sha256::new()
+        .chain_update(LEAF_DOMAIN)
+        .chain_update(key)
+        .chain_update(value)
+        .finalize()
+        .into()
+}
+
+fn internal_hash(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    Sha256::new()
+        .chain_update(INTERNAL_DOMAIN)
+        .chain_update(left)
+        .chain_update(right)
+        .finalize()
+        .into()
+}
+
+// Bit `index` of the path, most significant first
+fn bit(key: &[u8; 32], index: usize) -> bool {
+    (key[index / 8] >> (7 - index % 8)) & 1 == 1
+}
+
+// Nodes are immutable and shared between versions; an update copies only the path from
+// the root to the changed leaf
+#[derive(Debug)]
+enum Node {
+    Empty,
+    Leaf {
+        key: [u8; 32],
+        value: Vec<u8>,
+        hash: [u8; 32],
+    },
+    Internal {
+        left: Arc<Node>,
+        right: Arc<Node>,
+        hash: [u8; 32],
+    },
+}
+
+impl Node {
+    fn leaf(key: [u8; 32], value: Vec<u8>) -> Arc<Node> {
+        let hash = leaf_hash(&key, &value_hash(&value));
+        Arc::new(Node::Leaf { key, value, hash })
+    }
+
+    fn internal(left: Arc<Node>, right: Arc<Node>) -> Arc<Node> {
+        // Keep the shape canonical: a subtree holding a single leaf is that leaf, so the
+        // root depends only on the entries, never on the order they were written in
+        match (&*left, &*right) {
+            (Node::Empty, Node::Empty) => return Arc::new(Node::Empty),
+            (Node::Empty, Node::Leaf { .. }) => return right,
+            (Node::Leaf { .. }, Node::Empty) => return left,
+            _ => {}
+        }
+        let hash = internal_hash(&left.hash(), &right.hash());
+        Arc::new(Node::Internal { left, right, hash })
+    }
+
+    fn hash(&self) -> [u8; 32] {
+        match self {
+            Node::Empty => PLACEHOLDER_HASH,
+            Node::Leaf { hash, .. } | Node::Internal { hash, .. } => *hash,
+        }
+    }
+}
+
+fn insert(node: &Arc<Node>, depth: usize, key: [u8; 32], value: Vec<u8>) -> Arc<Node> {
+    match &**node {
+        Node::Empty => Node::leaf(key, value),
+        Node::Leaf { key: existing, .. } if *existing == key => Node::leaf(key, value),
+        Node::Leaf { key: existing, .. } => {
+            // Push the existing leaf down until the two paths part
+            let existing = *existing;
+            let (mut left, mut right) = (Arc::new(Node::Empty), Arc::new(Node::Empty));
+            if bit(&existing, depth) {
+                right = node.clone();
+            } else {
+                left = node.clone();
+            }
+            let (left, right) = match bit(&key, depth) {
+                true => (left, insert(&right, depth + 1, key, value)),
+                false => (insert(&left, depth + 1, key, value), right),
+            };
+            Node::internal(left, right)
+        }
+        Node::Internal { left, right, .. } => {
+            if bit(&key, depth) {
+                Node::internal(left.clone(), insert(right, depth + 1, key, value))
+            } else {
+                Node::internal(insert(left, depth + 1, key, value), right.clone())
+            }
+        }
+    }
+}
+
+fn remove(node: &Arc<Node>, depth: usize, key: &[u8; 32]) -> Arc<Node> {
+    match &**node {
+        Node::Leaf { key: existing, .. } if existing == key => Arc::new(Node::Empty),
+        Node::Empty | Node::Leaf { .. } => node.clone(),
+        Node::Internal { left, right, .. } => {
+            if bit(key, depth) {
+                Node::internal(left.clone(), remove(right, depth + 1, key))
+            } else {
+                Node::internal(remove(left, depth + 1, key), right.clone())
+            }
+        }
+    }
+}
+
+// Define the VersionedDictionary struct, a sparse Merkle tree over hashed keys in the
+// style of Jellyfish: a subtree with one entry is stored as that leaf, empty subtrees
+// hash to a placeholder, and every committed batch is a new version whose root, reads
+// and proofs stay available until pruned. Balances can be written as they change and
+// an epoch sealed from `entries` at the version it closes on.
+#[derive(Debug, Default)]
+pub struct VersionedDictionary {
+    // Root of each version; None once pruned
+    versions: Vec<Option<Arc<Node>>>,
+}
+
+impl VersionedDictionary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn latest_version(&self) -> Option<u64> {
+        self.versions.len().checked_sub(1).map(|version| version as u64)
+    }
+
+    // Applies a batch on top of the latest version (`None` deletes) and returns the new
+    // version. Updates apply in order, so the last write to a key wins.
+    pub fn commit<K, I>(&mut self, updates: I) -> u64
+    where
+        K: AsRef<[u8]>,
+        I: IntoIterator<Item = (K, Option<Vec<u8>>)>,
+    {
+        let mut root = match self.versions.last() {
+            Some(Some(root)) => root.clone(),
+            _ => Arc::new(Node::Empty),
+        };
+        for (key, value) in updates {
+            let key = key_hash(key.as_ref());
+            root = match value {
+                Some(value) => insert(&root, 0, key, value),
+                None => remove(&root, 0, &key),
+            };
+        }
+        self.versions.push(Some(root));
+        self.versions.len() as u64 - 1
+    }
+
+    // Drops every version before `version`; nodes still shared with later versions stay
+    pub fn prune_before(&mut self, version: u64) {
+        let end = (version as usize).min(self.versions.len().saturating_sub(1));
+        for root in &mut self.versions[..end] {
+            *root = None;
+        }
+    }
+
+    fn root_node(&self, version: u64) -> Result<&Arc<Node>, DictionaryError> {
+        match self.versions.get(version as usize) {
+            Some(Some(root)) => Ok(root),
+            Some(None) => Err(DictionaryError::Pruned(version)),
+            None => Err(DictionaryError::UnknownVersion(version)),
+        }
+    }
+
+    pub fn root(&self, version: u64) -> Result<[u8; 32], DictionaryError> {
+        Ok(self.root_node(version)?.hash())
+    }
+
+    pub fn get(&self, key: &[u8], version: u64) -> Result<Option<&[u8]>, DictionaryError> {
+        let key = key_hash(key);
+        let mut node = self.root_node(version)?;
+        let mut depth = 0;
+        loop {
+            match &**node {
+                Node::Leaf {
+                    key: existing,
+                    value,
+                    ..
+                } if *existing == key => return Ok(Some(value)),
+                Node::Empty | Node::Leaf { .. } => return Ok(None),
+                Node::Internal { left, right, .. } => {
+                    node = if bit(&key, depth) { right } else { left };
+                    depth += 1;
+                }
+            }
+        }
+    }
+
+    // Inclusion proof for present keys, exclusion proof otherwise
+    pub fn prove(&self, key: &[u8], version: u64) -> Result<SparseProof, DictionaryError> {
+        let key = key_hash(key);
+        let mut node = self.root_node(version)?;
+        let mut siblings = Vec::new();
+        loop {
+            match &**node {
+                Node::Empty => return Ok(SparseProof { leaf: None, siblings }),
+                Node::Leaf {
+                    key: existing,
+                    value,
+                    ..
+                } => {
+                    return Ok(SparseProof {
+                        leaf: Some((*existing, value_hash(value))),
+                        siblings,
+                    })
+                }
+                Node::Internal { left, right, .. } => {
+                    let (next, sibling) = match bit(&key, siblings.len()) {
+                        true => (right, left),
+                        false => (left, right),
+                    };
+                    siblings.push(sibling.hash());
+                    node = next;
+                }
+            }
+        }
+    }
+
+    // Every (key hash, value) at `version` in key order, e.g. to seal an epoch's tree
+    pub fn entries(&self, version: u64) -> Result<Vec<KeyedValue<'_>>, DictionaryError> {
+        let mut entries = Vec::new();
+        let mut stack = vec![self.root_node(version)?];
+        while let Some(node) = stack.pop() {
+            match &**node {
+                Node::Empty => {}
+                Node::Leaf { key, value, .. } => entries.push((*key, value.as_slice())),
+                Node::Internal { left, right, .. } => {
+                    stack.push(right);
+                    stack.push(left);
+                }
+            }
+        }
+        Ok(entries)
+    }
+}
+
+// Define the SparseProof struct, the leaf (or empty subtree) a key's path ends at and the
+// siblings on the way down from the root. A leaf for another key proves absence when it
+// sits where the key's path would end.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SparseProof {
+    // (key hash, value hash) of the leaf the path ends at
+    pub leaf: Option<([u8; 32], [u8; 32])>,
+    pub siblings: Vec<[u8; 32]>,
+}
+
+impl SparseProof {
+    // Checks that `key` holds `value` under `root`, or is absent when `value` is None
+    pub fn verify(
+        &self,
+        root: &[u8; 32],
+        key: &[u8],
+        value: Option<&[u8]>,
+    ) -> Result<(), DictionaryError> {
+        if self.siblings.len() > KEY_BITS {
+            return Err(DictionaryError::ProofMismatch);
+        }
+        let key = key_hash(key);
+        let holds = match (&self.leaf, value) {
+            (Some((leaf_key, leaf_value)), Some(value)) => {
+                *leaf_key == key && *leaf_value == value_hash(value)
+            }
+            // Another key's leaf on this path: it shares the path so far but not the key
+            (Some((leaf_key, _)), None) => {
+                *leaf_key != key
+                    && (0..self.siblings.len())
+                        .all(|index| bit(leaf_key, index) == bit(&key, index))
+            }
+            (None, value) => value.is_none(),
+        };
+        if !holds {
+            return Err(DictionaryError::ProofMismatch);
+        }
+        let mut current = match &self.leaf {
+            Some((leaf_key, leaf_value)) => leaf_hash(leaf_key, leaf_value),
+            None => PLACEHOLDER_HASH,
+        };
+        for (depth, sibling) in self.siblings.iter().enumerate().rev() {
+            current = if bit(&key, depth) {
+                internal_hash(sibling, &current)
+            } else {
+                internal_hash(&current, sibling)
+            };
+        }
+        if current != *root {
+            return Err(DictionaryError::ProofMismatch);
+        }
+        Ok(())
+    }
+}
+
+// Define the DictionaryError enum for versions that can't be read and proofs that don't
+// hold
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DictionaryError {
+    UnknownVersion(u64),
+    Pruned(u64),
+    ProofMismatch,
+}
+
+impl fmt::Display for DictionaryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DictionaryError::UnknownVersion(version) => write!(f, "no version {}", version),
+            DictionaryError::Pruned(version) => write!(f, "version {} has been pruned", version),
+            DictionaryError::ProofMismatch => write!(f, "proof does not match the root"),
+        }
+    }
+}
+
+impl std::error::Error for DictionaryError {}
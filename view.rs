@@ -0,0 +1,239 @@
+use std::fmt;
+
+use ed25519_dalek::VerifyingKey;
+
+use crate::bundle::SignedRoot;
+use crate::root_log::{verify_chain, verify_continuation, LogEntry, LogError};
+use crate::SumCommitment;
+
+// Define the Equivocation struct, two validly signed roots for one epoch. Either
+// signature alone is harmless; together they prove the operator showed different views.
+#[derive(Debug, Clone)]
+pub struct Equivocation<C: SumCommitment> {
+    pub epoch: u64,
+    pub first: SignedRoot<C>,
+    pub second: SignedRoot<C>,
+}
+
+impl<C: SumCommitment> Equivocation<C> {
+    // Lets a third party check the evidence without trusting whoever reported it
+    pub fn verify(&self, operator_key: &VerifyingKey) -> bool {
+        self.first.epoch == self.epoch
+            && self.second.epoch == self.epoch
+            && !self.first.root.matches(self.second.root.node())
+            && self.first.verify(operator_key).is_ok()
+            && self.second.verify(operator_key).is_ok()
+    }
+}
+
+// Define the ViewChecker struct, the client-side record of every root a user was shown
+#[derive(Debug, Clone)]
+pub struct ViewChecker<C: SumCommitment> {
+    operator_key: VerifyingKey,
+    seen: Vec<SignedRoot<C>>,
+    // Log entries checked so far; later segments must extend the last one
+    log: Vec<LogEntry<C>>,
+    equivocations: Vec<Equivocation<C>>,
+}
+
+impl<C: SumCommitment> ViewChecker<C> {
+    pub fn new(operator_key: VerifyingKey) -> Self {
+        ViewChecker {
+            operator_key,
+            seen: Vec::new(),
+            log: Vec::new(),
+            equivocations: Vec::new(),
+        }
+    }
+
+    // Resumes from a log entry checked in an earlier session
+    pub fn with_log_tip(mut self, entry: LogEntry<C>) -> Self {
+        self.log.push(entry);
+        self
+    }
+
+    pub fn log_tip(&self) -> Option<&LogEntry<C>> {
+        self.log.last()
+    }
+
+    // Records a root served to the user; a second, different root for an epoch
+    // already seen is kept as evidence and reported
+    pub fn observe(&mut self, signed_root: SignedRoot<C>) -> Result<(), ViewError> {
+        signed_root
+            .verify(&self.operator_key)
+            .map_err(|_| ViewError::BadSignature {
+                epoch: signed_root.epoch,
+            })?;
+
+        if let Some(previous) = self.root_for(signed_root.epoch) {
+            if previous.root.matches(signed_root.root.node()) {
+                return Ok(());
+            }
+            let epoch = signed_root.epoch;
+            self.equivocations.push(Equivocation {
+                epoch,
+                first: previous.clone(),
+                second: signed_root,
+            });
+            return Err(ViewError::Equivocation { epoch });
+        }
+
+        if let Some(entry) = self.log_entry_for(signed_root.epoch) {
+            if !entry.root.matches(signed_root.root.node()) {
+                return Err(ViewError::LogMismatch {
+                    epoch: signed_root.epoch,
+                });
+            }
+        }
+        self.seen.push(signed_root);
+        Ok(())
+    }
+
+    // Checks a log segment from the operator: it must extend the last segment checked
+    // (or start at genesis on first use) and agree with every root already seen
+    pub fn check_log(&mut self, entries: &[LogEntry<C>]) -> Result<(), ViewError> {
+        match self.log.last() {
+            Some(tip) => verify_continuation(tip, entries)?,
+            None => verify_chain(entries)?,
+        };
+
+        for entry in entries {
+            if let Some(seen) = self.root_for(entry.epoch) {
+                if !seen.root.matches(entry.root.node()) {
+                    return Err(ViewError::LogMismatch { epoch: entry.epoch });
+                }
+            }
+        }
+        self.log.extend_from_slice(entries);
+        Ok(())
+    }
+
+    pub fn root_for(&self, epoch: u64) -> Option<&SignedRoot<C>> {
+        self.seen.iter().find(|seen| seen.epoch == epoch)
+    }
+
+    pub fn seen(&self) -> &[SignedRoot<C>] {
+        &self.seen
+    }
+
+    pub fn equivocations(&self) -> &[Equivocation<C>] {
+        &self.equivocations
+    }
+
+    pub fn is_consistent(&self) -> bool {
+        self.equivocations.is_empty()
+    }
+
+    fn log_entry_for(&self, epoch: u64) -> Option<&LogEntry<C>> {
+        self.log.iter().find(|entry| entry.epoch == epoch)
+    }
+}
+
+// Define the ViewError enum for roots that contradict what the user saw before
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ViewError {
+    BadSignature { epoch: u64 },
+    Equivocation { epoch: u64 },
+    LogMismatch { epoch: u64 },
+    Log(LogError),
+}
+
+impl fmt::Display for ViewError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ViewError::BadSignature { epoch } => {
+                write!(f, "root for epoch {} is not signed by the operator", epoch)
+            }
+            ViewError::Equivocation { epoch } => {
+                write!(f, "operator signed two different roots for epoch {}", epoch)
+            }
+            ViewError::LogMismatch { epoch } => write!(
+                f,
+                "root log disagrees with the root seen for epoch {}",
+                epoch
+            ),
+            ViewError::Log(err) => write!(f, "inconsistent root log: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ViewError {}
+
+impl From<LogError> for ViewError {
+    fn from(err: LogError) -> Self {
+        ViewError::Log(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ed25519_dalek::SigningKey;
+
+    use super::*;
+    use crate::root_log::RootLog;
+    use crate::{MimiSumCommitment, Root};
+
+    fn root(amount: u64) -> Root<MimiSumCommitment> {
+        Root::from_node(MimiSumCommitment::from_parts(amount, [3; 32].into()))
+    }
+
+    fn operator() -> SigningKey {
+        SigningKey::from_bytes(&[1; 32])
+    }
+
+    #[test]
+    fn two_roots_for_one_epoch_are_kept_as_evidence() {
+        let mut checker = ViewChecker::new(operator().verifying_key());
+        checker
+            .observe(SignedRoot::sign(1, root(100), &operator()))
+            .unwrap();
+        checker
+            .observe(SignedRoot::sign(1, root(100), &operator()))
+            .unwrap();
+        assert!(checker.is_consistent());
+
+        let impostor = SigningKey::from_bytes(&[2; 32]);
+        assert_eq!(
+            checker.observe(SignedRoot::sign(1, root(200), &impostor)),
+            Err(ViewError::BadSignature { epoch: 1 })
+        );
+        assert!(checker.is_consistent());
+
+        assert_eq!(
+            checker.observe(SignedRoot::sign(1, root(200), &operator())),
+            Err(ViewError::Equivocation { epoch: 1 })
+        );
+        let evidence = &checker.equivocations()[0];
+        assert!(evidence.verify(&operator().verifying_key()));
+        assert!(!evidence.verify(&impostor.verifying_key()));
+    }
+
+    #[test]
+    fn logs_must_extend_and_agree_with_what_was_seen() {
+        let mut log = RootLog::new();
+        for epoch in 1..=3 {
+            log.append(epoch, root(epoch * 100)).unwrap();
+        }
+        let mut checker = ViewChecker::new(operator().verifying_key());
+        checker
+            .observe(SignedRoot::sign(2, root(999), &operator()))
+            .unwrap();
+        assert_eq!(
+            checker.check_log(log.entries()),
+            Err(ViewError::LogMismatch { epoch: 2 })
+        );
+
+        let mut checker = ViewChecker::new(operator().verifying_key());
+        checker.check_log(&log.entries()[..2]).unwrap();
+        assert!(matches!(
+            checker.check_log(&log.entries()[1..]),
+            Err(ViewError::Log(LogError::BrokenLink { .. }))
+        ));
+        checker.check_log(&log.entries()[2..]).unwrap();
+        assert_eq!(checker.log_tip().unwrap().epoch, 3);
+        assert_eq!(
+            checker.observe(SignedRoot::sign(3, root(999), &operator())),
+            Err(ViewError::LogMismatch { epoch: 3 })
+        );
+    }
+}
@@ -0,0 +1,274 @@
+use std::fmt;
+
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+
+use crate::bundle::SignedRoot;
+use crate::encoding::{ByteReader, COMMITMENT_LEN};
+use crate::{ParseError, Root, SumCommitment};
+
+const WITNESS_DOMAIN: &[u8] = b"mimi-root-witness-v1";
+
+// Encoded size of a RootWitness: epoch, root, witness key, signature
+pub const WITNESS_LEN: usize = 8 + COMMITMENT_LEN + 32 + 64;
+
+// Define the RootWitness struct, a third party's countersignature on a root it observed
+#[derive(Debug, Clone)]
+pub struct RootWitness<C: SumCommitment> {
+    pub epoch: u64,
+    pub root: Root<C>,
+    pub witness: VerifyingKey,
+    pub signature: Signature,
+}
+
+impl<C: SumCommitment> RootWitness<C> {
+    // Countersigns only roots that carry a valid operator signature
+    pub fn countersign(
+        signed_root: &SignedRoot<C>,
+        operator_key: &VerifyingKey,
+        witness_key: &SigningKey,
+    ) -> Result<Self, WitnessError> {
+        signed_root
+            .verify(operator_key)
+            .map_err(|_| WitnessError::BadOperatorSignature)?;
+        let payload = witness_payload(signed_root.epoch, &signed_root.root);
+        Ok(RootWitness {
+            epoch: signed_root.epoch,
+            root: signed_root.root.clone(),
+            witness: witness_key.verifying_key(),
+            signature: witness_key.sign(&payload),
+        })
+    }
+
+    pub fn verify(&self) -> Result<(), WitnessError> {
+        self.witness
+            .verify_strict(&witness_payload(self.epoch, &self.root), &self.signature)
+            .map_err(|_| WitnessError::BadWitnessSignature(self.witness.to_bytes()))
+    }
+
+    pub fn to_bytes(&self) -> [u8; WITNESS_LEN] {
+        let mut out = [0u8; WITNESS_LEN];
+        out[..8].copy_from_slice(&self.epoch.to_le_bytes());
+        out[8..8 + COMMITMENT_LEN].copy_from_slice(&self.root.to_bytes());
+        out[8 + COMMITMENT_LEN..8 + COMMITMENT_LEN + 32].copy_from_slice(self.witness.as_bytes());
+        out[8 + COMMITMENT_LEN + 32..].copy_from_slice(&self.signature.to_bytes());
+        out
+    }
+}
+
+impl<C: SumCommitment> TryFrom<&[u8]> for RootWitness<C> {
+    type Error = WitnessError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        let mut reader = ByteReader::new(bytes);
+        let epoch = u64::from_le_bytes(reader.take_array::<8>()?);
+        let root = Root::try_from(reader.take(COMMITMENT_LEN)?)?;
+        let witness = VerifyingKey::from_bytes(&reader.take_array::<32>()?)
+            .map_err(|_| WitnessError::InvalidKey)?;
+        let signature = Signature::from_bytes(&reader.take_array::<64>()?);
+        reader.finish()?;
+        Ok(RootWitness {
+            epoch,
+            root,
+            witness,
+            signature,
+        })
+    }
+}
+
+fn witness_payload<C: SumCommitment>(epoch: u64, root: &Root<C>) -> Vec<u8> {
+    let mut payload = WITNESS_DOMAIN.to_vec();
+    payload.extend_from_slice(&epoch.to_le_bytes());
+    payload.extend_from_slice(&root.to_bytes());
+    payload
+}
+
+// Concatenated records as mirrored between witnesses
+pub fn encode_witnesses<C: SumCommitment>(witnesses: &[RootWitness<C>]) -> Vec<u8> {
+    witnesses
+        .iter()
+        .flat_map(|witness| witness.to_bytes())
+        .collect()
+}
+
+pub fn decode_witnesses<C: SumCommitment>(
+    bytes: &[u8],
+) -> Result<Vec<RootWitness<C>>, WitnessError> {
+    // A short final chunk surfaces as a truncation error
+    bytes
+        .chunks(WITNESS_LEN)
+        .map(RootWitness::try_from)
+        .collect()
+}
+
+// Define the WitnessPolicy struct, the M trusted witnesses and the N required to agree
+#[derive(Debug, Clone)]
+pub struct WitnessPolicy {
+    pub witnesses: Vec<VerifyingKey>,
+    pub threshold: usize,
+}
+
+impl WitnessPolicy {
+    pub fn new(witnesses: Vec<VerifyingKey>, threshold: usize) -> Self {
+        WitnessPolicy {
+            witnesses,
+            threshold,
+        }
+    }
+
+    // Counts distinct trusted witnesses vouching for `root` at `epoch`. A trusted witness
+    // having signed a different root for the same epoch is reported as a split view.
+    pub fn verify<C: SumCommitment>(
+        &self,
+        epoch: u64,
+        root: &Root<C>,
+        records: &[RootWitness<C>],
+    ) -> Result<usize, WitnessError> {
+        let mut agreeing: Vec<VerifyingKey> = Vec::new();
+        for record in records {
+            if record.epoch != epoch || !self.witnesses.contains(&record.witness) {
+                continue;
+            }
+            record.verify()?;
+            if !record.root.matches(root.node()) {
+                return Err(WitnessError::SplitView {
+                    epoch,
+                    witness: record.witness.to_bytes(),
+                });
+            }
+            if !agreeing.contains(&record.witness) {
+                agreeing.push(record.witness);
+            }
+        }
+
+        if agreeing.len() < self.threshold {
+            return Err(WitnessError::BelowThreshold {
+                found: agreeing.len(),
+                required: self.threshold,
+            });
+        }
+        Ok(agreeing.len())
+    }
+}
+
+// Define the WitnessError enum for witness records and sets that fail verification
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WitnessError {
+    BadOperatorSignature,
+    BadWitnessSignature([u8; 32]),
+    InvalidKey,
+    SplitView { epoch: u64, witness: [u8; 32] },
+    BelowThreshold { found: usize, required: usize },
+    Parse(ParseError),
+}
+
+impl fmt::Display for WitnessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WitnessError::BadOperatorSignature => {
+                write!(f, "operator signature on the root does not verify")
+            }
+            WitnessError::BadWitnessSignature(key) => write!(
+                f,
+                "witness signature by {} does not verify",
+                hex::encode(key)
+            ),
+            WitnessError::InvalidKey => write!(f, "witness key is not a valid ed25519 point"),
+            WitnessError::SplitView { epoch, witness } => write!(
+                f,
+                "witness {} saw a different root for epoch {}",
+                hex::encode(witness),
+                epoch
+            ),
+            WitnessError::BelowThreshold { found, required } => write!(
+                f,
+                "{} trusted witnesses agree, {} required",
+                found, required
+            ),
+            WitnessError::Parse(err) => write!(f, "malformed witness record: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for WitnessError {}
+
+impl From<ParseError> for WitnessError {
+    fn from(err: ParseError) -> Self {
+        WitnessError::Parse(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MimiSumCommitment;
+
+    fn signed(epoch: u64, amount: u64) -> SignedRoot<MimiSumCommitment> {
+        let root = Root::from_node(MimiSumCommitment::from_parts(amount, [7; 32].into()));
+        SignedRoot::sign(epoch, root, &operator())
+    }
+
+    fn operator() -> SigningKey {
+        SigningKey::from_bytes(&[1; 32])
+    }
+
+    fn witnesses() -> [SigningKey; 3] {
+        [2, 3, 4].map(|seed| SigningKey::from_bytes(&[seed; 32]))
+    }
+
+    #[test]
+    fn policies_count_distinct_trusted_witnesses() {
+        let signed_root = signed(5, 600);
+        let operator_key = operator().verifying_key();
+        let [first, second, untrusted] = witnesses();
+        assert_eq!(
+            RootWitness::countersign(&signed_root, &first.verifying_key(), &first).unwrap_err(),
+            WitnessError::BadOperatorSignature
+        );
+        let records: Vec<_> = [&first, &first, &untrusted]
+            .into_iter()
+            .map(|key| RootWitness::countersign(&signed_root, &operator_key, key).unwrap())
+            .collect();
+        let records = decode_witnesses(&encode_witnesses(&records)).unwrap();
+
+        let policy = WitnessPolicy::new(vec![first.verifying_key(), second.verifying_key()], 2);
+        assert_eq!(
+            policy.verify(5, &signed_root.root, &records),
+            Err(WitnessError::BelowThreshold {
+                found: 1,
+                required: 2
+            })
+        );
+        let mut records = records;
+        records.push(RootWitness::countersign(&signed_root, &operator_key, &second).unwrap());
+        assert_eq!(policy.verify(5, &signed_root.root, &records), Ok(2));
+    }
+
+    #[test]
+    fn conflicting_or_forged_records_are_rejected() {
+        let operator_key = operator().verifying_key();
+        let [first, second, _] = witnesses();
+        let policy = WitnessPolicy::new(vec![first.verifying_key(), second.verifying_key()], 1);
+        let honest = signed(5, 600);
+        let other = RootWitness::countersign(&signed(5, 900), &operator_key, &second).unwrap();
+        assert_eq!(
+            policy.verify(5, &honest.root, &[other]),
+            Err(WitnessError::SplitView {
+                epoch: 5,
+                witness: second.verifying_key().to_bytes()
+            })
+        );
+
+        let mut forged = RootWitness::countersign(&honest, &operator_key, &first).unwrap();
+        forged.epoch = 6;
+        assert_eq!(
+            policy.verify(6, &honest.root, &[forged]),
+            Err(WitnessError::BadWitnessSignature(
+                first.verifying_key().to_bytes()
+            ))
+        );
+        assert!(matches!(
+            decode_witnesses::<MimiSumCommitment>(&[0; WITNESS_LEN - 1]),
+            Err(WitnessError::Parse(_))
+        ));
+    }
+}